@@ -0,0 +1,33 @@
+/******************************************************************************/
+/*                                                                            */
+/* SPDX-License-Identifier: MIT                                               */
+/* Copyright (c) [2023] Serokell <hi@serokell.io>                             */
+/*                                                                            */
+/******************************************************************************/
+
+//! Decodes arbitrary bytes as binary Micheline, then checks that whatever was
+//! accepted survives binary -> Micheline -> text -> Micheline -> binary.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mir::ast::Micheline;
+use mir::parser::Parser;
+use typed_arena::Arena;
+
+fuzz_target!(|data: &[u8]| {
+    let arena = Arena::new();
+    let Ok(mich) = Micheline::decode_raw(&arena, data) else {
+        return;
+    };
+    // NB: the input isn't necessarily in the canonical form, e.g. `-0` is
+    // accepted, hence comparing Micheline, not bytes.
+    let reencoded = mich.encode();
+    assert_eq!(Micheline::decode_raw(&arena, &reencoded), Ok(mich.clone()));
+    let text = mich.to_string();
+    let parser = Parser::new();
+    let parsed = parser
+        .parse(&text)
+        .unwrap_or_else(|e| panic!("failed to parse {text}: {e}"));
+    assert_eq!(parsed, mich);
+});
@@ -0,0 +1,31 @@
+/******************************************************************************/
+/*                                                                            */
+/* SPDX-License-Identifier: MIT                                               */
+/* Copyright (c) [2023] Serokell <hi@serokell.io>                             */
+/*                                                                            */
+/******************************************************************************/
+
+//! Parses arbitrary strings as Michelson, then checks that whatever was
+//! accepted survives text -> Micheline -> binary -> Micheline -> text.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mir::ast::Micheline;
+use mir::parser::Parser;
+use typed_arena::Arena;
+
+fuzz_target!(|src: &str| {
+    let parser = Parser::new();
+    let Ok(mich) = parser.parse(src) else {
+        return;
+    };
+    let arena = Arena::new();
+    let decoded = Micheline::decode_raw(&arena, &mich.encode()).unwrap();
+    assert_eq!(decoded, mich);
+    let text = mich.to_string();
+    let reparsed = parser
+        .parse(&text)
+        .unwrap_or_else(|e| panic!("failed to parse {text}: {e}"));
+    assert_eq!(reparsed, mich);
+});
@@ -0,0 +1,65 @@
+/******************************************************************************/
+/*                                                                            */
+/* SPDX-License-Identifier: MIT                                               */
+/* Copyright (c) [2023] Serokell <hi@serokell.io>                             */
+/*                                                                            */
+/******************************************************************************/
+
+//! Treats arbitrary bytes as a `PACK`ed value, and tries to `UNPACK` them as
+//! each of a set of types. Whenever that succeeds, packing the result and
+//! unpacking it again must give the same value.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mir::ast::{IntoMicheline, Micheline};
+use mir::context::Ctx;
+use mir::parser::Parser;
+use typed_arena::Arena;
+
+/// Types covering every pushable type constructor supported by MIR.
+const TYPES: &[&str] = &[
+    "int",
+    "nat",
+    "mutez",
+    "timestamp",
+    "string",
+    "bytes",
+    "unit",
+    "bool",
+    "address",
+    "chain_id",
+    "key",
+    "key_hash",
+    "signature",
+    "bls12_381_fr",
+    "bls12_381_g1",
+    "bls12_381_g2",
+    "never",
+    "pair int (pair nat string)",
+    "or (option bytes) (list int)",
+    "set (pair nat address)",
+    "map string (or unit key_hash)",
+    "lambda int int",
+];
+
+fuzz_target!(|data: &[u8]| {
+    let arena = Arena::new();
+    let Ok(mich) = Micheline::decode_packed(&arena, data) else {
+        return;
+    };
+    let parser = Parser::new();
+    for ty in TYPES {
+        let ty = parser.parse(ty).unwrap();
+        let mut ctx = Ctx::default();
+        let Ok(value) = mich.typecheck_value(&mut ctx, &ty) else {
+            continue;
+        };
+        let repacked = value
+            .clone()
+            .into_micheline_optimized_legacy(&arena)
+            .encode_for_pack();
+        let remich = Micheline::decode_packed(&arena, &repacked).unwrap();
+        assert_eq!(remich.typecheck_value(&mut Ctx::default(), &ty), Ok(value));
+    }
+});
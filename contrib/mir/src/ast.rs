@@ -8,10 +8,12 @@
 //! AST definitions for raw ([Micheline]) and typed representations of
 //! Michelson.
 
+pub mod annotated_type;
 pub mod annotations;
 pub mod big_map;
 pub mod byte_repr_trait;
 mod comparable;
+pub mod event;
 pub mod micheline;
 pub mod michelson_address;
 pub mod michelson_key;
@@ -36,9 +38,11 @@ use typed_arena::Arena;
 
 use crate::{bls, lexer::Prim};
 
+pub use annotated_type::AnnotatedType;
 pub use annotations::{Annotation, Annotations, FieldAnnotation, NO_ANNS};
 pub use big_map::BigMap;
 pub use byte_repr_trait::{ByteReprError, ByteReprTrait};
+pub use event::{Event, EventLog};
 pub use micheline::IntoMicheline;
 pub use michelson_address::*;
 pub use michelson_key::Key;
@@ -65,7 +69,8 @@ pub struct Ticket<'a> {
 /// Representation for a Michelson type. Used primarily in the typechecker. Note
 /// this representation doesn't store annotations, as annotations are mostly
 /// deprecated and ingored. For entrypoints, see
-/// [crate::ast::michelson_address::entrypoint].
+/// [crate::ast::michelson_address::entrypoint]. If annotations need to be
+/// preserved, see [AnnotatedType].
 ///
 /// The names of the variants correspond to the names of Michelson types, but
 /// snake_case is converted to PascalCase.
@@ -551,6 +556,8 @@ pub enum Instruction<'a> {
     Loop(Vec<Self>),
     Push(TypedValue<'a>),
     Swap,
+    Cast,
+    Rename,
     Failwith(Type),
     Never,
     Unit,
@@ -633,6 +640,12 @@ pub enum Instruction<'a> {
         arg_ty: Or<Type, Micheline<'a>>,
     },
     CreateContract(Rc<ContractScript<'a>>, &'a Micheline<'a>),
+    /// `CREATE_CONTRACT` with the deprecated pre-Babylon stack layout, i.e.
+    /// with manager, spendable and delegatable arguments. Only produced by the
+    /// typechecker in legacy mode.
+    CreateContractLegacy(Rc<ContractScript<'a>>, &'a Micheline<'a>),
+    /// Deprecated, only produced by the typechecker in legacy mode.
+    StepsToQuota,
     Map(overloads::Map, Vec<Self>),
 }
 
@@ -719,6 +732,51 @@ pub mod test_strategies {
         })
     }
 
+    /// Generates arbitrary, not necessarily well-typed, [Micheline]. Child
+    /// nodes and annotations are leaked to get a `'static` lifetime, which is
+    /// fine in tests.
+    pub fn micheline() -> impl Strategy<Value = Micheline<'static>> {
+        use strum::EnumCount;
+        let ann = prop_oneof![
+            "[_0-9a-zA-Z][_0-9a-zA-Z.%@]{0,4}"
+                .prop_map(|s| Annotation::Field(std::borrow::Cow::Owned(s))),
+            "[_0-9a-zA-Z][_0-9a-zA-Z.%@]{0,4}"
+                .prop_map(|s| Annotation::Variable(std::borrow::Cow::Owned(s))),
+            "[_0-9a-zA-Z][_0-9a-zA-Z.%@]{0,4}"
+                .prop_map(|s| Annotation::Type(std::borrow::Cow::Owned(s))),
+            prop_oneof![Just("@%"), Just("@%%"), Just("%@")]
+                .prop_map(|s| Annotation::Special(std::borrow::Cow::Borrowed(s))),
+        ];
+        // SAFETY: Prim is repr(u8), and the value is within bounds.
+        let prim =
+            (0..Prim::COUNT as u8).prop_map(|p| unsafe { std::mem::transmute::<u8, Prim>(p) });
+        let leaf = prop_oneof![
+            any::<i128>().prop_map(Micheline::from),
+            "[ -~\n]{0,8}".prop_map(Micheline::String),
+            prop::collection::vec(any::<u8>(), 0..=4).prop_map(Micheline::Bytes),
+            (prim.clone(), prop::collection::vec(ann.clone(), 0..=2))
+                .prop_map(|(p, anns)| Micheline::App(p, &[], anns.into_iter().collect())),
+        ];
+        leaf.prop_recursive(4, 32, 4, move |inner| {
+            fn leak(v: Vec<Micheline<'static>>) -> &'static [Micheline<'static>] {
+                Box::leak(v.into_boxed_slice())
+            }
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..=4).prop_map(|v| Micheline::Seq(leak(v))),
+                (
+                    prim.clone(),
+                    prop::collection::vec(inner, 1..=4),
+                    prop::collection::vec(ann.clone(), 0..=2)
+                )
+                    .prop_map(|(p, args, anns)| Micheline::App(
+                        p,
+                        leak(args),
+                        anns.into_iter().collect()
+                    )),
+            ]
+        })
+    }
+
     pub fn typed_value_by_type(t: &Type) -> impl Strategy<Value = TypedValue<'static>> {
         use Type as T;
         use TypedValue as V;
@@ -726,14 +784,12 @@ pub mod test_strategies {
         match t {
             // TODO: https://gitlab.com/tezos/tezos/-/issues/6755
             // Sometimes generate really large numbers (should take at least several `u64`s)
-            T::Int => (-100..100i128).prop_map(V::int).boxed(),
-            T::Nat => (0..100u64).prop_map(V::nat).boxed(),
+            T::Int => prop_oneof![(-100..100i128), any::<i128>()].prop_map(V::int).boxed(),
+            T::Nat => prop_oneof![(0..100u64), any::<u64>()].prop_map(V::nat).boxed(),
             T::Mutez => (0..100i64).prop_map(V::Mutez).boxed(),
             T::Timestamp => (-100i128..100i128).prop_map(V::timestamp).boxed(),
             T::Bool => any::<bool>().prop_map(V::Bool).boxed(),
-            // TODO: https://gitlab.com/tezos/tezos/-/issues/6755
-            // Allow all Michelson strings
-            T::String => "[A-Za-z0-9]".prop_map(V::String).boxed(),
+            T::String => "[ -~\n]{0,8}".prop_map(V::String).boxed(),
             T::Bytes => prop::collection::vec(any::<u8>(), 0..=3)
                 .prop_map(V::Bytes)
                 .boxed(),
@@ -0,0 +1,412 @@
+/******************************************************************************/
+/*                                                                            */
+/* SPDX-License-Identifier: MIT                                               */
+/* Copyright (c) [2023] Serokell <hi@serokell.io>                             */
+/*                                                                            */
+/******************************************************************************/
+
+//! Annotation-preserving representation of Michelson types. [Type] itself
+//! doesn't store annotations, which is what the typechecker and the
+//! interpreter want, but tools that need to reproduce the original `parameter`
+//! or `storage` declaration, e.g. with field annotations naming entrypoints,
+//! can use [AnnotatedType] instead.
+//!
+//! [AnnotatedType] also knows where each entrypoint is located in the
+//! parameter type, so it can be used to construct a full contract parameter
+//! from an entrypoint and its argument, see [AnnotatedType::build_parameter],
+//! and vice versa, see [AnnotatedType::split_parameter].
+
+use typed_arena::Arena;
+
+use super::{Annotations, Entrypoint, IntoMicheline, Micheline, Or, Type, TypedValue};
+use crate::irrefutable_match::irrefutable_match;
+use crate::lexer::Prim;
+use crate::typechecker::TcError;
+
+/// A [Type] along with the annotations on each of its type constructors.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AnnotatedType<'a> {
+    /// The type itself, with all annotations erased.
+    pub ty: Type,
+    /// Annotations on the outermost type constructor.
+    pub anns: Annotations<'a>,
+    /// Annotated type arguments, in the same order as in the original
+    /// Micheline. Note that `pair` combs are kept as written, i.e. `pair a b c`
+    /// has three arguments, while `pair a (pair b c)` has two.
+    pub args: Vec<AnnotatedType<'a>>,
+}
+
+impl<'a> AnnotatedType<'a> {
+    /// Attach annotations from `micheline` to `ty`. Assumes `ty` is the result
+    /// of successfully parsing `micheline` as a type, panics otherwise.
+    pub(crate) fn from_parsed(ty: &Type, micheline: &Micheline<'a>) -> Self {
+        irrefutable_match!(micheline; Micheline::App, _prim, args, anns);
+        let arg_tys: Vec<&Type> = match ty {
+            Type::Pair(_) => {
+                // walk the right comb, one component per Micheline argument;
+                // the last argument corresponds to the remainder of the comb
+                let mut res = Vec::with_capacity(args.len());
+                let mut cur = ty;
+                for _ in 1..args.len() {
+                    let p = irrefutable_match!(cur; Type::Pair);
+                    res.push(&p.0);
+                    cur = &p.1;
+                }
+                res.push(cur);
+                res
+            }
+            Type::Or(p) | Type::Map(p) | Type::BigMap(p) | Type::Lambda(p) => vec![&p.0, &p.1],
+            Type::Option(x)
+            | Type::List(x)
+            | Type::Set(x)
+            | Type::Contract(x)
+            | Type::Ticket(x) => {
+                vec![x.as_ref()]
+            }
+            _ => vec![],
+        };
+        assert_eq!(arg_tys.len(), args.len());
+        AnnotatedType {
+            ty: ty.clone(),
+            anns: anns.clone(),
+            args: arg_tys
+                .into_iter()
+                .zip(args.iter())
+                .map(|(ty, m)| AnnotatedType::from_parsed(ty, m))
+                .collect(),
+        }
+    }
+
+    /// Drop all annotations, returning the underlying [Type].
+    pub fn into_type(self) -> Type {
+        self.ty
+    }
+
+    /// Interpreting `self` as a contract parameter type, construct the full
+    /// parameter value for calling `entrypoint` with `arg`, i.e. wrap `arg` in
+    /// `Left`/`Right` according to the entrypoint's location. The caller is
+    /// responsible for ensuring `arg` has the type of the entrypoint, as
+    /// reported by [Micheline::get_entrypoints].
+    ///
+    /// Returns [TcError::NoSuchEntrypoint] if the entrypoint isn't defined.
+    pub fn build_parameter<'b>(
+        &self,
+        entrypoint: &Entrypoint,
+        arg: TypedValue<'b>,
+    ) -> Result<TypedValue<'b>, TcError> {
+        let mut path = vec![];
+        if self.find_entrypoint(entrypoint, &mut path).is_none() {
+            if !entrypoint.is_default() {
+                return Err(TcError::NoSuchEntrypoint(entrypoint.clone()));
+            }
+            // implicit default entrypoint is the whole parameter
+            path.clear();
+        }
+        Ok(path.into_iter().rev().fold(arg, |acc, dir| {
+            TypedValue::new_or(match dir {
+                Direction::Left => Or::Left(acc),
+                Direction::Right => Or::Right(acc),
+            })
+        }))
+    }
+
+    /// The inverse of [Self::build_parameter]: interpreting `self` as a
+    /// contract parameter type, find the entrypoint `param` calls and the
+    /// argument to that entrypoint. If several entrypoints fit, e.g. `%a` and
+    /// `%b` for `Left 1` with parameter type `or %a (int %b) unit`, the most
+    /// specific one, i.e. `%b`, is chosen.
+    ///
+    /// Returns [None] if `param` can't be produced by calling any entrypoint.
+    /// This only happens when `%default` entrypoint is defined explicitly, and
+    /// `param` doesn't correspond to any other entrypoint.
+    pub fn split_parameter<'b>(
+        &self,
+        param: TypedValue<'b>,
+    ) -> Option<(Entrypoint, TypedValue<'b>)> {
+        let mut path = vec![];
+        let mut candidates = vec![];
+        let mut node = self;
+        let mut val = &param;
+        loop {
+            if let Some(ep) = node.entrypoint() {
+                candidates.push((ep, path.len()));
+            }
+            match (&node.ty, val) {
+                (Type::Or(_), TypedValue::Or(or)) => {
+                    let (dir, v) = match or.as_ref() {
+                        Or::Left(v) => (Direction::Left, v),
+                        Or::Right(v) => (Direction::Right, v),
+                    };
+                    node = &node.args[dir as usize];
+                    val = v;
+                    path.push(dir);
+                }
+                _ => break,
+            }
+        }
+        // In legacy mode, entrypoints can be duplicated, and the first one
+        // found shadows the rest, so check the entrypoint actually resolves to
+        // the same location.
+        let found = candidates.into_iter().rev().find(|(ep, depth)| {
+            let mut ep_path = vec![];
+            self.find_entrypoint(ep, &mut ep_path).is_some() && ep_path == path[..*depth]
+        });
+        let (ep, depth) = match found {
+            Some(x) => x,
+            None if self
+                .find_entrypoint(&Entrypoint::default(), &mut vec![])
+                .is_none() =>
+            {
+                (Entrypoint::default(), 0)
+            }
+            None => return None,
+        };
+        let mut arg = param;
+        for _ in 0..depth {
+            arg = match *irrefutable_match!(arg; TypedValue::Or) {
+                Or::Left(x) | Or::Right(x) => x,
+            };
+        }
+        Some((ep, arg))
+    }
+
+    /// Entrypoint named by the field annotation on the outermost type
+    /// constructor, if any.
+    fn entrypoint(&self) -> Option<Entrypoint> {
+        self.anns
+            .get_single_field_ann()
+            .ok()
+            .flatten()
+            .and_then(|ann| Entrypoint::try_from(ann).ok())
+    }
+
+    /// Depth-first search for `entrypoint` through the `or` nodes, the same
+    /// way the protocol does it. On success, `path` is the path to the
+    /// entrypoint from the root.
+    fn find_entrypoint(&self, entrypoint: &Entrypoint, path: &mut Vec<Direction>) -> Option<&Self> {
+        if self.entrypoint().as_ref() == Some(entrypoint) {
+            return Some(self);
+        }
+        if let Type::Or(_) = self.ty {
+            for (dir, arg) in [Direction::Left, Direction::Right]
+                .into_iter()
+                .zip(&self.args)
+            {
+                path.push(dir);
+                if let Some(res) = arg.find_entrypoint(entrypoint, path) {
+                    return Some(res);
+                }
+                path.pop();
+            }
+        }
+        None
+    }
+}
+
+/// A branch of an `or` type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Left = 0,
+    Right = 1,
+}
+
+fn type_prim(ty: &Type) -> Prim {
+    use Type::*;
+    match ty {
+        Nat => Prim::nat,
+        Int => Prim::int,
+        Bool => Prim::bool,
+        Mutez => Prim::mutez,
+        String => Prim::string,
+        Unit => Prim::unit,
+        Never => Prim::never,
+        Pair(_) => Prim::pair,
+        Option(_) => Prim::option,
+        List(_) => Prim::list,
+        Operation => Prim::operation,
+        Set(_) => Prim::set,
+        Map(_) => Prim::map,
+        BigMap(_) => Prim::big_map,
+        Or(_) => Prim::or,
+        Contract(_) => Prim::contract,
+        Address => Prim::address,
+        ChainId => Prim::chain_id,
+        Bytes => Prim::bytes,
+        Key => Prim::key,
+        Signature => Prim::signature,
+        KeyHash => Prim::key_hash,
+        Lambda(_) => Prim::lambda,
+        Ticket(_) => Prim::ticket,
+        Timestamp => Prim::timestamp,
+        Bls12381Fr => Prim::bls12_381_fr,
+        Bls12381G1 => Prim::bls12_381_g1,
+        Bls12381G2 => Prim::bls12_381_g2,
+    }
+}
+
+/// Unlike the instance for [Type], this doesn't linearize `pair` combs, but
+/// reproduces them as they were originally written, along with all
+/// annotations.
+impl<'a> IntoMicheline<'a> for &'_ AnnotatedType<'a> {
+    fn into_micheline_optimized_legacy(self, arena: &'a Arena<Micheline<'a>>) -> Micheline<'a> {
+        Micheline::App(
+            type_prim(&self.ty),
+            Micheline::alloc_iter(
+                arena,
+                self.args
+                    .iter()
+                    .map(|arg| arg.into_micheline_optimized_legacy(arena)),
+            ),
+            self.anns.clone(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::Ctx;
+    use crate::parser::test_helpers::parse;
+    use crate::typechecker::typecheck_value;
+
+    #[track_caller]
+    fn check_roundtrip(src: &str) {
+        let arena = Arena::new();
+        let mich = parse(src).unwrap();
+        let annotated = mich.parse_annotated_ty(&mut Ctx::default()).unwrap();
+        assert_eq!(
+            annotated.ty,
+            mich.parse_ty(&mut Ctx::default()).unwrap(),
+            "erased type differs for {src}"
+        );
+        assert_eq!(annotated.into_micheline_optimized_legacy(&arena), mich);
+    }
+
+    #[test]
+    fn roundtrip() {
+        check_roundtrip("nat");
+        check_roundtrip("nat :n %foo");
+        check_roundtrip("or (nat %a) (or :x (unit %b) (pair %c (int :i) string))");
+        check_roundtrip("pair (int %x) (nat %y) (string %z) (bool %w)");
+        check_roundtrip("pair (int %x) (pair (nat %y) (string %z))");
+        check_roundtrip("map :m (string %k) (list (option (nat %v)))");
+        check_roundtrip("big_map %bm nat (lambda (unit :a) (ticket :t bytes))");
+        check_roundtrip("option (contract (or (unit %default) (nat %n)))");
+    }
+
+    #[test]
+    fn erased_type() {
+        let mich = parse("pair :p (int %x) (nat %y) (unit %z)").unwrap();
+        let annotated = mich.parse_annotated_ty(&mut Ctx::default()).unwrap();
+        assert_eq!(annotated.args.len(), 3);
+        assert_eq!(
+            annotated.args[2].ty,
+            Type::Unit,
+            "last comb component must be the remainder"
+        );
+        assert_eq!(
+            annotated.into_type(),
+            Type::new_pair(Type::Int, Type::new_pair(Type::Nat, Type::Unit))
+        );
+    }
+
+    #[track_caller]
+    fn check_entrypoint(param_ty: &str, ep: &str, arg: &str, param: &str) {
+        let mut ctx = Ctx::default();
+        let mich = parse(param_ty).unwrap();
+        let annotated = mich.parse_annotated_ty(&mut ctx).unwrap();
+        let ep = Entrypoint::try_from(ep).unwrap();
+        let ep_ty = mich.get_entrypoints(&mut ctx).unwrap().remove(&ep).unwrap();
+        let arg = typecheck_value(&parse(arg).unwrap(), &mut ctx, &ep_ty).unwrap();
+        let param = parse(param)
+            .unwrap()
+            .typecheck_value(&mut ctx, &mich)
+            .unwrap();
+        assert_eq!(
+            annotated.build_parameter(&ep, arg.clone()),
+            Ok(param.clone())
+        );
+        assert_eq!(annotated.split_parameter(param), Some((ep, arg)));
+    }
+
+    #[test]
+    fn entrypoints() {
+        let ty = "or (or (nat %a) (unit %b)) (or %c (int %d) string)";
+        check_entrypoint(ty, "a", "5", "Left (Left 5)");
+        check_entrypoint(ty, "b", "Unit", "Left (Right Unit)");
+        check_entrypoint(ty, "d", "-1", "Right (Left -1)");
+        check_entrypoint(ty, "c", "Right \"x\"", "Right (Right \"x\")");
+        // implicit default entrypoint takes the whole parameter, but more
+        // specific entrypoints take precedence when splitting
+        let annotated = parse(ty)
+            .unwrap()
+            .parse_annotated_ty(&mut Ctx::default())
+            .unwrap();
+        let param =
+            TypedValue::new_or(Or::Right(TypedValue::new_or(Or::Left(TypedValue::int(-1)))));
+        assert_eq!(
+            annotated.build_parameter(&Entrypoint::default(), param.clone()),
+            Ok(param.clone())
+        );
+        assert_eq!(
+            annotated.split_parameter(param),
+            Some((Entrypoint::try_from("d").unwrap(), TypedValue::int(-1)))
+        );
+        // unnamed branch is only reachable via the implicit default entrypoint
+        check_entrypoint("or (nat %a) unit", "default", "Right Unit", "Right Unit");
+        // root annotation
+        check_entrypoint("or %r (nat %a) unit", "r", "Right Unit", "Right Unit");
+        check_entrypoint("or %r (nat %a) unit", "a", "1", "Left 1");
+        // explicit default
+        check_entrypoint(
+            "or (nat %a) (unit %default)",
+            "default",
+            "Unit",
+            "Right Unit",
+        );
+        check_entrypoint("nat", "default", "1", "1");
+    }
+
+    #[test]
+    fn entrypoint_errors() {
+        let mut ctx = Ctx::default();
+        let annotated = parse("or (or (nat %a) int) (unit %default)")
+            .unwrap()
+            .parse_annotated_ty(&mut ctx)
+            .unwrap();
+        assert_eq!(
+            annotated.build_parameter(&Entrypoint::try_from("b").unwrap(), TypedValue::Unit),
+            Err(TcError::NoSuchEntrypoint(
+                Entrypoint::try_from("b").unwrap()
+            ))
+        );
+        // `Left (Right _)` isn't reachable by any entrypoint
+        assert_eq!(
+            annotated.split_parameter(TypedValue::new_or(Or::Left(TypedValue::new_or(Or::Right(
+                TypedValue::int(1)
+            ))))),
+            None
+        );
+    }
+
+    #[test]
+    fn legacy_duplicate_entrypoint() {
+        let mut ctx = Ctx::default();
+        ctx.legacy = true;
+        let annotated = parse("or (nat %a) (or %a int unit)")
+            .unwrap()
+            .parse_annotated_ty(&mut ctx)
+            .unwrap();
+        let a = Entrypoint::try_from("a").unwrap();
+        assert_eq!(
+            annotated.build_parameter(&a, TypedValue::nat(1)),
+            Ok(TypedValue::new_or(Or::Left(TypedValue::nat(1))))
+        );
+        // the second `%a` is shadowed, so this is only callable via default
+        let param = TypedValue::new_or(Or::Right(TypedValue::new_or(Or::Right(TypedValue::Unit))));
+        assert_eq!(
+            annotated.split_parameter(param.clone()),
+            Some((Entrypoint::default(), param))
+        );
+    }
+}
@@ -0,0 +1,291 @@
+/******************************************************************************/
+/*                                                                            */
+/* SPDX-License-Identifier: MIT                                               */
+/* Copyright (c) [2023] Serokell <hi@serokell.io>                             */
+/*                                                                            */
+/******************************************************************************/
+
+//! Contract events, i.e. the observable result of applying operations created
+//! by the `EMIT` instruction. Unlike [Emit], which holds a typed value, an
+//! [Event] holds the payload and its type as raw [Micheline], the way the
+//! protocol reports them in operation receipts. [EventLog] collects events
+//! emitted over the course of an execution.
+
+use typed_arena::Arena;
+
+use super::{
+    AddressHash, Emit, FieldAnnotation, IntoMicheline, Micheline, Operation, OperationInfo, Or,
+    Type, TypedValue,
+};
+use crate::context::Ctx;
+use crate::typechecker::TcError;
+
+/// A contract event.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Event<'a> {
+    /// Address of the contract that emitted the event.
+    pub source: AddressHash,
+    /// Nonce of the `EMIT` operation that produced the event.
+    pub nonce: u128,
+    /// Event tag.
+    pub tag: Option<FieldAnnotation<'a>>,
+    /// Event type. If the type was given explicitly to `EMIT`, it's reproduced
+    /// verbatim, including annotations. Otherwise, it's the type inferred by
+    /// the typechecker.
+    pub ty: Micheline<'a>,
+    /// Event payload, in the optimized legacy representation.
+    pub payload: Micheline<'a>,
+}
+
+impl<'a> Event<'a> {
+    /// Construct an [Event] from an [Emit] operation with the given `nonce`,
+    /// created by the contract at `source`.
+    pub fn from_emit(
+        arena: &'a Arena<Micheline<'a>>,
+        source: AddressHash,
+        nonce: u128,
+        emit: Emit<'a>,
+    ) -> Self {
+        Event {
+            source,
+            nonce,
+            tag: emit.tag,
+            ty: match emit.arg_ty {
+                Or::Left(ty) => ty.into_micheline_optimized_legacy(arena),
+                Or::Right(ty) => ty,
+            },
+            payload: emit.value.into_micheline_optimized_legacy(arena),
+        }
+    }
+
+    /// Parse the event type, erasing annotations.
+    pub fn parse_ty(&self, ctx: &mut Ctx) -> Result<Type, TcError> {
+        self.ty.parse_ty(ctx)
+    }
+
+    /// Typecheck the event payload against the event type, producing a
+    /// [TypedValue]. Useful when the event comes from an untrusted source,
+    /// e.g. an operation receipt.
+    pub fn decode_payload(&self, ctx: &mut Ctx) -> Result<TypedValue<'a>, TcError> {
+        self.payload.typecheck_value(ctx, &self.ty)
+    }
+}
+
+/// A log of [Event]s, in the order they were emitted.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct EventLog<'a> {
+    events: Vec<Event<'a>>,
+}
+
+impl<'a> EventLog<'a> {
+    /// Construct an empty [EventLog].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record events from operations produced by the contract at `source`,
+    /// e.g. ones returned by [crate::ast::ContractScript::interpret]. All
+    /// other operations are returned in their original order, so that the
+    /// caller can continue applying them. Events emitted by contracts called
+    /// by these operations can be recorded with subsequent calls to this
+    /// function.
+    pub fn collect(
+        &mut self,
+        arena: &'a Arena<Micheline<'a>>,
+        source: &AddressHash,
+        operations: impl IntoIterator<Item = OperationInfo<'a>>,
+    ) -> Vec<OperationInfo<'a>> {
+        let mut rest = vec![];
+        for op in operations {
+            match op.operation {
+                Operation::Emit(emit) => {
+                    self.events
+                        .push(Event::from_emit(arena, source.clone(), op.counter, emit))
+                }
+                _ => rest.push(op),
+            }
+        }
+        rest
+    }
+
+    /// All recorded events.
+    pub fn events(&self) -> &[Event<'a>] {
+        &self.events
+    }
+
+    /// Consume the log, returning recorded events.
+    pub fn into_events(self) -> Vec<Event<'a>> {
+        self.events
+    }
+
+    /// Iterate over events emitted by the contract at `source`.
+    pub fn from_source<'b>(
+        &'b self,
+        source: &'b AddressHash,
+    ) -> impl Iterator<Item = &'b Event<'a>> + 'b {
+        self.events.iter().filter(move |ev| &ev.source == source)
+    }
+
+    /// Iterate over events with the given `tag`. Untagged events are
+    /// matched by `None`.
+    pub fn with_tag<'b>(
+        &'b self,
+        tag: Option<&'b str>,
+    ) -> impl Iterator<Item = &'b Event<'a>> + 'b {
+        self.events
+            .iter()
+            .filter(move |ev| ev.tag.as_ref().map(FieldAnnotation::as_str) == tag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::test_helpers::{parse, parse_contract_script};
+
+    fn addr(s: &str) -> AddressHash {
+        AddressHash::try_from(s).unwrap()
+    }
+
+    #[test]
+    fn event_from_emit() {
+        let arena = Arena::new();
+        let src = addr("KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi");
+        let ev = Event::from_emit(
+            &arena,
+            src.clone(),
+            1,
+            Emit {
+                tag: Some(FieldAnnotation::from_str_unchecked("foo")),
+                value: TypedValue::new_pair(TypedValue::nat(1), TypedValue::String("a".into())),
+                arg_ty: Or::Right(parse("pair (nat %n) (string %s)").unwrap()),
+            },
+        );
+        assert_eq!(
+            ev,
+            Event {
+                source: src.clone(),
+                nonce: 1,
+                tag: Some(FieldAnnotation::from_str_unchecked("foo")),
+                ty: parse("pair (nat %n) (string %s)").unwrap(),
+                payload: parse("Pair 1 \"a\"").unwrap(),
+            }
+        );
+        assert_eq!(
+            ev.parse_ty(&mut Ctx::default()),
+            Ok(Type::new_pair(Type::Nat, Type::String))
+        );
+
+        // inferred type
+        let ev = Event::from_emit(
+            &arena,
+            src,
+            2,
+            Emit {
+                tag: None,
+                value: TypedValue::new_option(Some(TypedValue::int(-5))),
+                arg_ty: Or::Left(Type::new_option(Type::Int)),
+            },
+        );
+        assert_eq!(ev.ty, parse("option int").unwrap());
+        assert_eq!(ev.payload, parse("Some -5").unwrap());
+    }
+
+    #[test]
+    fn decode_payload() {
+        let ev = Event {
+            source: addr("KT1BEqzn5Wx8uJrZNvuS9DVHmLvG9td3fDLi"),
+            nonce: 0,
+            tag: None,
+            ty: parse("or (nat %a) (list %b bool)").unwrap(),
+            payload: parse("Right { True ; False }").unwrap(),
+        };
+        assert_eq!(
+            ev.decode_payload(&mut Ctx::default()),
+            Ok(TypedValue::new_or(Or::Right(TypedValue::List(
+                vec![TypedValue::Bool(true), TypedValue::Bool(false)].into()
+            ))))
+        );
+
+        let ev = Event {
+            payload: parse("Left -1").unwrap(),
+            ..ev
+        };
+        assert!(ev.decode_payload(&mut Ctx::default()).is_err());
+    }
+
+    #[test]
+    fn collect_events() {
+        let arena = Arena::new();
+        let cs = parse_contract_script(
+            "parameter nat;
+             storage unit;
+             code {
+               CAR;
+               DUP; EMIT %first nat;
+               SWAP; INT; EMIT;
+               NONE key_hash; SET_DELEGATE;
+               NIL operation; SWAP; CONS; SWAP; CONS; SWAP; CONS;
+               UNIT; SWAP; PAIR
+             }",
+        )
+        .unwrap()
+        .typecheck_script(&mut Ctx::default())
+        .unwrap();
+        let mut ctx = Ctx::default();
+        let (ops, _) = cs
+            .interpret(
+                &mut ctx,
+                &arena,
+                parse("3").unwrap(),
+                parse("Unit").unwrap(),
+            )
+            .unwrap();
+        let self_addr = ctx.self_address.clone();
+        let mut log = EventLog::new();
+        let rest = log.collect(&arena, &self_addr, ops);
+        assert!(matches!(
+            rest.as_slice(),
+            [OperationInfo {
+                operation: Operation::SetDelegate(_),
+                ..
+            }]
+        ));
+        let other = addr("KT1RJ6PbjHpwc3M5rw5s2Nbmefwbuwbdxton");
+        log.collect(
+            &arena,
+            &other,
+            [OperationInfo {
+                operation: Operation::Emit(Emit {
+                    tag: Some(FieldAnnotation::from_str_unchecked("first")),
+                    value: TypedValue::Unit,
+                    arg_ty: Or::Left(Type::Unit),
+                }),
+                counter: 10,
+            }],
+        );
+
+        assert_eq!(
+            log.events()
+                .iter()
+                .map(|ev| (
+                    ev.tag.as_ref().map(FieldAnnotation::as_str),
+                    ev.payload.clone()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (Some("first"), parse("3").unwrap()),
+                (None, parse("3").unwrap()),
+                (Some("first"), parse("Unit").unwrap()),
+            ]
+        );
+        assert_eq!(log.from_source(&self_addr).count(), 2);
+        assert_eq!(log.from_source(&other).count(), 1);
+        assert_eq!(log.with_tag(Some("first")).count(), 2);
+        assert_eq!(log.with_tag(None).count(), 1);
+        assert_eq!(
+            log.with_tag(None).next().unwrap().decode_payload(&mut ctx),
+            Ok(TypedValue::int(3))
+        );
+    }
+}
@@ -157,6 +157,62 @@ impl<'a> From<&str> for Micheline<'a> {
     }
 }
 
+/// Prints [Micheline] in Michelson concrete syntax, such that
+/// [crate::parser::Parser::parse] produces the same [Micheline] back.
+impl std::fmt::Display for Micheline<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        /// Print an argument of a primitive application, wrapping it in
+        /// parentheses when it would be ambiguous otherwise.
+        fn fmt_arg(m: &Micheline, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match m {
+                Micheline::App(_, args, anns) if !args.is_empty() || !anns.is_empty() => {
+                    write!(f, "({m})")
+                }
+                _ => write!(f, "{m}"),
+            }
+        }
+        match self {
+            Micheline::Int(i) => write!(f, "{i}"),
+            Micheline::String(s) => {
+                write!(f, "\"")?;
+                for c in s.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\r' => write!(f, "\\r")?,
+                        c => write!(f, "{c}")?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            Micheline::Bytes(b) => write!(f, "0x{}", hex::encode(b)),
+            Micheline::App(prim, args, anns) => {
+                write!(f, "{prim}")?;
+                for ann in anns {
+                    write!(f, " {ann}")?;
+                }
+                for arg in args.iter() {
+                    write!(f, " ")?;
+                    fmt_arg(arg, f)?;
+                }
+                Ok(())
+            }
+            Micheline::Seq([]) => write!(f, "{{}}"),
+            Micheline::Seq(elts) => {
+                write!(f, "{{ ")?;
+                for (i, elt) in elts.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ; ")?;
+                    }
+                    write!(f, "{elt}")?;
+                }
+                write!(f, " }}")
+            }
+        }
+    }
+}
+
 /// Trait for types that can be converted into [Micheline].
 pub trait IntoMicheline<'a> {
     /// Untypes a value using optimized representation in legacy mode.
@@ -263,10 +319,7 @@ macro_rules! micheline_unsupported_instructions {
             | Prim::OPEN_CHEST
             | Prim::VIEW
             | Prim::CREATE_ACCOUNT
-            | Prim::STEPS_TO_QUOTA
             | Prim::TICKET_DEPRECATED
-            | Prim::CAST
-            | Prim::RENAME
     };
 }
 
@@ -493,6 +546,45 @@ pub mod test_helpers {
 mod tests {
     use super::*;
 
+    #[test]
+    fn display() {
+        use crate::ast::micheline::test_helpers::{app, seq};
+        use crate::ast::Annotation;
+        use std::borrow::Cow;
+
+        assert_eq!(Micheline::from(-42).to_string(), "-42");
+        assert_eq!(
+            Micheline::from("a\"b\\c\nd\re").to_string(),
+            r#""a\"b\\c\nd\re""#
+        );
+        assert_eq!(Micheline::from(vec![0xde, 0xad]).to_string(), "0xdead");
+        assert_eq!(seq! {}.to_string(), "{}");
+        assert_eq!(
+            seq! { app!(DUP[2]); app!(DROP) }.to_string(),
+            "{ DUP 2 ; DROP }"
+        );
+        assert_eq!(
+            app!(PUSH[app!(option[app!(nat)]), app!(Some[1])]).to_string(),
+            "PUSH (option nat) (Some 1)"
+        );
+        assert_eq!(
+            Micheline::App(
+                Prim::pair,
+                &[
+                    Micheline::App(
+                        Prim::int,
+                        &[],
+                        [Annotation::Field(Cow::Borrowed("a"))].into()
+                    ),
+                    app!(unit)
+                ],
+                [Annotation::Type(Cow::Borrowed("t"))].into()
+            )
+            .to_string(),
+            "pair :t (int %a) unit"
+        );
+    }
+
     #[allow(dead_code)]
     /// Static test to check that `micheline_*` pattern synonyms cover all
     /// constructors except Seq.
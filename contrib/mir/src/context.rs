@@ -79,6 +79,13 @@ pub struct Ctx<'a> {
     /// admit a custom implementation of [LazyStorage] trait. Defaults to a new,
     /// empty, [InMemoryLazyStorage].
     pub big_map_storage: Box<dyn LazyStorage<'a> + 'a>,
+    /// Whether scripts are typechecked in legacy mode, i.e. the mode the
+    /// protocol uses for contracts that are already originated. In legacy mode,
+    /// deprecated instructions (`STEPS_TO_QUOTA` and the pre-Babylon
+    /// `CREATE_CONTRACT` stack layout) are accepted, annotations on top-level
+    /// script fields are ignored, and duplicate entrypoints are not an error.
+    /// Defaults to `false`.
+    pub legacy: bool,
     origination_counter: u32,
     operation_counter: u128,
 }
@@ -147,6 +154,7 @@ impl Default for Ctx<'_> {
             voting_powers: Box::new(|_| 0u32.into()),
             total_voting_power: 0u32.into(),
             big_map_storage: Box::new(InMemoryLazyStorage::new()),
+            legacy: false,
             operation_counter: 0,
             operation_group_hash: OperationListHash::from_base58_check(
                 "onvsLP3JFZia2mzZKWaFuFkWg2L5p3BDUhzh5Kr6CiDDN3rtQ1D",
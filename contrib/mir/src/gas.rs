@@ -386,6 +386,9 @@ pub mod interpret_cost {
     pub const LOOP_LEFT_ENTER: u32 = 10; // corresponds to KLoop_in_left in the Tezos protocol
     pub const LOOP_EXIT: u32 = 10;
     pub const CREATE_CONTRACT: u32 = 60;
+    // STEPS_TO_QUOTA is deprecated and absent from the current cost model, it
+    // is priced like other constant-time context instructions.
+    pub const STEPS_TO_QUOTA: u32 = 10;
 
     pub fn join_tickets(t1: &Ticket, t2: &Ticket) -> Result<u32, OutOfGas> {
         compare(&t1.content, &t2.content)?;
@@ -727,6 +727,9 @@ fn interpret_one<'a>(
             ctx.gas.consume(interpret_cost::PUSH)?;
             stack.push(v.clone());
         }
+        // CAST and RENAME only affect annotations, the protocol erases them
+        // during typechecking, hence no gas cost.
+        I::Cast | I::Rename => {}
         I::Swap => {
             ctx.gas.consume(interpret_cost::SWAP)?;
             stack.swap(0, 1);
@@ -1385,33 +1388,71 @@ fn interpret_one<'a>(
         }
         I::CreateContract(cs, micheline) => {
             ctx.gas.consume(interpret_cost::CREATE_CONTRACT)?;
-            let counter: u128 = ctx.operation_counter();
             let opt_keyhash = pop!(V::Option)
                 .as_ref()
                 .map(|keyhash| irrefutable_match!(keyhash.as_ref(); V::KeyHash).clone());
             let amount = pop!(V::Mutez);
             let storage = pop!();
-            let origination_counter = ctx.origination_counter();
-            stack.push(TypedValue::Address(compute_contract_address(
-                &ctx.operation_group_hash,
-                origination_counter,
-            )));
-            stack.push(TypedValue::new_operation(
-                Operation::CreateContract(CreateContract {
-                    delegate: opt_keyhash,
-                    amount,
-                    storage,
-                    code: cs.clone(), // This clone is cheap since it is an Rc.
-                    micheline_code: micheline,
-                }),
-                counter,
-            ))
+            let (address, operation) =
+                create_contract(ctx, opt_keyhash, amount, storage, cs, micheline);
+            stack.push(address);
+            stack.push(operation);
+        }
+        I::CreateContractLegacy(cs, micheline) => {
+            ctx.gas.consume(interpret_cost::CREATE_CONTRACT)?;
+            // manager, spendable and delegatable are meaningless since Babylon
+            let _manager = pop!(V::KeyHash);
+            let opt_keyhash = pop!(V::Option)
+                .as_ref()
+                .map(|keyhash| irrefutable_match!(keyhash.as_ref(); V::KeyHash).clone());
+            let _spendable = pop!(V::Bool);
+            let _delegatable = pop!(V::Bool);
+            let amount = pop!(V::Mutez);
+            let storage = pop!();
+            let (address, operation) =
+                create_contract(ctx, opt_keyhash, amount, storage, cs, micheline);
+            stack.push(address);
+            stack.push(operation);
+        }
+        I::StepsToQuota => {
+            ctx.gas.consume(interpret_cost::STEPS_TO_QUOTA)?;
+            let steps = ctx.gas.milligas() / 1000;
+            stack.push(V::Nat(steps.into()));
         }
         I::Seq(nested) => interpret(nested, ctx, arena, stack)?,
     }
     Ok(())
 }
 
+/// Shared implementation of both `CREATE_CONTRACT` layouts. Returns the new
+/// contract address and the origination operation.
+fn create_contract<'a>(
+    ctx: &mut Ctx,
+    delegate: Option<KeyHash>,
+    amount: i64,
+    storage: TypedValue<'a>,
+    cs: &Rc<ContractScript<'a>>,
+    micheline: &'a Micheline<'a>,
+) -> (TypedValue<'a>, TypedValue<'a>) {
+    let counter: u128 = ctx.operation_counter();
+    let origination_counter = ctx.origination_counter();
+    let address = TypedValue::Address(compute_contract_address(
+        &ctx.operation_group_hash,
+        origination_counter,
+    ));
+    let operation = TypedValue::new_operation(
+        Operation::CreateContract(CreateContract {
+            delegate,
+            amount,
+            storage,
+            code: cs.clone(), // This clone is cheap since it is an Rc.
+            micheline_code: micheline,
+        }),
+        counter,
+    );
+    (address, operation)
+}
+
 fn compute_contract_address(operation_group_hash: &[u8; 32], o_index: u32) -> Address {
     use tezos_crypto_rs::hash::{ContractKt1Hash, HashTrait};
     let mut input: [u8; 36] = [0; 36];
@@ -5525,6 +5566,63 @@ mod interpreter_tests {
         );
     }
 
+    #[test]
+    fn create_contract_legacy() {
+        use crate::parser::test_helpers::parse;
+
+        let cs_mich =
+            parse("{ parameter unit; storage unit; code { DROP; UNIT; NIL operation; PAIR; }}")
+                .unwrap();
+        let mut ctx = Ctx::default();
+        ctx.set_operation_counter(100);
+        let cs = cs_mich.typecheck_script(&mut ctx).unwrap();
+        let delegate = KeyHash::try_from("tz1Nw5nr152qddEjKT2dKBH8XcBMDAg72iLw").unwrap();
+        let expected_op = TypedValue::new_operation(
+            Operation::CreateContract(super::CreateContract {
+                delegate: Some(delegate.clone()),
+                amount: 100,
+                storage: TypedValue::Unit,
+                code: Rc::new(cs.clone()),
+                micheline_code: &cs_mich,
+            }),
+            101,
+        );
+        let expected_addr = TypedValue::Address(
+            addr::Address::try_from("KT1CvVk9uuEpf5t88frj41xMzHc5M6FHqxZw").unwrap(),
+        );
+        let mut stack = stk![
+            TypedValue::Unit,
+            TypedValue::Mutez(100),
+            TypedValue::Bool(true),
+            TypedValue::Bool(false),
+            TypedValue::new_option(Some(TypedValue::KeyHash(delegate))),
+            TypedValue::KeyHash(KeyHash::try_from("tz1TSbthBCECxmnABv73icw7yyyvUWFLAoSP").unwrap())
+        ];
+        assert_eq!(
+            interpret(
+                &[CreateContractLegacy(Rc::new(cs), &cs_mich)],
+                &mut ctx,
+                &mut stack
+            ),
+            Ok(())
+        );
+        assert_eq!(stack, stk![expected_addr, expected_op]);
+    }
+
+    #[test]
+    fn steps_to_quota() {
+        let mut ctx = Ctx::default();
+        ctx.gas = Gas::new(20_000);
+        let mut stack = stk![];
+        assert_eq!(interpret_one(&StepsToQuota, &mut ctx, &mut stack), Ok(()));
+        assert_eq!(
+            stack,
+            stk![TypedValue::nat(
+                ((20_000 - interpret_cost::STEPS_TO_QUOTA) / 1000).into()
+            )]
+        );
+    }
+
     #[test]
     fn contract_address_computation() {
         use tezos_crypto_rs::hash::OperationListHash;
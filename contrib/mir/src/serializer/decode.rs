@@ -47,6 +47,10 @@ pub enum DecodeError {
     /// Failed to deserialize an annotation.
     #[error("could not decode annotation")]
     BadAnnotation,
+    /// A number was encoded with a redundant trailing zero byte, i.e. not in
+    /// the canonical form. Such encodings are rejected by the protocol.
+    #[error("trailing zero byte in a number")]
+    TrailingZero,
 }
 
 /// If the number of arguments is small, an allocation-avoiding optimization is
@@ -173,10 +177,11 @@ fn decode_int(bytes: &mut BytesIt) -> Result<Micheline<'static>, DecodeError> {
     let mut sign = Sign::Plus;
     let mut first = true;
     loop {
-        let bits = bytes
-            .next_ref()
-            .ok_or(DecodeError::UnexpectedEOF)?
-            .view_bits::<Lsb0>();
+        let byte = bytes.next_ref().ok_or(DecodeError::UnexpectedEOF)?;
+        if !first && *byte == 0 {
+            return Err(DecodeError::TrailingZero);
+        }
+        let bits = byte.view_bits::<Lsb0>();
         let data_len = if first {
             sign = if bits[6] { Sign::Minus } else { Sign::Plus };
             first = false;
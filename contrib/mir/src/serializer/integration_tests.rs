@@ -69,3 +69,139 @@ mod test_typed_encode {
         )
     }
 }
+
+/// Differential round-trip tests between the text parser and the binary
+/// decoder. Corresponding fuzz targets live in `fuzz/`; inputs that uncovered
+/// bugs there are added as regression tests in [test_roundtrip_regressions].
+#[cfg(test)]
+mod test_roundtrip {
+    use proptest::prelude::*;
+    use typed_arena::Arena;
+
+    use crate::ast::test_strategies as TS;
+    use crate::ast::{Instruction, IntoMicheline, Micheline, TypedValue};
+    use crate::context::Ctx;
+    use crate::lexer::Prim;
+    use crate::parser::Parser;
+    use crate::stack::stk;
+
+    /// Check that `mich` survives text -> Micheline -> binary -> Micheline.
+    #[track_caller]
+    fn check_micheline_roundtrip(mich: &Micheline) {
+        let parser = Parser::new();
+        let text = mich.to_string();
+        let parsed = parser
+            .parse(&text)
+            .unwrap_or_else(|e| panic!("{text}: {e}"));
+        assert_eq!(&parsed, mich, "text round-trip for {text}");
+        let arena = Arena::new();
+        let encoded = parsed.encode();
+        assert_eq!(Micheline::decode_raw(&arena, &encoded).as_ref(), Ok(mich));
+    }
+
+    proptest! {
+        #[test]
+        fn micheline_text_binary_roundtrip(mich in TS::micheline()) {
+            check_micheline_roundtrip(&mich);
+        }
+
+        #[test]
+        fn micheline_binary_reencode(mich in TS::micheline()) {
+            // canonical encoding is a fixpoint of decode . encode
+            let arena = Arena::new();
+            let encoded = mich.encode();
+            let decoded = Micheline::decode_raw(&arena, &encoded).unwrap();
+            assert_eq!(decoded.encode(), encoded);
+        }
+
+        #[test]
+        fn typed_value_roundtrip(typed in TS::typed_value_and_type()) {
+            let arena = Arena::new();
+            let mich = typed.val.clone().into_micheline_optimized_legacy(&arena);
+            check_micheline_roundtrip(&mich);
+            let text = mich.to_string();
+            let parser = Parser::new();
+            let parsed = parser.parse(&text).unwrap();
+            assert_eq!(
+                parsed.typecheck_value(&mut Ctx::default(), &(&typed.ty).into_micheline_optimized_legacy(&arena)),
+                Ok(typed.val)
+            );
+        }
+
+        #[test]
+        fn script_roundtrip(typed in TS::typed_value_and_type()) {
+            let arena = Arena::new();
+            let ty = (&typed.ty).into_micheline_optimized_legacy(&arena);
+            let script = Micheline::seq(
+                &arena,
+                [
+                    Micheline::prim1(&arena, Prim::parameter, ty.clone()),
+                    Micheline::prim1(&arena, Prim::storage, ty),
+                    Micheline::prim1(
+                        &arena,
+                        Prim::code,
+                        Micheline::seq(
+                            &arena,
+                            [
+                                Micheline::prim0(Prim::CAR),
+                                Micheline::prim1(&arena, Prim::NIL, Micheline::prim0(Prim::operation)),
+                                Micheline::prim0(Prim::PAIR),
+                            ],
+                        ),
+                    ),
+                ],
+            );
+            check_micheline_roundtrip(&script);
+            let parser = Parser::new();
+            let text = script.to_string();
+            let parsed = parser.parse(&text).unwrap();
+            let cs = parsed.typecheck_script(&mut Ctx::default()).unwrap();
+            assert_eq!(cs, script.typecheck_script(&mut Ctx::default()).unwrap());
+        }
+
+        #[test]
+        fn pack_unpack_roundtrip(typed in TS::typed_value_and_type()) {
+            let arena = Arena::new();
+            let mut ctx = Ctx::default();
+            let mut stack = stk![typed.val.clone()];
+            Instruction::Pack.interpret(&mut ctx, &arena, &mut stack).unwrap();
+            Instruction::Unpack(typed.ty.clone())
+                .interpret(&mut ctx, &arena, &mut stack)
+                .unwrap();
+            assert_eq!(stack, stk![TypedValue::new_option(Some(typed.val))]);
+        }
+    }
+}
+
+/// Inputs found by the fuzz targets in `fuzz/`.
+#[cfg(test)]
+mod test_roundtrip_regressions {
+    use typed_arena::Arena;
+
+    use crate::ast::Micheline;
+    use crate::serializer::DecodeError;
+
+    #[test]
+    fn int_trailing_zero() {
+        // 0xfac5 is a complete encoding of an integer, 0x00 is a redundant
+        // continuation byte, which the protocol rejects.
+        let arena = Arena::new();
+        assert_eq!(
+            Micheline::decode_raw(&arena, &hex::decode("00fac500").unwrap()),
+            Err(DecodeError::TrailingZero)
+        );
+        assert_eq!(
+            Micheline::decode_raw(&arena, &hex::decode("008000").unwrap()),
+            Err(DecodeError::TrailingZero)
+        );
+    }
+
+    #[test]
+    fn int_negative_zero() {
+        // `-0` isn't canonical, but is accepted, and re-encodes as `0`.
+        let arena = Arena::new();
+        let mich = Micheline::decode_raw(&arena, &hex::decode("0040").unwrap()).unwrap();
+        assert_eq!(mich, Micheline::Int(0.into()));
+        assert_eq!(mich.encode(), hex::decode("0000").unwrap());
+    }
+}
@@ -145,6 +145,10 @@ pub enum TcError {
     /// Instruction is not yet implemented.
     #[error("Unhandled instruction: {0}")]
     TodoInstr(Prim),
+    /// Encountered a deprecated instruction, which is only allowed when
+    /// typechecking in legacy mode, see [Ctx::legacy].
+    #[error("deprecated instruction: {0}")]
+    DeprecatedInstruction(Prim),
     /// Type is not yet implemented.
     #[error("Unhandled type: {0}")]
     TodoType(Prim),
@@ -278,8 +282,15 @@ impl<'a> Micheline<'a> {
         parse_ty(ctx, self)
     }
 
+    /// Parse `Micheline` as a type, preserving annotations. Validates the type.
+    pub fn parse_annotated_ty(&self, ctx: &mut Ctx) -> Result<AnnotatedType<'a>, TcError> {
+        let ty = parse_ty(ctx, self)?;
+        Ok(AnnotatedType::from_parsed(&ty, self))
+    }
+
     /// Interpreting `Micheline` as a contract parameter type, collect its
-    /// entrypoints into [Entrypoints].
+    /// entrypoints into [Entrypoints]. To construct a parameter value for a
+    /// given entrypoint, see [AnnotatedType::build_parameter].
     pub fn get_entrypoints(&self, ctx: &mut Ctx) -> Result<Entrypoints, TcError> {
         let (entrypoints, _) = parse_parameter_ty_with_entrypoints(ctx, self)?;
         Ok(entrypoints)
@@ -308,13 +319,17 @@ impl<'a> Micheline<'a> {
         }
         for elt in seq.iter() {
             match elt {
-                Micheline::App(Prim::code, [content], anns) if anns.is_empty() => {
+                // NB: in legacy mode, annotations on the top-level fields are
+                // silently ignored, as in the protocol.
+                Micheline::App(Prim::code, [content], anns) if anns.is_empty() || ctx.legacy => {
                     set_if_none(Prim::code, &mut code, content)?
                 }
-                Micheline::App(Prim::parameter, [content], anns) if anns.is_empty() => {
+                Micheline::App(Prim::parameter, [content], anns)
+                    if anns.is_empty() || ctx.legacy =>
+                {
                     set_if_none(Prim::parameter, &mut parameter_ty, content)?
                 }
-                Micheline::App(Prim::storage, [content], anns) if anns.is_empty() => {
+                Micheline::App(Prim::storage, [content], anns) if anns.is_empty() || ctx.legacy => {
                     set_if_none(Prim::storage, &mut storage_ty, content)?
                 }
                 Micheline::Seq(..)
@@ -384,6 +399,18 @@ fn parse_ty_with_entrypoints(
         })
     }
     let unexpected = || Err(TcError::UnexpectedMicheline(format!("{ty:?}")));
+    // In legacy mode, a duplicate entrypoint resolves to the first one in
+    // root-first order, so this type's own entrypoint takes precedence over
+    // the ones of its arguments, but not over the ones found before it.
+    let found_before = match (&entrypoints, ty) {
+        (Option::Some(eps), App(_, _, anns)) if ctx.legacy => anns
+            .get_single_field_ann()
+            .ok()
+            .flatten()
+            .and_then(|field_ann| Entrypoint::try_from(field_ann).ok())
+            .is_some_and(|entrypoint| eps.contains_key(&entrypoint)),
+        _ => false,
+    };
     let parsed_ty = match ty {
         App(int, [], _) => Type::Int,
         App(int, ..) => unexpected()?,
@@ -520,10 +547,18 @@ fn parse_ty_with_entrypoints(
             if let Ok(entrypoint) = Entrypoint::try_from(field_ann) {
                 let entry = eps.entry(entrypoint);
                 match entry {
+                    // The protocol doesn't check entrypoints for well-formedness
+                    // in legacy mode; the first one found is used.
+                    Entry::Occupied(_) if ctx.legacy && found_before => (),
+                    Entry::Occupied(mut e) if ctx.legacy => {
+                        e.insert(parsed_ty.clone());
+                    }
                     Entry::Occupied(e) => {
                         return Err(TcError::DuplicateEntrypoint(e.key().clone()))
                     }
-                    Entry::Vacant(e) => e.insert(parsed_ty.clone()),
+                    Entry::Vacant(e) => {
+                        e.insert(parsed_ty.clone());
+                    }
                 };
             }
         }
@@ -1272,6 +1307,21 @@ pub(crate) fn typecheck_instruction<'a>(
         (App(SWAP, [], _), [] | [_]) => no_overload!(SWAP, len 2),
         (App(SWAP, expect_args!(0), _), _) => unexpected_micheline!(),
 
+        // NB: since types don't carry annotations, CAST can only change the
+        // type annotations of the top of the stack, so it's a no-op otherwise.
+        (App(CAST, [t], _), [.., ty]) => {
+            let cast_ty = parse_ty(ctx, t)?;
+            ensure_ty_eq(&mut ctx.gas, &cast_ty, ty)?;
+            I::Cast
+        }
+        (App(CAST, [_], _), []) => no_overload!(CAST, len 1),
+        (App(CAST, expect_args!(1), _), _) => unexpected_micheline!(),
+
+        // NB: RENAME only changes the variable annotation, which isn't tracked.
+        (App(RENAME, [], _), [.., _]) => I::Rename,
+        (App(RENAME, [], _), []) => no_overload!(RENAME, len 1),
+        (App(RENAME, expect_args!(0), _), _) => unexpected_micheline!(),
+
         (App(FAILWITH, [], _), [.., _]) => {
             let ty = pop!();
             // NB: the docs for the FAILWITH instruction
@@ -2004,6 +2054,19 @@ pub(crate) fn typecheck_instruction<'a>(
             stack.push(Type::Operation);
             I::CreateContract(Rc::new(contract_script), cs)
         }
+        // Pre-Babylon layout, with manager, spendable and delegatable
+        // arguments. Only allowed in legacy mode.
+        (
+            App(CREATE_CONTRACT, [cs], _),
+            [.., new_storage, T::Mutez, T::Bool, T::Bool, T::Option(opt_keyhash), T::KeyHash],
+        ) if ctx.legacy && matches!(opt_keyhash.as_ref(), Type::KeyHash) => {
+            let contract_script = cs.typecheck_script(ctx)?;
+            ensure_ty_eq(&mut ctx.gas, &contract_script.storage, new_storage)?;
+            stack.drop_top(6);
+            stack.push(Type::Address);
+            stack.push(Type::Operation);
+            I::CreateContractLegacy(Rc::new(contract_script), cs)
+        }
         (App(CREATE_CONTRACT, [_], _), [.., _, _, _]) => {
             no_overload!(CREATE_CONTRACT)
         }
@@ -2012,6 +2075,15 @@ pub(crate) fn typecheck_instruction<'a>(
         }
         (App(CREATE_CONTRACT, expect_args!(1), _), _) => unexpected_micheline!(),
 
+        (App(STEPS_TO_QUOTA, [], _), ..) if ctx.legacy => {
+            stack.push(T::Nat);
+            I::StepsToQuota
+        }
+        (App(STEPS_TO_QUOTA, [], _), ..) => {
+            return Err(TcError::DeprecatedInstruction(STEPS_TO_QUOTA))
+        }
+        (App(STEPS_TO_QUOTA, expect_args!(0), _), _) => unexpected_micheline!(),
+
         (App(prim @ micheline_unsupported_instructions!(), ..), _) => {
             Err(TcError::TodoInstr(*prim))?
         }
@@ -7932,4 +8004,179 @@ mod typecheck_tests {
             })
         );
     }
+
+    #[test]
+    fn steps_to_quota() {
+        let stk = &mut tc_stk![];
+        assert_eq!(
+            typecheck_instruction(&app!(STEPS_TO_QUOTA), &mut Ctx::default(), stk),
+            Err(TcError::DeprecatedInstruction(Prim::STEPS_TO_QUOTA))
+        );
+        let mut ctx = Ctx::default();
+        ctx.legacy = true;
+        assert_eq!(
+            typecheck_instruction(&app!(STEPS_TO_QUOTA), &mut ctx, stk),
+            Ok(StepsToQuota)
+        );
+        assert_eq!(stk, &tc_stk![Type::Nat]);
+    }
+
+    #[test]
+    fn create_contract_legacy() {
+        let create_contract_src = "CREATE_CONTRACT { parameter unit; storage unit; code { DROP; UNIT; NIL operation; PAIR; }}";
+        let cs_mich =
+            parse("{ parameter unit; storage unit; code { DROP; UNIT; NIL operation; PAIR; }}")
+                .unwrap();
+        let cs = cs_mich.typecheck_script(&mut Ctx::default()).unwrap();
+        let legacy_stk = || {
+            tc_stk![
+                Type::Unit,
+                Type::Mutez,
+                Type::Bool,
+                Type::Bool,
+                Type::new_option(Type::KeyHash),
+                Type::KeyHash
+            ]
+        };
+        let stk = &mut legacy_stk();
+        assert_eq!(
+            typecheck_instruction(
+                &parse(create_contract_src).unwrap(),
+                &mut Ctx::default(),
+                stk
+            ),
+            Err(TcError::NoMatchingOverload {
+                instr: Prim::CREATE_CONTRACT,
+                stack: stk![
+                    Type::Unit,
+                    Type::Mutez,
+                    Type::Bool,
+                    Type::Bool,
+                    Type::new_option(Type::KeyHash),
+                    Type::KeyHash
+                ],
+                reason: None
+            })
+        );
+        let mut ctx = Ctx::default();
+        ctx.legacy = true;
+        let stk = &mut legacy_stk();
+        assert_eq!(
+            typecheck_instruction(&parse(create_contract_src).unwrap(), &mut ctx, stk),
+            Ok(CreateContractLegacy(Rc::new(cs), &cs_mich))
+        );
+        assert_eq!(stk, &tc_stk![Type::Address, Type::Operation]);
+    }
+
+    #[test]
+    fn legacy_toplevel_annotations() {
+        let src = "parameter %root unit; storage :s unit; code @c { CDR; NIL operation; PAIR }";
+        assert!(matches!(
+            parse_contract_script(src)
+                .unwrap()
+                .typecheck_script(&mut Ctx::default()),
+            Err(TcError::UnexpectedMicheline(_))
+        ));
+        let mut ctx = Ctx::default();
+        ctx.legacy = true;
+        assert!(parse_contract_script(src)
+            .unwrap()
+            .typecheck_script(&mut ctx)
+            .is_ok());
+    }
+
+    #[test]
+    fn legacy_duplicate_entrypoints() {
+        let ty = parse("or (nat %a) (or (int %a) (unit %b))").unwrap();
+        assert_eq!(
+            ty.get_entrypoints(&mut Ctx::default()),
+            Err(TcError::DuplicateEntrypoint(
+                Entrypoint::try_from("a").unwrap()
+            ))
+        );
+        let mut ctx = Ctx::default();
+        ctx.legacy = true;
+        let eps = ty.get_entrypoints(&mut ctx).unwrap();
+        assert_eq!(
+            eps.get(&Entrypoint::try_from("a").unwrap()),
+            Some(&Type::Nat)
+        );
+        assert_eq!(
+            eps.get(&Entrypoint::try_from("b").unwrap()),
+            Some(&Type::Unit)
+        );
+    }
+
+    #[test]
+    fn legacy_nested_duplicate_entrypoints() {
+        // The outer entrypoint comes first in root-first order.
+        let ty = parse("or (or %a (nat %a) unit) (int %b)").unwrap();
+        let mut ctx = Ctx::default();
+        ctx.legacy = true;
+        let eps = ty.get_entrypoints(&mut ctx).unwrap();
+        assert_eq!(
+            eps.get(&Entrypoint::try_from("a").unwrap()),
+            Some(&Type::new_or(Type::Nat, Type::Unit))
+        );
+
+        // An entrypoint found before an outer duplicate is kept.
+        let ty = parse("or (unit %a) (or %a (nat %a) int)").unwrap();
+        let eps = ty.get_entrypoints(&mut ctx).unwrap();
+        assert_eq!(
+            eps.get(&Entrypoint::try_from("a").unwrap()),
+            Some(&Type::Unit)
+        );
+    }
+
+    #[test]
+    fn cast() {
+        let stk = &mut tc_stk![Type::new_pair(Type::Int, Type::Nat)];
+        assert_eq!(
+            typecheck_instruction(
+                &parse("CAST (pair :p (int %a) (nat %b))").unwrap(),
+                &mut Ctx::default(),
+                stk
+            ),
+            Ok(Cast)
+        );
+        assert_eq!(stk, &tc_stk![Type::new_pair(Type::Int, Type::Nat)]);
+        assert_eq!(
+            typecheck_instruction(&parse("CAST int").unwrap(), &mut Ctx::default(), stk),
+            Err(TypesNotEqual(Type::Int, Type::new_pair(Type::Int, Type::Nat)).into())
+        );
+        assert_eq!(
+            typecheck_instruction(
+                &parse("CAST int").unwrap(),
+                &mut Ctx::default(),
+                &mut tc_stk![]
+            ),
+            Err(TcError::NoMatchingOverload {
+                instr: Prim::CAST,
+                stack: stk![],
+                reason: Some(NoMatchingOverloadReason::StackTooShort { expected: 1 })
+            })
+        );
+    }
+
+    #[test]
+    fn rename() {
+        let stk = &mut tc_stk![Type::Nat];
+        assert_eq!(
+            typecheck_instruction(&parse("RENAME @foo").unwrap(), &mut Ctx::default(), stk),
+            Ok(Rename)
+        );
+        assert_eq!(stk, &tc_stk![Type::Nat]);
+        assert_eq!(
+            typecheck_instruction(
+                &parse("RENAME").unwrap(),
+                &mut Ctx::default(),
+                &mut tc_stk![]
+            ),
+            Err(TcError::NoMatchingOverload {
+                instr: Prim::RENAME,
+                stack: stk![],
+                reason: Some(NoMatchingOverloadReason::StackTooShort { expected: 1 })
+            })
+        );
+    }
 }
@@ -275,27 +275,36 @@ type transaction_receipt = {
   type_ : quantity;
   status : quantity;
   contractAddress : address option;
+  daFee : quantity;
 }
 
 let transaction_receipt_from_rlp block_hash bytes =
-  match Rlp.decode bytes with
-  | Ok
-      (Rlp.List
-        [
-          Value hash;
-          Value index;
-          Value block_number;
-          Value from;
-          Value to_;
-          Value cumulative_gas_used;
-          Value effective_gas_price;
-          Value gas_used;
-          Value contract_address;
-          List logs;
-          Value bloom;
-          Value type_;
-          Value status;
-        ]) ->
+  let fields =
+    match Rlp.decode bytes with
+    (* Receipts stored before the data availability fee have 13 fields. *)
+    | Ok (Rlp.List fields) when List.length fields = 13 ->
+        Some (fields @ [Rlp.Value Bytes.empty])
+    | Ok (Rlp.List fields) when List.length fields = 14 -> Some fields
+    | _ -> None
+  in
+  match fields with
+  | Some
+      [
+        Value hash;
+        Value index;
+        Value block_number;
+        Value from;
+        Value to_;
+        Value cumulative_gas_used;
+        Value effective_gas_price;
+        Value gas_used;
+        Value contract_address;
+        List logs;
+        Value bloom;
+        Value type_;
+        Value status;
+        Value da_fee;
+      ] ->
       let hash = decode_hash hash in
       let index = decode_number index in
       let block_number = decode_number block_number in
@@ -328,6 +337,7 @@ let transaction_receipt_from_rlp block_hash bytes =
       let bloom = decode_hex bloom in
       let type_ = decode_number type_ in
       let status = decode_number status in
+      let da_fee = decode_number da_fee in
       {
         transactionHash = hash;
         transactionIndex = index;
@@ -343,11 +353,12 @@ let transaction_receipt_from_rlp block_hash bytes =
         type_;
         status;
         contractAddress = contract_address;
+        daFee = da_fee;
       }
   | _ ->
       raise
         (Invalid_argument
-           "Expected a RlpList of 13 elements in transaction receipt")
+           "Expected a RlpList of 13 or 14 elements in transaction receipt")
 
 let transaction_receipt_encoding =
   let open Data_encoding in
@@ -367,6 +378,7 @@ let transaction_receipt_encoding =
            type_;
            status;
            contractAddress;
+           daFee;
          } ->
       ( ( transactionHash,
           transactionIndex,
@@ -378,7 +390,7 @@ let transaction_receipt_encoding =
           effectiveGasPrice,
           gasUsed,
           logs ),
-        (logsBloom, type_, status, contractAddress) ))
+        (logsBloom, type_, status, contractAddress, daFee) ))
     (fun ( ( transactionHash,
              transactionIndex,
              blockHash,
@@ -389,7 +401,7 @@ let transaction_receipt_encoding =
              effectiveGasPrice,
              gasUsed,
              logs ),
-           (logsBloom, type_, status, contractAddress) ) ->
+           (logsBloom, type_, status, contractAddress, daFee) ) ->
       {
         transactionHash;
         transactionIndex;
@@ -405,6 +417,7 @@ let transaction_receipt_encoding =
         type_;
         status;
         contractAddress;
+        daFee;
       })
     (merge_objs
        (obj10
@@ -418,11 +431,12 @@ let transaction_receipt_encoding =
           (req "effectiveGasPrice" quantity_encoding)
           (req "gasUsed" quantity_encoding)
           (req "logs" (list transaction_log_encoding)))
-       (obj4
+       (obj5
           (req "logsBloom" hex_encoding)
           (req "type" quantity_encoding)
           (req "status" quantity_encoding)
-          (req "contractAddress" (option address_encoding))))
+          (req "contractAddress" (option address_encoding))
+          (req "daFee" quantity_encoding)))
 
 type transaction_object = {
   blockHash : block_hash;
@@ -610,6 +624,7 @@ type block = {
   timestamp : quantity;
   transactions : block_transactions;
   uncles : hash list;
+  baseFeePerGas : quantity option;
 }
 
 let decode_list decoder list =
@@ -628,21 +643,20 @@ let block_from_rlp bytes =
   match Rlp.decode bytes with
   | Ok
       (Rlp.List
-        [
-          Value number;
-          Value hash;
-          Value parent_hash;
-          Value logsBloom;
-          Value transactionRoot;
-          Value stateRoot;
-          Value receiptRoot;
-          Value miner;
-          Value extraData;
-          Value gasLimit;
-          List transactions;
-          Value gasUsed;
-          Value timestamp;
-        ]) ->
+        (Value number
+        :: Value hash
+        :: Value parent_hash
+        :: Value logsBloom
+        :: Value transactionRoot
+        :: Value stateRoot
+        :: Value receiptRoot
+        :: Value miner
+        :: Value extraData
+        :: Value gasLimit
+        :: List transactions
+        :: Value gasUsed
+        :: Value timestamp
+        :: rest)) ->
       let (Qty number) = decode_number number in
       let hash = decode_block_hash hash in
       let parent = decode_block_hash parent_hash in
@@ -682,6 +696,16 @@ let block_from_rlp bytes =
       let transactions = TxHash (decode_list decode_hash transactions) in
       let gasUsed = decode_number gasUsed in
       let timestamp = decode_number timestamp in
+      (* The base fee per gas is unknown for blocks produced before it was
+         added to the block. *)
+      let baseFeePerGas =
+        match rest with
+        | [] -> None
+        | [Value baseFeePerGas] ->
+            if baseFeePerGas = Bytes.empty then None
+            else Some (decode_number baseFeePerGas)
+        | _ -> raise (Invalid_argument "Expected a List of 13 or 14 elements")
+      in
       {
         number = Block_height number;
         hash;
@@ -712,8 +736,9 @@ let block_from_rlp bytes =
         transactions;
         (* Post merge: always empty. *)
         uncles = [];
+        baseFeePerGas;
       }
-  | _ -> raise (Invalid_argument "Expected a List of 13 elements")
+  | _ -> raise (Invalid_argument "Expected a List of 13 or 14 elements")
 
 let block_encoding =
   let open Data_encoding in
@@ -738,6 +763,7 @@ let block_encoding =
            timestamp;
            transactions;
            uncles;
+           baseFeePerGas;
          } ->
       ( ( number,
           hash,
@@ -757,7 +783,8 @@ let block_encoding =
           gasUsed,
           timestamp,
           transactions,
-          uncles ) ))
+          uncles,
+          baseFeePerGas ) ))
     (fun ( ( number,
              hash,
              parent,
@@ -776,7 +803,8 @@ let block_encoding =
              gasUsed,
              timestamp,
              transactions,
-             uncles ) ) ->
+             uncles,
+             baseFeePerGas ) ) ->
       {
         number;
         hash;
@@ -797,6 +825,7 @@ let block_encoding =
         timestamp;
         transactions;
         uncles;
+        baseFeePerGas;
       })
     (merge_objs
        (obj10
@@ -810,7 +839,7 @@ let block_encoding =
           (req "stateRoot" hash_encoding)
           (req "receiptsRoot" hash_encoding)
           (req "miner" hex_encoding))
-       (obj9
+       (obj10
           (req "difficulty" quantity_encoding)
           (req "totalDifficulty" quantity_encoding)
           (req "extraData" hex_encoding)
@@ -819,7 +848,8 @@ let block_encoding =
           (req "gasUsed" quantity_encoding)
           (req "timestamp" quantity_encoding)
           (req "transactions" block_transactions_encoding)
-          (req "uncles" (list hash_encoding))))
+          (req "uncles" (list hash_encoding))
+          (opt "baseFeePerGas" quantity_encoding)))
 
 type transaction = {
   from : address;
@@ -0,0 +1,76 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+// Transient storage and memory copies of various sizes, to model the ticks of
+// TLOAD, TSTORE (EIP-1153) and MCOPY (EIP-5656). Cancun must be activated in
+// the benchmark configuration.
+//
+// The contract is written in bytecode, as the version of solc used by the
+// benchmarks doesn't support Cancun. Its calldata is two words, `n` and
+// `size`: it stores and loads `n` transient slots, then copies `size` bytes
+// of memory.
+
+const utils = require('./utils');
+let faucet = require('./players/faucet.json');
+let player1 = require('./players/player1.json');
+
+const runtime = [
+    "6000",   // 0x00 PUSH1 0           i = 0
+    "5b",     // 0x02 JUMPDEST          loop:
+    "6000",   // 0x03 PUSH1 0
+    "35",     // 0x05 CALLDATALOAD      n
+    "81",     // 0x06 DUP2
+    "10",     // 0x07 LT                i < n
+    "15",     // 0x08 ISZERO
+    "6018",   // 0x09 PUSH1 0x18
+    "57",     // 0x0b JUMPI             if i >= n goto copy
+    "80",     // 0x0c DUP1
+    "80",     // 0x0d DUP1
+    "5d",     // 0x0e TSTORE            transient[i] = i
+    "80",     // 0x0f DUP1
+    "5c",     // 0x10 TLOAD             transient[i]
+    "50",     // 0x11 POP
+    "6001",   // 0x12 PUSH1 1
+    "01",     // 0x14 ADD               i = i + 1
+    "6002",   // 0x15 PUSH1 0x02
+    "56",     // 0x17 JUMP              goto loop
+    "5b",     // 0x18 JUMPDEST          copy:
+    "50",     // 0x19 POP
+    "6020",   // 0x1a PUSH1 32
+    "35",     // 0x1c CALLDATALOAD      size
+    "80",     // 0x1d DUP1
+    "6000",   // 0x1e PUSH1 0
+    "90",     // 0x20 SWAP1
+    "5e",     // 0x21 MCOPY             memory[size..2 * size] = memory[0..size]
+    "00",     // 0x22 STOP
+].join("");
+
+// Copies the runtime code in memory and returns it.
+const runtime_length = (runtime.length / 2).toString(16).padStart(2, "0");
+const init = [
+    "60" + runtime_length, // PUSH1 runtime_length
+    "80",                  // DUP1
+    "600b",                // PUSH1 0x0b, the length of the init code
+    "6000",                // PUSH1 0
+    "39",                  // CODECOPY
+    "6000",                // PUSH1 0
+    "f3",                  // RETURN
+].join("");
+
+const word = (n) => n.toString(16).padStart(64, "0");
+const call = (n, size) => "0x" + word(n) + word(size);
+
+let txs = [];
+txs.push(utils.transfer(faucet, player1, 100000000));
+let create = utils.create(player1, 0, "0x" + init + runtime);
+txs.push(create.tx);
+
+for (const n of [1, 10, 100, 500]) {
+    txs.push(utils.send(player1, create.addr, 0, call(n, 0)));
+}
+for (const size of [32, 1024, 8192, 32768]) {
+    txs.push(utils.send(player1, create.addr, 0, call(0, size)));
+}
+
+utils.print_bench([txs])
@@ -30,6 +30,28 @@ for (var i = 0; i < 10; i++) {
     txs.push(build_precompile_call(identity_precompile_address, i * 32));
 }   
 
+// BLS12-381 precompiles (EIP-2537), called with the generators of G1 and G2
+let bls_g1 = "0000000000000000000000000000000017f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb0000000000000000000000000000000008b3f481e3aaa0f1a09e30ed741d8ae4fcf5e095d5d00af600db18cb2c04b3edd03cc744a2888ae40caa232946c5e7e1"
+let bls_g2 = "00000000000000000000000000000000024aa2b2f08f0a91260805272dc51051c6e47ad4fa403b02b4510b647ae3d1770bac0326a805bbefd48056c8c121bdb80000000000000000000000000000000013e02b6052719f607dacd3a088274f65596bd0d09920b61ab5da61bbdc7f5049334cf11213945d57e5ac7d055d042b7e000000000000000000000000000000000ce5d527727d6e118cc9cdc6da2e351aadfd9baa8cbdd3a76d429a695160d12c923ac9cc3baca289e193548608b82801000000000000000000000000000000000606c4a02ea734cc32acd2b02bc28b99cb3e287e85a763af267492ab572e99ab3f370d275cec1da1aaa9075ff05f79be"
+let bls_scalar = "ff".repeat(32)
+let bls_fp = "00".repeat(63) + "01"
+
+function bls_precompile_address(address) {
+    return address.toString(16).padStart(40, "0")
+}
+
+for (var k = 1; k <= 3; k++) {
+    txs.push(utils.send(player1, bls_precompile_address(13), 0, "0x" + (bls_g1 + bls_scalar).repeat(k)))
+    txs.push(utils.send(player1, bls_precompile_address(16), 0, "0x" + (bls_g2 + bls_scalar).repeat(k)))
+    txs.push(utils.send(player1, bls_precompile_address(17), 0, "0x" + (bls_g1 + bls_g2).repeat(k)))
+}
+txs.push(utils.send(player1, bls_precompile_address(11), 0, "0x" + bls_g1 + bls_g1))
+txs.push(utils.send(player1, bls_precompile_address(12), 0, "0x" + bls_g1 + bls_scalar))
+txs.push(utils.send(player1, bls_precompile_address(14), 0, "0x" + bls_g2 + bls_g2))
+txs.push(utils.send(player1, bls_precompile_address(15), 0, "0x" + bls_g2 + bls_scalar))
+txs.push(utils.send(player1, bls_precompile_address(18), 0, "0x" + bls_fp))
+txs.push(utils.send(player1, bls_precompile_address(19), 0, "0x" + bls_fp + bls_fp))
+
 txs.push(utils.send(player1, withdrawal_precompile_address, 0, withdraw_calldata_prefix + valid_withdraw_destination))
 txs.push(utils.send(player1, withdrawal_precompile_address, 1000, withdraw_calldata_prefix + valid_withdraw_destination))
 txs.push(utils.send(player1, withdrawal_precompile_address, 1000, withdraw_calldata_prefix + invalid_withdraw_destination))
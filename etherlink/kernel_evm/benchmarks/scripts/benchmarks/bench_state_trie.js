@@ -0,0 +1,80 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+// Transfers to new accounts and writes of new storage slots, to measure the
+// ticks of updating the state trie per touched account and per touched slot
+// (`TICKS_PER_TOUCHED_ACCOUNT` and `TICKS_PER_TOUCHED_SLOT`), see
+// `analysis/tx_overhead.js`.
+//
+// The contract is written in bytecode. Its calldata is three words, `n`,
+// `base` and `value`: it stores `value` in the slots `base` to `base + n - 1`.
+
+const utils = require('./utils');
+const addr = require('../lib/address');
+let faucet = require('./players/faucet.json');
+let player1 = require('./players/player1.json');
+
+const runtime = [
+    "6000",   // 0x00 PUSH1 0           i = 0
+    "5b",     // 0x02 JUMPDEST          loop:
+    "6000",   // 0x03 PUSH1 0
+    "35",     // 0x05 CALLDATALOAD      n
+    "81",     // 0x06 DUP2
+    "10",     // 0x07 LT                i < n
+    "15",     // 0x08 ISZERO
+    "601c",   // 0x09 PUSH1 0x1c
+    "57",     // 0x0b JUMPI             if i >= n goto end
+    "80",     // 0x0c DUP1
+    "6020",   // 0x0d PUSH1 32
+    "35",     // 0x0f CALLDATALOAD      base
+    "01",     // 0x10 ADD               base + i
+    "6040",   // 0x11 PUSH1 64
+    "35",     // 0x13 CALLDATALOAD      value
+    "90",     // 0x14 SWAP1
+    "55",     // 0x15 SSTORE            storage[base + i] = value
+    "6001",   // 0x16 PUSH1 1
+    "01",     // 0x18 ADD               i = i + 1
+    "6002",   // 0x19 PUSH1 0x02
+    "56",     // 0x1b JUMP              goto loop
+    "5b",     // 0x1c JUMPDEST          end:
+    "00",     // 0x1d STOP
+].join("");
+
+// Copies the runtime code in memory and returns it.
+const runtime_length = (runtime.length / 2).toString(16).padStart(2, "0");
+const init = [
+    "60" + runtime_length, // PUSH1 runtime_length
+    "80",                  // DUP1
+    "600b",                // PUSH1 0x0b, the length of the init code
+    "6000",                // PUSH1 0
+    "39",                  // CODECOPY
+    "6000",                // PUSH1 0
+    "f3",                  // RETURN
+].join("");
+
+const word = (n) => n.toString(16).padStart(64, "0");
+const call = (n, base, value) => "0x" + word(n) + word(base) + word(value);
+
+let txs = [];
+txs.push(utils.transfer(faucet, player1, 1000000000));
+
+// Each transfer adds an account to the state trie
+for (let i = 0; i < 100; i++) {
+    txs.push(utils.transfer(player1, addr.create_player(), 1));
+}
+
+let create = utils.create(player1, 0, "0x" + init + runtime);
+txs.push(create.tx);
+
+// New slots, then updates of existing ones
+let base = 1;
+for (const n of [1, 10, 100, 500]) {
+    txs.push(utils.send(player1, create.addr, 0, call(n, base, 1)));
+    base += n;
+}
+for (const n of [1, 10, 100, 500]) {
+    txs.push(utils.send(player1, create.addr, 0, call(n, 1, 2)));
+}
+
+utils.print_bench([txs])
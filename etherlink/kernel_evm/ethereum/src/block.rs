@@ -21,12 +21,16 @@ use tezos_smart_rollup_encoding::timestamp::Timestamp;
 #[derive(Debug, Clone, Copy)]
 pub struct BlockFees {
     base_fee_per_gas: U256,
+    da_fee_per_byte: U256,
 }
 
 impl BlockFees {
     /// Setup fee information for the current block
-    pub const fn new(base_fee_per_gas: U256) -> Self {
-        Self { base_fee_per_gas }
+    pub const fn new(base_fee_per_gas: U256, da_fee_per_byte: U256) -> Self {
+        Self {
+            base_fee_per_gas,
+            da_fee_per_byte,
+        }
     }
 
     /// The base fee per gas for doing a transaction within the current block.
@@ -34,6 +38,24 @@ impl BlockFees {
     pub const fn base_fee_per_gas(&self) -> U256 {
         self.base_fee_per_gas
     }
+
+    /// The fee paid for each byte of a transaction posted on the L1 inbox.
+    #[inline(always)]
+    pub const fn da_fee_per_byte(&self) -> U256 {
+        self.da_fee_per_byte
+    }
+
+    /// Gas paying for the data availability fee of a transaction of
+    /// `data_size` bytes at `gas_price`, rounded up.
+    pub fn gas_for_da_fee(&self, data_size: u64, gas_price: U256) -> u64 {
+        if gas_price.is_zero() {
+            return 0;
+        }
+        let da_fee = self.da_fee_per_byte.saturating_mul(data_size.into());
+        let (gas, rest) = da_fee.div_mod(gas_price);
+        let gas = if rest.is_zero() { gas } else { gas + 1 };
+        gas.try_into().unwrap_or(u64::MAX)
+    }
 }
 
 /// All data for an Ethereum block.
@@ -100,6 +122,9 @@ pub struct L2Block {
     pub gas_used: U256,
     pub timestamp: Timestamp,
     pub transactions: Vec<TransactionHash>,
+    /// Base fee per gas of the block, unknown for the blocks produced before
+    /// it was recorded.
+    pub base_fee_per_gas: Option<U256>,
 }
 
 impl L2Block {
@@ -125,6 +150,7 @@ impl L2Block {
         state_root: OwnedHash,
         receipts_root: OwnedHash,
         gas_used: U256,
+        base_fee_per_gas: U256,
     ) -> Self {
         let hash = Self::hash(
             parent_hash,
@@ -150,6 +176,7 @@ impl L2Block {
             state_root,
             receipts_root,
             gas_used,
+            base_fee_per_gas: Some(base_fee_per_gas),
             ..Self::default()
         }
     }
@@ -214,13 +241,14 @@ impl Default for L2Block {
             gas_used: U256::zero(),
             timestamp: Timestamp::from(0),
             transactions: Vec::new(),
+            base_fee_per_gas: None,
         }
     }
 }
 
 impl Encodable for L2Block {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(13);
+        s.begin_list(14);
         append_u256_le(s, &self.number);
         s.append(&self.hash);
         s.append(&self.parent_hash);
@@ -236,13 +264,16 @@ impl Encodable for L2Block {
         s.append_list::<Vec<u8>, _>(&transactions_bytes);
         append_u256_le(s, &self.gas_used);
         append_timestamp(s, self.timestamp);
+        append_option_explicit(s, &self.base_fee_per_gas, append_u256_le);
     }
 }
 
 impl Decodable for L2Block {
     fn decode(decoder: &Rlp) -> Result<Self, DecoderError> {
         if decoder.is_list() {
-            if Ok(13) == decoder.item_count() {
+            let item_count = decoder.item_count()?;
+            // Blocks produced before the base fee was recorded have 13 fields
+            if item_count == 13 || item_count == 14 {
                 let mut it = decoder.iter();
                 let number: U256 = decode_field_u256_le(&next(&mut it)?, "number")?;
                 let hash: H256 = decode_field_h256(&next(&mut it)?, "hash")?;
@@ -267,6 +298,15 @@ impl Decodable for L2Block {
                     decode_transaction_hash_list(&next(&mut it)?, "transactions")?;
                 let gas_used: U256 = decode_field_u256_le(&next(&mut it)?, "gas_used")?;
                 let timestamp = decode_timestamp(&next(&mut it)?)?;
+                let base_fee_per_gas: Option<U256> = if item_count == 14 {
+                    decode_option_explicit(
+                        &next(&mut it)?,
+                        "base_fee_per_gas",
+                        decode_field_u256_le,
+                    )?
+                } else {
+                    None
+                };
                 Ok(L2Block {
                     number,
                     hash,
@@ -281,6 +321,7 @@ impl Decodable for L2Block {
                     gas_used,
                     timestamp,
                     transactions,
+                    base_fee_per_gas,
                 })
             } else {
                 Err(DecoderError::RlpIncorrectListLen)
@@ -294,7 +335,7 @@ impl Decodable for L2Block {
 #[cfg(test)]
 mod tests {
 
-    use super::L2Block;
+    use super::{BlockFees, L2Block};
     use crate::rlp_helpers::FromRlpBytes;
     use crate::transaction::TRANSACTION_HASH_SIZE;
     use primitive_types::{H256, U256};
@@ -324,5 +365,34 @@ mod tests {
             let v: L2Block = dummy_block(tx_length);
             block_encoding_roundtrip(v);
         }
+        block_encoding_roundtrip(L2Block {
+            base_fee_per_gas: Some(U256::from(21000)),
+            ..dummy_block(1)
+        });
+    }
+
+    #[test]
+    fn decode_block_without_base_fee() {
+        let block = dummy_block(2);
+        let bytes = block.rlp_bytes();
+        let rlp = rlp::Rlp::new(&bytes);
+        let mut stream = rlp::RlpStream::new_list(13);
+        for item in rlp.iter().take(13) {
+            stream.append_raw(item.as_raw(), 1);
+        }
+        let decoded =
+            L2Block::from_rlp_bytes(&stream.out()).expect("L2Block should be decodable");
+        assert_eq!(block, decoded);
+    }
+
+    #[test]
+    fn gas_for_da_fee_is_rounded_up() {
+        let block_fees = BlockFees::new(U256::from(10), U256::from(25));
+        assert_eq!(block_fees.gas_for_da_fee(4, U256::from(10)), 10);
+        assert_eq!(block_fees.gas_for_da_fee(3, U256::from(10)), 8);
+        assert_eq!(block_fees.gas_for_da_fee(0, U256::from(10)), 0);
+        assert_eq!(block_fees.gas_for_da_fee(3, U256::zero()), 0);
+        let block_fees = BlockFees::new(U256::one(), U256::MAX);
+        assert_eq!(block_fees.gas_for_da_fee(2, U256::one()), u64::MAX);
     }
 }
@@ -0,0 +1,111 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! FA tickets bridged to the rollup.
+//!
+//! A ticket is identified by its ticketer and its FA2.1 content, the token
+//! id and optional metadata. Each ticket identity is wrapped on the rollup by
+//! an ERC-20 contract at an address derived from the identity.
+
+use primitive_types::{H160, U256};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use sha3::{Digest, Keccak256};
+use tezos_crypto_rs::hash::{ContractKt1Hash, HashTrait};
+
+use crate::rlp_helpers::{decode_field, next};
+
+/// Identity of a FA ticket, its amount excluded.
+///
+/// Encoding: `[ticketer, token_id, metadata]`, where the ticketer is the 20
+/// bytes of the KT1 hash, and the metadata a list of zero or one bytes.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FaTicket {
+    /// Contract that created the ticket.
+    pub ticketer: ContractKt1Hash,
+    pub token_id: U256,
+    pub metadata: Option<Vec<u8>>,
+}
+
+impl FaTicket {
+    /// Address of the ERC-20 contract wrapping the ticket, the last 20 bytes
+    /// of the Keccak256 hash of the ticket encoding.
+    pub fn wrapper_address(&self) -> H160 {
+        let hash = Keccak256::digest(self.rlp_bytes());
+        H160::from_slice(&hash[12..])
+    }
+}
+
+impl Encodable for FaTicket {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(3);
+        stream.append(&self.ticketer.as_ref().as_slice());
+        stream.append(&self.token_id);
+        stream.begin_list(self.metadata.iter().len());
+        if let Some(metadata) = &self.metadata {
+            stream.append(metadata);
+        }
+    }
+}
+
+impl Decodable for FaTicket {
+    fn decode(decoder: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let ticketer: Vec<u8> = decode_field(&next(&mut it)?, "ticketer")?;
+        let ticketer = ContractKt1Hash::try_from_bytes(&ticketer)
+            .map_err(|_| DecoderError::Custom("Invalid ticketer"))?;
+        let token_id = decode_field(&next(&mut it)?, "token_id")?;
+        let metadata: Vec<Vec<u8>> = next(&mut it)?.as_list()?;
+        let metadata = match metadata.len() {
+            0 => None,
+            1 => metadata.into_iter().next(),
+            _ => return Err(DecoderError::Custom("Invalid ticket metadata")),
+        };
+        Ok(Self {
+            ticketer,
+            token_id,
+            metadata,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticket(metadata: Option<Vec<u8>>) -> FaTicket {
+        FaTicket {
+            ticketer: ContractKt1Hash::from_b58check(
+                "KT1BuEZtb68c1Q4yjtckcNjGELqWt56Xyesc",
+            )
+            .unwrap(),
+            token_id: U256::from(42),
+            metadata,
+        }
+    }
+
+    #[test]
+    fn test_rlp_roundtrip() {
+        for metadata in [None, Some(vec![]), Some(vec![1, 2, 3])] {
+            let ticket = ticket(metadata);
+            let decoded = FaTicket::decode(&Rlp::new(&ticket.rlp_bytes())).unwrap();
+            assert_eq!(ticket, decoded);
+        }
+    }
+
+    #[test]
+    fn test_wrapper_address_depends_on_identity() {
+        let wrapper = ticket(None).wrapper_address();
+        assert_eq!(wrapper, ticket(None).wrapper_address());
+        assert_ne!(wrapper, ticket(Some(vec![])).wrapper_address());
+        let mut other_id = ticket(None);
+        other_id.token_id = U256::zero();
+        assert_ne!(wrapper, other_id.wrapper_address());
+    }
+}
@@ -0,0 +1,161 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Accounts allocated at genesis.
+//!
+//! The accounts are set in the durable storage by the installer, along with
+//! a [GenesisDeclaration] listing them and the state root they must have.
+//! The kernel adds them to the state trie in its first runs and checks the
+//! root.
+
+use std::collections::BTreeMap;
+
+use primitive_types::{H160, H256, U256};
+use rlp::RlpStream;
+use sha3::{Digest, Keccak256};
+
+/// An account allocated at genesis.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct GenesisAccount {
+    pub address: H160,
+    pub nonce: U256,
+    pub balance: U256,
+    pub code: Vec<u8>,
+    /// Non-zero values of the storage.
+    pub storage: BTreeMap<H256, H256>,
+}
+
+impl GenesisAccount {
+    /// An account is empty if it has no nonce, no balance and no code
+    /// (see EIP-161), it isn't part of the state trie.
+    pub fn is_empty(&self) -> bool {
+        self.nonce.is_zero() && self.balance.is_zero() && self.code.is_empty()
+    }
+
+    pub fn code_hash(&self) -> H256 {
+        H256(Keccak256::digest(&self.code).into())
+    }
+
+    pub fn storage_root(&self) -> H256 {
+        let slots = self
+            .storage
+            .iter()
+            .filter(|(_, value)| !value.is_zero())
+            .map(|(index, value)| {
+                let value = U256::from_big_endian(value.as_bytes());
+                (index.as_bytes().to_vec(), rlp::encode(&value).to_vec())
+            });
+        ethereum::util::sec_trie_root(slots)
+    }
+
+    /// Encoding of the account in the state trie.
+    fn leaf(&self) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(4);
+        stream.append(&self.nonce);
+        stream.append(&self.balance);
+        stream.append(&self.storage_root());
+        stream.append(&self.code_hash());
+        stream.out().to_vec()
+    }
+}
+
+/// Root of the state trie containing the `accounts`.
+pub fn state_root(accounts: &[GenesisAccount]) -> H256 {
+    let leaves = accounts
+        .iter()
+        .filter(|account| !account.is_empty())
+        .map(|account| (account.address.as_bytes().to_vec(), account.leaf()));
+    ethereum::util::sec_trie_root(leaves)
+}
+
+/// The accounts allocated at genesis, with the indexes of their storage,
+/// and the state root they must have.
+///
+/// The accounts are sorted by address, and the ones without any value in the
+/// storage of the kernel aren't declared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenesisDeclaration {
+    pub state_root: H256,
+    pub accounts: Vec<(H160, Vec<H256>)>,
+}
+
+impl GenesisDeclaration {
+    pub fn new(accounts: &[GenesisAccount]) -> Self {
+        let mut declared: Vec<(H160, Vec<H256>)> = accounts
+            .iter()
+            .filter(|account| !account.is_empty() || !account.storage.is_empty())
+            .map(|account| (account.address, account.storage.keys().copied().collect()))
+            .collect();
+        declared.sort_by_key(|(address, _)| *address);
+        Self {
+            state_root: state_root(accounts),
+            accounts: declared,
+        }
+    }
+
+    /// Encodings of the declared accounts, `[address, storage_indexes]`, in
+    /// the order the kernel adds them to the state trie.
+    pub fn encoded_accounts(&self) -> Vec<Vec<u8>> {
+        self.accounts
+            .iter()
+            .map(|(address, indexes)| {
+                let mut stream = RlpStream::new_list(2);
+                stream.append(address);
+                stream.append_list(indexes);
+                stream.out().to_vec()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rlp_helpers::{decode_field, decode_list};
+    use rlp::Rlp;
+
+    fn account(i: u8) -> GenesisAccount {
+        GenesisAccount {
+            address: H160::repeat_byte(i),
+            balance: U256::from(i),
+            ..GenesisAccount::default()
+        }
+    }
+
+    #[test]
+    fn test_empty_accounts_are_not_in_the_state() {
+        let accounts = vec![account(1), account(2)];
+        let mut with_empty = accounts.clone();
+        with_empty.push(account(0));
+        assert_eq!(state_root(&with_empty), state_root(&accounts));
+        assert_eq!(
+            state_root(&[]),
+            H256(Keccak256::digest(rlp::NULL_RLP).into())
+        );
+    }
+
+    #[test]
+    fn test_declared_accounts() {
+        let mut contract = account(3);
+        contract.code = vec![0x60, 0x00];
+        contract
+            .storage
+            .insert(H256::repeat_byte(1), H256::from_low_u64_be(1));
+        let declaration = GenesisDeclaration::new(&[contract, account(2), account(0)]);
+        // The accounts are sorted and the empty one isn't declared.
+        assert_eq!(
+            declaration.accounts,
+            vec![
+                (H160::repeat_byte(2), vec![]),
+                (H160::repeat_byte(3), vec![H256::repeat_byte(1)])
+            ]
+        );
+        let encoded = declaration.encoded_accounts();
+        let decoder = Rlp::new(&encoded[1]);
+        let address: H160 = decode_field(&decoder.at(0).unwrap(), "address").unwrap();
+        let indexes: Vec<H256> =
+            decode_list(&decoder.at(1).unwrap(), "storage_indexes").unwrap();
+        assert_eq!((address, indexes), declaration.accounts[1]);
+    }
+}
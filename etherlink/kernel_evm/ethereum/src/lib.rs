@@ -5,7 +5,10 @@
 pub mod access_list;
 pub mod block;
 pub mod eth_gen;
+pub mod fa_ticket;
+pub mod genesis;
 pub mod helpers;
+pub mod proof;
 pub mod rlp_helpers;
 pub mod transaction;
 pub mod tx_common;
@@ -0,0 +1,436 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Merkle proofs of accounts and storage slots, as returned by
+//! `eth_getProof`.
+//!
+//! A proof is the list of the nodes on the path from the root of a
+//! Merkle-Patricia trie to a key, each node being the RLP encoding whose hash
+//! is referenced by the previous one. Nodes shorter than 32 bytes are inlined
+//! in their parent and are not part of the list.
+
+use primitive_types::{H160, H256, U256};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use sha3::{Digest, Keccak256};
+use thiserror::Error;
+
+use crate::rlp_helpers::{decode_field, decode_list, next};
+
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum ProofError {
+    #[error("Error decoding a proof node: {0}")]
+    Decoder(#[from] DecoderError),
+
+    #[error("Missing proof node for hash {0}")]
+    MissingNode(H256),
+
+    #[error("Proof node doesn't match the hash {0}")]
+    InvalidHash(H256),
+
+    #[error("Unexpected node after the end of the proof")]
+    UnexpectedNode,
+
+    #[error("The account proof doesn't match the fields of {0}")]
+    AccountMismatch(H160),
+
+    #[error("The storage proof doesn't match the value of slot {0}")]
+    StorageMismatch(H256),
+}
+
+pub fn keccak(bytes: &[u8]) -> H256 {
+    H256(Keccak256::digest(bytes).into())
+}
+
+/// Root of the empty trie.
+fn empty_trie_root() -> H256 {
+    keccak(&rlp::NULL_RLP)
+}
+
+/// Where to go after a node of the proof.
+enum Step {
+    Value(Option<Vec<u8>>),
+    Inline(Vec<u8>),
+    Hash(H256),
+}
+
+fn step_to_child(child: &Rlp<'_>) -> Result<Step, DecoderError> {
+    if child.is_list() {
+        return Ok(Step::Inline(child.as_raw().to_vec()));
+    }
+    let data = child.data()?;
+    match data.len() {
+        0 => Ok(Step::Value(None)),
+        32 => Ok(Step::Hash(H256::from_slice(data))),
+        _ => Err(DecoderError::Custom("Invalid trie node reference")),
+    }
+}
+
+/// Nibbles of a key of the trie.
+pub fn key_nibbles(key: &H256) -> Vec<u8> {
+    key.as_bytes()
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect()
+}
+
+/// Hex-prefix encoding of a path, see appendix C of the Yellow Paper.
+pub fn compact_path(path: &[u8], is_leaf: bool) -> Vec<u8> {
+    let flag: u8 = if is_leaf { 2 } else { 0 };
+    let mut bytes = Vec::with_capacity(path.len() / 2 + 1);
+    let rest = if path.len() % 2 == 1 {
+        bytes.push(((flag + 1) << 4) | path[0]);
+        &path[1..]
+    } else {
+        bytes.push(flag << 4);
+        path
+    };
+    for pair in rest.chunks(2) {
+        bytes.push((pair[0] << 4) | pair[1]);
+    }
+    bytes
+}
+
+/// Decodes a hex-prefix encoded path, returns its nibbles and whether it is
+/// the path of a leaf.
+pub fn path_of_compact(bytes: &[u8]) -> Result<(Vec<u8>, bool), DecoderError> {
+    let (first, rest) = bytes
+        .split_first()
+        .ok_or(DecoderError::Custom("Empty trie node path"))?;
+    let flag = first >> 4;
+    let mut path = Vec::with_capacity(rest.len() * 2 + 1);
+    if flag & 1 == 1 {
+        path.push(first & 0x0f);
+    }
+    for byte in rest {
+        path.push(byte >> 4);
+        path.push(byte & 0x0f);
+    }
+    Ok((path, flag & 2 == 2))
+}
+
+/// Follows `node` along `path`, and returns the next step and the rest of
+/// the path.
+fn step<'p>(node: &[u8], path: &'p [u8]) -> Result<(Step, &'p [u8]), DecoderError> {
+    let node = Rlp::new(node);
+    match node.item_count()? {
+        2 => {
+            let (node_path, is_leaf) = path_of_compact(node.at(0)?.data()?)?;
+            match path.strip_prefix(node_path.as_slice()) {
+                Some(rest) if is_leaf && rest.is_empty() => {
+                    Ok((Step::Value(Some(node.at(1)?.data()?.to_vec())), rest))
+                }
+                Some(rest) if !is_leaf => Ok((step_to_child(&node.at(1)?)?, rest)),
+                _ => Ok((Step::Value(None), path)),
+            }
+        }
+        17 => {
+            let (nibble, rest) = path
+                .split_first()
+                .ok_or(DecoderError::Custom("Trie key too short"))?;
+            Ok((step_to_child(&node.at(*nibble as usize)?)?, rest))
+        }
+        _ => Err(DecoderError::RlpIncorrectListLen),
+    }
+}
+
+/// Verifies the proof of `key` in the trie of root `root`. Returns the value
+/// at `key`, or `None` if the proof shows that there is no value at `key`.
+pub fn verify_proof(
+    root: &H256,
+    key: &H256,
+    proof: &[Vec<u8>],
+) -> Result<Option<Vec<u8>>, ProofError> {
+    let mut nodes = proof.iter();
+    let nibbles = key_nibbles(key);
+    let mut path = nibbles.as_slice();
+    let mut next_step = if *root == empty_trie_root() {
+        Step::Value(None)
+    } else {
+        Step::Hash(*root)
+    };
+    loop {
+        let node = match next_step {
+            Step::Value(value) => {
+                return match nodes.next() {
+                    None => Ok(value),
+                    Some(_) => Err(ProofError::UnexpectedNode),
+                }
+            }
+            Step::Inline(node) => node,
+            Step::Hash(hash) => {
+                let node = nodes.next().ok_or(ProofError::MissingNode(hash))?;
+                if keccak(node) != hash {
+                    return Err(ProofError::InvalidHash(hash));
+                }
+                node.clone()
+            }
+        };
+        (next_step, path) = step(&node, path)?;
+    }
+}
+
+/// Proof of the value of a storage slot, see [AccountProof].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StorageProof {
+    /// Index of the slot.
+    pub key: H256,
+    /// Value of the slot, zero if the slot is not in the storage trie.
+    pub value: U256,
+    /// Nodes from the storage root to the slot.
+    pub proof: Vec<Vec<u8>>,
+}
+
+/// Proof of the fields of an account and of some of its storage slots,
+/// against a state root.
+///
+/// Encoding: `[address, balance, nonce, code_hash, storage_hash,
+/// [account_proof], [[key, value, [proof]]]]`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AccountProof {
+    pub address: H160,
+    pub balance: U256,
+    pub nonce: U256,
+    pub code_hash: H256,
+    /// Root of the storage trie of the account.
+    pub storage_hash: H256,
+    /// Nodes from the state root to the account.
+    pub account_proof: Vec<Vec<u8>>,
+    pub storage_proof: Vec<StorageProof>,
+}
+
+impl AccountProof {
+    /// Encoding of the account in the state trie, `None` if the account is
+    /// empty and is therefore not in the state trie (see EIP-161).
+    fn account_leaf(&self) -> Option<Vec<u8>> {
+        if self.nonce.is_zero()
+            && self.balance.is_zero()
+            && self.code_hash == keccak(&[])
+            && self.storage_hash == empty_trie_root()
+        {
+            return None;
+        }
+        let mut stream = RlpStream::new_list(4);
+        stream.append(&self.nonce);
+        stream.append(&self.balance);
+        stream.append(&self.storage_hash);
+        stream.append(&self.code_hash);
+        Some(stream.out().to_vec())
+    }
+
+    /// Verifies the fields of the account and the values of the slots
+    /// against `state_root`.
+    pub fn verify(&self, state_root: &H256) -> Result<(), ProofError> {
+        let key = keccak(self.address.as_bytes());
+        let leaf = verify_proof(state_root, &key, &self.account_proof)?;
+        if leaf != self.account_leaf() {
+            return Err(ProofError::AccountMismatch(self.address));
+        }
+        for slot in &self.storage_proof {
+            let key = keccak(slot.key.as_bytes());
+            let value = verify_proof(&self.storage_hash, &key, &slot.proof)?;
+            let expected =
+                (!slot.value.is_zero()).then(|| rlp::encode(&slot.value).to_vec());
+            if value != expected {
+                return Err(ProofError::StorageMismatch(slot.key));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn append_nodes(stream: &mut RlpStream, nodes: &[Vec<u8>]) {
+    stream.append_list::<Vec<u8>, Vec<u8>>(nodes);
+}
+
+impl Encodable for StorageProof {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(3);
+        stream.append(&self.key);
+        stream.append(&self.value);
+        append_nodes(stream, &self.proof);
+    }
+}
+
+impl Decodable for StorageProof {
+    fn decode(decoder: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let key = decode_field(&next(&mut it)?, "key")?;
+        let value = decode_field(&next(&mut it)?, "value")?;
+        let proof = decode_list(&next(&mut it)?, "proof")?;
+        Ok(Self { key, value, proof })
+    }
+}
+
+impl Encodable for AccountProof {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(7);
+        stream.append(&self.address);
+        stream.append(&self.balance);
+        stream.append(&self.nonce);
+        stream.append(&self.code_hash);
+        stream.append(&self.storage_hash);
+        append_nodes(stream, &self.account_proof);
+        stream.append_list(&self.storage_proof);
+    }
+}
+
+impl Decodable for AccountProof {
+    fn decode(decoder: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 7 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let address = decode_field(&next(&mut it)?, "address")?;
+        let balance = decode_field(&next(&mut it)?, "balance")?;
+        let nonce = decode_field(&next(&mut it)?, "nonce")?;
+        let code_hash = decode_field(&next(&mut it)?, "code_hash")?;
+        let storage_hash = decode_field(&next(&mut it)?, "storage_hash")?;
+        let account_proof = decode_list(&next(&mut it)?, "account_proof")?;
+        let storage_proof = decode_list(&next(&mut it)?, "storage_proof")?;
+        Ok(Self {
+            address,
+            balance,
+            nonce,
+            code_hash,
+            storage_hash,
+            account_proof,
+            storage_proof,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Trie with a single leaf, whose path is the whole key.
+    fn single_leaf_trie(key: &H256, value: &[u8]) -> (H256, Vec<u8>) {
+        let mut path = vec![0x20];
+        path.extend_from_slice(key.as_bytes());
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&path);
+        stream.append(&value);
+        let node = stream.out().to_vec();
+        (keccak(&node), node)
+    }
+
+    #[test]
+    fn test_compact_path() {
+        assert_eq!(
+            compact_path(&[1, 2, 3, 4, 5], false),
+            vec![0x11, 0x23, 0x45]
+        );
+        assert_eq!(
+            compact_path(&[0, 1, 2, 3, 4, 5], false),
+            vec![0x00, 0x01, 0x23, 0x45]
+        );
+        assert_eq!(
+            compact_path(&[0, 15, 1, 12, 11, 8], true),
+            vec![0x20, 0x0f, 0x1c, 0xb8]
+        );
+        assert_eq!(
+            compact_path(&[15, 1, 12, 11, 8], true),
+            vec![0x3f, 0x1c, 0xb8]
+        );
+        for (path, is_leaf) in
+            [(vec![1, 2, 3], true), (vec![], false), (vec![7, 0], true)]
+        {
+            assert_eq!(
+                path_of_compact(&compact_path(&path, is_leaf)).unwrap(),
+                (path, is_leaf)
+            )
+        }
+    }
+
+    #[test]
+    fn test_verify_single_leaf() {
+        let key = keccak(b"key");
+        let value = vec![42; 40];
+        let (root, node) = single_leaf_trie(&key, &value);
+
+        // The root matches the reference implementation
+        let mut entries = std::collections::BTreeMap::new();
+        entries.insert(b"key".to_vec(), value.clone());
+        assert_eq!(ethereum::util::sec_trie_root(&entries), root);
+
+        let proof = vec![node.clone()];
+        assert_eq!(verify_proof(&root, &key, &proof), Ok(Some(value)));
+        // Another key is proven absent
+        assert_eq!(verify_proof(&root, &keccak(b"other"), &proof), Ok(None));
+        // The proof must start from the root
+        assert_eq!(
+            verify_proof(&keccak(b"root"), &key, &proof),
+            Err(ProofError::InvalidHash(keccak(b"root")))
+        );
+        assert_eq!(
+            verify_proof(&root, &key, &[]),
+            Err(ProofError::MissingNode(root))
+        );
+        assert_eq!(
+            verify_proof(&root, &key, &[node.clone(), node]),
+            Err(ProofError::UnexpectedNode)
+        );
+    }
+
+    #[test]
+    fn test_verify_empty_trie() {
+        let key = keccak(b"key");
+        assert_eq!(verify_proof(&empty_trie_root(), &key, &[]), Ok(None));
+    }
+
+    #[test]
+    fn test_verify_empty_account() {
+        let proof = AccountProof {
+            address: H160::from_low_u64_be(1),
+            balance: U256::zero(),
+            nonce: U256::zero(),
+            code_hash: keccak(&[]),
+            storage_hash: empty_trie_root(),
+            account_proof: vec![],
+            storage_proof: vec![StorageProof {
+                key: H256::zero(),
+                value: U256::zero(),
+                proof: vec![],
+            }],
+        };
+        assert_eq!(proof.verify(&empty_trie_root()), Ok(()));
+        let proof = AccountProof {
+            balance: U256::one(),
+            ..proof
+        };
+        assert_eq!(
+            proof.verify(&empty_trie_root()),
+            Err(ProofError::AccountMismatch(proof.address))
+        );
+    }
+
+    #[test]
+    fn test_account_proof_encoding_roundtrip() {
+        let proof = AccountProof {
+            address: H160::from_low_u64_be(1),
+            balance: U256::from(1000),
+            nonce: U256::from(3),
+            code_hash: keccak(&[1, 2, 3]),
+            storage_hash: keccak(&[4, 5, 6]),
+            account_proof: vec![vec![1, 2, 3], vec![0xc0; 40]],
+            storage_proof: vec![StorageProof {
+                key: H256::from_low_u64_be(2),
+                value: U256::from(42),
+                proof: vec![vec![7; 50]],
+            }],
+        };
+        let bytes = proof.rlp_bytes();
+        let decoded = AccountProof::decode(&Rlp::new(&bytes)).unwrap();
+        assert_eq!(decoded, proof);
+    }
+}
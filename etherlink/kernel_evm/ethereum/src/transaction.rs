@@ -130,6 +130,9 @@ pub struct TransactionReceipt {
     pub effective_gas_price: U256,
     /// The amount of gas used by this specific transaction alone.
     pub gas_used: U256,
+    /// The data availability fee paid by the transaction, in Wei. It is
+    /// included in `gas_used` at the effective gas price.
+    pub da_fee: U256,
     /// The contract address created, if the transaction was a contract creation, otherwise null.
     pub contract_address: Option<H160>,
     /// The logs emitted during contract execution
@@ -155,6 +158,27 @@ impl TransactionReceipt {
         }
         bloom
     }
+
+    /// Encoding of the receipt in the receipts trie of a block, as defined by
+    /// EIP-658 and EIP-2718.
+    pub fn trie_encoding(&self) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(4);
+        match self.status {
+            TransactionStatus::Success => stream.append(&1u8),
+            TransactionStatus::Failure => stream.append_empty_data(),
+        };
+        stream.append(&self.cumulative_gas_used);
+        stream.append(&self.logs_bloom);
+        stream.begin_list(self.logs.len());
+        for log in &self.logs {
+            stream.append(&log.log);
+        }
+        let mut bytes = stream.out().to_vec();
+        if self.type_ != TransactionType::Legacy {
+            bytes.insert(0, self.type_.into());
+        }
+        bytes
+    }
 }
 
 impl Decodable for TransactionReceipt {
@@ -162,7 +186,9 @@ impl Decodable for TransactionReceipt {
         if !decoder.is_list() {
             return Err(DecoderError::RlpExpectedToBeList);
         }
-        if Ok(13) != decoder.item_count() {
+        // Receipts stored before the data availability fee have 13 fields.
+        let item_count = decoder.item_count()?;
+        if item_count != 13 && item_count != 14 {
             return Err(DecoderError::RlpIncorrectListLen);
         }
 
@@ -183,6 +209,11 @@ impl Decodable for TransactionReceipt {
         let logs_bloom = decode_field(&next(&mut it)?, "logs_bloom")?;
         let type_: TransactionType = decode_transaction_type(&next(&mut it)?)?;
         let status: TransactionStatus = decode_transaction_status(&next(&mut it)?)?;
+        let da_fee: U256 = if item_count == 14 {
+            decode_field_u256_le(&next(&mut it)?, "da_fee")?
+        } else {
+            U256::zero()
+        };
         Ok(Self {
             hash,
             index,
@@ -192,6 +223,7 @@ impl Decodable for TransactionReceipt {
             cumulative_gas_used,
             effective_gas_price,
             gas_used,
+            da_fee,
             contract_address,
             logs,
             logs_bloom,
@@ -203,7 +235,7 @@ impl Decodable for TransactionReceipt {
 
 impl Encodable for TransactionReceipt {
     fn rlp_append(&self, stream: &mut RlpStream) {
-        stream.begin_list(13);
+        stream.begin_list(14);
         stream.append(&self.hash.to_vec());
         stream.append(&self.index);
         append_u256_le(stream, &self.block_number);
@@ -223,6 +255,7 @@ impl Encodable for TransactionReceipt {
         stream.append(&self.logs_bloom);
         stream.append::<u8>(&self.type_.into());
         stream.append::<u8>(&self.status.into());
+        append_u256_le(stream, &self.da_fee);
     }
 }
 
@@ -352,6 +385,7 @@ mod test {
             cumulative_gas_used: U256::from(1252345235),
             effective_gas_price: U256::from(47457345),
             gas_used: U256::from(474573452),
+            da_fee: U256::from(3452),
             contract_address: Some(address_of_str(
                 "4335353535353535353535353535353535353543",
             )),
@@ -392,9 +426,64 @@ mod test {
         let v2 = TransactionReceipt {
             to: None,
             contract_address: None,
-            ..v
+            ..v.clone()
         };
         receipt_encoding_roundtrip(v2);
+
+        // Receipts without the data availability fee are still decodable
+        let bytes = v.rlp_bytes();
+        let rlp = Rlp::new(&bytes);
+        let mut stream = RlpStream::new_list(13);
+        for item in rlp.iter().take(13) {
+            stream.append_raw(item.as_raw(), 1);
+        }
+        let decoded = TransactionReceipt::from_rlp_bytes(&stream.out())
+            .expect("Transaction receipt should be decodable");
+        assert_eq!(
+            decoded,
+            TransactionReceipt {
+                da_fee: U256::zero(),
+                ..v
+            }
+        );
+    }
+
+    #[test]
+    fn test_receipt_trie_encoding() {
+        let logs = vec![IndexedLog {
+            log: Log {
+                address: address_of_str("ef2d6d194084c2de36e0dabfce45d046b37d1106"),
+                topics: vec![H256::from_low_u64_be(1), H256::from_low_u64_be(2)],
+                data: vec![0, 1, 2, 3],
+            },
+            index: 3,
+        }];
+        let receipt = tx_receipt(logs);
+        let data = ethereum::EIP658ReceiptData {
+            status_code: 1,
+            used_gas: receipt.cumulative_gas_used,
+            logs_bloom: receipt.logs_bloom,
+            logs: receipt.logs.iter().map(|log| log.log.clone()).collect(),
+        };
+
+        // The reference implementation is the `ethereum` crate
+        let expected = ethereum::EnvelopedEncodable::encode(
+            &ethereum::ReceiptV3::Legacy(data.clone()),
+        );
+        assert_eq!(receipt.trie_encoding(), expected.to_vec());
+
+        let receipt = TransactionReceipt {
+            type_: TransactionType::Eip1559,
+            status: TransactionStatus::Failure,
+            ..receipt
+        };
+        let expected = ethereum::EnvelopedEncodable::encode(
+            &ethereum::ReceiptV3::EIP1559(ethereum::EIP658ReceiptData {
+                status_code: 0,
+                ..data
+            }),
+        );
+        assert_eq!(receipt.trie_encoding(), expected.to_vec());
     }
 
     fn object_encoding_roundtrip(v: TransactionObject) {
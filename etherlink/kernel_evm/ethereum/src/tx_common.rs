@@ -70,15 +70,8 @@ pub struct EthereumTransactionCommon {
     /// A scalar value equal to the number of transactions sent by the sender
     pub nonce: U256,
 
-    /// Normally, this would be a fee paid per gas in addition to base fee per gas.
-    /// This would incentivise miners to include the transaction.
+    /// Fee paid per gas in addition to base fee per gas, to the sequencer.
     /// More details see here https://eips.ethereum.org/EIPS/eip-1559#abstract
-    ///
-    /// We choose to ignore this, however, as we actually do not implement eip-1559
-    /// mechanism exactly. The sequencer is compensated via the data availability fee
-    /// and gas fee.
-    ///
-    /// We keep this field purely for compatibility with existing ethereum tooling.
     max_priority_fee_per_gas: U256,
     /// Maximum amount of fee to be paid per gas.
     /// Thus, as a transaction might be included in the block
@@ -93,9 +86,8 @@ pub struct EthereumTransactionCommon {
 
     /// The maximum amount of gas that the user is willing to pay.
     ///
-    /// *NB* this is inclusive of any additional fees that are paid, prior to execution:
-    /// - flat fee
-    /// - data availability fee
+    /// *NB* this is inclusive of the data availability fee, paid prior to
+    /// execution.
     ///
     /// For the execution gas limit, see [execution_gas_limit].
     gas_limit: u64,
@@ -566,21 +558,36 @@ impl EthereumTransactionCommon {
         let block_base_fee_per_gas = block_fees.base_fee_per_gas();
 
         if self.max_fee_per_gas >= block_base_fee_per_gas {
-            Ok(block_base_fee_per_gas)
+            Ok(self.effective_gas_price(block_fees))
         } else {
             Err(anyhow::anyhow!("Underflow when calculating gas price"))
         }
     }
 
-    /// Returns the gas limit for executing this transaction.
-    ///
-    /// This is strictly lower than the gas limit set by the user, as additional gas is required
-    /// in order to pay for the *flat fee* and *data availability fee*.
+    /// Returns the gas price paid by the transaction: the base fee per gas,
+    /// plus the priority fee capped by the max fee per gas (EIP-1559).
+    pub fn effective_gas_price(&self, block_fees: &BlockFees) -> U256 {
+        let base_fee_per_gas = block_fees.base_fee_per_gas();
+        let priority_fee_per_gas = self
+            .max_priority_fee_per_gas
+            .min(self.max_fee_per_gas.saturating_sub(base_fee_per_gas));
+        base_fee_per_gas.saturating_add(priority_fee_per_gas)
+    }
+
+    /// Returns the gas limit set by the user, which includes the gas paying for
+    /// the *data availability fee*.
+    pub fn gas_limit_with_fees(&self) -> u64 {
+        self.gas_limit
+    }
+
+    /// Returns the gas limit for executing this transaction, once `gas_for_fees`
+    /// is set aside to pay for the *data availability fee*. Returns `None` if
+    /// the gas limit set by the user does not cover the fees.
     ///
-    /// The user pre-pays this (in addition to the flat fee & data availability fee) prior to execution.
+    /// The user pre-pays this (in addition to the data availability fee) prior to execution.
     /// If execution does not use all of the execution gas limit, they will be partially refunded.
-    pub fn execution_gas_limit(&self) -> u64 {
-        self.gas_limit
+    pub fn execution_gas_limit(&self, gas_for_fees: u64) -> Option<u64> {
+        self.gas_limit.checked_sub(gas_for_fees)
     }
 }
 
@@ -2,11 +2,58 @@
 //
 // SPDX-License-Identifier: MIT
 
-//! Withdrawals to layer 1 from the EVM kernel
+//! Withdrawals and calls to layer 1 from the EVM kernel
 
+use crate::fa_ticket::FaTicket;
 use primitive_types::U256;
-use tezos_data_encoding::nom::NomReader;
+use tezos_crypto_rs::hash::ContractKt1Hash;
+use tezos_data_encoding::enc::{BinResult, BinWriter};
+use tezos_data_encoding::encoding::{Encoding, HasEncoding};
+use tezos_data_encoding::nom::error::DecodeError;
+use tezos_data_encoding::nom::{NomReader, NomResult};
 use tezos_smart_rollup_encoding::contract::Contract;
+use tezos_smart_rollup_encoding::entrypoint::Entrypoint;
+use tezos_smart_rollup_encoding::michelson::Michelson;
+
+/// Maximum nesting of Micheline expressions accepted from the EVM.
+const MAX_MICHELINE_DEPTH: usize = 1_000;
+
+/// Index of the `Ticket` primitive, the last one of the Michelson encoding.
+const TICKET_PRIMITIVE: u8 = 157;
+
+/// Indices of the primitives of the Michelson encoding known to the type
+/// check of [MichelineExpr::has_ticket_free_type].
+mod primitives {
+    pub const D_FALSE: u8 = 3;
+    pub const D_ELT: u8 = 4;
+    pub const D_LEFT: u8 = 5;
+    pub const D_NONE: u8 = 6;
+    pub const D_PAIR: u8 = 7;
+    pub const D_RIGHT: u8 = 8;
+    pub const D_SOME: u8 = 9;
+    pub const D_TRUE: u8 = 10;
+    pub const D_UNIT: u8 = 11;
+    pub const T_BOOL: u8 = 89;
+    pub const T_INT: u8 = 91;
+    pub const T_KEY: u8 = 92;
+    pub const T_KEY_HASH: u8 = 93;
+    pub const T_LIST: u8 = 95;
+    pub const T_MAP: u8 = 96;
+    pub const T_NAT: u8 = 98;
+    pub const T_OPTION: u8 = 99;
+    pub const T_OR: u8 = 100;
+    pub const T_PAIR: u8 = 101;
+    pub const T_SET: u8 = 102;
+    pub const T_SIGNATURE: u8 = 103;
+    pub const T_STRING: u8 = 104;
+    pub const T_BYTES: u8 = 105;
+    pub const T_MUTEZ: u8 = 106;
+    pub const T_TIMESTAMP: u8 = 107;
+    pub const T_UNIT: u8 = 108;
+    pub const T_ADDRESS: u8 = 110;
+}
+
+use primitives::*;
 
 /// A single withdrawal from the rollup to an account on layer one.
 #[derive(Debug, Eq, PartialEq)]
@@ -15,8 +62,10 @@ pub struct Withdrawal {
     pub target: Contract,
     /// The amount in wei we wish to transfer. This has to be
     /// translated into CTEZ or whatever currency is used for
-    /// paying for L2XTZ.
+    /// paying for L2XTZ. For FA tickets, the amount of the ticket.
     pub amount: U256,
+    /// The FA ticket withdrawn, `None` for the native token.
+    pub ticket: Option<FaTicket>,
 }
 
 impl Withdrawal {
@@ -31,3 +80,543 @@ impl Withdrawal {
         Contract::from_b58check(s).ok()
     }
 }
+
+/// A call from the rollup to a smart contract on layer one.
+#[derive(Debug, Eq, PartialEq)]
+pub struct L1Call {
+    /// The contract called on layer one.
+    pub destination: ContractKt1Hash,
+    /// The entrypoint of the destination that is called.
+    pub entrypoint: Entrypoint,
+    /// The parameters of the call.
+    pub parameters: MichelineExpr,
+    /// The type of the parameters, layer one checks that it is the type of
+    /// the entrypoint.
+    pub parameters_ty: MichelineExpr,
+}
+
+/// A message sent from the rollup to layer one, in the order it was emitted
+/// during execution.
+#[derive(Debug, Eq, PartialEq)]
+pub enum L1Message {
+    Withdrawal(Withdrawal),
+    Call(L1Call),
+}
+
+/// A single Micheline expression in binary format.
+///
+/// The expression is only checked to be well-formed, its typing is left to
+/// layer one when the message is executed. As layer one reads the tickets of
+/// the parameters of a rollup from its own tickets, expressions sent from the
+/// EVM must also be checked with [MichelineExpr::has_ticket_free_type].
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct MichelineExpr(Vec<u8>);
+
+impl MichelineExpr {
+    /// Checks that the bytes are exactly one well-formed expression.
+    pub fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
+        if expr_length(&bytes, 0)? == bytes.len() {
+            Some(Self(bytes))
+        } else {
+            None
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Checks that the expression is a value of the type `ty`, which is built
+    /// from types that can't contain a ticket: `unit`, `bool`, `int`, `nat`,
+    /// `mutez`, `timestamp`, `string`, `bytes`, `address`, `key`,
+    /// `key_hash`, `signature`, `option`, `or`, `pair`, `list`, `set` and
+    /// `map`. Annotations are ignored.
+    pub fn has_ticket_free_type(&self, ty: &MichelineExpr) -> bool {
+        match (parse(&self.0), parse(&ty.0)) {
+            (Some((value, [])), Some((ty, []))) => {
+                is_ticket_free_type(&ty) && has_type(&value, &ty)
+            }
+            _ => false,
+        }
+    }
+
+    /// The expression `Pair <bytes> <self>`, where `bytes` is a bytes
+    /// literal.
+    pub fn paired_with_bytes(&self, bytes: &[u8]) -> Self {
+        // Pair primitive with two arguments and no annotations.
+        let mut pair = vec![7, D_PAIR];
+        // Bytes literal.
+        pair.push(10);
+        pair.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        pair.extend_from_slice(bytes);
+        pair.extend_from_slice(&self.0);
+        Self(pair)
+    }
+
+    /// The type `pair bytes <self>`.
+    pub fn paired_with_bytes_type(&self) -> Self {
+        // Pair type with two arguments and no annotations, and bytes type
+        // without arguments nor annotations.
+        let mut pair = vec![7, T_PAIR, 3, T_BYTES];
+        pair.extend_from_slice(&self.0);
+        Self(pair)
+    }
+}
+
+impl HasEncoding for MichelineExpr {
+    fn encoding() -> Encoding {
+        Encoding::Custom
+    }
+}
+
+impl NomReader for MichelineExpr {
+    fn nom_read(input: &[u8]) -> NomResult<Self> {
+        match expr_length(input, 0) {
+            Some(length) => Ok((&input[length..], Self(input[..length].to_vec()))),
+            None => Err(nom::Err::Error(DecodeError::invalid_tag(
+                input,
+                "Micheline expression".to_owned(),
+            ))),
+        }
+    }
+}
+
+impl BinWriter for MichelineExpr {
+    fn bin_write(&self, output: &mut Vec<u8>) -> BinResult {
+        output.extend_from_slice(&self.0);
+        Ok(())
+    }
+}
+
+impl Michelson for MichelineExpr {}
+
+/// Length of the Micheline expression at the start of `bytes`, `None` if it
+/// is malformed or nested deeper than [MAX_MICHELINE_DEPTH].
+fn expr_length(bytes: &[u8], depth: usize) -> Option<usize> {
+    if depth > MAX_MICHELINE_DEPTH {
+        return None;
+    }
+    let (tag, rest) = bytes.split_first()?;
+    let length = match tag {
+        // Integer, as a Zarith number.
+        0 => rest.iter().position(|byte| byte & 0x80 == 0)? + 1,
+        // String and bytes.
+        1 | 10 => sized_length(rest)?,
+        // Sequence.
+        2 => {
+            let length = sized_length(rest)?;
+            seq_check(&rest[4..length], depth + 1)?;
+            length
+        }
+        // Primitive applications, with 0 to 2 arguments, with or without
+        // annotations.
+        3..=8 => {
+            let arguments = (tag - 3) / 2;
+            let annotated = (tag - 3) % 2 == 1;
+            // The primitive itself.
+            let mut length = 1;
+            primitive(rest)?;
+            for _ in 0..arguments {
+                length += expr_length(rest.get(length..)?, depth + 1)?;
+            }
+            if annotated {
+                length += sized_length(rest.get(length..)?)?;
+            }
+            length
+        }
+        // Primitive application with a sequence of arguments and
+        // annotations.
+        9 => {
+            primitive(rest)?;
+            let arguments = sized_length(rest.get(1..)?)?;
+            seq_check(&rest[5..1 + arguments], depth + 1)?;
+            let annotations = sized_length(rest.get(1 + arguments..)?)?;
+            1 + arguments + annotations
+        }
+        _ => return None,
+    };
+    Some(1 + length)
+}
+
+/// Checks that `bytes` start with a primitive known to layer one.
+fn primitive(bytes: &[u8]) -> Option<u8> {
+    bytes
+        .first()
+        .copied()
+        .filter(|prim| *prim <= TICKET_PRIMITIVE)
+}
+
+/// Length of a field prefixed by its size on 4 bytes, size included.
+fn sized_length(bytes: &[u8]) -> Option<usize> {
+    let size = u32::from_be_bytes(bytes.get(..4)?.try_into().ok()?);
+    let length = (size as usize).checked_add(4)?;
+    bytes.get(..length)?;
+    Some(length)
+}
+
+/// Checks that the bytes are a concatenation of expressions.
+fn seq_check(mut bytes: &[u8], depth: usize) -> Option<()> {
+    while !bytes.is_empty() {
+        let length = expr_length(bytes, depth)?;
+        bytes = &bytes[length..];
+    }
+    Some(())
+}
+
+/// A Micheline expression read by the type check, without annotations.
+enum Node {
+    Int { negative: bool },
+    String,
+    Bytes,
+    Seq(Vec<Node>),
+    Prim(u8, Vec<Node>),
+}
+
+/// Parses the well-formed expression at the start of `bytes`, returns it
+/// with the rest of the bytes.
+fn parse(bytes: &[u8]) -> Option<(Node, &[u8])> {
+    let (tag, rest) = bytes.split_first()?;
+    match tag {
+        0 => {
+            let length = rest.iter().position(|byte| byte & 0x80 == 0)? + 1;
+            // The sign is the second bit of the first byte.
+            let negative = rest[0] & 0x40 != 0;
+            Some((Node::Int { negative }, &rest[length..]))
+        }
+        1 => Some((Node::String, &rest[sized_length(rest)?..])),
+        10 => Some((Node::Bytes, &rest[sized_length(rest)?..])),
+        2 => {
+            let length = sized_length(rest)?;
+            Some((Node::Seq(parse_seq(&rest[4..length])?), &rest[length..]))
+        }
+        3..=8 => {
+            let (prim, mut rest) = rest.split_first()?;
+            let mut arguments = vec![];
+            for _ in 0..(tag - 3) / 2 {
+                let (argument, next) = parse(rest)?;
+                arguments.push(argument);
+                rest = next;
+            }
+            if (tag - 3) % 2 == 1 {
+                rest = &rest[sized_length(rest)?..];
+            }
+            Some((Node::Prim(*prim, arguments), rest))
+        }
+        9 => {
+            let (prim, rest) = rest.split_first()?;
+            let length = sized_length(rest)?;
+            let arguments = parse_seq(&rest[4..length])?;
+            let rest = &rest[length..];
+            Some((Node::Prim(*prim, arguments), &rest[sized_length(rest)?..]))
+        }
+        _ => None,
+    }
+}
+
+/// Parses a concatenation of expressions.
+fn parse_seq(mut bytes: &[u8]) -> Option<Vec<Node>> {
+    let mut nodes = vec![];
+    while !bytes.is_empty() {
+        let (node, rest) = parse(bytes)?;
+        nodes.push(node);
+        bytes = rest;
+    }
+    Some(nodes)
+}
+
+/// Checks that `ty` is a type known to the type check, none of them can
+/// contain a ticket.
+fn is_ticket_free_type(ty: &Node) -> bool {
+    let Node::Prim(prim, arguments) = ty else {
+        return false;
+    };
+    let arity = match *prim {
+        T_UNIT | T_BOOL | T_INT | T_NAT | T_MUTEZ | T_TIMESTAMP | T_STRING | T_BYTES
+        | T_ADDRESS | T_KEY | T_KEY_HASH | T_SIGNATURE => 0..=0,
+        T_OPTION | T_LIST | T_SET => 1..=1,
+        T_OR | T_MAP => 2..=2,
+        T_PAIR => 2..=usize::MAX,
+        _ => return false,
+    };
+    arity.contains(&arguments.len()) && arguments.iter().all(is_ticket_free_type)
+}
+
+/// Checks that `value` has the type `ty`, known to the type check.
+fn has_type(value: &Node, ty: &Node) -> bool {
+    let Node::Prim(ty, types) = ty else {
+        return false;
+    };
+    match (value, *ty, types.as_slice()) {
+        (Node::Int { .. }, T_INT | T_TIMESTAMP, _) => true,
+        (Node::Int { negative }, T_NAT | T_MUTEZ, _) => !negative,
+        (Node::String, T_STRING | T_TIMESTAMP, _) => true,
+        (Node::Bytes, T_BYTES, _) => true,
+        (
+            Node::String | Node::Bytes,
+            T_ADDRESS | T_KEY | T_KEY_HASH | T_SIGNATURE,
+            _,
+        ) => true,
+        (Node::Seq(values), T_LIST | T_SET, [ty]) => {
+            values.iter().all(|value| has_type(value, ty))
+        }
+        (Node::Seq(values), T_MAP, [key, ty]) => values.iter().all(|value| {
+            matches!(value, Node::Prim(D_ELT, elt)
+                if matches!(elt.as_slice(), [k, v] if has_type(k, key) && has_type(v, ty)))
+        }),
+        (Node::Seq(values), T_PAIR, types) if values.len() >= 2 => {
+            has_pair_type(values, types)
+        }
+        (Node::Prim(prim, values), ty, types) => {
+            match (*prim, values.as_slice(), ty, types) {
+                (D_UNIT, [], T_UNIT, _)
+                | (D_TRUE | D_FALSE, [], T_BOOL, _)
+                | (D_NONE, [], T_OPTION, _) => true,
+                (D_SOME, [value], T_OPTION, [ty])
+                | (D_LEFT, [value], T_OR, [ty, _])
+                | (D_RIGHT, [value], T_OR, [_, ty]) => has_type(value, ty),
+                (D_PAIR, values, T_PAIR, types) if values.len() >= 2 => {
+                    has_pair_type(values, types)
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Checks that the components of a pair have the components of a pair type,
+/// comb pairs being equal to nested pairs on the right: `Pair 1 2 3`,
+/// `Pair 1 (Pair 2 3)` and `{ 1 ; 2 ; 3 }` all have the type
+/// `pair nat nat nat` and `pair nat (pair nat nat)`.
+fn has_pair_type(values: &[Node], types: &[Node]) -> bool {
+    match (values, types) {
+        ([value], [ty]) => has_type(value, ty),
+        ([value], types) => match value {
+            Node::Prim(D_PAIR, values) | Node::Seq(values) if values.len() >= 2 => {
+                has_pair_type(values, types)
+            }
+            _ => false,
+        },
+        (values, [Node::Prim(T_PAIR, types)]) => has_pair_type(values, types),
+        ([value, values @ ..], [ty, types @ ..]) => {
+            has_type(value, ty) && has_pair_type(values, types)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_micheline_expr_validation() {
+        let valid: [&[u8]; 6] = [
+            // 42
+            &[0x00, 0x2a],
+            // -1000
+            &[0x00, 0xe8, 0x0f],
+            // "ab"
+            &[0x01, 0x00, 0x00, 0x00, 0x02, 0x61, 0x62],
+            // { Unit ; 0x01 }
+            &[
+                0x02, 0x00, 0x00, 0x00, 0x08, 0x03, 0x0b, 0x0a, 0x00, 0x00, 0x00, 0x01,
+                0x01,
+            ],
+            // Pair 1 (Some Unit)
+            &[0x07, 0x07, 0x00, 0x01, 0x05, 0x09, 0x03, 0x0b],
+            // Unit %a, with generic application
+            &[
+                0x09, 0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x25, 0x61,
+            ],
+        ];
+        for bytes in valid {
+            assert_eq!(
+                MichelineExpr::from_bytes(bytes.to_vec()).map(|e| e.0),
+                Some(bytes.to_vec())
+            );
+        }
+
+        let invalid: [&[u8]; 7] = [
+            &[],
+            // Unfinished integer
+            &[0x00, 0x80],
+            // String shorter than its size
+            &[0x01, 0x00, 0x00, 0x00, 0x03, 0x61, 0x62],
+            // Two expressions
+            &[0x00, 0x01, 0x00, 0x01],
+            // Pair with a missing argument
+            &[0x07, 0x07, 0x00, 0x01],
+            // Unknown tag
+            &[0x0b],
+            // Unknown primitive
+            &[0x03, 0x9e],
+        ];
+        for bytes in invalid {
+            assert_eq!(MichelineExpr::from_bytes(bytes.to_vec()), None);
+        }
+
+        let too_deep: Vec<u8> = std::iter::repeat([0x05, 0x09])
+            .take(MAX_MICHELINE_DEPTH + 1)
+            .flatten()
+            .chain([0x03, 0x0b])
+            .collect();
+        assert_eq!(MichelineExpr::from_bytes(too_deep), None);
+    }
+
+    fn string(s: &str) -> Vec<u8> {
+        let mut bytes = vec![0x01];
+        bytes.extend((s.len() as u32).to_be_bytes());
+        bytes.extend(s.as_bytes());
+        bytes
+    }
+
+    fn nat(n: u8) -> Vec<u8> {
+        vec![0x00, n]
+    }
+
+    fn sized(tag: u8, content: Vec<Vec<u8>>) -> Vec<u8> {
+        let content = content.concat();
+        let mut bytes = vec![tag];
+        bytes.extend((content.len() as u32).to_be_bytes());
+        bytes.extend(content);
+        bytes
+    }
+
+    fn prim(prim: u8, arguments: Vec<Vec<u8>>) -> Vec<u8> {
+        match arguments.len() {
+            0..=2 => [
+                vec![0x03 + 2 * arguments.len() as u8, prim],
+                arguments.concat(),
+            ]
+            .concat(),
+            _ => [
+                vec![0x09, prim],
+                sized(0x02, arguments)[1..].to_vec(),
+                vec![0, 0, 0, 0],
+            ]
+            .concat(),
+        }
+    }
+
+    #[test]
+    fn test_micheline_expr_types() {
+        let kt1 = || string("KT1RJ6PbjHpwc3M5rw5s2Nbmefwbuwbdxton");
+        let ty = |p| prim(p, vec![]);
+        let pair = |arguments| prim(D_PAIR, arguments);
+        let pair_ty = |arguments| prim(T_PAIR, arguments);
+        // -1
+        let negative = || vec![0x00, 0x41];
+        // nat %a
+        let annotated_nat =
+            || [vec![0x04, T_NAT, 0x00, 0x00, 0x00, 0x02], b"%a".to_vec()].concat();
+
+        let well_typed = [
+            (nat(1), ty(T_NAT)),
+            (negative(), ty(T_INT)),
+            (kt1(), ty(T_ADDRESS)),
+            (prim(D_UNIT, vec![]), ty(T_UNIT)),
+            (prim(D_SOME, vec![nat(1)]), prim(T_OPTION, vec![ty(T_NAT)])),
+            (prim(D_NONE, vec![]), prim(T_OPTION, vec![ty(T_NAT)])),
+            (
+                prim(D_RIGHT, vec![string("a")]),
+                prim(T_OR, vec![ty(T_NAT), ty(T_STRING)]),
+            ),
+            // A KT1 address followed by other components is not a ticket if
+            // the type says so.
+            (
+                pair(vec![kt1(), pair(vec![nat(0), nat(1)])]),
+                pair_ty(vec![ty(T_ADDRESS), ty(T_NAT), ty(T_NAT)]),
+            ),
+            (
+                pair(vec![kt1(), nat(0), nat(1)]),
+                pair_ty(vec![ty(T_ADDRESS), pair_ty(vec![ty(T_NAT), ty(T_NAT)])]),
+            ),
+            (
+                sized(0x02, vec![kt1(), nat(0), nat(1)]),
+                pair_ty(vec![ty(T_ADDRESS), ty(T_NAT), ty(T_NAT)]),
+            ),
+            (
+                pair(vec![nat(0), nat(1)]),
+                pair_ty(vec![annotated_nat(), annotated_nat()]),
+            ),
+            (
+                sized(0x02, vec![nat(0), nat(1)]),
+                prim(T_LIST, vec![ty(T_NAT)]),
+            ),
+            (
+                sized(0x02, vec![prim(D_ELT, vec![string("a"), nat(1)])]),
+                prim(T_MAP, vec![ty(T_STRING), ty(T_NAT)]),
+            ),
+        ];
+        for (value, ty) in well_typed {
+            let value = MichelineExpr::from_bytes(value).unwrap();
+            let ty = MichelineExpr::from_bytes(ty).unwrap();
+            assert!(value.has_ticket_free_type(&ty));
+        }
+
+        // ticket nat
+        let ticket_ty = || prim(135, vec![ty(T_NAT)]);
+        let ill_typed = [
+            (negative(), ty(T_NAT)),
+            (string("a"), ty(T_BYTES)),
+            (
+                prim(D_LEFT, vec![string("a")]),
+                prim(T_OR, vec![ty(T_NAT), ty(T_STRING)]),
+            ),
+            (
+                pair(vec![kt1(), nat(0)]),
+                pair_ty(vec![ty(T_ADDRESS), ty(T_NAT), ty(T_NAT)]),
+            ),
+            (
+                sized(0x02, vec![nat(0), string("a")]),
+                prim(T_LIST, vec![ty(T_NAT)]),
+            ),
+            // Types containing tickets, even if no ticket is sent.
+            (pair(vec![kt1(), pair(vec![nat(0), nat(1)])]), ticket_ty()),
+            (prim(D_NONE, vec![]), prim(T_OPTION, vec![ticket_ty()])),
+            (sized(0x02, vec![]), prim(T_LIST, vec![ticket_ty()])),
+            // Types unknown to the type check.
+            // big_map nat nat
+            (sized(0x02, vec![]), prim(97, vec![ty(T_NAT), ty(T_NAT)])),
+            // A type is not a value.
+            (nat(1), nat(1)),
+        ];
+        for (value, ty) in ill_typed {
+            let value = MichelineExpr::from_bytes(value).unwrap();
+            let ty = MichelineExpr::from_bytes(ty).unwrap();
+            assert!(!value.has_ticket_free_type(&ty));
+        }
+    }
+
+    #[test]
+    fn test_micheline_expr_paired_with_bytes() {
+        let expr = MichelineExpr::from_bytes(vec![0x00, 0x2a]).unwrap();
+        let pair = expr.paired_with_bytes(&[0xaa, 0xbb]);
+        assert_eq!(
+            pair.as_bytes(),
+            [0x07, 0x07, 0x0a, 0x00, 0x00, 0x00, 0x02, 0xaa, 0xbb, 0x00, 0x2a]
+        );
+        assert_eq!(
+            MichelineExpr::from_bytes(pair.0.clone()),
+            Some(pair.clone())
+        );
+
+        let ty = MichelineExpr::from_bytes(vec![0x03, T_NAT]).unwrap();
+        let pair_ty = ty.paired_with_bytes_type();
+        assert_eq!(
+            pair_ty.as_bytes(),
+            [0x07, T_PAIR, 0x03, T_BYTES, 0x03, T_NAT]
+        );
+        assert!(pair.has_ticket_free_type(&pair_ty));
+    }
+
+    #[test]
+    fn test_micheline_expr_nom_read() {
+        let (rest, expr) = MichelineExpr::nom_read(&[0x00, 0x2a, 0xff]).unwrap();
+        assert_eq!(rest, [0xff]);
+        let mut encoded = vec![];
+        expr.bin_write(&mut encoded).unwrap();
+        assert_eq!(encoded, [0x00, 0x2a]);
+    }
+}
@@ -316,6 +316,25 @@ pub fn output_result(file: &mut File, name: &str, status: TestResult) {
     writeln!(file, "{}: {:?}", name, status).unwrap()
 }
 
+/// Name of a test execution in the results, the same test can be executed
+/// for several specs.
+pub fn full_test_name(
+    report_key: &str,
+    test_name: &str,
+    spec_name: &SpecName,
+    tx_indices: &TxPartIndices,
+) -> String {
+    format!(
+        "{}_{}_{}_data_index_{}_gas_index_{}_value_index_{}",
+        report_key,
+        test_name,
+        spec_name.to_str(),
+        tx_indices.data,
+        tx_indices.gas,
+        tx_indices.value
+    )
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn process(
     host: &mut EvalHost,
@@ -356,10 +375,7 @@ pub fn process(
         }
     }
 
-    let full_name = format!(
-        "{}_{}_data_index_{}_gas_index_{}_value_index_{}",
-        &report_key, test_name, tx_indices.data, tx_indices.gas, tx_indices.value
-    );
+    let full_name = full_test_name(&report_key, test_name, spec_name, tx_indices);
 
     match status {
         TestResult::Success => {
@@ -10,7 +10,7 @@ use evm_execution::account_storage::{
 };
 use evm_execution::handler::ExecutionOutcome;
 use evm_execution::precompiles::{precompile_set, PrecompileBTreeMap};
-use evm_execution::{run_transaction, Config, EthereumError};
+use evm_execution::{run_transaction, EthereumError, EvmFork};
 
 use tezos_ethereum::block::{BlockConstants, BlockFees};
 
@@ -24,7 +24,7 @@ use std::path::Path;
 use thiserror::Error;
 
 use crate::evalhost::EvalHost;
-use crate::fillers::{output_result, process, TestResult};
+use crate::fillers::{full_test_name, output_result, process, TestResult};
 use crate::helpers::{
     construct_folder_path, string_of_hexa, LabelIndexes, OutputOptions,
 };
@@ -180,7 +180,7 @@ fn execute_transaction(
     host: &mut EvalHost,
     evm_account_storage: &mut EthereumAccountStorage,
     precompiles: &PrecompileBTreeMap<EvalHost>,
-    config: &Config,
+    fork: EvmFork,
     unit: &TestUnit,
     env: &mut Env,
     test: &Test,
@@ -193,7 +193,7 @@ fn execute_transaction(
     env.tx.value = *unit.transaction.value.get(test.indexes.value).unwrap();
     env.tx.transact_to = unit.transaction.to;
 
-    let block_fees = BlockFees::new(env.block.basefee);
+    let block_fees = BlockFees::new(env.block.basefee, U256::zero());
 
     let block_constants = BlockConstants {
         number: env.block.number,
@@ -225,12 +225,13 @@ fn execute_transaction(
         &block_constants,
         evm_account_storage,
         precompiles,
-        config.clone(),
+        fork,
         address,
         caller,
         call_data,
         gas_limit,
         env.tx.gas_price,
+        U256::zero(),
         transaction_value,
         pay_for_gas,
         u64::MAX, // don't account for ticks during the test
@@ -325,11 +326,11 @@ pub fn run_test(
 
         // post and execution
         for (spec_name, tests) in &unit.post {
-            let config = match spec_name {
-                SpecName::Shanghai => Config::shanghai(),
-                // TODO: enable future configs when parallelization is enabled.
+            let fork = match spec_name {
+                SpecName::Shanghai => EvmFork::Shanghai,
+                SpecName::Cancun => EvmFork::Cancun,
                 // Other tests are ignored
-                _ => continue,
+                SpecName::Unknown => continue,
             };
 
             for test_execution in tests.iter() {
@@ -337,9 +338,11 @@ pub fn run_test(
                 let gas = test_execution.indexes.gas;
                 let value = test_execution.indexes.value;
                 if skip {
-                    let full_name = format!(
-                        "{}_{}_data_index_{}_gas_index_{}_value_index_{}",
-                        &report_key, name, data, gas, value
+                    let full_name = full_test_name(
+                        &report_key,
+                        &name,
+                        spec_name,
+                        &test_execution.indexes,
                     );
                     let status = TestResult::Skipped;
                     if output.result {
@@ -376,7 +379,7 @@ pub fn run_test(
                     &mut host,
                     &mut evm_account_storage,
                     &precompiles,
-                    &config,
+                    fork,
                     &unit,
                     &mut env,
                     test_execution,
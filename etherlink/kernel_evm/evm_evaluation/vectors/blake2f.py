@@ -0,0 +1,227 @@
+#!/usr/bin/env python3
+
+# SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+#
+# SPDX-License-Identifier: MIT
+
+"""Generate the BLAKE2F (EIP-152) state test and its filler.
+
+The vectors are:
+- the test cases of EIP-152, except the one with 2^32 - 1 rounds;
+- compressions of a single message block with 12 rounds, whose expected
+  output is the BLAKE2b hash computed by Python's hashlib, with various
+  messages, keys, salts and personalizations;
+- invalid inputs, for which the call fails.
+
+The contract calls the precompiled contract with its calldata, and stores
+the success flag in slot 0 (initially 0xff) and the output in slots 1 and 2.
+
+Usage: blake2f.py <output directory>
+"""
+
+import hashlib
+import json
+import os
+import struct
+import sys
+
+IV = [
+    0x6A09E667F3BCC908,
+    0xBB67AE8584CAA73B,
+    0x3C6EF372FE94F82B,
+    0xA54FF53A5F1D36F1,
+    0x510E527FADE682D1,
+    0x9B05688C2B3E6C1F,
+    0x1F83D9ABFB41BD6B,
+    0x5BE0CD19137E2179,
+]
+
+NAME = "blake2fVectors"
+SUB_DIRECTORY = "stPreCompiledContracts2"
+CONTRACT = "0x0000000000000000000000000000000000b1a2bf"
+SENDER = "0xa94f5374fce5edbc8e2a8697c15331677e6ebf0b"
+SECRET_KEY = "0x45a915e4d060149eb4365960e6a7a45f334393093061116b197e3240065ff2d8"
+
+# CALLDATACOPY the input to memory[0..], STATICCALL 0x09 with the output at
+# memory[256..320], then SSTORE the success flag and the two output words.
+CODE = (
+    "36600060003760406101003660006009"
+    "5afa6000556101005160015561012051600255"
+    "00"
+)
+
+
+def initial_state(key_length, salt, person):
+    parameters = bytes([64, key_length, 1, 1]) + bytes(28) + salt + person
+    words = struct.unpack("<8Q", parameters)
+    return [iv ^ word for iv, word in zip(IV, words)]
+
+
+def encode_input(rounds, state, block, counter, final):
+    return (
+        struct.pack(">I", rounds)
+        + struct.pack("<8Q", *state)
+        + block.ljust(128, b"\0")
+        + struct.pack("<QQ", counter, 0)
+        + bytes([final])
+    )
+
+
+def eip152(rounds, final):
+    return encode_input(
+        rounds,
+        initial_state(0, bytes(16), bytes(16)),
+        b"abc",
+        3,
+        final,
+    )
+
+
+def hashlib_vector(message=b"", key=b"", salt=bytes(16), person=bytes(16)):
+    """A single block compression, checked against hashlib."""
+    state = initial_state(len(key), salt, person)
+    if key:
+        assert not message
+        block, counter = key, 128
+    else:
+        assert len(message) <= 128
+        block, counter = message, len(message)
+    expected = hashlib.blake2b(
+        message, digest_size=64, key=key, salt=salt, person=person
+    ).digest()
+    return encode_input(12, state, block, counter, 1), expected
+
+
+def vectors():
+    # EIP-152, test cases 4 to 7
+    yield (
+        "eip152_0_rounds",
+        eip152(0, 1),
+        "08c9bcf367e6096a3ba7ca8485ae67bb2bf894fe72f36e3cf1361d5f3af54fa5"
+        "d282e6ad7f520e511f6c3e2b8c68059b9442be0454267ce079217e1319cde05b",
+    )
+    yield (
+        "eip152_12_rounds",
+        eip152(12, 1),
+        "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1"
+        "7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923",
+    )
+    yield (
+        "eip152_not_final",
+        eip152(12, 0),
+        "75ab69d3190a562c51aef8d88f1c2775876944407270c42c9844252c26d28752"
+        "98743e7f6d5ea2f2d3e8d226039cd31b4e426ac4f2d3d666a610c2116fde4735",
+    )
+    yield (
+        "eip152_1_round",
+        eip152(1, 1),
+        "b63a380cb2897d521994a85234ee2c181b5f844d2c624c002677e9703449d2fb"
+        "a551b3a8333bcdf5f2f7e08993d53923de3d64fcc68c034e717b9293fed7a421",
+    )
+    hashlib_cases = [
+        ("empty", {}),
+        ("one_byte", {"message": b"\x01"}),
+        ("64_bytes", {"message": bytes(range(64))}),
+        ("127_bytes", {"message": bytes(range(127))}),
+        ("full_block", {"message": bytes(range(128))}),
+        ("ones", {"message": b"\xff" * 128}),
+        ("key", {"key": b"etherlink"}),
+        ("64_bytes_key", {"key": bytes(range(64, 128))}),
+        ("salt", {"message": b"abc", "salt": b"0123456789abcdef"}),
+        ("person", {"message": b"abc", "person": b"etherlink blake2"}),
+    ]
+    for name, case in hashlib_cases:
+        data, expected = hashlib_vector(**case)
+        yield ("hashlib_" + name, data, expected.hex())
+    valid = eip152(12, 1)
+    yield ("too_short", valid[:-1], None)
+    yield ("too_long", valid + b"\0", None)
+    yield ("invalid_final_flag", eip152(12, 2), None)
+
+
+def word(value):
+    return "0x" + value.rjust(64, "0")
+
+
+def main(output):
+    cases = list(vectors())
+    labels = {str(i): name for i, (name, _, _) in enumerate(cases)}
+    post = [
+        {
+            "hash": word("00"),
+            "indexes": {"data": i, "gas": 0, "value": 0},
+            "logs": "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347",
+        }
+        for i in range(len(cases))
+    ]
+    test = {
+        NAME: {
+            "_info": {
+                "source": f"src/GeneralStateTestsFiller/{SUB_DIRECTORY}/{NAME}Filler.json",
+                "labels": labels,
+            },
+            "env": {
+                "currentCoinbase": "0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba",
+                "currentGasLimit": "0x05f5e100",
+                "currentNumber": "0x01",
+                "currentTimestamp": "0x03e8",
+                "currentBaseFee": "0x0a",
+            },
+            "pre": {
+                CONTRACT: {
+                    "balance": "0x00",
+                    "code": "0x" + CODE,
+                    "nonce": "0x01",
+                    "storage": {"0x00": "0xff"},
+                },
+                SENDER: {
+                    "balance": "0x3b9aca00",
+                    "code": "0x",
+                    "nonce": "0x00",
+                    "storage": {},
+                },
+            },
+            "post": {"Shanghai": post, "Cancun": post},
+            "transaction": {
+                "data": ["0x" + data.hex() for _, data, _ in cases],
+                "gasLimit": ["0x0f4240"],
+                "gasPrice": "0x0a",
+                "nonce": "0x00",
+                "secretKey": SECRET_KEY,
+                "sender": SENDER,
+                "to": CONTRACT,
+                "value": ["0x00"],
+            },
+        }
+    }
+    expect = []
+    for i, (_, _, expected) in enumerate(cases):
+        if expected is None:
+            storage = {"0x00": "0x00", "0x01": "0x00", "0x02": "0x00"}
+        else:
+            storage = {
+                "0x00": "0x01",
+                "0x01": "0x" + expected[:64],
+                "0x02": "0x" + expected[64:],
+            }
+        expect.append(
+            {
+                "indexes": {"data": i, "gas": -1, "value": -1},
+                "network": [">=Shanghai"],
+                "result": {CONTRACT: {"storage": storage}},
+            }
+        )
+    filler = {NAME: {"expect": expect}}
+    for path, content in [
+        (f"GeneralStateTests/{SUB_DIRECTORY}/{NAME}.json", test),
+        (f"src/GeneralStateTestsFiller/{SUB_DIRECTORY}/{NAME}Filler.json", filler),
+    ]:
+        path = os.path.join(output, path)
+        os.makedirs(os.path.dirname(path), exist_ok=True)
+        with open(path, "w") as f:
+            json.dump(content, f, indent=2)
+            f.write("\n")
+
+
+if __name__ == "__main__":
+    main(sys.argv[1])
@@ -0,0 +1,61 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+// Compiles `src/fa_bridge_wrapper.sol` and writes its runtime code to
+// `src/fa_bridge_wrapper.bin`, which is `WRAPPER_CODE` in `fa_bridge.rs`.
+// With `--check`, fails if the file isn't the compiled code instead.
+//
+// The compiler and OpenZeppelin versions are pinned in `package.json`:
+// $ npm install --prefix etherlink/kernel_evm/evm_execution/scripts
+// $ node etherlink/kernel_evm/evm_execution/scripts/fa_bridge_wrapper.js [--check]
+
+const fs = require('fs');
+const path = require('node:path');
+const solc = require('solc');
+
+const src = path.resolve(__dirname, '..', 'src');
+const source = 'fa_bridge_wrapper.sol';
+const output_file = path.join(src, 'fa_bridge_wrapper.bin');
+
+const input = {
+    language: 'Solidity',
+    sources: {
+        [source]: { content: fs.readFileSync(path.join(src, source), 'utf8') },
+    },
+    settings: {
+        evmVersion: 'shanghai',
+        optimizer: { enabled: true, runs: 200 },
+        // Without metadata hash, the code only depends on the compiled code.
+        metadata: { appendCBOR: false },
+        outputSelection: {
+            [source]: { FaBridgeWrapper: ['evm.deployedBytecode.object'] },
+        },
+    },
+};
+
+function find_imports(import_path) {
+    try {
+        return { contents: fs.readFileSync(require.resolve(import_path), 'utf8') };
+    } catch (error) {
+        return { error: error.message };
+    }
+}
+
+const output = JSON.parse(solc.compile(JSON.stringify(input), { import: find_imports }));
+const errors = (output.errors ?? []).filter((error) => error.severity == 'error');
+if (errors.length > 0) {
+    errors.forEach((error) => console.error(error.formattedMessage));
+    process.exit(1);
+}
+const code = output.contracts[source].FaBridgeWrapper.evm.deployedBytecode.object;
+
+if (process.argv[2] == '--check') {
+    if (fs.readFileSync(output_file, 'utf8') != code) {
+        console.error(`${output_file} is not the runtime code of ${source}, run ${path.basename(__filename)}`);
+        process.exit(1);
+    }
+} else {
+    fs.writeFileSync(output_file, code);
+    console.log(`Wrote ${code.length / 2} bytes to ${output_file}`);
+}
@@ -89,7 +89,8 @@ pub struct StorageEffect {
 /// `balance == nonce == code == 0x`.
 ///
 /// The Ethereum Yellow Paper also lists the **storageRoot** as a field associated with
-/// an account. We don't currently require it, and so it's omitted.
+/// an account. It is not maintained by the account itself, the kernel stores it
+/// when it updates the state trie after a transaction.
 #[derive(Debug, PartialEq)]
 pub struct EthereumAccount {
     path: OwnedPath,
@@ -106,23 +107,23 @@ pub const EVM_ACCOUNTS_PATH: RefPath = RefPath::assert_from(b"/eth_accounts");
 
 /// Path where an account nonce is stored. This should be prefixed with the path to
 /// where the account is stored for the world state or for the current transaction.
-const NONCE_PATH: RefPath = RefPath::assert_from(b"/nonce");
+pub const NONCE_PATH: RefPath = RefPath::assert_from(b"/nonce");
 
 /// Path where an account balance, ether held, is stored. This should be prefixed with the path to
 /// where the account is stored for the world state or for the current transaction.
-const BALANCE_PATH: RefPath = RefPath::assert_from(b"/balance");
+pub const BALANCE_PATH: RefPath = RefPath::assert_from(b"/balance");
 
 /// "Internal" accounts - accounts with contract code have a contract code hash.
 /// This value is computed when the code is stored and kept for future queries. This
 /// path should be prefixed with the path to
 /// where the account is stored for the world state or for the current transaction.
-const CODE_HASH_PATH: RefPath = RefPath::assert_from(b"/code.hash");
+pub const CODE_HASH_PATH: RefPath = RefPath::assert_from(b"/code.hash");
 
 /// "Internal" accounts - accounts with contract code, have their code stored here.
 /// This
 /// path should be prefixed with the path to
 /// where the account is stored for the world state or for the current transaction.
-const CODE_PATH: RefPath = RefPath::assert_from(b"/code");
+pub const CODE_PATH: RefPath = RefPath::assert_from(b"/code");
 
 /// The contracts of "internal" accounts have their own storage area. The account
 /// location prefixed to this path gives the root path (prefix) to where such storage
@@ -130,6 +131,10 @@ const CODE_PATH: RefPath = RefPath::assert_from(b"/code");
 /// such 256 bit integer value in storage.
 const STORAGE_ROOT_PATH: RefPath = RefPath::assert_from(b"/storage");
 
+/// Root of the Merkle-Patricia trie of the contract storage, as last computed
+/// by the kernel. Deleting the account also resets it.
+const STORAGE_TRIE_ROOT_PATH: RefPath = RefPath::assert_from(b"/storage.root");
+
 /// Flag indicating an account has already been indexed.
 const INDEXED_PATH: RefPath = RefPath::assert_from(b"/indexed");
 
@@ -152,6 +157,13 @@ const CODE_HASH_BYTES: [u8; WORD_SIZE] = Decoder::Hex
 /// The default hash for when there is no code - the hash of the empty string.
 pub const CODE_HASH_DEFAULT: H256 = H256(CODE_HASH_BYTES);
 
+const EMPTY_TRIE_ROOT_BYTES: [u8; WORD_SIZE] = Decoder::Hex
+    .decode(b"56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+
+/// The root of an empty Merkle-Patricia trie - the hash of the RLP encoding of
+/// the empty string.
+pub const EMPTY_TRIE_ROOT: H256 = H256(EMPTY_TRIE_ROOT_BYTES);
+
 /// Read a single unsigned 256 bit value from storage at the path given.
 fn read_u256(
     host: &impl Runtime,
@@ -298,8 +310,23 @@ impl EthereumAccount {
         }
     }
 
+    /// Set the balance of an account in Wei, regardless of its current value.
+    pub fn set_balance(
+        &mut self,
+        host: &mut impl Runtime,
+        balance: U256,
+    ) -> Result<(), AccountStorageError> {
+        let path = concat(&self.path, &BALANCE_PATH)?;
+
+        let mut value_bytes: [u8; WORD_SIZE] = [0; WORD_SIZE];
+        balance.to_little_endian(&mut value_bytes);
+
+        host.store_write(&path, &value_bytes, 0)
+            .map_err(AccountStorageError::from)
+    }
+
     /// Get the path to an index in durable storage for an account.
-    fn storage_path(&self, index: &H256) -> Result<OwnedPath, AccountStorageError> {
+    pub fn storage_path(&self, index: &H256) -> Result<OwnedPath, AccountStorageError> {
         let storage_path = concat(&self.path, &STORAGE_ROOT_PATH)?;
         let index_path = path_from_h256(index)?;
         concat(&storage_path, &index_path).map_err(AccountStorageError::from)
@@ -363,6 +390,32 @@ impl EthereumAccount {
             .map_err(AccountStorageError::from)
     }
 
+    /// Number of values in the contract storage of the account.
+    pub fn storage_size(&self, host: &impl Runtime) -> Result<u64, AccountStorageError> {
+        let path = concat(&self.path, &STORAGE_ROOT_PATH)?;
+
+        match host.store_has(&path)? {
+            Some(ValueType::Subtree | ValueType::ValueWithSubtree) => host
+                .store_count_subkeys(&path)
+                .map_err(AccountStorageError::from),
+            Some(ValueType::Value) | None => Ok(0),
+        }
+    }
+
+    /// Delete all the values in the contract storage of an account.
+    pub fn clear_storage(
+        &mut self,
+        host: &mut impl Runtime,
+    ) -> Result<(), AccountStorageError> {
+        let path = concat(&self.path, &STORAGE_ROOT_PATH)?;
+
+        if host.store_has(&path)?.is_some() {
+            host.store_delete(&path)?
+        }
+
+        Ok(())
+    }
+
     /// Find whether the account has any code associated with it.
     pub fn code_exists(&self, host: &impl Runtime) -> Result<bool, AccountStorageError> {
         let path = concat(&self.path, &CODE_PATH)?;
@@ -456,6 +509,27 @@ impl EthereumAccount {
         Ok(())
     }
 
+    /// Get the root of the trie of the contract storage. Default value is the
+    /// root of the empty trie.
+    pub fn storage_trie_root(
+        &self,
+        host: &impl Runtime,
+    ) -> Result<H256, AccountStorageError> {
+        let path = concat(&self.path, &STORAGE_TRIE_ROOT_PATH)?;
+        read_h256(host, &path, EMPTY_TRIE_ROOT)
+    }
+
+    /// Set the root of the trie of the contract storage.
+    pub fn set_storage_trie_root(
+        &mut self,
+        host: &mut impl Runtime,
+        root: &H256,
+    ) -> Result<(), AccountStorageError> {
+        let path = concat(&self.path, &STORAGE_TRIE_ROOT_PATH)?;
+        host.store_write(&path, root.as_bytes(), 0)
+            .map_err(AccountStorageError::from)
+    }
+
     pub fn indexed(&self, host: &impl Runtime) -> Result<bool, DurableStorageError> {
         let path = concat(&self.path, &INDEXED_PATH)?;
         Ok(host.store_has(&path)?.is_some())
@@ -612,6 +686,36 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_account_balance_set() {
+        let mut host = MockHost::default();
+        let mut storage =
+            init_account_storage().expect("Could not create EVM accounts storage API");
+
+        let a1_path = RefPath::assert_from(b"/dfkjd");
+
+        let v1: U256 = 17_u32.into();
+        let v2: U256 = 3_u32.into();
+
+        // Act - create an account and set its balance twice
+        let mut a1 = storage
+            .create_new(&mut host, &a1_path)
+            .expect("Could not create new account")
+            .expect("Account already exists");
+
+        a1.set_balance(&mut host, v1)
+            .expect("Could not set first balance");
+        a1.set_balance(&mut host, v2)
+            .expect("Could not set second balance");
+
+        // Assert
+        assert_eq!(
+            a1.balance(&host)
+                .expect("Could not get balance for account"),
+            v2
+        );
+    }
+
     #[test]
     fn test_account_balance_sub() {
         let mut host = MockHost::default();
@@ -766,6 +870,39 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_account_storage_clear() {
+        let mut host = MockHost::default();
+        let mut storage =
+            init_account_storage().expect("Could not create EVM accounts storage API");
+
+        let a1_path = RefPath::assert_from(b"/dfkjd");
+
+        let addr: H256 = H256::from_low_u64_be(17_u64);
+        let v: H256 = H256::from_low_u64_be(190_u64);
+
+        let mut a1 = storage
+            .create_new(&mut host, &a1_path)
+            .expect("Could not create new account")
+            .expect("Account already exists");
+
+        // Clearing an account without storage has no effect
+        a1.clear_storage(&mut host)
+            .expect("Could not clear account storage");
+
+        a1.set_storage(&mut host, &addr, &v)
+            .expect("Could not update account storage");
+        a1.clear_storage(&mut host)
+            .expect("Could not clear account storage");
+
+        // Assert
+        assert_eq!(
+            a1.get_storage(&host, &addr)
+                .expect("Could not read storage for account"),
+            STORAGE_DEFAULT_VALUE
+        );
+    }
+
     #[test]
     fn test_account_storage_update_checked() {
         let mut host = MockHost::default();
@@ -1111,4 +1248,48 @@ mod test {
             sample_code_hash
         );
     }
+
+    #[test]
+    fn test_empty_trie_root_matches_default() {
+        assert_eq!(EMPTY_TRIE_ROOT, bytes_hash(&rlp::NULL_RLP));
+    }
+
+    #[test]
+    fn test_storage_trie_root_is_reset_on_delete() {
+        let mut host = MockHost::default();
+        let mut storage =
+            init_account_storage().expect("Could not create EVM accounts storage API");
+
+        let a1_path = RefPath::assert_from(b"/asdf");
+        let root = H256::from([1; 32]);
+
+        let mut a1 = storage
+            .get_or_create(&host, &a1_path)
+            .expect("Could not create account");
+        assert_eq!(
+            a1.storage_trie_root(&host)
+                .expect("Could not read the storage trie root"),
+            EMPTY_TRIE_ROOT
+        );
+
+        a1.set_storage_trie_root(&mut host, &root)
+            .expect("Could not write the storage trie root");
+        assert_eq!(
+            a1.storage_trie_root(&host)
+                .expect("Could not read the storage trie root"),
+            root
+        );
+
+        storage
+            .delete(&mut host, &a1_path)
+            .expect("Could not delete account");
+        let a1 = storage
+            .get_or_create(&host, &a1_path)
+            .expect("Could not create account");
+        assert_eq!(
+            a1.storage_trie_root(&host)
+                .expect("Could not read the storage trie root"),
+            EMPTY_TRIE_ROOT
+        );
+    }
 }
@@ -0,0 +1,685 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! BLS12-381 precompiled contracts, see EIP-2537.
+//!
+//! The curve arithmetic is done by `blst`, the library used for the BLS
+//! signatures of `tezos_crypto_rs`. Points and field elements are encoded as
+//! in the EIP:
+//! - a base field element is 64 bytes, big endian, whose 16 first bytes are
+//!   zero, and must be strictly lower than the modulus,
+//! - an element of the quadratic extension `c0 + c1 * v` is the encoding of
+//!   `c0` followed by the encoding of `c1`,
+//! - a point is the encoding of its `x` coordinate followed by the encoding
+//!   of its `y` coordinate, and the point at infinity is encoded with zeros,
+//! - a scalar is 32 bytes, big endian, and doesn't need to be lower than the
+//!   order of the groups.
+
+use crate::fail_if_too_much;
+use crate::precompiles::call_precompile_with_gas_draining;
+use crate::{handler::EvmHandler, precompiles::PrecompileOutcome, EthereumError};
+use alloc::vec::Vec;
+use blst::{
+    blst_fp, blst_fp12, blst_fp2, blst_p1, blst_p1_affine, blst_p2, blst_p2_affine,
+};
+use const_decoder::Decoder;
+use evm::{executor::stack::PrecompileFailure, ExitError, ExitReason, ExitSucceed};
+use evm::{Context, Transfer};
+use host::runtime::Runtime;
+use tezos_evm_logging::log;
+use tezos_evm_logging::Level::Info;
+
+/// Length of an encoded base field element.
+const FP_LEN: usize = 64;
+
+/// Number of zero bytes prefixing an encoded base field element.
+const FP_PADDING_LEN: usize = 16;
+
+/// Length of an encoded G1 point.
+const G1_LEN: usize = 2 * FP_LEN;
+
+/// Length of an encoded G2 point.
+const G2_LEN: usize = 4 * FP_LEN;
+
+/// Big endian encoding of the modulus of the base field.
+const FP_MODULUS: [u8; FP_LEN - FP_PADDING_LEN] = Decoder::Hex.decode(
+    b"1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffaaab",
+);
+
+/// Length of an encoded scalar.
+const SCALAR_LEN: usize = 32;
+
+/// Length of a pair of the pairing check.
+const PAIR_LEN: usize = G1_LEN + G2_LEN;
+
+mod gas {
+    pub const G1_ADD: u64 = 500;
+    pub const G1_MUL: u64 = 12_000;
+    pub const G2_ADD: u64 = 800;
+    pub const G2_MUL: u64 = 45_000;
+    pub const PAIRING_BASE: u64 = 65_000;
+    pub const PAIRING_PER_PAIR: u64 = 43_000;
+    pub const MAP_FP_TO_G1: u64 = 5_500;
+    pub const MAP_FP2_TO_G2: u64 = 75_000;
+
+    /// Discount, in thousandths, of a multi-exponentiation of `k` pairs
+    /// compared to `k` multiplications. Larger inputs use the last discount.
+    const MULTIEXP_DISCOUNT: [u64; 128] = [
+        1200, 888, 764, 641, 594, 547, 500, 453, 438, 423, 408, 394, 379, 364, 349, 334,
+        330, 326, 322, 318, 314, 310, 306, 302, 298, 294, 289, 285, 281, 277, 273, 269,
+        268, 266, 265, 263, 262, 260, 259, 257, 256, 254, 253, 251, 250, 248, 247, 245,
+        244, 242, 241, 239, 238, 236, 235, 233, 232, 231, 229, 228, 226, 225, 223, 222,
+        221, 220, 219, 219, 218, 217, 216, 216, 215, 214, 213, 213, 212, 211, 211, 210,
+        209, 208, 208, 207, 206, 205, 205, 204, 203, 202, 202, 201, 200, 199, 199, 198,
+        197, 196, 196, 195, 194, 193, 193, 192, 191, 191, 190, 189, 188, 188, 187, 186,
+        185, 185, 184, 183, 182, 182, 181, 180, 179, 179, 178, 177, 176, 176, 175, 174,
+    ];
+
+    pub fn multiexp(k: usize, mul_cost: u64) -> u64 {
+        if k == 0 {
+            return 0;
+        }
+        let discount = MULTIEXP_DISCOUNT[k.min(MULTIEXP_DISCOUNT.len()) - 1];
+        (k as u64).saturating_mul(mul_cost).saturating_mul(discount) / 1000
+    }
+}
+
+mod tick_model {
+    // Instructions executed by the WASM build of a Rust implementation of the
+    // operations (rustc, wasm32-unknown-unknown, release), scaled by
+    // `ticks_of_ecrecover` for the instructions of the WASM build of
+    // ecrecover, then by the ratio of the timings of `blst` compiled without
+    // assembly, as for WASM, and of this implementation: 0.95 for the
+    // additions, 1.5 for the multiplications and the pairing, and 0.97 for
+    // the maps. The margin is about 20%. The multiplications and the pairing
+    // include the subgroup checks. The estimates for the pairing are 1268M
+    // ticks for one pair, 1935M for two and 3272M for four.
+
+    pub fn ticks_of_g1_add() -> u64 {
+        4_000_000
+    }
+
+    pub fn ticks_of_g1_mul() -> u64 {
+        300_000_000
+    }
+
+    pub fn ticks_of_g1_multiexp(k: usize) -> u64 {
+        (ticks_of_g1_mul() + ticks_of_g1_add()) * k as u64
+    }
+
+    pub fn ticks_of_g2_add() -> u64 {
+        7_000_000
+    }
+
+    pub fn ticks_of_g2_mul() -> u64 {
+        645_000_000
+    }
+
+    pub fn ticks_of_g2_multiexp(k: usize) -> u64 {
+        (ticks_of_g2_mul() + ticks_of_g2_add()) * k as u64
+    }
+
+    pub fn ticks_of_pairing(k: usize) -> u64 {
+        750_000_000 + 810_000_000 * k as u64
+    }
+
+    pub fn ticks_of_map_fp_to_g1() -> u64 {
+        100_000_000
+    }
+
+    pub fn ticks_of_map_fp2_to_g2() -> u64 {
+        320_000_000
+    }
+}
+
+fn bls12_381_error(reason: &'static str) -> EthereumError {
+    EthereumError::PrecompileFailed(PrecompileFailure::Error {
+        exit_status: ExitError::Other(std::borrow::Cow::Borrowed(reason)),
+    })
+}
+
+fn record_cost<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    cost: u64,
+) -> Result<(), PrecompileOutcome> {
+    handler.record_cost(cost).map_err(|err| PrecompileOutcome {
+        exit_status: ExitReason::Error(err),
+        output: vec![],
+        withdrawals: vec![],
+        estimated_ticks: 0,
+    })
+}
+
+fn succeed(output: Vec<u8>, estimated_ticks: u64) -> PrecompileOutcome {
+    PrecompileOutcome {
+        exit_status: ExitReason::Succeed(ExitSucceed::Returned),
+        output,
+        withdrawals: vec![],
+        estimated_ticks,
+    }
+}
+
+fn check_length(input: &[u8], length: usize) -> Result<(), EthereumError> {
+    if input.len() == length {
+        Ok(())
+    } else {
+        Err(bls12_381_error("Bls12381InvalidInputLength"))
+    }
+}
+
+/// Checks that the input is a non empty list of elements of `length` bytes.
+fn check_elements_length(input: &[u8], length: usize) -> Result<(), EthereumError> {
+    if input.is_empty() || input.len() % length != 0 {
+        Err(bls12_381_error("Bls12381InvalidInputLength"))
+    } else {
+        Ok(())
+    }
+}
+
+fn read_fp(input: &[u8]) -> Result<blst_fp, EthereumError> {
+    let (padding, bytes) = input[..FP_LEN].split_at(FP_PADDING_LEN);
+    if padding.iter().any(|byte| *byte != 0) {
+        return Err(bls12_381_error("Bls12381InvalidFieldElementPadding"));
+    }
+    // `blst` reduces the elements which aren't lower than the modulus, the
+    // comparison of big endian encodings of the same length is the
+    // lexicographic one.
+    if bytes >= &FP_MODULUS[..] {
+        return Err(bls12_381_error("Bls12381FieldElementNotAMember"));
+    }
+    let mut fp = blst_fp::default();
+    unsafe { blst::blst_fp_from_bendian(&mut fp, bytes.as_ptr()) };
+    Ok(fp)
+}
+
+fn write_fp(fp: &blst_fp, output: &mut [u8]) {
+    unsafe { blst::blst_bendian_from_fp(output[FP_PADDING_LEN..FP_LEN].as_mut_ptr(), fp) }
+}
+
+fn read_fp2(input: &[u8]) -> Result<blst_fp2, EthereumError> {
+    Ok(blst_fp2 {
+        fp: [read_fp(input)?, read_fp(&input[FP_LEN..])?],
+    })
+}
+
+fn write_fp2(fp2: &blst_fp2, output: &mut [u8]) {
+    write_fp(&fp2.fp[0], output);
+    write_fp(&fp2.fp[1], &mut output[FP_LEN..]);
+}
+
+/// Reads a point from its affine coordinates, the subgroup check is only
+/// performed if `check_subgroup` is set, as the addition doesn't require it.
+fn read_g1(input: &[u8], check_subgroup: bool) -> Result<blst_p1_affine, EthereumError> {
+    let point = blst_p1_affine {
+        x: read_fp(input)?,
+        y: read_fp(&input[FP_LEN..])?,
+    };
+    if unsafe { blst::blst_p1_affine_is_inf(&point) } {
+        return Ok(point);
+    }
+    if !unsafe { blst::blst_p1_affine_on_curve(&point) } {
+        return Err(bls12_381_error("Bls12381G1PointNotOnCurve"));
+    }
+    if check_subgroup && !unsafe { blst::blst_p1_affine_in_g1(&point) } {
+        return Err(bls12_381_error("Bls12381G1PointNotInSubgroup"));
+    }
+    Ok(point)
+}
+
+fn write_g1(point: &blst_p1) -> Vec<u8> {
+    let mut output = vec![0u8; G1_LEN];
+    if !unsafe { blst::blst_p1_is_inf(point) } {
+        let mut affine = blst_p1_affine::default();
+        unsafe { blst::blst_p1_to_affine(&mut affine, point) };
+        write_fp(&affine.x, &mut output);
+        write_fp(&affine.y, &mut output[FP_LEN..]);
+    }
+    output
+}
+
+fn read_g2(input: &[u8], check_subgroup: bool) -> Result<blst_p2_affine, EthereumError> {
+    let point = blst_p2_affine {
+        x: read_fp2(input)?,
+        y: read_fp2(&input[2 * FP_LEN..])?,
+    };
+    if unsafe { blst::blst_p2_affine_is_inf(&point) } {
+        return Ok(point);
+    }
+    if !unsafe { blst::blst_p2_affine_on_curve(&point) } {
+        return Err(bls12_381_error("Bls12381G2PointNotOnCurve"));
+    }
+    if check_subgroup && !unsafe { blst::blst_p2_affine_in_g2(&point) } {
+        return Err(bls12_381_error("Bls12381G2PointNotInSubgroup"));
+    }
+    Ok(point)
+}
+
+fn write_g2(point: &blst_p2) -> Vec<u8> {
+    let mut output = vec![0u8; G2_LEN];
+    if !unsafe { blst::blst_p2_is_inf(point) } {
+        let mut affine = blst_p2_affine::default();
+        unsafe { blst::blst_p2_to_affine(&mut affine, point) };
+        write_fp2(&affine.x, &mut output);
+        write_fp2(&affine.y, &mut output[2 * FP_LEN..]);
+    }
+    output
+}
+
+/// Little endian scalar, as expected by `blst`.
+fn read_scalar(input: &[u8]) -> [u8; SCALAR_LEN] {
+    let mut scalar = [0u8; SCALAR_LEN];
+    scalar.copy_from_slice(&input[..SCALAR_LEN]);
+    scalar.reverse();
+    scalar
+}
+
+fn g1_add(left: &blst_p1, right: &blst_p1_affine) -> blst_p1 {
+    let mut sum = blst_p1::default();
+    unsafe { blst::blst_p1_add_or_double_affine(&mut sum, left, right) };
+    sum
+}
+
+fn g1_mul(point: &blst_p1_affine, scalar: &[u8; SCALAR_LEN]) -> blst_p1 {
+    let mut point_jacobian = blst_p1::default();
+    let mut product = blst_p1::default();
+    unsafe {
+        blst::blst_p1_from_affine(&mut point_jacobian, point);
+        blst::blst_p1_mult(
+            &mut product,
+            &point_jacobian,
+            scalar.as_ptr(),
+            8 * SCALAR_LEN,
+        )
+    };
+    product
+}
+
+fn g2_add(left: &blst_p2, right: &blst_p2_affine) -> blst_p2 {
+    let mut sum = blst_p2::default();
+    unsafe { blst::blst_p2_add_or_double_affine(&mut sum, left, right) };
+    sum
+}
+
+fn g2_mul(point: &blst_p2_affine, scalar: &[u8; SCALAR_LEN]) -> blst_p2 {
+    let mut point_jacobian = blst_p2::default();
+    let mut product = blst_p2::default();
+    unsafe {
+        blst::blst_p2_from_affine(&mut point_jacobian, point);
+        blst::blst_p2_mult(
+            &mut product,
+            &point_jacobian,
+            scalar.as_ptr(),
+            8 * SCALAR_LEN,
+        )
+    };
+    product
+}
+
+fn g1_add_precompile_without_gas_draining<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+) -> Result<PrecompileOutcome, EthereumError> {
+    log!(
+        handler.borrow_host(),
+        Info,
+        "Calling BLS12_G1ADD precompile"
+    );
+    if let Err(outcome) = record_cost(handler, gas::G1_ADD) {
+        return Ok(outcome);
+    }
+    let estimated_ticks = fail_if_too_much!(tick_model::ticks_of_g1_add(), handler);
+
+    check_length(input, 2 * G1_LEN)?;
+    let left = read_g1(input, false)?;
+    let right = read_g1(&input[G1_LEN..], false)?;
+    let mut left_jacobian = blst_p1::default();
+    unsafe { blst::blst_p1_from_affine(&mut left_jacobian, &left) };
+
+    Ok(succeed(
+        write_g1(&g1_add(&left_jacobian, &right)),
+        estimated_ticks,
+    ))
+}
+
+pub fn g1_add_precompile<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+    _context: &Context,
+    _is_static: bool,
+    _transfer: Option<Transfer>,
+) -> Result<PrecompileOutcome, EthereumError> {
+    call_precompile_with_gas_draining(
+        handler,
+        input,
+        g1_add_precompile_without_gas_draining,
+    )
+}
+
+fn g1_mul_precompile_without_gas_draining<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+) -> Result<PrecompileOutcome, EthereumError> {
+    log!(
+        handler.borrow_host(),
+        Info,
+        "Calling BLS12_G1MUL precompile"
+    );
+    if let Err(outcome) = record_cost(handler, gas::G1_MUL) {
+        return Ok(outcome);
+    }
+    let estimated_ticks = fail_if_too_much!(tick_model::ticks_of_g1_mul(), handler);
+
+    check_length(input, G1_LEN + SCALAR_LEN)?;
+    let point = read_g1(input, true)?;
+    let scalar = read_scalar(&input[G1_LEN..]);
+
+    Ok(succeed(write_g1(&g1_mul(&point, &scalar)), estimated_ticks))
+}
+
+pub fn g1_mul_precompile<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+    _context: &Context,
+    _is_static: bool,
+    _transfer: Option<Transfer>,
+) -> Result<PrecompileOutcome, EthereumError> {
+    call_precompile_with_gas_draining(
+        handler,
+        input,
+        g1_mul_precompile_without_gas_draining,
+    )
+}
+
+fn g1_multiexp_precompile_without_gas_draining<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+) -> Result<PrecompileOutcome, EthereumError> {
+    log!(
+        handler.borrow_host(),
+        Info,
+        "Calling BLS12_G1MULTIEXP precompile"
+    );
+    const ELEMENT_LEN: usize = G1_LEN + SCALAR_LEN;
+    let k = input.len() / ELEMENT_LEN;
+    if let Err(outcome) = record_cost(handler, gas::multiexp(k, gas::G1_MUL)) {
+        return Ok(outcome);
+    }
+    let estimated_ticks = fail_if_too_much!(tick_model::ticks_of_g1_multiexp(k), handler);
+
+    check_elements_length(input, ELEMENT_LEN)?;
+    let mut result = blst_p1::default();
+    for element in input.chunks_exact(ELEMENT_LEN) {
+        let point = read_g1(element, true)?;
+        let scalar = read_scalar(&element[G1_LEN..]);
+        let mut product = blst_p1_affine::default();
+        unsafe { blst::blst_p1_to_affine(&mut product, &g1_mul(&point, &scalar)) };
+        result = g1_add(&result, &product);
+    }
+
+    Ok(succeed(write_g1(&result), estimated_ticks))
+}
+
+pub fn g1_multiexp_precompile<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+    _context: &Context,
+    _is_static: bool,
+    _transfer: Option<Transfer>,
+) -> Result<PrecompileOutcome, EthereumError> {
+    call_precompile_with_gas_draining(
+        handler,
+        input,
+        g1_multiexp_precompile_without_gas_draining,
+    )
+}
+
+fn g2_add_precompile_without_gas_draining<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+) -> Result<PrecompileOutcome, EthereumError> {
+    log!(
+        handler.borrow_host(),
+        Info,
+        "Calling BLS12_G2ADD precompile"
+    );
+    if let Err(outcome) = record_cost(handler, gas::G2_ADD) {
+        return Ok(outcome);
+    }
+    let estimated_ticks = fail_if_too_much!(tick_model::ticks_of_g2_add(), handler);
+
+    check_length(input, 2 * G2_LEN)?;
+    let left = read_g2(input, false)?;
+    let right = read_g2(&input[G2_LEN..], false)?;
+    let mut left_jacobian = blst_p2::default();
+    unsafe { blst::blst_p2_from_affine(&mut left_jacobian, &left) };
+
+    Ok(succeed(
+        write_g2(&g2_add(&left_jacobian, &right)),
+        estimated_ticks,
+    ))
+}
+
+pub fn g2_add_precompile<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+    _context: &Context,
+    _is_static: bool,
+    _transfer: Option<Transfer>,
+) -> Result<PrecompileOutcome, EthereumError> {
+    call_precompile_with_gas_draining(
+        handler,
+        input,
+        g2_add_precompile_without_gas_draining,
+    )
+}
+
+fn g2_mul_precompile_without_gas_draining<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+) -> Result<PrecompileOutcome, EthereumError> {
+    log!(
+        handler.borrow_host(),
+        Info,
+        "Calling BLS12_G2MUL precompile"
+    );
+    if let Err(outcome) = record_cost(handler, gas::G2_MUL) {
+        return Ok(outcome);
+    }
+    let estimated_ticks = fail_if_too_much!(tick_model::ticks_of_g2_mul(), handler);
+
+    check_length(input, G2_LEN + SCALAR_LEN)?;
+    let point = read_g2(input, true)?;
+    let scalar = read_scalar(&input[G2_LEN..]);
+
+    Ok(succeed(write_g2(&g2_mul(&point, &scalar)), estimated_ticks))
+}
+
+pub fn g2_mul_precompile<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+    _context: &Context,
+    _is_static: bool,
+    _transfer: Option<Transfer>,
+) -> Result<PrecompileOutcome, EthereumError> {
+    call_precompile_with_gas_draining(
+        handler,
+        input,
+        g2_mul_precompile_without_gas_draining,
+    )
+}
+
+fn g2_multiexp_precompile_without_gas_draining<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+) -> Result<PrecompileOutcome, EthereumError> {
+    log!(
+        handler.borrow_host(),
+        Info,
+        "Calling BLS12_G2MULTIEXP precompile"
+    );
+    const ELEMENT_LEN: usize = G2_LEN + SCALAR_LEN;
+    let k = input.len() / ELEMENT_LEN;
+    if let Err(outcome) = record_cost(handler, gas::multiexp(k, gas::G2_MUL)) {
+        return Ok(outcome);
+    }
+    let estimated_ticks = fail_if_too_much!(tick_model::ticks_of_g2_multiexp(k), handler);
+
+    check_elements_length(input, ELEMENT_LEN)?;
+    let mut result = blst_p2::default();
+    for element in input.chunks_exact(ELEMENT_LEN) {
+        let point = read_g2(element, true)?;
+        let scalar = read_scalar(&element[G2_LEN..]);
+        let mut product = blst_p2_affine::default();
+        unsafe { blst::blst_p2_to_affine(&mut product, &g2_mul(&point, &scalar)) };
+        result = g2_add(&result, &product);
+    }
+
+    Ok(succeed(write_g2(&result), estimated_ticks))
+}
+
+pub fn g2_multiexp_precompile<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+    _context: &Context,
+    _is_static: bool,
+    _transfer: Option<Transfer>,
+) -> Result<PrecompileOutcome, EthereumError> {
+    call_precompile_with_gas_draining(
+        handler,
+        input,
+        g2_multiexp_precompile_without_gas_draining,
+    )
+}
+
+fn pairing_precompile_without_gas_draining<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+) -> Result<PrecompileOutcome, EthereumError> {
+    log!(
+        handler.borrow_host(),
+        Info,
+        "Calling BLS12_PAIRING precompile"
+    );
+    let k = input.len() / PAIR_LEN;
+    let gas_cost = gas::PAIRING_BASE + gas::PAIRING_PER_PAIR * k as u64;
+    if let Err(outcome) = record_cost(handler, gas_cost) {
+        return Ok(outcome);
+    }
+    let estimated_ticks = fail_if_too_much!(tick_model::ticks_of_pairing(k), handler);
+
+    check_elements_length(input, PAIR_LEN)?;
+    let mut product = unsafe { *blst::blst_fp12_one() };
+    for pair in input.chunks_exact(PAIR_LEN) {
+        let g1 = read_g1(pair, true)?;
+        let g2 = read_g2(&pair[G1_LEN..], true)?;
+        // The pairing is one if any of the points is at infinity.
+        if unsafe { blst::blst_p1_affine_is_inf(&g1) || blst::blst_p2_affine_is_inf(&g2) }
+        {
+            continue;
+        }
+        let mut miller_loop = blst_fp12::default();
+        unsafe {
+            blst::blst_miller_loop(&mut miller_loop, &g2, &g1);
+            blst::blst_fp12_mul(&mut product, &product, &miller_loop)
+        };
+    }
+    let mut pairing = blst_fp12::default();
+    let is_one = unsafe {
+        blst::blst_final_exp(&mut pairing, &product);
+        blst::blst_fp12_is_one(&pairing)
+    };
+
+    let mut output = vec![0u8; 32];
+    output[31] = is_one as u8;
+    Ok(succeed(output, estimated_ticks))
+}
+
+pub fn pairing_precompile<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+    _context: &Context,
+    _is_static: bool,
+    _transfer: Option<Transfer>,
+) -> Result<PrecompileOutcome, EthereumError> {
+    call_precompile_with_gas_draining(
+        handler,
+        input,
+        pairing_precompile_without_gas_draining,
+    )
+}
+
+fn map_fp_to_g1_precompile_without_gas_draining<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+) -> Result<PrecompileOutcome, EthereumError> {
+    log!(
+        handler.borrow_host(),
+        Info,
+        "Calling BLS12_MAP_FP_TO_G1 precompile"
+    );
+    if let Err(outcome) = record_cost(handler, gas::MAP_FP_TO_G1) {
+        return Ok(outcome);
+    }
+    let estimated_ticks = fail_if_too_much!(tick_model::ticks_of_map_fp_to_g1(), handler);
+
+    check_length(input, FP_LEN)?;
+    let fp = read_fp(input)?;
+    // Maps to the isogenous curve, then to the curve, and clears the
+    // cofactor, as the EIP.
+    let mut point = blst_p1::default();
+    unsafe { blst::blst_map_to_g1(&mut point, &fp, core::ptr::null()) };
+
+    Ok(succeed(write_g1(&point), estimated_ticks))
+}
+
+pub fn map_fp_to_g1_precompile<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+    _context: &Context,
+    _is_static: bool,
+    _transfer: Option<Transfer>,
+) -> Result<PrecompileOutcome, EthereumError> {
+    call_precompile_with_gas_draining(
+        handler,
+        input,
+        map_fp_to_g1_precompile_without_gas_draining,
+    )
+}
+
+fn map_fp2_to_g2_precompile_without_gas_draining<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+) -> Result<PrecompileOutcome, EthereumError> {
+    log!(
+        handler.borrow_host(),
+        Info,
+        "Calling BLS12_MAP_FP2_TO_G2 precompile"
+    );
+    if let Err(outcome) = record_cost(handler, gas::MAP_FP2_TO_G2) {
+        return Ok(outcome);
+    }
+    let estimated_ticks =
+        fail_if_too_much!(tick_model::ticks_of_map_fp2_to_g2(), handler);
+
+    check_length(input, 2 * FP_LEN)?;
+    let fp2 = read_fp2(input)?;
+    let mut point = blst_p2::default();
+    unsafe { blst::blst_map_to_g2(&mut point, &fp2, core::ptr::null()) };
+
+    Ok(succeed(write_g2(&point), estimated_ticks))
+}
+
+pub fn map_fp2_to_g2_precompile<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+    _context: &Context,
+    _is_static: bool,
+    _transfer: Option<Transfer>,
+) -> Result<PrecompileOutcome, EthereumError> {
+    call_precompile_with_gas_draining(
+        handler,
+        input,
+        map_fp2_to_g2_precompile_without_gas_draining,
+    )
+}
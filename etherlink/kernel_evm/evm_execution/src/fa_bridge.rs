@@ -0,0 +1,610 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Bridge of arbitrary FA tickets to ERC-20 wrappers.
+//!
+//! Each FA ticket deposited on the rollup is wrapped by an ERC-20 contract at
+//! [FaTicket::wrapper_address]. The wrapper is deployed by the kernel on the
+//! first deposit of the ticket, and the deposits are minted by calling the
+//! wrapper from [FA_BRIDGE_ADDRESS]. Holders withdraw their tokens with
+//! `withdraw(uint256 amount, string target)`, which burns the tokens and calls
+//! the precompiled contract at [FA_BRIDGE_ADDRESS] to emit the ticket back to
+//! layer 1.
+
+use crate::abi;
+use crate::account_storage::{account_path, AccountStorageError, EthereumAccountStorage};
+use crate::fail_if_too_much;
+use crate::handler::EvmHandler;
+use crate::precompiles::PrecompileOutcome;
+use crate::{DurableStorageError, EthereumError};
+use alloc::borrow::Cow;
+use const_decoder::Decoder;
+use evm::{Context, ExitReason, ExitRevert, ExitSucceed, Transfer};
+use host::path::{concat, OwnedPath, RefPath};
+use host::runtime::Runtime;
+use num_bigint::BigInt;
+use primitive_types::{H160, U256};
+use rlp::{Decodable, Encodable, Rlp};
+use tezos_data_encoding::enc::BinWriter;
+use tezos_data_encoding::types::Zarith;
+use tezos_ethereum::fa_ticket::FaTicket;
+use tezos_ethereum::withdrawal::{L1Message, Withdrawal};
+use tezos_evm_logging::{log, Level::*};
+use tezos_smart_rollup_core::MAX_OUTPUT_SIZE;
+use tezos_smart_rollup_encoding::contract::Contract;
+use tezos_smart_rollup_encoding::entrypoint::Entrypoint;
+use tezos_smart_rollup_encoding::michelson::ticket::{FA2_1Ticket, Ticket, TicketError};
+use tezos_smart_rollup_encoding::michelson::{
+    MichelsonBytes, MichelsonContract, MichelsonInt, MichelsonOption, MichelsonPair,
+};
+use tezos_smart_rollup_encoding::outbox::{OutboxMessage, OutboxMessageTransaction};
+
+/// Address of the FA bridge: the caller of the mints, and the precompiled
+/// contract called by the wrappers on withdrawals.
+pub const FA_BRIDGE_ADDRESS: H160 = H160([
+    0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02,
+]);
+
+/// Path where the ticket wrapped by each deployed wrapper is stored.
+const FA_TICKETS_PATH: RefPath = RefPath::assert_from(b"/fa_tickets");
+
+/// Selector of `mint(address,uint256)`.
+const MINT_SELECTOR: [u8; 4] = [0x40, 0xc1, 0x0f, 0x19];
+
+/// Selector of `withdraw(uint256,string)`.
+const WITHDRAW_SELECTOR: [u8; 4] = [0xdc, 0x04, 0x8c, 0xf2];
+
+/// Runtime code of the ERC-20 wrappers in hexadecimal.
+const WRAPPER_CODE_HEX: &[u8] = include_bytes!("fa_bridge_wrapper.bin");
+
+const WRAPPER_CODE_SIZE: usize = WRAPPER_CODE_HEX.len() / 2;
+
+/// Runtime code of the ERC-20 wrappers. Besides the ERC-20 interface
+/// (`totalSupply`, `balanceOf`, `transfer`, `allowance`, `approve` and
+/// `transferFrom`), it implements:
+///  - `mint(address to, uint256 amount)`, callable by [FA_BRIDGE_ADDRESS] only,
+///  - `withdraw(uint256 amount, string target)`, which burns `amount` tokens of
+///    the caller and forwards its call data to [FA_BRIDGE_ADDRESS].
+///
+/// The storage follows the layout of Solidity: the balances are the mapping
+/// at slot 0, the allowances the mapping at slot 1, and the total supply is
+/// at slot 2. The reference implementation is `fa_bridge_wrapper.sol`, an
+/// OpenZeppelin ERC-20, whose compiled code is written to
+/// `fa_bridge_wrapper.bin` by `scripts/fa_bridge_wrapper.js`. The CI checks
+/// that the file is up to date.
+pub const WRAPPER_CODE: [u8; WRAPPER_CODE_SIZE] = Decoder::Hex.decode(WRAPPER_CODE_HEX);
+
+fn storage_error<E: Into<DurableStorageError>>(error: E) -> EthereumError {
+    AccountStorageError::from(error.into()).into()
+}
+
+fn ticket_path(wrapper: &H160) -> Result<OwnedPath, EthereumError> {
+    let path = account_path(wrapper).map_err(storage_error)?;
+    concat(&FA_TICKETS_PATH, &path).map_err(storage_error)
+}
+
+/// Ticket wrapped by the contract at `wrapper`, if it is a deployed wrapper.
+pub fn read_wrapped_ticket<Host: Runtime>(
+    host: &Host,
+    wrapper: &H160,
+) -> Result<Option<FaTicket>, EthereumError> {
+    let path = ticket_path(wrapper)?;
+    if host.store_has(&path).map_err(storage_error)?.is_none() {
+        return Ok(None);
+    }
+    let bytes = host.store_read_all(&path).map_err(storage_error)?;
+    let ticket = FaTicket::decode(&Rlp::new(&bytes)).map_err(|_| {
+        EthereumError::InconsistentState(Cow::from("Invalid wrapped FA ticket"))
+    })?;
+    Ok(Some(ticket))
+}
+
+/// Deploys the wrapper of `ticket` if it doesn't exist yet, and returns its
+/// address.
+pub fn deploy_wrapper<Host: Runtime>(
+    host: &mut Host,
+    evm_account_storage: &mut EthereumAccountStorage,
+    ticket: &FaTicket,
+) -> Result<H160, EthereumError> {
+    let wrapper = ticket.wrapper_address();
+    if read_wrapped_ticket(host, &wrapper)?.is_none() {
+        let path = account_path(&wrapper).map_err(storage_error)?;
+        let mut account = evm_account_storage.get_or_create(host, &path)?;
+        account.set_code(host, &WRAPPER_CODE)?;
+        // Contracts start with a nonce of 1 (EIP-161).
+        account.set_nonce(host, U256::one())?;
+        host.store_write_all(&ticket_path(&wrapper)?, &ticket.rlp_bytes())
+            .map_err(storage_error)?;
+        log!(
+            host,
+            Info,
+            "Deployed the wrapper of {:?} at {}",
+            ticket,
+            wrapper
+        );
+    }
+    Ok(wrapper)
+}
+
+/// Call data of `mint(receiver, amount)`.
+pub fn mint_call_data(receiver: &H160, amount: U256) -> Vec<u8> {
+    let mut data = Vec::with_capacity(4 + 2 * 32);
+    data.extend_from_slice(&MINT_SELECTOR);
+    data.extend_from_slice(&[0; 12]);
+    data.extend_from_slice(receiver.as_bytes());
+    let mut amount_bytes = [0; 32];
+    amount.to_big_endian(&mut amount_bytes);
+    data.extend_from_slice(&amount_bytes);
+    data
+}
+
+/// Precompiled contract called by the wrappers on `withdraw`, with the call
+/// data of `withdraw`. The tokens are already burnt by the wrapper.
+pub fn fa_withdrawal_precompile<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+    context: &Context,
+    _is_static: bool,
+    transfer: Option<Transfer>,
+) -> Result<PrecompileOutcome, EthereumError> {
+    let estimated_ticks = fail_if_too_much!(ticks_of_fa_withdraw(), handler);
+    fn revert_withdrawal() -> PrecompileOutcome {
+        PrecompileOutcome {
+            exit_status: ExitReason::Revert(ExitRevert::Reverted),
+            output: vec![],
+            withdrawals: vec![],
+            estimated_ticks: ticks_of_fa_withdraw(),
+        }
+    }
+
+    if transfer.map_or(false, |transfer| !transfer.value.is_zero()) {
+        log!(
+            handler.borrow_host(),
+            Info,
+            "FA withdrawal: unexpected transfer"
+        );
+        return Ok(revert_withdrawal());
+    }
+
+    let Some(ticket) = read_wrapped_ticket(handler.borrow_host(), &context.caller)? else {
+        log!(handler.borrow_host(), Info, "FA withdrawal: the caller is not a wrapper");
+        return Ok(revert_withdrawal())
+    };
+
+    let Some(rest) = input.strip_prefix(&WITHDRAW_SELECTOR) else {
+        log!(handler.borrow_host(), Info, "FA withdrawal: invalid function selector");
+        return Ok(revert_withdrawal())
+    };
+
+    let amount = abi::u256_parameter(rest, 0).unwrap_or_default();
+    if amount.is_zero() {
+        log!(
+            handler.borrow_host(),
+            Info,
+            "FA withdrawal: withdrawal of 0"
+        );
+        return Ok(revert_withdrawal());
+    }
+
+    let Some(target) = abi::string_parameter(rest, 1).and_then(Withdrawal::address_from_str) else {
+        log!(handler.borrow_host(), Info, "FA withdrawal: invalid target address");
+        return Ok(revert_withdrawal())
+    };
+
+    if !fa_withdrawal_transaction(&target, &ticket, amount)
+        .map_or(false, fits_in_outbox_message)
+    {
+        log!(
+            handler.borrow_host(),
+            Info,
+            "FA withdrawal: outbox message too big"
+        );
+        return Ok(revert_withdrawal());
+    }
+
+    log!(
+        handler.borrow_host(),
+        Info,
+        "FA withdrawal of {} {:?} to {:?}",
+        amount,
+        ticket,
+        target
+    );
+
+    Ok(PrecompileOutcome {
+        exit_status: ExitReason::Succeed(ExitSucceed::Returned),
+        output: vec![],
+        withdrawals: vec![L1Message::Withdrawal(Withdrawal {
+            target,
+            amount,
+            ticket: Some(ticket),
+        })],
+        estimated_ticks,
+    })
+}
+
+/// Parameters of the `withdraw` entrypoint of the ticketers, the target of
+/// the withdrawal and the ticket.
+pub type FaWithdrawalParameters = MichelsonPair<MichelsonContract, FA2_1Ticket>;
+
+fn u256_to_bigint(value: U256) -> BigInt {
+    let mut bytes = [0; 32];
+    value.to_little_endian(&mut bytes);
+    BigInt::from_bytes_le(num_bigint::Sign::Plus, &bytes)
+}
+
+/// Outbox transaction sending `amount` of a FA ticket back to the `withdraw`
+/// entrypoint of its ticketer, on behalf of `target`.
+pub fn fa_withdrawal_transaction(
+    target: &Contract,
+    ticket: &FaTicket,
+    amount: U256,
+) -> Result<OutboxMessageTransaction<FaWithdrawalParameters>, TicketError> {
+    let destination = Contract::Originated(ticket.ticketer.clone());
+    let ticket = Ticket::new(
+        destination.clone(),
+        MichelsonPair(
+            MichelsonInt(Zarith(u256_to_bigint(ticket.token_id))),
+            MichelsonOption(ticket.metadata.clone().map(MichelsonBytes)),
+        ),
+        u256_to_bigint(amount),
+    )?;
+    Ok(OutboxMessageTransaction {
+        parameters: MichelsonPair(MichelsonContract(target.clone()), ticket),
+        destination,
+        // NB: The `unwrap()` here is safe, `withdraw` is a valid entrypoint.
+        entrypoint: Entrypoint::try_from(String::from("withdraw")).unwrap(),
+    })
+}
+
+/// Checks that the withdrawal fits in an outbox message on its own, so that
+/// the burnt tokens can't be lost.
+fn fits_in_outbox_message(
+    transaction: OutboxMessageTransaction<FaWithdrawalParameters>,
+) -> bool {
+    let message = OutboxMessage::AtomicTransactionBatch(vec![transaction].into());
+    let mut encoded = Vec::with_capacity(MAX_OUTPUT_SIZE);
+    message.bin_write(&mut encoded).is_ok() && encoded.len() <= MAX_OUTPUT_SIZE
+}
+
+fn ticks_of_fa_withdraw() -> u64 {
+    1_100_000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account_storage::init_account_storage;
+    use crate::handler::ExecutionOutcome;
+    use crate::precompiles::precompile_set;
+    use crate::{run_transaction, EvmFork};
+    use crypto::hash::{ContractKt1Hash, HashTrait};
+    use evm::Opcode;
+    use tezos_ethereum::block::{BlockConstants, BlockFees};
+    use tezos_smart_rollup_encoding::contract::Contract;
+    use tezos_smart_rollup_mock::MockHost;
+
+    // Assembles the listing of `fa_bridge_wrapper.evm`: one instruction per
+    // line, `;` starts a comment, `label:` marks the position of the next
+    // instruction and `PUSH2 @label` pushes that position.
+    fn assemble(listing: &str) -> Vec<u8> {
+        let opcodes = [
+            ("STOP", Opcode::STOP),
+            ("ADD", Opcode::ADD),
+            ("SUB", Opcode::SUB),
+            ("GT", Opcode::GT),
+            ("EQ", Opcode::EQ),
+            ("ISZERO", Opcode::ISZERO),
+            ("AND", Opcode::AND),
+            ("SHR", Opcode::SHR),
+            ("SHA3", Opcode::SHA3),
+            ("CALLER", Opcode::CALLER),
+            ("CALLVALUE", Opcode::CALLVALUE),
+            ("CALLDATALOAD", Opcode::CALLDATALOAD),
+            ("CALLDATASIZE", Opcode::CALLDATASIZE),
+            ("CALLDATACOPY", Opcode::CALLDATACOPY),
+            ("MSTORE", Opcode::MSTORE),
+            ("SLOAD", Opcode::SLOAD),
+            ("SSTORE", Opcode::SSTORE),
+            ("JUMPI", Opcode::JUMPI),
+            ("GAS", Opcode::GAS),
+            ("JUMPDEST", Opcode::JUMPDEST),
+            ("DUP1", Opcode::DUP1),
+            ("DUP2", Opcode::DUP2),
+            ("DUP3", Opcode::DUP3),
+            ("DUP4", Opcode::DUP4),
+            ("SWAP1", Opcode::SWAP1),
+            ("LOG3", Opcode::LOG3),
+            ("CALL", Opcode::CALL),
+            ("RETURN", Opcode::RETURN),
+            ("REVERT", Opcode::REVERT),
+        ];
+        let instructions: Vec<Vec<&str>> = listing
+            .lines()
+            .map(|line| line.split(';').next().unwrap().split_whitespace().collect())
+            .filter(|words: &Vec<&str>| !words.is_empty())
+            .collect();
+        let push_size =
+            |push: &str| -> usize { push.strip_prefix("PUSH").unwrap().parse().unwrap() };
+
+        let mut labels = std::collections::BTreeMap::new();
+        let mut position = 0;
+        for words in &instructions {
+            match words[..] {
+                [label] if label.ends_with(':') => {
+                    labels.insert(label.trim_end_matches(':'), position);
+                }
+                [push, _] => position += 1 + push_size(push),
+                _ => position += 1,
+            }
+        }
+
+        let mut code = vec![];
+        for words in &instructions {
+            match words[..] {
+                [label] if label.ends_with(':') => (),
+                [name] => {
+                    let (_, opcode) = opcodes
+                        .iter()
+                        .find(|(known, _)| *known == name)
+                        .unwrap_or_else(|| panic!("Unknown instruction {}", name));
+                    code.push(opcode.as_u8())
+                }
+                [push, argument] => {
+                    let size = push_size(push);
+                    code.push(Opcode::PUSH1.as_u8() + size as u8 - 1);
+                    let bytes = match argument.strip_prefix('@') {
+                        Some(label) => (labels[label] as u16).to_be_bytes().to_vec(),
+                        None => {
+                            hex::decode(argument.strip_prefix("0x").unwrap()).unwrap()
+                        }
+                    };
+                    assert_eq!(bytes.len(), size, "Invalid argument {}", argument);
+                    code.extend(bytes)
+                }
+                _ => panic!("Invalid instruction {:?}", words),
+            }
+        }
+        code
+    }
+
+    #[test]
+    fn test_wrapper_code_is_assembled_from_its_source() {
+        let listing = include_str!("fa_bridge_wrapper.evm");
+        assert_eq!(assemble(listing), WRAPPER_CODE);
+    }
+
+    fn ticket() -> FaTicket {
+        FaTicket {
+            ticketer: ContractKt1Hash::from_b58check(
+                "KT1BuEZtb68c1Q4yjtckcNjGELqWt56Xyesc",
+            )
+            .unwrap(),
+            token_id: U256::one(),
+            metadata: None,
+        }
+    }
+
+    fn word(value: U256) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        value.to_big_endian(&mut bytes);
+        bytes
+    }
+
+    fn address_word(address: &H160) -> [u8; 32] {
+        word(U256::from_big_endian(address.as_bytes()))
+    }
+
+    fn call_data(selector: &str, words: &[[u8; 32]]) -> Vec<u8> {
+        let mut data = hex::decode(selector).unwrap();
+        words.iter().for_each(|w| data.extend_from_slice(w));
+        data
+    }
+
+    fn withdraw_call_data(amount: U256, target: &str) -> Vec<u8> {
+        let mut data = call_data(
+            "dc048cf2",
+            &[word(amount), word(64.into()), word(target.len().into())],
+        );
+        data.extend_from_slice(target.as_bytes());
+        data.resize(data.len() + (32 - target.len() % 32) % 32, 0);
+        data
+    }
+
+    struct Chain {
+        host: MockHost,
+        block: BlockConstants,
+        storage: EthereumAccountStorage,
+        wrapper: H160,
+    }
+
+    impl Chain {
+        fn new() -> Self {
+            Self::with_ticket(&ticket())
+        }
+
+        fn with_ticket(ticket: &FaTicket) -> Self {
+            let mut host = MockHost::default();
+            let mut storage = init_account_storage().unwrap();
+            let wrapper = deploy_wrapper(&mut host, &mut storage, ticket).unwrap();
+            let block_fees = BlockFees::new(U256::zero(), U256::zero());
+            let block =
+                BlockConstants::first_block(U256::zero(), U256::one(), block_fees);
+            Self {
+                host,
+                block,
+                storage,
+                wrapper,
+            }
+        }
+
+        fn call(&mut self, caller: H160, data: Vec<u8>) -> ExecutionOutcome {
+            let precompiles = precompile_set::<MockHost>();
+            run_transaction(
+                &mut self.host,
+                &self.block,
+                &mut self.storage,
+                &precompiles,
+                EvmFork::Shanghai,
+                Some(self.wrapper),
+                caller,
+                data,
+                Some(1_000_000),
+                U256::zero(),
+                U256::zero(),
+                None,
+                false,
+                10_000_000_000,
+            )
+            .unwrap()
+            .unwrap()
+        }
+
+        fn read(&mut self, data: Vec<u8>) -> U256 {
+            let outcome = self.call(H160::zero(), data);
+            assert!(outcome.is_success);
+            U256::from_big_endian(&outcome.result.unwrap())
+        }
+
+        fn balance_of(&mut self, owner: &H160) -> U256 {
+            self.read(call_data("70a08231", &[address_word(owner)]))
+        }
+
+        fn total_supply(&mut self) -> U256 {
+            self.read(call_data("18160ddd", &[]))
+        }
+    }
+
+    #[test]
+    fn test_deploy_wrapper_is_idempotent() {
+        let mut chain = Chain::new();
+        let wrapper =
+            deploy_wrapper(&mut chain.host, &mut chain.storage, &ticket()).unwrap();
+        assert_eq!(wrapper, chain.wrapper);
+        assert_eq!(wrapper, ticket().wrapper_address());
+        assert_eq!(
+            read_wrapped_ticket(&chain.host, &wrapper).unwrap(),
+            Some(ticket())
+        );
+        assert_eq!(
+            read_wrapped_ticket(&chain.host, &H160::from_low_u64_be(1)).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_only_the_bridge_mints() {
+        let mut chain = Chain::new();
+        let alice = H160::from_low_u64_be(0xa11ce);
+
+        let outcome = chain.call(alice, mint_call_data(&alice, 100.into()));
+        assert!(!outcome.is_success);
+        assert_eq!(chain.balance_of(&alice), U256::zero());
+
+        let outcome = chain.call(FA_BRIDGE_ADDRESS, mint_call_data(&alice, 100.into()));
+        assert!(outcome.is_success);
+        assert_eq!(outcome.logs.len(), 1);
+        assert_eq!(chain.balance_of(&alice), U256::from(100));
+        assert_eq!(chain.total_supply(), U256::from(100));
+    }
+
+    #[test]
+    fn test_transfers() {
+        let mut chain = Chain::new();
+        let alice = H160::from_low_u64_be(0xa11ce);
+        let bob = H160::from_low_u64_be(0xb0b);
+        chain.call(FA_BRIDGE_ADDRESS, mint_call_data(&alice, 100.into()));
+
+        let transfer = |to: &H160, amount: u64| {
+            call_data("a9059cbb", &[address_word(to), word(amount.into())])
+        };
+        assert!(!chain.call(alice, transfer(&bob, 101)).is_success);
+        assert!(chain.call(alice, transfer(&bob, 30)).is_success);
+        assert_eq!(chain.balance_of(&alice), U256::from(70));
+        assert_eq!(chain.balance_of(&bob), U256::from(30));
+
+        let approve = call_data("095ea7b3", &[address_word(&bob), word(50.into())]);
+        assert!(chain.call(alice, approve).is_success);
+        let allowance =
+            call_data("dd62ed3e", &[address_word(&alice), address_word(&bob)]);
+        assert_eq!(chain.read(allowance.clone()), U256::from(50));
+
+        let transfer_from = |amount: u64| {
+            call_data(
+                "23b872dd",
+                &[
+                    address_word(&alice),
+                    address_word(&bob),
+                    word(amount.into()),
+                ],
+            )
+        };
+        assert!(!chain.call(bob, transfer_from(51)).is_success);
+        assert!(chain.call(bob, transfer_from(20)).is_success);
+        assert_eq!(chain.read(allowance), U256::from(30));
+        assert_eq!(chain.balance_of(&alice), U256::from(50));
+        assert_eq!(chain.balance_of(&bob), U256::from(50));
+        assert_eq!(chain.total_supply(), U256::from(100));
+    }
+
+    #[test]
+    fn test_withdraw_burns_and_emits_the_ticket() {
+        let mut chain = Chain::new();
+        let alice = H160::from_low_u64_be(0xa11ce);
+        let target = "tz1RjtZUVeLhADFHDL8UwDZA6vjWWhojpu5w";
+        chain.call(FA_BRIDGE_ADDRESS, mint_call_data(&alice, 100.into()));
+
+        let outcome = chain.call(alice, withdraw_call_data(101.into(), target));
+        assert!(!outcome.is_success);
+        assert!(outcome.withdrawals.is_empty());
+
+        let outcome = chain.call(alice, withdraw_call_data(40.into(), "invalid"));
+        assert!(!outcome.is_success);
+        assert!(outcome.withdrawals.is_empty());
+
+        let outcome = chain.call(alice, withdraw_call_data(40.into(), target));
+        assert!(outcome.is_success);
+        assert_eq!(
+            outcome.withdrawals,
+            vec![L1Message::Withdrawal(Withdrawal {
+                target: Contract::from_b58check(target).unwrap(),
+                amount: 40.into(),
+                ticket: Some(ticket()),
+            })]
+        );
+        assert_eq!(chain.balance_of(&alice), U256::from(60));
+        assert_eq!(chain.total_supply(), U256::from(60));
+    }
+
+    #[test]
+    fn test_withdrawals_too_big_for_the_outbox_revert() {
+        let ticket = FaTicket {
+            metadata: Some(vec![0xab; MAX_OUTPUT_SIZE]),
+            ..ticket()
+        };
+        let mut chain = Chain::with_ticket(&ticket);
+        let alice = H160::from_low_u64_be(0xa11ce);
+        let target = "tz1RjtZUVeLhADFHDL8UwDZA6vjWWhojpu5w";
+        chain.call(FA_BRIDGE_ADDRESS, mint_call_data(&alice, 100.into()));
+
+        let outcome = chain.call(alice, withdraw_call_data(40.into(), target));
+        assert!(!outcome.is_success);
+        assert!(outcome.withdrawals.is_empty());
+        assert_eq!(chain.balance_of(&alice), U256::from(100));
+        assert_eq!(chain.total_supply(), U256::from(100));
+    }
+
+    #[test]
+    fn test_only_wrappers_withdraw() {
+        let mut chain = Chain::new();
+        let alice = H160::from_low_u64_be(0xa11ce);
+        chain.wrapper = FA_BRIDGE_ADDRESS;
+        let outcome = chain.call(
+            alice,
+            withdraw_call_data(40.into(), "tz1RjtZUVeLhADFHDL8UwDZA6vjWWhojpu5w"),
+        );
+        assert!(!outcome.is_success);
+        assert!(outcome.withdrawals.is_empty());
+    }
+}
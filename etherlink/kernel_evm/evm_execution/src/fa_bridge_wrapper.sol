@@ -0,0 +1,45 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+pragma solidity 0.8.24;
+
+import {ERC20} from "@openzeppelin/contracts/token/ERC20/ERC20.sol";
+
+/// ERC-20 wrapper of an FA ticket, `WRAPPER_CODE` in `fa_bridge.rs` is its
+/// runtime code, written to `fa_bridge_wrapper.bin` by
+/// `scripts/fa_bridge_wrapper.js`.
+///
+/// The kernel deploys the runtime code without running the constructor, the
+/// name and the symbol are therefore empty. The storage follows the layout of
+/// `ERC20`: the balances are the mapping at slot 0, the allowances the
+/// mapping at slot 1, and the total supply is at slot 2.
+contract FaBridgeWrapper is ERC20 {
+    /// `FA_BRIDGE_ADDRESS` in `fa_bridge.rs`, the only caller allowed to mint
+    /// and the precompiled contract emitting the withdrawals.
+    address constant BRIDGE = 0xff00000000000000000000000000000000000002;
+
+    error OnlyBridge();
+
+    error WithdrawalFailed();
+
+    constructor() ERC20("", "") {}
+
+    /// Mints the tokens of a deposit of the ticket.
+    function mint(address to, uint256 amount) external {
+        if (msg.sender != BRIDGE) {
+            revert OnlyBridge();
+        }
+        _mint(to, amount);
+    }
+
+    /// Burns `amount` tokens of the caller and forwards the call to the FA
+    /// bridge, which emits the ticket back to `target` on layer 1.
+    function withdraw(uint256 amount, string calldata /* target */) external {
+        _burn(msg.sender, amount);
+        (bool success, ) = BRIDGE.call(msg.data);
+        if (!success) {
+            revert WithdrawalFailed();
+        }
+    }
+}
@@ -0,0 +1,75 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! EVM hard forks supported by the execution engine
+//!
+//! SputnikVM describes the semantics of a fork with a [Config], but it
+//! doesn't know about forks more recent than Shanghai. Features of later
+//! forks are implemented by the [crate::handler::EvmHandler], which checks
+//! the [EvmFork] it was created with.
+
+use evm::Config;
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+
+/// The SputnikVM configuration of Shanghai.
+static SHANGHAI_CONFIG: Config = Config::shanghai();
+
+/// The SputnikVM configuration of Cancun. SputnikVM has no Cancun
+/// configuration, so it is derived from Shanghai: Cancun doesn't change the
+/// gas costs of existing opcodes. The handler prices the opcodes it adds
+/// from this configuration.
+static CANCUN_CONFIG: Config = Config {
+    // EIP-1153: TLOAD and TSTORE cost as much as a warm storage read
+    gas_storage_read_warm: 100,
+    ..Config::shanghai()
+};
+
+/// An EVM hard fork. Forks are ordered by activation, i.e. a fork compares
+/// greater than all the forks it supersedes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum EvmFork {
+    #[default]
+    Shanghai,
+    /// Adds transient storage (EIP-1153), MCOPY (EIP-5656) and restricts
+    /// SELFDESTRUCT (EIP-6780).
+    Cancun,
+}
+
+impl EvmFork {
+    /// The SputnikVM configuration for the fork.
+    pub fn config(self) -> &'static Config {
+        match self {
+            EvmFork::Shanghai => &SHANGHAI_CONFIG,
+            EvmFork::Cancun => &CANCUN_CONFIG,
+        }
+    }
+
+    /// Whether the features of Cancun are enabled.
+    pub fn is_cancun(self) -> bool {
+        self >= EvmFork::Cancun
+    }
+}
+
+const SHANGHAI_TAG: u8 = 0;
+const CANCUN_TAG: u8 = 1;
+
+impl Encodable for EvmFork {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        let tag = match self {
+            EvmFork::Shanghai => SHANGHAI_TAG,
+            EvmFork::Cancun => CANCUN_TAG,
+        };
+        stream.append(&tag);
+    }
+}
+
+impl Decodable for EvmFork {
+    fn decode(decoder: &Rlp) -> Result<Self, DecoderError> {
+        match decoder.as_val::<u8>()? {
+            SHANGHAI_TAG => Ok(EvmFork::Shanghai),
+            CANCUN_TAG => Ok(EvmFork::Cancun),
+            _ => Err(DecoderError::Custom("Unknown EVM fork")),
+        }
+    }
+}
@@ -12,6 +12,8 @@ use crate::account_storage::{
     account_path, AccountStorageError, EthereumAccount, EthereumAccountStorage,
     CODE_HASH_DEFAULT,
 };
+use crate::fork::EvmFork;
+use crate::trace::{exit_reason_error, CallType, Step, Tracer};
 use crate::transaction::TransactionContext;
 use crate::ArithmeticErrorKind::FeeOverflow;
 use crate::EthereumError;
@@ -24,21 +26,32 @@ use evm::executor::stack::Log;
 use evm::gasometer::{GasCost, Gasometer, MemoryCost};
 use evm::{
     Capture, Config, Context, CreateScheme, ExitError, ExitFatal, ExitReason, ExitRevert,
-    ExitSucceed, Handler, Opcode, Stack, Transfer,
+    ExitSucceed, Handler, Machine, Opcode, Stack, Transfer,
 };
 use host::runtime::Runtime;
 use primitive_types::{H160, H256, U256};
 use sha3::{Digest, Keccak256};
 use std::cmp::min;
-use std::collections::BTreeSet;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Debug;
 use tezos_ethereum::block::BlockConstants;
-use tezos_ethereum::withdrawal::Withdrawal;
+use tezos_ethereum::withdrawal::L1Message;
 use tezos_evm_logging::{log, Level::*};
 
 /// Maximum allowed code size as specified by EIP-170
 const MAX_CODE_SIZE: usize = 0x6000;
 
+// Opcodes introduced by the Cancun hard fork. SputnikVM doesn't know about
+// them, so they are implemented by the handler, see [EvmHandler::other].
+
+/// Load a word from transient storage, see EIP-1153
+pub const TLOAD: Opcode = Opcode(0x5c);
+/// Save a word to transient storage, see EIP-1153
+pub const TSTORE: Opcode = Opcode(0x5d);
+/// Copy a memory area, see EIP-5656
+pub const MCOPY: Opcode = Opcode(0x5e);
+
 /// Outcome of making the [EvmHandler] run an Ethereum transaction
 ///
 /// Be it contract -call, -create or simple transfer, the handler will update the world
@@ -63,9 +76,10 @@ pub struct ExecutionOutcome {
     pub logs: Vec<Log>,
     /// Result of the execution
     pub result: Option<Vec<u8>>,
-    /// Withdrawals generated by the transaction. This field will be empty if the
-    /// transaction fails (or if the transaction doesn't produce any withdrawals).
-    pub withdrawals: Vec<Withdrawal>,
+    /// Withdrawals and layer 1 calls generated by the transaction, in order.
+    /// This field will be empty if the transaction fails (or if the
+    /// transaction doesn't produce any).
+    pub withdrawals: Vec<L1Message>,
     /// Number of estimated ticks used at the end of the contract call
     pub estimated_ticks_used: u64,
 }
@@ -117,9 +131,16 @@ struct TransactionLayerData<'config> {
     /// The addresses of contracts that have been deleted as part of
     /// the current transaction.
     pub deleted_contracts: BTreeSet<H160>,
-    /// Any withdrawals generated by the current transaction level and
-    /// successful sub-levels.
-    pub withdrawals: Vec<Withdrawal>,
+    /// The addresses of contracts that have been created as part of the
+    /// current transaction and committed sub layers. Following EIP-6780,
+    /// only these contracts can be deleted by `SELFDESTRUCT`.
+    pub created_contracts: BTreeSet<H160>,
+    /// Transient storage (EIP-1153) written in this layer and committed sub
+    /// layers, indexed by contract address and storage index.
+    pub transient_storage: BTreeMap<(H160, H256), H256>,
+    /// Any withdrawals and layer 1 calls generated by the current transaction
+    /// level and successful sub-levels.
+    pub withdrawals: Vec<L1Message>,
 }
 
 impl<'config> TransactionLayerData<'config> {
@@ -134,6 +155,8 @@ impl<'config> TransactionLayerData<'config> {
             is_static,
             logs: vec![],
             deleted_contracts: BTreeSet::new(),
+            created_contracts: BTreeSet::new(),
+            transient_storage: BTreeMap::new(),
             withdrawals: vec![],
         }
     }
@@ -257,6 +280,10 @@ pub fn create_address_legacy(caller: &H160, nonce: &U256) -> H160 {
     H256::from_slice(Keccak256::digest(&stream.out()).as_slice()).into()
 }
 
+/// The accounts modified by a transaction, with the storage slots it wrote
+/// for each of them. It is an overapproximation: writes rolled back are kept.
+pub type TouchedAccounts = BTreeMap<H160, BTreeSet<H256>>;
+
 /// The implementation of the SputnikVM [Handler] trait
 pub struct EvmHandler<'a, Host: Runtime> {
     /// The host
@@ -269,7 +296,9 @@ pub struct EvmHandler<'a, Host: Runtime> {
     pub block: &'a BlockConstants,
     /// The precompiled functions
     precompiles: &'a dyn PrecompileSet<Host>,
-    /// The configuration, eg, London or Frontier for execution
+    /// The fork whose semantics are used for execution
+    fork: EvmFork,
+    /// The SputnikVM configuration of the fork
     config: &'a Config,
     /// The contexts associated with transaction(s) currently in
     /// progress
@@ -281,6 +310,17 @@ pub struct EvmHandler<'a, Host: Runtime> {
     pub estimated_ticks_used: u64,
     /// The effective gas price of the current transaction
     effective_gas_price: U256,
+    /// The address of the contract executing the current transient storage
+    /// opcode. [Handler::other], which implements them, doesn't have access
+    /// to the execution context, so it is set by [Handler::pre_validate].
+    transient_storage_context: Option<H160>,
+    /// Records the trace of the execution, if it is requested
+    tracer: Option<Tracer>,
+    /// The accounts modified since the handler was created
+    touched_accounts: TouchedAccounts,
+    /// Ticks needed to update the state trie for `touched_accounts`, kept
+    /// up to date by [EvmHandler::touch] and [EvmHandler::touch_storage]
+    touched_accounts_ticks: u64,
 }
 
 impl<'a, Host: Runtime> EvmHandler<'a, Host> {
@@ -291,7 +331,7 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
         evm_account_storage: &'a mut EthereumAccountStorage,
         origin: H160,
         block: &'a BlockConstants,
-        config: &'a Config,
+        fork: EvmFork,
         precompiles: &'a dyn PrecompileSet<Host>,
         ticks_allocated: u64,
         effective_gas_price: U256,
@@ -301,12 +341,57 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
             evm_account_storage,
             origin,
             block,
-            config,
+            fork,
+            config: fork.config(),
             precompiles,
             transaction_data: vec![],
             ticks_allocated,
             estimated_ticks_used: 0,
             effective_gas_price,
+            transient_storage_context: None,
+            tracer: None,
+            touched_accounts: BTreeMap::new(),
+            touched_accounts_ticks: 0,
+        }
+    }
+
+    /// Record the trace of the next executions with `tracer`
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    /// Stop tracing and get the tracer, with the trace recorded so far
+    pub fn take_tracer(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+
+    /// Get the accounts modified since the handler was created, and forget
+    /// them.
+    pub fn take_touched_accounts(&mut self) -> TouchedAccounts {
+        self.touched_accounts_ticks = 0;
+        core::mem::take(&mut self.touched_accounts)
+    }
+
+    /// Record that the account at `address` is modified.
+    fn touch(&mut self, address: H160) -> &mut BTreeSet<H256> {
+        match self.touched_accounts.entry(address) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                self.touched_accounts_ticks = self
+                    .touched_accounts_ticks
+                    .saturating_add(tick_model_opcodes::TICKS_PER_TOUCHED_ACCOUNT);
+                entry.insert(BTreeSet::new())
+            }
+        }
+    }
+
+    /// Record that the storage slot `index` of the contract at `address` is
+    /// modified.
+    fn touch_storage(&mut self, address: H160, index: H256) {
+        if self.touch(address).insert(index) {
+            self.touched_accounts_ticks = self
+                .touched_accounts_ticks
+                .saturating_add(tick_model_opcodes::TICKS_PER_TOUCHED_SLOT);
         }
     }
 
@@ -449,10 +534,10 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
         self.record_cost(base_cost + data_cost)
     }
 
-    /// Add withdrawals to the current transaction layer
+    /// Add withdrawals and layer 1 calls to the current transaction layer
     fn add_withdrawals(
         &mut self,
-        withdrawals: &mut Vec<Withdrawal>,
+        withdrawals: &mut Vec<L1Message>,
     ) -> Result<(), EthereumError> {
         match self.transaction_data.last_mut() {
             Some(layer) => {
@@ -465,20 +550,22 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
         }
     }
 
-    /// Have the caller account pay for gas. Returns `Ok(true)` if the payment
-    /// went through; returns `Ok(false)` if `caller` doesn't have the funds.
-    /// Return `Err(...)` in case something is at fault with durable storage or
-    /// runtime.
+    /// Have the caller account pay for gas and for the data availability fee.
+    /// Returns `Ok(true)` if the payment went through; returns `Ok(false)` if
+    /// `caller` doesn't have the funds. Return `Err(...)` in case something is
+    /// at fault with durable storage or runtime.
     pub fn pre_pay_transactions(
         &mut self,
         caller: H160,
         gas_limit: Option<u64>,
         effective_gas_price: U256,
+        da_fee: U256,
     ) -> Result<bool, EthereumError> {
         let Some(gas_limit) = gas_limit else { return Ok(true) };
 
         let amount = U256::from(gas_limit)
             .checked_mul(effective_gas_price)
+            .and_then(|amount| amount.checked_add(da_fee))
             .ok_or(EthereumError::ArithmeticError(FeeOverflow))?;
 
         log!(
@@ -492,17 +579,20 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
             .map_err(EthereumError::from)
     }
 
-    /// Repay unused gas
+    /// Repay unused gas, and the data availability fee `da_fee` if the
+    /// transaction did not go through.
     pub fn repay_gas(
         &mut self,
         caller: H160,
         unused_gas: Option<u64>,
         effective_gas_price: U256,
+        da_fee: U256,
     ) -> Result<(), EthereumError> {
         let Some(unused_gas) = unused_gas else { return Ok(()) };
 
         let amount = U256::from(unused_gas)
             .checked_mul(effective_gas_price)
+            .and_then(|amount| amount.checked_add(da_fee))
             .ok_or(EthereumError::ArithmeticError(FeeOverflow))?;
 
         log!(
@@ -516,20 +606,117 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
             .map_err(EthereumError::from)
     }
 
-    /// Account for the estimated ticks spent during the execution of the given opcode
+    /// Account for the estimated ticks spent during the execution of the given opcode.
+    /// The ticks the kernel needs to update the state trie once the transaction
+    /// is applied are reserved as well.
     pub fn account_for_ticks(
         &mut self,
         opcode: &Opcode,
         gas: u64,
     ) -> Result<(), EthereumError> {
         self.estimated_ticks_used += tick_model_opcodes::ticks(opcode, gas);
-        if self.estimated_ticks_used > self.ticks_allocated {
+        if self
+            .estimated_ticks_used
+            .saturating_add(self.touched_accounts_ticks)
+            > self.ticks_allocated
+        {
             Err(EthereumError::OutOfTicks)
         } else {
             Ok(())
         }
     }
 
+    /// Notify the tracer that `opcode` is about to be executed, and capture
+    /// the state of the machine if the struct logger is used.
+    fn start_traced_step(
+        &mut self,
+        runtime: &evm::Runtime,
+        opcode: Opcode,
+    ) -> Option<Step> {
+        let tracer = self.tracer.as_mut()?;
+        tracer.start_opcode(opcode);
+        let config = tracer.struct_logger_config()?;
+        let machine = runtime.machine();
+        let stack = machine.stack().data();
+        // Only the parts of the machine which are recorded are copied. The
+        // storage accesses need the two top elements of the stack.
+        let stack = if !config.disable_stack {
+            stack.clone()
+        } else if matches!(opcode, Opcode::SLOAD | Opcode::SSTORE) {
+            stack[stack.len().saturating_sub(2)..].to_vec()
+        } else {
+            vec![]
+        };
+        let memory = if config.enable_memory {
+            let memory = machine.memory();
+            memory.get(0, memory.effective_len().low_u64() as usize)
+        } else {
+            vec![]
+        };
+        Some(Step {
+            pc: machine.position().as_ref().map_or(0, |pc| *pc as u64),
+            opcode,
+            gas: self.gas_remaining(),
+            depth: self.evm_account_storage.stack_depth() as u64,
+            address: runtime.context().address,
+            stack,
+            memory,
+        })
+    }
+
+    /// Start a call frame in the tracer, if any. `from` is the caller, or the
+    /// executing contract in case of `DELEGATECALL`, given by `context`.
+    #[allow(clippy::too_many_arguments)]
+    fn begin_trace_frame(
+        &mut self,
+        is_create: bool,
+        caller: H160,
+        context_address: Option<H160>,
+        to: Option<H160>,
+        value: Option<U256>,
+        gas_limit: Option<u64>,
+        input: &[u8],
+    ) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            let call_type = tracer.call_type(is_create);
+            let from = match (call_type, context_address) {
+                (CallType::DelegateCall, Some(address)) => address,
+                _ => caller,
+            };
+            tracer.begin_frame(
+                call_type,
+                from,
+                to,
+                value,
+                gas_limit.unwrap_or_default(),
+                input.to_vec(),
+            );
+        }
+    }
+
+    /// End the innermost call frame of the tracer, if any. It must be called
+    /// before the transaction layer of the frame is closed.
+    fn end_trace_frame(&mut self, result: &Result<CreateOutcome, EthereumError>) {
+        if self.tracer.is_none() {
+            return;
+        }
+        let gas_used = self.gas_used();
+        let (new_address, output, error) = match result {
+            Ok((reason @ ExitReason::Succeed(_), Some(address), _)) => (
+                Some(*address),
+                self.code(*address),
+                exit_reason_error(reason),
+            ),
+            Ok((reason, new_address, output)) => {
+                (*new_address, output.clone(), exit_reason_error(reason))
+            }
+            Err(err) => (None, vec![], Some(format!("{:?}", err))),
+        };
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.end_frame(new_address, gas_used, output, error);
+        }
+    }
+
     /// Execute a SputnikVM run with this handler
     ///
     /// Never inlined when the kernel is compiled for benchmarks, to ensure the
@@ -551,15 +738,17 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
                 benchmarks::start_opcode_section(self.host, &opcode);
             }
 
-            // For now, these variables capturing the gas one will be marked
-            // unused without benchmarking, but they will be used during the
-            // tick accounting.
-            #[cfg_attr(not(feature = "benchmark"), allow(unused_variables))]
+            let traced_step =
+                opcode.and_then(|opcode| self.start_traced_step(runtime, opcode));
+
             let gas_before = self.gas_used();
 
-            let step_result = runtime.step(self);
+            // Traps are never resumed, they are dropped to release the runtime.
+            let step_result = runtime.step(self).map_err(|capture| match capture {
+                Capture::Exit(reason) => Capture::Exit(reason),
+                Capture::Trap(_) => Capture::Trap(()),
+            });
 
-            #[cfg_attr(not(feature = "benchmark"), allow(unused_variables))]
             let gas_after = self.gas_used();
 
             if let Some(opcode) = opcode {
@@ -569,6 +758,18 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
                 benchmarks::end_opcode_section(self.host, gas, &step_result);
             };
 
+            if let (Some(step), Some(tracer)) = (traced_step, self.tracer.as_mut()) {
+                let gas_cost = gas_after - gas_before;
+                let result = runtime.machine().stack().peek(0).ok();
+                let error = match &step_result {
+                    Err(Capture::Exit(
+                        reason @ (ExitReason::Error(_) | ExitReason::Fatal(_)),
+                    )) => exit_reason_error(reason),
+                    _ => None,
+                };
+                tracer.end_step(step, gas_cost, result, error);
+            }
+
             match step_result {
                 Ok(()) => (),
                 Err(Capture::Exit(reason)) => return Ok(reason),
@@ -627,6 +828,7 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
             // Nothing to transfer so succeeds by default
             Ok(TransferExitReason::Returned)
         } else if let Some(mut from_account) = self.get_account(from) {
+            self.touch(from);
             let mut to_account = self.get_or_create_account(to)?;
 
             if from_account.balance_remove(self.host, value)? {
@@ -791,6 +993,8 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
             self.increment_nonce(caller)?
         };
 
+        self.mark_created(address)?;
+
         let mut runtime = evm::Runtime::new(
             Rc::new(initial_code),
             Rc::new(Vec::new()),
@@ -915,6 +1119,15 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
         is_static: bool,
     ) -> Result<ExecutionOutcome, EthereumError> {
         self.begin_initial_transaction(is_static, gas_limit)?;
+        self.begin_trace_frame(
+            false,
+            caller,
+            None,
+            Some(callee),
+            value,
+            gas_limit,
+            &input,
+        );
 
         if let Err(err) = self.record_base_gas_cost(false, &input) {
             return self.end_initial_transaction(Ok((
@@ -947,6 +1160,7 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
         gas_limit: Option<u64>,
     ) -> Result<ExecutionOutcome, EthereumError> {
         self.begin_initial_transaction(false, gas_limit)?;
+        self.begin_trace_frame(true, caller, None, None, value, gas_limit, &input);
 
         if let Err(err) = self.record_base_gas_cost(true, &input) {
             return self.end_initial_transaction(Ok((
@@ -970,9 +1184,10 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
     }
 
     fn get_or_create_account(
-        &self,
+        &mut self,
         address: H160,
     ) -> Result<EthereumAccount, EthereumError> {
+        self.touch(address);
         self.evm_account_storage
             .get_or_create(
                 self.host,
@@ -1021,6 +1236,7 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
     }
 
     pub fn increment_nonce(&mut self, address: H160) -> Result<(), EthereumError> {
+        self.touch(address);
         match account_path(&address) {
             Ok(path) => {
                 let mut account =
@@ -1057,11 +1273,63 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
             .unwrap_or_default()
     }
 
+    /// Record that the contract at `address` is created by the current
+    /// transaction. See EIP-6780.
+    fn mark_created(&mut self, address: H160) -> Result<(), EthereumError> {
+        match self.transaction_data.last_mut() {
+            Some(layer) => {
+                layer.created_contracts.insert(address);
+                Ok(())
+            }
+            None => Err(EthereumError::InconsistentTransactionStack(0, false, false)),
+        }
+    }
+
+    /// Check if the contract at `address` was created by the current
+    /// transaction.
+    fn created(&self, address: H160) -> bool {
+        self.transaction_data
+            .iter()
+            .any(|layer| layer.created_contracts.contains(&address))
+    }
+
+    /// Read transient storage (EIP-1153). The most recent write in any
+    /// transaction layer in progress takes precedence. Unset values are zero.
+    fn transient_storage(&self, address: H160, index: H256) -> H256 {
+        self.transaction_data
+            .iter()
+            .rev()
+            .find_map(|layer| layer.transient_storage.get(&(address, index)))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Write transient storage (EIP-1153) in the current transaction layer.
+    /// The write is discarded if the layer is rolled back, and in any case
+    /// at the end of the initial transaction.
+    fn set_transient_storage(
+        &mut self,
+        address: H160,
+        index: H256,
+        value: H256,
+    ) -> Result<(), ExitError> {
+        match self.transaction_data.last_mut() {
+            Some(layer) => {
+                layer.transient_storage.insert((address, index), value);
+                Ok(())
+            }
+            None => Err(ExitError::Other(Cow::from(
+                "No transaction data for transient storage",
+            ))),
+        }
+    }
+
     /// Completely delete an account including nonce, code, and data. This is for
     /// contract selfdestruct completion, ie, when contract selfdestructs takes final
     /// effect.
     fn delete_contract(&mut self, address: H160) -> Result<(), EthereumError> {
         log!(self.host, Debug, "Deleting contract at {:?}", address);
+        self.touch(address);
 
         self.evm_account_storage
             .delete(
@@ -1262,6 +1530,8 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
         &mut self,
         execution_result: Result<CreateOutcome, EthereumError>,
     ) -> Result<ExecutionOutcome, EthereumError> {
+        self.end_trace_frame(&execution_result);
+
         match execution_result {
             Ok((ExitReason::Succeed(r), new_address, result)) => {
                 log!(
@@ -1399,6 +1669,14 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
                     .deleted_contracts
                     .extend(committed_data.deleted_contracts);
 
+                top_layer
+                    .created_contracts
+                    .extend(committed_data.created_contracts);
+
+                top_layer
+                    .transient_storage
+                    .extend(committed_data.transient_storage);
+
                 self.record_stipend(gas_remaining)?;
 
                 Ok(())
@@ -1459,6 +1737,8 @@ impl<'a, Host: Runtime> EvmHandler<'a, Host> {
         &mut self,
         execution_result: Result<CreateOutcome, EthereumError>,
     ) -> Capture<CreateOutcome, T> {
+        self.end_trace_frame(&execution_result);
+
         if let Ok((ref _r @ ExitReason::Succeed(_), _, _)) = execution_result {
             log!(
                 self.host,
@@ -1691,6 +1971,7 @@ impl<'a, Host: Runtime> Handler for EvmHandler<'a, Host> {
         index: H256,
         value: H256,
     ) -> Result<(), ExitError> {
+        self.touch_storage(address, index);
         let mut account = self.get_or_create_account(address).map_err(|_| {
             ExitError::Other(Cow::from("Could not get account for set_storage"))
         })?;
@@ -1727,6 +2008,12 @@ impl<'a, Host: Runtime> Handler for EvmHandler<'a, Host> {
                 ))
             })?;
 
+        // EIP-6780: unless the contract was created in the same transaction,
+        // SELFDESTRUCT only transfers the balance.
+        if self.fork.is_cancun() && !self.created(address) {
+            return Ok(());
+        }
+
         if let Some(top_data) = self.transaction_data.last_mut() {
             top_data.deleted_contracts.insert(address);
 
@@ -1764,6 +2051,15 @@ impl<'a, Host: Runtime> Handler for EvmHandler<'a, Host> {
                 vec![],
             ))
         } else {
+            self.begin_trace_frame(
+                true,
+                caller,
+                None,
+                None,
+                Some(value),
+                gas_limit,
+                &init_code,
+            );
             let result = self.execute_create(caller, scheme, value, init_code, true);
 
             self.end_inter_transaction(result)
@@ -1814,6 +2110,15 @@ impl<'a, Host: Runtime> Handler for EvmHandler<'a, Host> {
         if let Err(err) = self.begin_inter_transaction(is_static, gas_limit) {
             return Capture::Exit((ethereum_error_to_exit_reason(&err), vec![]));
         }
+        self.begin_trace_frame(
+            false,
+            context.caller,
+            Some(context.address),
+            Some(code_address),
+            transfer.as_ref().map(|transfer| transfer.value),
+            gas_limit,
+            &input,
+        );
 
         let result = self.execute_call(
             code_address,
@@ -1837,7 +2142,26 @@ impl<'a, Host: Runtime> Handler for EvmHandler<'a, Host> {
         opcode: Opcode,
         stack: &Stack,
     ) -> Result<(), ExitError> {
-        if let Some(cost) = evm::gasometer::static_opcode_cost(opcode) {
+        let is_cancun = self.fork.is_cancun();
+        if is_cancun && (opcode == TLOAD || opcode == TSTORE) {
+            // EIP-1153: TSTORE is a state modification, so it is forbidden
+            // in static calls like SSTORE
+            if opcode == TSTORE && self.is_static() {
+                return Err(ExitError::InvalidCode(opcode));
+            }
+            self.transient_storage_context = Some(context.address);
+            self.record_cost(self.config.gas_storage_read_warm)
+        } else if is_cancun && opcode == MCOPY {
+            // EIP-5656: same cost as the other copy opcodes, with memory
+            // expansion covering both the source and the destination
+            let dst = U256::from_big_endian(&stack.peek(0)?[..]);
+            let src = U256::from_big_endian(&stack.peek(1)?[..]);
+            let len = U256::from_big_endian(&stack.peek(2)?[..]);
+            let memory_cost =
+                MemoryCost { offset: dst, len }.join(MemoryCost { offset: src, len });
+
+            self.record_dynamic_cost(GasCost::VeryLowCopy { len }, Some(memory_cost))
+        } else if let Some(cost) = evm::gasometer::static_opcode_cost(opcode) {
             self.record_cost(cost)
         } else {
             let (cost, _target, memory_cost) = evm::gasometer::dynamic_opcode_cost(
@@ -1852,6 +2176,47 @@ impl<'a, Host: Runtime> Handler for EvmHandler<'a, Host> {
             self.record_dynamic_cost(cost, memory_cost)
         }
     }
+
+    fn other(&mut self, opcode: Opcode, machine: &mut Machine) -> Result<(), ExitError> {
+        if opcode == TLOAD {
+            let address = self.transient_storage_context.ok_or_else(|| {
+                ExitError::Other(Cow::from("No context for transient storage"))
+            })?;
+            let index = machine.stack_mut().pop()?;
+            let value = self.transient_storage(address, index);
+            machine.stack_mut().push(value)
+        } else if opcode == TSTORE {
+            let address = self.transient_storage_context.ok_or_else(|| {
+                ExitError::Other(Cow::from("No context for transient storage"))
+            })?;
+            let index = machine.stack_mut().pop()?;
+            let value = machine.stack_mut().pop()?;
+            self.set_transient_storage(address, index, value)
+        } else if opcode == MCOPY {
+            let dst = U256::from_big_endian(&machine.stack_mut().pop()?[..]);
+            let src = U256::from_big_endian(&machine.stack_mut().pop()?[..]);
+            let len = U256::from_big_endian(&machine.stack_mut().pop()?[..]);
+
+            if len.is_zero() {
+                return Ok(());
+            }
+
+            let max_end = dst.max(src).checked_add(len);
+            if max_end.map_or(true, |end| end > U256::from(usize::MAX)) {
+                return Err(ExitError::InvalidRange);
+            }
+
+            let memory = machine.memory_mut();
+            memory.resize_offset(dst, len)?;
+            memory.resize_offset(src, len)?;
+            let data = memory.get(src.as_usize(), len.as_usize());
+            memory
+                .set(dst.as_usize(), &data, None)
+                .map_err(|_| ExitError::InvalidRange)
+        } else {
+            Err(ExitError::InvalidCode(opcode))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -1859,14 +2224,18 @@ mod test {
     use super::*;
     use crate::account_storage::init_account_storage;
     use crate::precompiles;
-    use evm::Config;
+    use crate::trace::{StructLoggerConfig, Trace, TracerConfig};
     use primitive_types::{H160, H256};
     use std::cmp::Ordering;
     use std::str::FromStr;
     use tezos_ethereum::block::BlockFees;
     use tezos_smart_rollup_mock::MockHost;
 
-    const DUMMY_ALLOCATED_TICKS: u64 = 1_000_000;
+    // The ticks reserved for updating the state trie after the transaction
+    // come on top of the ticks of the opcodes.
+    const DUMMY_ALLOCATED_TICKS: u64 = 1_000_000
+        + 4 * tick_model_opcodes::TICKS_PER_TOUCHED_ACCOUNT
+        + 4 * tick_model_opcodes::TICKS_PER_TOUCHED_SLOT;
 
     fn set_code<'a>(
         handler: &mut EvmHandler<'a, MockHost>,
@@ -1916,7 +2285,7 @@ mod test {
     }
 
     fn dummy_first_block() -> BlockConstants {
-        let block_fees = BlockFees::new(U256::from(12345));
+        let block_fees = BlockFees::new(U256::from(12345), U256::zero());
         BlockConstants::first_block(U256::zero(), U256::one(), block_fees)
     }
 
@@ -1926,7 +2295,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
 
         let gas_price = U256::from(21000);
 
@@ -1945,7 +2313,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -1965,7 +2333,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller: H160 =
             H160::from_str("9bbfed6889322e016e0a02ee459d306fc19545d8").unwrap();
 
@@ -1976,7 +2343,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -2003,7 +2370,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
 
         let gas_price = U256::from(21000);
 
@@ -2015,7 +2381,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -2045,7 +2411,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller = H160::from_low_u64_be(28349_u64);
 
         // We use an origin distinct from caller for testing purposes
@@ -2058,7 +2423,7 @@ mod test {
             &mut evm_account_storage,
             origin,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -2108,7 +2473,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller = H160::from_low_u64_be(28349_u64);
 
         let gas_price = U256::from(21000);
@@ -2118,7 +2482,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -2206,7 +2570,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller = H160::from_low_u64_be(2340);
 
         let gas_price = U256::from(21000);
@@ -2216,7 +2579,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -2310,7 +2673,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller = H160::from_low_u64_be(8213);
 
         let gas_price = U256::from(21000);
@@ -2320,7 +2682,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -2401,7 +2763,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller = H160::from_low_u64_be(444);
 
         let gas_price = U256::from(21000);
@@ -2411,7 +2772,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -2471,7 +2832,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller = H160::from_low_u64_be(117);
 
         let gas_price = U256::from(21000);
@@ -2481,7 +2841,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -2527,7 +2887,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller = H160::from_low_u64_be(117);
 
         let gas_price = U256::from(21000);
@@ -2537,7 +2896,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -2590,7 +2949,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller = H160::from_low_u64_be(118);
 
         let gas_price = U256::from(21000);
@@ -2600,7 +2958,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -2651,7 +3009,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller = H160::from_low_u64_be(523_u64);
 
         let gas_price = U256::from(21000);
@@ -2661,7 +3018,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -2711,7 +3068,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller = H160::from_low_u64_be(523_u64);
 
         let gas_price = U256::from(21000);
@@ -2721,7 +3077,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -2780,7 +3136,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller = H160::from_low_u64_be(523_u64);
 
         let gas_price = U256::from(21000);
@@ -2790,7 +3145,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -2806,7 +3161,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::london();
         let caller = H160::from_low_u64_be(523_u64);
 
         let gas_price = U256::from(21000);
@@ -2816,7 +3170,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             10_000,
             gas_price,
@@ -2865,7 +3219,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller = H160::from_low_u64_be(523_u64);
 
         let gas_price = U256::from(21000);
@@ -2875,7 +3228,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -2915,7 +3268,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller = H160::from_low_u64_be(523_u64);
 
         let gas_price = U256::from(21000);
@@ -2925,7 +3277,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -2982,8 +3334,6 @@ mod test {
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
 
-        let config = Config::shanghai();
-
         let caller_address: [u8; 20] =
             hex::decode("a94f5374fce5edbc8e2a8697c15331677e6ebf0b")
                 .unwrap()
@@ -3010,7 +3360,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -3027,7 +3377,7 @@ mod test {
         let result =
             handler.execute_call(target_address, None, input, transaction_context);
 
-        // This assertion will change with the upcoming Dencun config/fork
+        // The contract existed before the transaction, so it isn't deleted.
         // See: https://eips.ethereum.org/EIPS/eip-6780
         assert_eq!(
             Ok((ExitReason::Succeed(ExitSucceed::Suicided), None, vec![],)),
@@ -3048,7 +3398,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
 
         let caller = H160::from_str("a94f5374fce5edbc8e2a8697c15331677e6ebf0b").unwrap();
 
@@ -3057,7 +3406,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             U256::one(),
@@ -3083,7 +3432,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller = H160::from_low_u64_be(523_u64);
 
         let gas_price = U256::from(21000);
@@ -3093,7 +3441,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -3157,7 +3505,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller = H160::from_low_u64_be(523_u64);
 
         let gas_price = U256::from(21000);
@@ -3167,7 +3514,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             gas_price,
@@ -3184,7 +3531,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
 
         let caller = H160::from_str("a94f5374fce5edbc8e2a8697c15331677e6ebf0b").unwrap();
         let withdrawal_contract =
@@ -3195,7 +3541,45 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
+            &precompiles,
+            DUMMY_ALLOCATED_TICKS,
+            U256::one(),
+        );
+
+        set_balance(&mut handler, &caller, U256::from(1000000000));
+
+        let code = hex::decode("732adc25665018aa1fe0e6bc666dac8fc2697ff9baff00").unwrap(); // transfer balance to 0x2adc25665018aa1fe0e6bc666dac8fc2697ff9ba and selfdestruct
+
+        let result = handler
+            .create_contract(caller, Some(U256::one()), code, None)
+            .unwrap();
+
+        let suicided_contract = result.new_address.unwrap();
+
+        assert_eq!(result.reason, ExitReason::Succeed(ExitSucceed::Suicided));
+        assert_eq!(get_balance(&mut handler, &withdrawal_contract), U256::one());
+        assert_eq!(get_balance(&mut handler, &caller), U256::from(999999999));
+        assert!(!handler.exists(suicided_contract));
+    }
+
+    #[test]
+    fn create_contract_with_selfdestruct_init_code_in_cancun() {
+        let mut mock_runtime = MockHost::default();
+        let block = dummy_first_block();
+        let precompiles = precompiles::precompile_set::<MockHost>();
+        let mut evm_account_storage = init_account_storage().unwrap();
+
+        let caller = H160::from_str("a94f5374fce5edbc8e2a8697c15331677e6ebf0b").unwrap();
+        let withdrawal_contract =
+            H160::from_str("2adc25665018aa1fe0e6bc666dac8fc2697ff9ba").unwrap();
+
+        let mut handler = EvmHandler::new(
+            &mut mock_runtime,
+            &mut evm_account_storage,
+            caller,
+            &block,
+            EvmFork::Cancun,
             &precompiles,
             DUMMY_ALLOCATED_TICKS,
             U256::one(),
@@ -3214,6 +3598,8 @@ mod test {
         assert_eq!(result.reason, ExitReason::Succeed(ExitSucceed::Suicided));
         assert_eq!(get_balance(&mut handler, &withdrawal_contract), U256::one());
         assert_eq!(get_balance(&mut handler, &caller), U256::from(999999999));
+        // EIP-6780: the contract is created in the same transaction, so it
+        // is deleted
         assert!(!handler.exists(suicided_contract));
     }
 
@@ -3223,7 +3609,6 @@ mod test {
         let block = dummy_first_block();
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_account_storage().unwrap();
-        let config = Config::shanghai();
         let caller = H160::from_low_u64_be(523_u64);
 
         let gas_price = U256::from(21000);
@@ -3233,7 +3618,7 @@ mod test {
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
             DUMMY_ALLOCATED_TICKS * 10000,
             gas_price,
@@ -3297,4 +3682,415 @@ mod test {
             result,
         )
     }
+
+    fn call_code(address: u8, delegate: bool) -> Vec<u8> {
+        let mut code = vec![
+            Opcode::PUSH1.as_u8(), // return size
+            32,
+            Opcode::PUSH1.as_u8(), // return offset
+            0,
+            Opcode::PUSH1.as_u8(), // arg size
+            0,
+            Opcode::PUSH1.as_u8(), // arg offset
+            0,
+        ];
+        if !delegate {
+            code.extend([Opcode::PUSH1.as_u8(), 0]); // value
+        }
+        code.extend([
+            Opcode::PUSH1.as_u8(), // address
+            address,
+            Opcode::PUSH2.as_u8(), // gas
+            0xFF,
+            0xFF,
+            if delegate {
+                Opcode::DELEGATECALL.as_u8()
+            } else {
+                Opcode::CALL.as_u8()
+            },
+            Opcode::POP.as_u8(),
+        ]);
+        code
+    }
+
+    const RETURN_WORD_0: [u8; 5] = [
+        Opcode::PUSH1.as_u8(), // return size
+        32,
+        Opcode::PUSH1.as_u8(), // return offset
+        0,
+        Opcode::RETURN.as_u8(),
+    ];
+
+    #[test]
+    fn transient_storage_is_transaction_scoped() {
+        let mut mock_runtime = MockHost::default();
+        let block = dummy_first_block();
+        let precompiles = precompiles::precompile_set::<MockHost>();
+        let mut evm_account_storage = init_account_storage().unwrap();
+        let caller = H160::from_low_u64_be(523_u64);
+
+        let mut handler = EvmHandler::new(
+            &mut mock_runtime,
+            &mut evm_account_storage,
+            caller,
+            &block,
+            EvmFork::Cancun,
+            &precompiles,
+            DUMMY_ALLOCATED_TICKS,
+            U256::from(21000),
+        );
+
+        // Returns the value at transient index 1, then sets it to 42
+        let counter = H160::from_low_u64_be(210_u64);
+        let mut code = vec![
+            Opcode::PUSH1.as_u8(),
+            1,
+            TLOAD.as_u8(),
+            Opcode::PUSH1.as_u8(),
+            0,
+            Opcode::MSTORE.as_u8(),
+            Opcode::PUSH1.as_u8(),
+            42,
+            Opcode::PUSH1.as_u8(),
+            1,
+            TSTORE.as_u8(),
+        ];
+        code.extend(RETURN_WORD_0);
+        set_code(&mut handler, &counter, code);
+
+        // Calls the contract above twice, returning the result of the
+        // second call
+        let caller_contract = H160::from_low_u64_be(211_u64);
+        let mut code = call_code(210, false);
+        code.extend(call_code(210, false));
+        code.extend(RETURN_WORD_0);
+        set_code(&mut handler, &caller_contract, code);
+
+        let result = handler
+            .call_contract(
+                caller,
+                caller_contract,
+                None,
+                vec![],
+                Some(1_000_000),
+                false,
+            )
+            .unwrap();
+        assert!(result.is_success);
+        assert_eq!(result.result, Some(H256::from_low_u64_be(42).0.to_vec()));
+
+        // The value is discarded at the end of the transaction
+        let result = handler
+            .call_contract(caller, counter, None, vec![], Some(1_000_000), false)
+            .unwrap();
+        assert!(result.is_success);
+        assert_eq!(result.result, Some(H256::zero().0.to_vec()));
+        assert!(handler.transaction_data.is_empty());
+
+        // TSTORE is forbidden in static calls
+        let result = handler
+            .call_contract(caller, counter, None, vec![], Some(1_000_000), true)
+            .unwrap();
+        assert_eq!(
+            result.reason,
+            ExitReason::Error(ExitError::InvalidCode(TSTORE))
+        );
+    }
+
+    #[test]
+    fn transient_storage_is_reverted_with_call() {
+        let mut mock_runtime = MockHost::default();
+        let block = dummy_first_block();
+        let precompiles = precompiles::precompile_set::<MockHost>();
+        let mut evm_account_storage = init_account_storage().unwrap();
+        let caller = H160::from_low_u64_be(523_u64);
+
+        let mut handler = EvmHandler::new(
+            &mut mock_runtime,
+            &mut evm_account_storage,
+            caller,
+            &block,
+            EvmFork::Cancun,
+            &precompiles,
+            DUMMY_ALLOCATED_TICKS,
+            U256::from(21000),
+        );
+
+        // Sets transient index 1 to 42
+        let writer = H160::from_low_u64_be(210_u64);
+        let store = [
+            Opcode::PUSH1.as_u8(),
+            42,
+            Opcode::PUSH1.as_u8(),
+            1,
+            TSTORE.as_u8(),
+        ];
+        set_code(&mut handler, &writer, store.to_vec());
+
+        // Same, but reverts
+        let reverting_writer = H160::from_low_u64_be(211_u64);
+        let mut code = store.to_vec();
+        code.extend([
+            Opcode::PUSH1.as_u8(),
+            0,
+            Opcode::PUSH1.as_u8(),
+            0,
+            Opcode::REVERT.as_u8(),
+        ]);
+        set_code(&mut handler, &reverting_writer, code);
+
+        let read = [
+            Opcode::PUSH1.as_u8(),
+            1,
+            TLOAD.as_u8(),
+            Opcode::PUSH1.as_u8(),
+            0,
+            Opcode::MSTORE.as_u8(),
+        ];
+
+        // The writers are executed in the context of the caller via
+        // DELEGATECALL, so they write to the caller's transient storage
+        let reverting_caller = H160::from_low_u64_be(212_u64);
+        let mut code = call_code(211, true);
+        code.extend(read);
+        code.extend(RETURN_WORD_0);
+        set_code(&mut handler, &reverting_caller, code);
+
+        let committing_caller = H160::from_low_u64_be(213_u64);
+        let mut code = call_code(210, true);
+        code.extend(read);
+        code.extend(RETURN_WORD_0);
+        set_code(&mut handler, &committing_caller, code);
+
+        let result = handler
+            .call_contract(
+                caller,
+                reverting_caller,
+                None,
+                vec![],
+                Some(1_000_000),
+                false,
+            )
+            .unwrap();
+        assert!(result.is_success);
+        assert_eq!(result.result, Some(H256::zero().0.to_vec()));
+
+        let result = handler
+            .call_contract(
+                caller,
+                committing_caller,
+                None,
+                vec![],
+                Some(1_000_000),
+                false,
+            )
+            .unwrap();
+        assert!(result.is_success);
+        assert_eq!(result.result, Some(H256::from_low_u64_be(42).0.to_vec()));
+    }
+
+    #[test]
+    fn mcopy_copies_memory() {
+        let mut mock_runtime = MockHost::default();
+        let block = dummy_first_block();
+        let precompiles = precompiles::precompile_set::<MockHost>();
+        let mut evm_account_storage = init_account_storage().unwrap();
+        let caller = H160::from_low_u64_be(523_u64);
+
+        let mut handler = EvmHandler::new(
+            &mut mock_runtime,
+            &mut evm_account_storage,
+            caller,
+            &block,
+            EvmFork::Cancun,
+            &precompiles,
+            DUMMY_ALLOCATED_TICKS,
+            U256::from(21000),
+        );
+
+        let address = H160::from_low_u64_be(210_u64);
+        let code = vec![
+            Opcode::PUSH2.as_u8(), // Memory[30..32] = 0xabcd
+            0xab,
+            0xcd,
+            Opcode::PUSH1.as_u8(),
+            0,
+            Opcode::MSTORE.as_u8(),
+            Opcode::PUSH1.as_u8(), // length
+            2,
+            Opcode::PUSH1.as_u8(), // source offset
+            30,
+            Opcode::PUSH1.as_u8(), // destination offset, expands memory
+            63,
+            MCOPY.as_u8(),
+            Opcode::PUSH1.as_u8(), // return Memory[32..96]
+            64,
+            Opcode::PUSH1.as_u8(),
+            32,
+            Opcode::RETURN.as_u8(),
+        ];
+        set_code(&mut handler, &address, code);
+
+        let result = handler
+            .call_contract(caller, address, None, vec![], Some(1_000_000), false)
+            .unwrap();
+
+        let mut expected = vec![0; 64];
+        expected[31] = 0xab;
+        expected[32] = 0xcd;
+        assert!(result.is_success);
+        assert_eq!(result.result, Some(expected));
+        // Memory of `words` words costs 3 per word plus words^2 / 512.
+        let memory_cost = |words: u64| 3 * words + words * words / 512;
+        // RETURN reads Memory[32..96], which doesn't expand the memory.
+        let expected_gas = 21000 // base cost
+            + 3 * 7 // PUSH2 and PUSH1 * 6
+            + 3 + memory_cost(1) // MSTORE, expands to 1 word
+            + 3 + 3 // MCOPY and 1 word copied
+            + (memory_cost(3) - memory_cost(1)); // MCOPY expands to 3 words
+        assert_eq!(result.gas_used, expected_gas);
+    }
+
+    #[test]
+    fn cancun_opcodes_are_invalid_before_cancun() {
+        let mut mock_runtime = MockHost::default();
+        let block = dummy_first_block();
+        let precompiles = precompiles::precompile_set::<MockHost>();
+        let mut evm_account_storage = init_account_storage().unwrap();
+        let caller = H160::from_low_u64_be(523_u64);
+
+        let mut handler = EvmHandler::new(
+            &mut mock_runtime,
+            &mut evm_account_storage,
+            caller,
+            &block,
+            EvmFork::Shanghai,
+            &precompiles,
+            // invalid opcodes consume all the gas
+            10_000_000_000,
+            U256::from(21000),
+        );
+
+        for (i, opcode) in [TLOAD, TSTORE, MCOPY].into_iter().enumerate() {
+            let address = H160::from_low_u64_be(210 + i as u64);
+            let code = vec![
+                Opcode::PUSH1.as_u8(),
+                0,
+                Opcode::PUSH1.as_u8(),
+                0,
+                Opcode::PUSH1.as_u8(),
+                0,
+                opcode.as_u8(),
+            ];
+            set_code(&mut handler, &address, code);
+
+            let result = handler
+                .call_contract(caller, address, None, vec![], Some(50_000), false)
+                .unwrap();
+            assert_eq!(
+                result.reason,
+                ExitReason::Error(ExitError::InvalidCode(opcode))
+            );
+        }
+    }
+
+    #[test]
+    fn tracer_records_nested_calls() {
+        let mut mock_runtime = MockHost::default();
+        let block = dummy_first_block();
+        let precompiles = precompiles::precompile_set::<MockHost>();
+        let mut evm_account_storage = init_account_storage().unwrap();
+        let caller = H160::from_low_u64_be(523_u64);
+
+        let mut handler = EvmHandler::new(
+            &mut mock_runtime,
+            &mut evm_account_storage,
+            caller,
+            &block,
+            EvmFork::Shanghai,
+            &precompiles,
+            1_000_000_000,
+            U256::from(21000),
+        );
+
+        // Sets storage index 1 to 42
+        let callee = H160::from_low_u64_be(210_u64);
+        let store = vec![
+            Opcode::PUSH1.as_u8(),
+            42,
+            Opcode::PUSH1.as_u8(),
+            1,
+            Opcode::SSTORE.as_u8(),
+        ];
+        set_code(&mut handler, &callee, store);
+
+        let contract = H160::from_low_u64_be(211_u64);
+        let mut code = call_code(210, false);
+        code.extend(RETURN_WORD_0);
+        set_code(&mut handler, &contract, code);
+
+        handler.set_tracer(Tracer::new(TracerConfig::CallTracer));
+        let result = handler
+            .call_contract(caller, contract, None, vec![], Some(1_000_000), false)
+            .unwrap();
+        assert!(result.is_success);
+        let Some(Trace::Call { root: frame, .. }) = handler.take_tracer().map(|t| t.into_trace(Some(&result)))
+            else { panic!("Expected a call frame") };
+        assert_eq!(frame.from, caller);
+        assert_eq!(frame.to, Some(contract));
+        assert_eq!(frame.gas_used, result.gas_used);
+        assert_eq!(frame.calls.len(), 1);
+        let call = &frame.calls[0];
+        assert_eq!(call.call_type, CallType::Call);
+        assert_eq!(call.from, contract);
+        assert_eq!(call.to, Some(callee));
+        assert_eq!(call.error, None);
+
+        handler.set_tracer(Tracer::new(TracerConfig::StructLogger(
+            StructLoggerConfig::default(),
+        )));
+        let result = handler
+            .call_contract(caller, contract, None, vec![], Some(1_000_000), false)
+            .unwrap();
+        let Some(Trace::StructLogs { struct_logs, .. }) = handler.take_tracer().map(|t| t.into_trace(Some(&result)))
+            else { panic!("Expected struct logs") };
+        let sstore = struct_logs
+            .iter()
+            .find(|log| log.op == Opcode::SSTORE.as_u8())
+            .unwrap();
+        assert_eq!(sstore.depth, 2);
+        assert_eq!(
+            sstore.storage,
+            Some(vec![(H256::from_low_u64_be(1), H256::from_low_u64_be(42))])
+        );
+        assert_eq!(
+            sstore.stack,
+            Some(vec![H256::from_low_u64_be(42), H256::from_low_u64_be(1)])
+        );
+        assert!(struct_logs.iter().all(|log| log.storage.is_none()
+            || log.op == Opcode::SSTORE.as_u8()
+            || log.op == Opcode::SLOAD.as_u8()));
+
+        // Without the stack, the storage accesses are still recorded.
+        handler.set_tracer(Tracer::new(TracerConfig::StructLogger(
+            StructLoggerConfig {
+                disable_stack: true,
+                ..StructLoggerConfig::default()
+            },
+        )));
+        let result = handler
+            .call_contract(caller, contract, None, vec![], Some(1_000_000), false)
+            .unwrap();
+        let Some(Trace::StructLogs { struct_logs, .. }) = handler.take_tracer().map(|t| t.into_trace(Some(&result)))
+            else { panic!("Expected struct logs") };
+        assert!(struct_logs.iter().all(|log| log.stack.is_none()));
+        let sstore = struct_logs
+            .iter()
+            .find(|log| log.op == Opcode::SSTORE.as_u8())
+            .unwrap();
+        assert_eq!(
+            sstore.storage,
+            Some(vec![(H256::from_low_u64_be(1), H256::from_low_u64_be(42))])
+        );
+    }
 }
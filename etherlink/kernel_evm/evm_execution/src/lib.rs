@@ -21,23 +21,31 @@ use thiserror::Error;
 
 pub mod abi;
 pub mod account_storage;
+pub mod blake2f;
+pub mod bls12_381_precompiled;
+pub mod fa_bridge;
+pub mod fork;
 pub mod handler;
 pub mod modexp;
 pub mod precompiles;
 pub mod storage;
 pub mod tick_model_opcodes;
+pub mod trace;
 pub mod transaction;
 pub mod utilities;
 pub mod zk_precompiled;
 
 pub use evm::Config;
+pub use fork::EvmFork;
 
 extern crate alloc;
 extern crate tezos_crypto_rs as crypto;
 extern crate tezos_smart_rollup_debug as debug;
 extern crate tezos_smart_rollup_host as host;
 
+use handler::TouchedAccounts;
 use precompiles::PrecompileSet;
+use trace::{Trace, Tracer, TracerConfig};
 
 #[derive(Error, Clone, Copy, Debug, Eq, PartialEq)]
 pub enum DurableStorageError {
@@ -128,18 +136,22 @@ pub enum EthereumError {
 ///
 /// If the gas limit is given as `None` (there is no gas limit), then there will be no
 /// accounting for gas usage at all. So the gas usage in the return value will be zero.
+///
+/// If `pay_for_gas` is set, the caller pre-pays the data availability fee
+/// `da_fee` along with the gas limit. It is not refunded.
 #[allow(clippy::too_many_arguments)]
 pub fn run_transaction<'a, Host>(
     host: &'a mut Host,
     block: &'a BlockConstants,
     evm_account_storage: &'a mut EthereumAccountStorage,
     precompiles: &'a precompiles::PrecompileBTreeMap<Host>,
-    config: Config,
+    fork: EvmFork,
     address: Option<H160>,
     caller: H160,
     call_data: Vec<u8>,
     gas_limit: Option<u64>,
     effective_gas_price: U256,
+    da_fee: U256,
     value: Option<U256>,
     pay_for_gas: bool,
     allocated_ticks: u64,
@@ -147,13 +159,47 @@ pub fn run_transaction<'a, Host>(
 where
     Host: Runtime,
 {
-    fn do_refund(outcome: &handler::ExecutionOutcome, pay_for_gas: bool) -> bool {
-        match outcome.reason {
-            ExitReason::Revert(_) => pay_for_gas,
-            _ => pay_for_gas && outcome.is_success,
-        }
-    }
+    run_transaction_and_track_accounts(
+        host,
+        block,
+        evm_account_storage,
+        precompiles,
+        fork,
+        address,
+        caller,
+        call_data,
+        gas_limit,
+        effective_gas_price,
+        da_fee,
+        value,
+        pay_for_gas,
+        allocated_ticks,
+    )
+    .map(|(outcome, _)| outcome)
+}
 
+/// Execute an Ethereum Transaction as [run_transaction], and return the
+/// accounts it modified along with the outcome.
+#[allow(clippy::too_many_arguments)]
+pub fn run_transaction_and_track_accounts<'a, Host>(
+    host: &'a mut Host,
+    block: &'a BlockConstants,
+    evm_account_storage: &'a mut EthereumAccountStorage,
+    precompiles: &'a precompiles::PrecompileBTreeMap<Host>,
+    fork: EvmFork,
+    address: Option<H160>,
+    caller: H160,
+    call_data: Vec<u8>,
+    gas_limit: Option<u64>,
+    effective_gas_price: U256,
+    da_fee: U256,
+    value: Option<U256>,
+    pay_for_gas: bool,
+    allocated_ticks: u64,
+) -> Result<(Option<handler::ExecutionOutcome>, TouchedAccounts), EthereumError>
+where
+    Host: Runtime,
+{
     log!(host, Info, "Going to run an Ethereum transaction\n  - from address: {}\n  - to address: {:?}", caller, address);
 
     let mut handler = handler::EvmHandler::<'_, Host>::new(
@@ -161,14 +207,147 @@ where
         evm_account_storage,
         caller,
         block,
-        &config,
+        fork,
         precompiles,
         allocated_ticks,
         effective_gas_price,
     );
 
+    let outcome = execute_transaction(
+        &mut handler,
+        address,
+        caller,
+        call_data,
+        gas_limit,
+        effective_gas_price,
+        da_fee,
+        value,
+        pay_for_gas,
+    )?;
+    Ok((outcome, handler.take_touched_accounts()))
+}
+
+/// Execute an Ethereum Transaction as [run_transaction], and record its
+/// trace with the given tracer.
+#[allow(clippy::too_many_arguments)]
+pub fn trace_transaction<'a, Host>(
+    host: &'a mut Host,
+    block: &'a BlockConstants,
+    evm_account_storage: &'a mut EthereumAccountStorage,
+    precompiles: &'a precompiles::PrecompileBTreeMap<Host>,
+    fork: EvmFork,
+    address: Option<H160>,
+    caller: H160,
+    call_data: Vec<u8>,
+    gas_limit: Option<u64>,
+    effective_gas_price: U256,
+    da_fee: U256,
+    value: Option<U256>,
+    pay_for_gas: bool,
+    allocated_ticks: u64,
+    tracer: TracerConfig,
+) -> Result<(Option<handler::ExecutionOutcome>, Trace), EthereumError>
+where
+    Host: Runtime,
+{
+    trace_transaction_and_track_accounts(
+        host,
+        block,
+        evm_account_storage,
+        precompiles,
+        fork,
+        address,
+        caller,
+        call_data,
+        gas_limit,
+        effective_gas_price,
+        da_fee,
+        value,
+        pay_for_gas,
+        allocated_ticks,
+        tracer,
+    )
+    .map(|(outcome, trace, _)| (outcome, trace))
+}
+
+/// Execute an Ethereum Transaction as [trace_transaction], and return the
+/// accounts it modified along with the outcome and the trace.
+#[allow(clippy::too_many_arguments)]
+pub fn trace_transaction_and_track_accounts<'a, Host>(
+    host: &'a mut Host,
+    block: &'a BlockConstants,
+    evm_account_storage: &'a mut EthereumAccountStorage,
+    precompiles: &'a precompiles::PrecompileBTreeMap<Host>,
+    fork: EvmFork,
+    address: Option<H160>,
+    caller: H160,
+    call_data: Vec<u8>,
+    gas_limit: Option<u64>,
+    effective_gas_price: U256,
+    da_fee: U256,
+    value: Option<U256>,
+    pay_for_gas: bool,
+    allocated_ticks: u64,
+    tracer: TracerConfig,
+) -> Result<(Option<handler::ExecutionOutcome>, Trace, TouchedAccounts), EthereumError>
+where
+    Host: Runtime,
+{
+    log!(host, Info, "Going to trace an Ethereum transaction\n  - from address: {}\n  - to address: {:?}", caller, address);
+
+    let mut handler = handler::EvmHandler::<'_, Host>::new(
+        host,
+        evm_account_storage,
+        caller,
+        block,
+        fork,
+        precompiles,
+        allocated_ticks,
+        effective_gas_price,
+    );
+    handler.set_tracer(Tracer::new(tracer));
+
+    let outcome = execute_transaction(
+        &mut handler,
+        address,
+        caller,
+        call_data,
+        gas_limit,
+        effective_gas_price,
+        da_fee,
+        value,
+        pay_for_gas,
+    )?;
+    let trace = handler
+        .take_tracer()
+        .ok_or(EthereumError::InconsistentState(Cow::from(
+            "Missing tracer",
+        )))?
+        .into_trace(outcome.as_ref());
+    Ok((outcome, trace, handler.take_touched_accounts()))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn execute_transaction<Host: Runtime>(
+    handler: &mut handler::EvmHandler<'_, Host>,
+    address: Option<H160>,
+    caller: H160,
+    call_data: Vec<u8>,
+    gas_limit: Option<u64>,
+    effective_gas_price: U256,
+    da_fee: U256,
+    value: Option<U256>,
+    pay_for_gas: bool,
+) -> Result<Option<handler::ExecutionOutcome>, EthereumError> {
+    fn do_refund(outcome: &handler::ExecutionOutcome, pay_for_gas: bool) -> bool {
+        match outcome.reason {
+            ExitReason::Revert(_) => pay_for_gas,
+            _ => pay_for_gas && outcome.is_success,
+        }
+    }
+
     if (!pay_for_gas)
-        || handler.pre_pay_transactions(caller, gas_limit, effective_gas_price)?
+        || handler.pre_pay_transactions(caller, gas_limit, effective_gas_price, da_fee)?
     {
         let result = if let Some(address) = address {
             handler.call_contract(caller, address, value, call_data, gas_limit, false)
@@ -183,7 +362,12 @@ where
 
                 if do_refund(&result, pay_for_gas) {
                     let unused_gas = gas_limit.map(|gl| gl - result.gas_used);
-                    handler.repay_gas(caller, unused_gas, effective_gas_price)?
+                    handler.repay_gas(
+                        caller,
+                        unused_gas,
+                        effective_gas_price,
+                        U256::zero(),
+                    )?
                 }
 
                 Ok(Some(result))
@@ -192,7 +376,7 @@ where
             // be repaid in the next attempt
             Err(EthereumError::OutOfTicks) => {
                 if pay_for_gas {
-                    handler.repay_gas(caller, gas_limit, effective_gas_price)?;
+                    handler.repay_gas(caller, gas_limit, effective_gas_price, da_fee)?;
                 }
                 Err(EthereumError::OutOfTicks)
             }
@@ -201,7 +385,11 @@ where
     } else {
         // caller was unable to pay for the gas limit
         if pay_for_gas {
-            log!(host, Info, "Caller was unable to pre-pay the transaction")
+            log!(
+                handler.borrow_host(),
+                Info,
+                "Caller was unable to pre-pay the transaction"
+            )
         };
         Ok(None)
     }
@@ -299,7 +487,7 @@ mod test {
     }
 
     fn dummy_first_block() -> BlockConstants {
-        let block_fees = BlockFees::new(U256::from(12345));
+        let block_fees = BlockFees::new(U256::from(12345), U256::zero());
         BlockConstants::first_block(U256::zero(), U256::one(), block_fees)
     }
 
@@ -335,12 +523,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(callee),
             caller,
             call_data,
             Some(22000),
             gas_price,
+            U256::zero(),
             Some(transaction_value),
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -400,12 +589,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(callee),
             caller,
             call_data,
             Some(21000),
             gas_price,
+            U256::zero(),
             Some(transaction_value),
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -459,12 +649,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             callee,
             caller,
             call_data,
             None,
             gas_price,
+            U256::zero(),
             Some(transaction_value),
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -515,12 +706,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             callee,
             caller,
             call_data,
             Some(gas_limit),
             gas_price,
+            U256::zero(),
             Some(transaction_value),
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -549,12 +741,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             new_address,
             caller,
             call_data2,
             Some(31000),
             gas_price,
+            U256::zero(),
             Some(U256::zero()),
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -577,12 +770,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             new_address,
             caller,
             call_data_set,
             Some(100000),
             gas_price,
+            U256::zero(),
             Some(U256::zero()),
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -604,12 +798,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             new_address,
             caller,
             hex::decode(STORAGE_CONTRACT_CALL_NUM).unwrap(),
             Some(31000),
             gas_price,
+            U256::zero(),
             Some(U256::zero()),
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -627,6 +822,70 @@ mod test {
         assert_eq!(U256::from(42), value, "unexpected result value");
     }
 
+    #[test]
+    fn modified_accounts_and_slots_are_tracked() {
+        let mut mock_runtime = MockHost::default();
+        let block = dummy_first_block();
+        let precompiles = precompiles::precompile_set::<MockHost>();
+        let mut evm_account_storage = init_evm_account_storage().unwrap();
+
+        let caller = H160::from_low_u64_be(117);
+        let gas_price = U256::from(1);
+        set_balance(
+            &mut mock_runtime,
+            &mut evm_account_storage,
+            &caller,
+            U256::from(1_000_000),
+        );
+
+        let (result, touched) = run_transaction_and_track_accounts(
+            &mut mock_runtime,
+            &block,
+            &mut evm_account_storage,
+            &precompiles,
+            EvmFork::Shanghai,
+            None,
+            caller,
+            hex::decode(STORAGE_CONTRACT_INITIALIZATION).unwrap(),
+            Some(300_000),
+            gas_price,
+            U256::zero(),
+            Some(U256::zero()),
+            true,
+            DUMMY_ALLOCATED_TICKS,
+        )
+        .unwrap();
+        let new_address = result.unwrap().new_address.unwrap();
+        assert_eq!(
+            touched.keys().copied().collect::<Vec<H160>>(),
+            vec![caller, new_address]
+        );
+        assert!(touched.values().all(|slots| slots.is_empty()));
+
+        let (_, touched) = run_transaction_and_track_accounts(
+            &mut mock_runtime,
+            &block,
+            &mut evm_account_storage,
+            &precompiles,
+            EvmFork::Shanghai,
+            Some(new_address),
+            caller,
+            hex::decode(STORAGE_CONTRACT_CALL_SET42).unwrap(),
+            Some(100_000),
+            gas_price,
+            U256::zero(),
+            Some(U256::zero()),
+            true,
+            DUMMY_ALLOCATED_TICKS,
+        )
+        .unwrap();
+        assert!(touched[&caller].is_empty());
+        assert_eq!(
+            touched[&new_address].iter().copied().collect::<Vec<H256>>(),
+            vec![H256::zero()]
+        );
+    }
+
     #[test]
     fn create_contract_erc20_succeeds() {
         let mut mock_runtime = MockHost::default();
@@ -658,12 +917,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             callee,
             caller,
             call_data,
             Some(gas_limit),
             gas_price,
+            U256::zero(),
             Some(transaction_value),
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -708,12 +968,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             callee,
             caller,
             call_data,
             None,
             gas_price,
+            U256::zero(),
             Some(transaction_value),
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -761,12 +1022,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             vec![],
             Some(22000),
             gas_price,
+            U256::zero(),
             None,
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -907,12 +1169,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             vec![],
             Some(all_the_gas),
             gas_price,
+            U256::zero(),
             None,
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -970,12 +1233,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             vec![],
             Some(init_balance),
             gas_price,
+            U256::zero(),
             None,
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -1032,12 +1296,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             vec![],
             None,
             gas_price,
+            U256::zero(),
             None,
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -1089,12 +1354,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(address),
             caller,
             vec![],
             None,
             gas_price,
+            U256::zero(),
             Some(U256::from(100)),
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -1147,12 +1413,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             data.to_vec(),
             Some(22001),
             gas_price,
+            U256::zero(),
             None,
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -1207,12 +1474,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             data.to_vec(),
             Some(gas_limit),
             gas_price,
+            U256::zero(),
             None,
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -1319,12 +1587,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             vec![],
             None,
             gas_price,
+            U256::zero(),
             None,
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -1397,12 +1666,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             vec![],
             Some(all_the_gas),
             gas_price,
+            U256::zero(),
             None,
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -1490,12 +1760,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             vec![],
             Some(all_the_gas),
             gas_price,
+            U256::zero(),
             None,
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -1605,12 +1876,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             vec![],
             Some(all_the_gas),
             gas_price,
+            U256::zero(),
             None,
             true,
             1_000_000_000,
@@ -1717,12 +1989,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             vec![],
             Some(all_the_gas),
             gas_price,
+            U256::zero(),
             None,
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -1818,12 +2091,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             vec![],
             Some(all_the_gas),
             gas_price,
+            U256::zero(),
             None,
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -1862,6 +2136,120 @@ mod test {
         );
     }
 
+    #[test]
+    fn contract_selfdestruct_keeps_contract_in_cancun() {
+        let mut mock_runtime = MockHost::default();
+        let block = dummy_first_block();
+        let precompiles = precompiles::precompile_set::<MockHost>();
+        let mut evm_account_storage = init_evm_account_storage().unwrap();
+        let target = H160::from_low_u64_be(42_u64);
+        let caller = H160::from_low_u64_be(115_u64);
+        let selfdestructing_contract = H160::from_low_u64_be(100_u64);
+        let all_the_gas = 1_000_000_u64;
+        let gas_price = U256::from(1);
+
+        // This contract selfdestructs and gives its funds to `caller`
+        let selfdestructing_code = vec![
+            Opcode::PUSH1.as_u8(), // push address of beneficiary
+            115,
+            Opcode::SUICIDE.as_u8(), // this also stops execution
+        ];
+
+        set_account_code(
+            &mut mock_runtime,
+            &mut evm_account_storage,
+            &selfdestructing_contract,
+            &selfdestructing_code,
+        );
+
+        set_balance(
+            &mut mock_runtime,
+            &mut evm_account_storage,
+            &caller,
+            all_the_gas.into(),
+        );
+
+        set_balance(
+            &mut mock_runtime,
+            &mut evm_account_storage,
+            &selfdestructing_contract,
+            1_000_000.into(),
+        );
+
+        // contract that does call to contract above
+        let code = vec![
+            Opcode::PUSH1.as_u8(), // push return data size
+            0,
+            Opcode::PUSH1.as_u8(), // push return data offset
+            0,
+            Opcode::PUSH1.as_u8(), // push arg size
+            0,
+            Opcode::PUSH1.as_u8(), // push arg offset
+            0,
+            Opcode::PUSH1.as_u8(), // push value
+            0,
+            Opcode::PUSH1.as_u8(), // push address
+            100,
+            Opcode::PUSH2.as_u8(), // push gas
+            0xFF,
+            0xFF,
+            Opcode::CALL.as_u8(),
+        ];
+
+        set_account_code(&mut mock_runtime, &mut evm_account_storage, &target, &code);
+
+        let result = run_transaction(
+            &mut mock_runtime,
+            &block,
+            &mut evm_account_storage,
+            &precompiles,
+            EvmFork::Cancun,
+            Some(target),
+            caller,
+            vec![],
+            Some(all_the_gas),
+            gas_price,
+            U256::zero(),
+            None,
+            true,
+            DUMMY_ALLOCATED_TICKS,
+        );
+        let expected_gas = 21000 // base cost
+        + 30124; // execution gas cost (taken at face value from tests)
+        let expected_result = Ok(Some(ExecutionOutcome {
+            gas_used: expected_gas,
+            is_success: true,
+            reason: ExitReason::Succeed(ExitSucceed::Stopped),
+            new_address: None,
+            logs: vec![],
+            result: Some(vec![]),
+            withdrawals: vec![],
+            estimated_ticks_used: 23749485,
+        }));
+
+        assert_eq!(result, expected_result);
+
+        // EIP-6780: the contract wasn't created in the same transaction, so
+        // only its balance is transferred
+        let account = evm_account_storage
+            .get(
+                &mock_runtime,
+                &account_path(&selfdestructing_contract).unwrap(),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(account.code(&mock_runtime).unwrap(), selfdestructing_code);
+        assert_eq!(account.balance(&mock_runtime).unwrap(), U256::zero());
+
+        let funds_total =
+            1_000_000 + all_the_gas - expected_result.unwrap().unwrap().gas_used;
+
+        assert_eq!(
+            get_balance(&mut mock_runtime, &mut evm_account_storage, &caller),
+            funds_total.into()
+        );
+    }
+
     #[test]
     fn selfdestruct_is_ignored_when_call_fails() {
         let mut mock_runtime = MockHost::default();
@@ -1936,12 +2324,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             vec![],
             Some(all_the_gas),
             gas_price,
+            U256::zero(),
             None,
             true,
             10_000_000_000,
@@ -1987,7 +2376,7 @@ mod test {
         let chain_id = U256::from(42);
         let mut chain_id_bytes = [0u8; 32];
         chain_id.to_big_endian(&mut chain_id_bytes);
-        let block_fees = BlockFees::new(U256::from(54321));
+        let block_fees = BlockFees::new(U256::from(54321), U256::zero());
         let block = BlockConstants::first_block(U256::zero(), chain_id, block_fees);
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_evm_account_storage().unwrap();
@@ -2024,12 +2413,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             vec![],
             Some(all_the_gas),
             gas_price,
+            U256::zero(),
             None,
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -2059,7 +2449,7 @@ mod test {
         let base_fee_per_gas = U256::from(23000);
         let mut base_fee_per_gas_bytes = [0u8; 32];
         base_fee_per_gas.to_big_endian(&mut base_fee_per_gas_bytes);
-        let block_fees = BlockFees::new(base_fee_per_gas);
+        let block_fees = BlockFees::new(base_fee_per_gas, U256::zero());
         let block = BlockConstants::first_block(U256::zero(), U256::one(), block_fees);
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_evm_account_storage().unwrap();
@@ -2096,12 +2486,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             vec![],
             Some(all_the_gas),
             gas_price,
+            U256::zero(),
             None,
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -2173,12 +2564,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             vec![],
             None,
             gas_price,
+            U256::zero(),
             Some(U256::from(100)),
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -2222,12 +2614,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             callee,
             caller,
             call_data,
             Some(gas_limit),
             gas_price,
+            U256::zero(),
             Some(transaction_value),
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -2276,12 +2669,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             callee,
             caller,
             create_data,
             Some(gas_limit),
             gas_price,
+            U256::zero(),
             Some(transaction_value),
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -2303,7 +2697,7 @@ mod test {
         // Arrange
         let mut mock_runtime = MockHost::default();
         let base_fee_per_gas = U256::from(23000);
-        let block_fees = BlockFees::new(base_fee_per_gas);
+        let block_fees = BlockFees::new(base_fee_per_gas, U256::zero());
         let block = BlockConstants::first_block(U256::zero(), U256::one(), block_fees);
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_evm_account_storage().unwrap();
@@ -2335,12 +2729,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             data.to_vec(),
             Some(all_the_gas),
             gas_price,
+            U256::zero(),
             None,
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -2360,7 +2755,7 @@ mod test {
         // Arrange
         let mut mock_runtime = MockHost::default();
         let base_fee_per_gas = U256::from(23000);
-        let block_fees = BlockFees::new(base_fee_per_gas);
+        let block_fees = BlockFees::new(base_fee_per_gas, U256::zero());
         let block = BlockConstants::first_block(U256::zero(), U256::one(), block_fees);
         let precompiles = precompiles::precompile_set::<MockHost>();
         let mut evm_account_storage = init_evm_account_storage().unwrap();
@@ -2392,12 +2787,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(target),
             caller,
             data.to_vec(),
             Some(all_the_gas),
             gas_price,
+            U256::zero(),
             None,
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -2414,7 +2810,7 @@ mod test {
 
     fn first_block() -> BlockConstants {
         let base_fee_per_gas = U256::from(23000);
-        let block_fees = BlockFees::new(base_fee_per_gas);
+        let block_fees = BlockFees::new(base_fee_per_gas, U256::zero());
         BlockConstants::first_block(U256::zero(), U256::one(), block_fees)
     }
 
@@ -2444,12 +2840,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             None,
             caller,
             data.to_vec(),
             Some(all_the_gas),
             gas_price,
+            U256::zero(),
             None,
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -2543,12 +2940,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             None,
             caller,
             call_data,
             Some(gas_limit),
             gas_price,
+            U256::zero(),
             None,
             true,
             10_000_000_000,
@@ -2606,12 +3004,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             None,
             caller,
             call_data,
             Some(gas_limit),
             gas_price,
+            U256::zero(),
             None,
             true,
             10_000_000_000,
@@ -2651,12 +3050,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             callee,
             caller,
             call_data,
             Some(gas_limit),
             gas_price,
+            U256::zero(),
             Some(transaction_value),
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -2702,12 +3102,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             Some(callee),
             caller,
             vec![],
             Some(20000000),
             U256::one(),
+            U256::zero(),
             Some(U256::zero()),
             true,
             DUMMY_ALLOCATED_TICKS,
@@ -2766,12 +3167,13 @@ mod test {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             callee,
             caller,
             call_data,
             Some(gas_limit),
             gas_price,
+            U256::zero(),
             Some(transaction_value),
             true,
             10_000,
@@ -13,20 +13,34 @@
 
 use std::{cmp::min, str::FromStr, vec};
 
+use crate::blake2f;
+use crate::bls12_381_precompiled::{
+    g1_add_precompile, g1_mul_precompile, g1_multiexp_precompile, g2_add_precompile,
+    g2_mul_precompile, g2_multiexp_precompile, map_fp2_to_g2_precompile,
+    map_fp_to_g1_precompile, pairing_precompile,
+};
+use crate::fa_bridge::{fa_withdrawal_precompile, FA_BRIDGE_ADDRESS};
 use crate::handler::EvmHandler;
 use crate::zk_precompiled::{ecadd_precompile, ecmul_precompile, ecpairing_precompile};
 use crate::EthereumError;
 use crate::{abi, modexp::modexp_precompile};
 use alloc::collections::btree_map::BTreeMap;
-use evm::{Context, ExitReason, ExitRevert, ExitSucceed, Handler, Transfer};
+use evm::{Context, ExitError, ExitReason, ExitRevert, ExitSucceed, Handler, Transfer};
+use host::path::RefPath;
 use host::runtime::Runtime;
 use libsecp256k1::{curve::Scalar, recover, Message, RecoveryId, Signature};
 use primitive_types::{H160, U256};
 use ripemd::Ripemd160;
 use sha2::{Digest, Sha256};
 use sha3::Keccak256;
-use tezos_ethereum::withdrawal::Withdrawal;
+use tezos_crypto_rs::hash::{ContractKt1Hash, HashTrait};
+use tezos_data_encoding::enc::BinWriter;
+use tezos_ethereum::withdrawal::{L1Call, L1Message, MichelineExpr, Withdrawal};
 use tezos_evm_logging::{log, Level::*};
+use tezos_smart_rollup_core::MAX_OUTPUT_SIZE;
+use tezos_smart_rollup_encoding::contract::Contract;
+use tezos_smart_rollup_encoding::entrypoint::Entrypoint;
+use tezos_smart_rollup_encoding::outbox::{OutboxMessage, OutboxMessageTransactionTyped};
 
 /// Outcome of executing a precompiled contract. Covers both successful
 /// return, stop and revert and additionally, it covers contract execution
@@ -39,9 +53,9 @@ pub struct PrecompileOutcome {
     pub exit_status: ExitReason,
     /// The return value of the call.
     pub output: Vec<u8>,
-    /// Any withdrawals produced by the precompiled contract. This encodes
-    /// withdrawals to Tezos Layer 1.
-    pub withdrawals: Vec<Withdrawal>,
+    /// Any withdrawals or calls produced by the precompiled contract. This
+    /// encodes messages to Tezos Layer 1.
+    pub withdrawals: Vec<L1Message>,
     /// Number of ticks estimated by the tick model of the precompiled contract.
     /// Note that the implementation of the contract is responsible for failing
     /// with EthereumError::OutOfTicks if the number of tricks would make the
@@ -363,6 +377,100 @@ fn ripemd160_precompile<Host: Runtime>(
     })
 }
 
+/// Length of the input of the BLAKE2F precompiled contract: number of rounds
+/// (4 bytes), state vector (64 bytes), message block (128 bytes), offset
+/// counters (16 bytes) and final block indicator (1 byte).
+const BLAKE2F_INPUT_LENGTH: usize = 213;
+
+// implementation of 0x09 precompiled (blake2f), see EIP-152
+fn blake2f_precompile<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+    _context: &Context,
+    _is_static: bool,
+    _transfer: Option<Transfer>,
+) -> Result<PrecompileOutcome, EthereumError> {
+    log!(handler.borrow_host(), Info, "Calling blake2f precompile");
+
+    fn invalid_input<Host: Runtime>(
+        handler: &mut EvmHandler<Host>,
+        reason: &'static str,
+        estimated_ticks: u64,
+    ) -> PrecompileOutcome {
+        // An invalid input consumes all the gas of the call
+        let exit_status = match handler.record_cost(handler.gas_left().as_u64()) {
+            Ok(()) => ExitError::Other(reason.into()),
+            Err(err) => err,
+        };
+        PrecompileOutcome {
+            exit_status: ExitReason::Error(exit_status),
+            output: vec![],
+            withdrawals: vec![],
+            estimated_ticks,
+        }
+    }
+
+    if input.len() != BLAKE2F_INPUT_LENGTH {
+        let estimated_ticks = fail_if_too_much!(tick_model::ticks_of_blake2f(0), handler);
+        return Ok(invalid_input(
+            handler,
+            "Invalid BLAKE2F input length",
+            estimated_ticks,
+        ));
+    }
+
+    // The number of rounds isn't bounded by the size of the input, so the
+    // gas is recorded first to avoid estimating ticks of calls that can't
+    // be paid for.
+    let rounds = u32::from_be_bytes([input[0], input[1], input[2], input[3]]);
+    if let Err(err) = handler.record_cost(rounds as u64) {
+        return Ok(PrecompileOutcome {
+            exit_status: ExitReason::Error(err),
+            output: vec![],
+            withdrawals: vec![],
+            estimated_ticks: tick_model::ticks_of_blake2f(0),
+        });
+    }
+    let estimated_ticks =
+        fail_if_too_much!(tick_model::ticks_of_blake2f(rounds), handler);
+
+    let f = match input[212] {
+        0 => false,
+        1 => true,
+        _ => {
+            return Ok(invalid_input(
+                handler,
+                "Invalid BLAKE2F final block indicator",
+                estimated_ticks,
+            ))
+        }
+    };
+
+    let word = |offset: usize| {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&input[offset..offset + 8]);
+        u64::from_le_bytes(bytes)
+    };
+    let mut h = [0u64; 8];
+    for (i, h) in h.iter_mut().enumerate() {
+        *h = word(4 + 8 * i);
+    }
+    let mut m = [0u64; 16];
+    for (i, m) in m.iter_mut().enumerate() {
+        *m = word(68 + 8 * i);
+    }
+    let t = [word(196), word(204)];
+
+    blake2f::compress(rounds, &mut h, &m, t, f);
+
+    Ok(PrecompileOutcome {
+        exit_status: ExitReason::Succeed(ExitSucceed::Returned),
+        output: h.iter().flat_map(|word| word.to_le_bytes()).collect(),
+        withdrawals: vec![],
+        estimated_ticks,
+    })
+}
+
 /// Implementation of Etherelink specific withdrawals precompiled contract.
 fn withdrawal_precompile<Host: Runtime>(
     handler: &mut EvmHandler<Host>,
@@ -421,10 +529,11 @@ fn withdrawal_precompile<Host: Runtime>(
             // TODO we need to measure number of ticks and translate this number into
             // Ethereum gas units
 
-            let withdrawals = vec![Withdrawal {
+            let withdrawals = vec![L1Message::Withdrawal(Withdrawal {
                 target,
                 amount: transfer.value,
-            }];
+                ticket: None,
+            })];
 
             Ok(PrecompileOutcome {
                 exit_status: ExitReason::Succeed(ExitSucceed::Returned),
@@ -445,6 +554,156 @@ fn withdrawal_precompile<Host: Runtime>(
     }
 }
 
+/// Address of the precompiled contract calling layer 1 contracts.
+pub const L1_CALL_ADDRESS: H160 = H160([
+    0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x03,
+]);
+
+/// Gas cost of a call to layer 1, on top of the cost per byte of its
+/// outbox message.
+const L1_CALL_BASE_COST: u64 = 10_000;
+
+const L1_CALL_COST_PER_BYTE: u64 = 16;
+
+/// Selector of `call(string,bytes,bytes)`.
+const L1_CALL_SELECTOR: [u8; 4] = [0x43, 0x3a, 0x71, 0x7e];
+
+/// Entrypoint of the layer 1 contracts called from the EVM.
+const L1_CALL_ENTRYPOINT: &str = "l2_call";
+
+/// Paths of `storage.rs` in the kernel, of the contracts trusting the calls
+/// from the rollup: the ticketer, the delayed bridge and the admins.
+const L1_CALL_RESERVED_DESTINATIONS: [RefPath; 4] = [
+    RefPath::assert_from(b"/ticketer"),
+    RefPath::assert_from(b"/delayed_bridge"),
+    RefPath::assert_from(b"/admin"),
+    RefPath::assert_from(b"/sequencer_admin"),
+];
+
+/// Checks if the destination is one of the contracts set in
+/// [L1_CALL_RESERVED_DESTINATIONS], stored in b58.
+fn is_reserved_destination<Host: Runtime>(
+    host: &Host,
+    destination: &ContractKt1Hash,
+) -> bool {
+    let destination = destination.to_b58check();
+    L1_CALL_RESERVED_DESTINATIONS.iter().any(|path| {
+        host.store_read(path, 0, destination.len())
+            .map_or(false, |contract| contract == destination.as_bytes())
+    })
+}
+
+/// Implementation of the precompiled contract sending calls to layer 1
+/// contracts, `call(string destination, bytes type, bytes parameters)` where
+/// the type and the parameters are binary Micheline expressions.
+///
+/// The parameters must be a value of the type, which can't contain tickets,
+/// see [MichelineExpr::has_ticket_free_type]. The destination is called on
+/// its [L1_CALL_ENTRYPOINT] entrypoint, of type `pair bytes <type>`, with
+/// the address of the caller and the parameters. The contracts of the rollup
+/// can't be called.
+///
+/// Each call is sent in its own typed outbox message, in the order of
+/// execution with the withdrawals of the transaction.
+fn l1_call_precompile<Host: Runtime>(
+    handler: &mut EvmHandler<Host>,
+    input: &[u8],
+    context: &Context,
+    is_static: bool,
+    transfer: Option<Transfer>,
+) -> Result<PrecompileOutcome, EthereumError> {
+    let estimated_ticks =
+        fail_if_too_much!(tick_model::ticks_of_l1_call(input.len()), handler);
+    let revert = |handler: &mut EvmHandler<Host>, reason: &str| {
+        log!(
+            handler.borrow_host(),
+            Info,
+            "L1 call precompiled contract: {}",
+            reason
+        );
+        Ok(PrecompileOutcome {
+            exit_status: ExitReason::Revert(ExitRevert::Reverted),
+            output: vec![],
+            withdrawals: vec![],
+            estimated_ticks,
+        })
+    };
+
+    if is_static {
+        return revert(handler, "static call");
+    }
+    if transfer.map_or(false, |transfer| !transfer.value.is_zero()) {
+        return revert(handler, "non zero transfer");
+    }
+
+    let Some(rest) = input.strip_prefix(&L1_CALL_SELECTOR) else {
+        return revert(handler, "invalid function selector");
+    };
+    let Some(Contract::Originated(destination)) = abi::string_parameter(rest, 0)
+        .and_then(|destination| Contract::from_b58check(destination).ok())
+    else {
+        return revert(handler, "invalid destination");
+    };
+    if is_reserved_destination(handler.borrow_host(), &destination) {
+        return revert(handler, "reserved destination");
+    }
+    let Some(parameters_ty) = abi::bytes_parameter(rest, 1)
+        .and_then(|parameters_ty| MichelineExpr::from_bytes(parameters_ty.to_vec()))
+    else {
+        return revert(handler, "invalid type");
+    };
+    let Some(parameters) = abi::bytes_parameter(rest, 2)
+        .and_then(|parameters| MichelineExpr::from_bytes(parameters.to_vec()))
+    else {
+        return revert(handler, "invalid parameters");
+    };
+    // The rollup pays the tickets of the parameters, only the bridges can
+    // send them. Layer 1 reads the parameters with their type, which must be
+    // the type of the entrypoint.
+    if !parameters.has_ticket_free_type(&parameters_ty) {
+        return revert(handler, "parameters are not of a ticket free type");
+    }
+    let parameters = parameters.paired_with_bytes(context.caller.as_bytes());
+    let parameters_ty = parameters_ty.paired_with_bytes_type();
+    let entrypoint = Entrypoint::try_from(String::from(L1_CALL_ENTRYPOINT)).unwrap();
+
+    let message = OutboxMessage::AtomicTransactionBatchTyped(
+        vec![OutboxMessageTransactionTyped {
+            parameters: parameters.clone(),
+            parameters_ty: parameters_ty.clone(),
+            destination: Contract::Originated(destination.clone()),
+            entrypoint: entrypoint.clone(),
+        }]
+        .into(),
+    );
+    let mut encoded = Vec::with_capacity(MAX_OUTPUT_SIZE);
+    if message.bin_write(&mut encoded).is_err() || encoded.len() > MAX_OUTPUT_SIZE {
+        return revert(handler, "outbox message too big");
+    }
+
+    let cost = L1_CALL_BASE_COST + L1_CALL_COST_PER_BYTE * encoded.len() as u64;
+    if let Err(err) = handler.record_cost(cost) {
+        return Ok(PrecompileOutcome {
+            exit_status: ExitReason::Error(err),
+            output: vec![],
+            withdrawals: vec![],
+            estimated_ticks,
+        });
+    }
+
+    Ok(PrecompileOutcome {
+        exit_status: ExitReason::Succeed(ExitSucceed::Returned),
+        output: vec![],
+        withdrawals: vec![L1Message::Call(L1Call {
+            destination,
+            entrypoint,
+            parameters,
+            parameters_ty,
+        })],
+        estimated_ticks,
+    })
+}
+
 /// Factory function for generating the precompileset that the EVM kernel uses.
 pub fn precompile_set<Host: Runtime>() -> PrecompileBTreeMap<Host> {
     BTreeMap::from([
@@ -480,6 +739,48 @@ pub fn precompile_set<Host: Runtime>() -> PrecompileBTreeMap<Host> {
             H160::from_low_u64_be(8u64),
             ecpairing_precompile as PrecompileFn<Host>,
         ),
+        (
+            H160::from_low_u64_be(9u64),
+            blake2f_precompile as PrecompileFn<Host>,
+        ),
+        // BLS12-381 precompiled contracts (EIP-2537), 0x0a is left for the
+        // point evaluation precompiled contract of EIP-4844.
+        (
+            H160::from_low_u64_be(11u64),
+            g1_add_precompile as PrecompileFn<Host>,
+        ),
+        (
+            H160::from_low_u64_be(12u64),
+            g1_mul_precompile as PrecompileFn<Host>,
+        ),
+        (
+            H160::from_low_u64_be(13u64),
+            g1_multiexp_precompile as PrecompileFn<Host>,
+        ),
+        (
+            H160::from_low_u64_be(14u64),
+            g2_add_precompile as PrecompileFn<Host>,
+        ),
+        (
+            H160::from_low_u64_be(15u64),
+            g2_mul_precompile as PrecompileFn<Host>,
+        ),
+        (
+            H160::from_low_u64_be(16u64),
+            g2_multiexp_precompile as PrecompileFn<Host>,
+        ),
+        (
+            H160::from_low_u64_be(17u64),
+            pairing_precompile as PrecompileFn<Host>,
+        ),
+        (
+            H160::from_low_u64_be(18u64),
+            map_fp_to_g1_precompile as PrecompileFn<Host>,
+        ),
+        (
+            H160::from_low_u64_be(19u64),
+            map_fp2_to_g2_precompile as PrecompileFn<Host>,
+        ),
         (
             // Prefixed by 'ff' to make sure we will not conflict with any
             // upcoming Ethereum upgrades.
@@ -487,6 +788,11 @@ pub fn precompile_set<Host: Runtime>() -> PrecompileBTreeMap<Host> {
             H160::from_str("ff00000000000000000000000000000000000001").unwrap(),
             withdrawal_precompile as PrecompileFn<Host>,
         ),
+        (
+            FA_BRIDGE_ADDRESS,
+            fa_withdrawal_precompile as PrecompileFn<Host>,
+        ),
+        (L1_CALL_ADDRESS, l1_call_precompile as PrecompileFn<Host>),
     ])
 }
 mod tick_model {
@@ -509,9 +815,25 @@ mod tick_model {
         1_000_000
     }
 
+    pub fn ticks_of_l1_call(data_size: usize) -> u64 {
+        // Measured in WASM instructions, scaled by the ticks of the opcodes
+        // with a benchmarked model, with sequences of small Micheline nodes
+        // (the worst case per byte): about 530_000 ticks to decode the destination and encode the
+        // message, and 1_650 ticks per byte to type check the parameters.
+        600_000 + 1_700 * data_size as u64
+    }
+
     pub fn ticks_of_ecrecover() -> u64 {
         30_000_000
     }
+
+    pub fn ticks_of_blake2f(rounds: u32) -> u64 {
+        // Measured in WASM instructions, scaled by the ticks of the opcodes
+        // with a benchmarked model, for 0 to 5000 rounds: about 56_600
+        // ticks to decode the input and encode the output, and 3_610 ticks
+        // per round.
+        60_000 + 4_000 * rounds as u64
+    }
 }
 
 #[cfg(test)]
@@ -520,10 +842,10 @@ mod tests {
     use super::*;
     use crate::account_storage::account_path;
     use crate::account_storage::init_account_storage as init_evm_account_storage;
+    use crate::fork::EvmFork;
     use crate::handler::ExecutionOutcome;
     use crate::EthereumAccountStorage;
-    use evm::Config;
-    use primitive_types::{H160, U256};
+    use primitive_types::{H160, H256, U256};
     use tezos_ethereum::block::BlockConstants;
     use tezos_ethereum::block::BlockFees;
     use tezos_smart_rollup_encoding::contract::Contract;
@@ -558,18 +880,65 @@ mod tests {
         transfer: Option<Transfer>,
         gas_limit: Option<u64>,
     ) -> Result<ExecutionOutcome, EthereumError> {
-        let caller = H160::from_low_u64_be(118u64);
+        execute_precompiled_call(address, input, transfer, gas_limit, true)
+    }
+
+    fn execute_precompiled_call(
+        address: H160,
+        input: &[u8],
+        transfer: Option<Transfer>,
+        gas_limit: Option<u64>,
+        is_static: bool,
+    ) -> Result<ExecutionOutcome, EthereumError> {
+        execute_precompiled_with_ticks(
+            address,
+            input,
+            transfer,
+            gas_limit,
+            is_static,
+            DUMMY_ALLOCATED_TICKS,
+        )
+    }
+
+    fn execute_precompiled_with_ticks(
+        address: H160,
+        input: &[u8],
+        transfer: Option<Transfer>,
+        gas_limit: Option<u64>,
+        is_static: bool,
+        allocated_ticks: u64,
+    ) -> Result<ExecutionOutcome, EthereumError> {
         let mut mock_runtime = MockHost::default();
-        let block_fees = BlockFees::new(U256::from(21000));
+        execute_precompiled_on_host(
+            &mut mock_runtime,
+            address,
+            input,
+            transfer,
+            gas_limit,
+            is_static,
+            allocated_ticks,
+        )
+    }
+
+    fn execute_precompiled_on_host(
+        mock_runtime: &mut MockHost,
+        address: H160,
+        input: &[u8],
+        transfer: Option<Transfer>,
+        gas_limit: Option<u64>,
+        is_static: bool,
+        allocated_ticks: u64,
+    ) -> Result<ExecutionOutcome, EthereumError> {
+        let caller = H160::from_low_u64_be(118u64);
+        let block_fees = BlockFees::new(U256::from(21000), U256::zero());
         let block = BlockConstants::first_block(U256::zero(), U256::one(), block_fees);
         let mut evm_account_storage = init_evm_account_storage().unwrap();
         let precompiles = precompile_set::<MockHost>();
-        let config = Config::shanghai();
         let gas_price = U256::from(21000);
 
         if let Some(Transfer { source, value, .. }) = transfer {
             set_balance(
-                &mut mock_runtime,
+                mock_runtime,
                 &mut evm_account_storage,
                 &source,
                 value
@@ -581,17 +950,16 @@ mod tests {
         }
 
         let mut handler = EvmHandler::new(
-            &mut mock_runtime,
+            mock_runtime,
             &mut evm_account_storage,
             caller,
             &block,
-            &config,
+            EvmFork::Shanghai,
             &precompiles,
-            DUMMY_ALLOCATED_TICKS,
+            allocated_ticks,
             gas_price,
         );
 
-        let is_static = true;
         let value = transfer.map(|t| t.value);
 
         handler.call_contract(
@@ -710,10 +1078,11 @@ mod tests {
             new_address: None,
             logs: vec![],
             result: Some(expected_output),
-            withdrawals: vec![Withdrawal {
+            withdrawals: vec![L1Message::Withdrawal(Withdrawal {
                 target: expected_target,
                 amount: 100.into(),
-            }],
+                ticket: None,
+            })],
             estimated_ticks_used: 1_000_000,
         };
 
@@ -765,10 +1134,11 @@ mod tests {
             new_address: None,
             logs: vec![],
             result: Some(expected_output),
-            withdrawals: vec![Withdrawal {
+            withdrawals: vec![L1Message::Withdrawal(Withdrawal {
                 target: expected_target,
                 amount: 100.into(),
-            }],
+                ticket: None,
+            })],
             // TODO (#6426): estimate the ticks consumption of precompiled contracts
             estimated_ticks_used: 1_000_000,
         };
@@ -1440,4 +1810,437 @@ mod tests {
         // ERR_BN128_INVALID_LEN
         assert!(result.is_err());
     }
+
+    // Test vectors from EIP-152:
+    // https://eips.ethereum.org/EIPS/eip-152#test-cases
+    fn blake2f_input(rounds: &str, f: &str) -> Vec<u8> {
+        hex::decode(format!(
+            "{rounds}\
+             48c9bdf267e6096a3ba7ca8485ae67bb2bf894fe72f36e3cf1361d5f3af54fa5\
+             d182e6ad7f520e511f6c3e2b8c68059b6bbd41fbabd9831f79217e1319cde05b\
+             6162630000000000000000000000000000000000000000000000000000000000\
+             0000000000000000000000000000000000000000000000000000000000000000\
+             0000000000000000000000000000000000000000000000000000000000000000\
+             0000000000000000000000000000000000000000000000000000000000000000\
+             03000000000000000000000000000000\
+             {f}"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_blake2f_precompile() {
+        let address = H160::from_low_u64_be(9);
+        let tests = [
+            (
+                "00000000",
+                "01",
+                "08c9bcf367e6096a3ba7ca8485ae67bb2bf894fe72f36e3cf1361d5f3af54fa5\
+                 d282e6ad7f520e511f6c3e2b8c68059b9442be0454267ce079217e1319cde05b",
+            ),
+            (
+                "0000000c",
+                "01",
+                "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+                 7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923",
+            ),
+            (
+                "0000000c",
+                "00",
+                "75ab69d3190a562c51aef8d88f1c2775876944407270c42c9844252c26d28752\
+                 98743e7f6d5ea2f2d3e8d226039cd31b4e426ac4f2d3d666a610c2116fde4735",
+            ),
+            (
+                "00000001",
+                "01",
+                "b63a380cb2897d521994a85234ee2c181b5f844d2c624c002677e9703449d2fb\
+                 a551b3a8333bcdf5f2f7e08993d53923de3d64fcc68c034e717b9293fed7a421",
+            ),
+        ];
+
+        for (rounds, f, expected) in tests {
+            let input = blake2f_input(rounds, f);
+            let result = execute_precompiled(address, &input, None, Some(30_000));
+
+            let outcome = result.unwrap();
+            assert!(outcome.is_success);
+            assert_eq!(hex::encode(outcome.result.unwrap()), expected);
+        }
+    }
+
+    #[test]
+    fn test_blake2f_invalid_input() {
+        let address = H160::from_low_u64_be(9);
+        let valid_input = blake2f_input("0000000c", "01");
+        let invalid_inputs = [
+            vec![],
+            valid_input[..212].to_vec(),
+            [valid_input.clone(), vec![0]].concat(),
+            blake2f_input("0000000c", "02"),
+        ];
+
+        for input in invalid_inputs {
+            let result = execute_precompiled(address, &input, None, Some(30_000));
+
+            let outcome = result.unwrap();
+            assert!(!outcome.is_success);
+            assert_eq!(outcome.gas_used, 30_000);
+        }
+    }
+
+    // Encodings of the generators of G1 and G2, and of `[2]G1`.
+    const BLS_G1: &str = "\
+        0000000000000000000000000000000017f1d3a73197d7942695638c4fa9ac0f\
+        c3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb\
+        0000000000000000000000000000000008b3f481e3aaa0f1a09e30ed741d8ae4\
+        fcf5e095d5d00af600db18cb2c04b3edd03cc744a2888ae40caa232946c5e7e1";
+    const BLS_G1_DOUBLE: &str = "\
+        000000000000000000000000000000000572cbea904d67468808c8eb50a9450c\
+        9721db309128012543902d0ac358a62ae28f75bb8f1c7c42c39a8c5529bf0f4e\
+        00000000000000000000000000000000166a9d8cabc673a322fda673779d8e38\
+        22ba3ecb8670e461f73bb9021d5fd76a4c56d9d4cd16bd1bba86881979749d28";
+    const BLS_G2: &str = "\
+        00000000000000000000000000000000024aa2b2f08f0a91260805272dc51051\
+        c6e47ad4fa403b02b4510b647ae3d1770bac0326a805bbefd48056c8c121bdb8\
+        0000000000000000000000000000000013e02b6052719f607dacd3a088274f65\
+        596bd0d09920b61ab5da61bbdc7f5049334cf11213945d57e5ac7d055d042b7e\
+        000000000000000000000000000000000ce5d527727d6e118cc9cdc6da2e351a\
+        adfd9baa8cbdd3a76d429a695160d12c923ac9cc3baca289e193548608b82801\
+        000000000000000000000000000000000606c4a02ea734cc32acd2b02bc28b99\
+        cb3e287e85a763af267492ab572e99ab3f370d275cec1da1aaa9075ff05f79be";
+    // The base field modulus, which is not a member of the field.
+    const BLS_FP_MODULUS: &str = "\
+        000000000000000000000000000000001a0111ea397fe69a4b1ba7b6434bacd7\
+        64774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffaaab";
+    // An element of Fp2, whose first component is also used as an element of
+    // Fp, and its images by the maps, checked against an independent
+    // implementation of the maps of the EIP.
+    const BLS_MAP_FP_INPUT: &str = "\
+        000000000000000000000000000000000a7f17a7a09f92e558a69fac7957076d\
+        bd7694b9561a4efacf10629029c334ba3969cb6046ec3115892eea133511731f\
+        0000000000000000000000000000000006ae71db4a94e70650f84d7583864397\
+        62e8e9fa09c58849b162e87def96fdec715b657b9d0ee4a91038ea254991fed7";
+    const BLS_MAP_FP_TO_G1: &str = "\
+        0000000000000000000000000000000011ebadb6118fef08986183f0de4724be\
+        ff3212cab0adb88114605785cad7bbf7a38b8f967a83423169b53c2b7bad2592\
+        000000000000000000000000000000000a6f8d22bb4d8d0534bc717a9cb9315f\
+        fd62fb6d57717b5e89fc0bf458a9d36db456ce3a2d3fa0b69f3005a22ac76463";
+    const BLS_MAP_FP2_TO_G2: &str = "\
+        0000000000000000000000000000000018d9360971de35f76143404a163bd864\
+        27697874b7752dccd8316268750edc4b4c66bb2820f475f91abf4692820b9f91\
+        00000000000000000000000000000000101024135e7538b489f9f9766b6bb12e\
+        bbbd5f157e7b104d9a9a99c512ea0b43cb5b1bf0d91558d6b73d01bf08b23ea9\
+        000000000000000000000000000000000ac988fb12a95290442234b2362cbfd4\
+        917c10ca8046b7b2f875e9b22807b71ea313fcc77f1d83a3063544fde6f65467\
+        0000000000000000000000000000000005a0b5bed9a47e913ecb2297c0d027c2\
+        b39a88e591daa1c1d0d7af08445621139c715fe28da7704f437d37a5455991d3";
+    // `r - 1`, where `r` is the order of G1 and G2.
+    const BLS_MINUS_ONE: &str =
+        "73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000000";
+
+    fn bls_scalar(n: u64) -> String {
+        format!("{:064x}", n)
+    }
+
+    // The pairing of two pairs needs more ticks than `DUMMY_ALLOCATED_TICKS`.
+    const BLS_ALLOCATED_TICKS: u64 = 5_000_000_000;
+
+    fn call_bls(address: u64, input: &str) -> Result<ExecutionOutcome, EthereumError> {
+        execute_precompiled_with_ticks(
+            H160::from_low_u64_be(address),
+            &hex::decode(input).unwrap(),
+            None,
+            Some(1_000_000),
+            true,
+            BLS_ALLOCATED_TICKS,
+        )
+    }
+
+    fn call_bls_success(address: u64, input: &str) -> String {
+        let outcome = call_bls(address, input).unwrap();
+        assert!(outcome.is_success);
+        hex::encode(outcome.result.unwrap())
+    }
+
+    #[test]
+    fn test_bls12_381_g1_precompiles() {
+        let infinity = "00".repeat(128);
+
+        let sum = call_bls_success(11, &format!("{BLS_G1}{BLS_G1}"));
+        assert_eq!(sum, BLS_G1_DOUBLE);
+        let sum = call_bls_success(11, &format!("{BLS_G1}{infinity}"));
+        assert_eq!(sum, BLS_G1);
+
+        let product = call_bls_success(12, &format!("{BLS_G1}{}", bls_scalar(2)));
+        assert_eq!(product, BLS_G1_DOUBLE);
+        let product = call_bls_success(12, &format!("{BLS_G1}{}", bls_scalar(0)));
+        assert_eq!(product, infinity);
+
+        let multiexp = call_bls_success(
+            13,
+            &format!("{BLS_G1}{}{BLS_G1}{}", bls_scalar(3), bls_scalar(5)),
+        );
+        let expected = call_bls_success(12, &format!("{BLS_G1}{}", bls_scalar(8)));
+        assert_eq!(multiexp, expected);
+    }
+
+    #[test]
+    fn test_bls12_381_g2_precompiles() {
+        let infinity = "00".repeat(256);
+
+        let sum = call_bls_success(14, &format!("{BLS_G2}{BLS_G2}"));
+        let product = call_bls_success(15, &format!("{BLS_G2}{}", bls_scalar(2)));
+        assert_eq!(sum, product);
+        let sum = call_bls_success(14, &format!("{BLS_G2}{infinity}"));
+        assert_eq!(sum, BLS_G2);
+
+        let multiexp = call_bls_success(
+            16,
+            &format!("{BLS_G2}{}{BLS_G2}{}", bls_scalar(1), bls_scalar(1)),
+        );
+        assert_eq!(multiexp, product);
+
+        let product = call_bls_success(15, &format!("{BLS_G2}{BLS_MINUS_ONE}"));
+        let sum = call_bls_success(14, &format!("{BLS_G2}{product}"));
+        assert_eq!(sum, infinity);
+    }
+
+    #[test]
+    fn test_bls12_381_pairing_precompile() {
+        let one = bls_scalar(1);
+        let zero = bls_scalar(0);
+        let minus_g1 = call_bls_success(12, &format!("{BLS_G1}{BLS_MINUS_ONE}"));
+        let g2_double = call_bls_success(15, &format!("{BLS_G2}{}", bls_scalar(2)));
+
+        // e([2]G1, G2) * e(-G1, [2]G2) = 1
+        let input = format!("{BLS_G1_DOUBLE}{BLS_G2}{minus_g1}{g2_double}");
+        assert_eq!(call_bls_success(17, &input), one);
+
+        let input = format!("{BLS_G1}{BLS_G2}");
+        assert_eq!(call_bls_success(17, &input), zero);
+
+        let input = format!("{}{BLS_G2}", "00".repeat(128));
+        assert_eq!(call_bls_success(17, &input), one);
+    }
+
+    #[test]
+    fn test_bls12_381_map_precompiles() {
+        // The results are in the subgroups, as the multiplications check it.
+        let fp = &BLS_FP_MODULUS.replace("1a0111ea", "0a0111ea");
+        let g1 = call_bls_success(18, fp);
+        assert_ne!(g1, "00".repeat(128));
+        call_bls_success(12, &format!("{g1}{}", bls_scalar(1)));
+
+        let g2 = call_bls_success(19, &format!("{fp}{fp}"));
+        assert_ne!(g2, "00".repeat(256));
+        call_bls_success(15, &format!("{g2}{}", bls_scalar(1)));
+
+        let fp = &BLS_MAP_FP_INPUT[..128];
+        assert_eq!(call_bls_success(18, fp), BLS_MAP_FP_TO_G1);
+        assert_eq!(call_bls_success(19, BLS_MAP_FP_INPUT), BLS_MAP_FP2_TO_G2);
+    }
+
+    #[test]
+    fn test_bls12_381_invalid_inputs() {
+        let not_on_curve = BLS_G1.replace("c5e7e1", "c5e7e2");
+        let invalid_padding = BLS_G1.replacen("00", "01", 1);
+        let invalid_inputs = [
+            // Invalid lengths
+            (11, BLS_G1.to_string()),
+            (12, BLS_G1.to_string()),
+            (13, String::new()),
+            (17, String::new()),
+            (18, format!("{BLS_FP_MODULUS}00")),
+            // Invalid field elements
+            (18, BLS_FP_MODULUS.to_string()),
+            (19, format!("{BLS_FP_MODULUS}{}", "00".repeat(64))),
+            (11, format!("{invalid_padding}{BLS_G1}")),
+            // Invalid points
+            (11, format!("{not_on_curve}{BLS_G1}")),
+            (12, format!("{not_on_curve}{}", bls_scalar(1))),
+            (17, format!("{not_on_curve}{BLS_G2}")),
+        ];
+
+        for (address, input) in invalid_inputs {
+            assert!(call_bls(address, &input).is_err(), "{address}: {input}");
+        }
+    }
+
+    fn l1_call_input(destination: &str, ty: &[u8], parameters: &[u8]) -> Vec<u8> {
+        let mut head = L1_CALL_SELECTOR.to_vec();
+        let mut tail = vec![];
+        for argument in [destination.as_bytes(), ty, parameters] {
+            head.extend_from_slice(&H256::from_low_u64_be(96 + tail.len() as u64).0);
+            tail.extend_from_slice(&H256::from_low_u64_be(argument.len() as u64).0);
+            tail.extend_from_slice(argument);
+            tail.resize(tail.len() + (32 - argument.len() % 32) % 32, 0);
+        }
+        head.extend(tail);
+        head
+    }
+
+    // nat
+    const NAT_TYPE: [u8; 2] = [0x03, 0x62];
+
+    fn data_cost(input: &[u8]) -> u64 {
+        input
+            .iter()
+            .map(|byte| if *byte == 0 { 4 } else { 16 })
+            .sum()
+    }
+
+    #[test]
+    fn call_l1_call() {
+        let destination = "KT1BuEZtb68c1Q4yjtckcNjGELqWt56Xyesc";
+        let input = l1_call_input(destination, &NAT_TYPE, &[0x00, 0x2a]);
+
+        let result =
+            execute_precompiled_call(L1_CALL_ADDRESS, &input, None, Some(50_000), false);
+
+        let expected_gas = 21000 // base cost
+            + 10_000 + 16 * 73 // call cost, for an outbox message of 73 bytes
+            + data_cost(&input);
+
+        // Pair <caller> 42
+        let parameters = MichelineExpr::from_bytes(vec![0x00, 0x2a])
+            .unwrap()
+            .paired_with_bytes(H160::from_low_u64_be(118u64).as_bytes());
+        // pair bytes nat
+        let parameters_ty =
+            MichelineExpr::from_bytes(vec![0x07, 0x65, 0x03, 0x69, 0x03, 0x62]).unwrap();
+
+        let expected = ExecutionOutcome {
+            gas_used: expected_gas,
+            reason: ExitReason::Succeed(ExitSucceed::Returned),
+            is_success: true,
+            new_address: None,
+            logs: vec![],
+            result: Some(vec![]),
+            withdrawals: vec![L1Message::Call(L1Call {
+                destination: ContractKt1Hash::from_b58check(destination).unwrap(),
+                entrypoint: Entrypoint::try_from(String::from("l2_call")).unwrap(),
+                parameters,
+                parameters_ty,
+            })],
+            estimated_ticks_used: tick_model::ticks_of_l1_call(input.len()),
+        };
+
+        assert_eq!(Ok(expected), result);
+    }
+
+    #[test]
+    fn call_l1_call_reverts_on_invalid_calls() {
+        let destination = "KT1BuEZtb68c1Q4yjtckcNjGELqWt56Xyesc";
+        let valid = l1_call_input(destination, &NAT_TYPE, &[0x00, 0x2a]);
+        // "KT1BuEZtb68c1Q4yjtckcNjGELqWt56Xyesc"
+        let kt1 = [&[0x01, 0x00, 0x00, 0x00, 0x24][..], destination.as_bytes()].concat();
+        // Pair "KT1BuEZtb68c1Q4yjtckcNjGELqWt56Xyesc" 0 1
+        let ticket = [
+            &[0x09, 0x07, 0x00, 0x00, 0x00, 0x2d][..],
+            &kt1,
+            &[0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00],
+        ]
+        .concat();
+        // The same value can be sent as `pair address nat nat`, layer 1
+        // checks the type of the destination.
+        let outcome = execute_precompiled_call(
+            L1_CALL_ADDRESS,
+            &l1_call_input(
+                destination,
+                &[
+                    0x09, 0x65, 0x00, 0x00, 0x00, 0x06, 0x03, 0x6e, 0x03, 0x62, 0x03,
+                    0x62, 0x00, 0x00, 0x00, 0x00,
+                ],
+                &ticket,
+            ),
+            None,
+            Some(50_000),
+            false,
+        )
+        .unwrap();
+        assert!(outcome.is_success);
+        let invalid_inputs = [
+            // Implicit destination
+            l1_call_input(
+                "tz1RjtZUVeLhADFHDL8UwDZA6vjWWhojpu5w",
+                &NAT_TYPE,
+                &[0x00, 0x2a],
+            ),
+            // Invalid Micheline
+            l1_call_input(destination, &[0x03], &[0x00, 0x2a]),
+            l1_call_input(destination, &NAT_TYPE, &[0x00, 0x2a, 0x00]),
+            // Ill typed parameters
+            l1_call_input(destination, &NAT_TYPE, &kt1),
+            // Ticket sent from the tickets of the rollup, as `ticket nat`
+            l1_call_input(destination, &[0x05, 0x87, 0x03, 0x62], &ticket),
+            // Outbox message too big
+            l1_call_input(
+                destination,
+                // string
+                &[0x03, 0x68],
+                &[&[0x01, 0x00, 0x00, 0x10, 0x00], &[0x61; 4096][..]].concat(),
+            ),
+        ];
+
+        let revert = |result: Result<ExecutionOutcome, EthereumError>| {
+            let outcome = result.unwrap();
+            assert_eq!(outcome.reason, ExitReason::Revert(ExitRevert::Reverted));
+            assert!(outcome.withdrawals.is_empty());
+        };
+
+        for input in invalid_inputs {
+            revert(execute_precompiled_call(
+                L1_CALL_ADDRESS,
+                &input,
+                None,
+                Some(200_000),
+                false,
+            ));
+        }
+
+        // Static calls cannot send messages.
+        revert(execute_precompiled_call(
+            L1_CALL_ADDRESS,
+            &valid,
+            None,
+            Some(50_000),
+            true,
+        ));
+
+        // The precompiled contract is not payable.
+        let transfer = Some(Transfer {
+            source: H160::from_low_u64_be(118u64),
+            target: L1_CALL_ADDRESS,
+            value: U256::one(),
+        });
+        revert(execute_precompiled_call(
+            L1_CALL_ADDRESS,
+            &valid,
+            transfer,
+            Some(50_000),
+            false,
+        ));
+
+        // The contracts of the rollup can't be called.
+        for path in L1_CALL_RESERVED_DESTINATIONS {
+            let mut host = MockHost::default();
+            host.store_write_all(&path, destination.as_bytes()).unwrap();
+            revert(execute_precompiled_on_host(
+                &mut host,
+                L1_CALL_ADDRESS,
+                &valid,
+                None,
+                Some(50_000),
+                false,
+                DUMMY_ALLOCATED_TICKS,
+            ));
+        }
+
+        // The gas of the call is charged per byte of the message.
+        let outcome =
+            execute_precompiled_call(L1_CALL_ADDRESS, &valid, None, Some(30_000), false)
+                .unwrap();
+        assert_eq!(outcome.reason, ExitReason::Error(ExitError::OutOfGas));
+        assert!(outcome.withdrawals.is_empty());
+    }
 }
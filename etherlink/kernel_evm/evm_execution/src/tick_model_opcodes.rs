@@ -10,6 +10,7 @@
 // it needs to be updated, please have a look at the script
 // `etherlink/kernel_evm/benchmarks/scripts/analysis/opcodes.js`.
 
+use crate::handler::TouchedAccounts;
 use evm::Opcode;
 
 // Default ticks per gas value
@@ -210,6 +211,20 @@ const MODEL_0X5A: u64 = 2969;
 // Average: 3354; Standard deviation: 0
 const MODEL_0X5B: u64 = 3354;
 
+// Average: 292; Standard deviation: 41
+// Correspond to opcode TLOAD (EIP-1153), sampled with up to 1000 transient
+// slots, see `bench_cancun.js`.
+const MODEL_0X5C: u64 = 374;
+
+// Average: 598; Standard deviation: 44
+// Correspond to opcode TSTORE (EIP-1153), see `MODEL_0X5C`.
+const MODEL_0X5D: u64 = 686;
+
+// Average: 6457; Standard deviation: 4624
+// Correspond to opcode MCOPY (EIP-5656), sampled with copies from 32 bytes to
+// 16KB. The fixed cost dominates small copies.
+const MODEL_0X5E: u64 = 15705;
+
 // No data
 const MODEL_0X5F: u64 = PUSH_DEFAULT;
 
@@ -460,6 +475,20 @@ const MODEL_0XFE: u64 = DEFAULT_TICKS_PER_GAS;
 // Average: 101; Standard deviation: 0
 const MODEL_0XFF: u64 = 101;
 
+// Ticks needed by the kernel to update the state trie for an account modified
+// by a transaction, see `bench_state_trie.js`. They grow with the depth of the
+// trie: 5.7M with 100 accounts, 8.6M with 1k, 11.4M with 10k and 14.4M with
+// 100k, about 2.9M more for ten times more accounts. The value covers a state
+// of 10M accounts.
+pub const TICKS_PER_TOUCHED_ACCOUNT: u64 = 20_000_000;
+
+// Ticks needed by the kernel to update the storage trie of a contract for a
+// slot written by a transaction, see `TICKS_PER_TOUCHED_ACCOUNT`. They are the
+// same as for an account with as many slots: 5.7M with 100 slots, 8.6M with
+// 1k, 11.4M with 10k and 14.4M with 100k. The value covers a storage of 10M
+// slots.
+pub const TICKS_PER_TOUCHED_SLOT: u64 = 20_000_000;
+
 pub fn ticks(opcode: &Opcode, gas: u64) -> u64 {
     match opcode.as_u8() {
         0x0 => MODEL_0X00, // constant, no gas accounted
@@ -526,6 +555,9 @@ pub fn ticks(opcode: &Opcode, gas: u64) -> u64 {
         0x59 => MODEL_0X59 * gas,
         0x5a => MODEL_0X5A * gas,
         0x5b => MODEL_0X5B * gas,
+        0x5c => MODEL_0X5C * gas,
+        0x5d => MODEL_0X5D * gas,
+        0x5e => MODEL_0X5E * gas,
         0x5f => MODEL_0X5F * gas,
         0x60 => MODEL_0X60 * gas,
         0x61 => MODEL_0X61 * gas,
@@ -609,3 +641,13 @@ pub fn ticks(opcode: &Opcode, gas: u64) -> u64 {
         _ => DEFAULT_TICKS_PER_GAS * gas,
     }
 }
+
+/// Ticks needed by the kernel to update the state trie for the accounts and
+/// storage slots modified by a transaction.
+pub fn ticks_of_touched_accounts(touched: &TouchedAccounts) -> u64 {
+    touched.values().fold(0u64, |ticks, slots| {
+        ticks
+            .saturating_add(TICKS_PER_TOUCHED_ACCOUNT)
+            .saturating_add(TICKS_PER_TOUCHED_SLOT.saturating_mul(slots.len() as u64))
+    })
+}
@@ -0,0 +1,576 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Execution traces, as returned by `debug_traceTransaction`
+//!
+//! Two tracers are supported, following the ones of go-ethereum:
+//! - the struct logger, which records the state of the machine before each
+//!   opcode is executed,
+//! - the call tracer, which records the tree of the calls and contract
+//!   creations.
+//!
+//! A [Tracer] is given to the [crate::handler::EvmHandler], which feeds it
+//! during the execution, and produces a [Trace] at the end.
+
+use crate::handler::ExecutionOutcome;
+use alloc::collections::BTreeMap;
+use evm::{ExitReason, Opcode};
+use primitive_types::{H160, H256, U256};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use tezos_ethereum::rlp_helpers::{
+    append_option, append_option_explicit, append_u256_le, append_u64_le, decode_field,
+    next,
+};
+
+const STRUCT_LOGGER_TAG: u8 = 0;
+const CALL_TRACER_TAG: u8 = 1;
+
+/// Maximal size of a trace, estimated from the size of its encoding. Once it
+/// is reached, the tracer stops recording and the trace is truncated.
+pub const MAX_TRACE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Options of the struct logger. By default, the memory is not recorded, but
+/// the stack and the storage are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StructLoggerConfig {
+    pub enable_memory: bool,
+    pub disable_stack: bool,
+    pub disable_storage: bool,
+}
+
+/// The tracer to use, and its options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TracerConfig {
+    StructLogger(StructLoggerConfig),
+    CallTracer,
+}
+
+impl Encodable for TracerConfig {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        match self {
+            TracerConfig::StructLogger(config) => {
+                stream.begin_list(4);
+                stream.append(&STRUCT_LOGGER_TAG);
+                stream.append(&config.enable_memory);
+                stream.append(&config.disable_stack);
+                stream.append(&config.disable_storage);
+            }
+            TracerConfig::CallTracer => {
+                stream.begin_list(1);
+                stream.append(&CALL_TRACER_TAG);
+            }
+        }
+    }
+}
+
+impl Decodable for TracerConfig {
+    fn decode(decoder: &Rlp) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        let mut it = decoder.iter();
+        let tag: u8 = decode_field(&next(&mut it)?, "tag")?;
+        match (tag, decoder.item_count()?) {
+            (STRUCT_LOGGER_TAG, 4) => {
+                let enable_memory = decode_field(&next(&mut it)?, "enable_memory")?;
+                let disable_stack = decode_field(&next(&mut it)?, "disable_stack")?;
+                let disable_storage = decode_field(&next(&mut it)?, "disable_storage")?;
+                Ok(TracerConfig::StructLogger(StructLoggerConfig {
+                    enable_memory,
+                    disable_stack,
+                    disable_storage,
+                }))
+            }
+            (CALL_TRACER_TAG, 1) => Ok(TracerConfig::CallTracer),
+            (STRUCT_LOGGER_TAG | CALL_TRACER_TAG, _) => {
+                Err(DecoderError::RlpIncorrectListLen)
+            }
+            _ => Err(DecoderError::Custom("Unknown tracer")),
+        }
+    }
+}
+
+/// State of the machine before the execution of an opcode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructLog {
+    pub pc: u64,
+    pub op: u8,
+    /// Gas remaining before the execution of the opcode
+    pub gas: u64,
+    pub gas_cost: u64,
+    /// Depth of the call, starting at 1
+    pub depth: u64,
+    pub error: Option<String>,
+    pub stack: Option<Vec<H256>>,
+    pub memory: Option<Vec<u8>>,
+    /// Storage slots of the contract accessed so far, only given for
+    /// `SLOAD` and `SSTORE`
+    pub storage: Option<Vec<(H256, H256)>>,
+}
+
+impl StructLog {
+    fn estimated_size(&self) -> usize {
+        // The integers and the list headers take at most 64 bytes.
+        64 + self.error.as_ref().map_or(0, String::len)
+            + self.stack.as_ref().map_or(0, |stack| 33 * stack.len())
+            + self.memory.as_ref().map_or(0, Vec::len)
+            + self
+                .storage
+                .as_ref()
+                .map_or(0, |storage| 68 * storage.len())
+    }
+}
+
+impl Encodable for StructLog {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(9);
+        append_u64_le(stream, &self.pc);
+        stream.append(&self.op);
+        append_u64_le(stream, &self.gas);
+        append_u64_le(stream, &self.gas_cost);
+        append_u64_le(stream, &self.depth);
+        append_option(stream, &self.error);
+        append_option_explicit(stream, &self.stack, |s, stack| s.append_list(stack));
+        append_option(stream, &self.memory);
+        append_option_explicit(stream, &self.storage, |s, storage| {
+            s.begin_list(storage.len());
+            for (index, value) in storage {
+                s.begin_list(2).append(index).append(value);
+            }
+            s
+        });
+    }
+}
+
+/// The kind of a call frame, given by the opcode that started it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallType {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+    Create,
+    Create2,
+}
+
+impl CallType {
+    /// The type of the call started by `opcode`, if it starts one.
+    fn of_opcode(opcode: Opcode) -> Option<Self> {
+        match opcode {
+            Opcode::CALL => Some(CallType::Call),
+            Opcode::CALLCODE => Some(CallType::CallCode),
+            Opcode::DELEGATECALL => Some(CallType::DelegateCall),
+            Opcode::STATICCALL => Some(CallType::StaticCall),
+            Opcode::CREATE => Some(CallType::Create),
+            Opcode::CREATE2 => Some(CallType::Create2),
+            _ => None,
+        }
+    }
+}
+
+impl Encodable for CallType {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        let tag: u8 = match self {
+            CallType::Call => 0,
+            CallType::CallCode => 1,
+            CallType::DelegateCall => 2,
+            CallType::StaticCall => 3,
+            CallType::Create => 4,
+            CallType::Create2 => 5,
+        };
+        stream.append(&tag);
+    }
+}
+
+/// A call or a contract creation, with the calls it made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallFrame {
+    pub call_type: CallType,
+    pub from: H160,
+    /// The called contract, or the created one if the creation succeeded
+    pub to: Option<H160>,
+    pub value: Option<U256>,
+    pub gas: u64,
+    pub gas_used: u64,
+    pub input: Vec<u8>,
+    pub output: Vec<u8>,
+    pub error: Option<String>,
+    pub calls: Vec<CallFrame>,
+}
+
+impl CallFrame {
+    /// Size of the frame, without the calls it made.
+    fn estimated_size(&self) -> usize {
+        // The addresses, the integers and the list headers take at most 128
+        // bytes.
+        128 + self.input.len()
+            + self.output.len()
+            + self.error.as_ref().map_or(0, String::len)
+    }
+}
+
+impl Encodable for CallFrame {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(10);
+        stream.append(&self.call_type);
+        stream.append(&self.from);
+        append_option(stream, &self.to);
+        append_option_explicit(stream, &self.value, append_u256_le);
+        append_u64_le(stream, &self.gas);
+        append_u64_le(stream, &self.gas_used);
+        stream.append(&self.input);
+        stream.append(&self.output);
+        append_option(stream, &self.error);
+        stream.append_list(&self.calls);
+    }
+}
+
+/// The trace of a transaction. It is `truncated` if it reached
+/// [MAX_TRACE_SIZE], the steps and the calls which came after are missing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trace {
+    StructLogs {
+        gas_used: u64,
+        failed: bool,
+        return_value: Vec<u8>,
+        struct_logs: Vec<StructLog>,
+        truncated: bool,
+    },
+    Call {
+        root: CallFrame,
+        truncated: bool,
+    },
+}
+
+impl Encodable for Trace {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(3);
+        match self {
+            Trace::StructLogs {
+                gas_used,
+                failed,
+                return_value,
+                struct_logs,
+                truncated,
+            } => {
+                stream.append(&STRUCT_LOGGER_TAG);
+                stream.begin_list(4);
+                append_u64_le(stream, gas_used);
+                stream.append(failed);
+                stream.append(return_value);
+                stream.append_list(struct_logs);
+                stream.append(truncated);
+            }
+            Trace::Call { root, truncated } => {
+                stream.append(&CALL_TRACER_TAG);
+                stream.append(root);
+                stream.append(truncated);
+            }
+        }
+    }
+}
+
+/// Error message of a frame or an opcode that didn't succeed.
+pub(crate) fn exit_reason_error(reason: &ExitReason) -> Option<String> {
+    match reason {
+        ExitReason::Succeed(_) => None,
+        ExitReason::Revert(_) => Some("execution reverted".to_string()),
+        ExitReason::Error(err) => Some(format!("{:?}", err)),
+        ExitReason::Fatal(err) => Some(format!("{:?}", err)),
+    }
+}
+
+/// Machine state captured before the execution of an opcode, completed
+/// once it is executed.
+pub(crate) struct Step {
+    pub pc: u64,
+    pub opcode: Opcode,
+    pub gas: u64,
+    pub depth: u64,
+    pub address: H160,
+    pub stack: Vec<H256>,
+    pub memory: Vec<u8>,
+}
+
+/// Records the trace of an execution.
+pub struct Tracer {
+    config: TracerConfig,
+    struct_logs: Vec<StructLog>,
+    /// Storage slots accessed so far, by contract
+    storage: BTreeMap<H160, BTreeMap<H256, H256>>,
+    /// The call frames in progress, the innermost being the last one
+    frames: Vec<CallFrame>,
+    /// The first call frame, once it is completed
+    root: Option<CallFrame>,
+    /// The opcode being executed, which gives the type of the calls it makes
+    current_opcode: Option<Opcode>,
+    /// Estimated size of the trace recorded so far
+    size: usize,
+    /// Whether the trace reached [MAX_TRACE_SIZE]
+    truncated: bool,
+}
+
+impl Tracer {
+    pub fn new(config: TracerConfig) -> Self {
+        Self {
+            config,
+            struct_logs: vec![],
+            storage: BTreeMap::new(),
+            frames: vec![],
+            root: None,
+            current_opcode: None,
+            size: 0,
+            truncated: false,
+        }
+    }
+
+    /// The options of the struct logger, if the state of the machine must be
+    /// captured at each step.
+    pub(crate) fn struct_logger_config(&self) -> Option<StructLoggerConfig> {
+        match self.config {
+            TracerConfig::StructLogger(config) if !self.truncated => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Account for `size` more bytes in the trace, or mark it as truncated
+    /// if it doesn't fit.
+    fn record(&mut self, size: usize) -> bool {
+        match self.size.checked_add(size) {
+            Some(size) if size <= MAX_TRACE_SIZE && !self.truncated => {
+                self.size = size;
+                true
+            }
+            _ => {
+                self.truncated = true;
+                false
+            }
+        }
+    }
+
+    pub(crate) fn start_opcode(&mut self, opcode: Opcode) {
+        self.current_opcode = Some(opcode);
+    }
+
+    /// Record a step of the struct logger. `result` is the top of the stack
+    /// after the execution, needed to know the value read by `SLOAD`.
+    pub(crate) fn end_step(
+        &mut self,
+        step: Step,
+        gas_cost: u64,
+        result: Option<H256>,
+        error: Option<String>,
+    ) {
+        let Some(config) = self.struct_logger_config() else {
+            return;
+        };
+        let top = |n: usize| step.stack.len().checked_sub(n + 1).map(|i| step.stack[i]);
+        let access = match step.opcode {
+            Opcode::SLOAD => top(0).zip(result),
+            Opcode::SSTORE => top(0).zip(top(1)),
+            _ => None,
+        };
+        let storage = access.and_then(|(index, value)| {
+            let storage = self.storage.entry(step.address).or_default();
+            storage.insert(index, value);
+            (!config.disable_storage)
+                .then(|| storage.iter().map(|(i, v)| (*i, *v)).collect())
+        });
+        let log = StructLog {
+            pc: step.pc,
+            op: step.opcode.as_u8(),
+            gas: step.gas,
+            gas_cost,
+            depth: step.depth,
+            error,
+            stack: (!config.disable_stack).then_some(step.stack),
+            memory: config.enable_memory.then_some(step.memory),
+            storage,
+        };
+        if self.record(log.estimated_size()) {
+            self.struct_logs.push(log)
+        }
+    }
+
+    /// The type of a call frame starting now, given by the opcode being
+    /// executed, or by `is_create` for the first frame.
+    pub(crate) fn call_type(&self, is_create: bool) -> CallType {
+        match self.current_opcode.and_then(CallType::of_opcode) {
+            Some(call_type) if !self.frames.is_empty() => call_type,
+            _ if is_create => CallType::Create,
+            _ => CallType::Call,
+        }
+    }
+
+    pub(crate) fn begin_frame(
+        &mut self,
+        call_type: CallType,
+        from: H160,
+        to: Option<H160>,
+        value: Option<U256>,
+        gas: u64,
+        input: Vec<u8>,
+    ) {
+        self.frames.push(CallFrame {
+            call_type,
+            from,
+            to,
+            value,
+            gas,
+            gas_used: 0,
+            input,
+            output: vec![],
+            error: None,
+            calls: vec![],
+        })
+    }
+
+    /// End the innermost call frame. The created contract is given in `to`,
+    /// if any.
+    pub(crate) fn end_frame(
+        &mut self,
+        to: Option<H160>,
+        gas_used: u64,
+        output: Vec<u8>,
+        error: Option<String>,
+    ) {
+        let Some(mut frame) = self.frames.pop() else {
+            return;
+        };
+        if to.is_some() {
+            frame.to = to;
+        }
+        frame.gas_used = gas_used;
+        frame.output = output;
+        frame.error = error;
+        // The first frame is always kept, the inner ones once the trace is
+        // full are dropped.
+        let fits = self.record(frame.estimated_size());
+        match self.frames.last_mut() {
+            Some(parent) if fits => parent.calls.push(frame),
+            Some(_) => (),
+            None => self.root = Some(frame),
+        }
+    }
+
+    /// The trace of the execution that ended with `outcome`.
+    pub fn into_trace(self, outcome: Option<&ExecutionOutcome>) -> Trace {
+        match self.config {
+            TracerConfig::StructLogger(_) => Trace::StructLogs {
+                gas_used: outcome.map(|o| o.gas_used).unwrap_or_default(),
+                failed: !outcome.map(|o| o.is_success).unwrap_or_default(),
+                return_value: outcome.and_then(|o| o.result.clone()).unwrap_or_default(),
+                struct_logs: self.struct_logs,
+                truncated: self.truncated,
+            },
+            TracerConfig::CallTracer => Trace::Call {
+                root: self.root.unwrap_or_else(|| CallFrame {
+                    call_type: CallType::Call,
+                    from: H160::zero(),
+                    to: None,
+                    value: None,
+                    gas: 0,
+                    gas_used: 0,
+                    input: vec![],
+                    output: vec![],
+                    error: Some("No call was traced".to_string()),
+                    calls: vec![],
+                }),
+                truncated: self.truncated,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracer_config_encoding_roundtrip() {
+        let configs = [
+            TracerConfig::CallTracer,
+            TracerConfig::StructLogger(StructLoggerConfig::default()),
+            TracerConfig::StructLogger(StructLoggerConfig {
+                enable_memory: true,
+                disable_stack: true,
+                disable_storage: false,
+            }),
+        ];
+        for config in configs {
+            let bytes = config.rlp_bytes();
+            let decoded = TracerConfig::decode(&Rlp::new(&bytes)).unwrap();
+            assert_eq!(decoded, config);
+        }
+    }
+
+    #[test]
+    fn struct_logs_are_truncated() {
+        let config = StructLoggerConfig {
+            enable_memory: true,
+            ..StructLoggerConfig::default()
+        };
+        let mut tracer = Tracer::new(TracerConfig::StructLogger(config));
+        let step = || Step {
+            pc: 0,
+            opcode: Opcode::MLOAD,
+            gas: 0,
+            depth: 1,
+            address: H160::zero(),
+            stack: vec![H256::zero()],
+            memory: vec![0; 1024 * 1024],
+        };
+        for _ in 0..20 {
+            tracer.end_step(step(), 3, None, None);
+        }
+        assert!(tracer.struct_logger_config().is_none());
+
+        let Trace::StructLogs {
+            struct_logs,
+            truncated,
+            ..
+        } = tracer.into_trace(None)
+        else {
+            panic!("Expected struct logs")
+        };
+        assert!(truncated);
+        assert_eq!(struct_logs.len(), 15);
+    }
+
+    #[test]
+    fn nested_frames() {
+        let mut tracer = Tracer::new(TracerConfig::CallTracer);
+        let (a, b, c) = (
+            H160::from_low_u64_be(1),
+            H160::from_low_u64_be(2),
+            H160::from_low_u64_be(3),
+        );
+        let call_type = tracer.call_type(false);
+        tracer.begin_frame(call_type, a, Some(b), Some(U256::one()), 100, vec![1]);
+        tracer.start_opcode(Opcode::STATICCALL);
+        let call_type = tracer.call_type(false);
+        tracer.begin_frame(call_type, b, Some(c), None, 50, vec![2]);
+        tracer.end_frame(None, 10, vec![3], None);
+        tracer.start_opcode(Opcode::CREATE2);
+        let call_type = tracer.call_type(true);
+        tracer.begin_frame(call_type, b, None, Some(U256::zero()), 30, vec![4]);
+        tracer.end_frame(Some(c), 20, vec![5], None);
+        tracer.end_frame(None, 60, vec![], Some("execution reverted".to_string()));
+
+        let Trace::Call { root, .. } = tracer.into_trace(None) else {
+            panic!("Expected a call trace")
+        };
+        assert_eq!(root.call_type, CallType::Call);
+        assert_eq!(root.error.as_deref(), Some("execution reverted"));
+        assert_eq!(root.gas_used, 60);
+        let calls: Vec<_> = root.calls.iter().map(|c| (c.call_type, c.to)).collect();
+        assert_eq!(
+            calls,
+            vec![
+                (CallType::StaticCall, Some(c)),
+                (CallType::Create2, Some(c))
+            ]
+        );
+    }
+}
@@ -0,0 +1,578 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! World state of a durable storage snapshot, in the format of geth `dump`.
+//!
+//! A snapshot is the installer configuration produced by
+//! `octez-smart-rollup-node dump durable storage`. It is written in a mock
+//! host, where the accounts are read with the account storage API of the
+//! kernel. The addresses and storage indexes are found in the paths of the
+//! snapshot, as the durable storage can't be enumerated.
+//!
+//! The storage roots, code hashes and state root are computed from the
+//! dumped values, as for a genesis (see [tezos_ethereum::genesis]). The state
+//! root last computed by the kernel is given alongside, so that the two can
+//! be compared.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use evm_execution::account_storage::{
+    account_path, AccountStorageError, EthereumAccount, EVM_ACCOUNTS_PATH,
+};
+use evm_execution::DurableStorageError;
+use primitive_types::{H160, H256, U256};
+use serde::{Deserialize, Serialize};
+use tezos_ethereum::genesis::{state_root, GenesisAccount};
+use tezos_smart_rollup_host::path::{concat, PathError, RefPath};
+use tezos_smart_rollup_host::runtime::{Runtime, RuntimeError};
+use tezos_smart_rollup_installer_config::binary::owned::OwnedConfigProgram;
+use tezos_smart_rollup_installer_config::yaml::{
+    ConfigConversionError, Instr, YamlConfig,
+};
+use tezos_smart_rollup_mock::MockHost;
+use thiserror::Error;
+
+/// Root of the durable storage of the kernel.
+const EVM_PATH: RefPath = RefPath::assert_from(b"/evm");
+
+/// Prefix of the paths of the accounts in the snapshot.
+const ACCOUNTS_PREFIX: &str = "/evm/eth_accounts/";
+
+// Paths of `storage.rs` and `world_state.rs` in the kernel.
+const CURRENT_BLOCK_NUMBER_PATH: RefPath =
+    RefPath::assert_from(b"/evm/blocks/current/number");
+const CURRENT_BLOCK_HASH_PATH: RefPath =
+    RefPath::assert_from(b"/evm/blocks/current/hash");
+const WORLD_STATE_ROOT_PATH: RefPath = RefPath::assert_from(b"/evm/world_state/root");
+
+#[derive(Debug, Error)]
+pub enum DumpError {
+    #[error("Invalid snapshot: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("Invalid snapshot: {0}")]
+    Conversion(#[from] ConfigConversionError),
+    #[error("Cannot write the snapshot: {0}")]
+    Evaluation(&'static str),
+    #[error("Invalid snapshot: {0}")]
+    InvalidSnapshot(&'static str),
+    #[error("Invalid {field} of account {address:?}")]
+    InvalidValue { field: &'static str, address: H160 },
+    #[error(transparent)]
+    AccountStorage(#[from] AccountStorageError),
+    #[error(transparent)]
+    DurableStorage(#[from] DurableStorageError),
+    #[error(transparent)]
+    Path(#[from] PathError),
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+}
+
+/// An account of the dump. Storage values are the hexadecimal big-endian
+/// encoding of the value without its leading zeros, as in geth. The storage
+/// root and code hash are computed from the storage and code.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DumpAccount {
+    pub balance: String,
+    pub nonce: u64,
+    pub root: String,
+    pub code_hash: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub code: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub storage: BTreeMap<String, String>,
+    pub address: String,
+}
+
+/// The world state at the current block, with its root computed from the
+/// accounts. The state root of the kernel, and the number and hash of the
+/// block are not part of the geth format. The block is absent before the
+/// first one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dump {
+    pub root: String,
+    pub kernel_root: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    pub accounts: BTreeMap<String, DumpAccount>,
+}
+
+fn hex_h256(value: &H256) -> String {
+    format!("0x{}", hex::encode(value))
+}
+
+fn hex_h160(value: &H160) -> String {
+    format!("0x{}", hex::encode(value))
+}
+
+/// A durable storage snapshot, with the accounts and storage indexes found in
+/// its paths.
+pub struct Snapshot {
+    host: MockHost,
+    accounts: BTreeMap<H160, BTreeSet<H256>>,
+}
+
+/// The address, and storage index if any, of a path under the accounts.
+fn parse_account_path(path: &str) -> Option<(H160, Option<H256>)> {
+    let mut segments = path.strip_prefix(ACCOUNTS_PREFIX)?.split('/');
+    let address = hex::decode(segments.next()?).ok()?;
+    if address.len() != 20 {
+        return None;
+    }
+    let index = match (segments.next(), segments.next()) {
+        (Some("storage"), Some(index)) => {
+            let index = hex::decode(index).ok()?;
+            (index.len() == 32).then(|| H256::from_slice(&index))
+        }
+        _ => None,
+    };
+    Some((H160::from_slice(&address), index))
+}
+
+impl Snapshot {
+    pub fn from_yaml(yaml: &str) -> Result<Self, DumpError> {
+        Self::from_config(YamlConfig::from_string(yaml)?)
+    }
+
+    pub fn from_config(config: YamlConfig) -> Result<Self, DumpError> {
+        let mut accounts: BTreeMap<H160, BTreeSet<H256>> = BTreeMap::new();
+        for instruction in &config.instructions {
+            if let Instr::Set(set) = instruction {
+                if let Some((address, index)) = parse_account_path(&set.to) {
+                    let indexes = accounts.entry(address).or_default();
+                    indexes.extend(index);
+                }
+            }
+        }
+        let program = OwnedConfigProgram::try_from(config)?;
+        let mut host = MockHost::default();
+        program.evaluate(&mut host).map_err(DumpError::Evaluation)?;
+        Ok(Self { host, accounts })
+    }
+
+    fn read_optional(&self, path: &RefPath) -> Result<Option<Vec<u8>>, DumpError> {
+        match self.host.store_has(path)? {
+            Some(_) => Ok(Some(self.host.store_read_all(path)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn read_account(&self, address: &H160) -> Result<GenesisAccount, DumpError> {
+        let accounts_path = concat(&EVM_PATH, &EVM_ACCOUNTS_PATH)?;
+        let account =
+            EthereumAccount::from(concat(&accounts_path, &account_path(address)?)?);
+        let mut storage = BTreeMap::new();
+        for index in &self.accounts[address] {
+            let value = account.get_storage(&self.host, index)?;
+            if !value.is_zero() {
+                storage.insert(*index, value);
+            }
+        }
+        Ok(GenesisAccount {
+            address: *address,
+            nonce: account.nonce(&self.host)?,
+            balance: account.balance(&self.host)?,
+            code: account.code(&self.host)?,
+            storage,
+        })
+    }
+
+    fn dump_account(account: &GenesisAccount) -> Result<DumpAccount, DumpError> {
+        let storage = account
+            .storage
+            .iter()
+            .map(|(index, value)| {
+                let bytes = value.as_bytes();
+                let leading_zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+                (hex_h256(index), hex::encode(&bytes[leading_zeros..]))
+            })
+            .collect();
+        Ok(DumpAccount {
+            balance: account.balance.to_string(),
+            nonce: u64::try_from(account.nonce).map_err(|_| DumpError::InvalidValue {
+                field: "nonce",
+                address: account.address,
+            })?,
+            root: hex_h256(&account.storage_root()),
+            code_hash: hex_h256(&account.code_hash()),
+            code: if account.code.is_empty() {
+                String::new()
+            } else {
+                format!("0x{}", hex::encode(&account.code))
+            },
+            storage,
+            address: hex_h160(&account.address),
+        })
+    }
+
+    /// Root of the state trie last computed by the kernel, which the snapshot
+    /// must contain.
+    fn kernel_root(&self) -> Result<H256, DumpError> {
+        match self.read_optional(&WORLD_STATE_ROOT_PATH)? {
+            Some(root) if root.len() == 32 => Ok(H256::from_slice(&root)),
+            Some(_) => Err(DumpError::InvalidSnapshot("the state root isn't 32 bytes")),
+            None => Err(DumpError::InvalidSnapshot("the state root is missing")),
+        }
+    }
+
+    /// Dump of the accounts, with the state roots and the current block.
+    pub fn dump(&self) -> Result<Dump, DumpError> {
+        let kernel_root = self.kernel_root()?;
+        let number = self
+            .read_optional(&CURRENT_BLOCK_NUMBER_PATH)?
+            .map(|number| {
+                if number.len() > 32 {
+                    return Err(DumpError::InvalidSnapshot(
+                        "the current block number isn't a 256 bits integer",
+                    ));
+                }
+                u64::try_from(U256::from_little_endian(&number)).map_err(|_| {
+                    DumpError::InvalidSnapshot(
+                        "the current block number doesn't fit in 64 bits",
+                    )
+                })
+            })
+            .transpose()?;
+        let hash = self
+            .read_optional(&CURRENT_BLOCK_HASH_PATH)?
+            .map(|hash| match hash.len() {
+                32 => Ok(hex_h256(&H256::from_slice(&hash))),
+                _ => Err(DumpError::InvalidSnapshot(
+                    "the current block hash isn't 32 bytes",
+                )),
+            })
+            .transpose()?;
+        let mut state = vec![];
+        let mut accounts = BTreeMap::new();
+        for address in self.accounts.keys() {
+            let account = self.read_account(address)?;
+            // Empty accounts are not part of the state (see EIP-161).
+            if account.is_empty() && account.storage.is_empty() {
+                continue;
+            }
+            accounts.insert(hex_h160(address), Self::dump_account(&account)?);
+            state.push(account);
+        }
+        Ok(Dump {
+            root: hex_h256(&state_root(&state)),
+            kernel_root: hex_h256(&kernel_root),
+            number,
+            hash,
+            accounts,
+        })
+    }
+}
+
+/// A value in the first dump and in the second one, `null` if absent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Change<T> {
+    pub before: Option<T>,
+    pub after: Option<T>,
+}
+
+fn change<T: PartialEq + Clone>(
+    before: Option<&T>,
+    after: Option<&T>,
+) -> Option<Change<T>> {
+    (before != after).then(|| Change {
+        before: before.cloned(),
+        after: after.cloned(),
+    })
+}
+
+/// The fields of an account which differ, an account created or deleted
+/// between the dumps has all its fields.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountDiff {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance: Option<Change<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<Change<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root: Option<Change<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_hash: Option<Change<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<Change<String>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub storage: BTreeMap<String, Change<String>>,
+}
+
+impl AccountDiff {
+    fn new(before: Option<&DumpAccount>, after: Option<&DumpAccount>) -> Self {
+        let code = |account: &DumpAccount| {
+            Some(account.code.clone()).filter(|code| !code.is_empty())
+        };
+        let mut storage = BTreeMap::new();
+        let empty = BTreeMap::new();
+        let before_storage = before.map_or(&empty, |account| &account.storage);
+        let after_storage = after.map_or(&empty, |account| &account.storage);
+        for index in before_storage.keys().chain(after_storage.keys()) {
+            if let Some(change) =
+                change(before_storage.get(index), after_storage.get(index))
+            {
+                storage.insert(index.clone(), change);
+            }
+        }
+        Self {
+            balance: change(before.map(|a| &a.balance), after.map(|a| &a.balance)),
+            nonce: change(before.map(|a| &a.nonce), after.map(|a| &a.nonce)),
+            root: change(before.map(|a| &a.root), after.map(|a| &a.root)),
+            code_hash: change(before.map(|a| &a.code_hash), after.map(|a| &a.code_hash)),
+            code: change(
+                before.and_then(code).as_ref(),
+                after.and_then(code).as_ref(),
+            ),
+            storage,
+        }
+    }
+}
+
+/// Differences between two dumps, only the accounts which differ are listed.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DumpDiff {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root: Option<Change<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kernel_root: Option<Change<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number: Option<Change<u64>>,
+    pub accounts: BTreeMap<String, AccountDiff>,
+}
+
+pub fn diff(before: &Dump, after: &Dump) -> DumpDiff {
+    let mut accounts = BTreeMap::new();
+    for address in before.accounts.keys().chain(after.accounts.keys()) {
+        let account_diff =
+            AccountDiff::new(before.accounts.get(address), after.accounts.get(address));
+        if account_diff != AccountDiff::default() {
+            accounts.insert(address.clone(), account_diff);
+        }
+    }
+    DumpDiff {
+        root: change(Some(&before.root), Some(&after.root)),
+        kernel_root: change(Some(&before.kernel_root), Some(&after.kernel_root)),
+        number: change(before.number.as_ref(), after.number.as_ref()),
+        accounts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evm_execution::account_storage::EMPTY_TRIE_ROOT;
+    use tezos_smart_rollup_installer_config::yaml::SetArgs;
+
+    fn set(to: &str, value: &[u8]) -> Instr {
+        Instr::Set(SetArgs {
+            value: hex::encode(value),
+            to: to.to_owned(),
+        })
+    }
+
+    fn u256_le(value: u64) -> Vec<u8> {
+        let mut bytes = [0u8; 32];
+        U256::from(value).to_little_endian(&mut bytes);
+        bytes.to_vec()
+    }
+
+    const USER: &str = "6ce4d79d4e77402e1ef3417fdda433aa744c6e1c";
+    const CONTRACT: &str = "b53dc01974176e5dff2298c5a94343c2585e3c54";
+
+    const KERNEL_ROOT: [u8; 32] = [7; 32];
+
+    fn snapshot_with(balance: u64, slot: u64, extra: Vec<Instr>) -> Snapshot {
+        let index = H256::from_low_u64_be(1);
+        let mut code_host = MockHost::default();
+        let mut contract = EthereumAccount::from_address(&H160::from_slice(
+            &hex::decode(CONTRACT).unwrap(),
+        ))
+        .unwrap();
+        contract.set_code(&mut code_host, &[0x60, 0x00]).unwrap();
+        let code_hash = contract.code_hash(&code_host).unwrap();
+        let account = |path: &str| format!("{}{}", ACCOUNTS_PREFIX, path);
+        let instructions = vec![
+            set("/evm/blocks/current/number", &u256_le(3)),
+            set(&account(&format!("{}/balance", USER)), &u256_le(balance)),
+            set(&account(&format!("{}/code", CONTRACT)), &[0x60, 0x00]),
+            set(
+                &account(&format!("{}/code.hash", CONTRACT)),
+                code_hash.as_bytes(),
+            ),
+            set(
+                &account(&format!("{}/storage/{}", CONTRACT, hex::encode(index))),
+                H256::from_low_u64_be(slot).as_bytes(),
+            ),
+            // An account with only zero values is empty.
+            set(&account(&format!("{}/nonce", "00".repeat(20))), &u256_le(0)),
+            set("/evm/chain_id", &u256_le(1337)),
+        ];
+        let instructions = instructions.into_iter().chain(extra).collect();
+        Snapshot::from_config(YamlConfig { instructions }).unwrap()
+    }
+
+    fn snapshot(balance: u64, slot: u64) -> Snapshot {
+        snapshot_with(
+            balance,
+            slot,
+            vec![set("/evm/world_state/root", &KERNEL_ROOT)],
+        )
+    }
+
+    #[test]
+    fn test_dump() {
+        let dump = snapshot(1000, 0x2a01).dump().unwrap();
+        assert_eq!(dump.kernel_root, hex_h256(&H256(KERNEL_ROOT)));
+        assert_eq!(dump.number, Some(3));
+        assert_eq!(dump.hash, None);
+        assert_eq!(dump.accounts.len(), 2);
+
+        let user = &dump.accounts[&format!("0x{}", USER)];
+        assert_eq!(user.balance, "1000");
+        assert_eq!(user.nonce, 0);
+        assert_eq!(user.code, "");
+        assert!(user.storage.is_empty());
+
+        let contract = &dump.accounts[&format!("0x{}", CONTRACT)];
+        assert_eq!(contract.balance, "0");
+        assert_eq!(contract.code, "0x6000");
+        assert_eq!(
+            contract.storage,
+            BTreeMap::from([(hex_h256(&H256::from_low_u64_be(1)), "2a01".to_owned())])
+        );
+
+        // The roots are computed from the dumped values.
+        let accounts = [
+            GenesisAccount {
+                address: H160::from_slice(&hex::decode(USER).unwrap()),
+                balance: U256::from(1000),
+                ..GenesisAccount::default()
+            },
+            GenesisAccount {
+                address: H160::from_slice(&hex::decode(CONTRACT).unwrap()),
+                code: vec![0x60, 0x00],
+                storage: BTreeMap::from([(
+                    H256::from_low_u64_be(1),
+                    H256::from_low_u64_be(0x2a01),
+                )]),
+                ..GenesisAccount::default()
+            },
+        ];
+        assert_eq!(dump.root, hex_h256(&state_root(&accounts)));
+        assert_eq!(contract.root, hex_h256(&accounts[1].storage_root()));
+        assert_eq!(contract.code_hash, hex_h256(&accounts[1].code_hash()));
+        assert_eq!(user.root, hex_h256(&EMPTY_TRIE_ROOT));
+
+        // The output follows the field names of geth.
+        let json = serde_json::to_value(&dump).unwrap();
+        assert!(json["kernelRoot"].is_string());
+        let contract = &json["accounts"][format!("0x{}", CONTRACT)];
+        assert!(contract["codeHash"].is_string());
+        assert_eq!(contract["address"], format!("0x{}", CONTRACT));
+        assert!(json["accounts"][format!("0x{}", USER)]
+            .get("storage")
+            .is_none());
+        let parsed: Dump = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, dump);
+    }
+
+    #[test]
+    fn test_diff() {
+        let before = snapshot(1000, 1).dump().unwrap();
+        let after = snapshot(900, 0).dump().unwrap();
+        assert_eq!(diff(&before, &before), DumpDiff::default());
+
+        let changes = diff(&before, &after);
+        assert!(changes.root.is_some());
+        assert_eq!(changes.kernel_root, None);
+        assert_eq!(changes.accounts.len(), 2);
+        assert_eq!(
+            changes.accounts[&format!("0x{}", USER)],
+            AccountDiff {
+                balance: Some(Change {
+                    before: Some("1000".to_owned()),
+                    after: Some("900".to_owned()),
+                }),
+                ..AccountDiff::default()
+            }
+        );
+        // The slot is cleared.
+        let contract = &changes.accounts[&format!("0x{}", CONTRACT)];
+        assert_eq!(contract.balance, None);
+        assert_eq!(
+            contract.storage[&hex_h256(&H256::from_low_u64_be(1))],
+            Change {
+                before: Some("01".to_owned()),
+                after: None,
+            }
+        );
+
+        // A deleted account has all its fields.
+        let mut deleted = after.clone();
+        deleted.accounts.remove(&format!("0x{}", USER));
+        let changes = diff(&after, &deleted);
+        let user = &changes.accounts[&format!("0x{}", USER)];
+        assert_eq!(
+            user.nonce,
+            Some(Change {
+                before: Some(0),
+                after: None
+            })
+        );
+        assert_eq!(user.code, None);
+    }
+
+    #[test]
+    fn test_invalid_snapshots() {
+        let mut large_number = [0u8; 32];
+        large_number[8] = 1;
+        let invalid_values = [
+            // Without the state root of the kernel
+            vec![],
+            vec![set("/evm/world_state/root", &[7; 20])],
+            vec![
+                set("/evm/world_state/root", &KERNEL_ROOT),
+                set("/evm/blocks/current/number", &large_number),
+            ],
+            vec![
+                set("/evm/world_state/root", &KERNEL_ROOT),
+                set("/evm/blocks/current/hash", &[1; 20]),
+            ],
+        ];
+        for extra in invalid_values {
+            assert!(matches!(
+                snapshot_with(1000, 1, extra).dump(),
+                Err(DumpError::InvalidSnapshot(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_account_path() {
+        let address = H160::from_slice(&hex::decode(USER).unwrap());
+        assert_eq!(
+            parse_account_path(&format!("{}{}/balance", ACCOUNTS_PREFIX, USER)),
+            Some((address, None))
+        );
+        assert_eq!(
+            parse_account_path(&format!(
+                "{}{}/storage/{}",
+                ACCOUNTS_PREFIX,
+                USER,
+                "00".repeat(32)
+            )),
+            Some((address, Some(H256::zero())))
+        );
+        assert_eq!(parse_account_path("/evm/chain_id"), None);
+        assert_eq!(
+            parse_account_path(&format!("{}01/nonce", ACCOUNTS_PREFIX)),
+            None
+        );
+    }
+}
@@ -0,0 +1,392 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Installer configuration from a geth `genesis.json`.
+//!
+//! The accounts of `alloc` are written with the account storage API of the
+//! kernel in a mock host, and read back under the paths of the durable
+//! storage layout, which gives the `set` instructions of the installer. The
+//! configuration also declares the accounts and their state root, which the
+//! kernel checks in its first runs.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use evm_execution::account_storage::{
+    account_path, AccountStorageError, EthereumAccount, BALANCE_PATH, CODE_HASH_PATH,
+    CODE_PATH, EVM_ACCOUNTS_PATH, NONCE_PATH,
+};
+use evm_execution::DurableStorageError;
+use primitive_types::{H160, H256, U256};
+use serde::Deserialize;
+use tezos_ethereum::genesis::{GenesisAccount, GenesisDeclaration};
+use tezos_smart_rollup_host::path::{concat, OwnedPath, Path, PathError, RefPath};
+use tezos_smart_rollup_host::runtime::{Runtime, RuntimeError};
+use tezos_smart_rollup_installer_config::yaml::{Instr, SetArgs, YamlConfig};
+use tezos_smart_rollup_mock::MockHost;
+use thiserror::Error;
+
+/// Root of the durable storage of the kernel.
+const EVM_PATH: RefPath = RefPath::assert_from(b"/evm");
+
+// Paths of `storage.rs` in the kernel.
+const CHAIN_ID_PATH: RefPath = RefPath::assert_from(b"/chain_id");
+const BASE_FEE_PER_GAS_PATH: RefPath = RefPath::assert_from(b"/base_fee_per_gas");
+// Paths of `genesis.rs` in the kernel, the accounts are an indexable storage.
+const GENESIS_STATE_ROOT_PATH: RefPath = RefPath::assert_from(b"/genesis/state_root");
+const GENESIS_ACCOUNTS_PATH: RefPath = RefPath::assert_from(b"/genesis/accounts");
+
+#[derive(Debug, Error)]
+pub enum GenesisError {
+    #[error("Invalid genesis file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid {field} {value:?}")]
+    InvalidValue { field: &'static str, value: String },
+    #[error(transparent)]
+    AccountStorage(#[from] AccountStorageError),
+    #[error(transparent)]
+    DurableStorage(#[from] DurableStorageError),
+    #[error(transparent)]
+    Path(#[from] PathError),
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+}
+
+/// A quantity, either a JSON number or a hexadecimal or decimal string.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Quantity {
+    Number(u64),
+    String(String),
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainConfig {
+    pub chain_id: Option<Quantity>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GenesisAlloc {
+    pub balance: Option<Quantity>,
+    pub nonce: Option<Quantity>,
+    pub code: Option<String>,
+    #[serde(default)]
+    pub storage: BTreeMap<String, String>,
+}
+
+/// The fields of a geth `genesis.json` which are part of the state of the
+/// kernel, the other ones are ignored.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Genesis {
+    #[serde(default)]
+    pub config: ChainConfig,
+    pub base_fee_per_gas: Option<Quantity>,
+    #[serde(default)]
+    pub alloc: BTreeMap<String, GenesisAlloc>,
+}
+
+impl Genesis {
+    pub fn from_json(json: &str) -> Result<Self, GenesisError> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+fn invalid(field: &'static str, value: &str) -> GenesisError {
+    GenesisError::InvalidValue {
+        field,
+        value: value.to_owned(),
+    }
+}
+
+fn parse_quantity(
+    field: &'static str,
+    quantity: &Quantity,
+) -> Result<U256, GenesisError> {
+    match quantity {
+        Quantity::Number(n) => Ok(U256::from(*n)),
+        Quantity::String(s) => match s.strip_prefix("0x") {
+            Some(hex) => U256::from_str_radix(hex, 16).ok(),
+            None => U256::from_dec_str(s).ok(),
+        }
+        .ok_or_else(|| invalid(field, s)),
+    }
+}
+
+fn parse_bytes(field: &'static str, s: &str) -> Result<Vec<u8>, GenesisError> {
+    hex::decode(s.strip_prefix("0x").unwrap_or(s)).map_err(|_| invalid(field, s))
+}
+
+/// Storage indexes and values can omit their leading zeros.
+fn parse_h256(field: &'static str, s: &str) -> Result<H256, GenesisError> {
+    let hex = s.strip_prefix("0x").unwrap_or(s);
+    if hex.len() > 64 {
+        return Err(invalid(field, s));
+    }
+    parse_bytes(field, &format!("{:0>64}", hex)).map(|bytes| H256::from_slice(&bytes))
+}
+
+fn parse_address(s: &str) -> Result<H160, GenesisError> {
+    let bytes = parse_bytes("address", s)?;
+    if bytes.len() != 20 {
+        return Err(invalid("address", s));
+    }
+    Ok(H160::from_slice(&bytes))
+}
+
+/// The accounts allocated by the genesis file, without the zero values of
+/// their storage. An address can be allocated only once.
+pub fn genesis_accounts(genesis: &Genesis) -> Result<Vec<GenesisAccount>, GenesisError> {
+    let accounts: Vec<GenesisAccount> = genesis
+        .alloc
+        .iter()
+        .map(|(address, alloc)| {
+            let mut storage = BTreeMap::new();
+            for (index, value) in &alloc.storage {
+                let value = parse_h256("storage value", value)?;
+                if !value.is_zero() {
+                    storage.insert(parse_h256("storage index", index)?, value);
+                }
+            }
+            Ok(GenesisAccount {
+                address: parse_address(address)?,
+                nonce: match &alloc.nonce {
+                    Some(nonce) => parse_quantity("nonce", nonce)?,
+                    None => U256::zero(),
+                },
+                balance: match &alloc.balance {
+                    Some(balance) => parse_quantity("balance", balance)?,
+                    None => U256::zero(),
+                },
+                code: match &alloc.code {
+                    Some(code) => parse_bytes("code", code)?,
+                    None => vec![],
+                },
+                storage,
+            })
+        })
+        .collect::<Result<_, GenesisError>>()?;
+    let mut addresses = BTreeSet::new();
+    for account in &accounts {
+        if !addresses.insert(account.address) {
+            return Err(invalid("address", &format!("{:?}", account.address)));
+        }
+    }
+    Ok(accounts)
+}
+
+fn set(path: &impl Path, value: &[u8]) -> Result<Instr, GenesisError> {
+    let path = concat(&EVM_PATH, path)?;
+    // A path is always valid UTF-8.
+    let to = String::from_utf8_lossy(path.as_bytes()).into_owned();
+    Ok(Instr::Set(SetArgs {
+        value: hex::encode(value),
+        to,
+    }))
+}
+
+fn set_u256(path: &impl Path, value: U256) -> Result<Instr, GenesisError> {
+    let mut bytes = [0u8; 32];
+    value.to_little_endian(&mut bytes);
+    set(path, &bytes)
+}
+
+/// Writes the account in the host, and returns the paths of its values.
+fn write_account(
+    host: &mut MockHost,
+    genesis_account: &GenesisAccount,
+) -> Result<Vec<OwnedPath>, GenesisError> {
+    let mut account = EthereumAccount::from_address(&genesis_account.address)?;
+    if !genesis_account.nonce.is_zero() {
+        account.set_nonce(host, genesis_account.nonce)?;
+    }
+    if !genesis_account.balance.is_zero() {
+        account.set_balance(host, genesis_account.balance)?;
+    }
+    if !genesis_account.code.is_empty() {
+        account.set_code(host, &genesis_account.code)?;
+    }
+    let account_path =
+        concat(&EVM_ACCOUNTS_PATH, &account_path(&genesis_account.address)?)?;
+    let mut paths = vec![];
+    for path in [NONCE_PATH, BALANCE_PATH, CODE_HASH_PATH, CODE_PATH] {
+        paths.push(concat(&account_path, &path)?);
+    }
+    for (index, value) in &genesis_account.storage {
+        account.set_storage(host, index, value)?;
+        paths.push(account.storage_path(index)?);
+    }
+    Ok(paths)
+}
+
+/// Installer configuration setting the chain id, the base fee per gas and
+/// the accounts of the genesis file, and declaring the genesis state.
+pub fn installer_config(genesis: &Genesis) -> Result<YamlConfig, GenesisError> {
+    let mut instructions = vec![];
+    if let Some(chain_id) = &genesis.config.chain_id {
+        instructions.push(set_u256(
+            &CHAIN_ID_PATH,
+            parse_quantity("chain id", chain_id)?,
+        )?);
+    }
+    if let Some(base_fee_per_gas) = &genesis.base_fee_per_gas {
+        instructions.push(set_u256(
+            &BASE_FEE_PER_GAS_PATH,
+            parse_quantity("base fee per gas", base_fee_per_gas)?,
+        )?);
+    }
+
+    let accounts = genesis_accounts(genesis)?;
+    let mut host = MockHost::default();
+    for account in &accounts {
+        for path in write_account(&mut host, account)? {
+            if host.store_has(&path)?.is_some() {
+                instructions.push(set(&path, &host.store_read_all(&path)?)?);
+            }
+        }
+    }
+
+    let declaration = GenesisDeclaration::new(&accounts);
+    instructions.push(set(
+        &GENESIS_STATE_ROOT_PATH,
+        declaration.state_root.as_bytes(),
+    )?);
+    let encoded_accounts = declaration.encoded_accounts();
+    for (i, account) in encoded_accounts.iter().enumerate() {
+        let path = OwnedPath::try_from(format!("/{}", i))?;
+        instructions.push(set(&concat(&GENESIS_ACCOUNTS_PATH, &path)?, account)?);
+    }
+    let length = encoded_accounts.len() as u64;
+    instructions.push(set(
+        &concat(&GENESIS_ACCOUNTS_PATH, &RefPath::assert_from(b"/length"))?,
+        &length.to_le_bytes(),
+    )?);
+    Ok(YamlConfig { instructions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tezos_smart_rollup_installer_config::binary::owned::OwnedConfigProgram;
+
+    const GENESIS: &str = r#"{
+        "config": { "chainId": 1337, "londonBlock": 0 },
+        "baseFeePerGas": "0x3b9aca00",
+        "gasLimit": "0x1c9c380",
+        "alloc": {
+            "0x6ce4d79d4e77402e1ef3417fdda433aa744c6e1c": {
+                "balance": "9999000000000000000000"
+            },
+            "b53dc01974176e5dff2298c5a94343c2585e3c54": {
+                "balance": "0x10",
+                "nonce": "0x2",
+                "code": "0x600054",
+                "storage": {
+                    "0x00": "0x2a",
+                    "0x01": "0x00"
+                }
+            }
+        }
+    }"#;
+
+    fn address(s: &str) -> H160 {
+        parse_address(s).unwrap()
+    }
+
+    #[test]
+    fn test_genesis_accounts() {
+        let genesis = Genesis::from_json(GENESIS).unwrap();
+        let accounts = genesis_accounts(&genesis).unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(
+            accounts[0].balance,
+            U256::from_dec_str("9999000000000000000000").unwrap()
+        );
+        let contract = &accounts[1];
+        assert_eq!(contract.nonce, U256::from(2));
+        assert_eq!(contract.code, vec![0x60, 0x00, 0x54]);
+        // The zero value isn't part of the storage.
+        assert_eq!(
+            contract.storage,
+            BTreeMap::from([(H256::zero(), H256::from_low_u64_be(42))])
+        );
+    }
+
+    #[test]
+    fn test_installer_config_sets_the_genesis_state() {
+        let genesis = Genesis::from_json(GENESIS).unwrap();
+        let config = installer_config(&genesis).unwrap();
+
+        // The configuration is written as the installer would.
+        let program = OwnedConfigProgram::try_from(config).unwrap();
+        let mut host = MockHost::default();
+        program.evaluate(&mut host).unwrap();
+        let path = |s: &str| OwnedPath::try_from(format!("/evm{}", s)).unwrap();
+
+        let chain_id = host.store_read_all(&path("/chain_id")).unwrap();
+        assert_eq!(U256::from_little_endian(&chain_id), U256::from(1337));
+
+        let user = EthereumAccount::from(path(
+            "/eth_accounts/6ce4d79d4e77402e1ef3417fdda433aa744c6e1c",
+        ));
+        assert_eq!(
+            user.balance(&host).unwrap(),
+            U256::from_dec_str("9999000000000000000000").unwrap()
+        );
+        assert!(host
+            .store_has(&path(
+                "/eth_accounts/6ce4d79d4e77402e1ef3417fdda433aa744c6e1c/nonce"
+            ))
+            .unwrap()
+            .is_none());
+
+        let contract = EthereumAccount::from(path(&format!(
+            "/eth_accounts/{}",
+            hex::encode(address("b53dc01974176e5dff2298c5a94343c2585e3c54"))
+        )));
+        assert_eq!(contract.nonce(&host).unwrap(), U256::from(2));
+        assert_eq!(contract.code(&host).unwrap(), vec![0x60, 0x00, 0x54]);
+        assert_eq!(
+            contract.get_storage(&host, &H256::zero()).unwrap(),
+            H256::from_low_u64_be(42)
+        );
+
+        let accounts = genesis_accounts(&genesis).unwrap();
+        let declaration = GenesisDeclaration::new(&accounts);
+        let state_root = host.store_read_all(&path("/genesis/state_root")).unwrap();
+        assert_eq!(state_root, declaration.state_root.as_bytes());
+        let length = host
+            .store_read_all(&path("/genesis/accounts/length"))
+            .unwrap();
+        assert_eq!(length, 2u64.to_le_bytes());
+        for (i, account) in declaration.encoded_accounts().iter().enumerate() {
+            let declared = host
+                .store_read_all(&path(&format!("/genesis/accounts/{}", i)))
+                .unwrap();
+            assert_eq!(&declared, account);
+        }
+    }
+
+    #[test]
+    fn test_invalid_genesis() {
+        let invalid_addresses = [
+            r#"{ "alloc": { "0x01": {} } }"#,
+            // The same address twice
+            r#"{ "alloc": {
+                "0x6ce4d79d4e77402e1ef3417fdda433aa744c6e1c": {},
+                "6ce4d79d4e77402e1ef3417fdda433aa744c6e1c": {}
+            } }"#,
+        ];
+        for json in invalid_addresses {
+            let genesis = Genesis::from_json(json).unwrap();
+            assert!(matches!(
+                installer_config(&genesis),
+                Err(GenesisError::InvalidValue {
+                    field: "address",
+                    ..
+                })
+            ));
+        }
+    }
+}
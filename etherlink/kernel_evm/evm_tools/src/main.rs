@@ -0,0 +1,114 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+mod dump;
+mod genesis;
+
+use std::{
+    error::Error,
+    fs::{read_to_string, write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+use dump::{diff, Snapshot};
+use genesis::{installer_config, Genesis};
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "evm-tools",
+    about = "Tools for the durable storage of the EVM kernel."
+)]
+pub enum Opt {
+    #[structopt(
+        about = "Produce the installer configuration of the state allocated by a geth genesis file."
+    )]
+    Genesis {
+        #[structopt(parse(from_os_str), about = "Path of the genesis.json file.")]
+        genesis: PathBuf,
+        #[structopt(
+            short = "o",
+            long = "output",
+            parse(from_os_str),
+            about = "File where the configuration is written. By default it is printed."
+        )]
+        output: Option<PathBuf>,
+    },
+    #[structopt(
+        about = "Dump the world state of a durable storage snapshot, in the JSON format of geth dump."
+    )]
+    Dump {
+        #[structopt(
+            parse(from_os_str),
+            about = "Path of the snapshot, as produced by `octez-smart-rollup-node dump durable storage`."
+        )]
+        snapshot: PathBuf,
+        #[structopt(
+            short = "o",
+            long = "output",
+            parse(from_os_str),
+            about = "File where the dump is written. By default it is printed."
+        )]
+        output: Option<PathBuf>,
+    },
+    #[structopt(
+        about = "Show the differences between the world states of two snapshots."
+    )]
+    Diff {
+        #[structopt(parse(from_os_str), about = "Path of the first snapshot.")]
+        before: PathBuf,
+        #[structopt(parse(from_os_str), about = "Path of the second snapshot.")]
+        after: PathBuf,
+        #[structopt(
+            short = "o",
+            long = "output",
+            parse(from_os_str),
+            about = "File where the differences are written. By default they are printed."
+        )]
+        output: Option<PathBuf>,
+    },
+}
+
+fn output(content: &str, path: &Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+    match path {
+        Some(path) => write(path, content)?,
+        None => print!("{}", content),
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    match Opt::from_args() {
+        Opt::Genesis {
+            genesis,
+            output: path,
+        } => {
+            let genesis = Genesis::from_json(&read_to_string(genesis)?)?;
+            let config = installer_config(&genesis)?;
+            output(&serde_yaml::to_string(&config)?, &path)
+        }
+        Opt::Dump {
+            snapshot,
+            output: path,
+        } => {
+            let dump = Snapshot::from_yaml(&read_to_string(snapshot)?)?.dump()?;
+            output(
+                &format!("{}\n", serde_json::to_string_pretty(&dump)?),
+                &path,
+            )
+        }
+        Opt::Diff {
+            before,
+            after,
+            output: path,
+        } => {
+            let before = Snapshot::from_yaml(&read_to_string(before)?)?.dump()?;
+            let after = Snapshot::from_yaml(&read_to_string(after)?)?.dump()?;
+            output(
+                &serde_json::to_string_pretty(&diff(&before, &after))?,
+                &path,
+            )
+        }
+    }
+}
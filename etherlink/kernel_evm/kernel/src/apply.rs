@@ -10,33 +10,42 @@ use evm::{ExitError, ExitReason, ExitSucceed};
 use evm_execution::account_storage::{
     account_path, EthereumAccount, EthereumAccountStorage,
 };
+use evm_execution::fa_bridge::{self, FA_BRIDGE_ADDRESS};
 use evm_execution::handler::ExecutionOutcome;
+use evm_execution::handler::TouchedAccounts;
 use evm_execution::precompiles::PrecompileBTreeMap;
-use evm_execution::{run_transaction, EthereumError};
+use evm_execution::tick_model_opcodes;
+use evm_execution::trace::{Trace, TracerConfig};
+use evm_execution::{
+    run_transaction_and_track_accounts, trace_transaction_and_track_accounts,
+    EthereumError, EvmFork,
+};
 use primitive_types::{H160, U256};
-use tezos_data_encoding::enc::BinWriter;
 use tezos_ethereum::block::{BlockConstants, BlockFees};
-use tezos_ethereum::transaction::TransactionHash;
+use tezos_ethereum::transaction::{TransactionHash, TransactionType};
 use tezos_ethereum::tx_common::EthereumTransactionCommon;
 use tezos_ethereum::tx_signature::TxSignature;
-use tezos_ethereum::withdrawal::Withdrawal;
+use tezos_ethereum::withdrawal::{L1Call, L1Message, Withdrawal};
 use tezos_evm_logging::{log, Level::*};
-use tezos_smart_rollup_core::MAX_OUTPUT_SIZE;
 use tezos_smart_rollup_encoding::contract::Contract;
 use tezos_smart_rollup_encoding::entrypoint::Entrypoint;
 use tezos_smart_rollup_encoding::michelson::ticket::{FA2_1Ticket, Ticket};
 use tezos_smart_rollup_encoding::michelson::{
     MichelsonContract, MichelsonOption, MichelsonPair,
 };
-use tezos_smart_rollup_encoding::outbox::OutboxMessage;
-use tezos_smart_rollup_encoding::outbox::OutboxMessageTransaction;
+use tezos_smart_rollup_encoding::outbox::{
+    OutboxMessageTransaction, OutboxMessageTransactionTyped,
+};
 use tezos_smart_rollup_host::runtime::Runtime;
 
 use crate::error::Error;
-use crate::inbox::{Deposit, Transaction, TransactionContent};
+use crate::inbox::{Deposit, FaDeposit, Transaction, TransactionContent};
 use crate::indexable_storage::IndexableStorage;
+use crate::outbox;
 use crate::storage::{index_account, read_ticketer};
-use crate::{tick_model, CONFIG};
+use crate::tick_model;
+use crate::world_state;
+use std::collections::BTreeSet;
 
 // This implementation of `Transaction` is used to share the logic of
 // transaction receipt and transaction object making. The functions
@@ -46,6 +55,9 @@ impl Transaction {
     fn to(&self) -> Option<H160> {
         match &self.content {
             TransactionContent::Deposit(Deposit { receiver, .. }) => Some(*receiver),
+            TransactionContent::FaDeposit(FaDeposit { ticket, .. }) => {
+                Some(ticket.wrapper_address())
+            }
             TransactionContent::Ethereum(transaction) => transaction.to,
         }
     }
@@ -53,6 +65,9 @@ impl Transaction {
     fn data(&self) -> Vec<u8> {
         match &self.content {
             TransactionContent::Deposit(_) => vec![],
+            TransactionContent::FaDeposit(FaDeposit {
+                amount, receiver, ..
+            }) => fa_bridge::mint_call_data(receiver, *amount),
             TransactionContent::Ethereum(transaction) => transaction.data.clone(),
         }
     }
@@ -62,7 +77,9 @@ impl Transaction {
     // This includes both the gas paid for execution, and for the additional flat & data-availability fees.
     fn overall_gas_price(&self, block_fees: &BlockFees) -> Result<U256, anyhow::Error> {
         match &self.content {
-            TransactionContent::Deposit(_) => Ok(U256::zero()),
+            TransactionContent::Deposit(_) | TransactionContent::FaDeposit(_) => {
+                Ok(U256::zero())
+            }
             TransactionContent::Ethereum(transaction) => {
                 transaction.overall_gas_price(block_fees)
             }
@@ -72,20 +89,23 @@ impl Transaction {
     fn value(&self) -> U256 {
         match &self.content {
             TransactionContent::Deposit(Deposit { amount, .. }) => *amount,
+            TransactionContent::FaDeposit(_) => U256::zero(),
             TransactionContent::Ethereum(transaction) => transaction.value,
         }
     }
 
     fn nonce(&self) -> U256 {
         match &self.content {
-            TransactionContent::Deposit(_) => U256::zero(),
+            TransactionContent::Deposit(_) | TransactionContent::FaDeposit(_) => {
+                U256::zero()
+            }
             TransactionContent::Ethereum(transaction) => transaction.nonce,
         }
     }
 
     fn signature(&self) -> Option<TxSignature> {
         match &self.content {
-            TransactionContent::Deposit(_) => None,
+            TransactionContent::Deposit(_) | TransactionContent::FaDeposit(_) => None,
             TransactionContent::Ethereum(transaction) => transaction.signature.clone(),
         }
     }
@@ -98,6 +118,8 @@ pub struct TransactionReceiptInfo {
     pub caller: H160,
     pub to: Option<H160>,
     pub effective_gas_price: U256,
+    pub da_fee: U256,
+    pub type_: TransactionType,
 }
 
 #[derive(Debug)]
@@ -114,6 +136,7 @@ pub struct TransactionObjectInfo {
     pub signature: Option<TxSignature>,
 }
 
+#[allow(clippy::too_many_arguments)]
 #[inline(always)]
 fn make_receipt_info(
     tx_hash: TransactionHash,
@@ -122,6 +145,8 @@ fn make_receipt_info(
     caller: H160,
     to: Option<H160>,
     effective_gas_price: U256,
+    da_fee: U256,
+    type_: TransactionType,
 ) -> TransactionReceiptInfo {
     TransactionReceiptInfo {
         tx_hash,
@@ -130,6 +155,8 @@ fn make_receipt_info(
         caller,
         to,
         effective_gas_price,
+        da_fee,
+        type_,
     }
 }
 
@@ -241,11 +268,12 @@ fn is_valid_ethereum_transaction_common<Host: Runtime>(
         return Ok(Validity::InvalidNonce);
     };
 
-    // The sender account balance contains at least the cost.
-    let execution_gas_limit = U256::from(transaction.execution_gas_limit());
-    let cost = execution_gas_limit.saturating_mul(effective_gas_price);
+    // The sender account balance contains at least the cost, including the
+    // data availability fee.
+    let gas_limit = U256::from(transaction.gas_limit_with_fees());
+    let cost = gas_limit.saturating_mul(effective_gas_price);
     // The sender can afford the max gas fee he set, see EIP-1559
-    let max_fee = execution_gas_limit.saturating_mul(transaction.max_fee_per_gas);
+    let max_fee = gas_limit.saturating_mul(transaction.max_fee_per_gas);
     if balance < cost || balance < max_fee {
         log!(host, Debug, "Transaction status: ERROR_PRE_PAY.");
         return Ok(Validity::InvalidPrePay);
@@ -270,18 +298,36 @@ pub struct TransactionResult {
     caller: H160,
     execution_outcome: Option<ExecutionOutcome>,
     gas_used: U256,
+    da_fee: U256,
     estimated_ticks_used: u64,
+    touched_accounts: TouchedAccounts,
+    trace: Option<Trace>,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn apply_ethereum_transaction_common<Host: Runtime>(
     host: &mut Host,
     block_constants: &BlockConstants,
     precompiles: &PrecompileBTreeMap<Host>,
     evm_account_storage: &mut EthereumAccountStorage,
     transaction: &EthereumTransactionCommon,
+    data_size: u64,
     allocated_ticks: u64,
+    fork: EvmFork,
+    tracer: Option<TracerConfig>,
 ) -> Result<ExecutionResult<TransactionResult>, anyhow::Error> {
-    let effective_gas_price = block_constants.base_fee_per_gas();
+    let effective_gas_price =
+        transaction.effective_gas_price(&block_constants.block_fees);
+    // The data availability fee is paid with gas, which is not available for
+    // the execution.
+    let gas_for_da_fee = block_constants
+        .block_fees
+        .gas_for_da_fee(data_size, effective_gas_price);
+    let Some(gas_limit) = transaction.execution_gas_limit(gas_for_da_fee) else {
+        log!(host, Debug, "Transaction status: ERROR_GAS_FOR_FEES.");
+        return Ok(ExecutionResult::Invalid);
+    };
+    let da_fee = U256::from(gas_for_da_fee).saturating_mul(effective_gas_price);
     let caller = match is_valid_ethereum_transaction_common(
         host,
         evm_account_storage,
@@ -295,24 +341,48 @@ fn apply_ethereum_transaction_common<Host: Runtime>(
 
     let to = transaction.to;
     let call_data = transaction.data.clone();
-    let gas_limit = transaction.execution_gas_limit();
     let value = transaction.value;
-    let execution_outcome = match run_transaction(
-        host,
-        block_constants,
-        evm_account_storage,
-        precompiles,
-        CONFIG,
-        to,
-        caller,
-        call_data,
-        Some(gas_limit),
-        effective_gas_price,
-        Some(value),
-        true,
-        allocated_ticks,
-    ) {
-        Ok(outcome) => outcome,
+    let result = match tracer {
+        None => run_transaction_and_track_accounts(
+            host,
+            block_constants,
+            evm_account_storage,
+            precompiles,
+            fork,
+            to,
+            caller,
+            call_data,
+            Some(gas_limit),
+            effective_gas_price,
+            da_fee,
+            Some(value),
+            true,
+            allocated_ticks,
+        )
+        .map(|(outcome, touched_accounts)| (outcome, touched_accounts, None)),
+        Some(tracer) => trace_transaction_and_track_accounts(
+            host,
+            block_constants,
+            evm_account_storage,
+            precompiles,
+            fork,
+            to,
+            caller,
+            call_data,
+            Some(gas_limit),
+            effective_gas_price,
+            da_fee,
+            Some(value),
+            true,
+            allocated_ticks,
+            tracer,
+        )
+        .map(|(outcome, trace, touched_accounts)| {
+            (outcome, touched_accounts, Some(trace))
+        }),
+    };
+    let (mut execution_outcome, mut touched_accounts, trace) = match result {
+        Ok(result) => result,
         Err(EthereumError::OutOfTicks) => return Ok(ExecutionResult::OutOfTicks),
         Err(err) => {
             // TODO: https://gitlab.com/tezos/tezos/-/issues/5665
@@ -323,7 +393,7 @@ fn apply_ethereum_transaction_common<Host: Runtime>(
         }
     };
 
-    let (gas_used, estimated_ticks_used) = match &execution_outcome {
+    let (gas_used, da_fee, estimated_ticks_used) = match &mut execution_outcome {
         Some(execution_outcome) => {
             log!(
                 host,
@@ -331,14 +401,28 @@ fn apply_ethereum_transaction_common<Host: Runtime>(
                 "Transaction status: OK_{}.",
                 execution_outcome.is_success
             );
+            pay_sequencer_fees(
+                host,
+                evm_account_storage,
+                &mut touched_accounts,
+                block_constants,
+                effective_gas_price,
+                execution_outcome.gas_used.into(),
+                da_fee,
+            )?;
+            // The gas paying for the data availability fee is reported as
+            // used by the transaction.
+            execution_outcome.gas_used =
+                execution_outcome.gas_used.saturating_add(gas_for_da_fee);
             (
                 execution_outcome.gas_used.into(),
+                da_fee,
                 execution_outcome.estimated_ticks_used,
             )
         }
         None => {
             log!(host, Debug, "Transaction status: OK_UNKNOWN.");
-            (U256::zero(), 0)
+            (U256::zero(), U256::zero(), 0)
         }
     };
 
@@ -346,14 +430,46 @@ fn apply_ethereum_transaction_common<Host: Runtime>(
         caller,
         execution_outcome,
         gas_used,
+        da_fee,
         estimated_ticks_used,
+        touched_accounts,
+        trace,
     }))
 }
 
+// Credits the coinbase with the part of the gas price exceeding the base fee
+// per gas, and with the data availability fee. If no coinbase is set, these
+// fees are burned along with the base fee.
+fn pay_sequencer_fees<Host: Runtime>(
+    host: &mut Host,
+    evm_account_storage: &mut EthereumAccountStorage,
+    touched_accounts: &mut TouchedAccounts,
+    block_constants: &BlockConstants,
+    effective_gas_price: U256,
+    execution_gas_used: U256,
+    da_fee: U256,
+) -> Result<(), Error> {
+    let priority_fees = effective_gas_price
+        .saturating_sub(block_constants.base_fee_per_gas())
+        .saturating_mul(execution_gas_used);
+    let fees = priority_fees.saturating_add(da_fee);
+    if block_constants.coinbase.is_zero() || fees.is_zero() {
+        return Ok(());
+    }
+    let mut coinbase = evm_account_storage
+        .get_or_create(host, &account_path(&block_constants.coinbase)?)?;
+    coinbase.balance_add(host, fees)?;
+    touched_accounts
+        .entry(block_constants.coinbase)
+        .or_default();
+    Ok(())
+}
+
 fn apply_deposit<Host: Runtime>(
     host: &mut Host,
     evm_account_storage: &mut EthereumAccountStorage,
     deposit: &Deposit,
+    fork: EvmFork,
 ) -> Result<Option<TransactionResult>, Error> {
     let Deposit { amount, receiver } = deposit;
 
@@ -372,7 +488,7 @@ fn apply_deposit<Host: Runtime>(
         ExitReason::Error(ExitError::Other(Cow::from("Deposit failed")))
     };
 
-    let gas_used = CONFIG.gas_transaction_call;
+    let gas_used = fork.config().gas_transaction_call;
 
     // TODO: https://gitlab.com/tezos/tezos/-/issues/6551
     let estimated_ticks_used = tick_model::constants::TICKS_FOR_DEPOSIT;
@@ -390,78 +506,230 @@ fn apply_deposit<Host: Runtime>(
 
     let caller = H160::zero();
 
+    let mut touched_accounts = TouchedAccounts::new();
+    touched_accounts.insert(*receiver, BTreeSet::new());
+
     Ok(Some(TransactionResult {
         caller,
         execution_outcome: Some(execution_outcome),
         gas_used: gas_used.into(),
+        da_fee: U256::zero(),
         estimated_ticks_used,
+        touched_accounts,
+        trace: None,
     }))
 }
 
+/// Gas limit of the mint of a FA deposit by its wrapper.
+const FA_DEPOSIT_GAS_LIMIT: u64 = 200_000;
+
+// Deploys the ERC-20 wrapper of the ticket on its first deposit, then mints
+// the deposit to the receiver from the FA bridge. If the mint fails, the
+// ticket is withdrawn back to the depositor, it would be lost otherwise.
+#[allow(clippy::too_many_arguments)]
+fn apply_fa_deposit<Host: Runtime>(
+    host: &mut Host,
+    block_constants: &BlockConstants,
+    precompiles: &PrecompileBTreeMap<Host>,
+    evm_account_storage: &mut EthereumAccountStorage,
+    deposit: &FaDeposit,
+    allocated_ticks: u64,
+    fork: EvmFork,
+) -> Result<ExecutionResult<TransactionResult>, Error> {
+    let FaDeposit {
+        ticket,
+        amount,
+        receiver,
+        depositor,
+    } = deposit;
+
+    let wrapper = fa_bridge::deploy_wrapper(host, evm_account_storage, ticket)
+        .map_err(Error::InvalidRunTransaction)?;
+
+    let (mut execution_outcome, mut touched_accounts) =
+        match run_transaction_and_track_accounts(
+            host,
+            block_constants,
+            evm_account_storage,
+            precompiles,
+            fork,
+            Some(wrapper),
+            FA_BRIDGE_ADDRESS,
+            fa_bridge::mint_call_data(receiver, *amount),
+            Some(FA_DEPOSIT_GAS_LIMIT),
+            U256::zero(),
+            U256::zero(),
+            None,
+            false,
+            allocated_ticks,
+        ) {
+            Ok(result) => result,
+            Err(EthereumError::OutOfTicks) => return Ok(ExecutionResult::OutOfTicks),
+            Err(err) => return Err(Error::InvalidRunTransaction(err)),
+        };
+    touched_accounts.entry(wrapper).or_default();
+
+    if let Some(outcome) = execution_outcome.as_mut().filter(|o| !o.is_success) {
+        log!(
+            host,
+            Info,
+            "FA deposit of {} {:?} failed, refunded to {}",
+            amount,
+            ticket,
+            depositor.to_b58check()
+        );
+        outcome.withdrawals = vec![L1Message::Withdrawal(Withdrawal {
+            target: Contract::Implicit(depositor.clone()),
+            amount: *amount,
+            ticket: Some(ticket.clone()),
+        })];
+    }
+
+    let (gas_used, estimated_ticks_used) = match &execution_outcome {
+        Some(outcome) => (
+            outcome.gas_used.into(),
+            outcome
+                .estimated_ticks_used
+                .saturating_add(tick_model::constants::TICKS_FOR_DEPOSIT),
+        ),
+        None => (U256::zero(), tick_model::constants::TICKS_FOR_DEPOSIT),
+    };
+
+    Ok(ExecutionResult::Valid(TransactionResult {
+        caller: FA_BRIDGE_ADDRESS,
+        execution_outcome,
+        gas_used,
+        da_fee: U256::zero(),
+        estimated_ticks_used,
+        touched_accounts,
+        trace: None,
+    }))
+}
+
+/// Pushes the messages of a transaction to layer 1 in the outbox queue, in
+/// the order they were emitted.
 fn post_withdrawals<Host: Runtime>(
     host: &mut Host,
-    withdrawals: &Vec<Withdrawal>,
+    tx_hash: &TransactionHash,
+    withdrawals: &Vec<L1Message>,
 ) -> Result<(), Error> {
-    if withdrawals.is_empty() {
-        return Ok(());
-    };
+    for message in withdrawals {
+        match message {
+            L1Message::Withdrawal(withdrawal) => {
+                post_withdrawal(host, tx_hash, withdrawal)?
+            }
+            L1Message::Call(call) => post_l1_call(host, tx_hash, call)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn post_withdrawal<Host: Runtime>(
+    host: &mut Host,
+    tx_hash: &TransactionHash,
+    withdrawal: &Withdrawal,
+) -> Result<(), Error> {
+    if let Some(ticket) = &withdrawal.ticket {
+        // FA tickets are sent back to their ticketer, the precompiled
+        // contract checked that the transaction fits in an outbox message.
+        let transaction = fa_bridge::fa_withdrawal_transaction(
+            &withdrawal.target,
+            ticket,
+            withdrawal.amount,
+        )?;
+        return outbox::push_transaction(host, tx_hash, transaction, false);
+    }
 
     let destination = match read_ticketer(host) {
         Some(x) => Contract::Originated(x),
         None => return Err(Error::InvalidParsing),
     };
-    let entrypoint = Entrypoint::try_from(String::from("burn"))?;
-
-    for withdrawal in withdrawals {
-        // Wei is 10^18, whereas mutez is 10^6.
-        let amount: U256 =
-            U256::checked_div(withdrawal.amount, U256::from(10).pow(U256::from(12)))
-                // If we reach the unwrap_or it will fail at the next step because
-                // we cannot create a ticket with no amount. But by construction
-                // it should not happen, we do not divide by 0.
-                .unwrap_or(U256::zero());
-
-        let amount = if amount < U256::from(u64::max_value()) {
-            amount.as_u64()
-        } else {
-            // Users can withdraw only mutez, converted to ETH, thus the
-            // maximum value of `amount` is `Int64.max_int` which fit
-            // in a u64.
-            return Err(Error::InvalidConversion);
-        };
 
-        let ticket: FA2_1Ticket = Ticket::new(
-            destination.clone(),
-            MichelsonPair(0.into(), MichelsonOption(None)),
-            amount,
-        )?;
-        let parameters = MichelsonPair::<MichelsonContract, FA2_1Ticket>(
-            MichelsonContract(withdrawal.target.clone()),
-            ticket,
-        );
+    // Wei is 10^18, whereas mutez is 10^6.
+    let amount: U256 =
+        U256::checked_div(withdrawal.amount, U256::from(10).pow(U256::from(12)))
+            // If we reach the unwrap_or it will fail at the next step because
+            // we cannot create a ticket with no amount. But by construction
+            // it should not happen, we do not divide by 0.
+            .unwrap_or(U256::zero());
 
-        let withdrawal = OutboxMessageTransaction {
-            parameters,
-            entrypoint: entrypoint.clone(),
-            destination: destination.clone(),
-        };
-        let outbox_message =
-            OutboxMessage::AtomicTransactionBatch(vec![withdrawal].into());
+    let amount = if amount < U256::from(u64::max_value()) {
+        amount.as_u64()
+    } else {
+        // Users can withdraw only mutez, converted to ETH, thus the
+        // maximum value of `amount` is `Int64.max_int` which fit
+        // in a u64.
+        return Err(Error::InvalidConversion);
+    };
 
-        let mut encoded = Vec::with_capacity(MAX_OUTPUT_SIZE);
+    let ticket: FA2_1Ticket = Ticket::new(
+        destination.clone(),
+        MichelsonPair(0.into(), MichelsonOption(None)),
+        amount,
+    )?;
+    post_ticket(
+        host,
+        tx_hash,
+        &withdrawal.target,
+        ticket,
+        Entrypoint::try_from(String::from("burn"))?,
+        destination,
+        // Only withdrawals of the native ticket to implicit accounts are sure
+        // to succeed on layer 1, and can be batched with other transactions.
+        matches!(withdrawal.target, Contract::Implicit(_)),
+    )
+}
 
-        outbox_message.bin_write(&mut encoded)?;
+fn post_ticket<Host: Runtime>(
+    host: &mut Host,
+    tx_hash: &TransactionHash,
+    target: &Contract,
+    ticket: FA2_1Ticket,
+    entrypoint: Entrypoint,
+    destination: Contract,
+    batchable: bool,
+) -> Result<(), Error> {
+    let parameters = MichelsonPair::<MichelsonContract, FA2_1Ticket>(
+        MichelsonContract(target.clone()),
+        ticket,
+    );
 
-        host.write_output(&encoded)?;
-    }
+    outbox::push_transaction(
+        host,
+        tx_hash,
+        OutboxMessageTransaction {
+            parameters,
+            entrypoint,
+            destination,
+        },
+        batchable,
+    )
+}
 
-    Ok(())
+fn post_l1_call<Host: Runtime>(
+    host: &mut Host,
+    tx_hash: &TransactionHash,
+    call: &L1Call,
+) -> Result<(), Error> {
+    outbox::push_typed_transaction(
+        host,
+        tx_hash,
+        OutboxMessageTransactionTyped {
+            parameters: call.parameters.clone(),
+            parameters_ty: call.parameters_ty.clone(),
+            entrypoint: call.entrypoint.clone(),
+            destination: Contract::Originated(call.destination.clone()),
+        },
+    )
 }
 
 pub struct ExecutionInfo {
     pub receipt_info: TransactionReceiptInfo,
     pub object_info: TransactionObjectInfo,
     pub estimated_ticks_used: u64,
+    /// Trace of the execution, only recorded by [trace_transaction]
+    pub trace: Option<Trace>,
 }
 
 pub enum ExecutionResult<T> {
@@ -489,6 +757,63 @@ pub fn apply_transaction<Host: Runtime>(
     evm_account_storage: &mut EthereumAccountStorage,
     accounts_index: &mut IndexableStorage,
     allocated_ticks: u64,
+    fork: EvmFork,
+) -> Result<ExecutionResult<ExecutionInfo>, anyhow::Error> {
+    apply_transaction_with_tracer(
+        host,
+        block_constants,
+        precompiles,
+        transaction,
+        index,
+        evm_account_storage,
+        accounts_index,
+        allocated_ticks,
+        fork,
+        None,
+    )
+}
+
+/// Apply a transaction as [apply_transaction], and record the trace of its
+/// execution. Deposits are not traced.
+#[allow(clippy::too_many_arguments)]
+pub fn trace_transaction<Host: Runtime>(
+    host: &mut Host,
+    block_constants: &BlockConstants,
+    precompiles: &PrecompileBTreeMap<Host>,
+    transaction: &Transaction,
+    index: u32,
+    evm_account_storage: &mut EthereumAccountStorage,
+    accounts_index: &mut IndexableStorage,
+    allocated_ticks: u64,
+    fork: EvmFork,
+    tracer: TracerConfig,
+) -> Result<ExecutionResult<ExecutionInfo>, anyhow::Error> {
+    apply_transaction_with_tracer(
+        host,
+        block_constants,
+        precompiles,
+        transaction,
+        index,
+        evm_account_storage,
+        accounts_index,
+        allocated_ticks,
+        fork,
+        Some(tracer),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply_transaction_with_tracer<Host: Runtime>(
+    host: &mut Host,
+    block_constants: &BlockConstants,
+    precompiles: &PrecompileBTreeMap<Host>,
+    transaction: &Transaction,
+    index: u32,
+    evm_account_storage: &mut EthereumAccountStorage,
+    accounts_index: &mut IndexableStorage,
+    allocated_ticks: u64,
+    fork: EvmFork,
+    tracer: Option<TracerConfig>,
 ) -> Result<ExecutionResult<ExecutionInfo>, anyhow::Error> {
     let to = transaction.to();
     let apply_result = match &transaction.content {
@@ -498,11 +823,26 @@ pub fn apply_transaction<Host: Runtime>(
             precompiles,
             evm_account_storage,
             tx,
+            transaction.data_size(),
             allocated_ticks,
+            fork,
+            tracer,
+        )?,
+        TransactionContent::Deposit(deposit) => ExecutionResult::from(apply_deposit(
+            host,
+            evm_account_storage,
+            deposit,
+            fork,
+        )?),
+        TransactionContent::FaDeposit(deposit) => apply_fa_deposit(
+            host,
+            block_constants,
+            precompiles,
+            evm_account_storage,
+            deposit,
+            allocated_ticks,
+            fork,
         )?,
-        TransactionContent::Deposit(deposit) => {
-            ExecutionResult::from(apply_deposit(host, evm_account_storage, deposit)?)
-        }
     };
 
     match apply_result {
@@ -510,14 +850,26 @@ pub fn apply_transaction<Host: Runtime>(
             caller,
             execution_outcome,
             gas_used,
+            da_fee,
             estimated_ticks_used: ticks_used,
+            touched_accounts,
+            trace,
         }) => {
+            world_state::update_state_root(host, evm_account_storage, &touched_accounts)?;
+            let ticks_used = ticks_used.saturating_add(
+                tick_model_opcodes::ticks_of_touched_accounts(&touched_accounts),
+            );
+
             if let Some(outcome) = &execution_outcome {
                 log!(host, Debug, "Transaction executed, outcome: {:?}", outcome);
             }
 
             if let Some(ref execution_outcome) = execution_outcome {
-                post_withdrawals(host, &execution_outcome.withdrawals)?
+                post_withdrawals(
+                    host,
+                    &transaction.tx_hash,
+                    &execution_outcome.withdrawals,
+                )?
             }
 
             let object_info = make_object_info(
@@ -535,6 +887,8 @@ pub fn apply_transaction<Host: Runtime>(
                 caller,
                 to,
                 object_info.gas_price,
+                da_fee,
+                transaction.type_(),
             );
 
             index_new_accounts(host, accounts_index, &receipt_info)?;
@@ -542,6 +896,7 @@ pub fn apply_transaction<Host: Runtime>(
                 receipt_info,
                 object_info,
                 estimated_ticks_used: ticks_used,
+                trace,
             }))
         }
         ExecutionResult::Invalid => Ok(ExecutionResult::Invalid),
@@ -562,14 +917,35 @@ mod tests {
     use tezos_smart_rollup_encoding::timestamp::Timestamp;
     use tezos_smart_rollup_mock::MockHost;
 
-    use crate::inbox::{Transaction, TransactionContent};
+    use crate::inbox::{FaDeposit, Transaction, TransactionContent};
+    use evm_execution::fa_bridge::WRAPPER_CODE;
+    use evm_execution::precompiles::precompile_set;
+    use evm_execution::run_transaction;
+    use tezos_crypto_rs::hash::{ContractKt1Hash, HashTrait};
+    use tezos_data_encoding::nom::NomReader;
+    use tezos_ethereum::fa_ticket::FaTicket;
+    use tezos_ethereum::withdrawal::{L1Call, L1Message, MichelineExpr, Withdrawal};
+    use tezos_smart_rollup_encoding::contract::Contract;
+    use tezos_smart_rollup_encoding::entrypoint::Entrypoint;
+    use tezos_smart_rollup_encoding::michelson::ticket::FA2_1Ticket;
+    use tezos_smart_rollup_encoding::michelson::{
+        MichelsonBytes, MichelsonContract, MichelsonInt, MichelsonOption, MichelsonPair,
+    };
+    use tezos_smart_rollup_encoding::outbox::OutboxMessage;
+    use tezos_smart_rollup_encoding::public_key_hash::PublicKeyHash;
+
+    use crate::outbox;
 
-    use super::{is_valid_ethereum_transaction_common, make_object_info};
+    use super::{
+        apply_ethereum_transaction_common, apply_transaction,
+        is_valid_ethereum_transaction_common, make_object_info, post_withdrawals,
+        ExecutionResult,
+    };
 
     const CHAIN_ID: u32 = 1337;
 
     fn mock_block_constants() -> BlockConstants {
-        let block_fees = BlockFees::new(U256::from(12345));
+        let block_fees = BlockFees::new(U256::from(12345), U256::zero());
         BlockConstants::first_block(
             U256::from(Timestamp::from(0).as_u64()),
             CHAIN_ID.into(),
@@ -630,6 +1006,27 @@ mod tests {
         resign(transaction)
     }
 
+    fn tx_with_data(
+        nonce: u64,
+        gas_limit: u64,
+        data: Vec<u8>,
+    ) -> EthereumTransactionCommon {
+        let transaction = EthereumTransactionCommon::new(
+            TransactionType::Eip1559,
+            Some(CHAIN_ID.into()),
+            U256::from(nonce),
+            U256::zero(),
+            U256::from(21000),
+            gas_limit,
+            Some(H160::zero()),
+            U256::zero(),
+            data,
+            vec![],
+            None,
+        );
+        resign(transaction)
+    }
+
     #[test]
     fn test_tx_is_valid() {
         let mut host = MockHost::default();
@@ -843,7 +1240,7 @@ mod tests {
             )),
         };
 
-        let block_fees = BlockFees::new(U256::from(9));
+        let block_fees = BlockFees::new(U256::from(9), U256::zero());
 
         let obj = make_object_info(
             &transaction,
@@ -854,4 +1251,295 @@ mod tests {
         );
         assert!(obj.is_err())
     }
+
+    #[test]
+    fn test_da_fee_is_paid_with_gas() {
+        let mut host = MockHost::default();
+        let mut evm_account_storage =
+            evm_execution::account_storage::init_account_storage().unwrap();
+        let precompiles = evm_execution::precompiles::precompile_set::<MockHost>();
+        let base_fee_per_gas = U256::from(12345);
+        // 10 bytes cost the same as 1000 gas
+        let block_fees = BlockFees::new(base_fee_per_gas, base_fee_per_gas * 100);
+        let block_constants =
+            BlockConstants::first_block(U256::zero(), CHAIN_ID.into(), block_fees);
+
+        let address = address_from_str("af1276cbb260bb13deddb4209ae99ae6e497f446");
+        let balance = U256::from(1_000_000) * base_fee_per_gas;
+        set_balance(&mut host, &mut evm_account_storage, &address, balance);
+
+        let transaction = tx_with_data(0, 30_000, vec![1; 10]);
+
+        let Ok(ExecutionResult::Valid(result)) = apply_ethereum_transaction_common(
+            &mut host,
+            &block_constants,
+            &precompiles,
+            &mut evm_account_storage,
+            &transaction,
+            10,
+            u64::MAX,
+            evm_execution::EvmFork::Shanghai,
+            None,
+        ) else {
+            panic!("The transaction should have been applied")
+        };
+
+        // The receipt reports the gas paying for the fee as used
+        let outcome = result.execution_outcome.unwrap();
+        assert_eq!(U256::from(outcome.gas_used), result.gas_used);
+        assert!(outcome.gas_used >= 21_000 + 1000);
+        // and the fee itself separately
+        assert_eq!(result.da_fee, U256::from(1000) * base_fee_per_gas);
+        let account = evm_account_storage
+            .get(&host, &account_path(&address).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            account.balance(&host).unwrap(),
+            balance - result.gas_used * base_fee_per_gas
+        );
+
+        // The gas limit must cover the data availability fee
+        let transaction = tx_with_data(1, 999, vec![1; 10]);
+        let result = apply_ethereum_transaction_common(
+            &mut host,
+            &block_constants,
+            &precompiles,
+            &mut evm_account_storage,
+            &transaction,
+            10,
+            u64::MAX,
+            evm_execution::EvmFork::Shanghai,
+            None,
+        );
+        assert!(matches!(result, Ok(ExecutionResult::Invalid)));
+    }
+
+    #[test]
+    fn test_fa_deposits_and_withdrawals() {
+        let mut host = MockHost::default();
+        let mut evm_account_storage =
+            evm_execution::account_storage::init_account_storage().unwrap();
+        let mut accounts_index = crate::storage::init_account_index().unwrap();
+        let block_constants = mock_block_constants();
+        let precompiles = precompile_set::<MockHost>();
+
+        let ticket = FaTicket {
+            ticketer: ContractKt1Hash::from_b58check(
+                "KT1BuEZtb68c1Q4yjtckcNjGELqWt56Xyesc",
+            )
+            .unwrap(),
+            token_id: U256::from(7),
+            metadata: Some(vec![1, 2]),
+        };
+        let receiver = H160::from_low_u64_be(0xa11ce);
+        let wrapper = ticket.wrapper_address();
+
+        for i in 0..2u8 {
+            let transaction = Transaction {
+                tx_hash: [i; TRANSACTION_HASH_SIZE],
+                content: TransactionContent::FaDeposit(FaDeposit {
+                    ticket: ticket.clone(),
+                    amount: U256::from(1000),
+                    receiver,
+                    depositor: PublicKeyHash::from_b58check(
+                        "tz1RjtZUVeLhADFHDL8UwDZA6vjWWhojpu5w",
+                    )
+                    .unwrap(),
+                }),
+            };
+            let result = apply_transaction(
+                &mut host,
+                &block_constants,
+                &precompiles,
+                &transaction,
+                i.into(),
+                &mut evm_account_storage,
+                &mut accounts_index,
+                10_000_000_000,
+                evm_execution::EvmFork::Shanghai,
+            )
+            .unwrap();
+            let ExecutionResult::Valid(info) = result else {
+                panic!("The deposit should be valid")
+            };
+            assert_eq!(info.receipt_info.to, Some(wrapper));
+            assert!(info.receipt_info.execution_outcome.unwrap().is_success);
+        }
+
+        let wrapper_account = evm_account_storage
+            .get(&host, &account_path(&wrapper).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(wrapper_account.code(&host).unwrap(), WRAPPER_CODE);
+
+        let mut balance_of = hex::decode("70a08231").unwrap();
+        balance_of.extend_from_slice(&[0; 12]);
+        balance_of.extend_from_slice(receiver.as_bytes());
+        let outcome = run_transaction(
+            &mut host,
+            &block_constants,
+            &mut evm_account_storage,
+            &precompiles,
+            evm_execution::EvmFork::Shanghai,
+            Some(wrapper),
+            H160::zero(),
+            balance_of,
+            None,
+            U256::zero(),
+            U256::zero(),
+            None,
+            false,
+            10_000_000_000,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            U256::from_big_endian(&outcome.result.unwrap()),
+            U256::from(2000)
+        );
+
+        let target =
+            Contract::from_b58check("tz1RjtZUVeLhADFHDL8UwDZA6vjWWhojpu5w").unwrap();
+        let withdrawal = Withdrawal {
+            target: target.clone(),
+            amount: U256::from(500),
+            ticket: Some(ticket.clone()),
+        };
+        // A call to layer 1 emitted before the withdrawal is posted first.
+        let call = L1Call {
+            destination: ticket.ticketer.clone(),
+            entrypoint: Entrypoint::try_from(String::from("ping")).unwrap(),
+            parameters: MichelineExpr::from_bytes(vec![0x00, 0x2a]).unwrap(),
+            // nat
+            parameters_ty: MichelineExpr::from_bytes(vec![0x03, 0x62]).unwrap(),
+        };
+        post_withdrawals(
+            &mut host,
+            &[1; 32],
+            &vec![L1Message::Call(call), L1Message::Withdrawal(withdrawal)],
+        )
+        .unwrap();
+        // The messages are queued until the end of the block.
+        assert!(host.outbox_at(host.level()).is_empty());
+        outbox::flush_queue(&mut host, u64::MAX).unwrap();
+
+        // Neither message can be batched.
+        let outbox = host.outbox_at(host.level());
+        assert_eq!(outbox.len(), 2);
+        let (_, OutboxMessage::AtomicTransactionBatchTyped(batch)) =
+            OutboxMessage::<MichelineExpr>::nom_read(&outbox[0]).unwrap()
+        else {
+            panic!("The call is not in a typed outbox message")
+        };
+        assert_eq!(batch.len(), 1);
+        assert_eq!(
+            batch[0].destination,
+            Contract::Originated(ticket.ticketer.clone())
+        );
+        assert_eq!(batch[0].entrypoint.name(), "ping");
+        assert_eq!(batch[0].parameters.as_bytes(), [0x00, 0x2a]);
+        assert_eq!(batch[0].parameters_ty.as_bytes(), [0x03, 0x62]);
+
+        let (_, message) =
+            OutboxMessage::<MichelsonPair<MichelsonContract, FA2_1Ticket>>::nom_read(
+                &outbox[1],
+            )
+            .unwrap();
+        let OutboxMessage::AtomicTransactionBatch(batch) = message else {
+            panic!("The withdrawal is not in an untyped outbox message")
+        };
+        assert_eq!(batch.len(), 1);
+        let transaction = &batch[0];
+        let ticketer = Contract::Originated(ticket.ticketer);
+        assert_eq!(transaction.destination, ticketer);
+        assert_eq!(transaction.entrypoint.name(), "withdraw");
+        let MichelsonPair(MichelsonContract(withdrawn_to), withdrawn) =
+            &transaction.parameters;
+        assert_eq!(*withdrawn_to, target);
+        assert_eq!(withdrawn.creator().0, ticketer);
+        assert_eq!(withdrawn.amount_as::<u64, _>().unwrap(), 500);
+        let MichelsonPair(MichelsonInt(token_id), MichelsonOption(metadata)) =
+            withdrawn.contents();
+        assert_eq!(token_id.0, 7.into());
+        assert_eq!(*metadata, Some(MichelsonBytes(vec![1, 2])));
+    }
+
+    #[test]
+    fn test_failed_fa_deposits_are_refunded() {
+        let mut host = MockHost::default();
+        let mut evm_account_storage =
+            evm_execution::account_storage::init_account_storage().unwrap();
+        let mut accounts_index = crate::storage::init_account_index().unwrap();
+        let block_constants = mock_block_constants();
+        let precompiles = precompile_set::<MockHost>();
+
+        let ticket = FaTicket {
+            ticketer: ContractKt1Hash::from_b58check(
+                "KT1BuEZtb68c1Q4yjtckcNjGELqWt56Xyesc",
+            )
+            .unwrap(),
+            token_id: U256::from(7),
+            metadata: None,
+        };
+        let depositor =
+            PublicKeyHash::from_b58check("tz1RjtZUVeLhADFHDL8UwDZA6vjWWhojpu5w").unwrap();
+
+        // The second deposit overflows the total supply of the wrapper, its
+        // mint reverts.
+        let mut outcomes = vec![];
+        for i in 0..2u8 {
+            let transaction = Transaction {
+                tx_hash: [i; TRANSACTION_HASH_SIZE],
+                content: TransactionContent::FaDeposit(FaDeposit {
+                    ticket: ticket.clone(),
+                    amount: U256::MAX,
+                    receiver: H160::from_low_u64_be(0xa11ce),
+                    depositor: depositor.clone(),
+                }),
+            };
+            let result = apply_transaction(
+                &mut host,
+                &block_constants,
+                &precompiles,
+                &transaction,
+                i.into(),
+                &mut evm_account_storage,
+                &mut accounts_index,
+                10_000_000_000,
+                evm_execution::EvmFork::Shanghai,
+            )
+            .unwrap();
+            let ExecutionResult::Valid(info) = result else {
+                panic!("The deposit should be valid")
+            };
+            outcomes.push(info.receipt_info.execution_outcome.unwrap());
+        }
+        assert!(outcomes[0].is_success);
+        assert!(outcomes[0].withdrawals.is_empty());
+        assert!(!outcomes[1].is_success);
+        assert_eq!(
+            outcomes[1].withdrawals,
+            vec![L1Message::Withdrawal(Withdrawal {
+                target: Contract::Implicit(depositor),
+                amount: U256::MAX,
+                ticket: Some(ticket.clone()),
+            })]
+        );
+
+        // The refund is sent to the ticketer, like the withdrawals.
+        outbox::flush_queue(&mut host, u64::MAX).unwrap();
+        let outbox = host.outbox_at(host.level());
+        assert_eq!(outbox.len(), 1);
+        let (_, OutboxMessage::AtomicTransactionBatch(batch)) =
+            OutboxMessage::<MichelsonPair<MichelsonContract, FA2_1Ticket>>::nom_read(
+                &outbox[0],
+            )
+            .unwrap()
+        else {
+            panic!("The refund is not in an untyped outbox message")
+        };
+        assert_eq!(batch[0].destination, Contract::Originated(ticket.ticketer));
+        assert_eq!(batch[0].entrypoint.name(), "withdraw");
+    }
 }
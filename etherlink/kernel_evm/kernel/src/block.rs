@@ -8,7 +8,10 @@
 use crate::apply::{apply_transaction, ExecutionInfo, ExecutionResult};
 use crate::blueprint_storage::{drop_head_blueprint, read_next_blueprint};
 use crate::error::Error;
+use crate::fork_schedule::{read_fork_schedule, ForkSchedule};
+use crate::gas_price;
 use crate::indexable_storage::IndexableStorage;
+use crate::outbox;
 use crate::safe_storage::KernelRuntime;
 use crate::storage;
 use crate::storage::init_account_index;
@@ -51,6 +54,7 @@ pub enum ComputationResult {
     Finished,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn compute<Host: Runtime>(
     host: &mut Host,
     block_in_progress: &mut BlockInProgress,
@@ -58,8 +62,10 @@ fn compute<Host: Runtime>(
     precompiles: &PrecompileBTreeMap<Host>,
     evm_account_storage: &mut EthereumAccountStorage,
     accounts_index: &mut IndexableStorage,
+    fork_schedule: &ForkSchedule,
     is_first_block_of_reboot: bool,
 ) -> Result<ComputationResult, anyhow::Error> {
+    let fork = fork_schedule.fork_at(block_in_progress.number);
     log!(
         host,
         Debug,
@@ -88,11 +94,13 @@ fn compute<Host: Runtime>(
             evm_account_storage,
             accounts_index,
             allocated_ticks,
+            fork,
         )? {
             ExecutionResult::Valid(ExecutionInfo {
                 receipt_info,
                 object_info,
                 estimated_ticks_used,
+                ..
             }) => {
                 block_in_progress.register_valid_transaction(
                     &transaction,
@@ -150,7 +158,7 @@ fn next_bip_from_blueprints<Host: Runtime>(
     match read_next_blueprint(host, config)? {
         Some(blueprint) => {
             if let Some(kernel_upgrade) = kernel_upgrade {
-                if blueprint.timestamp >= kernel_upgrade.activation_timestamp {
+                if kernel_upgrade.is_active(host, blueprint.timestamp)? {
                     upgrade::upgrade(host, kernel_upgrade.preimage_hash)?;
                     // We abort the call, as there is no blueprint to execute,
                     // the kernel will reboot.
@@ -180,6 +188,7 @@ fn compute_bip<Host: KernelRuntime>(
     precompiles: &PrecompileBTreeMap<Host>,
     evm_account_storage: &mut EthereumAccountStorage,
     accounts_index: &mut IndexableStorage,
+    fork_schedule: &ForkSchedule,
     tick_counter: &mut TickCounter,
     first_block_of_reboot: &mut bool,
 ) -> anyhow::Result<ComputationResult> {
@@ -190,6 +199,7 @@ fn compute_bip<Host: KernelRuntime>(
         precompiles,
         evm_account_storage,
         accounts_index,
+        fork_schedule,
         *first_block_of_reboot,
     )?;
     match result {
@@ -210,8 +220,15 @@ fn compute_bip<Host: KernelRuntime>(
                 .context("Failed to finalize the block in progress")?;
             *current_block_number = new_block.number + 1;
             *current_block_parent_hash = new_block.hash;
-            *current_constants = new_block
-                .constants(current_constants.chain_id, current_constants.block_fees);
+            let block_fees = gas_price::update_block_fees(
+                host,
+                current_constants.block_fees,
+                new_block.gas_used,
+            )?;
+            *current_constants = BlockConstants {
+                coinbase: current_constants.coinbase,
+                ..new_block.constants(current_constants.chain_id, block_fees)
+            };
             // Drop the processed blueprint from the storage
             drop_head_blueprint(host)?;
 
@@ -221,6 +238,20 @@ fn compute_bip<Host: KernelRuntime>(
     Ok(result)
 }
 
+/// Flushes the messages to layer 1 queued by the blocks, with the ticks left
+/// in the kernel run. It is called once the blueprints of the level are
+/// applied, so that the messages of its blocks are batched together.
+fn flush_outbox_queue<Host: Runtime>(
+    host: &mut Host,
+    tick_counter: &mut TickCounter,
+) -> Result<(), Error> {
+    let max_ticks =
+        tick_model::constants::MAX_ALLOWED_TICKS.saturating_sub(tick_counter.c);
+    let ticks = outbox::flush_queue(host, max_ticks)?;
+    tick_counter.c = tick_counter.c.saturating_add(ticks);
+    Ok(())
+}
+
 pub fn produce<Host: KernelRuntime>(
     host: &mut Host,
     chain_id: U256,
@@ -228,6 +259,7 @@ pub fn produce<Host: KernelRuntime>(
     config: &mut Configuration,
 ) -> Result<ComputationResult, anyhow::Error> {
     let kernel_upgrade = upgrade::read_kernel_upgrade(host)?;
+    let fork_schedule = read_fork_schedule(host)?;
 
     let (mut current_constants, mut current_block_number, mut current_block_parent_hash) =
         match storage::read_current_block(host) {
@@ -246,6 +278,9 @@ pub fn produce<Host: KernelRuntime>(
                 )
             }
         };
+    if let Some(coinbase) = storage::read_sequencer_coinbase(host) {
+        current_constants.coinbase = coinbase
+    }
     let mut evm_account_storage =
         init_account_storage().context("Failed to initialize EVM account storage")?;
     let mut accounts_index = init_account_index()?;
@@ -265,6 +300,7 @@ pub fn produce<Host: KernelRuntime>(
             &precompiles,
             &mut evm_account_storage,
             &mut accounts_index,
+            &fork_schedule,
             &mut tick_counter,
             &mut first_block_of_reboot,
         )? {
@@ -296,6 +332,7 @@ pub fn produce<Host: KernelRuntime>(
             &precompiles,
             &mut evm_account_storage,
             &mut accounts_index,
+            &fork_schedule,
             &mut tick_counter,
             &mut first_block_of_reboot,
         )? {
@@ -305,6 +342,7 @@ pub fn produce<Host: KernelRuntime>(
             }
         }
     }
+    flush_outbox_queue(host, &mut tick_counter)?;
     log!(host, Debug, "Estimated ticks: {}", tick_counter.c);
     Ok(ComputationResult::Finished)
 }
@@ -325,7 +363,7 @@ mod tests {
     use crate::tick_model;
     use crate::{retrieve_block_fees, retrieve_chain_id};
     use evm_execution::account_storage::{
-        account_path, init_account_storage, EthereumAccountStorage,
+        account_path, init_account_storage, EthereumAccountStorage, EMPTY_TRIE_ROOT,
     };
     use primitive_types::{H160, H256, U256};
     use std::str::FromStr;
@@ -334,7 +372,11 @@ mod tests {
     };
     use tezos_ethereum::tx_common::EthereumTransactionCommon;
     use tezos_ethereum::tx_signature::TxSignature;
+    use tezos_ethereum::withdrawal::MichelineExpr;
     use tezos_smart_rollup_core::SmartRollupCore;
+    use tezos_smart_rollup_encoding::contract::Contract;
+    use tezos_smart_rollup_encoding::entrypoint::Entrypoint;
+    use tezos_smart_rollup_encoding::outbox::OutboxMessageTransaction;
     use tezos_smart_rollup_encoding::timestamp::Timestamp;
     use tezos_smart_rollup_host::path::RefPath;
     use tezos_smart_rollup_mock::MockHost;
@@ -392,7 +434,7 @@ mod tests {
     const DUMMY_BASE_FEE_PER_GAS: u64 = 21000u64;
 
     fn dummy_block_fees() -> BlockFees {
-        BlockFees::new(DUMMY_BASE_FEE_PER_GAS.into())
+        BlockFees::new(DUMMY_BASE_FEE_PER_GAS.into(), U256::zero())
     }
 
     fn dummy_eth_gen_transaction(
@@ -702,6 +744,57 @@ mod tests {
         assert_eq!(dest_balance, U256::from(1000000000u64))
     }
 
+    #[test]
+    fn test_block_roots_match_reference_implementation() {
+        let mut mock_host = MockHost::default();
+        let mut internal = MockInternal();
+        let mut host = SafeStorage {
+            host: &mut mock_host,
+            internal: &mut internal,
+        };
+        let mut evm_account_storage = init_account_storage().unwrap();
+
+        produce_block_with_several_valid_txs(&mut host, &mut evm_account_storage);
+
+        let block = storage::read_current_block(&mut host).unwrap();
+        let transactions: Vec<Vec<u8>> = vec![
+            dummy_eth_transaction_zero().to_bytes(),
+            dummy_eth_transaction_one().to_bytes(),
+        ];
+        let receipts: Vec<Vec<u8>> = block
+            .transactions
+            .iter()
+            .map(|hash| {
+                let receipt = read_transaction_receipt(&mut host, hash).unwrap();
+                let data = ethereum::EIP658ReceiptData {
+                    status_code: 1,
+                    used_gas: receipt.cumulative_gas_used,
+                    logs_bloom: receipt.logs_bloom,
+                    logs: vec![],
+                };
+                ethereum::EnvelopedEncodable::encode(&ethereum::ReceiptV3::Legacy(data))
+                    .to_vec()
+            })
+            .collect();
+
+        // The reference implementation is the `ethereum` crate
+        assert_eq!(
+            block.transactions_root,
+            ethereum::util::ordered_trie_root(transactions)
+                .as_bytes()
+                .to_vec()
+        );
+        assert_eq!(
+            block.receipts_root,
+            ethereum::util::ordered_trie_root(receipts)
+                .as_bytes()
+                .to_vec()
+        );
+        let state_root = crate::world_state::read_state_root(&host).unwrap();
+        assert_ne!(state_root, EMPTY_TRIE_ROOT);
+        assert_eq!(block.state_root, state_root.as_bytes().to_vec());
+    }
+
     #[test]
     // Test if several valid proposals can produce valid blocks
     fn test_several_valid_proposals() {
@@ -875,7 +968,9 @@ mod tests {
 
         let expected_dest_balance = U256::from(500000000u64);
         let expected_gas = 21000;
-        let expected_fees = dummy_block_fees().base_fee_per_gas() * expected_gas;
+        let expected_fees = dummy_eth_transaction_zero()
+            .effective_gas_price(&dummy_block_fees())
+            * expected_gas;
         let expected_sender_balance =
             initial_sender_balance - expected_dest_balance - expected_fees;
 
@@ -883,6 +978,65 @@ mod tests {
         assert_eq!(sender_balance, expected_sender_balance);
     }
 
+    #[test]
+    fn test_priority_fees_are_paid_to_coinbase() {
+        let mut mock_host = MockHost::default();
+        let mut internal = MockInternal();
+        let mut host = SafeStorage {
+            host: &mut mock_host,
+            internal: &mut internal,
+        };
+
+        let coinbase =
+            H160::from_str("a0b1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9").unwrap();
+        host.store_write(
+            &RefPath::assert_from(b"/sequencer_coinbase"),
+            coinbase.as_bytes(),
+            0,
+        )
+        .unwrap();
+
+        let tx = Transaction {
+            tx_hash: [0; TRANSACTION_HASH_SIZE],
+            content: Ethereum(dummy_eth_transaction_zero()),
+        };
+        store_blueprints(&mut host, vec![blueprint(vec![tx])]);
+
+        let sender = dummy_eth_caller();
+        let mut evm_account_storage = init_account_storage().unwrap();
+        set_balance(
+            &mut host,
+            &mut evm_account_storage,
+            &sender,
+            U256::from(10000000000000000000u64),
+        );
+
+        let block_fees =
+            BlockFees::new(U256::from(DUMMY_BASE_FEE_PER_GAS) * 2, U256::zero());
+        produce(
+            &mut host,
+            DUMMY_CHAIN_ID,
+            block_fees,
+            &mut Configuration::Proxy,
+        )
+        .expect("The block production failed.");
+
+        // The coinbase receives the gas price above the base fee
+        let coinbase_balance =
+            get_balance(&mut host, &mut evm_account_storage, &coinbase);
+        let gas_price = dummy_eth_transaction_zero().effective_gas_price(&block_fees);
+        let expected_tips = (gas_price - block_fees.base_fee_per_gas()) * 21000;
+        assert_eq!(coinbase_balance, expected_tips);
+
+        // The block records its base fee, and the next one is lower as the
+        // block is below the gas target
+        let block = storage::read_current_block(&mut host).unwrap();
+        assert_eq!(block.base_fee_per_gas, Some(block_fees.base_fee_per_gas()));
+        let next_base_fee_per_gas = storage::read_base_fee_per_gas(&mut host).unwrap();
+        assert!(next_base_fee_per_gas < block_fees.base_fee_per_gas());
+        assert!(next_base_fee_per_gas >= U256::from(crate::BASE_FEE_PER_GAS));
+    }
+
     #[test]
     //Test accounts are indexed at the end of the block production
     fn test_accounts_are_indexed() {
@@ -1110,6 +1264,7 @@ mod tests {
             &precompiles,
             &mut evm_account_storage,
             &mut accounts_index,
+            &ForkSchedule::default(),
             true,
         )
         .expect("Should have computed block");
@@ -1163,7 +1318,7 @@ mod tests {
         // Ensures the caller has enough balance to pay for the fees, but not
         // the transaction itself, otherwise the transaction will not even be
         // taken into account.
-        let fees = U256::from(21000) * tx.execution_gas_limit();
+        let fees = U256::from(21000) * tx.gas_limit_with_fees();
         set_balance(&mut host, &mut evm_account_storage, &caller, fees);
 
         // Prepare a invalid transaction, i.e. with not enough funds.
@@ -1259,6 +1414,45 @@ mod tests {
         check_current_block_number(&mut host, 2);
     }
 
+    #[test]
+    fn test_outbox_queue_is_flushed_after_blocks() {
+        let mut mock_host = MockHost::default();
+        let mut internal = MockInternal();
+        let mut host = SafeStorage {
+            host: &mut mock_host,
+            internal: &mut internal,
+        };
+        let level = host.host.level();
+        outbox::start_level(&mut host, level).unwrap();
+        let transaction = OutboxMessageTransaction {
+            parameters: MichelineExpr::from_bytes(vec![0x00, 0x2a]).unwrap(),
+            destination: Contract::from_b58check("KT1BuEZtb68c1Q4yjtckcNjGELqWt56Xyesc")
+                .unwrap(),
+            entrypoint: Entrypoint::default(),
+        };
+        outbox::push_transaction(&mut host, &[1; 32], transaction, false).unwrap();
+
+        let blueprint = almost_empty_blueprint();
+        store_inbox_blueprint(&mut host, blueprint).expect("Should store a blueprint");
+        produce(
+            &mut host,
+            DUMMY_CHAIN_ID,
+            dummy_block_fees(),
+            &mut Configuration::Proxy,
+        )
+        .expect("Empty block should have been produced");
+
+        assert_eq!(host.host.outbox_at(level).len(), 1);
+        assert_eq!(
+            outbox::read_outbox_receipts(&host, &[1; 32]).unwrap(),
+            vec![outbox::OutboxReceipt {
+                level,
+                message_index: 0,
+                transaction_index: 0,
+            }]
+        );
+    }
+
     fn hash_from_nonce(nonce: u64) -> TransactionHash {
         let nonce = u64::to_le_bytes(nonce);
         let mut hash = [0; 32];
@@ -9,12 +9,13 @@ use crate::apply::{TransactionObjectInfo, TransactionReceiptInfo};
 use crate::error::Error;
 use crate::error::TransferError::CumulativeGasUsedOverflow;
 use crate::inbox::Transaction;
+use crate::logs_index;
 use crate::safe_storage::KernelRuntime;
 use crate::storage;
-use crate::storage::{EVM_TRANSACTIONS_OBJECTS, EVM_TRANSACTIONS_RECEIPTS};
 use crate::tick_model;
+use crate::world_state;
 use anyhow::Context;
-use evm_execution::account_storage::EVM_ACCOUNTS_PATH;
+use ethereum::util::ordered_trie_root;
 use primitive_types::{H256, U256};
 use rlp::{Decodable, DecoderError, Encodable};
 use std::collections::VecDeque;
@@ -22,12 +23,11 @@ use tezos_ethereum::block::{BlockConstants, L2Block};
 use tezos_ethereum::rlp_helpers::*;
 use tezos_ethereum::transaction::{
     IndexedLog, TransactionObject, TransactionReceipt, TransactionStatus,
-    TransactionType, TRANSACTION_HASH_SIZE,
+    TRANSACTION_HASH_SIZE,
 };
 use tezos_ethereum::Bloom;
 use tezos_evm_logging::{log, Level::*};
 use tezos_smart_rollup_encoding::timestamp::Timestamp;
-use tezos_smart_rollup_host::path::RefPath;
 use tezos_smart_rollup_host::runtime::Runtime;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -59,11 +59,15 @@ pub struct BlockInProgress {
     pub logs_offset: u64,
     /// Timestamp
     pub timestamp: Timestamp,
+    /// encodings of the valid transactions, leaves of the transactions trie
+    encoded_transactions: Vec<Vec<u8>>,
+    /// encodings of the receipts, leaves of the receipts trie
+    encoded_receipts: Vec<Vec<u8>>,
 }
 
 impl Encodable for BlockInProgress {
     fn rlp_append(&self, stream: &mut rlp::RlpStream) {
-        stream.begin_list(10);
+        stream.begin_list(12);
         stream.append(&self.number);
         append_queue(stream, &self.tx_queue);
         append_txs(stream, &self.valid_txs);
@@ -74,6 +78,8 @@ impl Encodable for BlockInProgress {
         stream.append(&self.logs_bloom);
         stream.append(&self.logs_offset);
         append_timestamp(stream, self.timestamp);
+        stream.append_list::<Vec<u8>, Vec<u8>>(&self.encoded_transactions);
+        stream.append_list::<Vec<u8>, Vec<u8>>(&self.encoded_receipts);
     }
 }
 
@@ -96,7 +102,11 @@ impl Decodable for BlockInProgress {
         if !decoder.is_list() {
             return Err(DecoderError::RlpExpectedToBeList);
         }
-        if decoder.item_count()? != 10 {
+        // The previous encoding has 10 elements, without the encodings of the
+        // transactions and receipts. The kernel is upgraded between blocks, so
+        // such a block in progress has no valid transaction yet.
+        let item_count = decoder.item_count()?;
+        if item_count != 10 && item_count != 12 {
             return Err(DecoderError::RlpIncorrectListLen);
         }
 
@@ -112,6 +122,12 @@ impl Decodable for BlockInProgress {
         let logs_bloom: Bloom = decode_field(&next(&mut it)?, "logs_bloom")?;
         let logs_offset: u64 = decode_field(&next(&mut it)?, "logs_offset")?;
         let timestamp = decode_timestamp(&next(&mut it)?)?;
+        let (encoded_transactions, encoded_receipts): (Vec<Vec<u8>>, Vec<Vec<u8>>) =
+            if item_count == 12 {
+                (next(&mut it)?.as_list()?, next(&mut it)?.as_list()?)
+            } else {
+                (vec![], vec![])
+            };
         let estimated_ticks: u64 = 0;
         let bip = Self {
             number,
@@ -125,6 +141,8 @@ impl Decodable for BlockInProgress {
             logs_bloom,
             logs_offset,
             timestamp,
+            encoded_transactions,
+            encoded_receipts,
         };
         Ok(bip)
     }
@@ -181,6 +199,8 @@ impl BlockInProgress {
             logs_bloom: Bloom::default(),
             logs_offset: 0,
             timestamp,
+            encoded_transactions: Vec::new(),
+            encoded_receipts: Vec::new(),
         }
     }
 
@@ -245,10 +265,18 @@ impl BlockInProgress {
 
         // register transaction as done
         self.valid_txs.push(transaction.tx_hash);
+        let encoded_transaction = transaction.trie_encoding();
+        self.estimated_ticks +=
+            tick_model::ticks_of_trie_leaf(encoded_transaction.len() as u64);
+        self.encoded_transactions.push(encoded_transaction);
         self.index += 1;
 
         // make receipt
         let receipt = self.make_receipt(receipt_info);
+        let encoded_receipt = receipt.trie_encoding();
+        self.estimated_ticks +=
+            tick_model::ticks_of_trie_leaf(encoded_receipt.len() as u64);
+        self.encoded_receipts.push(encoded_receipt);
         let receipt_bloom_size: u64 = tick_model::bloom_size(&receipt.logs).try_into()?;
         log!(
             host,
@@ -259,6 +287,11 @@ impl BlockInProgress {
         // extend BIP's logs bloom
         self.logs_bloom.accrue_bloom(&receipt.logs_bloom);
 
+        // index the logs by address and topic
+        logs_index::index_logs(host, receipt.block_number, receipt.index, &receipt.logs)
+            .context("Failed to index the logs")?;
+        self.estimated_ticks += tick_model::ticks_of_logs_index(&receipt.logs);
+
         // store info
         let receipt_size = storage::store_transaction_receipt(host, &receipt)
             .context("Failed to store the receipt")?;
@@ -277,35 +310,24 @@ impl BlockInProgress {
         self.estimated_ticks += tick_model::ticks_of_invalid_transaction(tx_data_size);
     }
 
-    fn safe_store_get_hash<Host: KernelRuntime>(
-        host: &mut Host,
-        path: Option<RefPath>,
-    ) -> Result<Vec<u8>, anyhow::Error> {
-        match host.store_get_hash(path) {
-            Ok(hash) => Ok(hash),
-            _ => Ok(L2Block::dummy_hash()),
-        }
-    }
-
     pub fn finalize_and_store<Host: KernelRuntime>(
         self,
         host: &mut Host,
     ) -> Result<L2Block, anyhow::Error> {
-        let state_root = Self::safe_store_get_hash(host, Some(EVM_ACCOUNTS_PATH))?;
-        let receipts_root =
-            Self::safe_store_get_hash(host, Some(EVM_TRANSACTIONS_RECEIPTS))?;
-        let transactions_root =
-            Self::safe_store_get_hash(host, Some(EVM_TRANSACTIONS_OBJECTS))?;
+        let state_root = world_state::read_state_root(host)?;
+        let receipts_root = ordered_trie_root(&self.encoded_receipts);
+        let transactions_root = ordered_trie_root(&self.encoded_transactions);
         let new_block = L2Block::new(
             self.number,
             self.valid_txs,
             self.timestamp,
             self.parent_hash,
             self.logs_bloom,
-            transactions_root,
-            state_root,
-            receipts_root,
+            transactions_root.as_bytes().to_vec(),
+            state_root.as_bytes().to_vec(),
+            receipts_root.as_bytes().to_vec(),
             self.cumulative_gas,
+            self.gas_price,
         );
         storage::store_current_block(host, &new_block)
             .context("Failed to store the current block")?;
@@ -335,6 +357,8 @@ impl BlockInProgress {
             to,
             execution_outcome,
             effective_gas_price,
+            da_fee,
+            type_,
             ..
         } = receipt_info;
 
@@ -365,10 +389,11 @@ impl BlockInProgress {
                     cumulative_gas_used: cumulative_gas,
                     effective_gas_price,
                     gas_used: U256::from(outcome.gas_used),
+                    da_fee,
                     contract_address: outcome.new_address,
                     logs_bloom: TransactionReceipt::logs_to_bloom(&logs),
                     logs,
-                    type_: TransactionType::Legacy,
+                    type_,
                     status: if outcome.is_success {
                         TransactionStatus::Success
                     } else {
@@ -385,10 +410,11 @@ impl BlockInProgress {
                 cumulative_gas_used: cumulative_gas,
                 effective_gas_price,
                 gas_used: U256::zero(),
+                da_fee,
                 contract_address: None,
                 logs: vec![],
                 logs_bloom: Bloom::default(),
-                type_: TransactionType::Legacy,
+                type_,
                 status: TransactionStatus::Failure,
             },
         }
@@ -416,15 +442,22 @@ mod tests {
 
     use super::BlockInProgress;
     use crate::inbox::{Deposit, Transaction, TransactionContent};
+    use crate::mock_internal::MockInternal;
+    use crate::safe_storage::SafeStorage;
+    use ethereum::Log;
     use primitive_types::{H160, H256, U256};
     use rlp::{Decodable, Encodable, Rlp};
     use tezos_ethereum::{
-        transaction::{TransactionType, TRANSACTION_HASH_SIZE},
+        transaction::{
+            IndexedLog, TransactionReceipt, TransactionStatus, TransactionType,
+            TRANSACTION_HASH_SIZE,
+        },
         tx_common::EthereumTransactionCommon,
         tx_signature::TxSignature,
         Bloom,
     };
     use tezos_smart_rollup_encoding::timestamp::Timestamp;
+    use tezos_smart_rollup_mock::MockHost;
 
     fn new_sig_unsafe(v: u64, r: H256, s: H256) -> TxSignature {
         TxSignature::new(U256::from(v), r, s).unwrap()
@@ -482,10 +515,12 @@ mod tests {
             logs_bloom: Bloom::default(),
             logs_offset: 33,
             timestamp: Timestamp::from(0i64),
+            encoded_transactions: vec![vec![1, 2], vec![3]],
+            encoded_receipts: vec![vec![4, 5]],
         };
 
         let encoded = bip.rlp_bytes();
-        let expected = "f9025e2af8e6f871a00101010101010101010101010101010101010101010101010101010101010101f84e01b84bf84901010180018026a00101010101010101010101010101010101010101010101010101010101010101a00101010101010101010101010101010101010101010101010101010101010101f871a00808080808080808080808080808080808080808080808080808080808080808f84e01b84bf84908080880088034a00808080808080808080808080808080808080808080808080808080808080808a00808080808080808080808080808080808080808080808080808080808080808f842a00202020202020202020202020202020202020202020202020202020202020202a00909090909090909090909090909090909090909090909090909090909090909030405a00505050505050505050505050505050505050505050505050505050505050505b901000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000021880000000000000000";
+        let expected = "f902672af8e6f871a00101010101010101010101010101010101010101010101010101010101010101f84e01b84bf84901010180018026a00101010101010101010101010101010101010101010101010101010101010101a00101010101010101010101010101010101010101010101010101010101010101f871a00808080808080808080808080808080808080808080808080808080808080808f84e01b84bf84908080880088034a00808080808080808080808080808080808080808080808080808080808080808a00808080808080808080808080808080808080808080808080808080808080808f842a00202020202020202020202020202020202020202020202020202020202020202a00909090909090909090909090909090909090909090909090909090909090909030405a00505050505050505050505050505050505050505050505050505050505050505b901000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000021880000000000000000c482010203c3820405";
 
         assert_eq!(hex::encode(encoded), expected);
 
@@ -516,10 +551,12 @@ mod tests {
             logs_bloom: Bloom::default(),
             logs_offset: 0,
             timestamp: Timestamp::from(0i64),
+            encoded_transactions: vec![],
+            encoded_receipts: vec![],
         };
 
         let encoded = bip.rlp_bytes();
-        let expected = "f901f02af878f83aa00101010101010101010101010101010101010101010101010101010101010101d802d601940101010101010101010101010101010101010101f83aa00808080808080808080808080808080808080808080808080808080808080808d802d608940808080808080808080808080808080808080808f842a00202020202020202020202020202020202020202020202020202020202020202a00909090909090909090909090909090909090909090909090909090909090909030405a00505050505050505050505050505050505050505050505050505050505050505b901000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000080880000000000000000";
+        let expected = "f901f22af878f83aa00101010101010101010101010101010101010101010101010101010101010101d802d601940101010101010101010101010101010101010101f83aa00808080808080808080808080808080808080808080808080808080808080808d802d608940808080808080808080808080808080808080808f842a00202020202020202020202020202020202020202020202020202020202020202a00909090909090909090909090909090909090909090909090909090909090909030405a00505050505050505050505050505050505050505050505050505050505050505b901000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000080880000000000000000c0c0";
 
         assert_eq!(hex::encode(encoded), expected);
 
@@ -550,10 +587,12 @@ mod tests {
             logs_bloom: Bloom::default(),
             logs_offset: 4,
             timestamp: Timestamp::from(0i64),
+            encoded_transactions: vec![],
+            encoded_receipts: vec![],
         };
 
         let encoded = bip.rlp_bytes();
-        let expected = "f902272af8aff871a00101010101010101010101010101010101010101010101010101010101010101f84e01b84bf84901010180018026a00101010101010101010101010101010101010101010101010101010101010101a00101010101010101010101010101010101010101010101010101010101010101f83aa00808080808080808080808080808080808080808080808080808080808080808d802d608940808080808080808080808080808080808080808f842a00202020202020202020202020202020202020202020202020202020202020202a00909090909090909090909090909090909090909090909090909090909090909030405a00505050505050505050505050505050505050505050505050505050505050505b901000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000004880000000000000000";
+        let expected = "f902292af8aff871a00101010101010101010101010101010101010101010101010101010101010101f84e01b84bf84901010180018026a00101010101010101010101010101010101010101010101010101010101010101a00101010101010101010101010101010101010101010101010101010101010101f83aa00808080808080808080808080808080808080808080808080808080808080808d802d608940808080808080808080808080808080808080808f842a00202020202020202020202020202020202020202020202020202020202020202a00909090909090909090909090909090909090909090909090909090909090909030405a00505050505050505050505050505050505050505050505050505050505050505b901000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000004880000000000000000c0c0";
 
         assert_eq!(hex::encode(encoded), expected);
 
@@ -569,4 +608,177 @@ mod tests {
         };
         assert_eq!(decoded, fresh_bip);
     }
+
+    #[test]
+    fn test_decode_bip_previous_encoding() {
+        // Encoding of a block in progress without the encodings of its
+        // transactions and receipts.
+        let encoded = "f901f02af878f83aa00101010101010101010101010101010101010101010101010101010101010101d802d601940101010101010101010101010101010101010101f83aa00808080808080808080808080808080808080808080808080808080808080808d802d608940808080808080808080808080808080808080808f842a00202020202020202020202020202020202020202020202020202020202020202a00909090909090909090909090909090909090909090909090909090909090909030405a00505050505050505050505050505050505050505050505050505050505050505b901000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000080880000000000000000";
+        let bytes = hex::decode(encoded).expect("Should be valid hex string");
+        let decoded =
+            BlockInProgress::decode(&Rlp::new(&bytes)).expect("Should have decoded data");
+
+        let expected = BlockInProgress {
+            number: U256::from(42),
+            tx_queue: vec![dummy_tx_deposit(1), dummy_tx_deposit(8)].into(),
+            valid_txs: vec![[2; TRANSACTION_HASH_SIZE], [9; TRANSACTION_HASH_SIZE]],
+            cumulative_gas: U256::from(3),
+            index: 4,
+            gas_price: U256::from(5),
+            parent_hash: H256::from([5; 32]),
+            estimated_ticks: 0,
+            logs_bloom: Bloom::default(),
+            logs_offset: 0,
+            timestamp: Timestamp::from(0i64),
+            encoded_transactions: vec![],
+            encoded_receipts: vec![],
+        };
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_finalize_ordered_roots() {
+        let mut mock_host = MockHost::default();
+        let mut internal = MockInternal();
+        let mut host = SafeStorage {
+            host: &mut mock_host,
+            internal: &mut internal,
+        };
+
+        // Roots of the blocks without transactions, e.g. block 1 of Ethereum
+        // mainnet.
+        let empty_root =
+            "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421";
+        let bip = BlockInProgress::new(U256::zero(), U256::one(), vec![].into());
+        let block = bip.finalize_and_store(&mut host).unwrap();
+        assert_eq!(hex::encode(&block.transactions_root), empty_root);
+        assert_eq!(hex::encode(&block.receipts_root), empty_root);
+
+        // The values are indexed by the RLP encoding of their position, as in
+        // the `trieanyorder` vectors of ethereum/tests.
+        let mut bip = BlockInProgress::new(U256::one(), U256::one(), vec![].into());
+        bip.encoded_transactions = vec![b"doe".to_vec(), b"reindeer".to_vec()];
+        let block = bip.finalize_and_store(&mut host).unwrap();
+        assert_eq!(
+            hex::encode(&block.transactions_root),
+            "e766d5d51b89dc39d981b41bda63248d7abce4f0225eefd023792a540bcffee3"
+        );
+        assert_eq!(hex::encode(&block.receipts_root), empty_root);
+    }
+
+    fn receipt(
+        type_: &str,
+        status: &str,
+        cumulative_gas_used: &str,
+        logs: Vec<IndexedLog>,
+    ) -> TransactionReceipt {
+        TransactionReceipt {
+            hash: [0; TRANSACTION_HASH_SIZE],
+            index: 0,
+            block_number: U256::zero(),
+            from: H160::zero(),
+            to: None,
+            cumulative_gas_used: U256::from_dec_str(cumulative_gas_used).unwrap(),
+            effective_gas_price: U256::zero(),
+            gas_used: U256::zero(),
+            da_fee: U256::zero(),
+            contract_address: None,
+            logs_bloom: TransactionReceipt::logs_to_bloom(&logs),
+            logs,
+            type_: match type_ {
+                "0" => TransactionType::Legacy,
+                "1" => TransactionType::Eip2930,
+                "2" => TransactionType::Eip1559,
+                _ => panic!("Unsupported transaction type {}", type_),
+            },
+            status: match status {
+                "1" => TransactionStatus::Success,
+                _ => TransactionStatus::Failure,
+            },
+        }
+    }
+
+    // Blocks of Ethereum networks, in the format described in the README of
+    // `tests/resources`. The transactions are decoded and the receipts built
+    // as the kernel does, and their roots must be the ones of the block.
+    #[test]
+    #[ignore = "requires the vectors of tests/resources/ethereum_blocks"]
+    fn test_roots_of_ethereum_blocks() {
+        let mut mock_host = MockHost::default();
+        let mut internal = MockInternal();
+        let mut host = SafeStorage {
+            host: &mut mock_host,
+            internal: &mut internal,
+        };
+        let bytes = |s: &str| {
+            if s == "-" {
+                vec![]
+            } else {
+                hex::decode(s).unwrap()
+            }
+        };
+
+        let mut checked = 0;
+        for entry in std::fs::read_dir("tests/resources/ethereum_blocks").unwrap() {
+            let path = entry.unwrap().path();
+            let vector = std::fs::read_to_string(&path).unwrap();
+            let mut roots = (None, None);
+            let mut bip = BlockInProgress::new(U256::one(), U256::one(), vec![].into());
+            let mut receipts: Vec<(Vec<&str>, Vec<IndexedLog>)> = vec![];
+            for line in vector.lines().filter(|line| !line.starts_with('#')) {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                match fields[..] {
+                    ["transactions_root", root] => roots.0 = Some(root),
+                    ["receipts_root", root] => roots.1 = Some(root),
+                    ["transaction", raw] => {
+                        let transaction = Transaction {
+                            tx_hash: [0; TRANSACTION_HASH_SIZE],
+                            content: TransactionContent::Ethereum(
+                                EthereumTransactionCommon::from_bytes(&bytes(raw))
+                                    .unwrap(),
+                            ),
+                        };
+                        bip.encoded_transactions.push(transaction.trie_encoding())
+                    }
+                    ["receipt", ..] => receipts.push((fields[1..].to_vec(), vec![])),
+                    ["log", address, data, ..] => {
+                        let log = Log {
+                            address: H160::from_slice(&bytes(address)),
+                            topics: fields[3..]
+                                .iter()
+                                .map(|topic| H256::from_slice(&bytes(topic)))
+                                .collect(),
+                            data: bytes(data),
+                        };
+                        let (_, logs) = receipts.last_mut().unwrap();
+                        let index = logs.len() as u64;
+                        logs.push(IndexedLog { log, index })
+                    }
+                    _ => (),
+                }
+            }
+            let (Some(transactions_root), Some(receipts_root)) = roots else {
+                continue;
+            };
+            for (fields, logs) in receipts {
+                let receipt = receipt(fields[0], fields[1], fields[2], logs);
+                bip.encoded_receipts.push(receipt.trie_encoding())
+            }
+            let block = bip.finalize_and_store(&mut host).unwrap();
+            assert_eq!(
+                hex::encode(&block.transactions_root),
+                transactions_root,
+                "{:?}",
+                path
+            );
+            assert_eq!(
+                hex::encode(&block.receipts_root),
+                receipts_root,
+                "{:?}",
+                path
+            );
+            checked += 1;
+        }
+        assert!(checked > 0, "No vector of Ethereum blocks");
+    }
 }
@@ -5,6 +5,7 @@
 use crate::blueprint::Blueprint;
 use crate::error::{Error, StorageError};
 use crate::sequencer_blueprint::{BlueprintWithDelayedHashes, SequencerBlueprint};
+use crate::sequencer_keys::{append_keys, decode_keys};
 use crate::stage_one::Configuration;
 use crate::storage::{
     read_current_block_number, read_rlp, store_read_slice, store_rlp, write_u256,
@@ -14,6 +15,7 @@ use primitive_types::U256;
 use rlp::{Decodable, DecoderError, Encodable};
 use tezos_ethereum::rlp_helpers;
 use tezos_evm_logging::{log, Level::*};
+use tezos_smart_rollup_encoding::public_key::PublicKey;
 use tezos_smart_rollup_host::path::*;
 use tezos_smart_rollup_host::runtime::{Runtime, RuntimeError};
 
@@ -31,9 +33,16 @@ const EVM_LAST_BLUEPRINT: RefPath = RefPath::assert_from(b"/blueprints/last");
 /// which can be chunked, and blueprints constructed from
 /// inbox messages. Note that the latter are only to be
 /// used when the kernel isn't running with a sequencer.
+///
+/// Encoding: `[0, chunk, signers]` for a sequencer chunk, with the keys
+/// which signed it (chunks stored without them have no third element), and
+/// `[1, blueprint]` for an inbox blueprint.
 #[derive(PartialEq, Debug, Clone)]
 enum StoreBlueprint {
-    SequencerChunk(Vec<u8>),
+    SequencerChunk {
+        chunk: Vec<u8>,
+        signers: Vec<PublicKey>,
+    },
     InboxBlueprint(Blueprint),
 }
 
@@ -42,13 +51,15 @@ const INBOX_BLUEPRINT_TAG: u8 = 1;
 
 impl Encodable for StoreBlueprint {
     fn rlp_append(&self, stream: &mut rlp::RlpStream) {
-        stream.begin_list(2);
         match &self {
-            StoreBlueprint::SequencerChunk(chunk) => {
+            StoreBlueprint::SequencerChunk { chunk, signers } => {
+                stream.begin_list(3);
                 stream.append(&SEQUENCER_CHUNK_TAG);
                 stream.append(chunk);
+                append_keys(stream, signers);
             }
             StoreBlueprint::InboxBlueprint(blueprint) => {
+                stream.begin_list(2);
                 stream.append(&INBOX_BLUEPRINT_TAG);
                 stream.append(blueprint);
             }
@@ -61,20 +72,25 @@ impl Decodable for StoreBlueprint {
         if !decoder.is_list() {
             return Err(DecoderError::RlpExpectedToBeList);
         }
-        if decoder.item_count()? != 2 {
-            return Err(DecoderError::RlpIncorrectListLen);
-        }
+        let item_count = decoder.item_count()?;
         let tag: u8 = decoder.at(0)?.as_val()?;
         let rest = decoder.at(1)?;
-        match tag {
-            SEQUENCER_CHUNK_TAG => {
+        match (tag, item_count) {
+            (SEQUENCER_CHUNK_TAG, 2 | 3) => {
                 let chunk: Vec<u8> = rest.as_val()?;
-                Ok(Self::SequencerChunk(chunk))
+                let signers = match item_count {
+                    3 => decode_keys(&decoder.at(2)?)?,
+                    _ => vec![],
+                };
+                Ok(Self::SequencerChunk { chunk, signers })
             }
-            INBOX_BLUEPRINT_TAG => {
+            (INBOX_BLUEPRINT_TAG, 2) => {
                 let blueprint = rlp_helpers::decode_field(&rest, "blueprint")?;
                 Ok(Self::InboxBlueprint(blueprint))
             }
+            (SEQUENCER_CHUNK_TAG | INBOX_BLUEPRINT_TAG, _) => {
+                Err(DecoderError::RlpIncorrectListLen)
+            }
             _ => Err(DecoderError::Custom("Unknown store blueprint tag.")),
         }
     }
@@ -166,17 +182,18 @@ pub fn store_sequencer_blueprint<Host: Runtime>(
     store_blueprint_nb_chunks(host, &blueprint_path, blueprint.blueprint.nb_chunks)?;
     let blueprint_chunk_path =
         blueprint_chunk_path(&blueprint_path, blueprint.blueprint.chunk_index)?;
-    let store_blueprint = StoreBlueprint::SequencerChunk(blueprint.blueprint.chunk);
+    let store_blueprint = StoreBlueprint::SequencerChunk {
+        chunk: blueprint.blueprint.chunk,
+        signers: blueprint.signers,
+    };
     store_rlp(&store_blueprint, host, &blueprint_chunk_path)
 }
 
-pub fn store_inbox_blueprint<Host: Runtime>(
+fn store_inbox_blueprint_by_number<Host: Runtime>(
     host: &mut Host,
     blueprint: Blueprint,
+    number: U256,
 ) -> Result<(), Error> {
-    let number = read_last_blueprint_number(host)?;
-    // We overflow, as the default is the max U256
-    let (number, _) = number.overflowing_add(U256::one());
     let blueprint_path = blueprint_path(number)?;
     store_last_blueprint_number(host, number)?;
     store_blueprint_nb_chunks(host, &blueprint_path, 1)?;
@@ -185,6 +202,30 @@ pub fn store_inbox_blueprint<Host: Runtime>(
     store_rlp(&store_blueprint, host, &chunk_path)
 }
 
+pub fn store_inbox_blueprint<Host: Runtime>(
+    host: &mut Host,
+    blueprint: Blueprint,
+) -> Result<(), Error> {
+    let number = read_last_blueprint_number(host)?;
+    // We overflow, as the default is the max U256
+    let (number, _) = number.overflowing_add(U256::one());
+    store_inbox_blueprint_by_number(host, blueprint, number)
+}
+
+/// Stores a blueprint made by the kernel as the next blueprint to execute,
+/// after removing the blueprints stored by the sequencer. The following
+/// inbox blueprints are numbered after it.
+pub fn store_forced_blueprint<Host: Runtime>(
+    host: &mut Host,
+    blueprint: Blueprint,
+) -> Result<(), Error> {
+    if host.store_has(&EVM_BLUEPRINTS)?.is_some() {
+        host.store_delete(&EVM_BLUEPRINTS)?;
+    }
+    let number = read_next_blueprint_number(host)?;
+    store_inbox_blueprint_by_number(host, blueprint, number)
+}
+
 fn read_next_blueprint_number<Host: Runtime>(host: &Host) -> Result<U256, Error> {
     match read_current_block_number(host) {
         Ok(number) => Ok(number.saturating_add(U256::one())),
@@ -228,7 +269,7 @@ fn read_all_chunks<Host: Runtime>(
                 // There must be only one chunk in this case.
                 return Ok(Some(blueprint));
             }
-            StoreBlueprint::SequencerChunk(chunk) => chunks.push(chunk),
+            StoreBlueprint::SequencerChunk { chunk, .. } => chunks.push(chunk),
         }
     }
     match config {
@@ -296,6 +337,9 @@ mod tests {
     use super::*;
     use crate::delayed_inbox::Hash;
     use crate::sequencer_blueprint::UnsignedSequencerBlueprint;
+    use crate::sequencer_keys::{
+        update_sequencers, KeySignature, SequencerKeys, SequencerUpdate, Sequencers,
+    };
     use crate::Timestamp;
     use tezos_crypto_rs::hash::ContractKt1Hash;
     use tezos_crypto_rs::hash::Signature;
@@ -303,6 +347,64 @@ mod tests {
     use tezos_smart_rollup_encoding::public_key::PublicKey;
     use tezos_smart_rollup_mock::MockHost;
 
+    /// Reads the keys which signed a stored chunk of a sequencer blueprint,
+    /// `None` if the chunk is not stored or is an inbox blueprint.
+    fn read_sequencer_chunk_signers<Host: Runtime>(
+        host: &Host,
+        number: U256,
+        chunk_index: u16,
+    ) -> Result<Option<Vec<PublicKey>>, Error> {
+        let blueprint_path = blueprint_path(number)?;
+        let chunk_path = blueprint_chunk_path(&blueprint_path, chunk_index)?;
+        if host.store_has(&chunk_path)?.is_none() {
+            return Ok(None);
+        }
+        match read_rlp(host, &chunk_path)? {
+            StoreBlueprint::SequencerChunk { signers, .. } => Ok(Some(signers)),
+            StoreBlueprint::InboxBlueprint(_) => Ok(None),
+        }
+    }
+
+    #[test]
+    fn test_sequencer_chunks_are_stored_with_their_signers() {
+        let mut host = MockHost::default();
+        let signer = PublicKey::from_b58check(
+            "edpkuDMUm7Y53wp4gxeLBXuiAhXZrLn8XB1R83ksvvesH8Lp8bmCfK",
+        )
+        .unwrap();
+        let blueprint = SequencerBlueprint {
+            blueprint: UnsignedSequencerBlueprint {
+                chunk: vec![1, 2, 3],
+                number: U256::from(7),
+                nb_chunks: 2,
+                chunk_index: 1,
+            },
+            signatures: vec![],
+            signers: vec![signer.clone()],
+        };
+        store_sequencer_blueprint(&mut host, blueprint).unwrap();
+        assert_eq!(
+            read_sequencer_chunk_signers(&host, U256::from(7), 1).unwrap(),
+            Some(vec![signer])
+        );
+        assert_eq!(
+            read_sequencer_chunk_signers(&host, U256::from(7), 0).unwrap(),
+            None
+        );
+
+        // Chunks stored before the signers are read without them.
+        let mut stream = rlp::RlpStream::new_list(2);
+        stream.append(&SEQUENCER_CHUNK_TAG);
+        stream.append(&vec![1u8, 2, 3]);
+        assert_eq!(
+            rlp::decode::<StoreBlueprint>(&stream.out()),
+            Ok(StoreBlueprint::SequencerChunk {
+                chunk: vec![1, 2, 3],
+                signers: vec![],
+            })
+        );
+    }
+
     #[test]
     fn test_invalid_sequencer_blueprint_is_removed() {
         let mut host = MockHost::default();
@@ -315,10 +417,20 @@ mod tests {
             "edpkuDMUm7Y53wp4gxeLBXuiAhXZrLn8XB1R83ksvvesH8Lp8bmCfK",
         )
         .unwrap();
+        update_sequencers(
+            &mut host,
+            SequencerUpdate {
+                keys: SequencerKeys::single(sequencer),
+                activation_level: 0,
+                overlap_levels: None,
+            },
+            0,
+        )
+        .unwrap();
         let mut config = Configuration::Sequencer {
             delayed_bridge,
             delayed_inbox: Box::new(delayed_inbox),
-            sequencer,
+            sequencers: Sequencers::read(&host).unwrap().unwrap(),
         };
 
         // Create empty blueprint with an invalid delayed hash
@@ -345,7 +457,11 @@ mod tests {
                 nb_chunks: 1u16,
                 chunk_index: 0u16,
             },
-            signature,
+            signatures: vec![KeySignature {
+                key_index: 0,
+                signature,
+            }],
+            signers: vec![],
         };
 
         // Store blueprint
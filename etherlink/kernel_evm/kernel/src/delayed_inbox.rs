@@ -2,27 +2,50 @@
 // SPDX-FileCopyrightText: 2024 Trilitech <contact@trili.tech>
 
 use crate::{
-    inbox::{Deposit, Transaction, TransactionContent},
+    inbox::{Deposit, FaDeposit, Transaction, TransactionContent},
     linked_list::LinkedList,
+    storage::{read_l1_level, read_last_info_per_level_timestamp, read_u256_or_default},
 };
 use anyhow::Result;
 use rlp::{Decodable, DecoderError, Encodable};
 use tezos_ethereum::{
-    transaction::TRANSACTION_HASH_SIZE, tx_common::EthereumTransactionCommon,
+    rlp_helpers::{append_timestamp, decode_field, decode_timestamp, next},
+    transaction::TRANSACTION_HASH_SIZE,
+    tx_common::EthereumTransactionCommon,
 };
 use tezos_evm_logging::{log, Level::*};
+use tezos_smart_rollup_encoding::timestamp::Timestamp;
 use tezos_smart_rollup_host::{path::RefPath, runtime::Runtime};
 
-pub struct DelayedInbox(LinkedList<Hash, DelayedTransaction>);
+pub struct DelayedInbox(LinkedList<Hash, DelayedInboxItem>);
 
 pub const DELAYED_INBOX_PATH: RefPath = RefPath::assert_from(b"/delayed-inbox");
 
+/// Path where the delayed inbox is rewritten by [migrate_legacy_items].
+const MIGRATED_DELAYED_INBOX_PATH: RefPath =
+    RefPath::assert_from(b"/delayed-inbox-migration");
+
+const DELAYED_INBOX_TIMEOUT: RefPath = RefPath::assert_from(b"/delayed_inbox_timeout");
+const DELAYED_INBOX_MIN_LEVELS: RefPath =
+    RefPath::assert_from(b"/delayed_inbox_min_levels");
+
+/// Number of seconds a delayed transaction can wait for the sequencer, used
+/// if no timeout is set in the durable storage.
+pub const DEFAULT_DELAYED_INBOX_TIMEOUT: u64 = 43_200;
+
+/// Number of L1 levels a delayed transaction can wait for the sequencer,
+/// used if no minimum is set in the durable storage.
+pub const DEFAULT_DELAYED_INBOX_MIN_LEVELS: u32 = 720;
+
 // Tag that indicates the delayed transaction is a eth transaction.
 pub const DELAYED_TRANSACTION_TAG: u8 = 0x01;
 
 // Tag that indicates the delayed transaction is a deposit.
 pub const DELAYED_DEPOSIT_TAG: u8 = 0x02;
 
+// Tag that indicates the delayed transaction is a FA deposit.
+pub const DELAYED_FA_DEPOSIT_TAG: u8 = 0x03;
+
 /// Hash of a transaction
 ///
 /// It represents the key of the transaction in the delayed inbox.
@@ -52,13 +75,12 @@ impl AsRef<[u8]> for Hash {
 }
 
 /// Delayed transaction
-/// Later it might be turned into a struct
-/// And fields like the timestamp might be added
 #[allow(clippy::large_enum_variant)]
 #[derive(Clone)]
 pub enum DelayedTransaction {
     Ethereum(EthereumTransactionCommon),
     Deposit(Deposit),
+    FaDeposit(FaDeposit),
 }
 
 impl Encodable for DelayedTransaction {
@@ -73,6 +95,10 @@ impl Encodable for DelayedTransaction {
                 stream.append(&DELAYED_DEPOSIT_TAG);
                 stream.append(delayed_deposit);
             }
+            DelayedTransaction::FaDeposit(delayed_deposit) => {
+                stream.append(&DELAYED_FA_DEPOSIT_TAG);
+                stream.append(delayed_deposit);
+            }
         }
     }
 }
@@ -97,28 +123,155 @@ impl Decodable for DelayedTransaction {
                 let deposit = Deposit::decode(&payload)?;
                 Ok(DelayedTransaction::Deposit(deposit))
             }
+            DELAYED_FA_DEPOSIT_TAG => {
+                let deposit = FaDeposit::decode(&payload)?;
+                Ok(DelayedTransaction::FaDeposit(deposit))
+            }
             _ => Err(DecoderError::Custom("unknown tag")),
         }
     }
 }
 
+/// Delayed transaction, with the timestamp and the level of the L1 block in
+/// which it was submitted.
+///
+/// Encoding: `[transaction, timestamp, level]`.
+#[derive(Clone)]
+pub struct DelayedInboxItem {
+    pub transaction: DelayedTransaction,
+    pub timestamp: Timestamp,
+    pub level: u32,
+}
+
+impl Encodable for DelayedInboxItem {
+    fn rlp_append(&self, stream: &mut rlp::RlpStream) {
+        stream.begin_list(3);
+        stream.append(&self.transaction);
+        append_timestamp(stream, self.timestamp);
+        stream.append(&self.level);
+    }
+}
+
+impl Decodable for DelayedInboxItem {
+    fn decode(decoder: &rlp::Rlp) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let transaction = decode_field(&next(&mut it)?, "transaction")?;
+        let timestamp = decode_timestamp(&next(&mut it)?)?;
+        let level = decode_field(&next(&mut it)?, "level")?;
+        Ok(Self {
+            transaction,
+            timestamp,
+            level,
+        })
+    }
+}
+
+/// Rewrites the transactions stored in the delayed inbox before it recorded
+/// their L1 block, encoded as a bare [DelayedTransaction]. They are given the
+/// timestamp and level of the last L1 block read by the kernel, which leaves
+/// the sequencer the whole timeout to include them.
+///
+/// At most `max_items` transactions are rewritten, returns true once the
+/// whole delayed inbox is rewritten.
+pub fn migrate_legacy_items<Host: Runtime>(
+    host: &mut Host,
+    max_items: usize,
+) -> Result<bool> {
+    let mut legacy: LinkedList<Hash, DelayedTransaction> =
+        LinkedList::new(&DELAYED_INBOX_PATH, host)?;
+    let mut migrated: LinkedList<Hash, DelayedInboxItem> =
+        LinkedList::new(&MIGRATED_DELAYED_INBOX_PATH, host)?;
+    if legacy.is_empty() && migrated.is_empty() {
+        return Ok(true);
+    }
+    let timestamp = read_last_info_per_level_timestamp(host)?;
+    let level = read_l1_level(host)?;
+
+    for _ in 0..max_items {
+        let Some(tx_hash) = legacy.first_id().copied() else { break };
+        let Some(transaction) = legacy.pop_first(host)? else { break };
+        let item = DelayedInboxItem {
+            transaction,
+            timestamp,
+            level,
+        };
+        migrated.push(host, &tx_hash, &item)?;
+    }
+    if !legacy.is_empty() {
+        return Ok(false);
+    }
+
+    migrated.move_to(host, &DELAYED_INBOX_PATH)?;
+    Ok(true)
+}
+
+fn transaction_of_delayed(tx_hash: Hash, delayed: DelayedTransaction) -> Transaction {
+    let content = match delayed {
+        DelayedTransaction::Ethereum(tx) => TransactionContent::Ethereum(tx),
+        DelayedTransaction::Deposit(deposit) => TransactionContent::Deposit(deposit),
+        DelayedTransaction::FaDeposit(deposit) => TransactionContent::FaDeposit(deposit),
+    };
+    Transaction {
+        tx_hash: tx_hash.0,
+        content,
+    }
+}
+
+pub fn read_delayed_inbox_timeout<Host: Runtime>(host: &Host) -> Result<u64> {
+    let timeout = read_u256_or_default(
+        host,
+        &DELAYED_INBOX_TIMEOUT,
+        DEFAULT_DELAYED_INBOX_TIMEOUT.into(),
+    )?;
+    Ok(timeout.try_into().unwrap_or(u64::MAX))
+}
+
+pub fn read_delayed_inbox_min_levels<Host: Runtime>(host: &Host) -> Result<u32> {
+    let min_levels = read_u256_or_default(
+        host,
+        &DELAYED_INBOX_MIN_LEVELS,
+        DEFAULT_DELAYED_INBOX_MIN_LEVELS.into(),
+    )?;
+    Ok(min_levels.try_into().unwrap_or(u32::MAX))
+}
+
 impl DelayedInbox {
     pub fn new<Host: Runtime>(host: &mut Host) -> Result<Self> {
         let linked_list = LinkedList::new(&DELAYED_INBOX_PATH, host)?;
         Ok(Self(linked_list))
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     pub fn save_transaction<Host: Runtime>(
         &mut self,
         host: &mut Host,
         tx: Transaction,
+        timestamp: Timestamp,
+        level: u32,
     ) -> Result<()> {
         let Transaction { tx_hash, content } = tx;
         let delayed_transaction = match content {
             TransactionContent::Ethereum(tx) => DelayedTransaction::Ethereum(tx),
             TransactionContent::Deposit(deposit) => DelayedTransaction::Deposit(deposit),
+            TransactionContent::FaDeposit(deposit) => {
+                DelayedTransaction::FaDeposit(deposit)
+            }
         };
-        self.0.push(host, &Hash(tx_hash), &delayed_transaction)?;
+        let item = DelayedInboxItem {
+            transaction: delayed_transaction,
+            timestamp,
+            level,
+        };
+        self.0.push(host, &Hash(tx_hash), &item)?;
         log!(
             host,
             Info,
@@ -139,27 +292,61 @@ impl DelayedInbox {
             "Removing transaction {} from the delayed inbox",
             hex::encode(tx_hash)
         );
-        let tx = self.0.remove(host, &tx_hash)?.map(|delayed| match delayed {
-            DelayedTransaction::Ethereum(tx) => Transaction {
-                tx_hash: tx_hash.0,
-                content: TransactionContent::Ethereum(tx),
-            },
-            DelayedTransaction::Deposit(deposit) => Transaction {
-                tx_hash: tx_hash.0,
-                content: TransactionContent::Deposit(deposit),
-            },
-        });
+        let tx = self
+            .0
+            .remove(host, &tx_hash)?
+            .map(|item| transaction_of_delayed(tx_hash, item.transaction));
 
         Ok(tx)
     }
+
+    /// Returns true if the oldest transaction of the delayed inbox was
+    /// submitted more than `timeout` seconds and `min_levels` levels before
+    /// the L1 block at `timestamp` and `level`.
+    pub fn first_has_timed_out<Host: Runtime>(
+        &self,
+        host: &Host,
+        timestamp: Timestamp,
+        level: u32,
+        timeout: u64,
+        min_levels: u32,
+    ) -> Result<bool> {
+        let Some(first) = self.0.first(host)? else { return Ok(false) };
+        let elapsed = timestamp.i64().saturating_sub(first.timestamp.i64());
+        let elapsed_levels = level.saturating_sub(first.level);
+        Ok(elapsed > timeout.try_into().unwrap_or(i64::MAX)
+            && elapsed_levels > min_levels)
+    }
+
+    /// Removes all the transactions of the delayed inbox and returns them,
+    /// oldest first.
+    pub fn remove_all<Host: Runtime>(
+        &mut self,
+        host: &mut Host,
+    ) -> Result<Vec<Transaction>> {
+        let mut transactions = vec![];
+        while let Some(tx_hash) = self.0.first_id().copied() {
+            match self.0.remove(host, &tx_hash)? {
+                Some(item) => {
+                    transactions.push(transaction_of_delayed(tx_hash, item.transaction))
+                }
+                None => break,
+            }
+        }
+        Ok(transactions)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::DelayedInbox;
     use super::Hash;
+    use super::{migrate_legacy_items, DelayedTransaction, DELAYED_INBOX_PATH};
     use crate::inbox::Transaction;
+    use crate::linked_list::LinkedList;
+    use crate::storage::{store_l1_level, store_last_info_per_level_timestamp};
     use primitive_types::{H160, U256};
+    use tezos_smart_rollup_encoding::timestamp::Timestamp;
 
     use crate::inbox::TransactionContent::Ethereum;
     use tezos_ethereum::{
@@ -204,7 +391,7 @@ mod tests {
 
         let tx: Transaction = dummy_transaction(0);
         delayed_inbox
-            .save_transaction(&mut host, tx.clone())
+            .save_transaction(&mut host, tx.clone(), Timestamp::from(0i64), 0)
             .expect("Tx should be saved in the delayed inbox");
 
         let mut delayed_inbox =
@@ -216,4 +403,77 @@ mod tests {
             .expect("Transaction should be in the delayed inbox");
         assert_eq!(tx, read_tx)
     }
+
+    #[test]
+    fn test_migrate_legacy_items() {
+        let mut host = MockHost::default();
+        store_last_info_per_level_timestamp(&mut host, Timestamp::from(1000)).unwrap();
+        store_l1_level(&mut host, 42).unwrap();
+        let mut legacy: LinkedList<Hash, DelayedTransaction> =
+            LinkedList::new(&DELAYED_INBOX_PATH, &host).unwrap();
+        for i in 0..5 {
+            let Transaction { tx_hash, content } = dummy_transaction(i);
+            let Ethereum(tx) = content else { unreachable!() };
+            legacy
+                .push(&mut host, &Hash(tx_hash), &DelayedTransaction::Ethereum(tx))
+                .unwrap();
+        }
+
+        assert!(!migrate_legacy_items(&mut host, 2).unwrap());
+        assert!(!migrate_legacy_items(&mut host, 2).unwrap());
+        assert!(migrate_legacy_items(&mut host, 2).unwrap());
+
+        let mut delayed_inbox = DelayedInbox::new(&mut host).unwrap();
+        let first = delayed_inbox.0.first(&host).unwrap().unwrap();
+        assert_eq!((first.timestamp, first.level), (Timestamp::from(1000), 42));
+        assert!(!delayed_inbox
+            .first_has_timed_out(&host, Timestamp::from(1100), 100, 100, 10)
+            .unwrap());
+        assert!(delayed_inbox
+            .first_has_timed_out(&host, Timestamp::from(1101), 100, 100, 10)
+            .unwrap());
+        let transactions = delayed_inbox.remove_all(&mut host).unwrap();
+        assert_eq!(
+            transactions,
+            (0..5).map(dummy_transaction).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_delayed_inbox_timeout() {
+        let mut host = MockHost::default();
+        let mut delayed_inbox =
+            DelayedInbox::new(&mut host).expect("Delayed inbox should be created");
+        let timed_out = |host: &MockHost, delayed_inbox: &DelayedInbox, t: i64, l| {
+            delayed_inbox
+                .first_has_timed_out(host, Timestamp::from(t), l, 100, 10)
+                .unwrap()
+        };
+        assert!(!timed_out(&host, &delayed_inbox, 1000, 1000));
+
+        for (i, (timestamp, level)) in [(50, 5), (500, 50)].into_iter().enumerate() {
+            delayed_inbox
+                .save_transaction(
+                    &mut host,
+                    dummy_transaction(i as u8),
+                    Timestamp::from(timestamp),
+                    level,
+                )
+                .unwrap();
+        }
+
+        // Both the timeout and the minimum number of levels must be exceeded
+        // by the oldest transaction.
+        assert!(!timed_out(&host, &delayed_inbox, 150, 100));
+        assert!(!timed_out(&host, &delayed_inbox, 500, 15));
+        assert!(timed_out(&host, &delayed_inbox, 151, 16));
+
+        let transactions = delayed_inbox.remove_all(&mut host).unwrap();
+        assert_eq!(
+            transactions,
+            vec![dummy_transaction(0), dummy_transaction(1)]
+        );
+        assert!(delayed_inbox.is_empty());
+        assert!(!timed_out(&host, &delayed_inbox, 1000, 1000));
+    }
 }
@@ -7,7 +7,7 @@
 use core::str::Utf8Error;
 use evm_execution::account_storage::AccountStorageError;
 use evm_execution::{DurableStorageError, EthereumError};
-use primitive_types::U256;
+use primitive_types::{H256, U256};
 use rlp::DecoderError;
 use tezos_data_encoding::enc::BinError;
 use tezos_ethereum::tx_common::SigError;
@@ -51,6 +51,10 @@ pub enum StorageError {
     AccountInitialisation,
     #[error("Storage error: failed to initialize a genesis account")]
     GenesisAccountInitialisation,
+    #[error("Storage error: the genesis state root is {computed} instead of the declared {declared}")]
+    InvalidGenesisState { declared: H256, computed: H256 },
+    #[error("Storage error: the declared accounts don't match the storage: {0}")]
+    InvalidDeclaredAccounts(&'static str),
     #[error("Storage error: error while reading a value (incorrect size). Expected {expected} but got {actual}")]
     InvalidLoadValue { expected: usize, actual: usize },
 }
@@ -73,6 +77,8 @@ pub enum EncodingError {
     Entrypoint(EntrypointError),
     #[error("Invalid serialization")]
     Bin(BinError),
+    #[error("Outbox transaction of {0} bytes too big for an outbox message")]
+    OutboxTransactionTooBig(usize),
 }
 
 #[derive(Error, Debug)]
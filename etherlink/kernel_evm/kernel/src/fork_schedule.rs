@@ -0,0 +1,239 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Schedule of the EVM hard forks, by block number.
+//!
+//! The EVM semantics used to execute a block is given by the latest fork
+//! activated at or before its number. Blocks produced before the first
+//! activation use [EvmFork::default]. The schedule is set by the
+//! administrator, and can only be modified for blocks that were not
+//! produced or started yet, so replaying the chain is deterministic. A
+//! schedule that breaks this rule is rejected and stored under
+//! `/fork_schedule_rejected` for inspection, until a schedule is accepted.
+
+use crate::error::Error;
+use crate::storage::{self, read_rlp, store_rlp};
+use evm_execution::EvmFork;
+use primitive_types::U256;
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use tezos_ethereum::rlp_helpers::{
+    append_u256_le, decode_field, decode_field_u256_le, next,
+};
+use tezos_evm_logging::{log, Level::*};
+use tezos_smart_rollup_host::path::RefPath;
+use tezos_smart_rollup_host::runtime::{Runtime, ValueType};
+
+const FORK_SCHEDULE: RefPath = RefPath::assert_from(b"/fork_schedule");
+
+const REJECTED_FORK_SCHEDULE: RefPath = RefPath::assert_from(b"/fork_schedule_rejected");
+
+/// Fork activations, ordered by block number. Both block numbers and forks
+/// are strictly increasing.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ForkSchedule {
+    activations: Vec<(U256, EvmFork)>,
+}
+
+impl ForkSchedule {
+    /// Build a schedule from a list of activations, returns `None` if block
+    /// numbers or forks are not strictly increasing.
+    pub fn new(activations: Vec<(U256, EvmFork)>) -> Option<Self> {
+        let is_increasing = activations
+            .windows(2)
+            .all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1);
+        is_increasing.then_some(Self { activations })
+    }
+
+    /// The fork used to execute the block `number`.
+    pub fn fork_at(&self, number: U256) -> EvmFork {
+        self.activations
+            .iter()
+            .rev()
+            .find(|(activation, _)| *activation <= number)
+            .map(|(_, fork)| *fork)
+            .unwrap_or_default()
+    }
+
+    /// Whether both schedules use the same forks for all blocks up to
+    /// `number` included.
+    fn agrees_until(&self, other: &Self, number: U256) -> bool {
+        let until = |schedule: &Self| {
+            schedule
+                .activations
+                .iter()
+                .filter(|(activation, _)| *activation <= number)
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        until(self) == until(other)
+    }
+}
+
+impl Encodable for ForkSchedule {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(self.activations.len());
+        for (number, fork) in &self.activations {
+            stream.begin_list(2);
+            append_u256_le(stream, number);
+            stream.append(fork);
+        }
+    }
+}
+
+impl Decodable for ForkSchedule {
+    fn decode(decoder: &Rlp) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        let mut activations = vec![];
+        for item in decoder.iter() {
+            if !item.is_list() {
+                return Err(DecoderError::RlpExpectedToBeList);
+            }
+            if item.item_count()? != 2 {
+                return Err(DecoderError::RlpIncorrectListLen);
+            }
+            let mut it = item.iter();
+            let number = decode_field_u256_le(&next(&mut it)?, "number")?;
+            let fork = decode_field(&next(&mut it)?, "fork")?;
+            activations.push((number, fork));
+        }
+        Self::new(activations)
+            .ok_or(DecoderError::Custom("Fork activations are not increasing"))
+    }
+}
+
+pub fn read_fork_schedule<Host: Runtime>(host: &Host) -> Result<ForkSchedule, Error> {
+    match host.store_has(&FORK_SCHEDULE)? {
+        Some(ValueType::Value) => read_rlp(host, &FORK_SCHEDULE),
+        _ => Ok(ForkSchedule::default()),
+    }
+}
+
+/// The fork used to execute the block `number`.
+pub fn read_fork_at<Host: Runtime>(host: &Host, number: U256) -> Result<EvmFork, Error> {
+    Ok(read_fork_schedule(host)?.fork_at(number))
+}
+
+/// Replace the fork schedule. The new schedule is rejected if it changes the
+/// fork of a block that is already produced, or of the next block, which may
+/// be in progress. The last rejected schedule is stored under
+/// `/fork_schedule_rejected`, and removed when a schedule is accepted.
+pub fn store_fork_schedule<Host: Runtime>(
+    host: &mut Host,
+    schedule: ForkSchedule,
+) -> Result<(), Error> {
+    let next_block_number = match storage::read_current_block_number(host) {
+        Ok(number) => number + 1,
+        Err(_) => U256::zero(),
+    };
+    let current_schedule = read_fork_schedule(host)?;
+    if !current_schedule.agrees_until(&schedule, next_block_number) {
+        log!(
+            host,
+            Error,
+            "Fork schedule {:?} rejected, it modifies blocks up to {}",
+            schedule,
+            next_block_number
+        );
+        return store_rlp(&schedule, host, &REJECTED_FORK_SCHEDULE);
+    }
+    log!(host, Info, "New fork schedule: {:?}", schedule);
+    if host.store_has(&REJECTED_FORK_SCHEDULE)?.is_some() {
+        host.store_delete(&REJECTED_FORK_SCHEDULE)?
+    }
+    store_rlp(&schedule, host, &FORK_SCHEDULE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tezos_ethereum::rlp_helpers::FromRlpBytes;
+    use tezos_smart_rollup_mock::MockHost;
+
+    fn schedule(activations: &[(u64, EvmFork)]) -> ForkSchedule {
+        ForkSchedule::new(
+            activations
+                .iter()
+                .map(|(number, fork)| (U256::from(*number), *fork))
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn fork_at() {
+        let schedule = schedule(&[(10, EvmFork::Cancun)]);
+        assert_eq!(schedule.fork_at(U256::zero()), EvmFork::Shanghai);
+        assert_eq!(schedule.fork_at(U256::from(9)), EvmFork::Shanghai);
+        assert_eq!(schedule.fork_at(U256::from(10)), EvmFork::Cancun);
+        assert_eq!(schedule.fork_at(U256::MAX), EvmFork::Cancun);
+        assert_eq!(
+            ForkSchedule::default().fork_at(U256::MAX),
+            EvmFork::Shanghai
+        );
+    }
+
+    #[test]
+    fn activations_are_increasing() {
+        let activations = vec![
+            (U256::from(10), EvmFork::Cancun),
+            (U256::from(5), EvmFork::Shanghai),
+        ];
+        assert_eq!(ForkSchedule::new(activations), None);
+        let activations = vec![
+            (U256::from(5), EvmFork::Cancun),
+            (U256::from(10), EvmFork::Shanghai),
+        ];
+        assert_eq!(ForkSchedule::new(activations), None);
+
+        let mut stream = RlpStream::new_list(2);
+        for number in [10, 5] {
+            stream.begin_list(2);
+            append_u256_le(&mut stream, &U256::from(number));
+            stream.append(&EvmFork::Cancun);
+        }
+        assert!(ForkSchedule::from_rlp_bytes(&stream.out()).is_err());
+    }
+
+    #[test]
+    fn encoding_roundtrip() {
+        let schedule = schedule(&[(0, EvmFork::Shanghai), (42, EvmFork::Cancun)]);
+        let decoded = ForkSchedule::from_rlp_bytes(&schedule.rlp_bytes()).unwrap();
+        assert_eq!(decoded, schedule);
+    }
+
+    #[test]
+    fn past_blocks_cannot_be_rescheduled() {
+        let mut host = MockHost::default();
+        assert_eq!(
+            read_fork_at(&host, U256::zero()).unwrap(),
+            EvmFork::Shanghai
+        );
+
+        // The next block is 0, its fork cannot change
+        let cancun_at_0 = schedule(&[(0, EvmFork::Cancun)]);
+        store_fork_schedule(&mut host, cancun_at_0.clone()).unwrap();
+        assert_eq!(read_fork_schedule(&host).unwrap(), ForkSchedule::default());
+        let rejected: ForkSchedule = read_rlp(&host, &REJECTED_FORK_SCHEDULE).unwrap();
+        assert_eq!(rejected, cancun_at_0);
+
+        let cancun_at_10 = schedule(&[(10, EvmFork::Cancun)]);
+        store_fork_schedule(&mut host, cancun_at_10.clone()).unwrap();
+        assert_eq!(read_fork_schedule(&host).unwrap(), cancun_at_10);
+        assert_eq!(host.store_has(&REJECTED_FORK_SCHEDULE).unwrap(), None);
+        assert_eq!(
+            read_fork_at(&host, U256::from(10)).unwrap(),
+            EvmFork::Cancun
+        );
+
+        // The activation can be postponed, as long as it is not reached
+        let cancun_at_20 = schedule(&[(20, EvmFork::Cancun)]);
+        store_fork_schedule(&mut host, cancun_at_20.clone()).unwrap();
+        assert_eq!(read_fork_schedule(&host).unwrap(), cancun_at_20);
+
+        assert!(cancun_at_20.agrees_until(&cancun_at_10, U256::from(9)));
+        assert!(!cancun_at_20.agrees_until(&cancun_at_10, U256::from(10)));
+    }
+}
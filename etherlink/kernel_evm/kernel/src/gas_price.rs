@@ -0,0 +1,149 @@
+// SPDX-FileCopyrightText: 2024 TriliTech <contact@trili.tech>
+//
+// SPDX-License-Identifier: MIT
+
+//! Adjustment of the base fee per gas, as in EIP-1559.
+//!
+//! After each block, the base fee increases if the block used more gas than
+//! the gas target, and decreases if it used less. The change is bounded to
+//! 1/8 of the base fee per block, and the base fee never goes below a minimum
+//! set in the durable storage.
+
+use crate::error::Error;
+use crate::storage::{read_u256_or_default, store_base_fee_per_gas};
+use crate::BASE_FEE_PER_GAS;
+use primitive_types::U256;
+use std::cmp::Ordering;
+use tezos_ethereum::block::BlockFees;
+use tezos_evm_logging::{log, Level::*};
+use tezos_smart_rollup_host::path::RefPath;
+use tezos_smart_rollup_host::runtime::Runtime;
+
+const GAS_TARGET: RefPath = RefPath::assert_from(b"/gas_target");
+const MINIMUM_BASE_FEE_PER_GAS: RefPath =
+    RefPath::assert_from(b"/minimum_base_fee_per_gas");
+
+/// Gas used by a block for which the base fee does not change, used if no
+/// target is set in the durable storage.
+pub const DEFAULT_GAS_TARGET: u64 = 15_000_000;
+
+/// Bounds the change of the base fee between two blocks to 1/8.
+const BASE_FEE_MAX_CHANGE_DENOMINATOR: u64 = 8;
+
+/// Base fee per gas of the block following a block using `gas_used`.
+pub fn next_base_fee_per_gas(
+    base_fee_per_gas: U256,
+    gas_used: U256,
+    gas_target: u64,
+    minimum_base_fee_per_gas: U256,
+) -> U256 {
+    let gas_target = U256::from(gas_target.max(1));
+    // The block gas limit is not enforced, the change is bounded by
+    // considering at most twice the target.
+    let change = |gas_delta: U256| {
+        base_fee_per_gas.saturating_mul(gas_delta.min(gas_target))
+            / gas_target
+            / BASE_FEE_MAX_CHANGE_DENOMINATOR
+    };
+    let next = match gas_used.cmp(&gas_target) {
+        Ordering::Equal => base_fee_per_gas,
+        Ordering::Greater => {
+            let change = change(gas_used - gas_target).max(U256::one());
+            base_fee_per_gas.saturating_add(change)
+        }
+        Ordering::Less => base_fee_per_gas.saturating_sub(change(gas_target - gas_used)),
+    };
+    next.max(minimum_base_fee_per_gas)
+}
+
+pub fn read_gas_target<Host: Runtime>(host: &Host) -> Result<u64, Error> {
+    let target = read_u256_or_default(host, &GAS_TARGET, DEFAULT_GAS_TARGET.into())?;
+    Ok(target.try_into().unwrap_or(u64::MAX))
+}
+
+pub fn read_minimum_base_fee_per_gas<Host: Runtime>(host: &Host) -> Result<U256, Error> {
+    read_u256_or_default(host, &MINIMUM_BASE_FEE_PER_GAS, BASE_FEE_PER_GAS.into())
+}
+
+/// Compute and store the fees of the block following a block using
+/// `gas_used`.
+pub fn update_block_fees<Host: Runtime>(
+    host: &mut Host,
+    block_fees: BlockFees,
+    gas_used: U256,
+) -> Result<BlockFees, Error> {
+    let gas_target = read_gas_target(host)?;
+    let minimum = read_minimum_base_fee_per_gas(host)?;
+    let base_fee_per_gas = next_base_fee_per_gas(
+        block_fees.base_fee_per_gas(),
+        gas_used,
+        gas_target,
+        minimum,
+    );
+    log!(host, Debug, "Next base fee per gas: {}", base_fee_per_gas);
+    store_base_fee_per_gas(host, base_fee_per_gas)?;
+    Ok(BlockFees::new(
+        base_fee_per_gas,
+        block_fees.da_fee_per_byte(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::write_u256;
+    use tezos_smart_rollup_mock::MockHost;
+
+    const TARGET: u64 = 1_000_000;
+
+    fn next(base_fee: u64, gas_used: u64, minimum: u64) -> U256 {
+        next_base_fee_per_gas(base_fee.into(), gas_used.into(), TARGET, minimum.into())
+    }
+
+    #[test]
+    fn base_fee_follows_gas_used() {
+        assert_eq!(next(80_000, TARGET, 0), U256::from(80_000));
+        // Twice the target increases the base fee by 1/8
+        assert_eq!(next(80_000, 2 * TARGET, 0), U256::from(90_000));
+        assert_eq!(next(80_000, TARGET + TARGET / 2, 0), U256::from(85_000));
+        // An empty block decreases the base fee by 1/8
+        assert_eq!(next(80_000, 0, 0), U256::from(70_000));
+        assert_eq!(next(80_000, TARGET / 2, 0), U256::from(75_000));
+    }
+
+    #[test]
+    fn base_fee_change_is_bounded() {
+        assert_eq!(next(80_000, 100 * TARGET, 0), U256::from(90_000));
+        // The base fee always increases above the target
+        assert_eq!(next(1, TARGET + 1, 0), U256::from(2));
+    }
+
+    #[test]
+    fn base_fee_has_a_floor() {
+        assert_eq!(next(80_000, 0, 75_000), U256::from(75_000));
+        assert_eq!(next(10, TARGET, 20), U256::from(20));
+    }
+
+    #[test]
+    fn block_fees_are_stored() {
+        let mut host = MockHost::default();
+        let block_fees = BlockFees::new(U256::from(BASE_FEE_PER_GAS) * 2, U256::zero());
+        let next_fees =
+            update_block_fees(&mut host, block_fees, DEFAULT_GAS_TARGET.into()).unwrap();
+        assert_eq!(next_fees.base_fee_per_gas(), block_fees.base_fee_per_gas());
+
+        let next_fees = update_block_fees(&mut host, next_fees, U256::zero()).unwrap();
+        let expected = U256::from(BASE_FEE_PER_GAS) * 2 * 7 / 8;
+        assert_eq!(next_fees.base_fee_per_gas(), expected);
+        assert_eq!(
+            crate::storage::read_base_fee_per_gas(&mut host).unwrap(),
+            expected
+        );
+
+        // The base fee does not go below the minimum
+        let minimum = expected - 1;
+        write_u256(&mut host, &MINIMUM_BASE_FEE_PER_GAS.into(), minimum).unwrap();
+        let next_fees = update_block_fees(&mut host, next_fees, U256::zero()).unwrap();
+        assert_eq!(next_fees.base_fee_per_gas(), minimum);
+    }
+}
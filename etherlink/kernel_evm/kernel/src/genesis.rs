@@ -0,0 +1,254 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Check of the genesis state.
+//!
+//! The installer sets the accounts allocated at genesis, and declares them
+//! under [GENESIS]: their state root under `/state_root`, and the accounts
+//! with the indexes of their storage in the indexable storage `/accounts`.
+//! In its first runs, the kernel adds them to the state trie as in
+//! [add_declared_accounts], rebooting until they are all added, and then
+//! checks the root.
+//!
+//! An invalid declaration doesn't prevent the kernel from starting, the
+//! error is logged and stored under [GENESIS_ERROR] for the operators.
+
+use crate::error::{Error, StorageError};
+use crate::indexable_storage::IndexableStorage;
+use crate::migration::MigrationStatus;
+use crate::storage::store_read_slice;
+use crate::world_state::{add_declared_accounts, read_state_root};
+use primitive_types::H256;
+use tezos_evm_logging::{log, Level::*};
+use tezos_smart_rollup_host::path::RefPath;
+use tezos_smart_rollup_host::runtime::Runtime;
+
+const GENESIS: RefPath = RefPath::assert_from(b"/genesis");
+
+const GENESIS_STATE_ROOT: RefPath = RefPath::assert_from(b"/genesis/state_root");
+
+const GENESIS_ACCOUNTS: RefPath = RefPath::assert_from(b"/genesis/accounts");
+
+const GENESIS_ERROR: RefPath = RefPath::assert_from(b"/genesis_error");
+
+/// Adds the next declared accounts to the state trie, within `ticks_limit`,
+/// and returns whether they were all added and the root checked.
+fn add_genesis_accounts<Host: Runtime>(
+    host: &mut Host,
+    ticks_limit: u64,
+) -> Result<bool, Error> {
+    let mut declared = [0u8; 32];
+    store_read_slice(host, &GENESIS_STATE_ROOT, &mut declared, 32)?;
+    let declared = H256(declared);
+    let accounts = IndexableStorage::new(&GENESIS_ACCOUNTS)?;
+    if !add_declared_accounts(host, &accounts, ticks_limit)? {
+        return Ok(false);
+    }
+    let computed = read_state_root(host)?;
+    if computed != declared {
+        return Err(Error::Storage(StorageError::InvalidGenesisState {
+            declared,
+            computed,
+        }));
+    }
+    Ok(true)
+}
+
+/// Checks the genesis state if it is declared, adding as many accounts to
+/// the state trie as `ticks_limit` allows. The kernel must reboot unless
+/// the status is [MigrationStatus::None], as for a migration. The
+/// declaration is removed once checked, so that it is done only once.
+pub fn check_genesis_state<Host: Runtime>(
+    host: &mut Host,
+    ticks_limit: u64,
+) -> anyhow::Result<MigrationStatus> {
+    if host.store_has(&GENESIS)?.is_none() {
+        return Ok(MigrationStatus::None);
+    }
+    match add_genesis_accounts(host, ticks_limit) {
+        Ok(false) => return Ok(MigrationStatus::InProgress),
+        Ok(true) => log!(host, Info, "The genesis state is checked"),
+        Err(err) => {
+            log!(host, Error, "The genesis state is invalid: {}", err);
+            host.store_write_all(&GENESIS_ERROR, err.to_string().as_bytes())?;
+        }
+    }
+    host.store_delete(&GENESIS)?;
+    Ok(MigrationStatus::Done)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evm_execution::account_storage::EthereumAccount;
+    use evm_execution::tick_model_opcodes::{
+        TICKS_PER_TOUCHED_ACCOUNT, TICKS_PER_TOUCHED_SLOT,
+    };
+    use primitive_types::{H160, U256};
+    use tezos_ethereum::genesis::{GenesisAccount, GenesisDeclaration};
+    use tezos_smart_rollup_mock::MockHost;
+
+    fn genesis_accounts() -> Vec<GenesisAccount> {
+        let mut contract = GenesisAccount {
+            address: H160::repeat_byte(1),
+            nonce: U256::one(),
+            code: vec![0x60, 0x00, 0x54],
+            ..GenesisAccount::default()
+        };
+        for i in 1..=10 {
+            contract
+                .storage
+                .insert(H256::from_low_u64_be(i), H256::from_low_u64_be(42));
+        }
+        let user = GenesisAccount {
+            address: H160::repeat_byte(2),
+            balance: U256::from(1_000_000),
+            ..GenesisAccount::default()
+        };
+        vec![contract, user]
+    }
+
+    fn install(host: &mut MockHost, accounts: &[GenesisAccount]) {
+        for genesis_account in accounts {
+            let mut account =
+                EthereumAccount::from_address(&genesis_account.address).unwrap();
+            account.set_nonce(host, genesis_account.nonce).unwrap();
+            account.set_balance(host, genesis_account.balance).unwrap();
+            if !genesis_account.code.is_empty() {
+                account.set_code(host, &genesis_account.code).unwrap();
+            }
+            for (index, value) in &genesis_account.storage {
+                account.set_storage(host, index, value).unwrap();
+            }
+        }
+    }
+
+    fn declare(host: &mut MockHost, declaration: &GenesisDeclaration) {
+        host.store_write_all(&GENESIS_STATE_ROOT, declaration.state_root.as_bytes())
+            .unwrap();
+        let mut accounts = IndexableStorage::new(&GENESIS_ACCOUNTS).unwrap();
+        for account in declaration.encoded_accounts() {
+            accounts.push_value(host, &account).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_genesis_state_is_checked_in_several_runs() {
+        let mut host = MockHost::default();
+        let accounts = genesis_accounts();
+        install(&mut host, &accounts);
+        let declaration = GenesisDeclaration::new(&accounts);
+        declare(&mut host, &declaration);
+
+        // Each run adds at most 4 storage slots.
+        let ticks_limit = TICKS_PER_TOUCHED_ACCOUNT * 2 + TICKS_PER_TOUCHED_SLOT * 4;
+        let mut runs = 1;
+        while check_genesis_state(&mut host, ticks_limit).unwrap()
+            == MigrationStatus::InProgress
+        {
+            runs += 1;
+        }
+        assert!(runs > 1);
+        assert_eq!(read_state_root(&host).unwrap(), declaration.state_root);
+        assert!(host.store_has(&GENESIS).unwrap().is_none());
+        assert!(host.store_has(&GENESIS_ERROR).unwrap().is_none());
+        // The check is done only once.
+        assert_eq!(
+            check_genesis_state(&mut host, ticks_limit).unwrap(),
+            MigrationStatus::None
+        );
+    }
+
+    #[test]
+    fn test_invalid_genesis_state_is_recorded() {
+        let mut host = MockHost::default();
+        let mut accounts = genesis_accounts();
+        install(&mut host, &accounts);
+        accounts[1].balance = U256::from(2_000_000);
+        let declaration = GenesisDeclaration::new(&accounts);
+        declare(&mut host, &declaration);
+
+        assert_eq!(
+            check_genesis_state(&mut host, u64::MAX).unwrap(),
+            MigrationStatus::Done
+        );
+        assert!(host.store_has(&GENESIS).unwrap().is_none());
+        let error = host.store_read_all(&GENESIS_ERROR).unwrap();
+        assert!(String::from_utf8(error)
+            .unwrap()
+            .contains("genesis state root"));
+    }
+
+    // Genesis of Ethereum networks, in the format described in the README of
+    // `tests/resources`. The allocated accounts are declared with the state
+    // root of the network, which the kernel must compute.
+    #[test]
+    #[ignore = "requires the vectors of tests/resources/ethereum_blocks"]
+    fn test_state_root_of_ethereum_genesis() {
+        let bytes = |s: &str| {
+            if s == "-" {
+                vec![]
+            } else {
+                hex::decode(s).unwrap()
+            }
+        };
+        let h256 = |s: &str| H256::from_slice(&bytes(&format!("{:0>64}", s)));
+
+        let mut checked = 0;
+        for entry in std::fs::read_dir("tests/resources/ethereum_blocks").unwrap() {
+            let path = entry.unwrap().path();
+            let vector = std::fs::read_to_string(&path).unwrap();
+            let mut state_root = None;
+            let mut accounts: Vec<GenesisAccount> = vec![];
+            for line in vector.lines().filter(|line| !line.starts_with('#')) {
+                match line.split_whitespace().collect::<Vec<&str>>()[..] {
+                    ["state_root", root] => state_root = Some(h256(root)),
+                    ["account", address, nonce, balance, code] => {
+                        accounts.push(GenesisAccount {
+                            address: H160::from_slice(&bytes(address)),
+                            nonce: U256::from_dec_str(nonce).unwrap(),
+                            balance: U256::from_dec_str(balance).unwrap(),
+                            code: bytes(code),
+                            ..GenesisAccount::default()
+                        })
+                    }
+                    ["storage", address, index, value] => {
+                        let address = H160::from_slice(&bytes(address));
+                        let account = accounts
+                            .iter_mut()
+                            .find(|account| account.address == address)
+                            .unwrap();
+                        let value = h256(value);
+                        if !value.is_zero() {
+                            account.storage.insert(h256(index), value);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            let Some(state_root) = state_root else {
+                continue;
+            };
+
+            let mut host = MockHost::default();
+            install(&mut host, &accounts);
+            let declaration = GenesisDeclaration {
+                state_root,
+                ..GenesisDeclaration::new(&accounts)
+            };
+            declare(&mut host, &declaration);
+            while check_genesis_state(&mut host, u64::MAX).unwrap()
+                == MigrationStatus::InProgress
+            {}
+            assert!(
+                host.store_has(&GENESIS_ERROR).unwrap().is_none(),
+                "{:?}",
+                path
+            );
+            assert_eq!(read_state_root(&host).unwrap(), state_root, "{:?}", path);
+            checked += 1;
+        }
+        assert!(checked > 0, "No vector of Ethereum genesis");
+    }
+}
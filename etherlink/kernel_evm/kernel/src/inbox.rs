@@ -7,14 +7,17 @@
 
 use std::fmt::Display;
 
+use crate::fork_schedule::store_fork_schedule;
+use crate::outbox;
 use crate::parsing::{Input, InputResult, MAX_SIZE_PER_CHUNK};
 use crate::sequencer_blueprint::SequencerBlueprint;
+use crate::sequencer_keys::{update_sequencers, Sequencers};
 use crate::simulation;
 use crate::storage::{
     chunked_hash_transaction_path, chunked_transaction_num_chunks,
     chunked_transaction_path, create_chunked_transaction,
-    get_and_increment_deposit_nonce, remove_chunked_transaction,
-    store_last_info_per_level_timestamp, store_sequencer, store_transaction_chunk,
+    get_and_increment_deposit_nonce, read_l1_level, remove_chunked_transaction,
+    store_l1_level, store_last_info_per_level_timestamp, store_transaction_chunk,
 };
 use crate::upgrade::*;
 use crate::Error;
@@ -22,11 +25,14 @@ use primitive_types::{H160, U256};
 use rlp::{Decodable, DecoderError, Encodable};
 use sha3::{Digest, Keccak256};
 use tezos_crypto_rs::hash::ContractKt1Hash;
+use tezos_ethereum::fa_ticket::FaTicket;
 use tezos_ethereum::rlp_helpers::{decode_field, decode_tx_hash, next};
-use tezos_ethereum::transaction::{TransactionHash, TRANSACTION_HASH_SIZE};
+use tezos_ethereum::transaction::{
+    TransactionHash, TransactionType, TRANSACTION_HASH_SIZE,
+};
 use tezos_ethereum::tx_common::EthereumTransactionCommon;
 use tezos_evm_logging::{log, Level::*};
-use tezos_smart_rollup_encoding::public_key::PublicKey;
+use tezos_smart_rollup_encoding::public_key_hash::PublicKeyHash;
 use tezos_smart_rollup_host::runtime::Runtime;
 
 #[derive(Debug, PartialEq, Clone, Default)]
@@ -90,15 +96,67 @@ impl Decodable for Deposit {
     }
 }
 
+/// Deposit of a FA ticket, minted to the receiver by the ERC-20 wrapper of
+/// the ticket.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FaDeposit {
+    pub ticket: FaTicket,
+    /// Amount of the ticket.
+    pub amount: U256,
+    pub receiver: H160,
+    /// Source of the deposit on layer 1, refunded if the deposit can't be
+    /// minted.
+    pub depositor: PublicKeyHash,
+}
+
+impl Encodable for FaDeposit {
+    fn rlp_append(&self, stream: &mut rlp::RlpStream) {
+        stream.begin_list(4);
+        stream.append(&self.ticket);
+        stream.append(&self.amount);
+        stream.append(&self.receiver);
+        stream.append(&self.depositor.to_b58check().as_bytes());
+    }
+}
+
+impl Decodable for FaDeposit {
+    fn decode(decoder: &rlp::Rlp) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+
+        let mut it = decoder.iter();
+        let ticket: FaTicket = decode_field(&next(&mut it)?, "ticket")?;
+        let amount: U256 = decode_field(&next(&mut it)?, "amount")?;
+        let receiver: H160 = decode_field(&next(&mut it)?, "receiver")?;
+        let depositor: Vec<u8> = decode_field(&next(&mut it)?, "depositor")?;
+        let depositor = std::str::from_utf8(&depositor)
+            .ok()
+            .and_then(|depositor| PublicKeyHash::from_b58check(depositor).ok())
+            .ok_or(DecoderError::Custom("Invalid depositor"))?;
+        Ok(FaDeposit {
+            ticket,
+            amount,
+            receiver,
+            depositor,
+        })
+    }
+}
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, PartialEq, Clone)]
 pub enum TransactionContent {
     Ethereum(EthereumTransactionCommon),
     Deposit(Deposit),
+    FaDeposit(FaDeposit),
 }
 
 const ETHEREUM_TX_TAG: u8 = 1;
 const DEPOSIT_TX_TAG: u8 = 2;
+const FA_DEPOSIT_TX_TAG: u8 = 3;
 
 impl Encodable for TransactionContent {
     fn rlp_append(&self, stream: &mut rlp::RlpStream) {
@@ -113,6 +171,10 @@ impl Encodable for TransactionContent {
                 stream.append(&DEPOSIT_TX_TAG);
                 dep.rlp_append(stream)
             }
+            TransactionContent::FaDeposit(dep) => {
+                stream.append(&FA_DEPOSIT_TX_TAG);
+                dep.rlp_append(stream)
+            }
         }
     }
 }
@@ -132,6 +194,10 @@ impl Decodable for TransactionContent {
                 let deposit = Deposit::decode(&tx)?;
                 Ok(Self::Deposit(deposit))
             }
+            FA_DEPOSIT_TX_TAG => {
+                let deposit = FaDeposit::decode(&tx)?;
+                Ok(Self::FaDeposit(deposit))
+            }
             ETHEREUM_TX_TAG => {
                 let bytes: Vec<u8> = tx.as_val()?;
                 let eth = EthereumTransactionCommon::from_bytes(&bytes)?;
@@ -150,10 +216,31 @@ pub struct Transaction {
 impl Transaction {
     pub fn data_size(&self) -> u64 {
         match &self.content {
-            TransactionContent::Deposit(_) => 0,
+            TransactionContent::Deposit(_) | TransactionContent::FaDeposit(_) => 0,
             TransactionContent::Ethereum(e) => e.data.len() as u64,
         }
     }
+
+    /// Type of the transaction, deposits are legacy transactions.
+    pub fn type_(&self) -> TransactionType {
+        match &self.content {
+            TransactionContent::Deposit(_) | TransactionContent::FaDeposit(_) => {
+                TransactionType::Legacy
+            }
+            TransactionContent::Ethereum(e) => e.type_,
+        }
+    }
+
+    /// Encoding of the transaction in the transactions trie of a block: the
+    /// signed EIP-2718 envelope of Ethereum transactions, and the RLP
+    /// encoding of deposits.
+    pub fn trie_encoding(&self) -> Vec<u8> {
+        match &self.content {
+            TransactionContent::Deposit(deposit) => deposit.rlp_bytes().to_vec(),
+            TransactionContent::FaDeposit(deposit) => deposit.rlp_bytes().to_vec(),
+            TransactionContent::Ethereum(e) => e.to_bytes(),
+        }
+    }
 }
 
 impl Encodable for Transaction {
@@ -182,7 +269,6 @@ impl Decodable for Transaction {
 
 #[derive(Debug, PartialEq)]
 pub struct InboxContent {
-    pub kernel_upgrade: Option<KernelUpgrade>,
     pub transactions: Vec<Transaction>,
     pub sequencer_blueprints: Vec<SequencerBlueprint>,
 }
@@ -193,12 +279,17 @@ pub fn read_input<Host: Runtime>(
     tezos_contracts: &TezosContracts,
     inbox_is_empty: &mut bool,
     delayed_bridge: &Option<ContractKt1Hash>,
-    sequencer: &Option<PublicKey>,
+    sequencers: &Option<Sequencers>,
 ) -> Result<InputResult, Error> {
     let input = host.read_input()?;
 
     match input {
         Some(input) => {
+            if *inbox_is_empty {
+                store_l1_level(host, input.level)?;
+                // The outbox messages are written at the level of the inbox.
+                outbox::start_level(host, input.level)?;
+            }
             *inbox_is_empty = false;
             Ok(InputResult::parse(
                 host,
@@ -206,7 +297,7 @@ pub fn read_input<Host: Runtime>(
                 smart_rollup_address,
                 tezos_contracts,
                 delayed_bridge,
-                sequencer,
+                sequencers,
             ))
         }
         None => Ok(InputResult::NoInput),
@@ -292,15 +383,41 @@ fn handle_deposit<Host: Runtime>(
     })
 }
 
+fn handle_fa_deposit<Host: Runtime>(
+    host: &mut Host,
+    deposit: FaDeposit,
+) -> Result<Transaction, Error> {
+    let deposit_nonce = get_and_increment_deposit_nonce(host)?;
+
+    let mut buffer_amount = [0; 32];
+    deposit.amount.to_little_endian(&mut buffer_amount);
+
+    let mut to_hash = vec![];
+    to_hash.extend_from_slice(&buffer_amount);
+    to_hash.extend_from_slice(&deposit.receiver.to_fixed_bytes());
+    to_hash.extend_from_slice(&deposit_nonce.to_le_bytes());
+    to_hash.extend_from_slice(&deposit.ticket.rlp_bytes());
+
+    let kec = Keccak256::digest(to_hash);
+    let tx_hash = kec
+        .as_slice()
+        .try_into()
+        .map_err(|_| Error::InvalidConversion)?;
+
+    Ok(Transaction {
+        tx_hash,
+        content: TransactionContent::FaDeposit(deposit),
+    })
+}
+
 pub fn read_inbox<Host: Runtime>(
     host: &mut Host,
     smart_rollup_address: [u8; 20],
     tezos_contracts: TezosContracts,
     delayed_bridge: Option<ContractKt1Hash>,
-    sequencer: Option<PublicKey>,
+    sequencers: Option<Sequencers>,
 ) -> Result<Option<InboxContent>, anyhow::Error> {
     let mut res = InboxContent {
-        kernel_upgrade: None,
         transactions: vec![],
         sequencer_blueprints: vec![],
     };
@@ -316,7 +433,7 @@ pub fn read_inbox<Host: Runtime>(
             &tezos_contracts,
             &mut inbox_is_empty,
             &delayed_bridge,
-            &sequencer,
+            &sequencers,
         )? {
             InputResult::NoInput => {
                 if inbox_is_empty {
@@ -352,10 +469,27 @@ pub fn read_inbox<Host: Runtime>(
                 }
             }
             InputResult::Input(Input::Upgrade(kernel_upgrade)) => {
-                res.kernel_upgrade = Some(kernel_upgrade)
+                // Only the administrator proposes upgrades, its proposal
+                // counts as an approval if it is part of the governance.
+                if let Some(admin) = &tezos_contracts.admin {
+                    let proposer = Approver::Contract(admin.clone());
+                    propose_kernel_upgrade(host, kernel_upgrade, proposer)?
+                }
+            }
+            InputResult::Input(Input::UpgradeApproval { approver, proposal }) => {
+                approve_kernel_upgrade(host, approver, proposal)?
+            }
+            InputResult::Input(Input::UpgradeCancellation(preimage_hash)) => {
+                cancel_kernel_upgrade(host, preimage_hash)?
+            }
+            InputResult::Input(Input::UpgradeGovernance(governance)) => {
+                update_upgrade_governance(host, governance)?
+            }
+            InputResult::Input(Input::ForkSchedule(fork_schedule)) => {
+                store_fork_schedule(host, fork_schedule)?
             }
-            InputResult::Input(Input::NewSequencer(sequencer)) => {
-                store_sequencer(host, sequencer)?
+            InputResult::Input(Input::NewSequencer(update)) => {
+                update_sequencers(host, update, read_l1_level(host)?)?
             }
             InputResult::Input(Input::Simulation) => {
                 // kernel enters in simulation mode, reading will be done by the
@@ -370,6 +504,9 @@ pub fn read_inbox<Host: Runtime>(
             InputResult::Input(Input::Deposit(deposit)) => {
                 res.transactions.push(handle_deposit(host, deposit)?)
             }
+            InputResult::Input(Input::FaDeposit(deposit)) => {
+                res.transactions.push(handle_fa_deposit(host, deposit)?)
+            }
             InputResult::Input(Input::SequencerBlueprint(seq_blueprint)) => {
                 res.sequencer_blueprints.push(seq_blueprint)
             }
@@ -380,16 +517,21 @@ pub fn read_inbox<Host: Runtime>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fork_schedule::{read_fork_schedule, ForkSchedule};
     use crate::inbox::TransactionContent::Ethereum;
     use crate::parsing::RollupType;
     use crate::storage::*;
-    use tezos_crypto_rs::hash::SmartRollupHash;
+    use evm_execution::EvmFork;
+    use tezos_crypto_rs::hash::{HashTrait, SmartRollupHash};
     use tezos_data_encoding::types::Bytes;
     use tezos_ethereum::transaction::TRANSACTION_HASH_SIZE;
     use tezos_smart_rollup_core::PREIMAGE_HASH_SIZE;
     use tezos_smart_rollup_encoding::contract::Contract;
     use tezos_smart_rollup_encoding::inbox::ExternalMessageFrame;
-    use tezos_smart_rollup_encoding::michelson::{MichelsonBytes, MichelsonOr};
+    use tezos_smart_rollup_encoding::michelson::ticket::{FA2_1Ticket, Ticket};
+    use tezos_smart_rollup_encoding::michelson::{
+        MichelsonBytes, MichelsonOption, MichelsonOr, MichelsonPair,
+    };
     use tezos_smart_rollup_encoding::public_key_hash::PublicKeyHash;
     use tezos_smart_rollup_encoding::smart_rollup::SmartRollupAddress;
     use tezos_smart_rollup_encoding::timestamp::Timestamp;
@@ -568,6 +710,7 @@ mod tests {
         let kernel_upgrade = KernelUpgrade {
             preimage_hash,
             activation_timestamp,
+            activation_level: Some(100),
         };
         let kernel_upgrade_payload = kernel_upgrade.rlp_bytes().to_vec();
 
@@ -586,7 +729,7 @@ mod tests {
 
         let transfer_metadata = TransferMetadata::new(sender.clone(), source);
         host.add_transfer(payload, &transfer_metadata);
-        let inbox_content = read_inbox(
+        read_inbox(
             &mut host,
             [0; 20],
             TezosContracts {
@@ -599,12 +742,171 @@ mod tests {
         )
         .unwrap()
         .unwrap();
-        let expected_upgrade = Some(KernelUpgrade {
-            preimage_hash,
-            activation_timestamp,
-        });
 
-        assert_eq!(inbox_content.kernel_upgrade, expected_upgrade);
+        // Without governance, the proposal of the administrator schedules
+        // the upgrade.
+        assert_eq!(read_kernel_upgrade(&host).unwrap(), Some(kernel_upgrade));
+        let status = read_kernel_upgrade_status(&host).unwrap().unwrap();
+        assert_eq!(status.status, UpgradeStatus::Scheduled);
+    }
+
+    #[test]
+    fn parse_upgrade_governance_update() {
+        let mut host = MockHost::default();
+        let source =
+            PublicKeyHash::from_b58check("tz1NiaviJwtMbpEcNqSP6neeoBYj8Brb3QPv").unwrap();
+        let admin =
+            ContractKt1Hash::from_b58check("KT1HJphVV3LUxqZnc7YSH6Zdfd3up1DjLqZv")
+                .unwrap();
+        let governance = UpgradeGovernance {
+            contracts: vec![admin.clone()],
+            keys: vec![],
+            threshold: 1,
+        };
+        // The threshold of the second update can't be reached, it is ignored.
+        let unreachable = UpgradeGovernance {
+            threshold: 2,
+            ..governance.clone()
+        };
+        for governance in [&governance, &unreachable] {
+            let payload = UpgradeDecision::UpdateGovernance(governance.clone());
+            let payload: RollupType =
+                MichelsonOr::Right(MichelsonBytes(payload.rlp_bytes().to_vec()));
+            host.add_transfer(
+                payload,
+                &TransferMetadata::new(admin.clone(), source.clone()),
+            );
+        }
+        read_inbox(
+            &mut host,
+            [0; 20],
+            TezosContracts {
+                ticketer: None,
+                admin: Some(admin),
+                sequencer_admin: None,
+            },
+            None,
+            None,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(read_upgrade_governance(&host).unwrap(), governance);
+    }
+
+    #[test]
+    fn parse_valid_fork_schedule() {
+        let mut host = MockHost::default();
+
+        let fork_schedule =
+            ForkSchedule::new(vec![(U256::from(10), EvmFork::Cancun)]).unwrap();
+        let fork_schedule_payload = fork_schedule.rlp_bytes().to_vec();
+
+        let source =
+            PublicKeyHash::from_b58check("tz1NiaviJwtMbpEcNqSP6neeoBYj8Brb3QPv").unwrap();
+        let contract =
+            Contract::from_b58check("KT1HJphVV3LUxqZnc7YSH6Zdfd3up1DjLqZv").unwrap();
+        let sender = match contract {
+            Contract::Originated(kt1) => kt1,
+            _ => panic!("The contract must be a KT1"),
+        };
+        let payload: RollupType =
+            MichelsonOr::Right(MichelsonBytes(fork_schedule_payload));
+
+        let transfer_metadata = TransferMetadata::new(sender.clone(), source);
+        host.add_transfer(payload, &transfer_metadata);
+        read_inbox(
+            &mut host,
+            [0; 20],
+            TezosContracts {
+                ticketer: None,
+                admin: Some(sender),
+                sequencer_admin: None,
+            },
+            None,
+            None,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(read_kernel_upgrade(&host).unwrap(), None);
+        assert_eq!(read_fork_schedule(&host).unwrap(), fork_schedule);
+    }
+
+    #[test]
+    fn parse_fa_deposits() {
+        let mut host = MockHost::default();
+
+        let source =
+            PublicKeyHash::from_b58check("tz1NiaviJwtMbpEcNqSP6neeoBYj8Brb3QPv").unwrap();
+        let native_ticketer =
+            ContractKt1Hash::from_b58check("KT1HJphVV3LUxqZnc7YSH6Zdfd3up1DjLqZv")
+                .unwrap();
+        let fa_ticketer =
+            ContractKt1Hash::from_b58check("KT1BuEZtb68c1Q4yjtckcNjGELqWt56Xyesc")
+                .unwrap();
+        let receiver = H160::from_low_u64_be(0xa11ce);
+
+        let mut add_deposit = |creator: Contract, token_id: i32, metadata| {
+            let ticket: FA2_1Ticket = Ticket::new(
+                creator,
+                MichelsonPair(token_id.into(), MichelsonOption(metadata)),
+                1000,
+            )
+            .unwrap();
+            let payload: RollupType = MichelsonOr::Left(MichelsonOr::Left(
+                MichelsonPair(MichelsonBytes(receiver.as_bytes().to_vec()), ticket),
+            ));
+            let sender = fa_ticketer.clone();
+            host.add_transfer(payload, &TransferMetadata::new(sender, source.clone()));
+        };
+        add_deposit(Contract::Originated(native_ticketer.clone()), 0, None);
+        add_deposit(
+            Contract::Originated(fa_ticketer.clone()),
+            7,
+            Some(MichelsonBytes(vec![1, 2])),
+        );
+        add_deposit(Contract::Originated(fa_ticketer.clone()), -1, None);
+        add_deposit(Contract::Implicit(source.clone()), 7, None);
+
+        let inbox_content = read_inbox(
+            &mut host,
+            [0; 20],
+            TezosContracts {
+                ticketer: Some(native_ticketer),
+                admin: None,
+                sequencer_admin: None,
+            },
+            None,
+            None,
+        )
+        .unwrap()
+        .unwrap();
+
+        let contents: Vec<TransactionContent> = inbox_content
+            .transactions
+            .into_iter()
+            .map(|tx| tx.content)
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                TransactionContent::Deposit(Deposit {
+                    amount: tezos_ethereum::wei::eth_from_mutez(1000),
+                    receiver,
+                }),
+                TransactionContent::FaDeposit(FaDeposit {
+                    ticket: FaTicket {
+                        ticketer: fa_ticketer,
+                        token_id: U256::from(7),
+                        metadata: Some(vec![1, 2]),
+                    },
+                    amount: U256::from(1000),
+                    receiver,
+                    depositor: source,
+                })
+            ]
+        );
     }
 
     #[test]
@@ -788,7 +1090,6 @@ mod tests {
         assert_eq!(
             inbox_content,
             InboxContent {
-                kernel_upgrade: None,
                 transactions: vec![],
                 sequencer_blueprints: vec![]
             }
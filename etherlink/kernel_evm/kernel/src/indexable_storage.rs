@@ -123,6 +123,17 @@ impl IndexableStorage {
     }
 
     /// Push a value at index `length`, and increments the length.
+    /// Deletes the value at `index`. The length is unchanged, the index is
+    /// not reused.
+    pub fn delete_value<Host: Runtime>(
+        &self,
+        host: &mut Host,
+        index: u64,
+    ) -> Result<(), StorageError> {
+        let key_path = self.value_path(index)?;
+        host.store_delete(&key_path).map_err(StorageError::from)
+    }
+
     pub fn push_value<Host: Runtime>(
         &mut self,
         host: &mut Host,
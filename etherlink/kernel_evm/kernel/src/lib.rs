@@ -14,15 +14,15 @@ use crate::stage_one::{fetch, Configuration};
 use crate::Error::UpgradeError;
 use anyhow::Context;
 use delayed_inbox::DelayedInbox;
-use evm_execution::Config;
 use migration::MigrationStatus;
 use primitive_types::U256;
+use sequencer_keys::Sequencers;
 use storage::{
-    read_admin, read_base_fee_per_gas, read_chain_id, read_delayed_transaction_bridge,
-    read_kernel_version, read_last_info_per_level_timestamp,
-    read_last_info_per_level_timestamp_stats, read_sequencer_admin, read_ticketer,
-    sequencer, store_base_fee_per_gas, store_chain_id, store_kernel_version,
-    store_storage_version, STORAGE_VERSION, STORAGE_VERSION_PATH,
+    read_admin, read_base_fee_per_gas, read_chain_id, read_da_fee_per_byte,
+    read_delayed_transaction_bridge, read_kernel_version,
+    read_last_info_per_level_timestamp, read_last_info_per_level_timestamp_stats,
+    read_sequencer_admin, read_ticketer, store_base_fee_per_gas, store_chain_id,
+    store_kernel_version, store_storage_version, STORAGE_VERSION, STORAGE_VERSION_PATH,
 };
 use tezos_crypto_rs::hash::ContractKt1Hash;
 use tezos_ethereum::block::BlockFees;
@@ -31,6 +31,7 @@ use tezos_smart_rollup_encoding::timestamp::Timestamp;
 use tezos_smart_rollup_entrypoint::kernel_entry;
 use tezos_smart_rollup_host::path::{concat, OwnedPath, RefPath};
 use tezos_smart_rollup_host::runtime::Runtime;
+use tick_model::constants::MAX_ALLOWED_TICKS;
 
 mod apply;
 mod block;
@@ -39,19 +40,27 @@ mod blueprint;
 mod blueprint_storage;
 mod delayed_inbox;
 mod error;
+mod fork_schedule;
+mod gas_price;
+mod genesis;
 mod inbox;
 mod indexable_storage;
 mod linked_list;
+mod logs_index;
 mod migration;
 mod mock_internal;
+mod outbox;
 mod parsing;
 mod safe_storage;
 mod sequencer_blueprint;
+mod sequencer_keys;
 mod simulation;
 mod stage_one;
 mod storage;
 mod tick_model;
+mod trie;
 mod upgrade;
+mod world_state;
 
 extern crate alloc;
 
@@ -64,8 +73,9 @@ pub const CHAIN_ID: u32 = 1337;
 /// Distinct from 'intrinsic base fee' of a simple Eth transfer: which costs 21_000 gas.
 pub const BASE_FEE_PER_GAS: u32 = 21_000;
 
-/// The configuration for the EVM execution.
-pub const CONFIG: Config = Config::shanghai();
+/// Default fee per byte of the transactions posted on the L1 inbox, the data
+/// availability fee is not charged unless set in the durable storage.
+pub const DA_FEE_PER_BYTE: u32 = 0;
 
 const KERNEL_VERSION: &str = env!("GIT_HASH");
 
@@ -148,15 +158,16 @@ fn retrieve_block_fees<Host: Runtime>(host: &mut Host) -> Result<BlockFees, Erro
         }
     };
 
-    let block_fees = BlockFees::new(base_fee_per_gas);
+    let da_fee_per_byte = read_da_fee_per_byte(host)?;
+
+    let block_fees = BlockFees::new(base_fee_per_gas, da_fee_per_byte);
 
     Ok(block_fees)
 }
 
 fn fetch_configuration<Host: Runtime>(host: &mut Host) -> anyhow::Result<Configuration> {
-    let sequencer = sequencer(host)?;
-    match sequencer {
-        Some(sequencer) => {
+    match Sequencers::read(host)? {
+        Some(sequencers) => {
             let delayed_bridge = read_delayed_transaction_bridge(host)
                 // The sequencer must declare a delayed transaction bridge. This
                 // default value is only to facilitate the testing.
@@ -170,7 +181,7 @@ fn fetch_configuration<Host: Runtime>(host: &mut Host) -> anyhow::Result<Configu
             Ok(Configuration::Sequencer {
                 delayed_bridge,
                 delayed_inbox,
-                sequencer,
+                sequencers,
             })
         }
         None => Ok(Configuration::Proxy),
@@ -205,6 +216,18 @@ pub fn main<Host: KernelRuntime>(host: &mut Host) -> Result<(), anyhow::Error> {
         }
     };
 
+    // The accounts allocated at genesis are checked in the first runs,
+    // rebooting as a migration does.
+    match genesis::check_genesis_state(host, MAX_ALLOWED_TICKS)
+        .context("Failed to check the genesis state")?
+    {
+        MigrationStatus::None => (),
+        MigrationStatus::InProgress | MigrationStatus::Done => {
+            host.mark_for_reboot()?;
+            return Ok(());
+        }
+    }
+
     // Fetch kernel metadata.
     let smart_rollup_address = retrieve_smart_rollup_address(host)
         .context("Failed to retrieve smart rollup address")?;
@@ -496,11 +519,20 @@ mod tests {
         let broken_kernel_upgrade = KernelUpgrade {
             preimage_hash: [0u8; PREIMAGE_HASH_SIZE],
             activation_timestamp: Timestamp::from(1_000_000i64),
+            activation_level: None,
         };
         crate::upgrade::store_kernel_upgrade(&mut host, &broken_kernel_upgrade)
             .expect("Should be able to store kernel upgrade");
 
-        let block_fees = BlockFees::new(DUMMY_BASE_FEE_PER_GAS.into());
+        let block_fees = BlockFees::new(DUMMY_BASE_FEE_PER_GAS.into(), U256::zero());
+        // The base fee of the second block must stay below the max fee per gas
+        // of its transactions.
+        storage::write_u256(
+            &mut host,
+            &RefPath::assert_from(b"/minimum_base_fee_per_gas").into(),
+            DUMMY_BASE_FEE_PER_GAS.into(),
+        )
+        .unwrap();
 
         // If the upgrade is started, it should raise an error
         crate::block::produce(
@@ -258,6 +258,17 @@ where
         storage::read_optional_rlp(host, &path)
     }
 
+    /// Moves the list and its elements to `path`, replacing what was stored
+    /// there.
+    pub fn move_to(&mut self, host: &mut impl Runtime, path: &impl Path) -> Result<()> {
+        if host.store_has(path)?.is_some() {
+            host.store_delete(path)?;
+        }
+        host.store_move(&self.path, path)?;
+        self.path = path.into();
+        self.save(host)
+    }
+
     /// Returns true if the list contains no elements.
     pub fn is_empty(&self) -> bool {
         self.pointers.is_none()
@@ -408,6 +419,13 @@ where
         Ok(Some(front.get_data(host, &self.path)?))
     }
 
+    /// Returns the id of the first element of the list
+    /// or `None` if it is empty.
+    pub fn first_id(&self) -> Option<&Id> {
+        let LinkedListPointer { front, .. } = self.pointers.as_ref()?;
+        Some(&front.id)
+    }
+
     /// Removes the first element of the list and returns it
     pub fn pop_first(&mut self, host: &mut impl Runtime) -> Result<Option<Elt>> {
         let Some(LinkedListPointer { front, .. }) = &self.pointers else {return Ok(None)};
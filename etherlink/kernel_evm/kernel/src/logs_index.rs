@@ -0,0 +1,440 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Index of the logs by emitting address and by first topic, to answer
+//! `eth_getLogs` without reading every receipt.
+//!
+//! Each address and each topic has an [IndexableStorage] of the positions
+//! of its logs. The positions are pushed in the order the logs are produced,
+//! they are therefore sorted, which allows to find the first log of a range
+//! of blocks by dichotomy. A query on both an address and a topic reads the
+//! positions of the index with the fewest ones in the range, and looks each
+//! of them up in the other index by dichotomy.
+
+use crate::error::Error;
+use crate::indexable_storage::IndexableStorage;
+use crate::storage::{init_address_logs_index, init_topic_logs_index};
+use primitive_types::{H160, H256, U256};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use tezos_ethereum::rlp_helpers::{append_option, decode_field, decode_option, next};
+use tezos_ethereum::transaction::IndexedLog;
+use tezos_smart_rollup_host::runtime::Runtime;
+
+/// Maximum number of positions returned by a query.
+pub const MAX_LOG_POSITIONS: usize = 1000;
+
+/// Maximum number of positions read by a query, each one is a read of the
+/// durable storage of about 55k ticks. The lookups of a position in an index
+/// are done by dichotomy, so a query intersecting two indexes reads a few
+/// dozen positions per position of the smallest one.
+pub const MAX_LOG_READS: u64 = 10_000;
+
+/// Position of a log in the chain.
+///
+/// Encoding: `[block_number, transaction_index, log_index]`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct LogPosition {
+    pub block_number: U256,
+    /// Index of the transaction in its block.
+    pub transaction_index: u32,
+    /// Index of the log in its block.
+    pub log_index: u64,
+}
+
+impl Encodable for LogPosition {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(3);
+        stream.append(&self.block_number);
+        stream.append(&self.transaction_index);
+        stream.append(&self.log_index);
+    }
+}
+
+impl Decodable for LogPosition {
+    fn decode(decoder: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let block_number = decode_field(&next(&mut it)?, "block_number")?;
+        let transaction_index = decode_field(&next(&mut it)?, "transaction_index")?;
+        let log_index = decode_field(&next(&mut it)?, "log_index")?;
+        Ok(Self {
+            block_number,
+            transaction_index,
+            log_index,
+        })
+    }
+}
+
+/// Indexes the `logs` of the transaction at `transaction_index` in the block
+/// `block_number`.
+pub fn index_logs<Host: Runtime>(
+    host: &mut Host,
+    block_number: U256,
+    transaction_index: u32,
+    logs: &[IndexedLog],
+) -> Result<(), Error> {
+    for IndexedLog { log, index } in logs {
+        let position = LogPosition {
+            block_number,
+            transaction_index,
+            log_index: *index,
+        }
+        .rlp_bytes();
+        init_address_logs_index(&log.address)?.push_value(host, &position)?;
+        if let Some(topic) = log.topics.first() {
+            init_topic_logs_index(topic)?.push_value(host, &position)?;
+        }
+    }
+    Ok(())
+}
+
+/// Filter of `eth_getLogs` answered by the index. At least the address or
+/// the topic is set.
+///
+/// Encoding: `[address, topic, from_block, to_block]` or `[address, topic,
+/// from_block, to_block, from_position]`, where the address and the topic are
+/// empty if they are not set.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LogsFilter {
+    pub address: Option<H160>,
+    /// First topic of the logs.
+    pub topic: Option<H256>,
+    pub from_block: U256,
+    pub to_block: U256,
+    /// Position to continue a query from, the `next` position of the result
+    /// of the previous query with the same filter.
+    pub from_position: Option<LogPosition>,
+}
+
+impl Decodable for LogsFilter {
+    fn decode(decoder: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        let item_count = decoder.item_count()?;
+        if item_count != 4 && item_count != 5 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let address = decode_option(&next(&mut it)?, "address")?;
+        let topic = decode_option(&next(&mut it)?, "topic")?;
+        let from_block = decode_field(&next(&mut it)?, "from_block")?;
+        let to_block = decode_field(&next(&mut it)?, "to_block")?;
+        let from_position = if item_count == 5 {
+            decode_option(&next(&mut it)?, "from_position")?
+        } else {
+            None
+        };
+        if address.is_none() && topic.is_none() {
+            return Err(DecoderError::Custom(
+                "Logs filter without address nor topic",
+            ));
+        }
+        Ok(Self {
+            address,
+            topic,
+            from_block,
+            to_block,
+            from_position,
+        })
+    }
+}
+
+/// Result of a query, the positions of the matching logs in order and the
+/// position to continue from if the query stopped before the end of the
+/// range of blocks.
+///
+/// Encoding: `[positions, next]`, where `next` is empty if the query is
+/// complete.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LogsResult {
+    pub positions: Vec<LogPosition>,
+    pub next: Option<LogPosition>,
+}
+
+impl Encodable for LogsResult {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(2);
+        stream.append_list(&self.positions);
+        append_option(stream, &self.next);
+    }
+}
+
+/// Reads the positions of the indexes, counting the reads.
+struct Reader<'a, Host> {
+    host: &'a Host,
+    reads: u64,
+}
+
+impl<'a, Host: Runtime> Reader<'a, Host> {
+    fn read(&mut self, index: &IndexableStorage, i: u64) -> Result<LogPosition, Error> {
+        self.reads += 1;
+        let bytes = index.unsafe_get_value(self.host, i)?;
+        Ok(LogPosition::decode(&Rlp::new(&bytes))?)
+    }
+
+    /// First position of `index` between `low` and `high` which isn't
+    /// `before`, found by dichotomy as the positions are sorted.
+    fn search(
+        &mut self,
+        index: &IndexableStorage,
+        mut low: u64,
+        mut high: u64,
+        before: impl Fn(&LogPosition) -> bool,
+    ) -> Result<u64, Error> {
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if before(&self.read(index, middle)?) {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        Ok(low)
+    }
+}
+
+/// Positions `start..end` of an index in the range of blocks of a filter.
+struct Range {
+    index: IndexableStorage,
+    start: u64,
+    end: u64,
+}
+
+/// Positions of the logs matching `filter`, in order. A query returns at
+/// most [MAX_LOG_POSITIONS] positions and stops after about [MAX_LOG_READS]
+/// reads, the result then gives the position to continue from.
+pub fn query<Host: Runtime>(
+    host: &Host,
+    filter: &LogsFilter,
+) -> Result<LogsResult, Error> {
+    let mut reader = Reader { host, reads: 0 };
+    let from = filter.from_position.clone().unwrap_or(LogPosition {
+        block_number: filter.from_block,
+        transaction_index: 0,
+        log_index: 0,
+    });
+    let mut indexes = Vec::with_capacity(2);
+    if let Some(address) = &filter.address {
+        indexes.push(init_address_logs_index(address)?);
+    }
+    if let Some(topic) = &filter.topic {
+        indexes.push(init_topic_logs_index(topic)?);
+    }
+    let mut ranges = Vec::with_capacity(2);
+    for index in indexes {
+        let length = index.length(host)?;
+        let start = reader.search(&index, 0, length, |position| *position < from)?;
+        let end = reader.search(&index, start, length, |position| {
+            position.block_number <= filter.to_block
+        })?;
+        ranges.push(Range { index, start, end });
+    }
+
+    // The positions of the smallest range are read in order. When both the
+    // address and the topic are set, each one is looked up in the other
+    // range, after the previous one.
+    ranges.sort_by_key(|range| range.end - range.start);
+    let Some((scanned, others)) = ranges.split_first_mut() else {
+        return Ok(LogsResult {
+            positions: vec![],
+            next: None,
+        });
+    };
+    let mut positions = Vec::new();
+    for i in scanned.start..scanned.end {
+        let position = reader.read(&scanned.index, i)?;
+        if positions.len() == MAX_LOG_POSITIONS || reader.reads > MAX_LOG_READS {
+            return Ok(LogsResult {
+                positions,
+                next: Some(position),
+            });
+        }
+        let mut matches = true;
+        for other in others.iter_mut() {
+            other.start =
+                reader.search(&other.index, other.start, other.end, |other| {
+                    *other < position
+                })?;
+            matches &= other.start < other.end
+                && reader.read(&other.index, other.start)? == position;
+        }
+        if matches {
+            positions.push(position);
+        }
+    }
+    Ok(LogsResult {
+        positions,
+        next: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tezos_ethereum::Log;
+    use tezos_smart_rollup_mock::MockHost;
+
+    fn log(address: u64, topic: Option<u64>, index: u64) -> IndexedLog {
+        IndexedLog {
+            log: Log {
+                address: H160::from_low_u64_be(address),
+                topics: topic.into_iter().map(H256::from_low_u64_be).collect(),
+                data: vec![],
+            },
+            index,
+        }
+    }
+
+    fn position(
+        block_number: u64,
+        transaction_index: u32,
+        log_index: u64,
+    ) -> LogPosition {
+        LogPosition {
+            block_number: U256::from(block_number),
+            transaction_index,
+            log_index,
+        }
+    }
+
+    fn filter(
+        address: Option<u64>,
+        topic: Option<u64>,
+        from: u64,
+        to: u64,
+    ) -> LogsFilter {
+        LogsFilter {
+            address: address.map(H160::from_low_u64_be),
+            topic: topic.map(H256::from_low_u64_be),
+            from_block: U256::from(from),
+            to_block: U256::from(to),
+            from_position: None,
+        }
+    }
+
+    #[test]
+    fn test_query_logs() {
+        let mut host = MockHost::default();
+        for block in 0..10u64 {
+            let logs = vec![
+                log(1, Some(10), 0),
+                log(2, Some(block % 2 + 10), 1),
+                log(1, None, 2),
+            ];
+            index_logs(&mut host, U256::from(block), 0, &logs).unwrap();
+            index_logs(&mut host, U256::from(block), 1, &[log(2, Some(11), 3)]).unwrap();
+        }
+
+        let positions = query(&host, &filter(Some(1), None, 3, 4))
+            .unwrap()
+            .positions;
+        assert_eq!(
+            positions,
+            vec![
+                position(3, 0, 0),
+                position(3, 0, 2),
+                position(4, 0, 0),
+                position(4, 0, 2)
+            ]
+        );
+
+        let positions = query(&host, &filter(None, Some(10), 8, 100))
+            .unwrap()
+            .positions;
+        assert_eq!(
+            positions,
+            vec![position(8, 0, 0), position(8, 0, 1), position(9, 0, 0)]
+        );
+
+        let positions = query(&host, &filter(Some(2), Some(10), 0, 5))
+            .unwrap()
+            .positions;
+        assert_eq!(
+            positions,
+            vec![position(0, 0, 1), position(2, 0, 1), position(4, 0, 1)]
+        );
+
+        let positions = query(&host, &filter(Some(3), None, 0, 100))
+            .unwrap()
+            .positions;
+        assert!(positions.is_empty());
+    }
+
+    // Queries the positions matching `filter` until the query is complete,
+    // and returns them with the number of queries.
+    fn query_all(host: &MockHost, mut filter: LogsFilter) -> (Vec<LogPosition>, usize) {
+        let mut positions = vec![];
+        let mut queries = 0;
+        loop {
+            let result = query(host, &filter).unwrap();
+            positions.extend(result.positions);
+            queries += 1;
+            match result.next {
+                Some(next) => filter.from_position = Some(next),
+                None => return (positions, queries),
+            }
+        }
+    }
+
+    #[test]
+    fn test_query_is_bounded() {
+        let mut host = MockHost::default();
+        let logs: Vec<IndexedLog> = (0..MAX_LOG_POSITIONS as u64 + 10)
+            .map(|i| log(1, None, i))
+            .collect();
+        index_logs(&mut host, U256::one(), 0, &logs).unwrap();
+        let result = query(&host, &filter(Some(1), None, 0, 1)).unwrap();
+        assert_eq!(result.positions.len(), MAX_LOG_POSITIONS);
+        assert_eq!(result.next, Some(position(1, 0, MAX_LOG_POSITIONS as u64)));
+
+        // The query continues in the same block.
+        let (positions, queries) = query_all(&host, filter(Some(1), None, 0, 1));
+        assert_eq!(queries, 2);
+        let expected: Vec<LogPosition> =
+            logs.iter().map(|log| position(1, 0, log.index)).collect();
+        assert_eq!(positions, expected);
+    }
+
+    #[test]
+    fn test_query_reads_are_bounded() {
+        let mut host = MockHost::default();
+        // Many logs of the address, and of the topic from another address,
+        // only the last one of the address matches both.
+        for block in 0..1_000u64 {
+            let logs = vec![log(1, Some(10), 0), log(2, Some(11), 1)];
+            index_logs(&mut host, U256::from(block), 0, &logs).unwrap();
+        }
+        let logs = vec![log(1, Some(11), 0), log(1, Some(12), 1)];
+        index_logs(&mut host, U256::from(1_000), 0, &logs).unwrap();
+
+        let result = query(&host, &filter(Some(1), Some(11), 0, 1_000)).unwrap();
+        assert!(result.positions.is_empty());
+        assert!(result.next.is_some());
+        let (positions, queries) = query_all(&host, filter(Some(1), Some(11), 0, 1_000));
+        assert!(queries > 1);
+        assert_eq!(positions, vec![position(1_000, 0, 0)]);
+
+        // The topic has a single log, which is looked up in the index of the
+        // address.
+        let result = query(&host, &filter(Some(1), Some(12), 0, 1_000)).unwrap();
+        assert_eq!(result.positions, vec![position(1_000, 0, 1)]);
+        assert_eq!(result.next, None);
+    }
+
+    #[test]
+    fn test_filter_needs_address_or_topic() {
+        let mut stream = RlpStream::new_list(4);
+        stream.append_empty_data();
+        stream.append_empty_data();
+        stream.append(&U256::zero());
+        stream.append(&U256::one());
+        assert!(LogsFilter::decode(&Rlp::new(&stream.out())).is_err());
+    }
+}
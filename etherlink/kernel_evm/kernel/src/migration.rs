@@ -2,13 +2,19 @@
 // SPDX-FileCopyrightText: 2023 Nomadic Labs <contact@nomadic-labs.com>
 //
 // SPDX-License-Identifier: MIT
+use crate::delayed_inbox::migrate_legacy_items;
 use crate::error::Error;
 use crate::error::UpgradeProcessError::Fallback;
 use crate::storage::{
-    read_storage_version, store_storage_version, SEQUENCER_ADMIN, STORAGE_VERSION,
+    init_account_index, read_storage_version, store_storage_version, SEQUENCER_ADMIN,
+    STORAGE_VERSION,
 };
+use crate::tick_model::constants::MAX_ALLOWED_TICKS;
+use crate::world_state::add_indexed_accounts;
+use tezos_smart_rollup_host::path::RefPath;
 use tezos_smart_rollup_host::runtime::Runtime;
 
+#[derive(Debug, PartialEq, Eq)]
 pub enum MigrationStatus {
     None,
     InProgress,
@@ -24,6 +30,39 @@ fn store_sequencer_admin<Host: Runtime>(host: &mut Host) -> Result<(), Error> {
         .map_err(Into::into)
 }
 
+/// Indexes of the storage of the contracts existing before the state trie
+/// was introduced (see [crate::world_state::add_indexed_accounts]). The
+/// kernel not being able to list the subkeys of a path, the installer of the
+/// upgrade writes them from a dump of the durable storage. They are checked
+/// with the number of values in the storage of each contract.
+const STATE_TRIE_STORAGE_INDEXES: RefPath =
+    RefPath::assert_from(b"/world_state/migration");
+
+/// Fills the state trie with the accounts of the index of accounts, in as
+/// many reboots as needed. A reboot adds about 500 storage slots at the
+/// benchmarked cost of a touched slot, which keeps the migration under the
+/// limit of reboots for about 500 thousand slots.
+fn fill_state_trie<Host: Runtime>(host: &mut Host) -> Result<MigrationStatus, Error> {
+    let accounts = init_account_index()?;
+    if add_indexed_accounts(
+        host,
+        &accounts,
+        &STATE_TRIE_STORAGE_INDEXES,
+        MAX_ALLOWED_TICKS,
+    )? {
+        if host.store_has(&STATE_TRIE_STORAGE_INDEXES)?.is_some() {
+            host.store_delete(&STATE_TRIE_STORAGE_INDEXES)?;
+        }
+        Ok(MigrationStatus::Done)
+    } else {
+        Ok(MigrationStatus::InProgress)
+    }
+}
+
+/// Number of transactions of the delayed inbox rewritten per reboot, each
+/// one is read, written back and unlinked from the previous list.
+const DELAYED_ITEMS_PER_REBOOT: usize = 500;
+
 // The workflow for migration is the following:
 //
 // - bump `storage::STORAGE_VERSION` by one
@@ -47,6 +86,14 @@ fn migration<Host: Runtime>(host: &mut Host) -> Result<MigrationStatus, Error> {
     if STORAGE_VERSION == current_version + 1 {
         // MIGRATION CODE - START
         store_sequencer_admin(host)?;
+        if let MigrationStatus::InProgress = fill_state_trie(host)? {
+            return Ok(MigrationStatus::InProgress);
+        }
+        if !migrate_legacy_items(host, DELAYED_ITEMS_PER_REBOOT)
+            .map_err(|_| Error::UpgradeError(Fallback))?
+        {
+            return Ok(MigrationStatus::InProgress);
+        }
         // MIGRATION CODE - END
         store_storage_version(host, STORAGE_VERSION)?;
         return Ok(MigrationStatus::Done);
@@ -0,0 +1,557 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Queue of the messages sent to layer 1.
+//!
+//! The withdrawals and calls to layer 1 of the transactions are pushed in a
+//! queue, flushed in the outbox once the blueprints of the level are applied. Consecutive
+//! transactions that cannot fail on layer 1, withdrawals of the native token
+//! to implicit accounts, are batched in the same outbox message up to its
+//! maximum size. The other transactions are sent alone, as the failure of
+//! one transaction fails the whole batch.
+//!
+//! At most [MAX_OUTBOX_MESSAGES_PER_LEVEL] messages can be written at each
+//! level, the rest of the queue is flushed during the next levels. The
+//! position of each message in the outbox is recorded in the outbox receipts
+//! of its transaction.
+
+use crate::error::{EncodingError, Error};
+use crate::indexable_storage::IndexableStorage;
+use crate::storage::{read_optional_rlp, store_rlp};
+use crate::tick_model::constants::{
+    OUTBOX_MESSAGE_TICKS_COEF, OUTBOX_MESSAGE_TICKS_INTERCEPT,
+};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use tezos_data_encoding::enc::BinWriter;
+use tezos_data_encoding::nom::NomReader;
+use tezos_ethereum::rlp_helpers::{decode_field, next};
+use tezos_ethereum::transaction::TransactionHash;
+use tezos_ethereum::withdrawal::MichelineExpr;
+use tezos_smart_rollup_core::MAX_OUTPUT_SIZE;
+use tezos_smart_rollup_encoding::michelson::Michelson;
+use tezos_smart_rollup_encoding::outbox::{
+    OutboxMessage, OutboxMessageTransaction, OutboxMessageTransactionTyped,
+};
+use tezos_smart_rollup_host::path::{concat, OwnedPath, RefPath};
+use tezos_smart_rollup_host::runtime::{Runtime, RuntimeError, ValueType};
+
+const OUTBOX_STATE: RefPath = RefPath::assert_from(b"/outbox/state");
+
+const OUTBOX_QUEUE: RefPath = RefPath::assert_from(b"/outbox/queue");
+
+const OUTBOX_RECEIPTS: RefPath = RefPath::assert_from(b"/outbox/receipts");
+
+/// Maximum number of messages in the outbox of a level, set by the protocol.
+pub const MAX_OUTBOX_MESSAGES_PER_LEVEL: u32 = 100;
+
+/// Size of an outbox message without its transactions: its tag and the size
+/// of its list of transactions.
+const OUTBOX_MESSAGE_OVERHEAD: usize = 5;
+
+/// Progress of the flush of the queue.
+///
+/// Encoding: `[level, level_messages, queue_head]`.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct OutboxState {
+    /// Current level of layer 1.
+    level: u32,
+    /// Number of messages written in the outbox of the current level.
+    level_messages: u32,
+    /// Index in the queue of the next transaction to flush.
+    queue_head: u64,
+}
+
+impl Encodable for OutboxState {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(3);
+        stream.append(&self.level);
+        stream.append(&self.level_messages);
+        stream.append(&self.queue_head);
+    }
+}
+
+impl Decodable for OutboxState {
+    fn decode(decoder: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let level = decode_field(&next(&mut it)?, "level")?;
+        let level_messages = decode_field(&next(&mut it)?, "level_messages")?;
+        let queue_head = decode_field(&next(&mut it)?, "queue_head")?;
+        Ok(Self {
+            level,
+            level_messages,
+            queue_head,
+        })
+    }
+}
+
+fn read_state<Host: Runtime>(host: &Host) -> Result<OutboxState, Error> {
+    Ok(read_optional_rlp(host, &OUTBOX_STATE)
+        .map_err(|_| Error::InvalidParsing)?
+        .unwrap_or_default())
+}
+
+/// A transaction waiting in the queue.
+///
+/// Encoding: `[tx_hash, batchable, typed, transaction]`, where the
+/// transaction is in the binary encoding of the outbox.
+#[derive(Debug, PartialEq, Eq)]
+struct QueuedTransaction {
+    /// Hash of the transaction of the rollup that sent the message.
+    tx_hash: TransactionHash,
+    /// Whether the transaction can be batched with others.
+    batchable: bool,
+    /// Whether the transaction has the type of its parameters, it is then
+    /// sent alone in a typed outbox message.
+    typed: bool,
+    transaction: Vec<u8>,
+}
+
+impl Encodable for QueuedTransaction {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(4);
+        stream.append(&self.tx_hash.to_vec());
+        stream.append(&self.batchable);
+        stream.append(&self.typed);
+        stream.append(&self.transaction);
+    }
+}
+
+impl Decodable for QueuedTransaction {
+    fn decode(decoder: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let tx_hash: Vec<u8> = decode_field(&next(&mut it)?, "tx_hash")?;
+        let tx_hash = tx_hash
+            .try_into()
+            .map_err(|_| DecoderError::Custom("Invalid transaction hash"))?;
+        let batchable = decode_field(&next(&mut it)?, "batchable")?;
+        let typed = decode_field(&next(&mut it)?, "typed")?;
+        let transaction = decode_field(&next(&mut it)?, "transaction")?;
+        Ok(Self {
+            tx_hash,
+            batchable,
+            typed,
+            transaction,
+        })
+    }
+}
+
+/// Position of a message of a transaction in the outbox.
+///
+/// Encoding: `[level, message_index, transaction_index]`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OutboxReceipt {
+    /// Level of the outbox.
+    pub level: u32,
+    /// Index of the outbox message in the level.
+    pub message_index: u32,
+    /// Index of the transaction in the batch of the outbox message.
+    pub transaction_index: u32,
+}
+
+impl Encodable for OutboxReceipt {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(3);
+        stream.append(&self.level);
+        stream.append(&self.message_index);
+        stream.append(&self.transaction_index);
+    }
+}
+
+impl Decodable for OutboxReceipt {
+    fn decode(decoder: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let level = decode_field(&next(&mut it)?, "level")?;
+        let message_index = decode_field(&next(&mut it)?, "message_index")?;
+        let transaction_index = decode_field(&next(&mut it)?, "transaction_index")?;
+        Ok(Self {
+            level,
+            message_index,
+            transaction_index,
+        })
+    }
+}
+
+fn receipts_path(tx_hash: &TransactionHash) -> Result<OwnedPath, Error> {
+    let hash_path: Vec<u8> = format!("/{}", hex::encode(tx_hash)).into();
+    let hash_path = OwnedPath::try_from(hash_path)?;
+    concat(&OUTBOX_RECEIPTS, &hash_path).map_err(Error::from)
+}
+
+/// Outbox receipts of a transaction, in the order its messages were sent,
+/// stored under `/outbox/receipts/<tx_hash>`. Messages still in the queue
+/// have no receipt yet.
+pub fn read_outbox_receipts<Host: Runtime>(
+    host: &Host,
+    tx_hash: &TransactionHash,
+) -> Result<Vec<OutboxReceipt>, Error> {
+    let path = receipts_path(tx_hash)?;
+    match host.store_has(&path)? {
+        Some(ValueType::Value | ValueType::ValueWithSubtree) => {
+            let bytes = host.store_read_all(&path)?;
+            Rlp::new(&bytes).as_list().map_err(Error::from)
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+fn add_outbox_receipt<Host: Runtime>(
+    host: &mut Host,
+    tx_hash: &TransactionHash,
+    receipt: OutboxReceipt,
+) -> Result<(), Error> {
+    let mut receipts = read_outbox_receipts(host, tx_hash)?;
+    receipts.push(receipt);
+    let mut stream = RlpStream::new();
+    stream.append_list(&receipts);
+    host.store_write_all(&receipts_path(tx_hash)?, &stream.out())
+        .map_err(Error::from)
+}
+
+/// Records the level of layer 1 whose inbox is read, the outbox messages
+/// are written at this level.
+pub fn start_level<Host: Runtime>(host: &mut Host, level: u32) -> Result<(), Error> {
+    let state = read_state(host)?;
+    if state.level != level {
+        let state = OutboxState {
+            level,
+            level_messages: 0,
+            ..state
+        };
+        store_rlp(&state, host, &OUTBOX_STATE)?;
+    }
+    Ok(())
+}
+
+/// Pushes a transaction of the outbox in the queue. The precompiled contracts
+/// revert the withdrawals and calls too big to fit in an outbox message
+/// before burning their value, pushing a transaction too big is an error, the
+/// changes of the kernel run are then discarded.
+pub fn push_transaction<Host: Runtime, Expr: Michelson>(
+    host: &mut Host,
+    tx_hash: &TransactionHash,
+    transaction: OutboxMessageTransaction<Expr>,
+    batchable: bool,
+) -> Result<(), Error> {
+    let mut encoded = vec![];
+    transaction.bin_write(&mut encoded)?;
+    push_queued(
+        host,
+        QueuedTransaction {
+            tx_hash: *tx_hash,
+            batchable,
+            typed: false,
+            transaction: encoded,
+        },
+    )
+}
+
+/// Pushes a transaction of the outbox with the type of its parameters in the
+/// queue, see [push_transaction]. It is never batched.
+pub fn push_typed_transaction<Host: Runtime, Expr: Michelson>(
+    host: &mut Host,
+    tx_hash: &TransactionHash,
+    transaction: OutboxMessageTransactionTyped<Expr>,
+) -> Result<(), Error> {
+    let mut encoded = vec![];
+    transaction.bin_write(&mut encoded)?;
+    push_queued(
+        host,
+        QueuedTransaction {
+            tx_hash: *tx_hash,
+            batchable: false,
+            typed: true,
+            transaction: encoded,
+        },
+    )
+}
+
+fn push_queued<Host: Runtime>(
+    host: &mut Host,
+    queued: QueuedTransaction,
+) -> Result<(), Error> {
+    if OUTBOX_MESSAGE_OVERHEAD + queued.transaction.len() > MAX_OUTPUT_SIZE {
+        return Err(Error::Encoding(EncodingError::OutboxTransactionTooBig(
+            queued.transaction.len(),
+        )));
+    }
+    let mut queue = IndexableStorage::new(&OUTBOX_QUEUE)?;
+    queue.push_value(host, &queued.rlp_bytes())?;
+    Ok(())
+}
+
+fn ticks_of_outbox_message(transactions: usize) -> u64 {
+    (transactions as u64)
+        .saturating_mul(OUTBOX_MESSAGE_TICKS_COEF)
+        .saturating_add(OUTBOX_MESSAGE_TICKS_INTERCEPT)
+}
+
+/// Decodes the transactions of a batch.
+fn decode_transactions<T: NomReader>(
+    batch: &[QueuedTransaction],
+) -> Result<Vec<T>, Error> {
+    batch
+        .iter()
+        .map(|queued| {
+            T::nom_read(&queued.transaction)
+                .map(|(_, transaction)| transaction)
+                .map_err(|_| Error::InvalidParsing)
+        })
+        .collect()
+}
+
+fn read_queued<Host: Runtime>(
+    host: &Host,
+    queue: &IndexableStorage,
+    index: u64,
+) -> Result<QueuedTransaction, Error> {
+    let bytes = queue.unsafe_get_value(host, index)?;
+    QueuedTransaction::decode(&Rlp::new(&bytes)).map_err(Error::from)
+}
+
+/// Writes the queued transactions in outbox messages, until the queue is
+/// empty, the outbox of the level is full or the estimated ticks would exceed
+/// `max_ticks`. Returns the estimated ticks used.
+pub fn flush_queue<Host: Runtime>(host: &mut Host, max_ticks: u64) -> Result<u64, Error> {
+    let mut state = read_state(host)?;
+    let queue = IndexableStorage::new(&OUTBOX_QUEUE)?;
+    let length = queue.length(host)?;
+    let mut ticks = 0u64;
+
+    while state.queue_head < length
+        && state.level_messages < MAX_OUTBOX_MESSAGES_PER_LEVEL
+        && ticks.saturating_add(ticks_of_outbox_message(1)) <= max_ticks
+    {
+        let first = read_queued(host, &queue, state.queue_head)?;
+        let mut size = OUTBOX_MESSAGE_OVERHEAD + first.transaction.len();
+        let mut batch = vec![first];
+        let mut next = state.queue_head + 1;
+        while batch[0].batchable
+            && next < length
+            && ticks.saturating_add(ticks_of_outbox_message(batch.len() + 1)) <= max_ticks
+        {
+            let queued = read_queued(host, &queue, next)?;
+            if !queued.batchable || size + queued.transaction.len() > MAX_OUTPUT_SIZE {
+                break;
+            }
+            size += queued.transaction.len();
+            batch.push(queued);
+            next += 1;
+        }
+
+        let message: OutboxMessage<MichelineExpr> = if batch[0].typed {
+            OutboxMessage::AtomicTransactionBatchTyped(
+                decode_transactions(&batch)?.into(),
+            )
+        } else {
+            OutboxMessage::AtomicTransactionBatch(decode_transactions(&batch)?.into())
+        };
+        let mut encoded = Vec::with_capacity(MAX_OUTPUT_SIZE);
+        message.bin_write(&mut encoded)?;
+
+        match host.write_output(&encoded) {
+            Ok(()) => (),
+            Err(RuntimeError::HostErr(tezos_smart_rollup_host::Error::FullOutbox)) => {
+                // The outbox was filled outside of the queue, it is flushed
+                // at the next level.
+                state.level_messages = MAX_OUTBOX_MESSAGES_PER_LEVEL;
+                break;
+            }
+            Err(err) => return Err(err.into()),
+        }
+
+        for (transaction_index, queued) in batch.iter().enumerate() {
+            let receipt = OutboxReceipt {
+                level: state.level,
+                message_index: state.level_messages,
+                transaction_index: transaction_index as u32,
+            };
+            add_outbox_receipt(host, &queued.tx_hash, receipt)?;
+        }
+        for index in state.queue_head..next {
+            queue.delete_value(host, index)?;
+        }
+        state.queue_head = next;
+        state.level_messages += 1;
+        ticks = ticks.saturating_add(ticks_of_outbox_message(batch.len()));
+    }
+
+    store_rlp(&state, host, &OUTBOX_STATE)?;
+    Ok(ticks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tezos_crypto_rs::hash::{ContractKt1Hash, HashTrait};
+    use tezos_smart_rollup_encoding::contract::Contract;
+    use tezos_smart_rollup_encoding::entrypoint::Entrypoint;
+    use tezos_smart_rollup_mock::MockHost;
+
+    fn transaction(parameters: Vec<u8>) -> OutboxMessageTransaction<MichelineExpr> {
+        OutboxMessageTransaction {
+            parameters: MichelineExpr::from_bytes(parameters).unwrap(),
+            destination: Contract::Originated(
+                ContractKt1Hash::from_b58check("KT1BuEZtb68c1Q4yjtckcNjGELqWt56Xyesc")
+                    .unwrap(),
+            ),
+            entrypoint: Entrypoint::try_from(String::from("burn")).unwrap(),
+        }
+    }
+
+    // A string of `n` bytes, as Micheline.
+    fn string(n: usize) -> Vec<u8> {
+        let mut bytes = vec![0x01];
+        bytes.extend_from_slice(&(n as u32).to_be_bytes());
+        bytes.resize(5 + n, 0x61);
+        bytes
+    }
+
+    fn batch_lengths(host: &MockHost, level: u32) -> Vec<usize> {
+        host.outbox_at(level)
+            .iter()
+            .map(|message| {
+                let (rest, message) =
+                    OutboxMessage::<MichelineExpr>::nom_read(message).unwrap();
+                assert!(rest.is_empty());
+                match message {
+                    OutboxMessage::AtomicTransactionBatch(batch) => batch.len(),
+                    OutboxMessage::AtomicTransactionBatchTyped(batch) => batch.len(),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_flush_batches_transactions() {
+        let mut host = MockHost::default();
+        let level = host.level();
+        start_level(&mut host, level).unwrap();
+        let (tx_a, tx_b) = ([1; 32], [2; 32]);
+
+        push_transaction(&mut host, &tx_a, transaction(string(1)), true).unwrap();
+        push_transaction(&mut host, &tx_b, transaction(string(2)), true).unwrap();
+        push_transaction(&mut host, &tx_a, transaction(string(3)), false).unwrap();
+        push_transaction(&mut host, &tx_b, transaction(string(4)), true).unwrap();
+        // Does not fit in the outbox message of the previous transaction.
+        push_transaction(&mut host, &tx_b, transaction(string(4040)), true).unwrap();
+        // Too big for any message.
+        assert!(
+            push_transaction(&mut host, &tx_b, transaction(string(5000)), true).is_err()
+        );
+
+        let ticks = flush_queue(&mut host, u64::MAX).unwrap();
+        assert_eq!(
+            ticks,
+            ticks_of_outbox_message(2) + 3 * ticks_of_outbox_message(1)
+        );
+        assert_eq!(batch_lengths(&host, level), vec![2, 1, 1, 1]);
+
+        let receipt = |message_index, transaction_index| OutboxReceipt {
+            level,
+            message_index,
+            transaction_index,
+        };
+        assert_eq!(
+            read_outbox_receipts(&host, &tx_a).unwrap(),
+            vec![receipt(0, 0), receipt(1, 0)]
+        );
+        assert_eq!(
+            read_outbox_receipts(&host, &tx_b).unwrap(),
+            vec![receipt(0, 1), receipt(2, 0), receipt(3, 0)]
+        );
+
+        // The queue is empty.
+        assert_eq!(flush_queue(&mut host, u64::MAX).unwrap(), 0);
+        assert_eq!(host.outbox_at(level).len(), 4);
+    }
+
+    #[test]
+    fn test_flush_sends_typed_transactions_alone() {
+        let mut host = MockHost::default();
+        let level = host.level();
+        start_level(&mut host, level).unwrap();
+        let tx_hash = [1; 32];
+        let untyped = transaction(string(1));
+        let typed = OutboxMessageTransactionTyped {
+            parameters: untyped.parameters.clone(),
+            // string
+            parameters_ty: MichelineExpr::from_bytes(vec![0x03, 0x68]).unwrap(),
+            destination: untyped.destination.clone(),
+            entrypoint: untyped.entrypoint.clone(),
+        };
+
+        // The typed transaction is not batched with its neighbours.
+        push_transaction(&mut host, &tx_hash, untyped, true).unwrap();
+        push_typed_transaction(&mut host, &tx_hash, typed).unwrap();
+        push_transaction(&mut host, &tx_hash, transaction(string(2)), true).unwrap();
+        flush_queue(&mut host, u64::MAX).unwrap();
+
+        let outbox = host.outbox_at(level);
+        assert_eq!(batch_lengths(&host, level), vec![1, 1, 1]);
+        let (_, message) = OutboxMessage::<MichelineExpr>::nom_read(&outbox[1]).unwrap();
+        let OutboxMessage::AtomicTransactionBatchTyped(batch) = message else {
+            panic!("The transaction is not in a typed outbox message")
+        };
+        assert_eq!(batch[0].parameters_ty.as_bytes(), [0x03, 0x68]);
+    }
+
+    #[test]
+    fn test_flush_respects_limits() {
+        let mut host = MockHost::default();
+        let level = host.level();
+        start_level(&mut host, level).unwrap();
+        let tx_hash = [1; 32];
+        let total = MAX_OUTBOX_MESSAGES_PER_LEVEL + 10;
+        for _ in 0..total {
+            push_transaction(&mut host, &tx_hash, transaction(string(1)), false).unwrap();
+        }
+
+        // The ticks limit the number of messages.
+        flush_queue(&mut host, 3 * ticks_of_outbox_message(1)).unwrap();
+        assert_eq!(host.outbox_at(level).len(), 3);
+
+        // The level limits the number of messages.
+        flush_queue(&mut host, u64::MAX).unwrap();
+        assert_eq!(
+            host.outbox_at(level).len(),
+            MAX_OUTBOX_MESSAGES_PER_LEVEL as usize
+        );
+
+        // The rest is flushed at the next level.
+        host.run_level(|_| {});
+        let next_level = host.level();
+        start_level(&mut host, next_level).unwrap();
+        flush_queue(&mut host, u64::MAX).unwrap();
+        assert_eq!(host.outbox_at(next_level).len(), 10);
+
+        let receipts = read_outbox_receipts(&host, &tx_hash).unwrap();
+        assert_eq!(receipts.len(), total as usize);
+        assert_eq!(
+            receipts.last(),
+            Some(&OutboxReceipt {
+                level: next_level,
+                message_index: 9,
+                transaction_index: 0,
+            })
+        );
+    }
+}
@@ -6,28 +6,40 @@
 // SPDX-License-Identifier: MIT
 
 use crate::{
-    inbox::{Deposit, TezosContracts, Transaction, TransactionContent},
+    fork_schedule::ForkSchedule,
+    inbox::{Deposit, FaDeposit, TezosContracts, Transaction, TransactionContent},
     sequencer_blueprint::{SequencerBlueprint, UnsignedSequencerBlueprint},
-    upgrade::KernelUpgrade,
+    sequencer_keys::{SequencerUpdate, Sequencers},
+    upgrade::{
+        Approver, KernelUpgrade, SignedUpgradeApproval, UpgradeDecision,
+        UpgradeGovernance, UpgradeProposal,
+    },
 };
+use num_bigint::BigInt;
+use num_traits::Signed;
 use primitive_types::{H160, U256};
 use rlp::Encodable;
 use sha3::{Digest, Keccak256};
-use tezos_crypto_rs::{hash::ContractKt1Hash, PublicKeySignatureVerifier};
+use tezos_crypto_rs::hash::ContractKt1Hash;
 use tezos_ethereum::{
+    fa_ticket::FaTicket,
     rlp_helpers::FromRlpBytes,
     transaction::{TransactionHash, TRANSACTION_HASH_SIZE},
     tx_common::EthereumTransactionCommon,
     wei::eth_from_mutez,
 };
 use tezos_evm_logging::{log, Level::*};
+use tezos_smart_rollup_core::PREIMAGE_HASH_SIZE;
 use tezos_smart_rollup_encoding::{
     contract::Contract,
     inbox::{
         ExternalMessageFrame, InboxMessage, InfoPerLevel, InternalInboxMessage, Transfer,
     },
-    michelson::{ticket::FA2_1Ticket, MichelsonBytes, MichelsonOr, MichelsonPair},
-    public_key::PublicKey,
+    michelson::{
+        ticket::FA2_1Ticket, MichelsonBytes, MichelsonInt, MichelsonOption, MichelsonOr,
+        MichelsonPair,
+    },
+    public_key_hash::PublicKeyHash,
 };
 use tezos_smart_rollup_host::input::Message;
 use tezos_smart_rollup_host::runtime::Runtime;
@@ -67,6 +79,8 @@ const TRANSACTION_CHUNK_TAG: u8 = 2;
 
 const SEQUENCER_BLUEPRINT_TAG: u8 = 3;
 
+const UPGRADE_APPROVAL_TAG: u8 = 4;
+
 pub const MAX_SIZE_PER_CHUNK: usize = 4095 // Max input size minus external tag
             - 1 // ExternalMessageFrame tag
             - 20 // Smart rollup address size (ExternalMessageFrame::Targetted)
@@ -79,8 +93,16 @@ pub const MAX_SIZE_PER_CHUNK: usize = 4095 // Max input size minus external tag
 pub enum Input {
     SimpleTransaction(Box<Transaction>),
     Deposit(Deposit),
+    FaDeposit(FaDeposit),
     Upgrade(KernelUpgrade),
-    NewSequencer(PublicKey),
+    UpgradeApproval {
+        approver: Approver,
+        proposal: UpgradeProposal,
+    },
+    UpgradeCancellation([u8; PREIMAGE_HASH_SIZE]),
+    UpgradeGovernance(UpgradeGovernance),
+    ForkSchedule(ForkSchedule),
+    NewSequencer(SequencerUpdate),
     NewChunkedTransaction {
         tx_hash: TransactionHash,
         num_chunks: u16,
@@ -113,6 +135,12 @@ pub type RollupType = MichelsonOr<
     MichelsonBytes,
 >;
 
+/// Converts a non-negative integer of at most 256 bits.
+fn u256_of_bigint(n: &BigInt) -> Option<U256> {
+    let (_sign, bytes) = n.to_bytes_le();
+    (!n.is_negative() && bytes.len() <= 32).then(|| U256::from_little_endian(&bytes))
+}
+
 impl InputResult {
     fn parse_simple_transaction(bytes: &[u8]) -> Self {
         // Next 32 bytes is the transaction hash.
@@ -183,20 +211,67 @@ impl InputResult {
         })
     }
 
-    fn parse_kernel_upgrade(bytes: &[u8]) -> Self {
-        let kernel_upgrade = parsable!(KernelUpgrade::from_rlp_bytes(bytes).ok());
-        Self::Input(Input::Upgrade(kernel_upgrade))
+    /// Parses a message of the administrator, either a kernel upgrade, the
+    /// cancellation of an upgrade, an update of the upgrade governance or a
+    /// new fork schedule. The first item of a kernel upgrade is a preimage
+    /// hash, the one of a cancellation or an update is a one byte tag and the
+    /// one of a fork schedule is a list, so the encodings can't overlap. An
+    /// invalid governance is ignored.
+    fn parse_admin_message(bytes: &[u8]) -> Self {
+        if let Ok(kernel_upgrade) = KernelUpgrade::from_rlp_bytes(bytes) {
+            return Self::Input(Input::Upgrade(kernel_upgrade));
+        }
+        match UpgradeDecision::from_rlp_bytes(bytes) {
+            Ok(UpgradeDecision::Cancel(preimage_hash)) => {
+                return Self::Input(Input::UpgradeCancellation(preimage_hash))
+            }
+            Ok(UpgradeDecision::UpdateGovernance(governance)) => {
+                let governance = parsable!(governance.is_valid().then_some(governance));
+                return Self::Input(Input::UpgradeGovernance(governance));
+            }
+            Ok(UpgradeDecision::Approve(_)) | Err(_) => (),
+        }
+        let fork_schedule = parsable!(ForkSchedule::from_rlp_bytes(bytes).ok());
+        Self::Input(Input::ForkSchedule(fork_schedule))
+    }
+
+    /// Parses the approval of an upgrade by a contract. Whether the contract
+    /// is part of the upgrade governance is checked when the approval is
+    /// applied.
+    fn parse_upgrade_approval(source: ContractKt1Hash, bytes: &[u8]) -> Self {
+        match parsable!(UpgradeDecision::from_rlp_bytes(bytes).ok()) {
+            UpgradeDecision::Approve(proposal) => Self::Input(Input::UpgradeApproval {
+                approver: Approver::Contract(source),
+                proposal,
+            }),
+            UpgradeDecision::Cancel(_) | UpgradeDecision::UpdateGovernance(_) => {
+                Self::Unparsable
+            }
+        }
+    }
+
+    fn parse_signed_upgrade_approval(bytes: &[u8], smart_rollup_address: &[u8]) -> Self {
+        let approval: SignedUpgradeApproval =
+            parsable!(FromRlpBytes::from_rlp_bytes(bytes).ok());
+        let approver = parsable!(approval.approver(smart_rollup_address));
+        Self::Input(Input::UpgradeApproval {
+            approver,
+            proposal: approval.proposal,
+        })
     }
 
     fn parse_sequencer_update(bytes: &[u8]) -> Self {
-        let pk_b58 = parsable!(String::from_utf8(bytes.to_vec()).ok());
-        let pk = parsable!(PublicKey::from_b58check(&pk_b58).ok());
-        Self::Input(Input::NewSequencer(pk))
+        let update = parsable!(SequencerUpdate::parse(bytes));
+        Self::Input(Input::NewSequencer(update))
     }
 
-    fn parse_sequencer_blueprint_input(sequencer: &PublicKey, bytes: &[u8]) -> Self {
+    fn parse_sequencer_blueprint_input(
+        sequencers: &Sequencers,
+        level: u32,
+        bytes: &[u8],
+    ) -> Self {
         // Parse the sequencer blueprint
-        let seq_blueprint: SequencerBlueprint =
+        let mut seq_blueprint: SequencerBlueprint =
             parsable!(FromRlpBytes::from_rlp_bytes(bytes).ok());
 
         // Creates and encodes the unsigned blueprint:
@@ -205,14 +280,12 @@ impl InputResult {
         // The sequencer signs the hash of the blueprint.
         let msg = tezos_crypto_rs::blake2b::digest_256(&bytes).unwrap();
 
-        let correctly_signed = sequencer
-            .verify_signature(&seq_blueprint.signature, &msg)
-            .unwrap_or(false);
-
-        if correctly_signed {
-            InputResult::Input(Input::SequencerBlueprint(seq_blueprint))
-        } else {
-            InputResult::Unparsable
+        match sequencers.signers(level, &seq_blueprint.signatures, &msg) {
+            Some(signers) => {
+                seq_blueprint.signers = signers;
+                InputResult::Input(Input::SequencerBlueprint(seq_blueprint))
+            }
+            None => InputResult::Unparsable,
         }
     }
 
@@ -244,7 +317,8 @@ impl InputResult {
     fn parse_external(
         input: &[u8],
         smart_rollup_address: &[u8],
-        sequencer: &Option<PublicKey>,
+        sequencers: &Option<Sequencers>,
+        level: u32,
     ) -> Self {
         // Compatibility with framing protocol for external messages
         let remaining = match ExternalMessageFrame::parse(input) {
@@ -259,21 +333,25 @@ impl InputResult {
         let (transaction_tag, remaining) = parsable!(remaining.split_first());
         // External transactions are only allowed in proxy mode
         match *transaction_tag {
-            SIMPLE_TRANSACTION_TAG if sequencer.is_none() => {
+            SIMPLE_TRANSACTION_TAG if sequencers.is_none() => {
                 Self::parse_simple_transaction(remaining)
             }
-            NEW_CHUNKED_TRANSACTION_TAG if sequencer.is_none() => {
+            NEW_CHUNKED_TRANSACTION_TAG if sequencers.is_none() => {
                 Self::parse_new_chunked_transaction(remaining)
             }
-            TRANSACTION_CHUNK_TAG if sequencer.is_none() => {
+            TRANSACTION_CHUNK_TAG if sequencers.is_none() => {
                 Self::parse_transaction_chunk(remaining)
             }
-            SEQUENCER_BLUEPRINT_TAG if sequencer.is_some() => {
+            SEQUENCER_BLUEPRINT_TAG if sequencers.is_some() => {
                 Self::parse_sequencer_blueprint_input(
-                    sequencer.as_ref().unwrap(),
+                    sequencers.as_ref().unwrap(),
+                    level,
                     remaining,
                 )
             }
+            UPGRADE_APPROVAL_TAG => {
+                Self::parse_signed_upgrade_approval(remaining, smart_rollup_address)
+            }
             _ => InputResult::Unparsable,
         }
     }
@@ -291,15 +369,66 @@ impl InputResult {
         ticket: FA2_1Ticket,
         receiver: MichelsonBytes,
         ticketer: &Option<ContractKt1Hash>,
+        depositor: PublicKeyHash,
     ) -> Self {
-        match &ticket.creator().0 {
-            Contract::Originated(kt1) if Some(kt1) == ticketer.as_ref() => (),
-            _ => {
-                log!(host, Info, "Deposit ignored because of different ticketer");
+        // EVM address
+        let receiver_bytes = receiver.0;
+        if receiver_bytes.len() != std::mem::size_of::<H160>() {
+            log!(
+                host,
+                Info,
+                "Deposit ignored because of invalid receiver address"
+            );
+            return InputResult::Unparsable;
+        }
+        let receiver = H160::from_slice(&receiver_bytes);
+
+        let ticket_creator = match &ticket.creator().0 {
+            Contract::Originated(kt1) if Some(kt1) == ticketer.as_ref() => {
+                return Self::parse_native_deposit(host, ticket, receiver)
+            }
+            Contract::Originated(kt1) => kt1.clone(),
+            Contract::Implicit(_) => {
+                log!(host, Info, "Deposit ignored because of implicit ticketer");
                 return InputResult::Unparsable;
             }
         };
 
+        let MichelsonPair(MichelsonInt(token_id), MichelsonOption(metadata)) =
+            ticket.contents();
+        let (Some(token_id), Some(amount)) =
+            (u256_of_bigint(&token_id.0), u256_of_bigint(ticket.amount()))
+        else {
+            log!(host, Info, "Deposit ignored because of invalid ticket");
+            return InputResult::Unparsable;
+        };
+        let ticket = FaTicket {
+            ticketer: ticket_creator,
+            token_id,
+            metadata: metadata.as_ref().map(|MichelsonBytes(bytes)| bytes.clone()),
+        };
+
+        log!(
+            host,
+            Info,
+            "Deposit of {} {:?} to {}.",
+            amount,
+            ticket,
+            receiver
+        );
+        Self::Input(Input::FaDeposit(FaDeposit {
+            ticket,
+            amount,
+            receiver,
+            depositor,
+        }))
+    }
+
+    fn parse_native_deposit<Host: Runtime>(
+        host: &mut Host,
+        ticket: FA2_1Ticket,
+        receiver: H160,
+    ) -> Self {
         // Amount
         let (_sign, amount_bytes) = ticket.amount().to_bytes_le();
         // We use the `U256::from_little_endian` as it takes arbitrary long
@@ -309,18 +438,6 @@ impl InputResult {
         let amount: u64 = U256::from_little_endian(&amount_bytes).as_u64();
         let amount: U256 = eth_from_mutez(amount);
 
-        // EVM address
-        let receiver_bytes = receiver.0;
-        if receiver_bytes.len() != std::mem::size_of::<H160>() {
-            log!(
-                host,
-                Info,
-                "Deposit ignored because of invalid receiver address"
-            );
-            return InputResult::Unparsable;
-        }
-        let receiver = H160::from_slice(&receiver_bytes);
-
         let content = Deposit { amount, receiver };
         log!(host, Info, "Deposit of {} to {}.", amount, receiver);
         Self::Input(Input::Deposit(content))
@@ -347,7 +464,13 @@ impl InputResult {
         match transfer.payload {
             MichelsonOr::Left(left) => match left {
                 MichelsonOr::Left(MichelsonPair(receiver, ticket)) => {
-                    Self::parse_deposit(host, ticket, receiver, &tezos_contracts.ticketer)
+                    Self::parse_deposit(
+                        host,
+                        ticket,
+                        receiver,
+                        &tezos_contracts.ticketer,
+                        transfer.source,
+                    )
                 }
                 MichelsonOr::Right(MichelsonBytes(bytes)) => {
                     Self::parse_transaction_from_delayed_inbox(
@@ -359,11 +482,11 @@ impl InputResult {
             },
             MichelsonOr::Right(MichelsonBytes(bytes)) => {
                 if tezos_contracts.is_admin(&source) {
-                    Self::parse_kernel_upgrade(&bytes)
+                    Self::parse_admin_message(&bytes)
                 } else if tezos_contracts.is_sequencer_admin(&source) {
                     Self::parse_sequencer_update(&bytes)
                 } else {
-                    Self::Unparsable
+                    Self::parse_upgrade_approval(source, &bytes)
                 }
             }
         }
@@ -397,7 +520,7 @@ impl InputResult {
         smart_rollup_address: [u8; 20],
         tezos_contracts: &TezosContracts,
         delayed_bridge: &Option<ContractKt1Hash>,
-        sequencer: &Option<PublicKey>,
+        sequencers: &Option<Sequencers>,
     ) -> Self {
         let bytes = Message::as_ref(&input);
         let (input_tag, remaining) = parsable!(bytes.split_first());
@@ -407,9 +530,12 @@ impl InputResult {
 
         match InboxMessage::<RollupType>::parse(bytes) {
             Ok((_remaing, message)) => match message {
-                InboxMessage::External(message) => {
-                    Self::parse_external(message, &smart_rollup_address, sequencer)
-                }
+                InboxMessage::External(message) => Self::parse_external(
+                    message,
+                    &smart_rollup_address,
+                    sequencers,
+                    input.level,
+                ),
                 InboxMessage::Internal(message) => Self::parse_internal(
                     host,
                     message,
@@ -426,6 +552,11 @@ impl InputResult {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sequencer_keys::{update_sequencers, KeySignature, SequencerKeys};
+    use crate::upgrade::approval_digest;
+    use tezos_crypto_rs::hash::SeedEd25519;
+    use tezos_smart_rollup_encoding::public_key::PublicKey;
+    use tezos_smart_rollup_encoding::timestamp::Timestamp;
     use tezos_smart_rollup_host::input::Message;
     use tezos_smart_rollup_mock::MockHost;
 
@@ -452,4 +583,100 @@ mod tests {
             InputResult::Unparsable
         )
     }
+
+    #[test]
+    fn parse_sequencer_blueprint_records_signers() {
+        let mut host = MockHost::default();
+        let (pk, sk) = SeedEd25519(vec![1; 32]).keypair().unwrap();
+        let key = PublicKey::Ed25519(pk);
+        let update = SequencerUpdate {
+            keys: SequencerKeys::single(key.clone()),
+            activation_level: 0,
+            overlap_levels: None,
+        };
+        update_sequencers(&mut host, update, 0).unwrap();
+        let sequencers = Sequencers::read(&host).unwrap();
+
+        let blueprint = UnsignedSequencerBlueprint {
+            chunk: vec![1, 2, 3],
+            number: U256::zero(),
+            nb_chunks: 1,
+            chunk_index: 0,
+        };
+        let msg = tezos_crypto_rs::blake2b::digest_256(&blueprint.rlp_bytes()).unwrap();
+        let seq_blueprint = SequencerBlueprint {
+            blueprint,
+            signatures: vec![KeySignature {
+                key_index: 0,
+                signature: sk.sign(msg).unwrap(),
+            }],
+            signers: vec![],
+        };
+        // External message targetting the rollup.
+        let mut bytes = vec![1, 0];
+        bytes.extend_from_slice(&ZERO_SMART_ROLLUP_ADDRESS);
+        bytes.push(SEQUENCER_BLUEPRINT_TAG);
+        bytes.extend_from_slice(&seq_blueprint.rlp_bytes());
+
+        let result = InputResult::parse(
+            &mut host,
+            Message::new(0, 0, bytes),
+            ZERO_SMART_ROLLUP_ADDRESS,
+            &TezosContracts {
+                ticketer: None,
+                admin: None,
+                sequencer_admin: None,
+            },
+            &None,
+            &sequencers,
+        );
+        assert_eq!(
+            result,
+            InputResult::Input(Input::SequencerBlueprint(SequencerBlueprint {
+                signers: vec![key],
+                ..seq_blueprint
+            }))
+        )
+    }
+
+    #[test]
+    fn parse_signed_upgrade_approval() {
+        let mut host = MockHost::default();
+        let (pk, sk) = SeedEd25519(vec![1; 32]).keypair().unwrap();
+        let proposal = UpgradeProposal {
+            id: 0,
+            upgrade: KernelUpgrade {
+                preimage_hash: [1; PREIMAGE_HASH_SIZE],
+                activation_timestamp: Timestamp::from(0),
+                activation_level: None,
+            },
+        };
+        let msg = approval_digest(&proposal, &ZERO_SMART_ROLLUP_ADDRESS);
+        let approval = SignedUpgradeApproval {
+            proposal: proposal.clone(),
+            public_key: PublicKey::Ed25519(pk.clone()),
+            signature: sk.sign(msg).unwrap(),
+        };
+        // External message targetting the rollup.
+        let mut bytes = vec![1, 0];
+        bytes.extend_from_slice(&ZERO_SMART_ROLLUP_ADDRESS);
+        bytes.push(UPGRADE_APPROVAL_TAG);
+        bytes.extend_from_slice(&approval.rlp_bytes());
+
+        let result = InputResult::parse(
+            &mut host,
+            Message::new(0, 0, bytes),
+            ZERO_SMART_ROLLUP_ADDRESS,
+            &TezosContracts::default(),
+            &None,
+            &None,
+        );
+        assert_eq!(
+            result,
+            InputResult::Input(Input::UpgradeApproval {
+                approver: Approver::Key(PublicKey::Ed25519(pk)),
+                proposal,
+            })
+        )
+    }
 }
@@ -10,7 +10,9 @@ use tezos_ethereum::rlp_helpers::{
     self, append_timestamp, append_u16_le, append_u256_le, decode_field_u16_le,
     decode_field_u256_le, decode_timestamp,
 };
+use tezos_smart_rollup_encoding::public_key::PublicKey;
 
+use crate::sequencer_keys::KeySignature;
 use crate::{blueprint::Blueprint, delayed_inbox::Hash};
 
 #[derive(Debug, Clone)]
@@ -70,10 +72,18 @@ pub struct UnsignedSequencerBlueprint {
     pub chunk_index: u16,
 }
 
+/// Chunk of a blueprint signed by the sequencer.
+///
+/// Encoding: `[chunk, number, nb_chunks, chunk_index, signatures]`, where
+/// a single signature of the first key is encoded as bytes, and otherwise
+/// the signatures are a list of `[key_index, signature]`.
 #[derive(PartialEq, Debug, Clone)]
 pub struct SequencerBlueprint {
     pub blueprint: UnsignedSequencerBlueprint,
-    pub signature: Signature,
+    pub signatures: Vec<KeySignature>,
+    /// Sequencer keys which signed the blueprint, set once the signatures
+    /// are checked. They are not part of the encoding.
+    pub signers: Vec<PublicKey>,
 }
 
 impl From<&SequencerBlueprint> for UnsignedSequencerBlueprint {
@@ -99,8 +109,45 @@ impl Encodable for SequencerBlueprint {
         append_u256_le(stream, &self.blueprint.number);
         append_u16_le(stream, &self.blueprint.nb_chunks);
         append_u16_le(stream, &self.blueprint.chunk_index);
-        stream.append(&self.signature.0);
+        match self.signatures.as_slice() {
+            [KeySignature {
+                key_index: 0,
+                signature,
+            }] => {
+                stream.append(&signature.0);
+            }
+            signatures => {
+                stream.begin_list(signatures.len());
+                for signature in signatures {
+                    stream.begin_list(2);
+                    stream.append(&signature.key_index);
+                    stream.append(&signature.signature.0);
+                }
+            }
+        }
+    }
+}
+
+fn decode_signature(decoder: &rlp::Rlp) -> Result<Signature, DecoderError> {
+    let bytes: Vec<u8> = rlp_helpers::decode_field(decoder, "signature")?;
+    Signature::try_from(bytes.as_slice())
+        .map_err(|_| DecoderError::Custom("Invalid signature encoding"))
+}
+
+fn decode_key_signature(decoder: &rlp::Rlp) -> Result<KeySignature, DecoderError> {
+    if !decoder.is_list() {
+        return Err(DecoderError::RlpExpectedToBeList);
     }
+    if decoder.item_count()? != 2 {
+        return Err(DecoderError::RlpIncorrectListLen);
+    }
+    let mut it = decoder.iter();
+    let key_index = rlp_helpers::decode_field(&rlp_helpers::next(&mut it)?, "key_index")?;
+    let signature = decode_signature(&rlp_helpers::next(&mut it)?)?;
+    Ok(KeySignature {
+        key_index,
+        signature,
+    })
 }
 
 impl Decodable for SequencerBlueprint {
@@ -117,10 +164,18 @@ impl Decodable for SequencerBlueprint {
         let nb_chunks = decode_field_u16_le(&rlp_helpers::next(&mut it)?, "nb_chunks")?;
         let chunk_index =
             decode_field_u16_le(&rlp_helpers::next(&mut it)?, "chunk_index")?;
-        let bytes: Vec<u8> =
-            rlp_helpers::decode_field(&rlp_helpers::next(&mut it)?, "signature")?;
-        let signature = Signature::try_from(bytes.as_slice())
-            .map_err(|_| DecoderError::Custom("Invalid signature encoding"))?;
+        let signatures = rlp_helpers::next(&mut it)?;
+        let signatures = if signatures.is_list() {
+            signatures
+                .iter()
+                .map(|signature| decode_key_signature(&signature))
+                .collect::<Result<_, _>>()?
+        } else {
+            vec![KeySignature {
+                key_index: 0,
+                signature: decode_signature(&signatures)?,
+            }]
+        };
         let blueprint = UnsignedSequencerBlueprint {
             chunk,
             number,
@@ -129,7 +184,8 @@ impl Decodable for SequencerBlueprint {
         };
         Ok(Self {
             blueprint,
-            signature,
+            signatures,
+            signers: vec![],
         })
     }
 }
@@ -140,6 +196,7 @@ mod tests {
     use crate::blueprint::Blueprint;
     use crate::inbox::Transaction;
     use crate::inbox::TransactionContent::Ethereum;
+    use crate::sequencer_keys::KeySignature;
     use primitive_types::{H160, U256};
     use rlp::Encodable;
     use tezos_crypto_rs::hash::Signature;
@@ -203,7 +260,11 @@ mod tests {
                 nb_chunks: 1u16,
                 chunk_index: 0u16,
             },
-            signature,
+            signatures: vec![KeySignature {
+                key_index: 0,
+                signature,
+            }],
+            signers: vec![],
         }
     }
 
@@ -212,4 +273,17 @@ mod tests {
         let v = dummy_blueprint();
         sequencer_blueprint_roundtrip(v);
     }
+
+    #[test]
+    fn roundtrip_rlp_several_signatures() {
+        let mut v = dummy_blueprint();
+        v.signatures.push(KeySignature {
+            key_index: 2,
+            ..v.signatures[0].clone()
+        });
+        sequencer_blueprint_roundtrip(v.clone());
+        // A single signature of another key than the first one.
+        v.signatures.remove(0);
+        sequencer_blueprint_roundtrip(v);
+    }
 }
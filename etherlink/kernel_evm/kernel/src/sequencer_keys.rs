@@ -0,0 +1,585 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Keys of the sequencer.
+//!
+//! Each chunk of a blueprint must be signed by a threshold of the sequencer
+//! keys. The sequencer administrator rotates the keys with an activation
+//! level: the new keys are pending until the L1 reaches this level, and the
+//! previous keys are still accepted during an overlap window after it, so
+//! that the blueprints in flight signed by the previous keys are not
+//! rejected.
+
+use crate::storage::{read_optional_rlp, read_u256_or_default, store_rlp};
+use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use tezos_crypto_rs::hash::Signature;
+use tezos_crypto_rs::PublicKeySignatureVerifier;
+use tezos_ethereum::rlp_helpers::{decode_field, next};
+use tezos_smart_rollup_encoding::public_key::PublicKey;
+use tezos_smart_rollup_host::path::{Path, RefPath};
+use tezos_smart_rollup_host::runtime::Runtime;
+
+const SEQUENCER: RefPath = RefPath::assert_from(b"/sequencer");
+const SEQUENCER_PENDING: RefPath = RefPath::assert_from(b"/sequencer_pending");
+const SEQUENCER_PREVIOUS: RefPath = RefPath::assert_from(b"/sequencer_previous");
+const SEQUENCER_OVERLAP_LEVELS: RefPath =
+    RefPath::assert_from(b"/sequencer_overlap_levels");
+
+/// Number of L1 levels during which the previous keys are accepted after a
+/// rotation, used if no window is set in the durable storage.
+pub const DEFAULT_SEQUENCER_OVERLAP_LEVELS: u32 = 20;
+
+fn parse_b58_key(bytes: &[u8]) -> Option<PublicKey> {
+    let key = std::str::from_utf8(bytes).ok()?;
+    PublicKey::from_b58check(key).ok()
+}
+
+/// Keys allowed to sign blueprints, at least `threshold` of them must sign
+/// each chunk.
+///
+/// Encoding: `[keys, threshold]`, where the keys are base58 encoded.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SequencerKeys {
+    pub keys: Vec<PublicKey>,
+    pub threshold: u8,
+}
+
+impl SequencerKeys {
+    pub fn single(key: PublicKey) -> Self {
+        Self {
+            keys: vec![key],
+            threshold: 1,
+        }
+    }
+
+    /// The threshold must be reachable, and each key counts once.
+    pub fn is_valid(&self) -> bool {
+        let distinct = self
+            .keys
+            .iter()
+            .enumerate()
+            .all(|(i, key)| !self.keys[..i].contains(key));
+        self.threshold >= 1 && usize::from(self.threshold) <= self.keys.len() && distinct
+    }
+
+    /// Returns the keys which signed `msg`, in the order of the keys, if at
+    /// least `threshold` of them did. Each signature is checked against the
+    /// key at its index only, the indices must be distinct and in range and
+    /// all the signatures must be valid.
+    pub fn signers(
+        &self,
+        signatures: &[KeySignature],
+        msg: &[u8],
+    ) -> Option<Vec<PublicKey>> {
+        let mut indices: Vec<usize> = Vec::with_capacity(signatures.len());
+        // The indices are checked first, it also bounds the number of
+        // signature checks by the number of keys.
+        for signature in signatures {
+            let index = usize::from(signature.key_index);
+            if index >= self.keys.len() || indices.contains(&index) {
+                return None;
+            }
+            indices.push(index);
+        }
+        if indices.len() < usize::from(self.threshold) {
+            return None;
+        }
+        for (index, signature) in indices.iter().zip(signatures) {
+            if !self.keys[*index]
+                .verify_signature(&signature.signature, msg)
+                .unwrap_or(false)
+            {
+                return None;
+            }
+        }
+        indices.sort_unstable();
+        Some(
+            indices
+                .iter()
+                .map(|index| self.keys[*index].clone())
+                .collect(),
+        )
+    }
+}
+
+/// Signature of a chunk by the key at `key_index` in the sequencer keys.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct KeySignature {
+    pub key_index: u8,
+    pub signature: Signature,
+}
+
+/// Appends a list of keys, encoded in base58.
+pub fn append_keys(stream: &mut RlpStream, keys: &[PublicKey]) {
+    stream.begin_list(keys.len());
+    for key in keys {
+        stream.append(&key.to_b58check().as_bytes());
+    }
+}
+
+/// Decodes a list of keys encoded by [append_keys].
+pub fn decode_keys(decoder: &Rlp) -> Result<Vec<PublicKey>, DecoderError> {
+    let keys: Vec<Vec<u8>> = decoder.as_list()?;
+    keys.into_iter()
+        .map(|key| {
+            let key = String::from_utf8(key)
+                .map_err(|_| DecoderError::Custom("Invalid public key"))?;
+            PublicKey::from_b58check(&key)
+                .map_err(|_| DecoderError::Custom("Invalid public key"))
+        })
+        .collect()
+}
+
+impl Encodable for SequencerKeys {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(2);
+        append_keys(stream, &self.keys);
+        stream.append(&self.threshold);
+    }
+}
+
+impl Decodable for SequencerKeys {
+    fn decode(decoder: &Rlp) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let keys = decode_keys(&next(&mut it)?)?;
+        let threshold = decode_field(&next(&mut it)?, "threshold")?;
+        Ok(Self { keys, threshold })
+    }
+}
+
+/// Keys replacing the current keys of the sequencer from the L1 level
+/// `activation_level`. The previous keys are accepted until the level
+/// `activation_level + overlap` excluded.
+///
+/// Encoding: `[keys, activation_level]`, or `[keys, activation_level,
+/// overlap_levels]` to choose the overlap.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SequencerUpdate {
+    pub keys: SequencerKeys,
+    pub activation_level: u32,
+    /// Overlap of the update, the one stored under
+    /// `/evm/sequencer_overlap_levels` if `None`. An overlap of 0 revokes
+    /// the previous keys at the activation, e.g. if they leaked.
+    pub overlap_levels: Option<u32>,
+}
+
+impl SequencerUpdate {
+    /// Parses a message of the sequencer administrator, either a base58
+    /// encoded key, activated immediately, or the encoding of an update.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let update = match parse_b58_key(bytes) {
+            Some(key) => Self {
+                keys: SequencerKeys::single(key),
+                activation_level: 0,
+                overlap_levels: None,
+            },
+            None => rlp::decode(bytes).ok()?,
+        };
+        update.keys.is_valid().then_some(update)
+    }
+}
+
+impl Encodable for SequencerUpdate {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(2 + self.overlap_levels.iter().len());
+        stream.append(&self.keys);
+        stream.append(&self.activation_level);
+        if let Some(overlap_levels) = &self.overlap_levels {
+            stream.append(overlap_levels);
+        }
+    }
+}
+
+impl Decodable for SequencerUpdate {
+    fn decode(decoder: &Rlp) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        let item_count = decoder.item_count()?;
+        if item_count != 2 && item_count != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let keys = decode_field(&next(&mut it)?, "keys")?;
+        let activation_level = decode_field(&next(&mut it)?, "activation_level")?;
+        let overlap_levels = match it.next() {
+            Some(item) => Some(decode_field(&item, "overlap_levels")?),
+            None => None,
+        };
+        Ok(Self {
+            keys,
+            activation_level,
+            overlap_levels,
+        })
+    }
+}
+
+/// Keys of the sequencer stored in the durable storage.
+#[derive(Debug, Clone)]
+pub struct Sequencers {
+    pub current: SequencerKeys,
+    pub pending: Option<SequencerUpdate>,
+    /// Keys replaced by the current ones, with the level from which they are
+    /// not accepted anymore.
+    pub previous: Option<SequencerUpdate>,
+    overlap_levels: u32,
+}
+
+impl std::fmt::Display for Sequencers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let keys: Vec<String> =
+            self.current.keys.iter().map(|k| k.to_b58check()).collect();
+        write!(f, "{:?} (threshold {})", keys, self.current.threshold)?;
+        if let Some(pending) = &self.pending {
+            write!(f, ", pending from level {}", pending.activation_level)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_current_keys<Host: Runtime>(
+    host: &Host,
+) -> anyhow::Result<Option<SequencerKeys>> {
+    if host.store_has(&SEQUENCER)?.is_none() {
+        return Ok(None);
+    }
+    let bytes = host.store_read_all(&SEQUENCER)?;
+    // A single key is stored in base58, as before the sets of keys.
+    let keys = match parse_b58_key(&bytes) {
+        Some(key) => Some(SequencerKeys::single(key)),
+        None => rlp::decode(&bytes).ok(),
+    };
+    Ok(keys)
+}
+
+fn store_current_keys<Host: Runtime>(
+    host: &mut Host,
+    keys: &SequencerKeys,
+) -> anyhow::Result<()> {
+    let bytes = match keys.keys.as_slice() {
+        [key] if keys.threshold == 1 => key.to_b58check().into_bytes(),
+        _ => keys.rlp_bytes().to_vec(),
+    };
+    host.store_write_all(&SEQUENCER, &bytes)?;
+    Ok(())
+}
+
+fn store_optional_update<Host: Runtime>(
+    host: &mut Host,
+    path: &impl Path,
+    update: &Option<SequencerUpdate>,
+) -> anyhow::Result<()> {
+    match update {
+        Some(update) => Ok(store_rlp(update, host, path)?),
+        None => remove_path(host, path),
+    }
+}
+
+fn remove_path<Host: Runtime>(host: &mut Host, path: &impl Path) -> anyhow::Result<()> {
+    if host.store_has(path)?.is_some() {
+        host.store_delete(path)?
+    }
+    Ok(())
+}
+
+impl Sequencers {
+    /// Reads the keys of the sequencer, `None` if there is no sequencer.
+    pub fn read<Host: Runtime>(host: &Host) -> anyhow::Result<Option<Self>> {
+        let Some(current) = read_current_keys(host)? else { return Ok(None) };
+        let pending = read_optional_rlp(host, &SEQUENCER_PENDING)?;
+        let previous = read_optional_rlp(host, &SEQUENCER_PREVIOUS)?;
+        let overlap_levels = read_u256_or_default(
+            host,
+            &SEQUENCER_OVERLAP_LEVELS,
+            DEFAULT_SEQUENCER_OVERLAP_LEVELS.into(),
+        )?;
+        Ok(Some(Self {
+            current,
+            pending,
+            previous,
+            overlap_levels: overlap_levels.try_into().unwrap_or(u32::MAX),
+        }))
+    }
+
+    fn store<Host: Runtime>(&self, host: &mut Host) -> anyhow::Result<()> {
+        store_current_keys(host, &self.current)?;
+        store_optional_update(host, &SEQUENCER_PENDING, &self.pending)?;
+        store_optional_update(host, &SEQUENCER_PREVIOUS, &self.previous)
+    }
+
+    /// Replaces the current keys by the pending ones if their activation
+    /// level is reached at the L1 `level`.
+    fn activate_pending(&mut self, level: u32) -> bool {
+        match self.pending.take() {
+            Some(pending) if pending.activation_level <= level => {
+                let previous = std::mem::replace(&mut self.current, pending.keys);
+                let overlap_levels =
+                    pending.overlap_levels.unwrap_or(self.overlap_levels);
+                self.previous = Some(SequencerUpdate {
+                    keys: previous,
+                    activation_level: pending
+                        .activation_level
+                        .saturating_add(overlap_levels),
+                    overlap_levels: None,
+                });
+                true
+            }
+            pending => {
+                self.pending = pending;
+                false
+            }
+        }
+    }
+
+    /// Activates the pending keys if their activation level is reached at
+    /// the L1 `level`.
+    pub fn activate<Host: Runtime>(
+        &mut self,
+        host: &mut Host,
+        level: u32,
+    ) -> anyhow::Result<()> {
+        if self.activate_pending(level) {
+            self.store(host)?
+        }
+        Ok(())
+    }
+
+    /// Returns the keys which signed `msg` at the L1 `level`, if enough keys
+    /// of a set accepted at this level did.
+    pub fn signers(
+        &self,
+        level: u32,
+        signatures: &[KeySignature],
+        msg: &[u8],
+    ) -> Option<Vec<PublicKey>> {
+        let mut sequencers = self.clone();
+        sequencers.activate_pending(level);
+        let previous = sequencers
+            .previous
+            .iter()
+            .filter(|previous| level < previous.activation_level)
+            .map(|previous| &previous.keys);
+        std::iter::once(&sequencers.current)
+            .chain(previous)
+            .find_map(|keys| keys.signers(signatures, msg))
+    }
+}
+
+/// Applies an update of the sequencer keys received at the L1 `level`. If
+/// there is no sequencer, the keys are activated immediately.
+pub fn update_sequencers<Host: Runtime>(
+    host: &mut Host,
+    update: SequencerUpdate,
+    level: u32,
+) -> anyhow::Result<()> {
+    match Sequencers::read(host)? {
+        None => store_current_keys(host, &update.keys),
+        Some(mut sequencers) => {
+            sequencers.activate_pending(level);
+            sequencers.pending = Some(SequencerUpdate {
+                activation_level: update.activation_level.max(level),
+                ..update
+            });
+            sequencers.activate_pending(level);
+            sequencers.store(host)
+        }
+    }
+}
+
+/// Removes the keys of the sequencer, the kernel runs in proxy mode until
+/// new keys are set.
+pub fn remove_sequencers<Host: Runtime>(host: &mut Host) -> anyhow::Result<()> {
+    remove_path(host, &SEQUENCER)?;
+    remove_path(host, &SEQUENCER_PENDING)?;
+    remove_path(host, &SEQUENCER_PREVIOUS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tezos_crypto_rs::hash::{SecretKeyEd25519, SeedEd25519};
+    use tezos_smart_rollup_mock::MockHost;
+
+    fn keypair(i: u8) -> (PublicKey, SecretKeyEd25519) {
+        let (pk, sk) = SeedEd25519(vec![i; 32]).keypair().unwrap();
+        (PublicKey::Ed25519(pk), sk)
+    }
+
+    fn key(i: u8) -> PublicKey {
+        keypair(i).0
+    }
+
+    // Signature of `msg` by the key `i`, at `key_index` in the keys.
+    fn sign(key_index: u8, i: u8, msg: &[u8]) -> KeySignature {
+        KeySignature {
+            key_index,
+            signature: keypair(i).1.sign(msg).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_threshold_of_signatures() {
+        let keys = SequencerKeys {
+            keys: vec![key(1), key(2), key(3)],
+            threshold: 2,
+        };
+        let msg = b"blueprint";
+        assert_eq!(keys.signers(&[sign(0, 1, msg)], msg), None);
+        assert_eq!(
+            keys.signers(&[sign(2, 3, msg), sign(0, 1, msg)], msg),
+            Some(vec![key(1), key(3)])
+        );
+        assert_eq!(
+            keys.signers(&[sign(0, 1, msg), sign(1, 2, msg), sign(2, 3, msg)], msg),
+            Some(vec![key(1), key(2), key(3)])
+        );
+        // Duplicated index.
+        assert_eq!(keys.signers(&[sign(0, 1, msg), sign(0, 1, msg)], msg), None);
+        // Index out of range.
+        assert_eq!(keys.signers(&[sign(0, 1, msg), sign(3, 4, msg)], msg), None);
+        // Signature of another key than the one at its index.
+        assert_eq!(keys.signers(&[sign(0, 1, msg), sign(1, 3, msg)], msg), None);
+        // Signature of another message.
+        assert_eq!(
+            keys.signers(&[sign(2, 3, b"other"), sign(0, 1, msg)], msg),
+            None
+        );
+
+        assert!(keys.is_valid());
+        assert!(!SequencerKeys {
+            threshold: 4,
+            ..keys.clone()
+        }
+        .is_valid());
+        assert!(!SequencerKeys {
+            keys: vec![key(1), key(1)],
+            threshold: 2,
+        }
+        .is_valid());
+    }
+
+    #[test]
+    fn test_parse_sequencer_update() {
+        let b58 = key(1).to_b58check();
+        assert_eq!(
+            SequencerUpdate::parse(b58.as_bytes()),
+            Some(SequencerUpdate {
+                keys: SequencerKeys::single(key(1)),
+                activation_level: 0,
+                overlap_levels: None,
+            })
+        );
+        let update = SequencerUpdate {
+            keys: SequencerKeys {
+                keys: vec![key(1), key(2)],
+                threshold: 2,
+            },
+            activation_level: 42,
+            overlap_levels: None,
+        };
+        assert_eq!(
+            SequencerUpdate::parse(&update.rlp_bytes()),
+            Some(update.clone())
+        );
+        let immediate_revocation = SequencerUpdate {
+            overlap_levels: Some(0),
+            ..update
+        };
+        assert_eq!(
+            SequencerUpdate::parse(&immediate_revocation.rlp_bytes()),
+            Some(immediate_revocation)
+        );
+        let invalid = SequencerUpdate {
+            keys: SequencerKeys {
+                keys: vec![],
+                threshold: 0,
+            },
+            activation_level: 42,
+            overlap_levels: None,
+        };
+        assert_eq!(SequencerUpdate::parse(&invalid.rlp_bytes()), None);
+    }
+
+    #[test]
+    fn test_rotation_with_overlap() {
+        let mut host = MockHost::default();
+        update_sequencers(
+            &mut host,
+            SequencerUpdate::parse(key(1).to_b58check().as_bytes()).unwrap(),
+            5,
+        )
+        .unwrap();
+        // A single key is stored in base58.
+        assert_eq!(
+            host.store_read_all(&SEQUENCER).unwrap(),
+            key(1).to_b58check().into_bytes()
+        );
+
+        let new_keys = SequencerKeys {
+            keys: vec![key(2), key(3)],
+            threshold: 2,
+        };
+        let update = SequencerUpdate {
+            keys: new_keys.clone(),
+            activation_level: 100,
+            overlap_levels: None,
+        };
+        update_sequencers(&mut host, update, 10).unwrap();
+
+        let msg = b"blueprint";
+        let old = [sign(0, 1, msg)];
+        let new = [sign(0, 2, msg), sign(1, 3, msg)];
+        let sequencers = Sequencers::read(&host).unwrap().unwrap();
+        let accepts = |level, signatures: &[KeySignature]| {
+            sequencers.signers(level, signatures, msg).is_some()
+        };
+        assert!(accepts(99, &old));
+        assert!(!accepts(99, &new));
+        assert!(accepts(100, &old));
+        assert!(accepts(100, &new));
+        assert!(accepts(119, &old));
+        assert!(!accepts(120, &old));
+        assert!(accepts(120, &new));
+
+        let mut sequencers = Sequencers::read(&host).unwrap().unwrap();
+        sequencers.activate(&mut host, 110).unwrap();
+        let sequencers = Sequencers::read(&host).unwrap().unwrap();
+        assert_eq!(sequencers.current, new_keys);
+        assert!(sequencers.pending.is_none());
+        assert_eq!(
+            sequencers.signers(119, &old, msg),
+            Some(vec![key(1)]),
+            "The previous key is accepted during the overlap window"
+        );
+        assert!(sequencers.signers(120, &old, msg).is_none());
+
+        remove_sequencers(&mut host).unwrap();
+        assert!(Sequencers::read(&host).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_rotation_without_overlap() {
+        let mut host = MockHost::default();
+        let update = SequencerUpdate::parse(key(1).to_b58check().as_bytes()).unwrap();
+        update_sequencers(&mut host, update, 5).unwrap();
+        let update = SequencerUpdate {
+            keys: SequencerKeys::single(key(2)),
+            activation_level: 100,
+            overlap_levels: Some(0),
+        };
+        update_sequencers(&mut host, update, 10).unwrap();
+
+        let msg = b"blueprint";
+        let sequencers = Sequencers::read(&host).unwrap().unwrap();
+        assert!(sequencers.signers(99, &[sign(0, 1, msg)], msg).is_some());
+        assert!(sequencers.signers(100, &[sign(0, 1, msg)], msg).is_none());
+        assert!(sequencers.signers(100, &[sign(0, 2, msg)], msg).is_some());
+    }
+}
@@ -9,19 +9,32 @@
 
 use crate::{error::Error, error::StorageError, storage};
 
+use crate::apply::{apply_transaction, trace_transaction as trace_block_transaction};
+use crate::apply::{ExecutionInfo, ExecutionResult};
+use crate::inbox::Transaction;
+use crate::logs_index::{self, LogsFilter};
 use crate::{
-    current_timestamp, parsable, parsing, retrieve_block_fees, retrieve_chain_id,
-    tick_model, CONFIG,
+    current_timestamp, fork_schedule, parsable, parsing, retrieve_block_fees,
+    retrieve_chain_id, tick_model, world_state,
 };
 
-use evm_execution::run_transaction;
-use evm_execution::{account_storage, handler::ExecutionOutcome, precompiles};
-use primitive_types::{H160, U256};
-use rlp::{Decodable, DecoderError, Rlp};
-use tezos_ethereum::block::BlockConstants;
-use tezos_ethereum::rlp_helpers::{decode_field, decode_option, next};
+use evm_execution::trace::{Trace, TracerConfig};
+use evm_execution::{
+    account_storage, account_storage::EthereumAccountStorage, handler::ExecutionOutcome,
+    precompiles, EthereumError,
+};
+use evm_execution::{run_transaction, trace_transaction};
+use primitive_types::{H160, H256, U256};
+use rlp::{Decodable, DecoderError, Encodable, Rlp};
+use tezos_ethereum::block::{BlockConstants, BlockFees, L2Block};
+use tezos_ethereum::proof::AccountProof;
+use tezos_ethereum::rlp_helpers::{
+    decode_field, decode_field_u256_le, decode_field_u64_le, decode_list, decode_option,
+    decode_option_explicit, next,
+};
 use tezos_ethereum::tx_common::EthereumTransactionCommon;
 use tezos_evm_logging::{log, Level::*};
+use tezos_smart_rollup_host::path::RefPath;
 use tezos_smart_rollup_host::runtime::Runtime;
 
 // SIMULATION/SIMPLE/RLP_ENCODED_SIMULATION
@@ -34,6 +47,31 @@ pub const SIMULATION_CHUNK_TAG: u8 = 3;
 pub const EVALUATION_TAG: u8 = 0x00;
 /// Tag indicating simulation is a validation.
 pub const VALIDATION_TAG: u8 = 0x01;
+/// Tag indicating simulation is a traced evaluation.
+pub const TRACE_TAG: u8 = 0x02;
+/// Tag indicating simulation is a gas estimation.
+pub const ESTIMATION_TAG: u8 = 0x03;
+/// Tag indicating simulation is a proof of an account and its storage.
+pub const PROOF_TAG: u8 = 0x04;
+/// Tag indicating simulation is a query of the logs index.
+pub const LOGS_TAG: u8 = 0x05;
+/// Tag indicating simulation is the trace of a transaction of a block.
+pub const TRACE_TRANSACTION_TAG: u8 = 0x06;
+
+/// Gas given to the callee of a call with a transfer, on top of the gas limit
+/// of the call.
+const CALL_STIPEND: u64 = 2300;
+
+/// Copy of the accounts, saved before a simulation.
+const ACCOUNTS_BACKUP: RefPath = RefPath::assert_from(b"/estimation_accounts_backup");
+
+const OUT_OF_TICKS_MSG: &[u8] =
+    b"The transaction would exhaust all the ticks it is allocated. \
+      Try reducing its gas consumption or splitting the call in \
+      multiple steps, if possible.";
+
+const INVALID_TRANSACTION_MSG: &[u8] =
+    b"The transaction is invalid at the state of its block.";
 
 /// Container for eth_call data, used in messages sent by the rollup node
 /// simulation.
@@ -72,6 +110,161 @@ pub struct Evaluation {
     pub value: Option<U256>,
     /// (optional) Hash of the method signature and encoded parameters.
     pub data: Vec<u8>,
+    /// (optional) Accounts modified for the duration of the simulation.\
+    /// Encoding: list of [AccountOverride]
+    pub state_override: Vec<AccountOverride>,
+    /// (optional) Constants of the block the simulation is executed in.\
+    /// Encoding: see [BlockOverride]
+    pub block_override: BlockOverride,
+}
+
+/// Modification of an account, applied before a simulation (`stateOverride`
+/// in geth). Storage values in `state` replace the whole storage of the
+/// account, the ones in `state_diff` only replace the given slots.
+///
+/// Encoding: `[address, balance, nonce, code, state, state_diff]`, where all
+/// fields but the address are optional (empty, 0x80), balance and nonce are in
+/// little endian, and storage values are lists of `[index, value]`. An empty
+/// `state` cannot be distinguished from a missing one.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AccountOverride {
+    pub address: H160,
+    pub balance: Option<U256>,
+    pub nonce: Option<u64>,
+    pub code: Option<Vec<u8>>,
+    pub state: Option<Vec<(H256, H256)>>,
+    pub state_diff: Option<Vec<(H256, H256)>>,
+}
+
+impl AccountOverride {
+    fn apply<Host: Runtime>(
+        &self,
+        host: &mut Host,
+        evm_account_storage: &mut EthereumAccountStorage,
+    ) -> Result<(), Error> {
+        let mut account = evm_account_storage
+            .get_or_create(host, &account_storage::account_path(&self.address)?)?;
+        if let Some(balance) = self.balance {
+            account.set_balance(host, balance)?
+        }
+        if let Some(nonce) = self.nonce {
+            account.set_nonce(host, U256::from(nonce))?
+        }
+        if let Some(code) = &self.code {
+            account.set_code(host, code)?
+        }
+        if let Some(state) = &self.state {
+            account.clear_storage(host)?;
+            for (index, value) in state {
+                account.set_storage(host, index, value)?
+            }
+        }
+        if let Some(state_diff) = &self.state_diff {
+            for (index, value) in state_diff {
+                account.set_storage_checked(host, index, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn decode_storage(
+    decoder: &Rlp<'_>,
+    field_name: &'static str,
+) -> Result<Vec<(H256, H256)>, DecoderError> {
+    decoder
+        .iter()
+        .map(|slot| {
+            if slot.item_count()? != 2 {
+                return Err(DecoderError::RlpIncorrectListLen);
+            }
+            let index = decode_field(&slot.at(0)?, field_name)?;
+            let value = decode_field(&slot.at(1)?, field_name)?;
+            Ok((index, value))
+        })
+        .collect()
+}
+
+impl Decodable for AccountOverride {
+    fn decode(decoder: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 6 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let address = decode_field(&next(&mut it)?, "address")?;
+        let balance =
+            decode_option_explicit(&next(&mut it)?, "balance", decode_field_u256_le)?;
+        let nonce =
+            decode_option_explicit(&next(&mut it)?, "nonce", decode_field_u64_le)?;
+        let code = decode_option(&next(&mut it)?, "code")?;
+        let state = decode_option_explicit(&next(&mut it)?, "state", decode_storage)?;
+        let state_diff =
+            decode_option_explicit(&next(&mut it)?, "state_diff", decode_storage)?;
+        Ok(Self {
+            address,
+            balance,
+            nonce,
+            code,
+            state,
+            state_diff,
+        })
+    }
+}
+
+/// Constants of the block a simulation is executed in, replacing the ones of
+/// the current block (`blockOverrides` in geth).
+///
+/// Encoding: `[number, timestamp, base_fee_per_gas]`, all optional and in
+/// little endian.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct BlockOverride {
+    pub number: Option<U256>,
+    pub timestamp: Option<U256>,
+    pub base_fee_per_gas: Option<U256>,
+}
+
+impl BlockOverride {
+    fn apply(&self, constants: &mut BlockConstants) {
+        if let Some(number) = self.number {
+            constants.number = number
+        }
+        if let Some(timestamp) = self.timestamp {
+            constants.timestamp = timestamp
+        }
+        if let Some(base_fee_per_gas) = self.base_fee_per_gas {
+            constants.block_fees =
+                BlockFees::new(base_fee_per_gas, constants.block_fees.da_fee_per_byte())
+        }
+    }
+}
+
+impl Decodable for BlockOverride {
+    fn decode(decoder: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let number =
+            decode_option_explicit(&next(&mut it)?, "number", decode_field_u256_le)?;
+        let timestamp =
+            decode_option_explicit(&next(&mut it)?, "timestamp", decode_field_u256_le)?;
+        let base_fee_per_gas = decode_option_explicit(
+            &next(&mut it)?,
+            "base_fee_per_gas",
+            decode_field_u256_le,
+        )?;
+        Ok(Self {
+            number,
+            timestamp,
+            base_fee_per_gas,
+        })
+    }
 }
 
 impl Evaluation {
@@ -86,10 +279,141 @@ impl Evaluation {
         &self,
         host: &mut Host,
     ) -> Result<Option<ExecutionOutcome>, Error> {
+        self.execute(host, None).map(|(outcome, _)| outcome)
+    }
+
+    /// Execute the simulation, and record its trace.
+    pub fn trace<Host: Runtime>(
+        &self,
+        host: &mut Host,
+        tracer: TracerConfig,
+    ) -> Result<(Option<ExecutionOutcome>, Option<Trace>), Error> {
+        self.execute(host, Some(tracer))
+    }
+
+    /// Estimate the minimal gas limit for which the call succeeds, using the
+    /// evaluation gas, capped by the maximum gas of a transaction, as the
+    /// highest limit. The state is left unchanged.
+    pub fn estimate_gas<Host: Runtime>(
+        &self,
+        host: &mut Host,
+    ) -> Result<GasEstimation, Error> {
+        let maximum_gas = tick_model::maximum_gas_per_transaction(self.data.len() as u64);
+        let cap = self.gas.map_or(maximum_gas, |gas| gas.min(maximum_gas));
+        let gas_used = match self.run_with_gas_limit(host, cap)? {
+            GasEstimation::Gas(gas_used) => gas_used,
+            failure => return Ok(failure),
+        };
+
+        // The call cannot succeed with less gas than it used. The limit is
+        // the lowest failing one, `hi` the lowest succeeding one.
+        let mut lo = gas_used.saturating_sub(1);
+        let mut hi = cap;
+
+        // Most calls succeed with the gas they used, plus the part of the
+        // gas kept by the caller of inner calls (EIP-150), try it first.
+        let optimistic = gas_used.saturating_add(CALL_STIPEND).saturating_mul(64) / 63;
+        if lo < optimistic && optimistic < hi {
+            if self.succeeds_with_gas_limit(host, optimistic)? {
+                hi = optimistic
+            } else {
+                lo = optimistic
+            }
+        }
+
+        while lo + 1 < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.succeeds_with_gas_limit(host, mid)? {
+                hi = mid
+            } else {
+                lo = mid
+            }
+        }
+        Ok(GasEstimation::Gas(hi))
+    }
+
+    fn succeeds_with_gas_limit<Host: Runtime>(
+        &self,
+        host: &mut Host,
+        gas: u64,
+    ) -> Result<bool, Error> {
+        Ok(matches!(
+            self.run_with_gas_limit(host, gas)?,
+            GasEstimation::Gas(_)
+        ))
+    }
+
+    /// Execute the call with the given gas limit. On success, the gas used is
+    /// returned.
+    fn run_with_gas_limit<Host: Runtime>(
+        &self,
+        host: &mut Host,
+        gas: u64,
+    ) -> Result<GasEstimation, Error> {
+        let evaluation = Evaluation {
+            gas: Some(gas),
+            ..self.clone()
+        };
+        let result = evaluation.run(host);
+        match result {
+            Ok(Some(outcome)) if outcome.is_success => {
+                Ok(GasEstimation::Gas(outcome.gas_used))
+            }
+            Ok(outcome) => Ok(GasEstimation::Failure(outcome)),
+            Err(Error::Simulation(EthereumError::OutOfTicks)) => {
+                Ok(GasEstimation::OutOfTicks)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Execute the simulation on a throwaway copy of the accounts, where the
+    /// state overrides are applied.
+    fn execute<Host: Runtime>(
+        &self,
+        host: &mut Host,
+        tracer: Option<TracerConfig>,
+    ) -> Result<(Option<ExecutionOutcome>, Option<Trace>), Error> {
+        let has_accounts = host
+            .store_has(&account_storage::EVM_ACCOUNTS_PATH)?
+            .is_some();
+        if has_accounts {
+            host.store_copy(&account_storage::EVM_ACCOUNTS_PATH, &ACCOUNTS_BACKUP)?;
+        }
+
+        let result = self
+            .apply_state_override(host)
+            .and_then(|()| self.execute_transaction(host, tracer));
+
+        if has_accounts {
+            host.store_move(&ACCOUNTS_BACKUP, &account_storage::EVM_ACCOUNTS_PATH)?;
+        } else if host
+            .store_has(&account_storage::EVM_ACCOUNTS_PATH)?
+            .is_some()
+        {
+            host.store_delete(&account_storage::EVM_ACCOUNTS_PATH)?;
+        }
+        result
+    }
+
+    fn apply_state_override<Host: Runtime>(&self, host: &mut Host) -> Result<(), Error> {
+        let mut evm_account_storage = account_storage::init_account_storage()
+            .map_err(|_| Error::Storage(StorageError::AccountInitialisation))?;
+        for account_override in &self.state_override {
+            account_override.apply(host, &mut evm_account_storage)?
+        }
+        Ok(())
+    }
+
+    fn execute_transaction<Host: Runtime>(
+        &self,
+        host: &mut Host,
+        tracer: Option<TracerConfig>,
+    ) -> Result<(Option<ExecutionOutcome>, Option<Trace>), Error> {
         let chain_id = retrieve_chain_id(host)?;
         let block_fees = retrieve_block_fees(host)?;
 
-        let current_constants = match storage::read_current_block(host) {
+        let mut current_constants = match storage::read_current_block(host) {
             Ok(block) => block.constants(chain_id, block_fees),
             Err(_) => {
                 let timestamp = current_timestamp(host);
@@ -97,10 +421,13 @@ impl Evaluation {
                 BlockConstants::first_block(timestamp, chain_id, block_fees)
             }
         };
+        self.block_override.apply(&mut current_constants);
+        let block_fees = current_constants.block_fees;
 
         let mut evm_account_storage = account_storage::init_account_storage()
             .map_err(|_| Error::Storage(StorageError::AccountInitialisation))?;
         let precompiles = precompiles::precompile_set::<Host>();
+        let fork = fork_schedule::read_fork_at(host, current_constants.number)?;
         let default_caller = H160::zero();
         let tx_data_size = self.data.len() as u64;
         let allocated_ticks =
@@ -114,24 +441,63 @@ impl Evaluation {
         } else {
             block_fees.base_fee_per_gas()
         };
+        // The gas limit includes the gas paying for the data availability
+        // fee, which is reported as used.
+        let gas_for_da_fee = block_fees.gas_for_da_fee(tx_data_size, gas_price);
+        let gas_limit = match self.gas {
+            Some(gas) => gas.saturating_sub(gas_for_da_fee),
+            None => u64::MAX,
+        };
+        let with_da_fee = |outcome: Option<ExecutionOutcome>| {
+            outcome.map(|outcome| ExecutionOutcome {
+                gas_used: outcome.gas_used.saturating_add(gas_for_da_fee),
+                ..outcome
+            })
+        };
 
-        let outcome = run_transaction(
-            host,
-            &current_constants,
-            &mut evm_account_storage,
-            &precompiles,
-            CONFIG,
-            self.to,
-            self.from.unwrap_or(default_caller),
-            self.data.clone(),
-            self.gas.or(Some(u64::MAX)),
-            gas_price,
-            self.value,
-            false,
-            allocated_ticks,
-        )
-        .map_err(Error::Simulation)?;
-        Ok(outcome)
+        match tracer {
+            None => {
+                let outcome = run_transaction(
+                    host,
+                    &current_constants,
+                    &mut evm_account_storage,
+                    &precompiles,
+                    fork,
+                    self.to,
+                    self.from.unwrap_or(default_caller),
+                    self.data.clone(),
+                    Some(gas_limit),
+                    gas_price,
+                    U256::zero(),
+                    self.value,
+                    false,
+                    allocated_ticks,
+                )
+                .map_err(Error::Simulation)?;
+                Ok((with_da_fee(outcome), None))
+            }
+            Some(tracer) => {
+                let (outcome, trace) = trace_transaction(
+                    host,
+                    &current_constants,
+                    &mut evm_account_storage,
+                    &precompiles,
+                    fork,
+                    self.to,
+                    self.from.unwrap_or(default_caller),
+                    self.data.clone(),
+                    Some(gas_limit),
+                    gas_price,
+                    U256::zero(),
+                    self.value,
+                    false,
+                    allocated_ticks,
+                    tracer,
+                )
+                .map_err(Error::Simulation)?;
+                Ok((with_da_fee(outcome), Some(trace)))
+            }
+        }
     }
 }
 
@@ -141,7 +507,10 @@ impl Decodable for Evaluation {
         let u64_from_le = |v: Vec<u8>| u64::from_le_bytes(parsable!(v.try_into().ok()));
         let u256_from_le = |v: Vec<u8>| U256::from_little_endian(&v);
         if decoder.is_list() {
-            if Ok(6) == decoder.item_count() {
+            let item_count = decoder.item_count()?;
+            // The overrides are optional, for compatibility with the messages
+            // without them
+            if item_count == 6 || item_count == 8 {
                 let mut it = decoder.iter();
                 let from: Option<H160> = decode_option(&next(&mut it)?, "from")?;
                 let to: Option<H160> = decode_option(&next(&mut it)?, "to")?;
@@ -152,6 +521,14 @@ impl Decodable for Evaluation {
                 let value: Option<U256> =
                     decode_option(&next(&mut it)?, "value")?.map(u256_from_le);
                 let data: Vec<u8> = decode_field(&next(&mut it)?, "data")?;
+                let (state_override, block_override) = if item_count == 8 {
+                    (
+                        decode_list(&next(&mut it)?, "state_override")?,
+                        decode_field(&next(&mut it)?, "block_override")?,
+                    )
+                } else {
+                    (vec![], BlockOverride::default())
+                };
                 Ok(Self {
                     from,
                     to,
@@ -159,6 +536,8 @@ impl Decodable for Evaluation {
                     gas_price,
                     value,
                     data,
+                    state_override,
+                    block_override,
                 })
             } else {
                 Err(DecoderError::RlpIncorrectListLen)
@@ -177,6 +556,233 @@ impl TryFrom<&[u8]> for Evaluation {
     }
 }
 
+/// Result of a gas estimation.
+#[derive(Debug, PartialEq, Eq)]
+pub enum GasEstimation {
+    /// The minimal gas limit for which the call succeeds
+    Gas(u64),
+    /// The call fails even with the highest gas limit, with this outcome
+    Failure(Option<ExecutionOutcome>),
+    /// The call exhausts the ticks allocated to a transaction
+    OutOfTicks,
+}
+
+/// Container for debug_traceCall and debug_traceTransaction data, used in
+/// messages sent by the rollup node simulation. It is an evaluation, along
+/// with the tracer used to record its execution.
+///
+/// Encoding: `[tracer, evaluation]`, see [TracerConfig] and [Evaluation].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TraceEvaluation {
+    pub tracer: TracerConfig,
+    pub evaluation: Evaluation,
+}
+
+impl Decodable for TraceEvaluation {
+    fn decode(decoder: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let tracer = decode_field(&next(&mut it)?, "tracer")?;
+        let evaluation = decode_field(&next(&mut it)?, "evaluation")?;
+        Ok(Self { tracer, evaluation })
+    }
+}
+
+impl TryFrom<&[u8]> for TraceEvaluation {
+    type Error = DecoderError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Self::decode(&Rlp::new(bytes))
+    }
+}
+
+/// Container for debug_traceTransaction data, used in messages sent by the
+/// rollup node simulation. The transactions are the ones of a block, up to
+/// the traced one which is the last of the list. They are applied as in the
+/// block production, so the simulation must be run on the durable storage of
+/// the parent of the block, and only the last one is traced.
+///
+/// Encoding: `[tracer, [transactions]]`, see [TracerConfig] and [Transaction].
+#[derive(Debug, PartialEq, Clone)]
+pub struct TransactionTrace {
+    pub tracer: TracerConfig,
+    pub transactions: Vec<Transaction>,
+}
+
+impl TransactionTrace {
+    /// Replays the transactions of the block and traces the last one. The
+    /// changes made to the durable storage are not reverted, as the state of
+    /// a simulation is discarded.
+    pub fn run<Host: Runtime>(
+        &self,
+        host: &mut Host,
+    ) -> Result<ExecutionResult<ExecutionInfo>, anyhow::Error> {
+        let Some((traced, transactions)) = self.transactions.split_last() else {
+            return Ok(ExecutionResult::Invalid)
+        };
+        let chain_id = retrieve_chain_id(host)?;
+        let block_fees = retrieve_block_fees(host)?;
+
+        // Same constants as the block production, see `block::produce`.
+        let (mut constants, number) = match storage::read_current_block(host) {
+            Ok(block) => (block.constants(chain_id, block_fees), block.number + 1),
+            Err(_) => {
+                let timestamp = current_timestamp(host);
+                let timestamp = U256::from(timestamp.as_u64());
+                (
+                    BlockConstants::first_block(timestamp, chain_id, block_fees),
+                    U256::zero(),
+                )
+            }
+        };
+        if let Some(coinbase) = storage::read_sequencer_coinbase(host) {
+            constants.coinbase = coinbase
+        }
+        let fork = fork_schedule::read_fork_at(host, number)?;
+
+        let mut evm_account_storage = account_storage::init_account_storage()
+            .map_err(|_| Error::Storage(StorageError::AccountInitialisation))?;
+        let mut accounts_index = storage::init_account_index()?;
+        let precompiles = precompiles::precompile_set::<Host>();
+
+        let mut index = 0;
+        for transaction in transactions {
+            let allocated_ticks =
+                tick_model::estimate_remaining_ticks_for_transaction_execution(
+                    0,
+                    transaction.data_size(),
+                );
+            if let ExecutionResult::Valid(_) = apply_transaction(
+                host,
+                &constants,
+                &precompiles,
+                transaction,
+                index,
+                &mut evm_account_storage,
+                &mut accounts_index,
+                allocated_ticks,
+                fork,
+            )? {
+                index += 1
+            }
+        }
+
+        let allocated_ticks =
+            tick_model::estimate_remaining_ticks_for_transaction_execution(
+                0,
+                traced.data_size(),
+            );
+        trace_block_transaction(
+            host,
+            &constants,
+            &precompiles,
+            traced,
+            index,
+            &mut evm_account_storage,
+            &mut accounts_index,
+            allocated_ticks,
+            fork,
+            self.tracer,
+        )
+    }
+}
+
+impl Decodable for TransactionTrace {
+    fn decode(decoder: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let tracer = decode_field(&next(&mut it)?, "tracer")?;
+        let transactions = decode_list(&next(&mut it)?, "transactions")?;
+        Ok(Self {
+            tracer,
+            transactions,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for TransactionTrace {
+    type Error = DecoderError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Self::decode(&Rlp::new(bytes))
+    }
+}
+
+/// Container for eth_getProof data, used in messages sent by the rollup node
+/// simulation. The proof is stored RLP encoded as the simulation result, see
+/// [AccountProof].
+///
+/// Encoding: `[address, [storage_keys], block_hash]`, where the block hash is
+/// empty for the latest state.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ProofRequest {
+    pub address: H160,
+    pub storage_keys: Vec<H256>,
+    /// Hash of the block whose state is proven, the latest state if `None`.
+    /// The durable storage only keeps the trie nodes of the latest state, the
+    /// simulation must run on the storage of that block.
+    pub block: Option<H256>,
+}
+
+impl ProofRequest {
+    fn state_root<Host: Runtime>(&self, host: &Host) -> Result<H256, Error> {
+        match self.block {
+            None => world_state::read_state_root(host),
+            Some(hash) => {
+                let block: L2Block =
+                    storage::read_rlp(host, &storage::block_path(hash)?)?;
+                if block.state_root.len() != 32 {
+                    return Err(Error::InvalidConversion);
+                }
+                Ok(H256::from_slice(&block.state_root))
+            }
+        }
+    }
+
+    pub fn run<Host: Runtime>(&self, host: &Host) -> Result<AccountProof, Error> {
+        let state_root = self.state_root(host)?;
+        world_state::account_proof(host, &state_root, &self.address, &self.storage_keys)
+    }
+}
+
+impl Decodable for ProofRequest {
+    fn decode(decoder: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let address = decode_field(&next(&mut it)?, "address")?;
+        let storage_keys = decode_list(&next(&mut it)?, "storage_keys")?;
+        let block = decode_option(&next(&mut it)?, "block")?;
+        Ok(Self {
+            address,
+            storage_keys,
+            block,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for ProofRequest {
+    type Error = DecoderError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Self::decode(&Rlp::new(bytes))
+    }
+}
+
 #[derive(Debug, PartialEq)]
 struct TxValidation {
     transaction: EthereumTransactionCommon,
@@ -189,6 +795,7 @@ enum TxValidationOutcome {
     NotCorrectSignature,
     InvalidChainId,
     MaxGasFeeTooLow,
+    GasLimitTooLowForDaFee,
     OutOfTicks,
 }
 
@@ -215,6 +822,7 @@ impl TxValidation {
         let mut evm_account_storage = account_storage::init_account_storage()
             .map_err(|_| Error::Storage(StorageError::AccountInitialisation))?;
         let precompiles = precompiles::precompile_set::<Host>();
+        let fork = fork_schedule::read_fork_at(host, current_constants.number)?;
         let tx_data_size = transaction.data.len() as u64;
         let allocated_ticks =
             tick_model::estimate_remaining_ticks_for_transaction_execution(
@@ -222,11 +830,12 @@ impl TxValidation {
                 tx_data_size,
             );
 
-        let gas_price = if let Ok(gas_price) = transaction.overall_gas_price(&block_fees)
-        {
-            gas_price
-        } else {
-            block_fees.base_fee_per_gas()
+        let gas_price = Self::gas_price(transaction, &block_fees);
+        let gas_for_da_fee = block_fees.gas_for_da_fee(tx_data_size, gas_price);
+        // The transaction is invalid and not run if its gas limit doesn't
+        // cover the data availability fee.
+        let Some(gas_limit) = transaction.execution_gas_limit(gas_for_da_fee) else {
+            return Ok(false)
         };
 
         match run_transaction(
@@ -234,12 +843,13 @@ impl TxValidation {
             &current_constants,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            fork,
             transaction.to,
             *caller,
             transaction.data.clone(),
-            Some(transaction.execution_gas_limit()), // gas could be omitted
+            Some(gas_limit),
             gas_price,
+            U256::zero(),
             Some(transaction.value),
             false,
             allocated_ticks,
@@ -249,6 +859,15 @@ impl TxValidation {
         }
     }
 
+    fn gas_price(
+        transaction: &EthereumTransactionCommon,
+        block_fees: &BlockFees,
+    ) -> U256 {
+        transaction
+            .overall_gas_price(block_fees)
+            .unwrap_or_else(|_| block_fees.base_fee_per_gas())
+    }
+
     /// Execute the simulation
     pub fn run<Host: Runtime>(
         &self,
@@ -277,6 +896,12 @@ impl TxValidation {
         if tx.chain_id.is_some() && tx.chain_id != Some(chain_id) {
             return Ok(TxValidationOutcome::InvalidChainId);
         }
+        // Check if the gas limit covers the data availability fee
+        let gas_for_da_fee = block_fees
+            .gas_for_da_fee(tx.data.len() as u64, Self::gas_price(tx, &block_fees));
+        if tx.execution_gas_limit(gas_for_da_fee).is_none() {
+            return Ok(TxValidationOutcome::GasLimitTooLowForDaFee);
+        }
         // Check if running the transaction (assuming it is valid) would run out
         // of ticks.
         if let Ok(true) = Self::would_exhaust_ticks(host, tx, &caller) {
@@ -305,6 +930,11 @@ impl TryFrom<&[u8]> for TxValidation {
 enum Message {
     Evaluation(Evaluation),
     TxValidation(Box<TxValidation>),
+    Trace(TraceEvaluation),
+    TransactionTrace(TransactionTrace),
+    Estimation(Evaluation),
+    Proof(ProofRequest),
+    Logs(LogsFilter),
 }
 
 impl TryFrom<&[u8]> for Message {
@@ -318,6 +948,13 @@ impl TryFrom<&[u8]> for Message {
             EVALUATION_TAG => Evaluation::try_from(bytes).map(Message::Evaluation),
             VALIDATION_TAG => TxValidation::try_from(bytes)
                 .map(|tx| Message::TxValidation(Box::new(tx))),
+            TRACE_TAG => TraceEvaluation::try_from(bytes).map(Message::Trace),
+            TRACE_TRANSACTION_TAG => {
+                TransactionTrace::try_from(bytes).map(Message::TransactionTrace)
+            }
+            ESTIMATION_TAG => Evaluation::try_from(bytes).map(Message::Estimation),
+            PROOF_TAG => ProofRequest::try_from(bytes).map(Message::Proof),
+            LOGS_TAG => LogsFilter::decode(&Rlp::new(bytes)).map(Message::Logs),
             _ => Err(DecoderError::Custom("Unknown message to simulate")),
         }
     }
@@ -430,6 +1067,44 @@ fn store_simulation_outcome<Host: Runtime>(
     }
 }
 
+fn store_trace<Host: Runtime>(
+    host: &mut Host,
+    trace: Option<Trace>,
+) -> Result<(), anyhow::Error> {
+    match trace {
+        Some(trace) => storage::store_simulation_trace(host, &trace),
+        None => Ok(()),
+    }
+}
+
+fn store_gas_estimation<Host: Runtime>(
+    host: &mut Host,
+    estimation: GasEstimation,
+) -> Result<(), anyhow::Error> {
+    log!(host, Debug, "estimation={:?} ", estimation);
+    match estimation {
+        GasEstimation::Gas(gas) => {
+            storage::store_simulation_status(host, true)?;
+            storage::store_evaluation_gas(host, gas)?;
+            Ok(())
+        }
+        GasEstimation::Failure(outcome) => {
+            storage::store_simulation_status(host, false)?;
+            match outcome {
+                Some(outcome) => {
+                    storage::store_evaluation_gas(host, outcome.gas_used)?;
+                    storage::store_simulation_result(host, outcome.result)
+                }
+                None => Ok(()),
+            }
+        }
+        GasEstimation::OutOfTicks => {
+            storage::store_simulation_status(host, false)?;
+            storage::store_simulation_result(host, Some(OUT_OF_TICKS_MSG.to_vec()))
+        }
+    }
+}
+
 fn store_tx_validation_outcome<Host: Runtime>(
     host: &mut Host,
     outcome: TxValidationOutcome,
@@ -455,18 +1130,17 @@ fn store_tx_validation_outcome<Host: Runtime>(
             storage::store_simulation_status(host, false)?;
             storage::store_simulation_result(host, Some(b"Max gas fee too low.".to_vec()))
         }
-        TxValidationOutcome::OutOfTicks => {
+        TxValidationOutcome::GasLimitTooLowForDaFee => {
             storage::store_simulation_status(host, false)?;
             storage::store_simulation_result(
                 host,
-                Some(
-                    b"The transaction would exhaust all the ticks it is allocated. \
-                      Try reducing its gas consumption or splitting the call in \
-                      multiple steps, if possible."
-                        .to_vec(),
-                ),
+                Some(b"Gas limit too low for DA fee.".to_vec()),
             )
         }
+        TxValidationOutcome::OutOfTicks => {
+            storage::store_simulation_status(host, false)?;
+            storage::store_simulation_result(host, Some(OUT_OF_TICKS_MSG.to_vec()))
+        }
     }
 }
 
@@ -484,18 +1158,68 @@ pub fn start_simulation_mode<Host: Runtime>(
             let outcome = tx_validation.run(host)?;
             store_tx_validation_outcome(host, outcome)
         }
+        Message::Trace(TraceEvaluation { tracer, evaluation }) => {
+            let (outcome, trace) = evaluation.trace(host, tracer)?;
+            store_simulation_outcome(host, outcome)?;
+            store_trace(host, trace)
+        }
+        Message::TransactionTrace(transaction_trace) => {
+            match transaction_trace.run(host)? {
+                ExecutionResult::Valid(ExecutionInfo {
+                    receipt_info,
+                    trace,
+                    ..
+                }) => {
+                    store_simulation_outcome(host, receipt_info.execution_outcome)?;
+                    store_trace(host, trace)
+                }
+                ExecutionResult::Invalid => {
+                    storage::store_simulation_status(host, false)?;
+                    storage::store_simulation_result(
+                        host,
+                        Some(INVALID_TRANSACTION_MSG.to_vec()),
+                    )
+                }
+                ExecutionResult::OutOfTicks => {
+                    storage::store_simulation_status(host, false)?;
+                    storage::store_simulation_result(
+                        host,
+                        Some(OUT_OF_TICKS_MSG.to_vec()),
+                    )
+                }
+            }
+        }
+        Message::Estimation(evaluation) => {
+            let estimation = evaluation.estimate_gas(host)?;
+            store_gas_estimation(host, estimation)
+        }
+        Message::Proof(request) => {
+            let proof = request.run(host)?;
+            storage::store_simulation_status(host, true)?;
+            storage::store_simulation_result(host, Some(proof.rlp_bytes().to_vec()))
+        }
+        Message::Logs(filter) => {
+            let result = logs_index::query(host, &filter)?;
+            storage::store_simulation_status(host, true)?;
+            storage::store_simulation_result(host, Some(result.rlp_bytes().to_vec()))
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
 
-    use primitive_types::H256;
+    use evm::Opcode;
+    use evm_execution::trace::{CallType, StructLoggerConfig};
+    use evm_execution::EvmFork;
+    use tezos_ethereum::rlp_helpers::append_u256_le;
     use tezos_ethereum::{
         block::BlockConstants, transaction::TransactionType, tx_signature::TxSignature,
     };
     use tezos_smart_rollup_mock::MockHost;
 
+    use crate::inbox::TransactionContent;
+
     use crate::{current_timestamp, retrieve_block_fees, retrieve_chain_id};
 
     use super::*;
@@ -526,6 +1250,8 @@ mod tests {
             gas_price: None,
             value: None,
             data: vec![],
+            state_override: vec![],
+            block_override: BlockOverride::default(),
         };
 
         let evaluation = Evaluation::from_rlp(input_string);
@@ -552,6 +1278,8 @@ mod tests {
             gas_price: Some(22222),
             value: Some(U256::from(33333)),
             data,
+            state_override: vec![],
+            block_override: BlockOverride::default(),
         };
 
         let evaluation = Evaluation::from_rlp(input_string);
@@ -608,12 +1336,13 @@ mod tests {
             &block,
             &mut evm_account_storage,
             &precompiles,
-            CONFIG,
+            EvmFork::Shanghai,
             callee,
             caller,
             call_data,
             Some(gas_limit),
             gas_price,
+            U256::zero(),
             Some(transaction_value),
             false,
             DUMMY_ALLOCATED_TICKS,
@@ -641,6 +1370,8 @@ mod tests {
             data: hex::decode(STORAGE_CONTRACT_CALL_NUM).unwrap(),
             gas: Some(100000),
             value: None,
+            state_override: vec![],
+            block_override: BlockOverride::default(),
         };
         let outcome = evaluation.run(&mut host);
 
@@ -665,6 +1396,8 @@ mod tests {
             data: hex::decode(STORAGE_CONTRACT_CALL_GET).unwrap(),
             gas: Some(111111),
             value: None,
+            state_override: vec![],
+            block_override: BlockOverride::default(),
         };
         let outcome = evaluation.run(&mut host);
 
@@ -696,6 +1429,8 @@ mod tests {
             data: hex::decode(STORAGE_CONTRACT_CALL_NUM).unwrap(),
             gas: None,
             value: None,
+            state_override: vec![],
+            block_override: BlockOverride::default(),
         };
         let outcome = evaluation.run(&mut host);
 
@@ -713,6 +1448,197 @@ mod tests {
         );
     }
 
+    #[test]
+    fn estimate_gas() {
+        let mut host = MockHost::default();
+        let new_address = create_contract(&mut host);
+
+        // call: set(42)
+        let mut data = hex::decode("60fe47b1").unwrap();
+        data.extend(H256::from_low_u64_be(42).as_bytes());
+        let evaluation = Evaluation {
+            from: None,
+            gas_price: None,
+            to: Some(new_address),
+            data,
+            gas: None,
+            value: None,
+            state_override: vec![],
+            block_override: BlockOverride::default(),
+        };
+        let GasEstimation::Gas(gas) = evaluation.estimate_gas(&mut host).unwrap()
+            else { panic!("The estimation should have succeeded") };
+
+        // The estimation has no effect on the state
+        let num = Evaluation {
+            data: hex::decode(STORAGE_CONTRACT_CALL_NUM).unwrap(),
+            ..evaluation.clone()
+        };
+        let outcome = num.run(&mut host).unwrap().unwrap();
+        assert_eq!(Some(vec![0u8; 32]), outcome.result);
+
+        // The estimation is the minimal limit that succeeds
+        let with_gas = |gas| Evaluation {
+            gas: Some(gas),
+            ..evaluation.clone()
+        };
+        let outcome = with_gas(gas - 1).run(&mut host).unwrap().unwrap();
+        assert!(!outcome.is_success);
+        let outcome = with_gas(gas).run(&mut host).unwrap().unwrap();
+        assert!(outcome.is_success);
+    }
+
+    #[test]
+    fn estimate_gas_of_failing_call() {
+        let mut host = MockHost::default();
+        let new_address = create_contract(&mut host);
+
+        // The contract has no function with this selector, it reverts
+        let evaluation = Evaluation {
+            from: None,
+            gas_price: None,
+            to: Some(new_address),
+            data: hex::decode("deadbeef").unwrap(),
+            gas: Some(1_000_000),
+            value: None,
+            state_override: vec![],
+            block_override: BlockOverride::default(),
+        };
+        let estimation = evaluation.estimate_gas(&mut host).unwrap();
+        let GasEstimation::Failure(Some(outcome)) = estimation
+            else { panic!("Expected a failure, got {:?}", estimation) };
+        assert!(!outcome.is_success);
+        assert!(matches!(outcome.reason, evm::ExitReason::Revert(_)));
+    }
+
+    #[test]
+    fn estimate_gas_is_capped_by_the_maximum_gas_per_transaction() {
+        let mut host = MockHost::default();
+        let address = H160::from_low_u64_be(42);
+        let maximum_gas = tick_model::maximum_gas_per_transaction(0);
+
+        // Succeeds only with more than the maximum gas left
+        let mut code = vec![Opcode::PUSH4.as_u8()];
+        code.extend_from_slice(&(maximum_gas as u32).to_be_bytes());
+        code.extend_from_slice(&[
+            Opcode::GAS.as_u8(),
+            Opcode::GT.as_u8(),
+            Opcode::PUSH1.as_u8(),
+            14,
+            Opcode::JUMPI.as_u8(),
+            Opcode::PUSH1.as_u8(),
+            0,
+            Opcode::DUP1.as_u8(),
+            Opcode::REVERT.as_u8(),
+            Opcode::JUMPDEST.as_u8(),
+            Opcode::STOP.as_u8(),
+        ]);
+        let evaluation = Evaluation {
+            from: None,
+            gas_price: None,
+            to: Some(address),
+            data: vec![],
+            gas: Some(u64::MAX),
+            value: None,
+            state_override: vec![AccountOverride {
+                address,
+                balance: None,
+                nonce: None,
+                code: Some(code),
+                state: None,
+                state_diff: None,
+            }],
+            block_override: BlockOverride::default(),
+        };
+        let outcome = evaluation.run(&mut host).unwrap().unwrap();
+        assert!(outcome.is_success);
+
+        for gas in [None, Some(u64::MAX)] {
+            let evaluation = Evaluation {
+                gas,
+                ..evaluation.clone()
+            };
+            let estimation = evaluation.estimate_gas(&mut host).unwrap();
+            let GasEstimation::Failure(Some(outcome)) = estimation
+                else { panic!("Expected a failure, got {:?}", estimation) };
+            assert!(matches!(outcome.reason, evm::ExitReason::Revert(_)));
+        }
+    }
+
+    #[test]
+    fn parse_estimation() {
+        let encoded = hex::decode("f84894242424242424242424242424242424242424242494353535353535353535353535353535353535353588672b00000000000088ce56000000000000883582000000000000821616").unwrap();
+        let mut input = vec![
+            parsing::SIMULATION_TAG,
+            SIMULATION_SIMPLE_TAG,
+            ESTIMATION_TAG,
+        ];
+        input.extend(&encoded);
+
+        let expected = Evaluation::from_rlp_bytes(&encoded).unwrap();
+        let parsed = Input::parse(&input);
+
+        assert_eq!(
+            Input::Simple(Box::new(Message::Estimation(expected))),
+            parsed,
+            "should have been parsed as a gas estimation"
+        );
+    }
+
+    #[test]
+    fn parse_proof_request() {
+        let address = address_of_str("3535353535353535353535353535353535353535").unwrap();
+        let mut stream = rlp::RlpStream::new_list(3);
+        stream.append(&address);
+        stream.append_list(&[H256::zero(), H256::from_low_u64_be(1)]);
+        stream.append_empty_data();
+        let mut input = vec![parsing::SIMULATION_TAG, SIMULATION_SIMPLE_TAG, PROOF_TAG];
+        input.extend(stream.out().iter());
+
+        let expected = ProofRequest {
+            address,
+            storage_keys: vec![H256::zero(), H256::from_low_u64_be(1)],
+            block: None,
+        };
+        assert_eq!(
+            Input::Simple(Box::new(Message::Proof(expected.clone()))),
+            Input::parse(&input),
+            "should have been parsed as a proof request"
+        );
+
+        // The account is not in the empty state
+        let host = MockHost::default();
+        let proof = expected.run(&host).unwrap();
+        assert_eq!(proof.nonce, U256::zero());
+        assert_eq!(proof.storage_proof.len(), 2);
+        assert_eq!(proof.verify(&account_storage::EMPTY_TRIE_ROOT), Ok(()));
+    }
+
+    #[test]
+    fn parse_logs_filter() {
+        let address = address_of_str("3535353535353535353535353535353535353535").unwrap();
+        let mut stream = rlp::RlpStream::new_list(4);
+        stream.append(&address);
+        stream.append_empty_data();
+        stream.append(&U256::from(1));
+        stream.append(&U256::from(10));
+        let mut input = vec![parsing::SIMULATION_TAG, SIMULATION_SIMPLE_TAG, LOGS_TAG];
+        input.extend(stream.out().iter());
+
+        let expected = LogsFilter {
+            address: Some(address),
+            topic: None,
+            from_block: U256::from(1),
+            to_block: U256::from(10),
+            from_position: None,
+        };
+        assert_eq!(
+            Input::Simple(Box::new(Message::Logs(expected))),
+            Input::parse(&input),
+            "should have been parsed as a logs query"
+        );
+    }
+
     #[test]
     fn parse_simulation() {
         let to = address_of_str("3535353535353535353535353535353535353535");
@@ -725,6 +1651,8 @@ mod tests {
             gas_price: Some(22222),
             value: Some(U256::from(33333)),
             data,
+            state_override: vec![],
+            block_override: BlockOverride::default(),
         };
 
         let mut encoded =
@@ -761,6 +1689,8 @@ mod tests {
             gas_price: None,
             value: None,
             data,
+            state_override: vec![],
+            block_override: BlockOverride::default(),
         };
 
         let encoded = hex::decode(
@@ -776,6 +1706,379 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_trace_simulation() {
+        let evaluation = hex::decode("f84894242424242424242424242424242424242424242494353535353535353535353535353535353535353588672b00000000000088ce56000000000000883582000000000000821616").unwrap();
+        let tracer = TracerConfig::CallTracer;
+        let mut stream = rlp::RlpStream::new_list(2);
+        stream.append(&tracer);
+        stream.append_raw(&evaluation, 1);
+        let mut input = vec![parsing::SIMULATION_TAG, SIMULATION_SIMPLE_TAG, TRACE_TAG];
+        input.extend(stream.out());
+
+        let expected = TraceEvaluation {
+            tracer,
+            evaluation: Evaluation::from_rlp_bytes(&evaluation).unwrap(),
+        };
+        let parsed = Input::parse(&input);
+
+        assert_eq!(
+            Input::Simple(Box::new(Message::Trace(expected))),
+            parsed,
+            "should have been parsed as a traced simulation"
+        );
+    }
+
+    #[test]
+    fn trace_evaluation() {
+        let mut host = MockHost::default();
+        let new_address = create_contract(&mut host);
+        let evaluation = Evaluation {
+            from: None,
+            gas_price: None,
+            to: Some(new_address),
+            data: hex::decode(STORAGE_CONTRACT_CALL_GET).unwrap(),
+            gas: Some(111111),
+            value: None,
+            state_override: vec![],
+            block_override: BlockOverride::default(),
+        };
+
+        let (outcome, trace) = evaluation
+            .trace(
+                &mut host,
+                TracerConfig::StructLogger(StructLoggerConfig::default()),
+            )
+            .unwrap();
+        let outcome = outcome.unwrap();
+        let Some(Trace::StructLogs { gas_used, failed, return_value, struct_logs, truncated }) = trace
+            else { panic!("Expected struct logs, got {:?}", trace) };
+        assert_eq!(gas_used, outcome.gas_used);
+        assert!(!failed);
+        assert!(!truncated);
+        assert_eq!(Some(return_value), outcome.result);
+        let sload = struct_logs
+            .iter()
+            .find(|log| log.op == Opcode::SLOAD.as_u8())
+            .expect("The contract reads its storage");
+        assert_eq!(sload.storage, Some(vec![(H256::zero(), H256::zero())]));
+        assert!(struct_logs.iter().all(|log| log.depth == 1));
+
+        let (_, trace) = evaluation
+            .trace(&mut host, TracerConfig::CallTracer)
+            .unwrap();
+        let Some(Trace::Call { root: frame, .. }) = trace
+            else { panic!("Expected a call frame, got {:?}", trace) };
+        assert_eq!(frame.call_type, CallType::Call);
+        assert_eq!(frame.to, Some(new_address));
+        assert_eq!(frame.output, vec![0u8; 32]);
+        assert!(frame.calls.is_empty());
+    }
+
+    fn signed_transfer(nonce: u64, to: H160, value: U256) -> Transaction {
+        let transaction = EthereumTransactionCommon::new(
+            TransactionType::Eip1559,
+            Some(U256::from(1)),
+            U256::from(nonce),
+            U256::zero(),
+            U256::from(crate::BASE_FEE_PER_GAS),
+            21000,
+            Some(to),
+            value,
+            vec![],
+            vec![],
+            None,
+        );
+        let signed = transaction
+            .sign_transaction(
+                "e922354a3e5902b5ac474f3ff08a79cff43533826b8f451ae2190b65a9d26158"
+                    .to_string(),
+            )
+            .unwrap();
+        Transaction {
+            tx_hash: [nonce as u8; 32],
+            content: TransactionContent::Ethereum(signed),
+        }
+    }
+
+    #[test]
+    fn parse_transaction_trace() {
+        let transactions =
+            vec![signed_transfer(0, H160::from_low_u64_be(1), U256::one())];
+        let tracer = TracerConfig::CallTracer;
+        let mut stream = rlp::RlpStream::new_list(2);
+        stream.append(&tracer);
+        stream.append_list(&transactions);
+        let mut input = vec![
+            parsing::SIMULATION_TAG,
+            SIMULATION_SIMPLE_TAG,
+            TRACE_TRANSACTION_TAG,
+        ];
+        input.extend(stream.out());
+
+        let expected = TransactionTrace {
+            tracer,
+            transactions,
+        };
+        let parsed = Input::parse(&input);
+
+        assert_eq!(
+            Input::Simple(Box::new(Message::TransactionTrace(expected))),
+            parsed,
+            "should have been parsed as the trace of a transaction"
+        );
+    }
+
+    #[test]
+    fn trace_transaction_replays_the_previous_transactions() {
+        let mut host = MockHost::default();
+        storage::store_chain_id(&mut host, U256::from(1)).unwrap();
+        let caller =
+            address_from_str("f95abdf6ede4c3703e0e9453771fbee8592d31e9").unwrap();
+        let evm_account_storage = account_storage::init_account_storage().unwrap();
+        let mut account = evm_account_storage
+            .get_or_create(&host, &account_storage::account_path(&caller).unwrap())
+            .unwrap();
+        account
+            .balance_add(&mut host, U256::from(1_000_000_000_000_000_000u64))
+            .unwrap();
+
+        let first = H160::from_low_u64_be(1);
+        let second = H160::from_low_u64_be(2);
+        let transactions = vec![
+            signed_transfer(0, first, U256::from(10)),
+            signed_transfer(1, second, U256::from(20)),
+        ];
+
+        // Without the first transaction, the nonce of the second one is too
+        // high.
+        let invalid = TransactionTrace {
+            tracer: TracerConfig::CallTracer,
+            transactions: transactions[1..].to_vec(),
+        };
+        assert!(matches!(
+            invalid.run(&mut host).unwrap(),
+            ExecutionResult::Invalid
+        ));
+
+        let transaction_trace = TransactionTrace {
+            tracer: TracerConfig::CallTracer,
+            transactions,
+        };
+        let ExecutionResult::Valid(ExecutionInfo { receipt_info, trace, .. }) =
+            transaction_trace.run(&mut host).unwrap()
+            else { panic!("The second transaction should be valid") };
+        assert_eq!(receipt_info.index, 1);
+        assert_eq!(receipt_info.tx_hash, [1u8; 32]);
+        let Some(Trace::Call { root: frame, truncated }) = trace
+            else { panic!("Expected a call frame, got {:?}", trace) };
+        assert!(!truncated);
+        assert_eq!(frame.from, caller);
+        assert_eq!(frame.to, Some(second));
+        assert_eq!(frame.value, Some(U256::from(20)));
+    }
+
+    #[test]
+    fn parse_simulation_with_overrides() {
+        let address = H160::from_low_u64_be(42);
+        let slot = (H256::from_low_u64_be(1), H256::from_low_u64_be(2));
+
+        let mut stream = rlp::RlpStream::new_list(8);
+        stream
+            .append_empty_data()
+            .append(&address)
+            .append_empty_data()
+            .append_empty_data()
+            .append_empty_data()
+            .append(&vec![0x16u8]);
+        stream.begin_list(1);
+        stream.begin_list(6);
+        stream.append(&address);
+        append_u256_le(&mut stream, &U256::from(100));
+        stream.append_empty_data().append(&vec![0x00u8]);
+        stream.append_empty_data();
+        stream
+            .begin_list(1)
+            .begin_list(2)
+            .append(&slot.0)
+            .append(&slot.1);
+        stream.begin_list(3);
+        stream.append_empty_data();
+        append_u256_le(&mut stream, &U256::from(1234));
+        stream.append_empty_data();
+
+        let mut input = vec![
+            parsing::SIMULATION_TAG,
+            SIMULATION_SIMPLE_TAG,
+            EVALUATION_TAG,
+        ];
+        input.extend(stream.out());
+
+        let expected = Evaluation {
+            from: None,
+            to: Some(address),
+            gas: None,
+            gas_price: None,
+            value: None,
+            data: vec![0x16],
+            state_override: vec![AccountOverride {
+                address,
+                balance: Some(U256::from(100)),
+                nonce: None,
+                code: Some(vec![0x00]),
+                state: None,
+                state_diff: Some(vec![slot]),
+            }],
+            block_override: BlockOverride {
+                number: None,
+                timestamp: Some(U256::from(1234)),
+                base_fee_per_gas: None,
+            },
+        };
+        assert_eq!(
+            Input::Simple(Box::new(Message::Evaluation(expected))),
+            Input::parse(&input),
+            "should have been parsed as a simulation with overrides"
+        );
+    }
+
+    #[test]
+    fn simulation_with_state_override() {
+        let mut host = MockHost::default();
+        let new_address = create_contract(&mut host);
+
+        let num = Evaluation {
+            from: None,
+            gas_price: None,
+            to: Some(new_address),
+            data: hex::decode(STORAGE_CONTRACT_CALL_NUM).unwrap(),
+            gas: Some(100000),
+            value: None,
+            state_override: vec![AccountOverride {
+                address: new_address,
+                balance: None,
+                nonce: None,
+                code: None,
+                state: None,
+                state_diff: Some(vec![(H256::zero(), H256::from_low_u64_be(42))]),
+            }],
+            block_override: BlockOverride::default(),
+        };
+        let outcome = num.run(&mut host).unwrap().unwrap();
+        assert_eq!(
+            Some(H256::from_low_u64_be(42).as_bytes().to_vec()),
+            outcome.result
+        );
+
+        // The override is discarded after the simulation
+        let num = Evaluation {
+            state_override: vec![],
+            ..num
+        };
+        let outcome = num.run(&mut host).unwrap().unwrap();
+        assert_eq!(Some(vec![0u8; 32]), outcome.result);
+    }
+
+    #[test]
+    fn simulation_includes_da_fee() {
+        let mut host = MockHost::default();
+        let new_address = create_contract(&mut host);
+
+        let num = Evaluation {
+            from: None,
+            gas_price: None,
+            to: Some(new_address),
+            data: hex::decode(STORAGE_CONTRACT_CALL_NUM).unwrap(),
+            gas: Some(100000),
+            value: None,
+            state_override: vec![],
+            block_override: BlockOverride::default(),
+        };
+        let gas_used = num.run(&mut host).unwrap().unwrap().gas_used;
+        let GasEstimation::Gas(estimation) = num.estimate_gas(&mut host).unwrap() else {
+            panic!("The gas estimation should have succeeded")
+        };
+
+        // The 4 bytes of the call cost the same as 100 gas
+        let da_fee_per_byte = U256::from(crate::BASE_FEE_PER_GAS) * 25;
+        storage::write_u256(
+            &mut host,
+            &RefPath::assert_from(b"/da_fee_per_byte").into(),
+            da_fee_per_byte,
+        )
+        .unwrap();
+
+        let outcome = num.run(&mut host).unwrap().unwrap();
+        assert!(outcome.is_success);
+        assert_eq!(outcome.gas_used, gas_used + 100);
+        assert_eq!(
+            num.estimate_gas(&mut host).unwrap(),
+            GasEstimation::Gas(estimation + 100)
+        );
+    }
+
+    #[test]
+    fn simulation_with_code_and_block_override() {
+        let mut host = MockHost::default();
+        let address = H160::from_low_u64_be(42);
+        let caller = H160::from_low_u64_be(43);
+        // Returns the timestamp
+        let code = vec![
+            Opcode::TIMESTAMP.as_u8(),
+            Opcode::PUSH1.as_u8(),
+            0,
+            Opcode::MSTORE.as_u8(),
+            Opcode::PUSH1.as_u8(),
+            32,
+            Opcode::PUSH1.as_u8(),
+            0,
+            Opcode::RETURN.as_u8(),
+        ];
+        let evaluation = Evaluation {
+            from: Some(caller),
+            gas_price: None,
+            to: Some(address),
+            data: vec![],
+            gas: Some(100000),
+            value: Some(U256::from(100)),
+            state_override: vec![
+                AccountOverride {
+                    address,
+                    balance: None,
+                    nonce: None,
+                    code: Some(code),
+                    state: None,
+                    state_diff: None,
+                },
+                AccountOverride {
+                    address: caller,
+                    balance: Some(U256::from(100)),
+                    nonce: None,
+                    code: None,
+                    state: None,
+                    state_diff: None,
+                },
+            ],
+            block_override: BlockOverride {
+                number: None,
+                timestamp: Some(U256::from(1234)),
+                base_fee_per_gas: None,
+            },
+        };
+        let outcome = evaluation.run(&mut host).unwrap().unwrap();
+        assert!(outcome.is_success);
+        assert_eq!(
+            Some(H256::from_low_u64_be(1234).as_bytes().to_vec()),
+            outcome.result
+        );
+
+        // Without the balance override, the caller cannot transfer the value
+        let mut evaluation = evaluation;
+        evaluation.state_override.pop();
+        let outcome = evaluation.run(&mut host).unwrap().unwrap();
+        assert!(!outcome.is_success);
+    }
+
     #[test]
     fn parse_num_chunks() {
         let num: u16 = 42;
@@ -917,4 +2220,49 @@ mod tests {
         assert!(result.is_ok());
         assert_eq!(TxValidationOutcome::MaxGasFeeTooLow, result.unwrap());
     }
+
+    #[test]
+    fn test_tx_validation_gas_limit_too_low_for_da_fee() {
+        let mut host = MockHost::default();
+        storage::store_chain_id(&mut host, U256::from(1))
+            .expect("should be able to store a chain id");
+        // The 4 bytes of the call cost the same as 100 gas
+        let da_fee_per_byte = U256::from(crate::BASE_FEE_PER_GAS) * 25;
+        storage::write_u256(
+            &mut host,
+            &RefPath::assert_from(b"/da_fee_per_byte").into(),
+            da_fee_per_byte,
+        )
+        .unwrap();
+
+        let validation = |gas_limit| {
+            let transaction = EthereumTransactionCommon::new(
+                TransactionType::Eip1559,
+                Some(U256::from(1)),
+                U256::from(0),
+                U256::zero(),
+                U256::from(crate::BASE_FEE_PER_GAS),
+                gas_limit,
+                Some(H160::zero()),
+                U256::zero(),
+                vec![0; 4],
+                vec![],
+                None,
+            );
+            let signed = transaction
+                .sign_transaction(
+                    "e922354a3e5902b5ac474f3ff08a79cff43533826b8f451ae2190b65a9d26158"
+                        .to_string(),
+                )
+                .unwrap();
+            TxValidation {
+                transaction: signed,
+            }
+        };
+
+        let result = validation(99).run(&mut host).unwrap();
+        assert_eq!(TxValidationOutcome::GasLimitTooLowForDaFee, result);
+        let result = validation(100).run(&mut host).unwrap();
+        assert_ne!(TxValidationOutcome::GasLimitTooLowForDaFee, result);
+    }
 }
@@ -3,12 +3,19 @@
 // SPDX-License-Identifier: MIT
 
 use crate::blueprint::Blueprint;
-use crate::blueprint_storage::{store_inbox_blueprint, store_sequencer_blueprint};
+use crate::blueprint_storage::{
+    store_forced_blueprint, store_inbox_blueprint, store_sequencer_blueprint,
+};
 use crate::current_timestamp;
-use crate::delayed_inbox::DelayedInbox;
+use crate::delayed_inbox::{
+    read_delayed_inbox_min_levels, read_delayed_inbox_timeout, DelayedInbox,
+};
 use crate::inbox::InboxContent;
 use crate::inbox::{read_inbox, TezosContracts};
-use crate::upgrade::store_kernel_upgrade;
+use crate::sequencer_keys::{remove_sequencers, Sequencers};
+use crate::storage::{
+    has_block_in_progress, read_l1_level, read_last_info_per_level_timestamp,
+};
 use anyhow::Ok;
 use tezos_crypto_rs::hash::ContractKt1Hash;
 use tezos_evm_logging::{log, Level::*};
@@ -22,7 +29,7 @@ pub enum Configuration {
     Sequencer {
         delayed_bridge: ContractKt1Hash,
         delayed_inbox: Box<DelayedInbox>,
-        sequencer: PublicKey,
+        sequencers: Sequencers,
     },
 }
 
@@ -33,11 +40,11 @@ impl std::fmt::Display for Configuration {
             Configuration::Sequencer {
                 delayed_bridge,
                 delayed_inbox: _, // Ignoring delayed_inbox
-                sequencer,
+                sequencers,
             } => write!(
                 f,
-                "Sequencer {{ delayed_bridge: {:?}, sequencer: {:?} }}",
-                delayed_bridge, sequencer
+                "Sequencer {{ delayed_bridge: {:?}, sequencers: {} }}",
+                delayed_bridge, sequencers
             ),
         }
     }
@@ -49,7 +56,6 @@ pub fn fetch_inbox_blueprints<Host: Runtime>(
     tezos_contracts: TezosContracts,
 ) -> Result<(), anyhow::Error> {
     if let Some(InboxContent {
-        kernel_upgrade,
         transactions,
         sequencer_blueprints: _,
     }) = read_inbox(host, smart_rollup_address, tezos_contracts, None, None)?
@@ -61,10 +67,6 @@ pub fn fetch_inbox_blueprints<Host: Runtime>(
         };
         // Store the blueprint.
         store_inbox_blueprint(host, blueprint)?;
-        // Store kernel upgrade.
-        if let Some(kernel_upgrade) = kernel_upgrade {
-            store_kernel_upgrade(host, &kernel_upgrade)?;
-        };
     }
     Ok(())
 }
@@ -75,10 +77,9 @@ fn fetch_sequencer_blueprints<Host: Runtime>(
     tezos_contracts: TezosContracts,
     delayed_bridge: ContractKt1Hash,
     delayed_inbox: &mut DelayedInbox,
-    sequencer: PublicKey,
+    sequencers: &mut Sequencers,
 ) -> Result<(), anyhow::Error> {
     if let Some(InboxContent {
-        kernel_upgrade,
         transactions,
         sequencer_blueprints,
     }) = read_inbox(
@@ -86,11 +87,15 @@ fn fetch_sequencer_blueprints<Host: Runtime>(
         smart_rollup_address,
         tezos_contracts,
         Some(delayed_bridge),
-        Some(sequencer),
+        Some(sequencers.clone()),
     )? {
-        // Store the transactions in the delayed inbox.
+        // Store the transactions in the delayed inbox, with the L1 block
+        // they were submitted in.
+        let timestamp = read_last_info_per_level_timestamp(host)?;
+        let level = read_l1_level(host)?;
+        sequencers.activate(host, level)?;
         for transaction in transactions {
-            delayed_inbox.save_transaction(host, transaction)?;
+            delayed_inbox.save_transaction(host, transaction, timestamp, level)?;
         }
 
         // Store the blueprints.
@@ -98,20 +103,62 @@ fn fetch_sequencer_blueprints<Host: Runtime>(
             log!(
                 host,
                 Debug,
-                "Storing chunk {} of sequencer blueprint number {}",
+                "Storing chunk {} of sequencer blueprint number {} signed by {:?}",
                 seq_blueprint.blueprint.chunk_index,
-                seq_blueprint.blueprint.number
+                seq_blueprint.blueprint.number,
+                seq_blueprint
+                    .signers
+                    .iter()
+                    .map(PublicKey::to_b58check)
+                    .collect::<Vec<_>>()
             );
             store_sequencer_blueprint(host, seq_blueprint)?
         }
-        // Store kernel upgrade.
-        if let Some(kernel_upgrade) = kernel_upgrade {
-            store_kernel_upgrade(host, &kernel_upgrade)?;
-        };
     }
     Ok(())
 }
 
+/// Returns true if the oldest transaction of the delayed inbox waited for the
+/// sequencer longer than the timeout.
+fn delayed_inbox_timed_out<Host: Runtime>(
+    host: &mut Host,
+    delayed_inbox: &DelayedInbox,
+) -> Result<bool, anyhow::Error> {
+    if delayed_inbox.is_empty() {
+        return Ok(false);
+    }
+    let timestamp = read_last_info_per_level_timestamp(host)?;
+    let level = read_l1_level(host)?;
+    let timeout = read_delayed_inbox_timeout(host)?;
+    let min_levels = read_delayed_inbox_min_levels(host)?;
+    delayed_inbox.first_has_timed_out(host, timestamp, level, timeout, min_levels)
+}
+
+/// Includes the delayed inbox in a blueprint made by the kernel, in place of
+/// the blueprints of the sequencer, and removes the sequencer. The kernel
+/// then runs in proxy mode until a new sequencer is set. As no sequencer
+/// will include them, the transactions which are not overdue yet are
+/// included as well.
+fn force_delayed_inbox_inclusion<Host: Runtime>(
+    host: &mut Host,
+    delayed_inbox: &mut DelayedInbox,
+) -> Result<(), anyhow::Error> {
+    log!(
+        host,
+        Error,
+        "The sequencer did not include the delayed transactions in time, \
+         falling back to proxy mode"
+    );
+    let transactions = delayed_inbox.remove_all(host)?;
+    let timestamp = current_timestamp(host);
+    let blueprint = Blueprint {
+        transactions,
+        timestamp,
+    };
+    store_forced_blueprint(host, blueprint)?;
+    remove_sequencers(host)
+}
+
 pub fn fetch<Host: Runtime>(
     host: &mut Host,
     smart_rollup_address: [u8; RAW_ROLLUP_ADDRESS_SIZE],
@@ -122,17 +169,138 @@ pub fn fetch<Host: Runtime>(
         Configuration::Sequencer {
             delayed_bridge,
             delayed_inbox,
-            sequencer,
-        } => fetch_sequencer_blueprints(
-            host,
-            smart_rollup_address,
-            tezos_contracts,
-            delayed_bridge.clone(),
-            delayed_inbox,
-            sequencer.clone(),
-        ),
+            sequencers,
+        } => {
+            fetch_sequencer_blueprints(
+                host,
+                smart_rollup_address,
+                tezos_contracts,
+                delayed_bridge.clone(),
+                delayed_inbox,
+                sequencers,
+            )?;
+            // The blueprint is forced once the block in progress, if any, is
+            // finished, as it takes the number of the next block.
+            if delayed_inbox_timed_out(host, delayed_inbox)?
+                && !has_block_in_progress(host)?
+            {
+                force_delayed_inbox_inclusion(host, delayed_inbox)?;
+                *config = Configuration::Proxy;
+            }
+            Ok(())
+        }
         Configuration::Proxy => {
             fetch_inbox_blueprints(host, smart_rollup_address, tezos_contracts)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blueprint_storage::read_next_blueprint;
+    use crate::inbox::{Deposit, Transaction, TransactionContent};
+    use crate::sequencer_keys::{update_sequencers, SequencerUpdate};
+    use crate::storage::{store_l1_level, store_last_info_per_level_timestamp};
+    use primitive_types::{H160, U256};
+    use tezos_ethereum::transaction::TRANSACTION_HASH_SIZE;
+    use tezos_smart_rollup_encoding::timestamp::Timestamp;
+    use tezos_smart_rollup_mock::MockHost;
+
+    const SMART_ROLLUP_ADDRESS: [u8; 20] = [
+        20, 19, 18, 17, 16, 15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1,
+    ];
+
+    fn tezos_contracts() -> TezosContracts {
+        TezosContracts {
+            ticketer: None,
+            admin: None,
+            sequencer_admin: None,
+        }
+    }
+
+    fn deposit(i: u8) -> Transaction {
+        Transaction {
+            tx_hash: [i; TRANSACTION_HASH_SIZE],
+            content: TransactionContent::Deposit(Deposit {
+                amount: U256::from(i),
+                receiver: H160::zero(),
+            }),
+        }
+    }
+
+    fn sequencer_config(host: &mut MockHost) -> Configuration {
+        let sequencer = "edpkuDMUm7Y53wp4gxeLBXuiAhXZrLn8XB1R83ksvvesH8Lp8bmCfK";
+        update_sequencers(
+            host,
+            SequencerUpdate::parse(sequencer.as_bytes()).unwrap(),
+            0,
+        )
+        .unwrap();
+        let delayed_inbox = DelayedInbox::new(host).unwrap();
+        Configuration::Sequencer {
+            delayed_bridge: ContractKt1Hash::from_base58_check(
+                "KT18amZmM5W7qDWVt2pH6uj7sCEd3kbzLrHT",
+            )
+            .unwrap(),
+            delayed_inbox: Box::new(delayed_inbox),
+            sequencers: Sequencers::read(host).unwrap().unwrap(),
+        }
+    }
+
+    fn set_l1_block(host: &mut MockHost, timestamp: i64, level: u32) {
+        store_last_info_per_level_timestamp(host, Timestamp::from(timestamp)).unwrap();
+        store_l1_level(host, level).unwrap();
+    }
+
+    fn save_deposits(host: &mut MockHost, config: &mut Configuration, ids: &[u8]) {
+        let Configuration::Sequencer { delayed_inbox, .. } = config else {
+            panic!("The kernel should be in sequencer mode")
+        };
+        let timestamp = read_last_info_per_level_timestamp(host).unwrap();
+        let level = read_l1_level(host).unwrap();
+        for i in ids {
+            delayed_inbox
+                .save_transaction(host, deposit(*i), timestamp, level)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_delayed_transactions_are_forced_after_timeout() {
+        let mut host = MockHost::default();
+        let mut config = sequencer_config(&mut host);
+        set_l1_block(&mut host, 1_000, 10);
+        save_deposits(&mut host, &mut config, &[1]);
+        set_l1_block(&mut host, 20_000, 400);
+        save_deposits(&mut host, &mut config, &[2]);
+
+        // The first deposit waits for less than the minimum number of levels.
+        fetch(
+            &mut host,
+            SMART_ROLLUP_ADDRESS,
+            tezos_contracts(),
+            &mut config,
+        )
+        .unwrap();
+        assert!(matches!(config, Configuration::Sequencer { .. }));
+
+        set_l1_block(&mut host, 50_000, 800);
+        fetch(
+            &mut host,
+            SMART_ROLLUP_ADDRESS,
+            tezos_contracts(),
+            &mut config,
+        )
+        .unwrap();
+        assert!(matches!(config, Configuration::Proxy));
+        assert!(Sequencers::read(&host).unwrap().is_none());
+        assert!(DelayedInbox::new(&mut host).unwrap().is_empty());
+
+        // All the delayed transactions are included in the next blueprint.
+        let blueprint = read_next_blueprint(&mut host, &mut config)
+            .unwrap()
+            .expect("The forced blueprint should be stored");
+        assert_eq!(blueprint.transactions, vec![deposit(1), deposit(2)]);
+    }
+}
@@ -11,12 +11,12 @@ use evm_execution::account_storage::EthereumAccount;
 use tezos_crypto_rs::hash::{ContractKt1Hash, HashTrait};
 use tezos_evm_logging::{log, Level::*};
 use tezos_smart_rollup_core::MAX_FILE_CHUNK_SIZE;
-use tezos_smart_rollup_encoding::public_key::PublicKey;
 use tezos_smart_rollup_encoding::timestamp::Timestamp;
 use tezos_smart_rollup_host::path::*;
 use tezos_smart_rollup_host::runtime::{Runtime, ValueType};
 
 use crate::error::{Error, StorageError};
+use crate::DA_FEE_PER_BYTE;
 use rlp::{Decodable, Encodable, Rlp};
 use tezos_ethereum::block::L2Block;
 use tezos_ethereum::rlp_helpers::FromRlpBytes;
@@ -27,7 +27,7 @@ use tezos_ethereum::wei::Wei;
 
 use primitive_types::{H160, H256, U256};
 
-pub const STORAGE_VERSION: u64 = 4;
+pub const STORAGE_VERSION: u64 = 5;
 pub const STORAGE_VERSION_PATH: RefPath = RefPath::assert_from(b"/storage_version");
 
 const KERNEL_VERSION_PATH: RefPath = RefPath::assert_from(b"/kernel_version");
@@ -55,6 +55,9 @@ const EVM_CHAIN_ID: RefPath = RefPath::assert_from(b"/chain_id");
 
 const EVM_BASE_FEE_PER_GAS: RefPath = RefPath::assert_from(b"/base_fee_per_gas");
 
+// Fee paid for each byte of the transactions posted on the L1 inbox.
+const EVM_DA_FEE_PER_BYTE: RefPath = RefPath::assert_from(b"/da_fee_per_byte");
+
 /// Path to the last info per level timestamp seen.
 const EVM_INFO_PER_LEVEL_TIMESTAMP: RefPath =
     RefPath::assert_from(b"/info_per_level/timestamp");
@@ -65,9 +68,13 @@ const EVM_INFO_PER_LEVEL_STATS_NUMBERS: RefPath =
 const EVM_INFO_PER_LEVEL_STATS_TOTAL: RefPath =
     RefPath::assert_from(b"/info_per_level/stats/total");
 
+// Level of the L1 block whose inbox was read last.
+const EVM_L1_LEVEL: RefPath = RefPath::assert_from(b"/l1_level");
+
 pub const SIMULATION_RESULT: RefPath = RefPath::assert_from(b"/simulation_result");
 pub const SIMULATION_STATUS: RefPath = RefPath::assert_from(b"/simulation_status");
 pub const SIMULATION_GAS: RefPath = RefPath::assert_from(b"/simulation_gas");
+pub const SIMULATION_TRACE: RefPath = RefPath::assert_from(b"/trace");
 
 pub const DEPOSIT_NONCE: RefPath = RefPath::assert_from(b"/deposit_nonce");
 
@@ -83,12 +90,17 @@ const BLOCKS_INDEX: RefPath = EVM_BLOCKS;
 /// Subpath where transactions are indexed
 const TRANSACTIONS_INDEX: RefPath = RefPath::assert_from(b"/transactions");
 
+/// Subpath where logs are indexed by emitting address.
+const LOGS_ADDRESSES_INDEX: RefPath = RefPath::assert_from(b"/logs/addresses");
+
+/// Subpath where logs are indexed by first topic.
+const LOGS_TOPICS_INDEX: RefPath = RefPath::assert_from(b"/logs/topics");
+
 /// The size of one 256 bit word. Size in bytes
 pub const WORD_SIZE: usize = 32usize;
 
-// Path to the tz1 administrating the sequencer. If there is nothing
-// at this path, the kernel is in proxy mode.
-const SEQUENCER: RefPath = RefPath::assert_from(b"/sequencer");
+// Address receiving the priority fees of the transactions.
+const SEQUENCER_COINBASE: RefPath = RefPath::assert_from(b"/sequencer_coinbase");
 
 pub fn store_read_slice<Host: Runtime, T: Path>(
     host: &Host,
@@ -113,6 +125,22 @@ fn read_u256(host: &impl Runtime, path: &OwnedPath) -> Result<U256, Error> {
     Ok(Wei::from_little_endian(&bytes))
 }
 
+/// Read a single unsigned 256 bit value from storage at the path given, or
+/// `default` if there is none.
+pub fn read_u256_or_default(
+    host: &impl Runtime,
+    path: &impl Path,
+    default: U256,
+) -> Result<U256, Error> {
+    match host.store_has(path)? {
+        Some(ValueType::Value | ValueType::ValueWithSubtree) => {
+            let bytes = host.store_read(path, 0, WORD_SIZE)?;
+            Ok(Wei::from_little_endian(&bytes))
+        }
+        _ => Ok(default),
+    }
+}
+
 pub fn write_u256(
     host: &mut impl Runtime,
     path: &OwnedPath,
@@ -295,6 +323,14 @@ pub fn store_simulation_status<Host: Runtime>(
         .context("Failed to write the simulation status.")
 }
 
+pub fn store_simulation_trace<Host: Runtime, T: Encodable>(
+    host: &mut Host,
+    trace: &T,
+) -> Result<(), anyhow::Error> {
+    host.store_write_all(&SIMULATION_TRACE, &trace.rlp_bytes())
+        .context("Failed to write the simulation trace.")
+}
+
 pub fn store_transaction_receipt<Host: Runtime>(
     host: &mut Host,
     receipt: &TransactionReceipt,
@@ -555,6 +591,10 @@ pub fn read_base_fee_per_gas<Host: Runtime>(host: &mut Host) -> Result<U256, Err
     read_u256(host, &EVM_BASE_FEE_PER_GAS.into())
 }
 
+pub fn read_da_fee_per_byte<Host: Runtime>(host: &Host) -> Result<U256, Error> {
+    read_u256_or_default(host, &EVM_DA_FEE_PER_BYTE, DA_FEE_PER_BYTE.into())
+}
+
 pub fn store_timestamp_path<Host: Runtime>(
     host: &mut Host,
     path: &OwnedPath,
@@ -620,6 +660,17 @@ pub fn read_last_info_per_level_timestamp<Host: Runtime>(
     read_timestamp_path(host, &EVM_INFO_PER_LEVEL_TIMESTAMP.into())
 }
 
+pub fn store_l1_level<Host: Runtime>(host: &mut Host, level: u32) -> Result<(), Error> {
+    host.store_write_all(&EVM_L1_LEVEL, &level.to_le_bytes())?;
+    Ok(())
+}
+
+pub fn read_l1_level<Host: Runtime>(host: &Host) -> Result<u32, Error> {
+    let mut buffer = [0u8; 4];
+    store_read_slice(host, &EVM_L1_LEVEL, &mut buffer, 4)?;
+    Ok(u32::from_le_bytes(buffer))
+}
+
 /// Get the index of accounts.
 pub fn init_account_index() -> Result<IndexableStorage, StorageError> {
     let path = concat(&EVM_INDEXES, &ACCOUNTS_INDEX)?;
@@ -638,6 +689,26 @@ pub fn init_transaction_hashes_index() -> Result<IndexableStorage, StorageError>
     IndexableStorage::new(&RefPath::from(&path))
 }
 
+fn init_logs_index(
+    subpath: &RefPath,
+    key: &[u8],
+) -> Result<IndexableStorage, StorageError> {
+    let key_path: Vec<u8> = format!("/{}", hex::encode(key)).into();
+    let key_path = OwnedPath::try_from(key_path)?;
+    let path = concat(&concat(&EVM_INDEXES, subpath)?, &key_path)?;
+    IndexableStorage::new(&RefPath::from(&path))
+}
+
+/// Get the index of the logs emitted by `address`.
+pub fn init_address_logs_index(address: &H160) -> Result<IndexableStorage, StorageError> {
+    init_logs_index(&LOGS_ADDRESSES_INDEX, address.as_bytes())
+}
+
+/// Get the index of the logs whose first topic is `topic`.
+pub fn init_topic_logs_index(topic: &H256) -> Result<IndexableStorage, StorageError> {
+    init_logs_index(&LOGS_TOPICS_INDEX, topic.as_bytes())
+}
+
 pub fn index_account(
     host: &mut impl Runtime,
     address: &H160,
@@ -675,6 +746,13 @@ pub fn read_sequencer_admin<Host: Runtime>(host: &mut Host) -> Option<ContractKt
     read_b58_kt1(host, &SEQUENCER_ADMIN.into())
 }
 
+/// Reads the address receiving the priority fees, if any.
+pub fn read_sequencer_coinbase<Host: Runtime>(host: &Host) -> Option<H160> {
+    let mut buffer = [0; 20];
+    store_read_slice(host, &SEQUENCER_COINBASE, &mut buffer, 20).ok()?;
+    Some(H160::from(buffer))
+}
+
 pub fn get_and_increment_deposit_nonce<Host: Runtime>(
     host: &mut Host,
 ) -> Result<u32, Error> {
@@ -791,30 +869,15 @@ pub fn read_block_in_progress<Host: Runtime>(
     }
 }
 
+pub fn has_block_in_progress<Host: Runtime>(host: &Host) -> anyhow::Result<bool> {
+    Ok(host.store_has(&EVM_BLOCK_IN_PROGRESS)?.is_some())
+}
+
 pub fn delete_block_in_progress<Host: Runtime>(host: &mut Host) -> anyhow::Result<()> {
     host.store_delete(&EVM_BLOCK_IN_PROGRESS)
         .context("Failed to delete block in progress")
 }
 
-pub fn sequencer<Host: Runtime>(host: &Host) -> anyhow::Result<Option<PublicKey>> {
-    if host.store_has(&SEQUENCER)?.is_some() {
-        let bytes = host.store_read_all(&SEQUENCER)?;
-        let Ok(tz1_b58) = String::from_utf8(bytes) else { return Ok(None) };
-        let Ok(tz1) = PublicKey::from_b58check(&tz1_b58) else { return Ok(None)};
-        Ok(Some(tz1))
-    } else {
-        Ok(None)
-    }
-}
-pub fn store_sequencer<Host: Runtime>(
-    host: &mut Host,
-    public_key: PublicKey,
-) -> anyhow::Result<()> {
-    let pk_b58 = PublicKey::to_b58check(&public_key);
-    let bytes = String::as_bytes(&pk_b58);
-    host.store_write_all(&SEQUENCER, bytes).map_err(Into::into)
-}
-
 #[cfg(test)]
 mod internal_for_tests {
     use super::*;
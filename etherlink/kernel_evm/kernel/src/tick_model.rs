@@ -48,7 +48,7 @@ pub mod constants {
     pub const SAFETY_MARGIN: u64 = QUEUE_STORING_UPPER_BOUND + 1_000_000_000;
 
     /// The minimum amount of gas for an ethereum transaction.
-    pub const BASE_GAS: u64 = crate::CONFIG.gas_transaction_call;
+    pub const BASE_GAS: u64 = evm_execution::Config::shanghai().gas_transaction_call;
 
     /// Overapproximation of the upper bound of the number of ticks used to
     /// fetch the inbox. Considers an inbox with the size of a full block, and
@@ -94,6 +94,28 @@ pub mod constants {
     pub const BLOOM_TICKS_INTERCEPT: u64 = 10000;
     pub const BLOOM_TICKS_COEF: u64 = 85000;
 
+    /// The number of ticks used to push the position of a log to an index of
+    /// the logs, reading and updating its length and storing the position.
+    /// Each log is indexed by its address and its first topic. Benchmarked on
+    /// `index_logs` compiled to WASM, from 130k to 142k ticks per entry for
+    /// indexes of up to 10k positions.
+    pub const LOG_INDEX_TICKS_PER_ENTRY: u64 = 200_000;
+
+    /// The number of ticks used by the transactions and receipts tries at
+    /// finalization is overapproximated by an affine function of the size of
+    /// each leaf. Benchmarked on `ordered_trie_root` compiled to WASM, from
+    /// 1 to 1000 leaves of 100B to 10kB.
+    pub const TRIE_LEAF_TICKS_INTERCEPT: u64 = 200_000;
+    pub const TRIE_LEAF_TICKS_COEF: u64 = 1_500;
+
+    /// The number of ticks used to flush an outbox message from the outbox
+    /// queue is overapproximated by an affine function of its number of
+    /// transactions. Writing the message is in the intercept, reading,
+    /// deleting and recording the receipt of a transaction in the
+    /// coefficient. Not benchmarked.
+    pub const OUTBOX_MESSAGE_TICKS_INTERCEPT: u64 = 5_000_000;
+    pub const OUTBOX_MESSAGE_TICKS_COEF: u64 = 1_500_000;
+
     /// The number of ticks used during transaction execution doing something
     /// other than executing an opcode is overapproximated by an affine function
     /// of the size of a transaction object
@@ -113,6 +135,15 @@ pub fn estimate_remaining_ticks_for_transaction_execution(
         .saturating_sub(ticks)
 }
 
+/// Highest gas limit a transaction can use before exhausting the ticks it is
+/// allocated. No opcode whose ticks depend on the gas it consumes uses less
+/// than [constants::TICKS_PER_GAS] ticks per gas unit, storage writes and
+/// precompiled contracts are modeled separately and can use more gas.
+pub fn maximum_gas_per_transaction(tx_data_size: u64) -> u64 {
+    estimate_remaining_ticks_for_transaction_execution(0, tx_data_size)
+        / constants::TICKS_PER_GAS
+}
+
 /// Estimation of the number of ticks used up for executing a transaction
 /// besides executing the opcodes.
 fn ticks_of_transaction_overhead(tx_data_size: u64) -> u64 {
@@ -146,7 +177,8 @@ pub fn ticks_of_valid_transaction(
         }
         // Ticks are already spent during the validation of the transaction (see
         // apply.rs).
-        crate::inbox::TransactionContent::Deposit(_) => resulting_ticks,
+        crate::inbox::TransactionContent::Deposit(_)
+        | crate::inbox::TransactionContent::FaDeposit(_) => resulting_ticks,
     }
 }
 
@@ -169,6 +201,24 @@ pub fn bloom_size(logs: &[IndexedLog]) -> usize {
     size
 }
 
+/// Ticks of indexing the `logs` by address and first topic, see
+/// [crate::logs_index].
+pub fn ticks_of_logs_index(logs: &[IndexedLog]) -> u64 {
+    let entries = logs
+        .iter()
+        .map(|log| if log.log.topics.is_empty() { 1 } else { 2 })
+        .sum::<u64>();
+    entries.saturating_mul(constants::LOG_INDEX_TICKS_PER_ENTRY)
+}
+
+/// Ticks of hashing the encoding of a transaction or a receipt in the
+/// ordered trie computed at finalization, see [ethereum::util::ordered_trie_root].
+pub fn ticks_of_trie_leaf(encoding_size: u64) -> u64 {
+    encoding_size
+        .saturating_mul(constants::TRIE_LEAF_TICKS_COEF)
+        .saturating_add(constants::TRIE_LEAF_TICKS_INTERCEPT)
+}
+
 pub fn ticks_of_register(receipt_size: u64, obj_size: u64, bloom_size: u64) -> u64 {
     let receipt_ticks: u64 = receipt_size
         .saturating_mul(constants::RECEIPT_TICKS_COEF)
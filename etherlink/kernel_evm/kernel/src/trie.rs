@@ -0,0 +1,884 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! Merkle-Patricia tries persisted in the durable storage.
+//!
+//! The nodes of all the tries are stored under [TRIE_NODES_PATH], indexed by
+//! the hexadecimal encoding of their hash, so that the tries share their
+//! common nodes. Nodes whose encoding is shorter than 32 bytes are inlined in
+//! their parent, as in Ethereum.
+//!
+//! Each stored node counts its references, from the stored nodes and from
+//! the owners of the roots. Updating a trie replaces its root: the new root
+//! is referenced and the previous one released, and the nodes no longer
+//! referenced are deleted. Only the latest version of each trie is kept.
+//!
+//! The keys are always 32 bytes long (they are hashes of addresses or storage
+//! indexes), a branch node never holds a value.
+
+use crate::error::Error;
+use crate::storage::store_read_slice;
+use evm_execution::account_storage::EMPTY_TRIE_ROOT;
+use primitive_types::H256;
+use rlp::{DecoderError, Rlp, RlpStream};
+use std::collections::{BTreeMap, BTreeSet};
+use tezos_ethereum::proof::{compact_path, keccak, key_nibbles, path_of_compact};
+use tezos_smart_rollup_host::path::{concat, OwnedPath, RefPath};
+use tezos_smart_rollup_host::runtime::Runtime;
+
+pub const TRIE_NODES_PATH: RefPath = RefPath::assert_from(b"/world_state/nodes");
+
+/// Number of references to a node (u64 LE), under the path of the node.
+const NODE_REFERENCES: RefPath = RefPath::assert_from(b"/references");
+
+/// Reference to a child node: either its hash, or its encoding if it is
+/// shorter than 32 bytes.
+#[derive(Debug, Clone, PartialEq)]
+enum NodeRef {
+    Hash(H256),
+    Inline(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Leaf {
+        path: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Extension {
+        path: Vec<u8>,
+        child: NodeRef,
+    },
+    Branch {
+        children: Box<[Option<NodeRef>; 16]>,
+    },
+}
+
+fn inconsistent_key() -> Error {
+    Error::from(DecoderError::Custom("Inconsistent trie key length"))
+}
+
+fn append_ref(stream: &mut RlpStream, node_ref: &Option<NodeRef>) {
+    match node_ref {
+        None => {
+            stream.append_empty_data();
+        }
+        Some(NodeRef::Hash(hash)) => {
+            stream.append(hash);
+        }
+        Some(NodeRef::Inline(encoding)) => {
+            stream.append_raw(encoding, 1);
+        }
+    }
+}
+
+fn decode_ref(item: &Rlp<'_>) -> Result<Option<NodeRef>, DecoderError> {
+    if item.is_list() {
+        return Ok(Some(NodeRef::Inline(item.as_raw().to_vec())));
+    }
+    let data = item.data()?;
+    match data.len() {
+        0 => Ok(None),
+        32 => Ok(Some(NodeRef::Hash(H256::from_slice(data)))),
+        _ => Err(DecoderError::Custom("Invalid trie node reference")),
+    }
+}
+
+impl Node {
+    fn encode(&self) -> Vec<u8> {
+        let mut stream = RlpStream::new();
+        match self {
+            Node::Leaf { path, value } => {
+                stream.begin_list(2);
+                stream.append(&compact_path(path, true));
+                stream.append(value);
+            }
+            Node::Extension { path, child } => {
+                stream.begin_list(2);
+                stream.append(&compact_path(path, false));
+                append_ref(&mut stream, &Some(child.clone()));
+            }
+            Node::Branch { children } => {
+                stream.begin_list(17);
+                for child in children.iter() {
+                    append_ref(&mut stream, child);
+                }
+                stream.append_empty_data();
+            }
+        }
+        stream.out().to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, DecoderError> {
+        let decoder = Rlp::new(bytes);
+        match decoder.item_count()? {
+            2 => {
+                let (path, is_leaf) = path_of_compact(decoder.at(0)?.data()?)?;
+                if is_leaf {
+                    let value = decoder.at(1)?.data()?.to_vec();
+                    Ok(Node::Leaf { path, value })
+                } else {
+                    let child = decode_ref(&decoder.at(1)?)?
+                        .ok_or(DecoderError::Custom("Extension without child"))?;
+                    Ok(Node::Extension { path, child })
+                }
+            }
+            17 => {
+                let mut children: Box<[Option<NodeRef>; 16]> = Default::default();
+                for (i, child) in children.iter_mut().enumerate() {
+                    *child = decode_ref(&decoder.at(i)?)?;
+                }
+                Ok(Node::Branch { children })
+            }
+            _ => Err(DecoderError::RlpIncorrectListLen),
+        }
+    }
+}
+
+fn node_path(hash: &H256) -> Result<OwnedPath, Error> {
+    let path: Vec<u8> = format!("/{}", hex::encode(hash)).into();
+    let path = OwnedPath::try_from(path)?;
+    Ok(concat(&TRIE_NODES_PATH, &path)?)
+}
+
+/// Hashes of the children of `node` stored by hash.
+fn stored_children(node: &Node) -> Vec<H256> {
+    match node {
+        Node::Leaf { .. } => vec![],
+        Node::Extension { child, .. } => match child {
+            NodeRef::Hash(hash) => vec![*hash],
+            NodeRef::Inline(_) => vec![],
+        },
+        Node::Branch { children } => children
+            .iter()
+            .filter_map(|child| match child {
+                Some(NodeRef::Hash(hash)) => Some(*hash),
+                _ => None,
+            })
+            .collect(),
+    }
+}
+
+fn read_references<Host: Runtime>(host: &Host, path: &OwnedPath) -> Result<u64, Error> {
+    let mut buffer = [0u8; 8];
+    store_read_slice(host, &concat(path, &NODE_REFERENCES)?, &mut buffer, 8)?;
+    Ok(u64::from_le_bytes(buffer))
+}
+
+fn write_references<Host: Runtime>(
+    host: &mut Host,
+    path: &OwnedPath,
+    references: u64,
+) -> Result<(), Error> {
+    host.store_write_all(&concat(path, &NODE_REFERENCES)?, &references.to_le_bytes())?;
+    Ok(())
+}
+
+/// Changes of the references to the stored nodes during an update of a
+/// trie. They are applied once the update is done (see [References::apply]),
+/// so that the children shared by a new node and the node it replaces keep
+/// their number of references untouched.
+#[derive(Default)]
+struct References {
+    changes: BTreeMap<H256, i64>,
+    created: BTreeSet<H256>,
+    /// Children of the nodes loaded by the update, which are the ones it can
+    /// delete.
+    loaded: BTreeMap<H256, Vec<H256>>,
+}
+
+impl References {
+    fn change(&mut self, hash: H256, change: i64) {
+        *self.changes.entry(hash).or_insert(0) += change;
+    }
+
+    /// Applies the changes, the nodes left without reference being deleted
+    /// and their children released. The releases are applied first, so that
+    /// a child of a deleted node and of a new one is only written once.
+    fn apply<Host: Runtime>(mut self, host: &mut Host) -> Result<(), Error> {
+        let mut released: Vec<H256> = self
+            .changes
+            .iter()
+            .filter_map(|(hash, change)| (*change < 0).then_some(*hash))
+            .collect();
+        loop {
+            let Some(hash) = released
+                .pop()
+                .or_else(|| self.changes.keys().next().copied())
+            else {
+                return Ok(());
+            };
+            let Some(change) = self.changes.remove(&hash) else {
+                continue;
+            };
+            let created = self.created.remove(&hash);
+            if change == 0 && !created {
+                continue;
+            }
+            let path = node_path(&hash)?;
+            let previous = if created {
+                0
+            } else {
+                read_references(host, &path)?
+            };
+            match previous.checked_add_signed(change) {
+                Some(0) => {
+                    let children = match self.loaded.remove(&hash) {
+                        Some(children) => children,
+                        None => {
+                            stored_children(&Node::decode(&host.store_read_all(&path)?)?)
+                        }
+                    };
+                    host.store_delete(&path)?;
+                    for child in children {
+                        self.change(child, -1);
+                        released.push(child);
+                    }
+                }
+                Some(references) => write_references(host, &path, references)?,
+                None => {
+                    return Err(Error::from(DecoderError::Custom("Released trie node")))
+                }
+            }
+        }
+    }
+}
+
+/// Stores `node` by hash. A new node references its children.
+fn write_node<Host: Runtime>(
+    host: &mut Host,
+    references: &mut References,
+    node: &Node,
+) -> Result<H256, Error> {
+    let encoding = node.encode();
+    let hash = keccak(&encoding);
+    let path = node_path(&hash)?;
+    if !references.created.contains(&hash) && host.store_has(&path)?.is_none() {
+        host.store_write_all(&path, &encoding)?;
+        references.created.insert(hash);
+        references.change(hash, 0);
+        for child in stored_children(node) {
+            references.change(child, 1);
+        }
+    }
+    Ok(hash)
+}
+
+fn load<Host: Runtime>(
+    host: &Host,
+    references: &mut References,
+    node_ref: &NodeRef,
+) -> Result<Node, Error> {
+    match node_ref {
+        NodeRef::Hash(hash) => {
+            let encoding = host.store_read_all(&node_path(hash)?)?;
+            let node = Node::decode(&encoding)?;
+            references.loaded.insert(*hash, stored_children(&node));
+            Ok(node)
+        }
+        NodeRef::Inline(encoding) => Ok(Node::decode(encoding)?),
+    }
+}
+
+fn store<Host: Runtime>(
+    host: &mut Host,
+    references: &mut References,
+    node: &Node,
+) -> Result<NodeRef, Error> {
+    let encoding = node.encode();
+    if encoding.len() < 32 {
+        Ok(NodeRef::Inline(encoding))
+    } else {
+        Ok(NodeRef::Hash(write_node(host, references, node)?))
+    }
+}
+
+/// Prefixes `node` with the nibbles of `prefix`, merging them with its own
+/// path if any.
+fn prepend<Host: Runtime>(
+    host: &mut Host,
+    references: &mut References,
+    prefix: &[u8],
+    node: Node,
+) -> Result<Node, Error> {
+    match node {
+        Node::Leaf { path, value } => Ok(Node::Leaf {
+            path: [prefix, &path].concat(),
+            value,
+        }),
+        Node::Extension { path, child } => Ok(Node::Extension {
+            path: [prefix, &path].concat(),
+            child,
+        }),
+        branch @ Node::Branch { .. } if !prefix.is_empty() => Ok(Node::Extension {
+            path: prefix.to_vec(),
+            child: store(host, references, &branch)?,
+        }),
+        branch => Ok(branch),
+    }
+}
+
+fn common_prefix_length(left: &[u8], right: &[u8]) -> usize {
+    left.iter().zip(right).take_while(|(l, r)| l == r).count()
+}
+
+/// Branch where `node` is the child at the first nibble of `node_path`, and
+/// a leaf for `value` is the child at the first nibble of `path`. Both paths
+/// must differ on their first nibble.
+fn branch_with_leaf<Host: Runtime>(
+    host: &mut Host,
+    references: &mut References,
+    node_path: &[u8],
+    node: Option<NodeRef>,
+    path: &[u8],
+    value: Vec<u8>,
+) -> Result<Node, Error> {
+    let (node_nibble, path_nibble) = match (node_path.first(), path.first()) {
+        (Some(node_nibble), Some(path_nibble)) => (*node_nibble, *path_nibble),
+        _ => return Err(inconsistent_key()),
+    };
+    let mut children: Box<[Option<NodeRef>; 16]> = Default::default();
+    children[node_nibble as usize] = node;
+    let leaf = Node::Leaf {
+        path: path[1..].to_vec(),
+        value,
+    };
+    children[path_nibble as usize] = Some(store(host, references, &leaf)?);
+    Ok(Node::Branch { children })
+}
+
+fn insert_at<Host: Runtime>(
+    host: &mut Host,
+    references: &mut References,
+    node: Option<Node>,
+    path: &[u8],
+    value: Vec<u8>,
+) -> Result<Node, Error> {
+    match node {
+        None => Ok(Node::Leaf {
+            path: path.to_vec(),
+            value,
+        }),
+        Some(Node::Leaf {
+            path: leaf_path,
+            value: leaf_value,
+        }) => {
+            if leaf_path == path {
+                return Ok(Node::Leaf {
+                    path: leaf_path,
+                    value,
+                });
+            }
+            let common = common_prefix_length(&leaf_path, path);
+            let leaf_rest = leaf_path.get(common..).ok_or_else(inconsistent_key)?;
+            let leaf = Node::Leaf {
+                path: leaf_rest.get(1..).ok_or_else(inconsistent_key)?.to_vec(),
+                value: leaf_value,
+            };
+            let leaf = store(host, references, &leaf)?;
+            let branch = branch_with_leaf(
+                host,
+                references,
+                leaf_rest,
+                Some(leaf),
+                &path[common..],
+                value,
+            )?;
+            prepend(host, references, &path[..common], branch)
+        }
+        Some(Node::Extension {
+            path: extension_path,
+            child,
+        }) => {
+            let common = common_prefix_length(&extension_path, path);
+            if common == extension_path.len() {
+                let child_node = load(host, references, &child)?;
+                let child_node = insert_at(
+                    host,
+                    references,
+                    Some(child_node),
+                    &path[common..],
+                    value,
+                )?;
+                return Ok(Node::Extension {
+                    path: extension_path,
+                    child: store(host, references, &child_node)?,
+                });
+            }
+            let extension_rest = &extension_path[common..];
+            let child = if extension_rest.len() == 1 {
+                child
+            } else {
+                let extension = Node::Extension {
+                    path: extension_rest[1..].to_vec(),
+                    child,
+                };
+                store(host, references, &extension)?
+            };
+            let branch = branch_with_leaf(
+                host,
+                references,
+                extension_rest,
+                Some(child),
+                &path[common..],
+                value,
+            )?;
+            prepend(host, references, &path[..common], branch)
+        }
+        Some(Node::Branch { mut children }) => {
+            let (nibble, rest) = path.split_first().ok_or_else(inconsistent_key)?;
+            let child = match children[*nibble as usize].take() {
+                Some(child) => Some(load(host, references, &child)?),
+                None => None,
+            };
+            let child = insert_at(host, references, child, rest, value)?;
+            children[*nibble as usize] = Some(store(host, references, &child)?);
+            Ok(Node::Branch { children })
+        }
+    }
+}
+
+/// Removes the value at `path` below `node`. Returns `None` if there is no
+/// such value, otherwise the updated node, if any is left.
+fn remove_at<Host: Runtime>(
+    host: &mut Host,
+    references: &mut References,
+    node: Node,
+    path: &[u8],
+) -> Result<Option<Option<Node>>, Error> {
+    match node {
+        Node::Leaf {
+            path: leaf_path, ..
+        } => Ok(if leaf_path == path { Some(None) } else { None }),
+        Node::Extension {
+            path: extension_path,
+            child,
+        } => {
+            let Some(rest) = path.strip_prefix(extension_path.as_slice()) else {
+                return Ok(None);
+            };
+            let child = load(host, references, &child)?;
+            match remove_at(host, references, child, rest)? {
+                None => Ok(None),
+                Some(None) => Ok(Some(None)),
+                Some(Some(child)) => Ok(Some(Some(prepend(
+                    host,
+                    references,
+                    &extension_path,
+                    child,
+                )?))),
+            }
+        }
+        Node::Branch { mut children } => {
+            let (nibble, rest) = path.split_first().ok_or_else(inconsistent_key)?;
+            let Some(child) = children[*nibble as usize].take() else {
+                return Ok(None);
+            };
+            let child = load(host, references, &child)?;
+            match remove_at(host, references, child, rest)? {
+                None => Ok(None),
+                Some(Some(child)) => {
+                    children[*nibble as usize] = Some(store(host, references, &child)?);
+                    Ok(Some(Some(Node::Branch { children })))
+                }
+                Some(None) => {
+                    let mut remaining = children
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(i, child)| child.as_ref().map(|child| (i, child)));
+                    match (remaining.next(), remaining.next()) {
+                        (None, _) => Ok(Some(None)),
+                        // A branch with a single child is merged with it.
+                        (Some((i, child)), None) => {
+                            let child = load(host, references, child)?;
+                            Ok(Some(Some(prepend(host, references, &[i as u8], child)?)))
+                        }
+                        (Some(_), Some(_)) => Ok(Some(Some(Node::Branch { children }))),
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn load_root<Host: Runtime>(
+    host: &Host,
+    references: &mut References,
+    root: &H256,
+) -> Result<Option<Node>, Error> {
+    if *root == EMPTY_TRIE_ROOT {
+        Ok(None)
+    } else {
+        load(host, references, &NodeRef::Hash(*root)).map(Some)
+    }
+}
+
+/// The root node is always stored by hash, even if its encoding is shorter
+/// than 32 bytes.
+fn store_root<Host: Runtime>(
+    host: &mut Host,
+    references: &mut References,
+    node: Option<Node>,
+) -> Result<H256, Error> {
+    match node {
+        None => Ok(EMPTY_TRIE_ROOT),
+        Some(node) => write_node(host, references, &node),
+    }
+}
+
+/// Replaces the trie of root `previous` by the one of root `root`.
+fn replace_root<Host: Runtime>(
+    host: &mut Host,
+    mut references: References,
+    previous: &H256,
+    root: H256,
+) -> Result<H256, Error> {
+    if root != EMPTY_TRIE_ROOT {
+        references.change(root, 1);
+    }
+    if *previous != EMPTY_TRIE_ROOT {
+        references.change(*previous, -1);
+    }
+    references.apply(host)?;
+    Ok(root)
+}
+
+/// Sets `value` at `key` in the trie of root `root`, and returns the root of
+/// the updated trie, which replaces it.
+pub fn insert<Host: Runtime>(
+    host: &mut Host,
+    root: &H256,
+    key: &H256,
+    value: Vec<u8>,
+) -> Result<H256, Error> {
+    let mut references = References::default();
+    let node = load_root(host, &mut references, root)?;
+    let node = insert_at(host, &mut references, node, &key_nibbles(key), value)?;
+    let updated = store_root(host, &mut references, Some(node))?;
+    replace_root(host, references, root, updated)
+}
+
+/// Value at a key, if any, along with the encodings of the nodes on the path
+/// from the root to the key, see [tezos_ethereum::proof::verify_proof].
+pub type ValueWithProof = (Option<Vec<u8>>, Vec<Vec<u8>>);
+
+/// Returns the value at `key` in the trie of root `root` and its proof.
+pub fn proof<Host: Runtime>(
+    host: &Host,
+    root: &H256,
+    key: &H256,
+) -> Result<ValueWithProof, Error> {
+    let mut nodes = Vec::new();
+    if *root == EMPTY_TRIE_ROOT {
+        return Ok((None, nodes));
+    }
+    let nibbles = key_nibbles(key);
+    let mut path = nibbles.as_slice();
+    let mut node_ref = NodeRef::Hash(*root);
+    loop {
+        let node = match node_ref {
+            NodeRef::Hash(hash) => {
+                let encoding = host.store_read_all(&node_path(&hash)?)?;
+                let node = Node::decode(&encoding)?;
+                nodes.push(encoding);
+                node
+            }
+            NodeRef::Inline(encoding) => Node::decode(&encoding)?,
+        };
+        match node {
+            Node::Leaf {
+                path: leaf_path,
+                value,
+            } => {
+                let value = (leaf_path == path).then_some(value);
+                return Ok((value, nodes));
+            }
+            Node::Extension {
+                path: extension_path,
+                child,
+            } => match path.strip_prefix(extension_path.as_slice()) {
+                Some(rest) => {
+                    path = rest;
+                    node_ref = child;
+                }
+                None => return Ok((None, nodes)),
+            },
+            Node::Branch { mut children } => {
+                let (nibble, rest) = path.split_first().ok_or_else(inconsistent_key)?;
+                match children[*nibble as usize].take() {
+                    Some(child) => {
+                        path = rest;
+                        node_ref = child;
+                    }
+                    None => return Ok((None, nodes)),
+                }
+            }
+        }
+    }
+}
+
+/// Removes the value at `key` in the trie of root `root`, and returns the root
+/// of the updated trie, which replaces it.
+pub fn remove<Host: Runtime>(
+    host: &mut Host,
+    root: &H256,
+    key: &H256,
+) -> Result<H256, Error> {
+    let mut references = References::default();
+    match load_root(host, &mut references, root)? {
+        None => Ok(*root),
+        Some(node) => match remove_at(host, &mut references, node, &key_nibbles(key))? {
+            None => Ok(*root),
+            Some(node) => {
+                let updated = store_root(host, &mut references, node)?;
+                replace_root(host, references, root, updated)
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use tezos_smart_rollup_mock::MockHost;
+
+    // The values are inserted at the hash of their key, which is how
+    // `sec_trie_root` computes the root of the reference implementation.
+    fn reference_root(entries: &BTreeMap<Vec<u8>, Vec<u8>>) -> H256 {
+        ethereum::util::sec_trie_root(entries)
+    }
+
+    #[test]
+    fn test_empty_trie() {
+        let mut host = MockHost::default();
+        let root = remove(&mut host, &EMPTY_TRIE_ROOT, &H256::zero()).unwrap();
+        assert_eq!(root, EMPTY_TRIE_ROOT);
+        assert_eq!(reference_root(&BTreeMap::new()), EMPTY_TRIE_ROOT)
+    }
+
+    #[test]
+    fn test_single_value_root() {
+        let mut host = MockHost::default();
+        let mut entries = BTreeMap::new();
+        entries.insert(b"key".to_vec(), b"value".to_vec());
+        let root = insert(
+            &mut host,
+            &EMPTY_TRIE_ROOT,
+            &keccak(b"key"),
+            b"value".to_vec(),
+        )
+        .unwrap();
+        assert_eq!(root, reference_root(&entries));
+        let root = remove(&mut host, &root, &keccak(b"key")).unwrap();
+        assert_eq!(root, EMPTY_TRIE_ROOT);
+    }
+
+    #[test]
+    fn test_roots_match_reference_implementation() {
+        let mut host = MockHost::default();
+        let mut entries = BTreeMap::new();
+        let mut root = EMPTY_TRIE_ROOT;
+
+        // Insertions, including values short enough to be inlined, and
+        // updates of existing keys.
+        for i in 0u32..300 {
+            let key = (i % 200).to_be_bytes().to_vec();
+            let value = if i % 3 == 0 {
+                vec![(i % 250) as u8 + 1]
+            } else {
+                keccak(&i.to_le_bytes()).as_bytes().to_vec()
+            };
+            root = insert(&mut host, &root, &keccak(&key), value.clone()).unwrap();
+            entries.insert(key, value);
+            if i % 25 == 0 {
+                assert_eq!(root, reference_root(&entries));
+            }
+        }
+        assert_eq!(root, reference_root(&entries));
+
+        // Removals, including keys that are not in the trie.
+        for i in (0u32..250).step_by(2) {
+            let key = i.to_be_bytes().to_vec();
+            root = remove(&mut host, &root, &keccak(&key)).unwrap();
+            entries.remove(&key);
+            if i % 50 == 0 {
+                assert_eq!(root, reference_root(&entries));
+            }
+        }
+        assert_eq!(root, reference_root(&entries));
+
+        for i in (1u32..200).step_by(2) {
+            let key = i.to_be_bytes().to_vec();
+            root = remove(&mut host, &root, &keccak(&key)).unwrap();
+        }
+        assert_eq!(root, EMPTY_TRIE_ROOT);
+    }
+
+    #[test]
+    fn test_inlined_nodes_match_reference_implementation() {
+        let mut host = MockHost::default();
+        let mut entries = BTreeMap::new();
+        let mut root = EMPTY_TRIE_ROOT;
+
+        // Keys sharing long prefixes give leaves and branches whose encoding
+        // is shorter than 32 bytes.
+        let keys: Vec<H256> = [0x00, 0x01, 0x10, 0x11, 0x1f, 0xf0]
+            .iter()
+            .map(|last| {
+                let mut key = [0xab; 32];
+                key[31] = *last;
+                H256(key)
+            })
+            .collect();
+        for (i, key) in keys.iter().enumerate() {
+            root = insert(&mut host, &root, key, vec![i as u8 + 1]).unwrap();
+            entries.insert(key.as_bytes().to_vec(), vec![i as u8 + 1]);
+            assert_eq!(root, ethereum::util::trie_root(&entries));
+        }
+        for key in keys.iter().rev() {
+            root = remove(&mut host, &root, key).unwrap();
+            entries.remove(key.as_bytes());
+            assert_eq!(root, ethereum::util::trie_root(&entries));
+        }
+        assert_eq!(root, EMPTY_TRIE_ROOT);
+    }
+
+    // Vectors of the `secureTrieTest` suite of ethereum/tests, the roots are
+    // the expected ones of the suite and don't depend on an implementation.
+    #[test]
+    fn test_roots_of_ethereum_tests_vectors() {
+        // Insertions of a value, or removals of the key
+        type Operation<'a> = (&'a [u8], Option<&'a [u8]>);
+        let vectors: [(&[Operation], &str); 2] = [
+            (
+                &[
+                    (b"do", Some(b"verb")),
+                    (b"ether", Some(b"wookiedoo")),
+                    (b"horse", Some(b"stallion")),
+                    (b"shaman", Some(b"horse")),
+                    (b"doge", Some(b"coin")),
+                    (b"ether", None),
+                    (b"dog", Some(b"puppy")),
+                    (b"shaman", None),
+                ],
+                "29b235a58c3c25ab83010c327d5932bcf05324b7d6b1185e650798034783ca9d",
+            ),
+            (
+                &[
+                    (b"doe", Some(b"reindeer")),
+                    (b"dog", Some(b"puppy")),
+                    (b"dogglesworth", Some(b"cat")),
+                ],
+                "d4cd937e4a4368d7931a9cf51686b7e10abb3dce38a39000fd7902a092b64585",
+            ),
+        ];
+        for (operations, expected) in vectors {
+            let mut host = MockHost::default();
+            let mut root = EMPTY_TRIE_ROOT;
+            for (key, value) in operations {
+                root = match value {
+                    Some(value) => {
+                        insert(&mut host, &root, &keccak(key), value.to_vec()).unwrap()
+                    }
+                    None => remove(&mut host, &root, &keccak(key)).unwrap(),
+                }
+            }
+            assert_eq!(hex::encode(root), expected)
+        }
+    }
+
+    fn stored_nodes(host: &MockHost) -> u64 {
+        match host.store_has(&TRIE_NODES_PATH).unwrap() {
+            None => 0,
+            Some(_) => host.store_count_subkeys(&TRIE_NODES_PATH).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_replaced_nodes_are_deleted() {
+        let mut host = MockHost::default();
+        let mut root = EMPTY_TRIE_ROOT;
+        let keys: Vec<H256> = (0u32..100).map(|i| keccak(&i.to_be_bytes())).collect();
+        for (i, key) in keys.iter().enumerate() {
+            root = insert(&mut host, &root, key, keccak(&[i as u8]).0.to_vec()).unwrap();
+        }
+        // Updates of existing keys
+        for key in keys.iter().step_by(3) {
+            root = insert(&mut host, &root, key, b"updated value".repeat(3)).unwrap();
+        }
+
+        // The same trie built in a single version uses as many nodes.
+        let mut fresh_host = MockHost::default();
+        let mut fresh_root = EMPTY_TRIE_ROOT;
+        for (i, key) in keys.iter().enumerate() {
+            let value = if i % 3 == 0 {
+                b"updated value".repeat(3)
+            } else {
+                keccak(&[i as u8]).0.to_vec()
+            };
+            fresh_root = insert(&mut fresh_host, &fresh_root, key, value).unwrap();
+        }
+        assert_eq!(root, fresh_root);
+        assert_eq!(stored_nodes(&host), stored_nodes(&fresh_host));
+
+        // Two tries sharing their nodes
+        let other_root =
+            insert(&mut host, &EMPTY_TRIE_ROOT, &keys[0], b"value".repeat(10)).unwrap();
+        let mut shared_root = other_root;
+        for key in &keys[1..3] {
+            shared_root =
+                insert(&mut host, &shared_root, key, b"value".repeat(10)).unwrap();
+        }
+        let _ =
+            insert(&mut host, &EMPTY_TRIE_ROOT, &keys[0], b"value".repeat(10)).unwrap();
+        for key in &keys[1..3] {
+            shared_root = remove(&mut host, &shared_root, key).unwrap();
+        }
+        assert_eq!(shared_root, other_root);
+        assert!(proof(&host, &other_root, &keys[0]).unwrap().0.is_some());
+        let _ = remove(&mut host, &shared_root, &keys[0]).unwrap();
+        assert!(proof(&host, &other_root, &keys[0]).unwrap().0.is_some());
+        let _ = remove(&mut host, &other_root, &keys[0]).unwrap();
+
+        for key in &keys {
+            root = remove(&mut host, &root, key).unwrap();
+        }
+        assert_eq!(root, EMPTY_TRIE_ROOT);
+        assert_eq!(stored_nodes(&host), 0);
+    }
+
+    #[test]
+    fn test_proofs_are_verified() {
+        let mut host = MockHost::default();
+        let mut root = EMPTY_TRIE_ROOT;
+        let mut keys: Vec<H256> = (0u32..50).map(|i| keccak(&i.to_be_bytes())).collect();
+        // Keys giving inlined nodes
+        keys.extend([0x00, 0x01, 0x10].iter().map(|last| {
+            let mut key = [0xab; 32];
+            key[31] = *last;
+            H256(key)
+        }));
+        for (i, key) in keys.iter().enumerate() {
+            root = insert(&mut host, &root, key, vec![i as u8 + 1]).unwrap();
+        }
+
+        for (i, key) in keys.iter().enumerate() {
+            let (value, nodes) = proof(&host, &root, key).unwrap();
+            assert_eq!(value, Some(vec![i as u8 + 1]));
+            assert_eq!(
+                tezos_ethereum::proof::verify_proof(&root, key, &nodes),
+                Ok(value)
+            );
+        }
+        for absent in [keccak(b"absent"), H256([0xab; 32])] {
+            let (value, nodes) = proof(&host, &root, &absent).unwrap();
+            assert_eq!(value, None);
+            assert_eq!(
+                tezos_ethereum::proof::verify_proof(&root, &absent, &nodes),
+                Ok(None)
+            );
+        }
+    }
+}
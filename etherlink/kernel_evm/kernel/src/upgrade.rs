@@ -4,28 +4,92 @@
 //
 // SPDX-License-Identifier: MIT
 
+//! Kernel upgrades.
+//!
+//! The administrator proposes an upgrade, which is scheduled once a
+//! threshold of approvals is reached. The approvers are the contracts and
+//! keys of the upgrade governance, and the proposal of the administrator
+//! counts as one approval if the administrator is one of them. A scheduled
+//! upgrade is applied by the first
+//! block after its activation timestamp and level, which gives a notice
+//! period to the users. The administrator can cancel an upgrade until it
+//! is applied.
+//!
+//! Each proposal gets an id, one more than the id of the previous proposal,
+//! and the approvals are for a proposal: its id, preimage hash and
+//! activation. An approval can't be replayed for a later proposal, even one
+//! of the same kernel, nor for a proposal whose activation differs from the
+//! approved one.
+//!
+//! The governance is stored under `/upgrade_governance` by the installer
+//! configuration at the origination of the rollup, and the administrator
+//! updates it with a message. The approvals of the current proposal by
+//! approvers removed from the governance no longer count.
+
 use crate::error::UpgradeProcessError;
-use crate::storage::read_optional_rlp;
+use crate::storage::{read_l1_level, read_optional_rlp, store_rlp};
 use anyhow::Context;
 use rlp::Decodable;
 use rlp::DecoderError;
 use rlp::Encodable;
+use rlp::Rlp;
+use rlp::RlpStream;
+use tezos_crypto_rs::hash::ContractKt1Hash;
+use tezos_crypto_rs::hash::Signature;
+use tezos_crypto_rs::PublicKeySignatureVerifier;
 use tezos_ethereum::rlp_helpers::append_timestamp;
 use tezos_ethereum::rlp_helpers::decode_field;
 use tezos_ethereum::rlp_helpers::decode_timestamp;
 use tezos_ethereum::rlp_helpers::next;
 use tezos_evm_logging::{log, Level::*};
 use tezos_smart_rollup_core::PREIMAGE_HASH_SIZE;
+use tezos_smart_rollup_encoding::public_key::PublicKey;
 use tezos_smart_rollup_encoding::timestamp::Timestamp;
 use tezos_smart_rollup_host::path::OwnedPath;
 use tezos_smart_rollup_host::path::RefPath;
 use tezos_smart_rollup_host::runtime::Runtime;
 use tezos_smart_rollup_installer_config::binary::promote::upgrade_reveal_flow;
 
+/// Encoding: `[preimage_hash, activation_timestamp]`, followed by the
+/// activation level if any.
 #[derive(Debug, PartialEq, Clone)]
 pub struct KernelUpgrade {
     pub preimage_hash: [u8; PREIMAGE_HASH_SIZE],
     pub activation_timestamp: Timestamp,
+    /// L1 level from which the upgrade can be applied.
+    pub activation_level: Option<u32>,
+}
+
+impl KernelUpgrade {
+    /// Returns true if a block with the given timestamp applies the upgrade.
+    pub fn is_active<Host: Runtime>(
+        &self,
+        host: &Host,
+        timestamp: Timestamp,
+    ) -> anyhow::Result<bool> {
+        if timestamp < self.activation_timestamp {
+            return Ok(false);
+        }
+        match self.activation_level {
+            Some(level) => Ok(read_l1_level(host)? >= level),
+            None => Ok(true),
+        }
+    }
+}
+
+impl std::fmt::Display for KernelUpgrade {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at {}",
+            hex::encode(self.preimage_hash),
+            self.activation_timestamp
+        )?;
+        if let Some(level) = self.activation_level {
+            write!(f, " and level {}", level)?;
+        }
+        Ok(())
+    }
 }
 
 impl Decodable for KernelUpgrade {
@@ -33,45 +97,460 @@ impl Decodable for KernelUpgrade {
         if !decoder.is_list() {
             return Err(DecoderError::RlpExpectedToBeList);
         }
-        if decoder.item_count()? != 2 {
+        let item_count = decoder.item_count()?;
+        if item_count != 2 && item_count != 3 {
             return Err(DecoderError::RlpIncorrectListLen);
         }
 
         let mut it = decoder.iter();
-        let preimage_hash: Vec<u8> = decode_field(&next(&mut it)?, "preimage_hash")?;
-        let preimage_hash: [u8; PREIMAGE_HASH_SIZE] = preimage_hash
-            .try_into()
-            .map_err(|_| DecoderError::RlpInvalidLength)?;
+        let preimage_hash = decode_preimage_hash(&next(&mut it)?)?;
         let activation_timestamp = decode_timestamp(&next(&mut it)?)?;
+        let activation_level = if item_count == 3 {
+            Some(decode_field(&next(&mut it)?, "activation_level")?)
+        } else {
+            None
+        };
 
         Ok(Self {
             preimage_hash,
             activation_timestamp,
+            activation_level,
         })
     }
 }
 
 impl Encodable for KernelUpgrade {
     fn rlp_append(&self, stream: &mut rlp::RlpStream) {
+        match self.activation_level {
+            Some(level) => {
+                stream.begin_list(3);
+                stream.append_iter(self.preimage_hash);
+                append_timestamp(stream, self.activation_timestamp);
+                stream.append(&level);
+            }
+            None => {
+                stream.begin_list(2);
+                stream.append_iter(self.preimage_hash);
+                append_timestamp(stream, self.activation_timestamp);
+            }
+        }
+    }
+}
+
+/// A kernel upgrade proposed by the administrator, what the governance
+/// approves.
+///
+/// Encoding: `[id, upgrade]`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct UpgradeProposal {
+    pub id: u64,
+    pub upgrade: KernelUpgrade,
+}
+
+impl std::fmt::Display for UpgradeProposal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "proposal {} of {}", self.id, self.upgrade)
+    }
+}
+
+impl Encodable for UpgradeProposal {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(2);
+        stream.append(&self.id);
+        stream.append(&self.upgrade);
+    }
+}
+
+impl Decodable for UpgradeProposal {
+    fn decode(decoder: &Rlp) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let id = decode_field(&next(&mut it)?, "id")?;
+        let upgrade = decode_field(&next(&mut it)?, "upgrade")?;
+        Ok(Self { id, upgrade })
+    }
+}
+
+fn decode_preimage_hash(decoder: &Rlp) -> Result<[u8; PREIMAGE_HASH_SIZE], DecoderError> {
+    let preimage_hash: Vec<u8> = decode_field(decoder, "preimage_hash")?;
+    preimage_hash
+        .try_into()
+        .map_err(|_| DecoderError::RlpInvalidLength)
+}
+
+fn decode_b58<T>(
+    decoder: &Rlp,
+    from_b58: impl Fn(&str) -> Option<T>,
+) -> Result<T, DecoderError> {
+    let bytes: Vec<u8> = decoder.as_val()?;
+    std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(from_b58)
+        .ok_or(DecoderError::Custom("Invalid base58 encoding"))
+}
+
+fn decode_contract(decoder: &Rlp) -> Result<ContractKt1Hash, DecoderError> {
+    decode_b58(decoder, |s| ContractKt1Hash::from_base58_check(s).ok())
+}
+
+fn decode_public_key(decoder: &Rlp) -> Result<PublicKey, DecoderError> {
+    decode_b58(decoder, |s| PublicKey::from_b58check(s).ok())
+}
+
+/// A contract or a key allowed to approve kernel upgrades.
+///
+/// Encoding: `[tag, base58 encoding]`, the tag is 0 for a contract and 1 for
+/// a key.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Approver {
+    Contract(ContractKt1Hash),
+    Key(PublicKey),
+}
+
+const APPROVER_CONTRACT_TAG: u8 = 0;
+const APPROVER_KEY_TAG: u8 = 1;
+
+impl Encodable for Approver {
+    fn rlp_append(&self, stream: &mut RlpStream) {
         stream.begin_list(2);
-        stream.append_iter(self.preimage_hash);
-        append_timestamp(stream, self.activation_timestamp);
+        match self {
+            Approver::Contract(contract) => {
+                stream.append(&APPROVER_CONTRACT_TAG);
+                stream.append(&contract.to_base58_check().as_bytes());
+            }
+            Approver::Key(key) => {
+                stream.append(&APPROVER_KEY_TAG);
+                stream.append(&key.to_b58check().as_bytes());
+            }
+        }
+    }
+}
+
+impl Decodable for Approver {
+    fn decode(decoder: &Rlp) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let tag: u8 = decode_field(&next(&mut it)?, "tag")?;
+        let approver = next(&mut it)?;
+        match tag {
+            APPROVER_CONTRACT_TAG => Ok(Approver::Contract(decode_contract(&approver)?)),
+            APPROVER_KEY_TAG => Ok(Approver::Key(decode_public_key(&approver)?)),
+            _ => Err(DecoderError::Custom("Unknown approver tag")),
+        }
+    }
+}
+
+/// Contracts and keys allowed to approve the upgrades proposed by the
+/// administrator, `threshold` approvals are needed to schedule an upgrade.
+/// Without governance, the threshold is 0 and the proposal of the
+/// administrator is enough.
+///
+/// Encoding: `[contracts, keys, threshold]`, where the contracts and keys are
+/// base58 encoded.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct UpgradeGovernance {
+    pub contracts: Vec<ContractKt1Hash>,
+    pub keys: Vec<PublicKey>,
+    pub threshold: u8,
+}
+
+impl UpgradeGovernance {
+    pub fn is_approver(&self, approver: &Approver) -> bool {
+        match approver {
+            Approver::Contract(contract) => self.contracts.contains(contract),
+            Approver::Key(key) => self.keys.contains(key),
+        }
+    }
+
+    /// The threshold must be reachable, and each approver counts once.
+    pub fn is_valid(&self) -> bool {
+        let distinct_contracts = self
+            .contracts
+            .iter()
+            .enumerate()
+            .all(|(i, contract)| !self.contracts[..i].contains(contract));
+        let distinct_keys = self
+            .keys
+            .iter()
+            .enumerate()
+            .all(|(i, key)| !self.keys[..i].contains(key));
+        usize::from(self.threshold) <= self.contracts.len() + self.keys.len()
+            && distinct_contracts
+            && distinct_keys
+    }
+}
+
+impl Encodable for UpgradeGovernance {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(3);
+        stream.begin_list(self.contracts.len());
+        for contract in &self.contracts {
+            stream.append(&contract.to_base58_check().as_bytes());
+        }
+        stream.begin_list(self.keys.len());
+        for key in &self.keys {
+            stream.append(&key.to_b58check().as_bytes());
+        }
+        stream.append(&self.threshold);
+    }
+}
+
+impl Decodable for UpgradeGovernance {
+    fn decode(decoder: &Rlp) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let contracts = next(&mut it)?
+            .iter()
+            .map(|contract| decode_contract(&contract))
+            .collect::<Result<_, _>>()?;
+        let keys = next(&mut it)?
+            .iter()
+            .map(|key| decode_public_key(&key))
+            .collect::<Result<_, _>>()?;
+        let threshold = decode_field(&next(&mut it)?, "threshold")?;
+        Ok(Self {
+            contracts,
+            keys,
+            threshold,
+        })
+    }
+}
+
+/// Message of a governance contract approving a proposal, or of the
+/// administrator cancelling an upgrade or updating the governance.
+///
+/// Encoding: `[0, proposal]` for an approval, `[1, preimage_hash]` for a
+/// cancellation and `[2, governance]` for an update of the governance.
+#[derive(Debug, PartialEq, Clone)]
+pub enum UpgradeDecision {
+    Approve(UpgradeProposal),
+    Cancel([u8; PREIMAGE_HASH_SIZE]),
+    UpdateGovernance(UpgradeGovernance),
+}
+
+const UPGRADE_APPROVE_TAG: u8 = 0;
+const UPGRADE_CANCEL_TAG: u8 = 1;
+const UPGRADE_GOVERNANCE_TAG: u8 = 2;
+
+impl Encodable for UpgradeDecision {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(2);
+        match self {
+            UpgradeDecision::Approve(proposal) => {
+                stream.append(&UPGRADE_APPROVE_TAG);
+                stream.append(proposal);
+            }
+            UpgradeDecision::Cancel(preimage_hash) => {
+                stream.append(&UPGRADE_CANCEL_TAG);
+                stream.append_iter(*preimage_hash);
+            }
+            UpgradeDecision::UpdateGovernance(governance) => {
+                stream.append(&UPGRADE_GOVERNANCE_TAG);
+                stream.append(governance);
+            }
+        }
+    }
+}
+
+impl Decodable for UpgradeDecision {
+    fn decode(decoder: &Rlp) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 2 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let tag: u8 = decode_field(&next(&mut it)?, "tag")?;
+        let decision = next(&mut it)?;
+        match tag {
+            UPGRADE_APPROVE_TAG => Ok(UpgradeDecision::Approve(decode_field(
+                &decision, "proposal",
+            )?)),
+            UPGRADE_CANCEL_TAG => {
+                Ok(UpgradeDecision::Cancel(decode_preimage_hash(&decision)?))
+            }
+            UPGRADE_GOVERNANCE_TAG => Ok(UpgradeDecision::UpdateGovernance(
+                decode_field(&decision, "governance")?,
+            )),
+            _ => Err(DecoderError::Custom("Unknown upgrade decision tag")),
+        }
+    }
+}
+
+/// Approval of a proposal signed by a key of the governance, sent as an
+/// external message. The key signs the hash of the encoding of the proposal
+/// followed by the address of the rollup, see [approval_digest].
+///
+/// Encoding: `[proposal, public_key, signature]`, where the public key is
+/// base58 encoded.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SignedUpgradeApproval {
+    pub proposal: UpgradeProposal,
+    pub public_key: PublicKey,
+    pub signature: Signature,
+}
+
+/// Message signed by the keys approving `proposal`. It covers the id of the
+/// proposal and the activation of the upgrade, so that the approval can't be
+/// replayed for another proposal of the same kernel.
+pub fn approval_digest(
+    proposal: &UpgradeProposal,
+    smart_rollup_address: &[u8],
+) -> Vec<u8> {
+    let mut bytes = proposal.rlp_bytes().to_vec();
+    bytes.extend_from_slice(smart_rollup_address);
+    // Hashing to 32 bytes can't fail.
+    tezos_crypto_rs::blake2b::digest_256(&bytes).unwrap()
+}
+
+impl SignedUpgradeApproval {
+    /// Returns the approver if the signature is valid.
+    pub fn approver(&self, smart_rollup_address: &[u8]) -> Option<Approver> {
+        let msg = approval_digest(&self.proposal, smart_rollup_address);
+        self.public_key
+            .verify_signature(&self.signature, &msg)
+            .unwrap_or(false)
+            .then(|| Approver::Key(self.public_key.clone()))
+    }
+}
+
+impl Encodable for SignedUpgradeApproval {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(3);
+        stream.append(&self.proposal);
+        stream.append(&self.public_key.to_b58check().as_bytes());
+        stream.append(self.signature.as_ref());
+    }
+}
+
+impl Decodable for SignedUpgradeApproval {
+    fn decode(decoder: &Rlp) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let proposal = decode_field(&next(&mut it)?, "proposal")?;
+        let public_key = decode_public_key(&next(&mut it)?)?;
+        let signature: Vec<u8> = decode_field(&next(&mut it)?, "signature")?;
+        let signature = Signature::try_from(signature.as_slice())
+            .map_err(|_| DecoderError::Custom("Invalid signature"))?;
+        Ok(Self {
+            proposal,
+            public_key,
+            signature,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UpgradeStatus {
+    /// Waiting for approvals.
+    Proposed,
+    /// Approved, the upgrade is applied after its activation.
+    Scheduled,
+    Cancelled,
+    Applied,
+}
+
+impl UpgradeStatus {
+    fn tag(&self) -> u8 {
+        match self {
+            UpgradeStatus::Proposed => 0,
+            UpgradeStatus::Scheduled => 1,
+            UpgradeStatus::Cancelled => 2,
+            UpgradeStatus::Applied => 3,
+        }
+    }
+}
+
+impl Encodable for UpgradeStatus {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.append(&self.tag());
+    }
+}
+
+impl Decodable for UpgradeStatus {
+    fn decode(decoder: &Rlp) -> Result<Self, DecoderError> {
+        match decoder.as_val::<u8>()? {
+            0 => Ok(UpgradeStatus::Proposed),
+            1 => Ok(UpgradeStatus::Scheduled),
+            2 => Ok(UpgradeStatus::Cancelled),
+            3 => Ok(UpgradeStatus::Applied),
+            _ => Err(DecoderError::Custom("Unknown upgrade status")),
+        }
+    }
+}
+
+/// Status of the last upgrade proposed by the administrator, stored under
+/// `/kernel_upgrade_status` for the nodes.
+///
+/// Encoding: `[status, proposal, approvals]`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct KernelUpgradeStatus {
+    pub status: UpgradeStatus,
+    pub proposal: UpgradeProposal,
+    pub approvals: Vec<Approver>,
+}
+
+impl Encodable for KernelUpgradeStatus {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(3);
+        stream.append(&self.status);
+        stream.append(&self.proposal);
+        stream.append_list(&self.approvals);
+    }
+}
+
+impl Decodable for KernelUpgradeStatus {
+    fn decode(decoder: &Rlp) -> Result<Self, DecoderError> {
+        if !decoder.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if decoder.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut it = decoder.iter();
+        let status = decode_field(&next(&mut it)?, "status")?;
+        let proposal = decode_field(&next(&mut it)?, "proposal")?;
+        let approvals = next(&mut it)?.as_list()?;
+        Ok(Self {
+            status,
+            proposal,
+            approvals,
+        })
     }
 }
 
 const KERNEL_UPGRADE: RefPath = RefPath::assert_from(b"/kernel_upgrade");
 
+const KERNEL_UPGRADE_STATUS: RefPath = RefPath::assert_from(b"/kernel_upgrade_status");
+
+const UPGRADE_GOVERNANCE: RefPath = RefPath::assert_from(b"/upgrade_governance");
+
 pub fn store_kernel_upgrade<Host: Runtime>(
     host: &mut Host,
     kernel_upgrade: &KernelUpgrade,
 ) -> Result<(), anyhow::Error> {
-    log!(
-        host,
-        Info,
-        "An upgrade to {} is planned for {}",
-        hex::encode(kernel_upgrade.preimage_hash),
-        kernel_upgrade.activation_timestamp
-    );
+    log!(host, Info, "An upgrade to {} is planned", kernel_upgrade);
     let path = OwnedPath::from(KERNEL_UPGRADE);
     let bytes = &kernel_upgrade.rlp_bytes();
     host.store_write_all(&path, bytes)
@@ -90,6 +569,174 @@ fn delete_kernel_upgrade<Host: Runtime>(host: &mut Host) -> anyhow::Result<()> {
         .context("Failed to delete kernel upgrade")
 }
 
+pub fn read_kernel_upgrade_status<Host: Runtime>(
+    host: &Host,
+) -> anyhow::Result<Option<KernelUpgradeStatus>> {
+    read_optional_rlp(host, &KERNEL_UPGRADE_STATUS)
+        .context("Failed to decode kernel upgrade status")
+}
+
+fn store_kernel_upgrade_status<Host: Runtime>(
+    host: &mut Host,
+    status: &KernelUpgradeStatus,
+) -> anyhow::Result<()> {
+    store_rlp(status, host, &KERNEL_UPGRADE_STATUS)
+        .context("Failed to store kernel upgrade status")
+}
+
+pub fn read_upgrade_governance<Host: Runtime>(
+    host: &Host,
+) -> anyhow::Result<UpgradeGovernance> {
+    let governance = read_optional_rlp(host, &UPGRADE_GOVERNANCE)
+        .context("Failed to decode upgrade governance")?;
+    Ok(governance.unwrap_or_default())
+}
+
+/// Schedules the upgrade if it has enough approvals, and stores its status.
+fn schedule_if_approved<Host: Runtime>(
+    host: &mut Host,
+    mut status: KernelUpgradeStatus,
+) -> anyhow::Result<()> {
+    let governance = read_upgrade_governance(host)?;
+    if status.approvals.len() >= usize::from(governance.threshold) {
+        store_kernel_upgrade(host, &status.proposal.upgrade)?;
+        status.status = UpgradeStatus::Scheduled;
+    } else {
+        log!(
+            host,
+            Info,
+            "The {} has {} of the {} approvals needed",
+            status.proposal,
+            status.approvals.len(),
+            governance.threshold
+        );
+    }
+    store_kernel_upgrade_status(host, &status)
+}
+
+/// Updates the governance. The approvals of the current proposal by
+/// approvers no longer part of the governance are removed, and the proposal
+/// is scheduled if it has enough approvals for the new threshold.
+pub fn update_upgrade_governance<Host: Runtime>(
+    host: &mut Host,
+    governance: UpgradeGovernance,
+) -> anyhow::Result<()> {
+    log!(host, Info, "The upgrade governance is updated");
+    store_rlp(&governance, host, &UPGRADE_GOVERNANCE)
+        .context("Failed to store upgrade governance")?;
+    match read_kernel_upgrade_status(host)? {
+        Some(mut status) if status.status == UpgradeStatus::Proposed => {
+            status
+                .approvals
+                .retain(|approver| governance.is_approver(approver));
+            schedule_if_approved(host, status)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Proposes an upgrade, approved by its proposer if it is part of the
+/// governance. It replaces the upgrade proposed or scheduled previously, if
+/// any, and its id is one more than the id of the previous proposal.
+pub fn propose_kernel_upgrade<Host: Runtime>(
+    host: &mut Host,
+    upgrade: KernelUpgrade,
+    proposer: Approver,
+) -> anyhow::Result<()> {
+    if read_kernel_upgrade(host)?.is_some() {
+        delete_kernel_upgrade(host)?;
+    }
+    let id = match read_kernel_upgrade_status(host)? {
+        Some(previous) => previous.proposal.id + 1,
+        None => 0,
+    };
+    let governance = read_upgrade_governance(host)?;
+    let approvals = if governance.is_approver(&proposer) {
+        vec![proposer]
+    } else {
+        vec![]
+    };
+    let status = KernelUpgradeStatus {
+        status: UpgradeStatus::Proposed,
+        proposal: UpgradeProposal { id, upgrade },
+        approvals,
+    };
+    schedule_if_approved(host, status)
+}
+
+/// Approves `proposal`. The approval is ignored if it is not the current
+/// proposal, or if the approver is not part of the governance or already
+/// approved it.
+pub fn approve_kernel_upgrade<Host: Runtime>(
+    host: &mut Host,
+    approver: Approver,
+    proposal: UpgradeProposal,
+) -> anyhow::Result<()> {
+    let status = match read_kernel_upgrade_status(host)? {
+        Some(status)
+            if status.status == UpgradeStatus::Proposed
+                && status.proposal == proposal =>
+        {
+            status
+        }
+        _ => {
+            log!(
+                host,
+                Info,
+                "Ignoring the approval of {}, which is not the current proposal",
+                proposal
+            );
+            return Ok(());
+        }
+    };
+    let governance = read_upgrade_governance(host)?;
+    if !governance.is_approver(&approver) || status.approvals.contains(&approver) {
+        log!(
+            host,
+            Info,
+            "Ignoring an approval of {} by {:?}",
+            proposal,
+            approver
+        );
+        return Ok(());
+    }
+    let mut status = status;
+    status.approvals.push(approver);
+    schedule_if_approved(host, status)
+}
+
+/// Cancels the upgrade to `preimage_hash` if it is proposed or scheduled.
+pub fn cancel_kernel_upgrade<Host: Runtime>(
+    host: &mut Host,
+    preimage_hash: [u8; PREIMAGE_HASH_SIZE],
+) -> anyhow::Result<()> {
+    match read_kernel_upgrade_status(host)? {
+        Some(mut status)
+            if status.proposal.upgrade.preimage_hash == preimage_hash
+                && matches!(
+                    status.status,
+                    UpgradeStatus::Proposed | UpgradeStatus::Scheduled
+                ) =>
+        {
+            if status.status == UpgradeStatus::Scheduled {
+                delete_kernel_upgrade(host)?;
+            }
+            log!(host, Info, "The {} is cancelled", status.proposal);
+            status.status = UpgradeStatus::Cancelled;
+            store_kernel_upgrade_status(host, &status)
+        }
+        _ => {
+            log!(
+                host,
+                Info,
+                "Ignoring the cancellation of {}, which is not planned",
+                hex::encode(preimage_hash)
+            );
+            Ok(())
+        }
+    }
+}
+
 pub fn upgrade<Host: Runtime>(
     host: &mut Host,
     root_hash: [u8; PREIMAGE_HASH_SIZE],
@@ -102,6 +749,12 @@ pub fn upgrade<Host: Runtime>(
         .map_err(UpgradeProcessError::InternalUpgrade)?;
 
     delete_kernel_upgrade(host)?;
+    if let Some(mut status) = read_kernel_upgrade_status(host)? {
+        if status.proposal.upgrade.preimage_hash == root_hash {
+            status.status = UpgradeStatus::Applied;
+            store_kernel_upgrade_status(host, &status)?;
+        }
+    }
 
     // Mark for reboot, the upgrade/migration will happen at next
     // kernel run, it doesn't matter if it is within the Tezos level
@@ -115,13 +768,39 @@ pub fn upgrade<Host: Runtime>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::store_l1_level;
     use std::ffi::OsString;
     use std::fs;
     use std::path::Path;
+    use tezos_crypto_rs::hash::SeedEd25519;
     use tezos_smart_rollup_encoding::dac::{prepare_preimages, PreimageHash};
     use tezos_smart_rollup_host::KERNEL_BOOT_PATH;
     use tezos_smart_rollup_mock::MockHost;
 
+    fn contract(kt1: &str) -> ContractKt1Hash {
+        ContractKt1Hash::from_base58_check(kt1).unwrap()
+    }
+
+    fn admin_contract() -> ContractKt1Hash {
+        contract("KT1HJphVV3LUxqZnc7YSH6Zdfd3up1DjLqZv")
+    }
+
+    fn admin() -> Approver {
+        Approver::Contract(admin_contract())
+    }
+
+    fn dummy_upgrade() -> KernelUpgrade {
+        KernelUpgrade {
+            preimage_hash: [1; PREIMAGE_HASH_SIZE],
+            activation_timestamp: Timestamp::from(1_000),
+            activation_level: Some(100),
+        }
+    }
+
+    fn status(host: &MockHost) -> UpgradeStatus {
+        read_kernel_upgrade_status(host).unwrap().unwrap().status
+    }
+
     fn preliminary_upgrade(host: &mut MockHost) -> (PreimageHash, Vec<u8>) {
         let upgrade_to = OsString::from("tests/resources/debug_kernel.wasm");
         let upgrade_to = Path::new(&upgrade_to);
@@ -149,6 +828,7 @@ mod tests {
         let kernel_upgrade = KernelUpgrade {
             preimage_hash,
             activation_timestamp: Timestamp::from(0),
+            activation_level: None,
         };
         store_kernel_upgrade(&mut host, &kernel_upgrade)
             .expect("It should be able to store");
@@ -158,4 +838,284 @@ mod tests {
         let boot_kernel = host.store_read_all(&KERNEL_BOOT_PATH).unwrap();
         assert_eq!(original_kernel, boot_kernel);
     }
+
+    #[test]
+    fn test_kernel_upgrade_rlp_roundtrip() {
+        let mut kernel_upgrade = dummy_upgrade();
+        let decoded: KernelUpgrade = rlp::decode(&kernel_upgrade.rlp_bytes()).unwrap();
+        assert_eq!(decoded, kernel_upgrade);
+
+        // Upgrades without activation level keep the two items encoding.
+        kernel_upgrade.activation_level = None;
+        let bytes = kernel_upgrade.rlp_bytes();
+        assert_eq!(Rlp::new(&bytes).item_count().unwrap(), 2);
+        let decoded: KernelUpgrade = rlp::decode(&bytes).unwrap();
+        assert_eq!(decoded, kernel_upgrade);
+    }
+
+    #[test]
+    fn test_kernel_upgrade_activation() {
+        let mut host = MockHost::default();
+        let kernel_upgrade = dummy_upgrade();
+        store_l1_level(&mut host, 99).unwrap();
+        assert!(!kernel_upgrade
+            .is_active(&host, Timestamp::from(999))
+            .unwrap());
+        assert!(!kernel_upgrade
+            .is_active(&host, Timestamp::from(1_000))
+            .unwrap());
+        store_l1_level(&mut host, 100).unwrap();
+        assert!(!kernel_upgrade
+            .is_active(&host, Timestamp::from(999))
+            .unwrap());
+        assert!(kernel_upgrade
+            .is_active(&host, Timestamp::from(1_000))
+            .unwrap());
+    }
+
+    #[test]
+    fn test_upgrade_is_scheduled_after_threshold() {
+        let mut host = MockHost::default();
+        let governance_contract = contract("KT18amZmM5W7qDWVt2pH6uj7sCEd3kbzLrHT");
+        let (key, _) = SeedEd25519(vec![1; 32]).keypair().unwrap();
+        let key = PublicKey::Ed25519(key);
+        let governance = UpgradeGovernance {
+            contracts: vec![admin_contract(), governance_contract.clone()],
+            keys: vec![key.clone()],
+            threshold: 3,
+        };
+        store_rlp(&governance, &mut host, &UPGRADE_GOVERNANCE).unwrap();
+        let kernel_upgrade = dummy_upgrade();
+
+        propose_kernel_upgrade(&mut host, kernel_upgrade.clone(), admin()).unwrap();
+        assert_eq!(status(&host), UpgradeStatus::Proposed);
+        assert_eq!(read_kernel_upgrade(&host).unwrap(), None);
+        let proposal = UpgradeProposal {
+            id: 0,
+            upgrade: kernel_upgrade.clone(),
+        };
+
+        // Approvals of unknown approvers, of another upgrade, or repeated
+        // approvals are ignored.
+        let unknown =
+            Approver::Contract(contract("KT1BuEZtb68c1Q4yjtckcNjGELqWt56Xyesc"));
+        approve_kernel_upgrade(&mut host, unknown, proposal.clone()).unwrap();
+        approve_kernel_upgrade(&mut host, admin(), proposal.clone()).unwrap();
+        let approver = Approver::Contract(governance_contract);
+        let other_upgrade = UpgradeProposal {
+            id: 0,
+            upgrade: KernelUpgrade {
+                preimage_hash: [2; PREIMAGE_HASH_SIZE],
+                ..kernel_upgrade.clone()
+            },
+        };
+        approve_kernel_upgrade(&mut host, approver.clone(), other_upgrade).unwrap();
+        approve_kernel_upgrade(&mut host, approver.clone(), proposal.clone()).unwrap();
+        approve_kernel_upgrade(&mut host, approver.clone(), proposal.clone()).unwrap();
+        let upgrade_status = read_kernel_upgrade_status(&host).unwrap().unwrap();
+        assert_eq!(upgrade_status.status, UpgradeStatus::Proposed);
+        assert_eq!(upgrade_status.approvals, vec![admin(), approver]);
+
+        approve_kernel_upgrade(&mut host, Approver::Key(key), proposal).unwrap();
+        assert_eq!(status(&host), UpgradeStatus::Scheduled);
+        assert_eq!(read_kernel_upgrade(&host).unwrap(), Some(kernel_upgrade));
+    }
+
+    #[test]
+    fn test_approvals_are_bound_to_the_proposal() {
+        let mut host = MockHost::default();
+        let governance_contract = contract("KT18amZmM5W7qDWVt2pH6uj7sCEd3kbzLrHT");
+        let governance = UpgradeGovernance {
+            contracts: vec![admin_contract(), governance_contract.clone()],
+            keys: vec![],
+            threshold: 2,
+        };
+        store_rlp(&governance, &mut host, &UPGRADE_GOVERNANCE).unwrap();
+        let approver = Approver::Contract(governance_contract);
+        let kernel_upgrade = dummy_upgrade();
+        let first = UpgradeProposal {
+            id: 0,
+            upgrade: kernel_upgrade.clone(),
+        };
+
+        // The same upgrade proposed again is a new proposal, the approvals
+        // of the first one don't count for it.
+        propose_kernel_upgrade(&mut host, kernel_upgrade.clone(), admin()).unwrap();
+        cancel_kernel_upgrade(&mut host, kernel_upgrade.preimage_hash).unwrap();
+        propose_kernel_upgrade(&mut host, kernel_upgrade.clone(), admin()).unwrap();
+        approve_kernel_upgrade(&mut host, approver.clone(), first).unwrap();
+        let upgrade_status = read_kernel_upgrade_status(&host).unwrap().unwrap();
+        assert_eq!(upgrade_status.proposal.id, 1);
+        assert_eq!(upgrade_status.status, UpgradeStatus::Proposed);
+
+        // Neither do approvals of another activation.
+        let earlier = UpgradeProposal {
+            id: 1,
+            upgrade: KernelUpgrade {
+                activation_level: Some(10),
+                ..kernel_upgrade.clone()
+            },
+        };
+        approve_kernel_upgrade(&mut host, approver.clone(), earlier).unwrap();
+        assert_eq!(status(&host), UpgradeStatus::Proposed);
+
+        let second = UpgradeProposal {
+            id: 1,
+            upgrade: kernel_upgrade,
+        };
+        approve_kernel_upgrade(&mut host, approver, second).unwrap();
+        assert_eq!(status(&host), UpgradeStatus::Scheduled);
+    }
+
+    #[test]
+    fn test_administrator_approves_only_as_part_of_the_governance() {
+        let mut host = MockHost::default();
+        let governance_contract = contract("KT18amZmM5W7qDWVt2pH6uj7sCEd3kbzLrHT");
+        let approver = Approver::Contract(governance_contract.clone());
+        let governance = UpgradeGovernance {
+            contracts: vec![governance_contract],
+            keys: vec![],
+            threshold: 1,
+        };
+        store_rlp(&governance, &mut host, &UPGRADE_GOVERNANCE).unwrap();
+        let kernel_upgrade = dummy_upgrade();
+        let proposal = UpgradeProposal {
+            id: 0,
+            upgrade: kernel_upgrade.clone(),
+        };
+
+        propose_kernel_upgrade(&mut host, kernel_upgrade, admin()).unwrap();
+        let upgrade_status = read_kernel_upgrade_status(&host).unwrap().unwrap();
+        assert_eq!(upgrade_status.status, UpgradeStatus::Proposed);
+        assert!(upgrade_status.approvals.is_empty());
+
+        approve_kernel_upgrade(&mut host, approver, proposal).unwrap();
+        assert_eq!(status(&host), UpgradeStatus::Scheduled);
+    }
+
+    #[test]
+    fn test_update_upgrade_governance() {
+        let mut host = MockHost::default();
+        let governance_contract = contract("KT18amZmM5W7qDWVt2pH6uj7sCEd3kbzLrHT");
+        let approver = Approver::Contract(governance_contract.clone());
+        let (key, _) = SeedEd25519(vec![1; 32]).keypair().unwrap();
+        let key = PublicKey::Ed25519(key);
+        let governance = UpgradeGovernance {
+            contracts: vec![admin_contract(), governance_contract],
+            keys: vec![key.clone()],
+            threshold: 3,
+        };
+        update_upgrade_governance(&mut host, governance).unwrap();
+        let kernel_upgrade = dummy_upgrade();
+        let proposal = UpgradeProposal {
+            id: 0,
+            upgrade: kernel_upgrade.clone(),
+        };
+        propose_kernel_upgrade(&mut host, kernel_upgrade, admin()).unwrap();
+        approve_kernel_upgrade(&mut host, approver, proposal.clone()).unwrap();
+        assert_eq!(status(&host), UpgradeStatus::Proposed);
+
+        // The approval of the removed contract no longer counts.
+        let governance = UpgradeGovernance {
+            contracts: vec![admin_contract()],
+            keys: vec![key.clone()],
+            threshold: 2,
+        };
+        update_upgrade_governance(&mut host, governance.clone()).unwrap();
+        assert_eq!(read_upgrade_governance(&host).unwrap(), governance);
+        let upgrade_status = read_kernel_upgrade_status(&host).unwrap().unwrap();
+        assert_eq!(upgrade_status.status, UpgradeStatus::Proposed);
+        assert_eq!(upgrade_status.approvals, vec![admin()]);
+
+        // Lowering the threshold schedules the upgrade.
+        update_upgrade_governance(
+            &mut host,
+            UpgradeGovernance {
+                threshold: 1,
+                ..governance
+            },
+        )
+        .unwrap();
+        assert_eq!(status(&host), UpgradeStatus::Scheduled);
+    }
+
+    #[test]
+    fn test_upgrade_governance_validity() {
+        let (key, _) = SeedEd25519(vec![1; 32]).keypair().unwrap();
+        let key = PublicKey::Ed25519(key);
+        let governance = UpgradeGovernance {
+            contracts: vec![admin_contract()],
+            keys: vec![key.clone()],
+            threshold: 2,
+        };
+        assert!(governance.is_valid());
+        assert!(UpgradeGovernance::default().is_valid());
+        assert!(!UpgradeGovernance {
+            threshold: 3,
+            ..governance.clone()
+        }
+        .is_valid());
+        assert!(!UpgradeGovernance {
+            keys: vec![key.clone(), key],
+            ..governance.clone()
+        }
+        .is_valid());
+        assert!(!UpgradeGovernance {
+            contracts: vec![admin_contract(), admin_contract()],
+            ..governance.clone()
+        }
+        .is_valid());
+
+        let decision = UpgradeDecision::UpdateGovernance(governance);
+        let decoded: UpgradeDecision = rlp::decode(&decision.rlp_bytes()).unwrap();
+        assert_eq!(decoded, decision);
+    }
+
+    #[test]
+    fn test_cancel_kernel_upgrade() {
+        let mut host = MockHost::default();
+        let kernel_upgrade = dummy_upgrade();
+        let preimage_hash = kernel_upgrade.preimage_hash;
+        propose_kernel_upgrade(&mut host, kernel_upgrade, admin()).unwrap();
+        assert_eq!(status(&host), UpgradeStatus::Scheduled);
+
+        // Cancelling another upgrade has no effect.
+        cancel_kernel_upgrade(&mut host, [2; PREIMAGE_HASH_SIZE]).unwrap();
+        assert_eq!(status(&host), UpgradeStatus::Scheduled);
+
+        cancel_kernel_upgrade(&mut host, preimage_hash).unwrap();
+        assert_eq!(status(&host), UpgradeStatus::Cancelled);
+        assert_eq!(read_kernel_upgrade(&host).unwrap(), None);
+    }
+
+    #[test]
+    fn test_signed_upgrade_approval() {
+        let (pk, sk) = SeedEd25519(vec![1; 32]).keypair().unwrap();
+        let proposal = UpgradeProposal {
+            id: 3,
+            upgrade: dummy_upgrade(),
+        };
+        let smart_rollup_address = [3; 20];
+        let msg = approval_digest(&proposal, &smart_rollup_address);
+        let mut approval = SignedUpgradeApproval {
+            proposal,
+            public_key: PublicKey::Ed25519(pk.clone()),
+            signature: sk.sign(msg).unwrap(),
+        };
+        let decoded: SignedUpgradeApproval = rlp::decode(&approval.rlp_bytes()).unwrap();
+        assert_eq!(decoded, approval);
+
+        assert_eq!(approval.approver(&[4; 20]), None);
+        assert_eq!(
+            approval.approver(&smart_rollup_address),
+            Some(Approver::Key(PublicKey::Ed25519(pk)))
+        );
+
+        // The signature doesn't hold for another proposal or activation.
+        approval.proposal.id = 4;
+        assert_eq!(approval.approver(&smart_rollup_address), None);
+        approval.proposal.id = 3;
+        approval.proposal.upgrade.activation_timestamp = Timestamp::from(0);
+        assert_eq!(approval.approver(&smart_rollup_address), None);
+    }
 }
@@ -0,0 +1,790 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+//! State root of the world state.
+//!
+//! The state trie is updated after each transaction, for the accounts and
+//! storage slots it modified (see [TouchedAccounts]). Accounts set without
+//! a transaction, for instance by the installer, are only part of the state
+//! trie once a transaction modifies them, unless they are declared as
+//! genesis accounts (see [crate::genesis]).
+//!
+//! Accounts can also be declared, with the indexes of their storage, in an
+//! [IndexableStorage] of RLP encoded `[address, [index, ..]]` entries, see
+//! [add_declared_accounts]. This is how the genesis accounts are added (see
+//! [crate::genesis]). The accounts existing before the state trie was
+//! introduced are added from the index of accounts instead, see
+//! [add_indexed_accounts] and [crate::migration].
+
+use crate::error::{Error, StorageError};
+use crate::indexable_storage::IndexableStorage;
+use crate::storage::store_read_slice;
+use crate::trie;
+use evm_execution::account_storage::{
+    account_path, init_account_storage, EthereumAccount, EthereumAccountStorage,
+    CODE_HASH_DEFAULT, EMPTY_TRIE_ROOT,
+};
+use evm_execution::handler::TouchedAccounts;
+use evm_execution::tick_model_opcodes::{
+    TICKS_PER_TOUCHED_ACCOUNT, TICKS_PER_TOUCHED_SLOT,
+};
+use primitive_types::{H160, H256, U256};
+use rlp::{DecoderError, Rlp, RlpStream};
+use tezos_ethereum::proof::{keccak, AccountProof, StorageProof};
+use tezos_ethereum::rlp_helpers::{decode_field, decode_list, next};
+use tezos_smart_rollup_host::path::{concat, Path, RefPath};
+use tezos_smart_rollup_host::runtime::{Runtime, ValueType};
+
+/// Path to the root of the state trie.
+const WORLD_STATE_ROOT: RefPath = RefPath::assert_from(b"/world_state/root");
+
+/// Progress of [add_declared_accounts] and [add_indexed_accounts] in their
+/// list of accounts.
+const ACCOUNTS_CURSOR: RefPath = RefPath::assert_from(b"/cursor");
+
+/// Reads the root of the state trie, which is empty if no transaction was
+/// applied yet.
+pub fn read_state_root<Host: Runtime>(host: &Host) -> Result<H256, Error> {
+    match host.store_has(&WORLD_STATE_ROOT)? {
+        Some(ValueType::Value | ValueType::ValueWithSubtree) => {
+            let mut buffer = [0_u8; 32];
+            store_read_slice(host, &WORLD_STATE_ROOT, &mut buffer, 32)?;
+            Ok(H256(buffer))
+        }
+        _ => Ok(EMPTY_TRIE_ROOT),
+    }
+}
+
+/// Updates the storage trie of `account` with the current values of the
+/// storage `slots`.
+fn update_storage_root<'a, Host: Runtime>(
+    host: &mut Host,
+    account: &mut EthereumAccount,
+    slots: impl Iterator<Item = &'a H256>,
+) -> Result<H256, Error> {
+    let mut root = account.storage_trie_root(host)?;
+    for slot in slots {
+        let value = account.get_storage(host, slot)?;
+        let key = keccak(slot.as_bytes());
+        root = if value.is_zero() {
+            trie::remove(host, &root, &key)?
+        } else {
+            let value = U256::from_big_endian(value.as_bytes());
+            trie::insert(host, &root, &key, rlp::encode(&value).to_vec())?
+        };
+    }
+    account.set_storage_trie_root(host, &root)?;
+    Ok(root)
+}
+
+/// Encoding of an account in the state trie, or `None` if the account is
+/// empty (see EIP-161).
+fn account_leaf<Host: Runtime>(
+    host: &Host,
+    account: &EthereumAccount,
+    storage_root: H256,
+) -> Result<Option<Vec<u8>>, Error> {
+    let nonce = account.nonce(host)?;
+    let balance = account.balance(host)?;
+    let code_hash = account.code_hash(host)?;
+    if nonce.is_zero() && balance.is_zero() && code_hash == CODE_HASH_DEFAULT {
+        return Ok(None);
+    }
+    let mut stream = RlpStream::new_list(4);
+    stream.append(&nonce);
+    stream.append(&balance);
+    stream.append(&storage_root);
+    stream.append(&code_hash);
+    Ok(Some(stream.out().to_vec()))
+}
+
+/// Updates the state trie with the current state of the `touched` accounts.
+///
+/// The storage trie of a deleted account is not released, its root being
+/// deleted with the account: its nodes stay in the durable storage. Since
+/// Cancun (EIP-6780), only the accounts created by the same transaction are
+/// deleted, and their storage trie is never stored.
+pub fn update_state_root<Host: Runtime>(
+    host: &mut Host,
+    evm_account_storage: &mut EthereumAccountStorage,
+    touched: &TouchedAccounts,
+) -> Result<(), Error> {
+    let mut root = read_state_root(host)?;
+    for (address, slots) in touched {
+        let key = keccak(address.as_bytes());
+        let leaf = match evm_account_storage.get(host, &account_path(address)?)? {
+            None => None,
+            Some(mut account) => {
+                let storage_root = if slots.is_empty() {
+                    account.storage_trie_root(host)?
+                } else {
+                    update_storage_root(host, &mut account, slots.iter())?
+                };
+                account_leaf(host, &account, storage_root)?
+            }
+        };
+        root = match leaf {
+            None => trie::remove(host, &root, &key)?,
+            Some(leaf) => trie::insert(host, &root, &key, leaf)?,
+        };
+    }
+    host.store_write_all(&WORLD_STATE_ROOT, root.as_bytes())?;
+    Ok(())
+}
+
+/// Position of [add_declared_accounts] and [add_indexed_accounts] in their
+/// list: the next account, the next index of its storage, and the previous
+/// account.
+///
+/// Encoding: `account (u64 LE) || slot (u64 LE) || previous address`.
+#[derive(Default)]
+struct AccountsCursor {
+    account: u64,
+    slot: u64,
+    previous: Option<H160>,
+}
+
+impl AccountsCursor {
+    fn read<Host: Runtime>(host: &Host, path: &impl Path) -> Result<Self, Error> {
+        if host.store_has(path)?.is_none() {
+            return Ok(Self::default());
+        }
+        let bytes = host.store_read_all(path)?;
+        let previous = match bytes.len() {
+            16 => None,
+            36 => Some(H160::from_slice(&bytes[16..])),
+            actual => {
+                return Err(Error::Storage(StorageError::InvalidLoadValue {
+                    expected: 36,
+                    actual,
+                }))
+            }
+        };
+        let mut account = [0u8; 8];
+        account.copy_from_slice(&bytes[0..8]);
+        let mut slot = [0u8; 8];
+        slot.copy_from_slice(&bytes[8..16]);
+        Ok(Self {
+            account: u64::from_le_bytes(account),
+            slot: u64::from_le_bytes(slot),
+            previous,
+        })
+    }
+
+    fn store<Host: Runtime>(
+        &self,
+        host: &mut Host,
+        path: &impl Path,
+    ) -> Result<(), Error> {
+        let mut bytes = self.account.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&self.slot.to_le_bytes());
+        if let Some(previous) = self.previous {
+            bytes.extend_from_slice(previous.as_bytes());
+        }
+        host.store_write_all(path, &bytes)?;
+        Ok(())
+    }
+}
+
+fn decode_declared_account(bytes: &[u8]) -> Result<(H160, Vec<H256>), Error> {
+    let decoder = Rlp::new(bytes);
+    if decoder.item_count()? != 2 {
+        return Err(Error::from(DecoderError::RlpIncorrectListLen));
+    }
+    let mut it = decoder.iter();
+    let address = decode_field(&next(&mut it)?, "address")?;
+    let indexes = decode_list(&next(&mut it)?, "storage_indexes")?;
+    Ok((address, indexes))
+}
+
+fn invalid_declaration(reason: &'static str) -> Error {
+    Error::Storage(StorageError::InvalidDeclaredAccounts(reason))
+}
+
+/// Adds `account` to the state trie with its storage at `indexes`, starting
+/// from the index at `slot`, as long as `ticks_limit` allows it. Returns
+/// `None` once the account is added, or the position in its storage to
+/// continue from.
+///
+/// The ticks are counted as in the execution, each account and storage slot
+/// reserving the ticks of updating the state trie (see
+/// [evm_execution::handler]). Reading the indexes is negligible in
+/// comparison.
+#[allow(clippy::too_many_arguments)]
+fn add_account<Host: Runtime>(
+    host: &mut Host,
+    evm_account_storage: &mut EthereumAccountStorage,
+    address: H160,
+    mut account: EthereumAccount,
+    indexes: &[H256],
+    slot: u64,
+    ticks: &mut u64,
+    ticks_limit: u64,
+) -> Result<Option<u64>, Error> {
+    if indexes.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(invalid_declaration("the storage indexes are not sorted"));
+    }
+    if slot == 0 && account.storage_size(host)? != indexes.len() as u64 {
+        return Err(invalid_declaration("the storage indexes are incomplete"));
+    }
+
+    // The storage is added in chunks, the root of the storage trie of the
+    // account being kept between reboots.
+    let remaining = &indexes[slot as usize..];
+    let affordable = ticks_limit
+        .saturating_sub(*ticks)
+        .saturating_sub(TICKS_PER_TOUCHED_ACCOUNT)
+        / TICKS_PER_TOUCHED_SLOT;
+    let chunk = &remaining[..remaining.len().min(affordable as usize)];
+    for index in chunk {
+        if account.get_storage(host, index)?.is_zero() {
+            return Err(invalid_declaration("a storage index has no value"));
+        }
+    }
+    if !chunk.is_empty() {
+        update_storage_root(host, &mut account, chunk.iter())?;
+        *ticks += chunk.len() as u64 * TICKS_PER_TOUCHED_SLOT;
+    }
+    if chunk.len() < remaining.len()
+        || ticks.saturating_add(TICKS_PER_TOUCHED_ACCOUNT) > ticks_limit
+    {
+        return Ok(Some(slot + chunk.len() as u64));
+    }
+
+    let mut touched = TouchedAccounts::new();
+    touched.insert(address, Default::default());
+    update_state_root(host, evm_account_storage, &touched)?;
+    *ticks += TICKS_PER_TOUCHED_ACCOUNT;
+    Ok(None)
+}
+
+/// Adds the `accounts` declared with the indexes of their storage to the
+/// state trie, as long as `ticks_limit` allows it. Returns
+/// whether all of them were added, otherwise the kernel must reboot and call
+/// it again to continue.
+///
+/// The accounts must be sorted by address, and their indexes sorted and of
+/// non-zero values. The indexes of an account must be all the ones of its
+/// storage, which is checked with the number of values in its storage.
+pub fn add_declared_accounts<Host: Runtime>(
+    host: &mut Host,
+    accounts: &IndexableStorage,
+    ticks_limit: u64,
+) -> Result<bool, Error> {
+    let mut evm_account_storage = init_account_storage()?;
+    let length = accounts.length(host)?;
+    let cursor_path = concat(&accounts.path, &ACCOUNTS_CURSOR)?;
+    let mut cursor = AccountsCursor::read(host, &cursor_path)?;
+    let mut ticks: u64 = 0;
+
+    while cursor.account < length {
+        let (address, indexes) =
+            decode_declared_account(&accounts.unsafe_get_value(host, cursor.account)?)?;
+        if cursor
+            .previous
+            .map_or(false, |previous| previous >= address)
+        {
+            return Err(invalid_declaration("the accounts are not sorted"));
+        }
+        let account = evm_account_storage
+            .get(host, &account_path(&address)?)?
+            .ok_or(invalid_declaration("an account doesn't exist"))?;
+        if let Some(slot) = add_account(
+            host,
+            &mut evm_account_storage,
+            address,
+            account,
+            &indexes,
+            cursor.slot,
+            &mut ticks,
+            ticks_limit,
+        )? {
+            cursor.slot = slot;
+            cursor.store(host, &cursor_path)?;
+            return Ok(false);
+        }
+        cursor = AccountsCursor {
+            account: cursor.account + 1,
+            slot: 0,
+            previous: Some(address),
+        };
+    }
+    Ok(true)
+}
+
+/// Indexes of the storage of `account`, declared under `storage_indexes` as
+/// the RLP list of the indexes at the path of the account. Accounts without
+/// storage don't need a declaration.
+fn declared_storage_indexes<Host: Runtime>(
+    host: &Host,
+    storage_indexes: &impl Path,
+    address: &H160,
+    account: &EthereumAccount,
+) -> Result<Vec<H256>, Error> {
+    if account.storage_size(host)? == 0 {
+        return Ok(vec![]);
+    }
+    let path = concat(storage_indexes, &account_path(address)?)?;
+    if host.store_has(&path)?.is_none() {
+        return Err(invalid_declaration("the storage indexes are incomplete"));
+    }
+    let bytes = host.store_read_all(&path)?;
+    Ok(decode_list(&Rlp::new(&bytes), "storage_indexes")?)
+}
+
+/// Adds the accounts of the index of `accounts` (see
+/// [crate::storage::init_account_index]) to the state trie, as long as
+/// `ticks_limit` allows it. Returns whether all of them were added, otherwise
+/// the kernel must reboot and call it again to continue. Indexed accounts
+/// that no longer exist are skipped.
+///
+/// The kernel cannot list the subkeys of a path, the indexes of the storage
+/// of the accounts that have one are declared under `storage_indexes`, see
+/// [declared_storage_indexes]. They are checked as in
+/// [add_declared_accounts].
+pub fn add_indexed_accounts<Host: Runtime>(
+    host: &mut Host,
+    accounts: &IndexableStorage,
+    storage_indexes: &impl Path,
+    ticks_limit: u64,
+) -> Result<bool, Error> {
+    let mut evm_account_storage = init_account_storage()?;
+    let length = accounts.length(host)?;
+    let cursor_path = concat(storage_indexes, &ACCOUNTS_CURSOR)?;
+    let mut cursor = AccountsCursor::read(host, &cursor_path)?;
+    let mut ticks: u64 = 0;
+
+    while cursor.account < length {
+        let bytes = accounts.unsafe_get_value(host, cursor.account)?;
+        if bytes.len() != 20 {
+            return Err(Error::Storage(StorageError::InvalidLoadValue {
+                expected: 20,
+                actual: bytes.len(),
+            }));
+        }
+        let address = H160::from_slice(&bytes);
+        if let Some(account) = evm_account_storage.get(host, &account_path(&address)?)? {
+            let indexes =
+                declared_storage_indexes(host, storage_indexes, &address, &account)?;
+            if let Some(slot) = add_account(
+                host,
+                &mut evm_account_storage,
+                address,
+                account,
+                &indexes,
+                cursor.slot,
+                &mut ticks,
+                ticks_limit,
+            )? {
+                cursor.slot = slot;
+                cursor.store(host, &cursor_path)?;
+                return Ok(false);
+            }
+        }
+        cursor = AccountsCursor {
+            account: cursor.account + 1,
+            slot: 0,
+            previous: None,
+        };
+    }
+    Ok(true)
+}
+
+/// Proof of the account at `address` and of its `storage_keys` in the state
+/// of root `state_root`. Only the latest state is kept (see [crate::trie]),
+/// the state of a previous block can only be proven in the durable storage
+/// of that block.
+pub fn account_proof<Host: Runtime>(
+    host: &Host,
+    state_root: &H256,
+    address: &H160,
+    storage_keys: &[H256],
+) -> Result<AccountProof, Error> {
+    let (leaf, account_proof) =
+        trie::proof(host, state_root, &keccak(address.as_bytes()))?;
+    let (nonce, balance, storage_hash, code_hash) = match leaf {
+        None => (
+            U256::zero(),
+            U256::zero(),
+            EMPTY_TRIE_ROOT,
+            CODE_HASH_DEFAULT,
+        ),
+        Some(leaf) => {
+            let leaf = Rlp::new(&leaf);
+            if leaf.item_count()? != 4 {
+                return Err(Error::from(DecoderError::RlpIncorrectListLen));
+            }
+            (
+                leaf.val_at(0)?,
+                leaf.val_at(1)?,
+                leaf.val_at(2)?,
+                leaf.val_at(3)?,
+            )
+        }
+    };
+    let mut storage_proof = Vec::with_capacity(storage_keys.len());
+    for key in storage_keys {
+        let (value, proof) = trie::proof(host, &storage_hash, &keccak(key.as_bytes()))?;
+        let value = match value {
+            None => U256::zero(),
+            Some(value) => rlp::decode(&value)?,
+        };
+        storage_proof.push(StorageProof {
+            key: *key,
+            value,
+            proof,
+        });
+    }
+    Ok(AccountProof {
+        address: *address,
+        balance,
+        nonce,
+        code_hash,
+        storage_hash,
+        account_proof,
+        storage_proof,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{index_account, init_account_index};
+    use evm_execution::account_storage::init_account_storage;
+    use std::collections::{BTreeMap, BTreeSet};
+    use tezos_smart_rollup_mock::MockHost;
+
+    fn account(
+        host: &mut MockHost,
+        evm_account_storage: &mut EthereumAccountStorage,
+        address: &H160,
+    ) -> EthereumAccount {
+        evm_account_storage
+            .get_or_create(host, &account_path(address).unwrap())
+            .unwrap()
+    }
+
+    // Encoding of an account in the reference implementation.
+    fn reference_account(
+        nonce: u64,
+        balance: u64,
+        storage_root: H256,
+        code: &[u8],
+    ) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(4);
+        stream.append(&U256::from(nonce));
+        stream.append(&U256::from(balance));
+        stream.append(&storage_root);
+        stream.append(&keccak(code));
+        stream.out().to_vec()
+    }
+
+    #[test]
+    fn test_state_root_matches_reference_implementation() {
+        let mut host = MockHost::default();
+        let mut evm_account_storage = init_account_storage().unwrap();
+        assert_eq!(read_state_root(&host).unwrap(), EMPTY_TRIE_ROOT);
+
+        let alice = H160::from_low_u64_be(1);
+        let contract = H160::from_low_u64_be(2);
+        let empty = H160::from_low_u64_be(3);
+
+        let mut alice_account = account(&mut host, &mut evm_account_storage, &alice);
+        alice_account
+            .balance_add(&mut host, U256::from(1000))
+            .unwrap();
+        alice_account.increment_nonce(&mut host).unwrap();
+        let mut contract_account =
+            account(&mut host, &mut evm_account_storage, &contract);
+        contract_account.set_code(&mut host, &[0x60, 0x00]).unwrap();
+        let slots = [
+            H256::zero(),
+            H256::from_low_u64_be(1),
+            H256::from_low_u64_be(2),
+        ];
+        for (i, slot) in slots.iter().enumerate() {
+            contract_account
+                .set_storage(&mut host, slot, &H256::from_low_u64_be(i as u64 + 1))
+                .unwrap();
+        }
+        account(&mut host, &mut evm_account_storage, &empty);
+
+        let mut touched = TouchedAccounts::new();
+        touched.insert(alice, BTreeSet::new());
+        touched.insert(contract, slots.iter().copied().collect());
+        touched.insert(empty, BTreeSet::new());
+        update_state_root(&mut host, &mut evm_account_storage, &touched).unwrap();
+
+        let storage: BTreeMap<_, _> = slots
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| (slot.as_bytes().to_vec(), rlp::encode(&(i + 1)).to_vec()))
+            .collect();
+        let storage_root = ethereum::util::sec_trie_root(&storage);
+        let mut accounts = BTreeMap::new();
+        accounts.insert(
+            alice.as_bytes().to_vec(),
+            reference_account(1, 1000, EMPTY_TRIE_ROOT, &[]),
+        );
+        accounts.insert(
+            contract.as_bytes().to_vec(),
+            reference_account(0, 0, storage_root, &[0x60, 0x00]),
+        );
+        assert_eq!(
+            read_state_root(&host).unwrap(),
+            ethereum::util::sec_trie_root(&accounts)
+        );
+
+        // Clearing a slot and deleting an account
+        contract_account
+            .set_storage(&mut host, &slots[1], &H256::zero())
+            .unwrap();
+        evm_account_storage
+            .delete(&mut host, &account_path(&alice).unwrap())
+            .unwrap();
+        let mut touched = TouchedAccounts::new();
+        touched.insert(alice, BTreeSet::new());
+        touched.insert(contract, [slots[1]].into_iter().collect());
+        update_state_root(&mut host, &mut evm_account_storage, &touched).unwrap();
+
+        let mut storage = storage;
+        storage.remove(slots[1].as_bytes());
+        let storage_root = ethereum::util::sec_trie_root(&storage);
+        let mut accounts = BTreeMap::new();
+        accounts.insert(
+            contract.as_bytes().to_vec(),
+            reference_account(0, 0, storage_root, &[0x60, 0x00]),
+        );
+        assert_eq!(
+            read_state_root(&host).unwrap(),
+            ethereum::util::sec_trie_root(&accounts)
+        );
+    }
+
+    #[test]
+    fn test_account_proofs_are_verified() {
+        let mut host = MockHost::default();
+        let mut evm_account_storage = init_account_storage().unwrap();
+        let contract = H160::from_low_u64_be(2);
+        let slot = H256::from_low_u64_be(1);
+
+        let mut contract_account =
+            account(&mut host, &mut evm_account_storage, &contract);
+        contract_account.set_code(&mut host, &[0x60, 0x00]).unwrap();
+        contract_account
+            .set_storage(&mut host, &slot, &H256::from_low_u64_be(42))
+            .unwrap();
+        let mut touched = TouchedAccounts::new();
+        touched.insert(contract, [slot].into_iter().collect());
+        update_state_root(&mut host, &mut evm_account_storage, &touched).unwrap();
+        let previous_root = read_state_root(&host).unwrap();
+
+        contract_account
+            .set_storage(&mut host, &slot, &H256::from_low_u64_be(43))
+            .unwrap();
+        update_state_root(&mut host, &mut evm_account_storage, &touched).unwrap();
+        let state_root = read_state_root(&host).unwrap();
+
+        let absent_slot = H256::from_low_u64_be(7);
+        let proof =
+            account_proof(&host, &state_root, &contract, &[slot, absent_slot]).unwrap();
+        assert_eq!(proof.code_hash, keccak(&[0x60, 0x00]));
+        assert_eq!(proof.storage_proof[0].value, U256::from(43));
+        assert_eq!(proof.storage_proof[1].value, U256::zero());
+        assert_eq!(proof.verify(&state_root), Ok(()));
+
+        // The nodes of a previous state are deleted
+        assert!(account_proof(&host, &previous_root, &contract, &[slot]).is_err());
+
+        // An account that is not in the state
+        let absent = H160::from_low_u64_be(3);
+        let proof = account_proof(&host, &state_root, &absent, &[slot]).unwrap();
+        assert_eq!(proof.balance, U256::zero());
+        assert_eq!(proof.storage_hash, EMPTY_TRIE_ROOT);
+        assert_eq!(proof.verify(&state_root), Ok(()));
+    }
+
+    fn declare(host: &mut MockHost, accounts: &[(H160, Vec<H256>)]) -> IndexableStorage {
+        let mut declaration =
+            IndexableStorage::new(&RefPath::assert_from(b"/declared")).unwrap();
+        for (address, indexes) in accounts {
+            let mut stream = RlpStream::new_list(2);
+            stream.append(address);
+            stream.append_list(indexes);
+            declaration.push_value(host, &stream.out()).unwrap();
+        }
+        declaration
+    }
+
+    #[test]
+    fn test_declared_accounts_are_added_in_several_runs() {
+        let mut host = MockHost::default();
+        let mut evm_account_storage = init_account_storage().unwrap();
+        let user = H160::from_low_u64_be(1);
+        let contract = H160::from_low_u64_be(2);
+
+        account(&mut host, &mut evm_account_storage, &user)
+            .balance_add(&mut host, U256::from(1000))
+            .unwrap();
+        let slots: Vec<H256> = (1..=10u64).map(H256::from_low_u64_be).collect();
+        let mut contract_account =
+            account(&mut host, &mut evm_account_storage, &contract);
+        contract_account.set_code(&mut host, &[0x60, 0x00]).unwrap();
+        for slot in &slots {
+            contract_account.set_storage(&mut host, slot, slot).unwrap();
+        }
+
+        let declaration =
+            declare(&mut host, &[(user, vec![]), (contract, slots.clone())]);
+        // The first run adds the user and 4 slots, the second one 5 slots and
+        // the last one the remaining slot and the contract.
+        let ticks_limit = TICKS_PER_TOUCHED_ACCOUNT * 2 + TICKS_PER_TOUCHED_SLOT * 4;
+        assert!(!add_declared_accounts(&mut host, &declaration, ticks_limit).unwrap());
+        assert_ne!(read_state_root(&host).unwrap(), EMPTY_TRIE_ROOT);
+        assert!(!add_declared_accounts(&mut host, &declaration, ticks_limit).unwrap());
+        assert!(add_declared_accounts(&mut host, &declaration, ticks_limit).unwrap());
+
+        let storage: BTreeMap<_, _> = slots
+            .iter()
+            .map(|slot| {
+                let value = U256::from_big_endian(slot.as_bytes());
+                (slot.as_bytes().to_vec(), rlp::encode(&value).to_vec())
+            })
+            .collect();
+        let mut accounts = BTreeMap::new();
+        accounts.insert(
+            user.as_bytes().to_vec(),
+            reference_account(0, 1000, EMPTY_TRIE_ROOT, &[]),
+        );
+        accounts.insert(
+            contract.as_bytes().to_vec(),
+            reference_account(
+                0,
+                0,
+                ethereum::util::sec_trie_root(&storage),
+                &[0x60, 0x00],
+            ),
+        );
+        assert_eq!(
+            read_state_root(&host).unwrap(),
+            ethereum::util::sec_trie_root(&accounts)
+        );
+    }
+
+    #[test]
+    fn test_indexed_accounts_are_added_in_several_runs() {
+        let mut host = MockHost::default();
+        let mut evm_account_storage = init_account_storage().unwrap();
+        let mut index = init_account_index().unwrap();
+        let storage_indexes = RefPath::assert_from(b"/storage_indexes");
+        let contract = H160::from_low_u64_be(2);
+        let user = H160::from_low_u64_be(1);
+        let deleted = H160::from_low_u64_be(3);
+
+        let slots: Vec<H256> = (1..=10u64).map(H256::from_low_u64_be).collect();
+        let mut contract_account =
+            account(&mut host, &mut evm_account_storage, &contract);
+        contract_account.set_code(&mut host, &[0x60, 0x00]).unwrap();
+        for slot in &slots {
+            contract_account.set_storage(&mut host, slot, slot).unwrap();
+        }
+        account(&mut host, &mut evm_account_storage, &user)
+            .balance_add(&mut host, U256::from(1000))
+            .unwrap();
+        account(&mut host, &mut evm_account_storage, &deleted);
+        for address in [contract, user, deleted] {
+            index_account(&mut host, &address, &mut index).unwrap();
+        }
+        evm_account_storage
+            .delete(&mut host, &account_path(&deleted).unwrap())
+            .unwrap();
+
+        // The storage of the contract must be declared
+        assert!(
+            add_indexed_accounts(&mut host, &index, &storage_indexes, u64::MAX).is_err()
+        );
+        let mut stream = RlpStream::new();
+        stream.append_list(&slots);
+        host.store_write_all(
+            &concat(&storage_indexes, &account_path(&contract).unwrap()).unwrap(),
+            &stream.out(),
+        )
+        .unwrap();
+
+        // A run adds at most 4 slots, the contract is added in the third one.
+        let ticks_limit = TICKS_PER_TOUCHED_ACCOUNT + TICKS_PER_TOUCHED_SLOT * 4;
+        let mut runs = 1;
+        while !add_indexed_accounts(&mut host, &index, &storage_indexes, ticks_limit)
+            .unwrap()
+        {
+            if runs == 1 {
+                assert_eq!(read_state_root(&host).unwrap(), EMPTY_TRIE_ROOT);
+            }
+            runs += 1;
+        }
+        assert!(runs >= 3);
+
+        let storage: BTreeMap<_, _> = slots
+            .iter()
+            .map(|slot| {
+                let value = U256::from_big_endian(slot.as_bytes());
+                (slot.as_bytes().to_vec(), rlp::encode(&value).to_vec())
+            })
+            .collect();
+        let mut accounts = BTreeMap::new();
+        accounts.insert(
+            user.as_bytes().to_vec(),
+            reference_account(0, 1000, EMPTY_TRIE_ROOT, &[]),
+        );
+        accounts.insert(
+            contract.as_bytes().to_vec(),
+            reference_account(
+                0,
+                0,
+                ethereum::util::sec_trie_root(&storage),
+                &[0x60, 0x00],
+            ),
+        );
+        assert_eq!(
+            read_state_root(&host).unwrap(),
+            ethereum::util::sec_trie_root(&accounts)
+        );
+    }
+
+    #[test]
+    fn test_incomplete_declarations_are_rejected() {
+        let user = H160::from_low_u64_be(1);
+        let contract = H160::from_low_u64_be(2);
+        let slots = [H256::from_low_u64_be(1), H256::from_low_u64_be(2)];
+
+        let invalid_declarations = [
+            // A missing storage index
+            vec![(user, vec![]), (contract, vec![slots[0]])],
+            // A storage index without value
+            vec![
+                (user, vec![]),
+                (contract, vec![slots[0], H256::from_low_u64_be(3)]),
+            ],
+            // Unsorted accounts
+            vec![(contract, slots.to_vec()), (user, vec![])],
+            // An account that doesn't exist
+            vec![(H160::from_low_u64_be(3), vec![])],
+        ];
+        for accounts in invalid_declarations {
+            let mut host = MockHost::default();
+            let mut evm_account_storage = init_account_storage().unwrap();
+            account(&mut host, &mut evm_account_storage, &user)
+                .balance_add(&mut host, U256::from(1000))
+                .unwrap();
+            let mut contract_account =
+                account(&mut host, &mut evm_account_storage, &contract);
+            for slot in &slots {
+                contract_account.set_storage(&mut host, slot, slot).unwrap();
+            }
+
+            let declaration = declare(&mut host, &accounts);
+            assert!(add_declared_accounts(&mut host, &declaration, u64::MAX).is_err());
+        }
+    }
+}
@@ -0,0 +1,105 @@
+// SPDX-FileCopyrightText: 2024 Nomadic Labs <contact@nomadic-labs.com>
+//
+// SPDX-License-Identifier: MIT
+
+// Writes the vectors of `ethereum_blocks/` from the JSON-RPC API of an
+// Ethereum node, see README.md for their format.
+//
+// Usage:
+// $ node fetch_ethereum_blocks.js <rpc url> <name> <block number>..
+// $ node fetch_ethereum_blocks.js <rpc url> <name> --genesis <genesis.json>
+//
+// The first form writes the transactions and receipts of the blocks with
+// their roots, in `ethereum_blocks/<name>_<block number>.txt`. The blocks
+// can only contain legacy, EIP-2930 and EIP-1559 transactions. The second
+// form writes the accounts allocated by a geth genesis file with the state
+// root of the block 0 of the node, in `ethereum_blocks/<name>_genesis.txt`.
+
+const fs = require('fs');
+const path = require('node:path');
+
+const [rpc, name, ...args] = process.argv.slice(2);
+if (rpc === undefined || name === undefined || args.length == 0) {
+    console.error('Usage: node fetch_ethereum_blocks.js <rpc url> <name> (<block number>.. | --genesis <genesis.json>)');
+    process.exit(1);
+}
+
+let id = 0;
+async function call(method, params) {
+    const response = await fetch(rpc, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ jsonrpc: '2.0', id: id++, method, params }),
+    });
+    const json = await response.json();
+    if (json.error !== undefined) {
+        throw new Error(`${method}: ${json.error.message}`);
+    }
+    return json.result;
+}
+
+const strip = (hex) => hex.replace(/^0x/, '').toLowerCase();
+
+// Quantities are written in decimal, e.g. `0x10` or `16` as `16`.
+const quantity = (value) => BigInt(value ?? 0).toString();
+
+function write(file, lines) {
+    const dir = path.join(__dirname, 'ethereum_blocks');
+    fs.mkdirSync(dir, { recursive: true });
+    fs.writeFileSync(path.join(dir, file), lines.join('\n') + '\n');
+    console.log(`Wrote ${file}`);
+}
+
+async function fetch_block(number) {
+    const block = await call('eth_getBlockByNumber', ['0x' + BigInt(number).toString(16), false]);
+    const lines = [
+        `# Block ${BigInt(block.number)} ${block.hash}`,
+        `transactions_root ${strip(block.transactionsRoot)}`,
+        `receipts_root ${strip(block.receiptsRoot)}`,
+    ];
+    for (const hash of block.transactions) {
+        const raw = await call('eth_getRawTransactionByHash', [hash]);
+        const receipt = await call('eth_getTransactionReceipt', [hash]);
+        const type = Number(receipt.type ?? 0);
+        if (type > 2) {
+            throw new Error(`Transaction ${hash} of block ${number} has the unsupported type ${type}`);
+        }
+        lines.push(`transaction ${strip(raw)}`);
+        lines.push(`receipt ${type} ${quantity(receipt.status)} ${quantity(receipt.cumulativeGasUsed)}`);
+        for (const log of receipt.logs) {
+            const topics = log.topics.map(strip).join(' ');
+            lines.push(`log ${strip(log.address)} ${strip(log.data) || '-'} ${topics}`.trimEnd());
+        }
+    }
+    write(`${name}_${number}.txt`, lines);
+}
+
+async function fetch_genesis(file) {
+    const genesis = JSON.parse(fs.readFileSync(file));
+    const block = await call('eth_getBlockByNumber', ['0x0', false]);
+    const lines = [
+        `# Genesis ${block.hash}`,
+        `state_root ${strip(block.stateRoot)}`,
+    ];
+    for (const [address, alloc] of Object.entries(genesis.alloc)) {
+        const code = strip(alloc.code ?? '') || '-';
+        lines.push(`account ${strip(address)} ${quantity(alloc.nonce)} ${quantity(alloc.balance)} ${code}`);
+        for (const [index, value] of Object.entries(alloc.storage ?? {})) {
+            lines.push(`storage ${strip(address)} ${strip(index)} ${strip(value)}`);
+        }
+    }
+    write(`${name}_genesis.txt`, lines);
+}
+
+(async () => {
+    if (args[0] == '--genesis') {
+        await fetch_genesis(args[1]);
+    } else {
+        for (const number of args) {
+            await fetch_block(number);
+        }
+    }
+})().catch((error) => {
+    console.error(error.message);
+    process.exit(1);
+});
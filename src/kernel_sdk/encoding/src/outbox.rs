@@ -31,6 +31,10 @@ pub enum OutboxMessage<Expr: Michelson> {
     /// List of outbox transactions that must succeed together.
     #[encoding(tag = 0)]
     AtomicTransactionBatch(OutboxMessageTransactionBatch<Expr>),
+    /// List of outbox transactions that must succeed together, with the type
+    /// of their parameters.
+    #[encoding(tag = 1)]
+    AtomicTransactionBatchTyped(OutboxMessageTransactionBatchTyped<Expr>),
     /// Only keys in the whitelist are allowed to stake and publish a commitment.
     #[cfg(feature = "proto-alpha")]
     #[encoding(tag = 2)]
@@ -94,6 +98,73 @@ pub struct OutboxMessageTransaction<Expr: Michelson> {
     pub entrypoint: Entrypoint,
 }
 
+/// A batch of [`OutboxMessageTransactionTyped`].
+#[derive(Debug, PartialEq, Eq, HasEncoding, BinWriter, NomReader)]
+pub struct OutboxMessageTransactionBatchTyped<Expr: Michelson> {
+    #[encoding(dynamic, list)]
+    batch: Vec<OutboxMessageTransactionTyped<Expr>>,
+}
+
+impl<Expr: Michelson> OutboxMessageTransactionBatchTyped<Expr> {
+    /// Returns the number of transactions in the batch.
+    pub fn len(&self) -> usize {
+        self.batch.len()
+    }
+
+    /// Returns whether the batch is empty.
+    pub fn is_empty(&self) -> bool {
+        self.batch.is_empty()
+    }
+}
+
+impl<Expr: Michelson> core::ops::Index<usize>
+    for OutboxMessageTransactionBatchTyped<Expr>
+{
+    type Output = OutboxMessageTransactionTyped<Expr>;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.batch.index(index)
+    }
+}
+
+impl<Expr: Michelson> From<Vec<OutboxMessageTransactionTyped<Expr>>>
+    for OutboxMessageTransactionBatchTyped<Expr>
+{
+    fn from(batch: Vec<OutboxMessageTransactionTyped<Expr>>) -> Self {
+        Self { batch }
+    }
+}
+
+/// Outbox message transaction with the type of its parameters, part of the
+/// typed outbox message.
+///
+/// Layer 1 parses the parameters with the given type, instead of the type of
+/// the entrypoint of the destination, and the transaction fails if the two
+/// types differ.
+///
+/// Encoded as:
+/// ```ocaml
+/// (obj4
+///   (req "parameters" Script_repr.expr_encoding)
+///   (req "parameters_ty" Script_repr.expr_encoding)
+///   (req "destination" Contract_repr.originated_encoding)
+///   (req "entrypoint" Entrypoint_repr.simple_encoding))
+/// ```
+#[derive(Debug, PartialEq, Eq, HasEncoding, BinWriter, NomReader)]
+pub struct OutboxMessageTransactionTyped<Expr: Michelson> {
+    /// Micheline-encoded payload, sent to the destination contract.
+    pub parameters: Expr,
+    /// Micheline-encoded type of the payload.
+    pub parameters_ty: Expr,
+    /// The destination smart-contract.
+    ///
+    /// Protocol side this is a `Contract_hash` (aka `ContractKT1Hash`), but encoded as
+    /// `Contract.originated_encoding`.
+    pub destination: Contract,
+    /// The entrypoint of the destination that will be called.
+    pub entrypoint: Entrypoint,
+}
+
 /// Whitelist update, part of the outbox message.
 /// The keys in the whitelist are allowed to stake and publish a commitment.
 /// The whitelist is either Some (non empty list), or None (remove the whitelist,
@@ -207,6 +278,20 @@ mod test {
         b'a', b'n', b'o', b't', b'h', b'e', b'r', // Entrypoint name
     ];
 
+    // first byte is union tag (`1` for typed batches), next four are list size of batch
+    const ENCODED_OUTBOX_MESSAGE_TYPED_PREFIX: [u8; 5] = [1, 0, 0, 0, 37];
+
+    const ENCODED_TRANSACTION_TYPED: [u8; 37] = [
+        3, 11, // Unit
+        3, 108, // unit
+        // Destination
+        1, 36, 102, 103, 169, 49, 254, 11, 210, 251, 28, 182, 4, 247, 20, 96, 30, 136, 40,
+        69, 80, // end originated
+        0,  // padding
+        // Entrypoint
+        0, 0, 0, 7, b'd', b'e', b'f', b'a', b'u', b'l', b't',
+    ];
+
     // To display the encoding from OCaml:
     // Format.asprintf "%a"
     // Binary_schema.pp
@@ -323,6 +408,34 @@ mod test {
         assert_eq!(expected, message);
     }
 
+    #[test]
+    fn encode_outbox_message_typed() {
+        let mut expected = ENCODED_OUTBOX_MESSAGE_TYPED_PREFIX.to_vec();
+        expected.extend_from_slice(ENCODED_TRANSACTION_TYPED.as_slice());
+
+        let message =
+            OutboxMessage::AtomicTransactionBatchTyped(vec![transaction_typed()].into());
+
+        let mut bin = vec![];
+        message.bin_write(&mut bin).unwrap();
+
+        assert_eq!(expected, bin);
+    }
+
+    #[test]
+    fn decode_outbox_message_typed() {
+        let mut bytes = ENCODED_OUTBOX_MESSAGE_TYPED_PREFIX.to_vec();
+        bytes.extend_from_slice(ENCODED_TRANSACTION_TYPED.as_slice());
+
+        let expected =
+            OutboxMessage::AtomicTransactionBatchTyped(vec![transaction_typed()].into());
+
+        let (remaining, message) = OutboxMessage::nom_read(bytes.as_slice()).unwrap();
+
+        assert!(remaining.is_empty());
+        assert_eq!(expected, message);
+    }
+
     #[test]
     fn decode_outbox_message_err_on_invalid_prefix() {
         let mut bytes = ENCODED_OUTBOX_MESSAGE_PREFIX.to_vec();
@@ -366,6 +479,49 @@ mod test {
         make_transaction(ticket, "KT1AaiUqbT3NmQts2w7ofY4vJviVchztiW4y", "another")
     }
 
+    /// A primitive without arguments nor annotations, a value or a type.
+    #[derive(Debug, PartialEq, Eq)]
+    struct Prim(u8);
+
+    impl HasEncoding for Prim {
+        fn encoding() -> tezos_data_encoding::encoding::Encoding {
+            tezos_data_encoding::encoding::Encoding::Custom
+        }
+    }
+
+    impl NomReader for Prim {
+        fn nom_read(input: &[u8]) -> tezos_data_encoding::nom::NomResult<Self> {
+            nom::combinator::map(
+                nom::sequence::preceded(
+                    nom::bytes::complete::tag([3u8]),
+                    nom::number::complete::u8,
+                ),
+                Prim,
+            )(input)
+        }
+    }
+
+    impl BinWriter for Prim {
+        fn bin_write(&self, output: &mut Vec<u8>) -> tezos_data_encoding::enc::BinResult {
+            output.extend_from_slice(&[3, self.0]);
+            Ok(())
+        }
+    }
+
+    impl Michelson for Prim {}
+
+    fn transaction_typed() -> OutboxMessageTransactionTyped<Prim> {
+        OutboxMessageTransactionTyped {
+            // Unit
+            parameters: Prim(11),
+            // unit
+            parameters_ty: Prim(108),
+            destination: Contract::from_b58check("KT1BuEZtb68c1Q4yjtckcNjGELqWt56Xyesc")
+                .unwrap(),
+            entrypoint: Entrypoint::try_from("default".to_string()).unwrap(),
+        }
+    }
+
     fn make_transaction(
         ticket: StringTicket,
         destination: &str,